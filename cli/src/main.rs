@@ -1,47 +1,248 @@
 mod output;
 mod repl;
+mod report;
 
 use kalk::parser;
-use seahorse::{App, Context, Flag, FlagType};
+use seahorse::{App, Command, Context, Flag, FlagType};
+use std::collections::HashMap;
 use std::env;
 use std::fs::File;
-use std::io::Read;
+use std::io::{BufRead, Read};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let app = App::new("kalker")
+    let mut app = App::new("kalker")
         .author(env!("CARGO_PKG_AUTHORS"))
         .version(env!("CARGO_PKG_VERSION"))
         .usage("kalker [options] [input]")
-        .action(default_action)
-        .flag(
-            Flag::new("input-file", FlagType::String)
-                .description("Load a file with predefined variables and functions. End lines with a semicolon.")
-                .alias("i"),
-        )
-        .flag(
-            Flag::new("precision", FlagType::Int)
-                .description("Specify number precision")
-                .alias("p"),
-        )
+        .action(default_action);
+    for flag in flags() {
+        app = app.flag(flag);
+    }
+    app = app
+        .flag(Flag::new("apply", FlagType::String).description(
+            "Evaluate an expression for each number on stdin (one on each line), where the number is x, and print the results, eg. --apply 'x * 1.21'.",
+        ))
+        .flag(Flag::new("column", FlagType::Int).description(
+            "With --apply, take the numbers from this column (from 1) of tab-separated or comma-separated lines on stdin.",
+        ));
+
+    let mut run_command = Command::new("run")
+        .description("Evaluate a script, and print the results of its expressions.")
+        .usage("kalker run [options] [file]")
+        .action(run_action)
+        .flag(Flag::new("annotate", FlagType::Bool).description(
+            "Print each line of the script, with the result after it, eg. 'x^2  # = 9'.",
+        ));
+    for flag in flags() {
+        run_command = run_command.flag(flag);
+    }
+
+    let mut report_command = Command::new("report")
+        .description("Evaluate a script, and write it as a Markdown or HTML document with the results.")
+        .usage("kalker report [options] [file] -o [report.md]")
+        .action(report_action)
         .flag(
-            Flag::new("angle-unit", FlagType::String)
-                .description("Unit used for angles, either rad or deg. This can also be specified using an environment variable with the name 'ANGLE_UNIT'.")
-                .alias("a"),
+            Flag::new("output", FlagType::String)
+                .description("The file to write the report to. It is written as HTML if the name ends with .html, and as Markdown otherwise. Without it, the report is printed.")
+                .alias("o"),
         );
+    for flag in flags().into_iter().filter(|flag| flag.name != "output") {
+        report_command = report_command.flag(flag);
+    }
+
+    app.command(run_command).command(report_command).run(args);
+}
 
-    app.run(args);
+/// The flags that are used both when starting kalker and when running a script.
+fn flags() -> Vec<Flag> {
+    vec![
+        Flag::new("input-file", FlagType::String)
+            .description("Load a file with predefined variables and functions. End lines with a semicolon.")
+            .alias("i"),
+        Flag::new("prelude", FlagType::String)
+            .description("Load a prelude file with personal functions and variables instead of prelude.kalk in the config directory."),
+        Flag::new("precision", FlagType::Int)
+            .description("Specify number precision")
+            .alias("p"),
+        Flag::new("angle-unit", FlagType::String)
+            .description("Unit used for angles, either rad, deg, grad or turn. This can also be specified using an environment variable with the name 'ANGLE_UNIT'.")
+            .alias("a"),
+        Flag::new("max-recursion-depth", FlagType::Int)
+            .description("Maximum amount of nested function calls, eg. when a function calls itself"),
+        Flag::new("physics", FlagType::Bool)
+            .description("Define physical constants, eg. c, h and k_B. These can also be loaded with 'load constants' in the REPL."),
+        Flag::new("auto-precision", FlagType::Bool)
+            .description("Evaluate at a low precision first, and only use a higher precision (at most the one given with --precision) when it changes the result."),
+        Flag::new("exchange-rates", FlagType::String)
+            .description("Load a file with currency exchange rates, eg. 'eur 0.92' on each line, relative to a common base currency."),
+        Flag::new("overflow", FlagType::String)
+            .description("What happens when a result is too large or too small to be represented, either inf (give infinity or zero with a warning) or error."),
+        Flag::new("indeterminate", FlagType::String)
+            .description("What 0^0, ∞^0, 1^∞, 0·∞ and ∞ - ∞ give, either conventional (1 for the powers, undefined for the others), nan or error."),
+        Flag::new("domain", FlagType::String)
+            .description("What 1/0, log(0), sqrt(-1) etc. give, either complex (a complex value where there is one, and infinity at poles, with a warning), real (undefined instead of complex values) or error."),
+        Flag::new("shadowing", FlagType::String)
+            .description("What happens when a declaration has the name of a built-in function or constant, eg. pi = 3, either deny (give an error), warn or allow."),
+        Flag::new("output", FlagType::String)
+            .description("How results are printed, either text or json. With json, each line of the input (or each line on stdin) gives one JSON object with the fields input, value, estimate, unit and error.")
+            .alias("o"),
+        Flag::new("strict-units", FlagType::Bool)
+            .description("Give an error instead of a warning when an operation loses a unit, eg. 2 + 3km."),
+        Flag::new("locale", FlagType::String)
+            .description("How the numbers of results are written, eg. en for 1,234.56, de for 1.234,56, fr for 1 234,56 or si (default) for 1234.56 and 12 345.6."),
+        Flag::new("seed", FlagType::Int)
+            .description("Seed the random number generator of rand, randint and randnormal, so that they give the same numbers every time."),
+        Flag::new("interval", FlagType::Bool)
+            .description("Calculate bounds for real results with interval arithmetic, and only show the decimals that are certain."),
+    ]
 }
 
 fn default_action(context: &Context) {
     #[cfg(windows)]
     ansi_term::enable_ansi_support().unwrap_or_default();
 
+    let (mut parser_context, precision) = create_parser_context(context);
+
+    if let Ok(expression) = context.string_flag("apply") {
+        let column = match context.int_flag("column") {
+            Ok(column) if column >= 1 => Some(column as usize),
+            Ok(_) => {
+                output::print_err("Invalid column. Expected a number from 1.");
+                std::process::exit(1);
+            }
+            Err(_) => None,
+        };
+
+        // The expression is only parsed once, and then evaluated for each line
+        let mut compiled = match parser_context.compile(&expression, precision) {
+            Ok(compiled) => compiled,
+            Err(err) => {
+                output::print_err(&err.to_string());
+                std::process::exit(1);
+            }
+        };
+        for line in std::io::stdin().lock().lines() {
+            let line = line.expect("Failed to read from stdin.");
+            if !line.trim().is_empty() {
+                output::eval_apply(&mut compiled, &line, column);
+            }
+        }
+
+        return;
+    }
+
+    let is_json = match context.string_flag("output").as_deref() {
+        Ok("json") => true,
+        Ok("text") | Err(_) => false,
+        Ok(_) => {
+            output::print_err("Invalid output format. Expected 'text' or 'json'.");
+            std::process::exit(1);
+        }
+    };
+
+    if is_json {
+        if context.args.is_empty() {
+            // Batch mode, one result for each line on stdin
+            for line in std::io::stdin().lock().lines() {
+                let line = line.expect("Failed to read from stdin.");
+                if !line.trim().is_empty() {
+                    output::eval_json(&mut parser_context, line.trim(), precision);
+                }
+            }
+        } else {
+            output::eval_json(&mut parser_context, &context.args.join(" "), precision);
+        }
+    } else if context.args.is_empty() {
+        // REPL
+        parser_context = parser_context.set_interrupt_flag(repl::INTERRUPT_FLAG.clone());
+        repl::start(&mut parser_context, precision);
+    } else {
+        // Direct output
+        output::eval(
+            &mut parser_context,
+            &context.args.join(" "),
+            precision,
+            10u8,
+        );
+    }
+}
+
+fn run_action(context: &Context) {
+    #[cfg(windows)]
+    ansi_term::enable_ansi_support().unwrap_or_default();
+
+    let source = read_script(context, "run");
+    let (mut parser_context, precision) = create_parser_context(context);
+    output::run_script(
+        &mut parser_context,
+        &source,
+        precision,
+        context.bool_flag("annotate"),
+    );
+}
+
+fn report_action(context: &Context) {
+    #[cfg(windows)]
+    ansi_term::enable_ansi_support().unwrap_or_default();
+
+    let source = read_script(context, "report");
+    let (mut parser_context, precision) = create_parser_context(context);
+    let output_path = context.string_flag("output").ok();
+    let is_html = output_path.as_deref().map_or(false, |path| {
+        path.ends_with(".html") || path.ends_with(".htm")
+    });
+    let report = report::create_report(
+        &mut parser_context,
+        &context.args[0],
+        &source,
+        precision,
+        is_html,
+    );
+
+    if let Some(output_path) = output_path {
+        if let Err(error) = std::fs::write(&output_path, report) {
+            output::print_err(&format!(
+                "Couldn't write report '{}': {}",
+                output_path, error
+            ));
+            std::process::exit(1);
+        }
+    } else {
+        print!("{}", report);
+    }
+}
+
+/// Read the script given as the first argument of a command, eg. `kalker run script.kalker`.
+fn read_script(context: &Context, command: &str) -> String {
+    let file_name = if let Some(file_name) = context.args.first() {
+        file_name
+    } else {
+        output::print_err(&format!(
+            "Expected the name of a script, eg. 'kalker {} script.kalker'",
+            command
+        ));
+        std::process::exit(1);
+    };
+
+    let mut source = String::new();
+    if let Err(error) = File::open(file_name).and_then(|mut file| file.read_to_string(&mut source))
+    {
+        output::print_err(&format!("Couldn't read script '{}': {}", file_name, error));
+        std::process::exit(1);
+    }
+
+    source
+}
+
+/// Create a parser context with the options of the flags, and load the prelude and input files.
+/// Returns the context and the precision.
+fn create_parser_context(context: &Context) -> (parser::Context, u32) {
     let angle_unit = if let Ok(angle_unit) = context.string_flag("angle-unit") {
         match angle_unit.as_ref() {
-            "rad" | "deg" => angle_unit,
+            "rad" | "deg" | "grad" | "turn" => angle_unit,
             _ => {
-                output::print_err("Invalid angle unit. Expected 'rad' or 'deg'.");
+                output::print_err("Invalid angle unit. Expected 'rad', 'deg', 'grad' or 'turn'.");
                 std::process::exit(1);
             }
         }
@@ -51,9 +252,111 @@ fn default_action(context: &Context) {
     let mut parser_context = parser::Context::new()
         .set_angle_unit(&angle_unit)
         .set_timeout(None);
+    if let Ok(overflow_policy) = context.string_flag("overflow") {
+        let overflow_policy = match overflow_policy.as_ref() {
+            "inf" => parser::OverflowPolicy::Infinity,
+            "error" => parser::OverflowPolicy::Error,
+            _ => {
+                output::print_err("Invalid overflow policy. Expected 'inf' or 'error'.");
+                std::process::exit(1);
+            }
+        };
+        parser_context = parser_context.set_overflow_policy(overflow_policy);
+    }
+
+    if let Ok(indeterminate_policy) = context.string_flag("indeterminate") {
+        let indeterminate_policy = match indeterminate_policy.as_ref() {
+            "conventional" => parser::IndeterminatePolicy::Conventional,
+            "nan" => parser::IndeterminatePolicy::NaN,
+            "error" => parser::IndeterminatePolicy::Error,
+            _ => {
+                output::print_err(
+                    "Invalid indeterminate policy. Expected 'conventional', 'nan' or 'error'.",
+                );
+                std::process::exit(1);
+            }
+        };
+        parser_context = parser_context.set_indeterminate_policy(indeterminate_policy);
+    }
+
+    if let Ok(domain_policy) = context.string_flag("domain") {
+        let domain_policy = match domain_policy.as_ref() {
+            "complex" => parser::DomainPolicy::Complex,
+            "real" => parser::DomainPolicy::Real,
+            "error" => parser::DomainPolicy::Error,
+            _ => {
+                output::print_err("Invalid domain policy. Expected 'complex', 'real' or 'error'.");
+                std::process::exit(1);
+            }
+        };
+        parser_context = parser_context.set_domain_policy(domain_policy);
+    }
+
+    if let Ok(shadowing_policy) = context.string_flag("shadowing") {
+        let shadowing_policy = match shadowing_policy.as_ref() {
+            "deny" => parser::ShadowingPolicy::Deny,
+            "warn" => parser::ShadowingPolicy::Warn,
+            "allow" => parser::ShadowingPolicy::Allow,
+            _ => {
+                output::print_err("Invalid shadowing policy. Expected 'deny', 'warn' or 'allow'.");
+                std::process::exit(1);
+            }
+        };
+        parser_context = parser_context.set_shadowing_policy(shadowing_policy);
+    }
+
+    if context.bool_flag("strict-units") {
+        parser_context = parser_context.set_strict_units(true);
+    }
+
+    if let Ok(locale) = context.string_flag("locale") {
+        if let Some(number_format) = kalk::kalk_value::NumberFormat::from_locale(&locale) {
+            parser_context = parser_context.set_number_format(number_format);
+        } else {
+            output::print_err("Unknown locale. Expected eg. 'en', 'de', 'fr' or 'si'.");
+            std::process::exit(1);
+        }
+    }
+
+    if context.bool_flag("interval") {
+        parser_context = parser_context.set_interval_mode(true);
+    }
+
+    if let Ok(seed) = context.int_flag("seed") {
+        parser_context = parser_context.set_seed(seed as u64);
+    }
+
+    if let Ok(max_recursion_depth) = context.int_flag("max-recursion-depth") {
+        parser_context = parser_context.set_max_recursion_depth(max_recursion_depth as u32);
+    }
+
+    if context.bool_flag("physics") {
+        parser_context.load_physics_constants();
+    }
+
+    if let Ok(exchange_rates_path) = context.string_flag("exchange-rates") {
+        load_exchange_rates(&exchange_rates_path, &mut parser_context);
+    }
+
+    // With automatic precision, the precision flag is the highest precision that may be used
+    let auto_precision = context.bool_flag("auto-precision");
+    let default_precision = if auto_precision {
+        output::DEFAULT_MAX_AUTO_PRECISION
+    } else {
+        output::DEFAULT_PRECISION
+    };
+    parser_context = parser_context.set_auto_precision(auto_precision);
     let precision = context
         .int_flag("precision")
-        .unwrap_or(output::DEFAULT_PRECISION as isize) as u32;
+        .unwrap_or(default_precision as isize) as u32;
+
+    let prelude_path = context
+        .string_flag("prelude")
+        .ok()
+        .or_else(get_prelude_file);
+    if let Some(prelude_path) = prelude_path {
+        load_prelude(&prelude_path, precision, &mut parser_context);
+    }
 
     if let Some(input_file_path) = get_input_file_by_name("default") {
         load_input_file(&input_file_path, precision, &mut parser_context);
@@ -63,18 +366,7 @@ fn default_action(context: &Context) {
         load_input_file(&input_file_path, precision, &mut parser_context);
     }
 
-    if context.args.is_empty() {
-        // REPL
-        repl::start(&mut parser_context, precision);
-    } else {
-        // Direct output
-        output::eval(
-            &mut parser_context,
-            &context.args.join(" "),
-            precision,
-            10u8,
-        );
-    }
+    (parser_context, precision)
 }
 
 pub(crate) fn get_input_file_by_name(name: &str) -> Option<String> {
@@ -90,6 +382,39 @@ pub(crate) fn get_input_file_by_name(name: &str) -> Option<String> {
     }
 }
 
+fn get_prelude_file() -> Option<String> {
+    let mut path = dirs::config_dir()?;
+    path.push("kalker");
+    path.push("prelude.kalk");
+
+    if path.exists() {
+        Some(path.to_str()?.to_string())
+    } else {
+        None
+    }
+}
+
+/// Load a prelude file, where an error is reported with the file name and line,
+/// eg. `prelude.kalk:3: Undefined function: 'g'.`
+fn load_prelude(file_name: &str, precision: u32, parser_context: &mut parser::Context) {
+    let mut file_content = String::new();
+    if let Err(error) =
+        File::open(file_name).and_then(|mut file| file.read_to_string(&mut file_content))
+    {
+        output::print_err(&format!(
+            "Couldn't read prelude file '{}': {}",
+            file_name, error
+        ));
+        return;
+    }
+
+    if let Err(errors) = parser::load(parser_context, &file_content, precision) {
+        for (line, error) in errors {
+            output::print_err(&format!("{}:{}: {}", file_name, line, error.to_string()));
+        }
+    }
+}
+
 pub fn load_input_file(file_name: &str, precision: u32, parser_context: &mut parser::Context) {
     let mut file_content = String::new();
     File::open(file_name)
@@ -104,6 +429,33 @@ pub fn load_input_file(file_name: &str, precision: u32, parser_context: &mut par
     }
 }
 
+/// Load exchange rates from a file where each line contains a currency code
+/// and how much of it one unit of the base currency is worth, eg. `eur 0.92`.
+fn load_exchange_rates(file_name: &str, parser_context: &mut parser::Context) {
+    let mut file_content = String::new();
+    File::open(file_name)
+        .expect("Couldn't find file.")
+        .read_to_string(&mut file_content)
+        .expect("Failed to read exchange rates file.");
+
+    let mut exchange_rates = HashMap::new();
+    for line in file_content.lines().map(|line| line.trim()) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut parts = line.split_whitespace();
+        match (parts.next(), parts.next().map(|rate| rate.parse::<f64>())) {
+            (Some(currency), Some(Ok(rate))) if rate > 0f64 => {
+                exchange_rates.insert(currency.to_string(), rate);
+            }
+            _ => eprintln!("Invalid exchange rate: '{}'", line),
+        }
+    }
+
+    parser_context.set_exchange_rates(exchange_rates);
+}
+
 fn get_env_angle_unit() -> String {
     if let Ok(angle_unit_var) = env::var("ANGLE_UNIT") {
         angle_unit_var
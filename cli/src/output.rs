@@ -1,24 +1,483 @@
 use ansi_term::Colour::Red;
-use kalk::parser;
+use kalk::{parser, rpn};
 
 pub(crate) const DEFAULT_PRECISION: u32 = 63;
+pub(crate) const DEFAULT_MAX_AUTO_PRECISION: u32 = 1024;
 
 pub fn eval(parser: &mut parser::Context, input: &str, precision: u32, base: u8) {
-    match parser::eval(parser, input, precision) {
+    let result = parser::eval(parser, input, precision);
+    print_shadowing_warnings(parser);
+    if result.is_err() {
+        print_error_span(input, parser.get_error_span());
+    }
+
+    print_result(parser, result, precision, base);
+}
+
+/// Point at the part of the input that an error came from, eg.
+/// ```text
+/// 2 + 3 * radius
+///         ^^^^^^
+/// ```
+/// Nothing is printed for input with several lines, since the lines are printed one by one.
+fn print_error_span(input: &str, span: Option<(usize, usize)>) {
+    let (start, end) = match span {
+        Some(span) if !input.contains('\n') => span,
+        _ => return,
+    };
+
+    print_err(input);
+    print_err(&format!(
+        "{}{}",
+        " ".repeat(start),
+        "^".repeat(end.saturating_sub(start).max(1))
+    ));
+}
+
+fn print_shadowing_warnings(parser: &mut parser::Context) {
+    for warning in parser.take_shadowing_warnings() {
+        print_err(&format!("Warning: {}", warning));
+    }
+}
+
+/// Evaluate the input and print the result as a JSON object on a single line, eg.
+/// `{"input":"1/2","value":"0.5","estimate":"1/2","unit":null,"error":null}`.
+pub fn eval_json(parser: &mut parser::Context, input: &str, precision: u32) {
+    let (value, estimate, unit, error) = match parser::eval(parser, input, precision) {
+        Ok(Some(result)) => (
+            Some(result.to_string_big()),
+            result.estimate_js(),
+            result.get_unit(),
+            None,
+        ),
+        Ok(None) => (None, None, None, None),
+        Err(err) => (None, None, None, Some(err.to_string())),
+    };
+
+    println!(
+        "{{\"input\":{},\"value\":{},\"estimate\":{},\"unit\":{},\"error\":{}}}",
+        json_string(Some(input)),
+        json_string(value.as_deref()),
+        json_string(estimate.as_deref()),
+        json_string(unit.as_deref()),
+        json_string(error.as_deref()),
+    );
+}
+
+/// Evaluate the compiled expression with a number from the line as `x`, eg. `x * 1.21` for
+/// `100`, and print the result without formatting, so that it can be used by other programs.
+/// With a column (from 1), the number is taken from that column of tab-separated
+/// or comma-separated values, eg. `widget,100`.
+pub fn eval_apply(compiled: &mut parser::CompiledInput, line: &str, column: Option<usize>) {
+    let field = match column {
+        Some(column) => {
+            let delimiter = if line.contains('\t') { '\t' } else { ',' };
+            line.split(delimiter).nth(column - 1)
+        }
+        None => Some(line),
+    };
+    let number = match field.map(|field| field.trim().trim_matches('"').parse::<f64>()) {
+        Some(Ok(number)) if number.is_finite() => number,
+        _ => return print_err(&format!("Expected a number: '{}'", line)),
+    };
+
+    match compiled.eval_with(&[("x", number)]) {
+        Ok(Some(result)) => println!("{}", result),
+        Ok(None) => print_err("Expected the expression to give a value."),
+        Err(err) => print_err(&err.to_string()),
+    }
+}
+
+/// Write a value as a JSON string, or `null` if there is no value.
+fn json_string(value: Option<&str>) -> String {
+    let value = if let Some(value) = value {
+        value
+    } else {
+        return String::from("null");
+    };
+
+    let mut output = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if c.is_control() => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+
+    output
+}
+
+/// Apply the input to the stack in RPN mode and print the stack,
+/// where the top of the stack is the last line, at level 1.
+pub fn eval_rpn(
+    parser: &mut parser::Context,
+    stack: &mut rpn::Stack,
+    input: &str,
+    precision: u32,
+    base: u8,
+) {
+    if let Err(err) = rpn::eval(parser, stack, input, precision) {
+        print_err(&err.to_string());
+    }
+
+    let values = stack.values();
+    for (i, value) in values.iter().enumerate() {
+        println!(
+            "{}: {}",
+            values.len() - i,
+            value.to_string_pretty_radix(base)
+        );
+    }
+}
+
+/// Evaluate a script one statement at a time, where statements can span several lines
+/// while there are unclosed brackets. The results are printed like direct output, or with
+/// `annotate`, each line of the script is printed with the result after it, eg. `x^2  # = 9`.
+pub fn run_script(parser: &mut parser::Context, source: &str, precision: u32, annotate: bool) {
+    for lines in script_statements(source) {
+        run_statement(parser, &lines, precision, annotate);
+    }
+}
+
+/// Split a script into statements, where a statement continues on the next lines
+/// as long as it has brackets that haven't been closed. Empty lines and comments,
+/// which are lines that start with `#`, are kept as statements of their own.
+pub(crate) fn script_statements(source: &str) -> Vec<Vec<&str>> {
+    let mut statements = Vec::new();
+    let mut lines = Vec::new();
+    let mut open_brackets = 0i32;
+    for line in source.lines() {
+        if lines.is_empty() && is_comment(line) {
+            statements.push(vec![line]);
+            continue;
+        }
+
+        lines.push(line);
+        for c in line.chars() {
+            match c {
+                '(' | '[' | '{' | '⌈' | '⌊' => open_brackets += 1,
+                ')' | ']' | '}' | '⌉' | '⌋' => open_brackets -= 1,
+                _ => (),
+            }
+        }
+
+        if open_brackets <= 0 {
+            statements.push(std::mem::take(&mut lines));
+            open_brackets = 0;
+        }
+    }
+
+    if !lines.is_empty() {
+        statements.push(lines);
+    }
+
+    statements
+}
+
+pub(crate) fn is_comment(line: &str) -> bool {
+    line.trim_start().starts_with('#')
+}
+
+fn run_statement(parser: &mut parser::Context, lines: &[&str], precision: u32, annotate: bool) {
+    let input = lines.join("\n");
+    if input.trim().is_empty() || is_comment(&input) {
+        if annotate {
+            println!("{}", input);
+        }
+
+        return;
+    }
+
+    if !annotate {
+        eval(parser, input.trim(), precision, 10u8);
+
+        return;
+    }
+
+    let (last_line, first_lines) = lines.split_last().unwrap();
+    for line in first_lines {
+        println!("{}", line);
+    }
+
+    match parser::eval(parser, input.trim(), precision) {
+        Ok(Some(result)) => {
+            let pretty = result.to_string_pretty();
+            let mut result_lines = pretty.lines();
+            println!(
+                "{}  # = {}",
+                last_line,
+                result_lines.next().unwrap_or_default()
+            );
+            for result_line in result_lines {
+                println!(
+                    "{}  #   {}",
+                    " ".repeat(last_line.chars().count()),
+                    result_line
+                );
+            }
+        }
+        Ok(None) => println!("{}", last_line),
+        Err(err) => println!("{}  # error: {}", last_line, err.to_string()),
+    }
+    print_shadowing_warnings(parser);
+}
+
+/// Continue the last calculation that was interrupted with Ctrl-C.
+pub fn eval_resume(parser: &mut parser::Context, precision: u32, base: u8) {
+    let result = parser::resume(parser, precision);
+    print_result(parser, result, precision, base);
+}
+
+fn print_result(
+    parser: &parser::Context,
+    result: Result<Option<kalk::calculation_result::CalculationResult>, impl ToString>,
+    precision: u32,
+    base: u8,
+) {
+    match result {
         Ok(Some(mut result)) => {
-            result.set_radix(base);
+            // Keep the radix of a conversion like `to hex`
+            if result.get_radix() == 10 {
+                result.set_radix(base);
+            }
+
+            for step in result.get_steps() {
+                println!("{}", ansi_term::Colour::Fixed(246).paint(step.to_string()));
+            }
 
-            if precision == DEFAULT_PRECISION {
+            if precision == DEFAULT_PRECISION || parser.is_auto_precision() {
                 println!("{}", result.to_string_pretty())
             } else {
                 println!("{}", result.to_string_big())
             }
+
+            if result.is_out_of_range() {
+                print_err("Warning: a value was too large or too small to be represented, and was replaced with infinity or zero.");
+            }
+
+            // The reason is already shown for undefined results
+            if let (Some(note), None) = (result.get_domain_note(), result.get_undefined_reason()) {
+                print_err(&format!("Warning: {}.", note));
+            }
+
+            if let Some(unit) = result.get_dropped_unit() {
+                print_err(&format!("Warning: the unit '{}' was lost in an operation, since the units didn't match.", unit));
+            }
         }
         Ok(None) => print!(""),
         Err(err) => print_err(&err.to_string()),
     }
 }
 
+pub fn eval_latex(parser: &mut parser::Context, input: &str, precision: u32) {
+    let statements = match parser::parse(parser, input) {
+        Ok(statements) => statements,
+        Err(err) => return print_err(&err.to_string()),
+    };
+
+    let mut output = statements
+        .iter()
+        .map(|stmt| stmt.to_latex())
+        .collect::<Vec<String>>()
+        .join(";\\\\ ");
+    if let Some(kalk::ast::Stmt::Expr(_)) = statements.last() {
+        match parser::eval(parser, input, precision) {
+            Ok(Some(result)) => output.push_str(&format!(" = {}", result.to_latex())),
+            Ok(None) => (),
+            Err(err) => return print_err(&err.to_string()),
+        }
+    }
+
+    println!("{}", output);
+}
+
+/// Print the Taylor polynomial of a function, or an expression of x, from arguments like
+/// `sin(x), 0, 5`, where 0 is the point it is expanded around and 5 is the degree.
+pub fn eval_taylor(parser: &mut parser::Context, args: &str, precision: u32) {
+    let args = split_arguments(args);
+    if args.len() != 3 {
+        return print_err("Expected: taylor(function, x0, degree)");
+    }
+
+    let mut numbers = Vec::new();
+    for arg in &args[1..] {
+        match parser::eval(parser, arg, precision) {
+            Ok(Some(result)) => numbers.push(result.to_f64()),
+            Ok(None) => return print_err("Expected a number."),
+            Err(err) => return print_err(&err.to_string()),
+        }
+    }
+
+    let degree = numbers[1];
+    if degree < 0f64 || degree.fract() != 0f64 {
+        return print_err("Expected a non-negative integer as the degree.");
+    }
+
+    match parser::taylor(parser, args[0], numbers[0], degree as usize, precision) {
+        Ok(polynomial) => println!("{}", polynomial),
+        Err(err) => print_err(&err.to_string()),
+    }
+}
+
+/// Print a table of the values of a function, from arguments like `f, 0, 10, 1`.
+/// The step is optional and defaults to 1.
+pub fn eval_table(
+    parser: &mut parser::Context,
+    args: &str,
+    threads: usize,
+    precision: u32,
+    base: u8,
+) {
+    let args = split_arguments(args);
+    if args.len() != 3 && args.len() != 4 {
+        return print_err("Expected: table(function, start, end, step)");
+    }
+
+    let mut numbers = Vec::new();
+    for arg in &args[1..] {
+        match parser::eval(parser, arg, precision) {
+            Ok(Some(result)) => numbers.push(result.to_f64()),
+            Ok(None) => return print_err("Expected a number."),
+            Err(err) => return print_err(&err.to_string()),
+        }
+    }
+
+    let step = numbers.get(2).cloned().unwrap_or(1f64);
+    let rows = match parser::table(
+        parser, args[0], numbers[0], numbers[1], step, threads, precision,
+    ) {
+        Ok(rows) => rows,
+        Err(err) => return print_err(&err.to_string()),
+    };
+
+    let format = |value: &kalk::kalk_value::KalkValue| {
+        if precision == DEFAULT_PRECISION {
+            value.to_string_pretty_radix(base)
+        } else {
+            value.to_string_big()
+        }
+    };
+    let rows: Vec<(String, String)> = rows.iter().map(|(x, y)| (format(x), format(y))).collect();
+    let width = rows
+        .iter()
+        .map(|(x, _)| x.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    for (x, y) in rows {
+        println!("{}{}  {}", x, " ".repeat(width - x.chars().count()), y);
+    }
+}
+
+/// Print a rough plot of a parametric curve, from arguments like `r, 0, 2π`,
+/// where `r` is a function returning a vector with two values.
+pub fn eval_plotparam(parser: &mut parser::Context, args: &str, threads: usize, precision: u32) {
+    const WIDTH: usize = 61;
+    const HEIGHT: usize = 21;
+    const SAMPLES: f64 = 400f64;
+
+    let args = split_arguments(args);
+    if args.len() != 3 {
+        return print_err("Expected: plotparam(function, start, end)");
+    }
+
+    let mut numbers = Vec::new();
+    for arg in &args[1..] {
+        match parser::eval(parser, arg, precision) {
+            Ok(Some(result)) => numbers.push(result.to_f64()),
+            Ok(None) => return print_err("Expected a number."),
+            Err(err) => return print_err(&err.to_string()),
+        }
+    }
+
+    let (start, end) = (numbers[0], numbers[1]);
+    let rows = match parser::table(
+        parser,
+        args[0],
+        start,
+        end,
+        (end - start) / SAMPLES,
+        threads,
+        precision,
+    ) {
+        Ok(rows) => rows,
+        Err(err) => return print_err(&err.to_string()),
+    };
+
+    let mut points = Vec::new();
+    for (_, value) in rows {
+        match value {
+            kalk::kalk_value::KalkValue::Vector(values) if values.len() == 2 => {
+                let (x, y) = (values[0].to_f64(), values[1].to_f64());
+                if x.is_finite() && y.is_finite() {
+                    points.push((x, y));
+                }
+            }
+            _ => return print_err("Expected the function to return a vector with two values."),
+        }
+    }
+
+    if points.is_empty() {
+        return print_err("Nothing to plot.");
+    }
+
+    let min_x = points.iter().map(|(x, _)| *x).fold(f64::INFINITY, f64::min);
+    let max_x = points
+        .iter()
+        .map(|(x, _)| *x)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let min_y = points.iter().map(|(_, y)| *y).fold(f64::INFINITY, f64::min);
+    let max_y = points
+        .iter()
+        .map(|(_, y)| *y)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let mut grid = vec![vec![' '; WIDTH]; HEIGHT];
+    for (x, y) in points {
+        let column = ((x - min_x) / (max_x - min_x).max(f64::EPSILON) * (WIDTH - 1) as f64).round();
+        let row = ((max_y - y) / (max_y - min_y).max(f64::EPSILON) * (HEIGHT - 1) as f64).round();
+        grid[row as usize][column as usize] = '•';
+    }
+
+    for row in grid {
+        println!("{}", row.into_iter().collect::<String>().trim_end());
+    }
+
+    println!(
+        "x: {} to {}, y: {} to {}",
+        kalk::kalk_value::format_number(min_x),
+        kalk::kalk_value::format_number(max_x),
+        kalk::kalk_value::format_number(min_y),
+        kalk::kalk_value::format_number(max_y)
+    );
+}
+
+/// Split a string by the commas that aren't inside brackets.
+fn split_arguments(input: &str) -> Vec<&str> {
+    let mut args = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in input.char_indices() {
+        match c {
+            '(' | '[' | '{' | '⟦' => depth += 1,
+            ')' | ']' | '}' | '⟧' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(input[start..i].trim());
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    args.push(input[start..].trim());
+
+    args
+}
+
 pub fn print_err(msg: &str) {
     Red.paint(msg).to_string();
     eprintln!("{}", msg);
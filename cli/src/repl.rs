@@ -1,9 +1,10 @@
 use crate::output;
 use ansi_term::Colour::{self, Cyan};
-use kalk::parser;
+use kalk::kalk_value::NumberFormat;
+use kalk::lexer::TokenCategory;
+use kalk::parser::{self, ProgrammerMode};
+use kalk::rpn;
 use lazy_static::lazy_static;
-use regex::Captures;
-use regex::Regex;
 use rustyline::completion::Completer;
 use rustyline::config::Configurer;
 use rustyline::error::ReadlineError;
@@ -16,21 +17,86 @@ use rustyline::validate::Validator;
 use rustyline::{Editor, Helper};
 use std::borrow::Cow;
 use std::borrow::Cow::Owned;
-use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
 use std::fs;
+use std::hash::{Hash, Hasher};
 use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 struct Context {
     base: u8,
+    /// Amount of threads used to evaluate the rows of tables and plots.
+    threads: usize,
+    /// The canonical hashes of the history entries, in the same order.
+    history_hashes: Vec<u64>,
+    /// The digit group separator of the locale, which is used when grouping is turned on again.
+    group_separator: char,
+    /// The stack of RPN mode, if it is turned on.
+    rpn_stack: Option<rpn::Stack>,
+    /// Inputs that have been recorded with `:record name`, which can be replayed with `:play name`.
+    macros: HashMap<String, Vec<String>>,
+    /// The name and inputs of the macro that is currently being recorded.
+    recording: Option<(String, Vec<String>)>,
+    /// The macros that are currently being played, to stop a macro from playing itself.
+    playing: Vec<String>,
+}
+
+impl Context {
+    /// Add the input to the history. An earlier entry that is equivalent to it,
+    /// eg. `1+2` for `1 + 2`, is moved to the most recent position instead.
+    fn add_history_entry(
+        &mut self,
+        editor: &mut Editor<RLHelper>,
+        parser: &parser::Context,
+        input: &str,
+    ) {
+        let hash = parser.canonical_hash(input).unwrap_or_else(|| {
+            let mut hasher = DefaultHasher::new();
+            input.trim().hash(&mut hasher);
+
+            hasher.finish()
+        });
+
+        // The history can't remove single entries, so it is filled again without the old one
+        if let Some(index) = self.history_hashes.iter().position(|h| *h == hash) {
+            let entries: Vec<String> = editor.history().iter().cloned().collect();
+            editor.clear_history();
+            for (i, entry) in entries.into_iter().enumerate() {
+                if i != index {
+                    editor.add_history_entry(entry);
+                }
+            }
+
+            self.history_hashes.remove(index);
+        }
+
+        if editor.add_history_entry(input) {
+            self.history_hashes.push(hash);
+        }
+
+        // The oldest entries are removed when the history is full
+        let overflow = self
+            .history_hashes
+            .len()
+            .saturating_sub(editor.history().len());
+        self.history_hashes.drain(..overflow);
+    }
 }
 
 pub fn start(parser: &mut parser::Context, precision: u32) {
     let mut editor = Editor::<RLHelper>::new();
     editor.set_helper(Some(RLHelper {
-        highlighter: LineHighlighter {},
+        highlighter: LineHighlighter {
+            units: unit_names(parser),
+        },
         validator: MatchingBracketValidator::new(),
+        completions: parser.get_completions(""),
     }));
     editor.set_max_history_size(30);
+    set_interrupt_handler();
 
     // Load history
     let mut history_path = None;
@@ -54,7 +120,22 @@ pub fn start(parser: &mut parser::Context, precision: u32) {
         );
     }
 
-    let mut repl = Context { base: 10u8 };
+    let mut repl = Context {
+        base: 10u8,
+        threads: 1,
+        history_hashes: Vec::new(),
+        group_separator: parser.get_number_format().group_separator.unwrap_or(' '),
+        rpn_stack: None,
+        macros: HashMap::new(),
+        recording: None,
+        playing: Vec::new(),
+    };
+    let entries: Vec<String> = editor.history().iter().cloned().collect();
+    editor.clear_history();
+    for entry in entries {
+        repl.add_history_entry(&mut editor, parser, &entry);
+    }
+
     loop {
         let prompt = if cfg!(windows) {
             String::from(">> ")
@@ -65,8 +146,13 @@ pub fn start(parser: &mut parser::Context, precision: u32) {
 
         match readline {
             Ok(input) => {
-                editor.add_history_entry(input.as_str());
+                repl.add_history_entry(&mut editor, parser, &input);
+                INTERRUPT_FLAG.store(false, Ordering::Relaxed);
                 eval_repl(&mut repl, parser, &input, precision);
+                if let Some(helper) = editor.helper_mut() {
+                    helper.completions = parser.get_completions("");
+                    helper.highlighter.units = unit_names(parser);
+                }
             }
             Err(ReadlineError::Interrupted) => break,
             _ => break,
@@ -78,10 +164,84 @@ pub fn start(parser: &mut parser::Context, precision: u32) {
     }
 }
 
+/// Make Ctrl-C interrupt the current calculation instead of closing kalker.
+/// While a line is being read, Ctrl-C is handled by rustyline instead.
+#[cfg(unix)]
+fn set_interrupt_handler() {
+    extern "C" fn handle_interrupt(_: libc::c_int) {
+        INTERRUPT_FLAG.store(true, Ordering::Relaxed);
+    }
+
+    // The flag needs to be initialized before the handler is called
+    lazy_static::initialize(&INTERRUPT_FLAG);
+    unsafe {
+        libc::signal(libc::SIGINT, handle_interrupt as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+fn set_interrupt_handler() {}
+
 fn eval_repl(repl: &mut self::Context, parser: &mut parser::Context, input: &str, precision: u32) {
+    if let Some(name) = input.strip_prefix(":record ") {
+        let name = name.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            eprintln!("Expected a name for the macro, eg. ':record setup'");
+        } else if repl.recording.is_some() {
+            eprintln!("Already recording a macro. Stop it with ':stop'");
+        } else {
+            repl.recording = Some((name.to_string(), Vec::new()));
+        }
+
+        return;
+    }
+
+    if input.trim() == ":stop" {
+        if let Some((name, inputs)) = repl.recording.take() {
+            println!("Recorded {} lines as '{}'", inputs.len(), name);
+            repl.macros.insert(name, inputs);
+        } else {
+            eprintln!("No macro is being recorded");
+        }
+
+        return;
+    }
+
+    if let Some(name) = input.strip_prefix(":play ") {
+        let name = name.trim();
+        let inputs = match repl.macros.get(name) {
+            Some(inputs) => inputs.clone(),
+            None => {
+                eprintln!("Unable to find the macro '{}'", name);
+                return;
+            }
+        };
+
+        if repl.playing.iter().any(|playing| playing == name) {
+            eprintln!("The macro '{}' can not play itself", name);
+            return;
+        }
+
+        // A macro that plays another one records the play command, rather than its inputs
+        let recording = repl.recording.take();
+        repl.playing.push(name.to_string());
+        for input in inputs {
+            eval_repl(repl, parser, &input, precision);
+        }
+        repl.playing.pop();
+        repl.recording = recording;
+        record_input(repl, input);
+
+        return;
+    }
+
+    record_input(repl, input);
+
     if let Some(file_name) = input.strip_prefix("load ") {
         if let Some(file_path) = crate::get_input_file_by_name(file_name) {
             crate::load_input_file(&file_path, precision, parser);
+        } else if file_name == "constants" {
+            parser.load_physics_constants();
         } else {
             eprintln!("Unable to find '{}'", file_name);
         }
@@ -101,8 +261,247 @@ fn eval_repl(repl: &mut self::Context, parser: &mut parser::Context, input: &str
         }
     }
 
+    if let Some(name) = input.strip_prefix(":store ") {
+        let name = name.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            eprintln!("Expected a name for the register, eg. ':store a'");
+        } else if let Err(err) = parser.store_register(name) {
+            output::print_err(&err.to_string());
+        }
+
+        return;
+    }
+
+    if let Some(name) = input.strip_prefix(":recall ") {
+        match parser.recall_register(name.trim()) {
+            Ok(()) => output::eval(parser, "ans", precision, repl.base),
+            Err(err) => output::print_err(&err.to_string()),
+        }
+
+        return;
+    }
+
+    if input == ":vars" || input == ":funcs" {
+        let definitions = if input == ":vars" {
+            parser.get_variables()
+        } else {
+            parser.get_functions()
+        };
+        for definition in definitions {
+            println!("{}", definition);
+        }
+
+        return;
+    }
+
+    if let Some(name) = input.strip_prefix(":show ") {
+        let name = name.trim();
+        match parser.get_definition(name) {
+            Some(definition) => println!("{}", definition),
+            None => eprintln!("'{}' isn't defined", name),
+        }
+
+        return;
+    }
+
+    if let Some(name) = input.strip_prefix(":del ") {
+        let name = name.trim();
+        if name.is_empty() {
+            eprintln!("Expected a name, eg. ':del f'");
+        } else if !parser.remove_definition(name) {
+            eprintln!("'{}' isn't defined", name);
+        }
+
+        return;
+    }
+
+    if let Some(threads_str) = input.strip_prefix(":set threads ") {
+        match threads_str.trim().parse::<usize>() {
+            Ok(threads) if threads > 0 => repl.threads = threads,
+            _ => eprintln!("Invalid amount of threads"),
+        }
+
+        return;
+    }
+
+    if let Some(group_str) = input.strip_prefix(":set group ") {
+        let group_separator = match group_str.trim() {
+            "on" => Some(repl.group_separator),
+            "off" => None,
+            _ => {
+                eprintln!("Expected 'on' or 'off'");
+                return;
+            }
+        };
+        let number_format = NumberFormat {
+            group_separator,
+            ..parser.get_number_format()
+        };
+        *parser = std::mem::take(parser).set_number_format(number_format);
+
+        return;
+    }
+
+    if let Some(sigfigs_str) = input.strip_prefix(":set sigfigs ") {
+        let significant_figures = match sigfigs_str.trim() {
+            "off" => None,
+            sigfigs_str => match sigfigs_str.parse::<usize>() {
+                Ok(significant_figures) if (1..=17).contains(&significant_figures) => {
+                    Some(significant_figures)
+                }
+                _ => {
+                    eprintln!(
+                        "Expected an amount of significant figures between 1 and 17, or 'off'"
+                    );
+                    return;
+                }
+            },
+        };
+        let number_format = NumberFormat {
+            significant_figures,
+            ..parser.get_number_format()
+        };
+        *parser = std::mem::take(parser).set_number_format(number_format);
+
+        return;
+    }
+
+    if let Some(estimate_str) = input.strip_prefix(":set estimate ") {
+        let (estimate, side_by_side) = match estimate_str.trim() {
+            "on" => (true, false),
+            "side" => (true, true),
+            "off" => (false, false),
+            _ => {
+                eprintln!("Expected 'on', 'side' or 'off'");
+                return;
+            }
+        };
+        *parser = std::mem::take(parser)
+            .set_estimate(estimate)
+            .set_side_by_side_estimate(side_by_side);
+
+        return;
+    }
+
+    if let Some(tolerance_str) = input.strip_prefix(":set round ") {
+        let round_tolerance = match tolerance_str.trim() {
+            "default" => None,
+            "off" => Some(0f64),
+            tolerance_str => match tolerance_str.parse::<f64>() {
+                Ok(tolerance) if (0f64..1f64).contains(&tolerance) => Some(tolerance),
+                _ => {
+                    eprintln!("Expected a tolerance between 0 and 1, 'off' or 'default'");
+                    return;
+                }
+            },
+        };
+        *parser = std::mem::take(parser).set_round_tolerance(round_tolerance);
+
+        return;
+    }
+
+    if let Some(rpn_str) = input.strip_prefix(":set rpn ") {
+        match rpn_str.trim() {
+            "on" => repl.rpn_stack = Some(rpn::Stack::new()),
+            "off" => repl.rpn_stack = None,
+            _ => eprintln!("Expected 'on' or 'off'"),
+        }
+
+        return;
+    }
+
+    // In RPN mode, everything except the commands is applied to the stack
+    if let Some(stack) = &mut repl.rpn_stack {
+        if !input.starts_with(':') && !matches!(input, "exit" | "help") {
+            output::eval_rpn(parser, stack, input, precision, repl.base);
+
+            return;
+        }
+    }
+
+    if let Some(steps_str) = input.strip_prefix(":steps ") {
+        match steps_str.trim() {
+            "on" => *parser = std::mem::take(parser).set_trace(true),
+            "off" => *parser = std::mem::take(parser).set_trace(false),
+            _ => eprintln!("Expected 'on' or 'off'"),
+        }
+
+        return;
+    }
+
+    if let Some(mode_str) = input.strip_prefix(":mode ") {
+        let args: Vec<&str> = mode_str.split_whitespace().collect();
+        let programmer_mode = match args.as_slice() {
+            ["normal"] => None,
+            ["prog", word_size] | ["prog", word_size, "signed"] => word_size
+                .parse()
+                .ok()
+                .and_then(|x| ProgrammerMode::new(x, true)),
+            ["prog", word_size, "unsigned"] => word_size
+                .parse()
+                .ok()
+                .and_then(|x| ProgrammerMode::new(x, false)),
+            _ => {
+                eprintln!(
+                    "Expected eg. ':mode prog 32', ':mode prog 8 unsigned' or ':mode normal'"
+                );
+                return;
+            }
+        };
+
+        if programmer_mode.is_none() && args[0] == "prog" {
+            eprintln!("The word size must be 8, 16, 32 or 64");
+        } else {
+            *parser = std::mem::take(parser).set_programmer_mode(programmer_mode);
+        }
+
+        return;
+    }
+
+    if let Some(latex_input) = input.strip_prefix(":latex ") {
+        output::eval_latex(parser, latex_input, precision);
+
+        return;
+    }
+
+    if let Some(table_args) = input.strip_prefix("table(") {
+        // The values can be converted to another radix, eg. table(f, 0, 10) to hex
+        let (table_args, base) = match table_args.rsplit_once(" to ") {
+            Some((args, name)) => match kalk::radix::radix_by_name(name.trim()) {
+                Some(base) => (args, base),
+                None => (table_args, repl.base),
+            },
+            None => (table_args, repl.base),
+        };
+
+        if let Some(table_args) = table_args.trim_end().strip_suffix(')') {
+            output::eval_table(parser, table_args, repl.threads, precision, base);
+
+            return;
+        }
+    }
+
+    if let Some(taylor_args) = input
+        .strip_prefix("taylor(")
+        .and_then(|args| args.trim_end().strip_suffix(')'))
+    {
+        output::eval_taylor(parser, taylor_args, precision);
+
+        return;
+    }
+
+    if let Some(plot_args) = input
+        .strip_prefix("plotparam(")
+        .and_then(|args| args.trim_end().strip_suffix(')'))
+    {
+        output::eval_plotparam(parser, plot_args, repl.threads, precision);
+
+        return;
+    }
+
     match input {
         "" => eprint!(""),
+        ":resume" => output::eval_resume(parser, precision, repl.base),
         "clear" => print!("\x1B[2J"),
         "exit" => process::exit(0),
         "help" => print_cli_help(),
@@ -110,51 +509,106 @@ fn eval_repl(repl: &mut self::Context, parser: &mut parser::Context, input: &str
     }
 }
 
+/// Add the input to the macro that is being recorded, if any.
+fn record_input(repl: &mut self::Context, input: &str) {
+    if let Some((_, inputs)) = &mut repl.recording {
+        if !input.trim().is_empty() {
+            inputs.push(input.to_string());
+        }
+    }
+}
+
 fn print_cli_help() {
     let help_text = include_str!("../help.txt");
     println!("{}", help_text);
 }
 
-struct LineHighlighter {}
+fn unit_names(parser: &parser::Context) -> HashSet<String> {
+    parser
+        .get_completions("")
+        .into_iter()
+        .filter(|item| item.kind == kalk::completion::CompletionKind::Unit)
+        .map(|item| item.name)
+        .collect()
+}
+
+struct LineHighlighter {
+    /// Units that have been defined, updated after each input.
+    units: HashSet<String>,
+}
+
+/// Words that start a command instead of an expression.
+const COMMANDS: [&str; 19] = [
+    "load", "base", ":set", ":steps", ":mode", ":latex", ":resume", ":record", ":stop", ":play",
+    ":store", ":recall", ":vars", ":funcs", ":show", ":del", "clear", "exit", "help",
+];
+
+impl LineHighlighter {
+    fn highlight_expression(&self, input: &str) -> String {
+        let chars: Vec<char> = input.chars().collect();
+        let mut coloured = String::new();
+        let mut pos = 0;
+        let spans = kalk::lexer::highlight_with(input, |identifier| {
+            self.units.contains(identifier) || matches!(identifier, "rad" | "deg" | "grad" | "turn")
+        });
+
+        for ((start, end), category) in spans {
+            coloured.extend(&chars[pos..start]);
+            let text: String = chars[start..end].iter().collect();
+            let colour = match category {
+                TokenCategory::Number => None,
+                // Keep brackets and separators uncoloured to make them easier to tell apart
+                TokenCategory::Operator if "()[]{}⟦⟧⌈⌉⌊⌋|,;".contains(&text) => None,
+                TokenCategory::Operator => Some(Colour::Fixed(172)),
+                TokenCategory::Function | TokenCategory::Variable => Some(Colour::Fixed(32)),
+                TokenCategory::String => Some(Colour::Green),
+                TokenCategory::Unit => Some(Colour::Yellow),
+                TokenCategory::Error => Some(Colour::Red),
+            };
+            match colour {
+                Some(colour) => write!(coloured, "{}", colour.paint(text)).unwrap(),
+                None => coloured.push_str(&text),
+            }
+            pos = end;
+        }
+        coloured.extend(&chars[pos..]);
+
+        coloured
+    }
+}
 
 impl Highlighter for LineHighlighter {
     fn highlight<'l>(&self, line: &'l str, _: usize) -> Cow<'l, str> {
-        let mut coloured = line.to_string();
-
-        let reg = Regex::new(
-            r"(?x)
-            (?P<op>([+\-/*%^!×÷⋅∧∨¬ᵀ]|if|otherwise|\b(and|or|mod|true|false|not)\b|load|exit|clear|help)) |
-            (?P<radix>0[box][a-zA-Z0-9]+) |
-            (?P<identifier>[^!-@\s_|^⌊⌋⌈⌉\[\]\{\}⟦⟧≠≥≤⁰¹²³⁴⁵⁶⁷⁸⁹⁺⁻⁼⁽⁾₀₁₂₃₄₅₆₇₈₉₊₋₌₍₎ᵀ]+(_\d+)?)",
-        )
-        .unwrap();
-
-        coloured = reg
-            .replace_all(&coloured, |caps: &Captures| {
-                if let Some(cap) = caps.name("identifier") {
-                    match cap.as_str() {
-                        "rad" | "deg" | "°" => Colour::Yellow.paint(cap.as_str()).to_string(),
-                        _ => Colour::Fixed(32).paint(cap.as_str()).to_string(),
-                    }
-                } else if let Some(cap) = caps.name("op") {
-                    Colour::Fixed(172).paint(cap.as_str()).to_string()
-                } else {
-                    caps[0].to_string()
-                }
-            })
-            .to_string();
+        let command = line.split_whitespace().next().unwrap_or_default();
+        if COMMANDS.contains(&command) {
+            let (command, rest) = line.split_at(line.find(command).unwrap() + command.len());
+            let rest = if command.ends_with(":latex") {
+                self.highlight_expression(rest)
+            } else {
+                rest.to_string()
+            };
+
+            return Owned(format!("{}{}", Colour::Fixed(172).paint(command), rest));
+        }
 
-        Owned(coloured)
+        Owned(self.highlight_expression(line))
     }
 }
 
 struct RLHelper {
     highlighter: LineHighlighter,
     validator: MatchingBracketValidator,
+    /// Names that can be completed, updated after each input.
+    completions: Vec<kalk::completion::CompletionItem>,
 }
 
 impl Helper for RLHelper {}
 
+lazy_static! {
+    /// Set when Ctrl-C is pressed during a calculation.
+    pub static ref INTERRUPT_FLAG: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+}
+
 lazy_static! {
     pub static ref COMPLETION_FUNCS: HashMap<&'static str, &'static str> = {
         let mut m = HashMap::new();
@@ -173,6 +627,7 @@ lazy_static! {
         m.insert("(", "()");
         m.insert("[[", "⟦⟧");
         m.insert("!=", "≠");
+        m.insert("~=", "≈");
         m.insert(">=", "≥");
         m.insert("<=", "≤");
         m.insert(" and", " ∧");
@@ -237,6 +692,29 @@ impl Completer for RLHelper {
             }
         }
 
+        // Complete the names of functions, variables and units
+        let word_start = line[..pos]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| c.is_alphanumeric() || *c == '_')
+            .last()
+            .map(|(i, _)| i);
+        if let Some(word_start) = word_start {
+            let word = &line[word_start..pos];
+            let candidates: Vec<String> = self
+                .completions
+                .iter()
+                .filter(|item| item.name.starts_with(word))
+                .map(|item| match item.arity {
+                    Some(_) => format!("{}()", item.name),
+                    None => item.name.clone(),
+                })
+                .collect();
+            if !candidates.is_empty() {
+                return Ok((word_start, candidates));
+            }
+        }
+
         Ok((0, vec![line.to_string()]))
     }
 
@@ -294,7 +772,13 @@ impl Validator for RLHelper {
             }
         }
 
-        if !group_symbol_count.into_iter().all(|x| x % 2 == 0) {
+        // A line ending with an operator continues on the next line
+        let ends_with_operator = ctx
+            .input()
+            .trim_end()
+            .ends_with(&['+', '-', '*', '/', '^', ',', '×', '÷'][..]);
+
+        if !group_symbol_count.into_iter().all(|x| x % 2 == 0) || ends_with_operator {
             Ok(ValidationResult::Incomplete)
         } else {
             self.validator.validate(ctx)
@@ -0,0 +1,142 @@
+use crate::output::{is_comment, script_statements};
+use kalk::ast::Stmt;
+use kalk::parser;
+
+/// A part of a report, in the order they appear in the script.
+enum Block {
+    /// The text of comments that follow each other.
+    Prose(Vec<String>),
+    /// Statements that follow each other, as they are written in the script.
+    Code(Vec<String>),
+    /// An expression and its result, in LaTeX.
+    Math(String),
+    Error(String),
+    /// An empty line, which ends the paragraph or code block before it.
+    Break,
+}
+
+/// Evaluate a script and write it as a Markdown document, or as an HTML document if
+/// `html` is true. Comments become paragraphs, statements become code blocks and the
+/// result of each expression is written in LaTeX after it, eg. `$$x^{2} = 9$$`.
+pub fn create_report(
+    parser: &mut parser::Context,
+    title: &str,
+    source: &str,
+    precision: u32,
+    html: bool,
+) -> String {
+    let blocks = eval_script(parser, source, precision);
+    if html {
+        to_html(title, &blocks)
+    } else {
+        to_markdown(&blocks)
+    }
+}
+
+fn eval_script(parser: &mut parser::Context, source: &str, precision: u32) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    for lines in script_statements(source) {
+        let input = lines.join("\n");
+        if input.trim().is_empty() {
+            blocks.push(Block::Break);
+            continue;
+        }
+
+        if is_comment(&input) {
+            let text = input
+                .trim_start()
+                .trim_start_matches('#')
+                .trim()
+                .to_string();
+            match blocks.last_mut() {
+                Some(Block::Prose(paragraph)) if !text.is_empty() => paragraph.push(text),
+                _ if text.is_empty() => blocks.push(Block::Break),
+                _ => blocks.push(Block::Prose(vec![text])),
+            }
+            continue;
+        }
+
+        match blocks.last_mut() {
+            Some(Block::Code(code)) => code.extend(lines.iter().map(|line| line.to_string())),
+            _ => blocks.push(Block::Code(
+                lines.iter().map(|line| line.to_string()).collect(),
+            )),
+        }
+
+        let statements = match parser::parse(parser, input.trim()) {
+            Ok(statements) => statements,
+            Err(err) => {
+                blocks.push(Block::Error(err.to_string()));
+                continue;
+            }
+        };
+
+        match parser::eval(parser, input.trim(), precision) {
+            Ok(Some(result)) => {
+                if let Some(Stmt::Expr(expr)) = statements.last() {
+                    blocks.push(Block::Math(format!(
+                        "{} = {}",
+                        expr.to_latex(),
+                        result.to_latex()
+                    )));
+                }
+            }
+            Ok(None) => (),
+            Err(err) => blocks.push(Block::Error(err.to_string())),
+        }
+    }
+
+    blocks
+}
+
+fn to_markdown(blocks: &[Block]) -> String {
+    let mut output = Vec::new();
+    for block in blocks {
+        match block {
+            Block::Break => continue,
+            Block::Prose(lines) => output.push(lines.join("\n")),
+            Block::Code(lines) => output.push(format!("```\n{}\n```", lines.join("\n"))),
+            Block::Math(latex) => output.push(format!("$${}$$", latex)),
+            Block::Error(message) => output.push(format!("> Error: {}", message)),
+        }
+    }
+
+    let mut output = output.join("\n\n");
+    output.push('\n');
+
+    output
+}
+
+fn to_html(title: &str, blocks: &[Block]) -> String {
+    let mut body = Vec::new();
+    for block in blocks {
+        match block {
+            Block::Break => continue,
+            Block::Prose(lines) => body.push(format!("<p>{}</p>", escape_html(&lines.join("\n")))),
+            Block::Code(lines) => body.push(format!(
+                "<pre><code>{}</code></pre>",
+                escape_html(&lines.join("\n"))
+            )),
+            Block::Math(latex) => body.push(format!("<p>\\[{}\\]</p>", escape_html(latex))),
+            Block::Error(message) => body.push(format!(
+                "<blockquote>Error: {}</blockquote>",
+                escape_html(message)
+            )),
+        }
+    }
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+        <script src=\"https://cdn.jsdelivr.net/npm/mathjax@3/es5/tex-chtml.js\"></script>\n\
+        </head>\n<body>\n{}\n</body>\n</html>\n",
+        escape_html(title),
+        body.join("\n")
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
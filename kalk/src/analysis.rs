@@ -1,9 +1,9 @@
 use crate::{
-    ast::{ConditionalPiece, Expr, Identifier, RangedVar, Stmt},
+    ast::{ConditionalPiece, Expr, ExprSpan, Identifier, RangedVar, Stmt},
     errors::KalkError,
     inverter,
     lexer::TokenKind,
-    parser, prelude,
+    parser,
     symbol_table::SymbolTable,
 };
 
@@ -20,6 +20,11 @@ pub(crate) struct Context<'a> {
     in_equation: bool,
     in_comprehension: bool,
     comprehension_vars: Option<Vec<RangedVar>>,
+    /// Variables bound with `let` around the expression that is being analysed, with the
+    /// innermost one last. They are renamed to eg. `let1-t`, like parameters are named
+    /// eg. `f-x`, so that functions called in the body don't see them.
+    let_variables: Vec<(String, Identifier)>,
+    let_count: u32,
 }
 
 pub(crate) fn analyse_stmt(
@@ -39,15 +44,26 @@ pub(crate) fn analyse_stmt(
         in_equation: false,
         in_comprehension: false,
         comprehension_vars: None,
+        let_variables: Vec::new(),
+        let_count: 0,
     };
 
     Ok(match statement {
         Stmt::VarDecl(identifier, value) => {
+            // A compound assignment, eg. `x += 2`, refers to the previous value of the
+            // variable, which has to stay in the symbol table until it is evaluated.
+            let is_update =
+                inverter::contains_var(context.symbol_table, &value, &identifier.full_name);
             let var_decl = Stmt::VarDecl(identifier, Box::new(analyse_expr(&mut context, *value)?));
-            context.symbol_table.insert(var_decl.clone());
+            if !is_update {
+                context.symbol_table.insert(var_decl.clone());
+            }
 
             var_decl
         }
+        Stmt::MultiVarDecl(identifiers, value) => {
+            build_multi_var_decl(&mut context, identifiers, *value)?
+        }
         Stmt::FnDecl(identifier, parameters, body) => {
             context.current_function_name = Some(identifier.pure_name.clone());
             context.current_function_parameters = Some(parameters.clone());
@@ -79,15 +95,13 @@ pub(crate) fn analyse_stmt(
 
 fn analyse_stmt_expr(context: &mut Context, value: Expr) -> Result<Stmt, KalkError> {
     Ok(
-        if let Expr::Binary(left, TokenKind::Equals, right) = value {
+        if let Expr::Binary(left, TokenKind::Equals, right, _) = value {
             if let Some((identifier, parameters)) = is_fn_decl(&left) {
                 return build_fn_decl_from_scratch(context, identifier, parameters, *right);
             }
 
             match *left {
-                Expr::FnCall(identifier, arguments)
-                    if !prelude::is_prelude_func(&identifier.full_name) =>
-                {
+                Expr::FnCall(identifier, arguments) => {
                     // First loop through with a reference
                     // to arguments, to be able to back-track if
                     // one of the arguments can't be made into a parameter.
@@ -102,16 +116,19 @@ fn analyse_stmt_expr(context: &mut Context, value: Expr) -> Result<Stmt, KalkErr
                             context,
                             Expr::Binary(
                                 Box::new(Expr::Binary(
-                                    Box::new(Expr::Literal(0f64)),
+                                    Box::new(Expr::Literal(0f64, ExprSpan::default())),
                                     TokenKind::Plus,
                                     Box::new(Expr::FnCall(identifier, arguments)),
+                                    ExprSpan::default(),
                                 )),
                                 TokenKind::Equals,
                                 right,
+                                ExprSpan::default(),
                             ),
                         );
                     }
 
+                    context.symbol_table.check_shadowing(&identifier, true)?;
                     let mut parameters = Vec::new();
                     for argument in arguments {
                         if let Expr::Var(parameter_identifier) = argument {
@@ -126,15 +143,26 @@ fn analyse_stmt_expr(context: &mut Context, value: Expr) -> Result<Stmt, KalkErr
 
                     fn_decl
                 }
+                Expr::Vector(targets)
+                    if !context.in_conditional
+                        && targets.iter().all(|target| matches!(target, Expr::Var(_))) =>
+                {
+                    let identifiers = targets
+                        .into_iter()
+                        .map(|target| match target {
+                            Expr::Var(identifier) => identifier,
+                            _ => unreachable!(),
+                        })
+                        .collect();
+
+                    build_multi_var_decl(context, identifiers, *right)?
+                }
                 Expr::Var(identifier) if !context.in_conditional => {
                     if inverter::contains_var(context.symbol_table, &right, &identifier.full_name) {
                         return Err(KalkError::VariableReferencesItself);
                     }
 
-                    if prelude::is_constant(&identifier.full_name) {
-                        return Err(KalkError::UnableToOverrideConstant(identifier.pure_name));
-                    }
-
+                    context.symbol_table.check_shadowing(&identifier, false)?;
                     let result =
                         Stmt::VarDecl(identifier, Box::new(analyse_expr(context, *right)?));
                     context.symbol_table.insert(result.clone());
@@ -143,7 +171,7 @@ fn analyse_stmt_expr(context: &mut Context, value: Expr) -> Result<Stmt, KalkErr
                 }
                 _ => Stmt::Expr(Box::new(analyse_expr(
                     context,
-                    Expr::Binary(left, TokenKind::Equals, right),
+                    Expr::Binary(left, TokenKind::Equals, right, ExprSpan::default()),
                 )?)),
             }
         } else {
@@ -152,8 +180,49 @@ fn analyse_stmt_expr(context: &mut Context, value: Expr) -> Result<Stmt, KalkErr
     )
 }
 
+/// Declare each of the variables as an element of the value, eg. `q = divmod(17, 5)⟦1⟧`
+/// and `r = divmod(17, 5)⟦2⟧` for `(q, r) = divmod(17, 5)`.
+fn build_multi_var_decl(
+    context: &mut Context,
+    identifiers: Vec<Identifier>,
+    value: Expr,
+) -> Result<Stmt, KalkError> {
+    for identifier in &identifiers {
+        if inverter::contains_var(context.symbol_table, &value, &identifier.full_name) {
+            return Err(KalkError::VariableReferencesItself);
+        }
+
+        context.symbol_table.check_shadowing(identifier, false)?;
+    }
+
+    let value = analyse_expr(context, value)?;
+    for var_decl in multi_var_decl_elements(&identifiers, &value) {
+        context.symbol_table.insert(var_decl);
+    }
+
+    Ok(Stmt::MultiVarDecl(identifiers, Box::new(value)))
+}
+
+/// The declarations of the variables of a `Stmt::MultiVarDecl`, where each variable is
+/// an element of the value, eg. `r = divmod(17, 5)⟦2⟧`.
+pub(crate) fn multi_var_decl_elements(identifiers: &[Identifier], value: &Expr) -> Vec<Stmt> {
+    identifiers
+        .iter()
+        .enumerate()
+        .map(|(i, identifier)| {
+            Stmt::VarDecl(
+                identifier.clone(),
+                Box::new(Expr::Indexer(
+                    Box::new(Expr::Group(Box::new(value.clone()))),
+                    vec![Expr::Literal((i + 1) as f64, ExprSpan::default())],
+                )),
+            )
+        })
+        .collect()
+}
+
 pub fn is_fn_decl(expr: &Expr) -> Option<(Identifier, Vec<String>)> {
-    if let Expr::Binary(left, TokenKind::Star, right) = expr {
+    if let Expr::Binary(left, TokenKind::Star, right, _) = expr {
         let identifier = if let Expr::Var(identifier) = &**left {
             identifier
         } else {
@@ -176,9 +245,7 @@ pub fn is_fn_decl(expr: &Expr) -> Option<(Identifier, Vec<String>)> {
             }
         }
 
-        if !prelude::is_prelude_func(&identifier.full_name) {
-            return Some((identifier.clone(), parameters));
-        }
+        return Some((identifier.clone(), parameters));
     }
 
     None
@@ -190,6 +257,7 @@ fn build_fn_decl_from_scratch(
     parameters: Vec<String>,
     right: Expr,
 ) -> Result<Stmt, KalkError> {
+    context.symbol_table.check_shadowing(&identifier, true)?;
     context.current_function_name = Some(identifier.pure_name.clone());
     context.current_function_parameters = Some(parameters.clone());
     let fn_decl = Stmt::FnDecl(
@@ -204,15 +272,81 @@ fn build_fn_decl_from_scratch(
     Ok(fn_decl)
 }
 
+/// Check if the name is a unit of time or an SI unit that is also used as a variable
+/// or function parameter, in which case the variable is what's meant.
+fn is_shadowed_builtin_unit(context: &Context, name: &str) -> bool {
+    let is_builtin_unit =
+        crate::duration::is_duration_unit(name) || crate::units::is_builtin_unit(name);
+    if !is_builtin_unit || context.symbol_table.unit_types.contains_key(name) {
+        return false;
+    }
+
+    is_parameter(context, name)
+        || is_sum_variable(context, name)
+        || context.symbol_table.contains_var(name)
+}
+
+/// Check if the name is the variable of a sum or product that is being analysed, eg. the `k`
+/// in `sum(k=1, 5, 2k)`.
+fn is_sum_variable(context: &Context, name: &str) -> bool {
+    context.in_sum_prod
+        && context
+            .sum_variable_names
+            .iter()
+            .flatten()
+            .any(|variable_name| variable_name == name)
+}
+
+fn is_parameter(context: &Context, name: &str) -> bool {
+    match (
+        &context.current_function_name,
+        &context.current_function_parameters,
+    ) {
+        (Some(function_name), Some(parameters)) => parameters
+            .iter()
+            .any(|parameter| parameter == &format!("{}-{}", function_name, name)),
+        _ => false,
+    }
+}
+
+/// Check if the name is a unit on its own, eg. the `s` in `10 m/s`, which is then one of it.
+/// A prefix on its own, eg. `k`, isn't counted, since it's more likely to be a variable.
+fn is_bare_unit(context: &Context, name: &str) -> bool {
+    crate::units::standalone_prefix(name).is_none()
+        && !context.in_equation
+        && !context.in_unit_decl
+        && context.symbol_table.contains_unit(name)
+        && !context.symbol_table.contains_var(name)
+        && !is_parameter(context, name)
+}
+
 fn analyse_expr(context: &mut Context, expr: Expr) -> Result<Expr, KalkError> {
     Ok(match expr {
-        Expr::Binary(left, op, right) => analyse_binary(context, *left, op, *right)?,
-        Expr::Unary(op, value) => Expr::Unary(op, Box::new(analyse_expr(context, *value)?)),
+        Expr::Binary(left, op, right, span) => {
+            analyse_binary(context, *left, op, *right)?.with_span(span)
+        }
+        Expr::Unary(TokenKind::Not, value, span) => {
+            // Eg. `not x = 1` is a comparison rather than an equation
+            let previous_in_conditional = context.in_conditional;
+            context.in_conditional = true;
+            let value = analyse_expr(context, *value);
+            context.in_conditional = previous_in_conditional;
+
+            Expr::Unary(TokenKind::Not, Box::new(value?), span)
+        }
+        Expr::Unary(op, value, span) => {
+            Expr::Unary(op, Box::new(analyse_expr(context, *value)?), span)
+        }
+        Expr::Unit(name, value) if is_shadowed_builtin_unit(context, &name) => {
+            // Eg. `2h` where h is a variable, rather than two hours
+            let var = Expr::Var(Identifier::from_full_name(&name));
+            analyse_binary(context, *value, TokenKind::Star, var)?
+        }
         Expr::Unit(name, value) => Expr::Unit(name, Box::new(analyse_expr(context, *value)?)),
         Expr::Var(identifier) => analyse_var(context, identifier, None, None)?,
         Expr::Group(value) => Expr::Group(Box::new(analyse_expr(context, *value)?)),
         Expr::FnCall(identifier, arguments) => analyse_fn(context, identifier, arguments)?,
-        Expr::Literal(_) | Expr::Boolean(_) => expr,
+        Expr::Literal(_, _) | Expr::Integer(_) | Expr::Boolean(_) | Expr::String(_) => expr,
         Expr::Piecewise(pieces) => {
             let mut analysed_pieces = Vec::new();
             for piece in pieces {
@@ -259,6 +393,44 @@ fn analyse_expr(context: &mut Context, expr: Expr) -> Result<Expr, KalkError> {
         }
         Expr::Comprehension(left, right, vars) => Expr::Comprehension(left, right, vars),
         Expr::Equation(left, right, identifier) => Expr::Equation(left, right, identifier),
+        Expr::Let(identifier, value, body) => {
+            let value = analyse_expr(context, *value)?;
+
+            // Make the variable known while analysing the body,
+            // so that eg. `tt` isn't split up into `t*t` if `tt` is the local variable.
+            context.let_count += 1;
+            let local_identifier = Identifier::parameter_from_name(
+                &identifier.full_name,
+                &format!("let{}", context.let_count),
+            );
+            context.symbol_table.push_scope();
+            context
+                .symbol_table
+                .insert_local(Stmt::VarDecl(identifier.clone(), Box::new(value.clone())))?;
+            context
+                .let_variables
+                .push((identifier.full_name, local_identifier.clone()));
+            let body = analyse_expr(context, *body);
+            context.let_variables.pop();
+            context.symbol_table.pop_scope();
+
+            Expr::Let(local_identifier, Box::new(value), Box::new(body?))
+        }
+        Expr::WithAngleUnit(angle_unit, expr) => {
+            Expr::WithAngleUnit(angle_unit, Box::new(analyse_expr(context, *expr)?))
+        }
+        Expr::If(condition, then_branch, else_branch) => {
+            let previous_in_conditional = context.in_conditional;
+            context.in_conditional = true;
+            let condition = analyse_expr(context, *condition);
+            context.in_conditional = previous_in_conditional;
+
+            Expr::If(
+                Box::new(condition?),
+                Box::new(analyse_expr(context, *then_branch)?),
+                Box::new(analyse_expr(context, *else_branch)?),
+            )
+        }
     })
 }
 
@@ -303,6 +475,24 @@ fn analyse_binary(
 
             Ok(Expr::Equation(Box::new(left), Box::new(right), identifier))
         }
+        (_, TokenKind::ToKeyword, Expr::Var(_)) => {
+            // The right side is the name of the unit to convert to, rather than a variable
+            Ok(Expr::Binary(
+                Box::new(analyse_expr(context, left)?),
+                op,
+                Box::new(right),
+                ExprSpan::default(),
+            ))
+        }
+        (Expr::Var(_), TokenKind::Arrow, _) => {
+            // The left side is the variable of a limit, which shouldn't be evaluated
+            Ok(Expr::Binary(
+                Box::new(left),
+                op,
+                Box::new(analyse_expr(context, right)?),
+                ExprSpan::default(),
+            ))
+        }
         (Expr::Var(_), TokenKind::Star, _) => {
             if let Expr::Var(identifier) = left {
                 analyse_var(context, identifier, Some(right), None)
@@ -320,6 +510,7 @@ fn analyse_binary(
                 Box::new(analyse_expr(context, left)?),
                 TokenKind::Power,
                 Box::new(analyse_expr(context, right)?),
+                ExprSpan::default(),
             )),
         },
         (_, TokenKind::Colon, _) => {
@@ -336,9 +527,10 @@ fn analyse_binary(
 
             let mut conditions = vec![right];
             let mut has_comma = false;
-            while let Expr::Binary(_, TokenKind::Comma, _) = conditions.last().unwrap() {
+            while let Expr::Binary(_, TokenKind::Comma, _, _) = conditions.last().unwrap() {
                 has_comma = true;
-                if let Expr::Binary(left_condition, _, right_condition) = conditions.pop().unwrap()
+                if let Expr::Binary(left_condition, _, right_condition, _) =
+                    conditions.pop().unwrap()
                 {
                     conditions.push(analyse_expr(context, *left_condition.to_owned())?);
                     conditions.push(analyse_expr(context, *right_condition.to_owned())?);
@@ -388,6 +580,7 @@ fn analyse_binary(
             Box::new(analyse_expr(context, left)?),
             op,
             Box::new(analyse_expr(context, right)?),
+            ExprSpan::default(),
         )),
     };
 
@@ -409,6 +602,7 @@ fn analyse_comparison_with_var(
             Box::new(analyse_expr(context, var)?),
             op,
             Box::new(right),
+            ExprSpan::default(),
         ));
     }
 
@@ -429,7 +623,8 @@ fn analyse_comparison_with_var(
                     ranged_var.min = Expr::Binary(
                         Box::new(right),
                         TokenKind::Plus,
-                        Box::new(Expr::Literal(1f64)),
+                        Box::new(Expr::Literal(1f64, ExprSpan::default())),
+                        ExprSpan::default(),
                     );
                 }
                 TokenKind::LessThan => {
@@ -442,7 +637,8 @@ fn analyse_comparison_with_var(
                     ranged_var.max = Expr::Binary(
                         Box::new(right),
                         TokenKind::Plus,
-                        Box::new(Expr::Literal(1f64)),
+                        Box::new(Expr::Literal(1f64, ExprSpan::default())),
+                        ExprSpan::default(),
                     );
                 }
                 _ => unreachable!(),
@@ -453,9 +649,10 @@ fn analyse_comparison_with_var(
     }
 
     Ok(Expr::Binary(
-        Box::new(Expr::Literal(0f64)),
+        Box::new(Expr::Literal(0f64, ExprSpan::default())),
         TokenKind::Equals,
-        Box::new(Expr::Literal(0f64)),
+        Box::new(Expr::Literal(0f64, ExprSpan::default())),
+        ExprSpan::default(),
     ))
 }
 
@@ -464,6 +661,27 @@ fn analyse_var(
     identifier: Identifier,
     adjacent_factor: Option<Expr>,
     adjacent_exponent: Option<Expr>,
+) -> Result<Expr, KalkError> {
+    let span = identifier.span.clone();
+    let mut expr =
+        analyse_var_without_span(context, identifier, adjacent_factor, adjacent_exponent)?;
+
+    // The identifier may have been split up, eg. `xy` into `x*y`, or turned into a function call,
+    // and what it became should still point at it in the input.
+    if let Some(span) = span {
+        crate::ast::for_each_span_mut(&mut expr, &mut |expr_span| {
+            expr_span.get_or_insert_with(|| span.clone());
+        });
+    }
+
+    Ok(expr)
+}
+
+fn analyse_var_without_span(
+    context: &mut Context,
+    identifier: Identifier,
+    adjacent_factor: Option<Expr>,
+    adjacent_exponent: Option<Expr>,
 ) -> Result<Expr, KalkError> {
     let adjacent_factor = if let Some(adjacent_factor) = adjacent_factor {
         Some(analyse_expr(context, adjacent_factor)?)
@@ -479,6 +697,15 @@ fn analyse_var(
 
     if is_comprehension_var {
         with_adjacent(Expr::Var(identifier), adjacent_factor, adjacent_exponent)
+    } else if is_bare_unit(context, &identifier.full_name) {
+        with_adjacent(
+            Expr::Unit(
+                identifier.full_name,
+                Box::new(Expr::Literal(1f64, ExprSpan::default())),
+            ),
+            adjacent_factor,
+            adjacent_exponent,
+        )
     } else if context.symbol_table.contains_var(&identifier.pure_name)
         || (identifier.pure_name.len() == 1 && !context.in_equation)
     {
@@ -563,12 +790,14 @@ fn with_adjacent(
             Box::new(expr),
             TokenKind::Star,
             Box::new(factor),
+            ExprSpan::default(),
         ))
     } else if let Some(exponent) = exponent {
         Ok(Expr::Binary(
             Box::new(expr),
             TokenKind::Power,
             Box::new(exponent),
+            ExprSpan::default(),
         ))
     } else {
         Ok(expr)
@@ -581,7 +810,10 @@ fn build_indexed_var(context: &mut Context, identifier: Identifier) -> Result<Ex
     let lowered = &identifier.pure_name[underscore_pos + 1..];
     let lowered_expr =
         if !lowered.is_empty() && lowered.chars().next().unwrap_or('\0').is_ascii_digit() {
-            Expr::Literal(lowered.parse::<f64>().unwrap_or(f64::NAN))
+            Expr::Literal(
+                lowered.parse::<f64>().unwrap_or(f64::NAN),
+                ExprSpan::default(),
+            )
         } else {
             build_var(context, lowered)
         };
@@ -615,6 +847,7 @@ fn build_dx(
                 "d{}",
                 char_after_d
             )))),
+            ExprSpan::default(),
         ))
     }
 }
@@ -658,18 +891,37 @@ fn build_split_up_vars(
         // If last iteration
         if chars_iter.peek().is_none() {
             if let Some(exponent) = adjacent_exponent {
-                right = Expr::Binary(Box::new(right), TokenKind::Power, Box::new(exponent));
+                right = Expr::Binary(
+                    Box::new(right),
+                    TokenKind::Power,
+                    Box::new(exponent),
+                    ExprSpan::default(),
+                );
                 adjacent_exponent = None;
             }
         }
 
-        left = Expr::Binary(Box::new(left), TokenKind::Star, Box::new(right))
+        left = Expr::Binary(
+            Box::new(left),
+            TokenKind::Star,
+            Box::new(right),
+            ExprSpan::default(),
+        )
     }
 
     with_adjacent(left, adjacent_factor, adjacent_exponent)
 }
 
 fn build_var(context: &mut Context, name: &str) -> Expr {
+    if let Some((_, identifier)) = context
+        .let_variables
+        .iter()
+        .rev()
+        .find(|(let_name, _)| let_name == name)
+    {
+        return Expr::Var(identifier.clone());
+    }
+
     if let (Some(function_name), Some(params)) = (
         context.current_function_name.as_ref(),
         context.current_function_parameters.as_ref(),
@@ -680,13 +932,7 @@ fn build_var(context: &mut Context, name: &str) -> Expr {
         }
     }
 
-    if context.in_sum_prod
-        && context
-            .sum_variable_names
-            .as_ref()
-            .unwrap()
-            .contains(&name.to_string())
-    {
+    if is_sum_variable(context, name) {
         return Expr::Var(Identifier::from_full_name(name));
     }
 
@@ -699,8 +945,8 @@ fn build_var(context: &mut Context, name: &str) -> Expr {
         if let Some(vars) = context.comprehension_vars.as_mut() {
             vars.push(RangedVar {
                 name: name.to_string(),
-                max: Expr::Literal(0f64),
-                min: Expr::Literal(0f64),
+                max: Expr::Literal(0f64, ExprSpan::default()),
+                min: Expr::Literal(0f64, ExprSpan::default()),
             });
         }
     }
@@ -729,7 +975,7 @@ fn analyse_fn(
     }
 
     // Don't perform equation solving on special functions
-    if is_integral || is_sum_prod {
+    if is_integral || is_sum_prod || identifier.pure_name == "lim" {
         context.in_equation = false;
     }
 
@@ -738,7 +984,7 @@ fn analyse_fn(
         if i == 0 && context.in_sum_prod {
             context.in_conditional = true;
             let vars = context.sum_variable_names.as_mut().unwrap();
-            if let Expr::Binary(left, TokenKind::Equals, _) = argument {
+            if let Expr::Binary(left, TokenKind::Equals, _, _) = argument {
                 if let Expr::Var(var_identifier) = &**left {
                     vars.push(var_identifier.pure_name.clone());
                 } else {
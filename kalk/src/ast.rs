@@ -1,9 +1,15 @@
-use crate::lexer::TokenKind;
+use crate::kalk_value::BigInt;
+use crate::lexer::{Span, TokenKind};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 
 /// A tree structure of a statement.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Stmt {
     VarDecl(Identifier, Box<Expr>),
+    /// Variables that are declared as the elements of a vector, eg. `(q, r) = divmod(17, 5)`.
+    MultiVarDecl(Vec<Identifier>, Box<Expr>),
     FnDecl(Identifier, Vec<String>, Box<Expr>),
     UnitDecl(String, String, Box<Expr>),
     /// For simplicity, expressions can be put into statements. This is the form in which expressions are passed to the interpreter.
@@ -12,42 +18,163 @@ pub enum Stmt {
 
 /// A tree structure of an expression.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expr {
-    Binary(Box<Expr>, TokenKind, Box<Expr>),
-    Unary(TokenKind, Box<Expr>),
+    Binary(
+        Box<Expr>,
+        TokenKind,
+        Box<Expr>,
+        #[cfg_attr(feature = "serde", serde(skip))] ExprSpan,
+    ),
+    Unary(
+        TokenKind,
+        Box<Expr>,
+        #[cfg_attr(feature = "serde", serde(skip))] ExprSpan,
+    ),
     Unit(String, Box<Expr>),
     Var(Identifier),
     Group(Box<Expr>),
     FnCall(Identifier, Vec<Expr>),
-    Literal(f64),
+    Literal(f64, #[cfg_attr(feature = "serde", serde(skip))] ExprSpan),
+    /// An integer that is too large to be exact as a float, eg. `9007199254740993`.
+    Integer(BigInt),
     Boolean(bool),
+    /// Text between double quotes, eg. `"km"` in `format(x, "%.2f km")`.
+    String(String),
     Piecewise(Vec<ConditionalPiece>),
     Vector(Vec<Expr>),
     Matrix(Vec<Vec<Expr>>),
     Indexer(Box<Expr>, Vec<Expr>),
     Comprehension(Box<Expr>, Vec<Expr>, Vec<RangedVar>),
     Equation(Box<Expr>, Box<Expr>, Identifier),
+    /// A local binding, eg. `let t = x^2 in t + t`. The variable is only visible inside the body.
+    Let(Identifier, Box<Expr>, Box<Expr>),
+    /// An expression evaluated with a specific angle unit, eg. `with deg { sin(90) }`.
+    WithAngleUnit(String, Box<Expr>),
+    /// A conditional expression, eg. `if x > 0 then x else -x`, with the condition
+    /// and the branches. Only the branch that is taken is evaluated.
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+/// The characters of the input that an operation or a literal was parsed from, so that errors
+/// can point at it, eg. `1/0`. Like the span of an identifier, it is ignored when comparing
+/// expressions, and boxed to keep expressions small. Expressions that weren't parsed from
+/// the input, eg. the ones made by the simplifier, don't have one.
+#[derive(Debug, Clone, Default)]
+pub struct ExprSpan(pub(crate) Option<Box<Span>>);
+
+impl ExprSpan {
+    pub fn new(span: Span) -> Self {
+        ExprSpan(Some(Box::new(span)))
+    }
+
+    pub fn get(&self) -> Option<Span> {
+        self.0.as_deref().copied()
+    }
+}
+
+impl PartialEq for ExprSpan {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Stmt {
+    /// Get a hash of the statement that does not depend on whitespace or redundant parentheses,
+    /// eg. `1+2` and `(1 + 2)` give the same hash.
+    pub fn canonical_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        hash_stmt(self, &mut hasher);
+
+        hasher.finish()
+    }
+}
+
+impl Expr {
+    /// Give the operation or literal the span, unless it already has one,
+    /// eg. when an operation was analysed into another one.
+    pub(crate) fn with_span(mut self, span: ExprSpan) -> Self {
+        if let Expr::Binary(_, _, _, own_span)
+        | Expr::Unary(_, _, own_span)
+        | Expr::Literal(_, own_span) = &mut self
+        {
+            if own_span.0.is_none() {
+                *own_span = span;
+            }
+        }
+
+        self
+    }
+
+    /// The characters of the input that the expression was parsed from, if it is known.
+    pub(crate) fn span(&self) -> Option<Span> {
+        match self {
+            Expr::Binary(_, _, _, span) | Expr::Unary(_, _, span) | Expr::Literal(_, span) => {
+                span.get()
+            }
+            Expr::Var(identifier) | Expr::FnCall(identifier, _) => {
+                identifier.span.as_deref().copied()
+            }
+            _ => None,
+        }
+    }
+
+    /// Get a hash of the expression that does not depend on whitespace or redundant parentheses.
+    pub fn canonical_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        hash_expr(self, &mut hasher);
+
+        hasher.finish()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConditionalPiece {
     pub expr: Expr,
     pub condition: Expr,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RangedVar {
     pub name: String,
     pub max: Expr,
     pub min: Expr,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Identifier {
     pub full_name: String,
     pub pure_name: String,
     pub parameter_of_function: Option<String>,
     pub prime_count: u32,
+    /// The characters of the input that the identifier was parsed from, so that errors
+    /// can point at it. Identifiers are compared and hashed without it, and it is boxed
+    /// to keep expressions small, since the interpreter recurses through them.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub span: Option<Box<Span>>,
+}
+
+impl PartialEq for Identifier {
+    fn eq(&self, other: &Self) -> bool {
+        self.full_name == other.full_name
+            && self.pure_name == other.pure_name
+            && self.parameter_of_function == other.parameter_of_function
+            && self.prime_count == other.prime_count
+    }
+}
+
+impl Eq for Identifier {}
+
+impl Hash for Identifier {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.full_name.hash(state);
+        self.pure_name.hash(state);
+        self.parameter_of_function.hash(state);
+        self.prime_count.hash(state);
+    }
 }
 
 impl Identifier {
@@ -59,6 +186,7 @@ impl Identifier {
             pure_name,
             parameter_of_function: None,
             prime_count,
+            span: None,
         }
     }
 
@@ -68,6 +196,7 @@ impl Identifier {
             pure_name: pure_name.into(),
             parameter_of_function: None,
             prime_count,
+            span: None,
         }
     }
 
@@ -77,9 +206,16 @@ impl Identifier {
             pure_name: name.into(),
             parameter_of_function: Some(function.into()),
             prime_count: 0u32,
+            span: None,
         }
     }
 
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(Box::new(span));
+
+        self
+    }
+
     pub fn get_name_without_lowered(&self) -> &str {
         if let Some(underscore_pos) = self.pure_name.find('_') {
             &self.pure_name[0..underscore_pos]
@@ -98,18 +234,265 @@ impl Identifier {
 }
 
 pub fn build_literal_ast(kalk_value: &crate::kalk_value::KalkValue) -> Expr {
-    if kalk_value.has_imaginary() {
+    if let crate::kalk_value::KalkValue::Boolean(boolean) = kalk_value {
+        Expr::Boolean(*boolean)
+    } else if let crate::kalk_value::KalkValue::String(value) = kalk_value {
+        Expr::String(value.clone())
+    } else if let crate::kalk_value::KalkValue::Integer(integer) = kalk_value {
+        // Kept exact, eg. so that `ans` after `25!` is still the same integer
+        Expr::Integer(integer.clone())
+    } else if let crate::kalk_value::KalkValue::Vector(values) = kalk_value {
+        Expr::Vector(values.iter().map(build_element_ast).collect())
+    } else if let crate::kalk_value::KalkValue::SparseVector(vector) = kalk_value {
+        Expr::Vector(vector.to_dense().iter().map(build_element_ast).collect())
+    } else if let crate::kalk_value::KalkValue::Matrix(rows) = kalk_value {
+        Expr::Matrix(
+            rows.iter()
+                .map(|row| row.iter().map(build_element_ast).collect())
+                .collect(),
+        )
+    } else if kalk_value.has_imaginary() {
         Expr::Binary(
-            Box::new(Expr::Literal(kalk_value.to_f64())),
+            Box::new(Expr::Literal(kalk_value.to_f64(), ExprSpan::default())),
             TokenKind::Plus,
             Box::new(Expr::Binary(
-                Box::new(Expr::Literal(kalk_value.imaginary_to_f64())),
+                Box::new(Expr::Literal(
+                    kalk_value.imaginary_to_f64(),
+                    ExprSpan::default(),
+                )),
                 TokenKind::Star,
                 Box::new(Expr::Var(Identifier::from_full_name("i"))),
+                ExprSpan::default(),
             )),
+            ExprSpan::default(),
         )
     } else {
-        Expr::Literal(kalk_value.to_f64())
+        Expr::Literal(kalk_value.to_f64(), ExprSpan::default())
+    }
+}
+
+/// An element of a vector or matrix, which may have its own unit, eg. `2 m`.
+fn build_element_ast(kalk_value: &crate::kalk_value::KalkValue) -> Expr {
+    match kalk_value.get_unit() {
+        Some(unit) => Expr::Unit(unit.to_string(), Box::new(build_literal_ast(kalk_value))),
+        None => build_literal_ast(kalk_value),
+    }
+}
+
+/// Call the function with the expression and every expression inside it, outermost first.
+pub(crate) fn for_each_expr_mut(expr: &mut Expr, f: &mut impl FnMut(&mut Expr)) {
+    f(expr);
+    match expr {
+        Expr::Binary(left, _, right, _) => {
+            for_each_expr_mut(left, f);
+            for_each_expr_mut(right, f);
+        }
+        Expr::Unary(_, expr, _)
+        | Expr::Unit(_, expr)
+        | Expr::Group(expr)
+        | Expr::WithAngleUnit(_, expr) => for_each_expr_mut(expr, f),
+        Expr::FnCall(_, arguments) => {
+            for argument in arguments {
+                for_each_expr_mut(argument, f);
+            }
+        }
+        Expr::Var(_)
+        | Expr::Literal(_, _)
+        | Expr::Integer(_)
+        | Expr::Boolean(_)
+        | Expr::String(_) => (),
+        Expr::Piecewise(pieces) => {
+            for piece in pieces {
+                for_each_expr_mut(&mut piece.expr, f);
+                for_each_expr_mut(&mut piece.condition, f);
+            }
+        }
+        Expr::Vector(values) => {
+            for value in values {
+                for_each_expr_mut(value, f);
+            }
+        }
+        Expr::Matrix(rows) => {
+            for value in rows.iter_mut().flatten() {
+                for_each_expr_mut(value, f);
+            }
+        }
+        Expr::Indexer(value, indexes) => {
+            for_each_expr_mut(value, f);
+            for index in indexes {
+                for_each_expr_mut(index, f);
+            }
+        }
+        Expr::Comprehension(left, conditions, vars) => {
+            for_each_expr_mut(left, f);
+            for condition in conditions {
+                for_each_expr_mut(condition, f);
+            }
+            for var in vars {
+                for_each_expr_mut(&mut var.min, f);
+                for_each_expr_mut(&mut var.max, f);
+            }
+        }
+        Expr::Equation(left, right, _) => {
+            for_each_expr_mut(left, f);
+            for_each_expr_mut(right, f);
+        }
+        Expr::Let(_, value, body) => {
+            for_each_expr_mut(value, f);
+            for_each_expr_mut(body, f);
+        }
+        Expr::If(condition, then_branch, else_branch) => {
+            for_each_expr_mut(condition, f);
+            for_each_expr_mut(then_branch, f);
+            for_each_expr_mut(else_branch, f);
+        }
+    }
+}
+
+/// Call the function with every identifier in the expression.
+pub(crate) fn for_each_identifier_mut(expr: &mut Expr, f: &mut impl FnMut(&mut Identifier)) {
+    for_each_expr_mut(expr, &mut |expr| match expr {
+        Expr::Var(identifier)
+        | Expr::FnCall(identifier, _)
+        | Expr::Equation(_, _, identifier)
+        | Expr::Let(identifier, _, _) => f(identifier),
+        _ => (),
+    });
+}
+
+/// Call the function with the span of every operation, literal and identifier
+/// in the expression, eg. to change them.
+pub(crate) fn for_each_span_mut(expr: &mut Expr, f: &mut impl FnMut(&mut Option<Box<Span>>)) {
+    for_each_expr_mut(expr, &mut |expr| match expr {
+        Expr::Binary(_, _, _, span) | Expr::Unary(_, _, span) | Expr::Literal(_, span) => {
+            f(&mut span.0)
+        }
+        Expr::Var(identifier)
+        | Expr::FnCall(identifier, _)
+        | Expr::Equation(_, _, identifier)
+        | Expr::Let(identifier, _, _) => f(&mut identifier.span),
+        _ => (),
+    });
+}
+
+/// Get a hash of several statements, eg. the ones from a line of input.
+pub fn canonical_hash(statements: &[Stmt]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for stmt in statements {
+        hash_stmt(stmt, &mut hasher);
+    }
+
+    hasher.finish()
+}
+
+fn hash_stmt(stmt: &Stmt, state: &mut DefaultHasher) {
+    std::mem::discriminant(stmt).hash(state);
+    match stmt {
+        Stmt::VarDecl(identifier, expr) => {
+            identifier.hash(state);
+            hash_expr(expr, state);
+        }
+        Stmt::MultiVarDecl(identifiers, expr) => {
+            identifiers.hash(state);
+            hash_expr(expr, state);
+        }
+        Stmt::FnDecl(identifier, parameters, body) => {
+            identifier.hash(state);
+            parameters.hash(state);
+            hash_expr(body, state);
+        }
+        Stmt::UnitDecl(identifier, base_unit, expr) => {
+            identifier.hash(state);
+            base_unit.hash(state);
+            hash_expr(expr, state);
+        }
+        Stmt::Expr(expr) => hash_expr(expr, state),
+    }
+}
+
+fn hash_exprs(exprs: &[Expr], state: &mut DefaultHasher) {
+    exprs.len().hash(state);
+    for expr in exprs {
+        hash_expr(expr, state);
+    }
+}
+
+fn hash_expr(expr: &Expr, state: &mut DefaultHasher) {
+    // Parentheses don't affect the structure of the tree,
+    // so a group is hashed the same way as its contents.
+    if let Expr::Group(inner) = expr {
+        return hash_expr(inner, state);
+    }
+
+    std::mem::discriminant(expr).hash(state);
+    match expr {
+        Expr::Binary(left, op, right, _) => {
+            hash_expr(left, state);
+            op.hash(state);
+            hash_expr(right, state);
+        }
+        Expr::Unary(op, expr, _) => {
+            op.hash(state);
+            hash_expr(expr, state);
+        }
+        Expr::Unit(identifier, expr) => {
+            identifier.hash(state);
+            hash_expr(expr, state);
+        }
+        Expr::Var(identifier) => identifier.hash(state),
+        Expr::Group(_) => unreachable!(),
+        Expr::FnCall(identifier, arguments) => {
+            identifier.hash(state);
+            hash_exprs(arguments, state);
+        }
+        Expr::Literal(value, _) => value.to_bits().hash(state),
+        Expr::Integer(value) => value.to_string().hash(state),
+        Expr::Boolean(value) => value.hash(state),
+        Expr::String(value) => value.hash(state),
+        Expr::Piecewise(pieces) => {
+            for piece in pieces {
+                hash_expr(&piece.expr, state);
+                hash_expr(&piece.condition, state);
+            }
+        }
+        Expr::Vector(values) => hash_exprs(values, state),
+        Expr::Matrix(rows) => {
+            for row in rows {
+                hash_exprs(row, state);
+            }
+        }
+        Expr::Indexer(value, indexes) => {
+            hash_expr(value, state);
+            hash_exprs(indexes, state);
+        }
+        Expr::Comprehension(left, conditions, vars) => {
+            hash_expr(left, state);
+            hash_exprs(conditions, state);
+            for var in vars {
+                var.name.hash(state);
+                hash_expr(&var.min, state);
+                hash_expr(&var.max, state);
+            }
+        }
+        Expr::Equation(left, right, identifier) => {
+            hash_expr(left, state);
+            hash_expr(right, state);
+            identifier.hash(state);
+        }
+        Expr::Let(identifier, value, body) => {
+            identifier.hash(state);
+            hash_expr(value, state);
+            hash_expr(body, state);
+        }
+        Expr::WithAngleUnit(angle_unit, expr) => {
+            angle_unit.hash(state);
+            hash_expr(expr, state);
+        }
+        Expr::If(condition, then_branch, else_branch) => {
+            hash_expr(condition, state);
+            hash_expr(then_branch, state);
+            hash_expr(else_branch, state);
+        }
     }
 }
 
@@ -126,3 +509,24 @@ fn separate_identifier_and_prime(identifier: &str) -> (String, u32) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::parser;
+
+    fn hash(input: &str) -> u64 {
+        let mut context = parser::Context::new();
+        crate::ast::canonical_hash(&parser::parse(&mut context, input).unwrap())
+    }
+
+    #[test]
+    fn test_canonical_hash() {
+        assert_eq!(hash("1+2"), hash("1 + 2"));
+        assert_eq!(hash("(1 + 2)"), hash("1 + 2"));
+        assert_eq!(hash("f(x)=2x"), hash("f(x) = 2x"));
+        assert_eq!(hash("2 * (3 + x)"), hash("2 * ((3 + x))"));
+        assert_ne!(hash("1 + 2"), hash("2 + 1"));
+        assert_ne!(hash("(1 + 2) * 3"), hash("1 + 2 * 3"));
+        assert_ne!(hash("1; 2"), hash("1 + 2"));
+    }
+}
@@ -1,12 +1,28 @@
 use wasm_bindgen::prelude::wasm_bindgen;
 
-use crate::kalk_value::{ComplexNumberType, KalkValue, ScientificNotation};
+use crate::kalk_value::{
+    BigInt, ComplexNumberType, KalkValue, RoundingOptions, ScientificNotation,
+};
+use crate::parser::ProgrammerMode;
+use crate::steps::Step;
 
 #[wasm_bindgen]
+#[derive(Clone)]
 pub struct CalculationResult {
     value: KalkValue,
     radix: u8,
     is_approximation: bool,
+    is_out_of_range: bool,
+    domain_note: Option<String>,
+    undefined_reason: Option<String>,
+    rounding_options: RoundingOptions,
+    dropped_unit: Option<String>,
+    steps: Vec<Step>,
+    programmer_mode: Option<ProgrammerMode>,
+    /// Bounds for the exact value, found with interval arithmetic.
+    bounds: Option<(f64, f64)>,
+    /// Notes added by middleware, eg. where a value came from.
+    annotations: Vec<String>,
 }
 
 // Wraps around KalkValue since enums don't work
@@ -18,9 +34,66 @@ impl CalculationResult {
             value,
             radix,
             is_approximation,
+            is_out_of_range: false,
+            domain_note: None,
+            undefined_reason: None,
+            rounding_options: RoundingOptions::default(),
+            dropped_unit: None,
+            steps: Vec::new(),
+            programmer_mode: None,
+            bounds: None,
+            annotations: Vec::new(),
         }
     }
 
+    pub(crate) fn set_steps(mut self, steps: Vec<Step>) -> Self {
+        self.steps = steps;
+
+        self
+    }
+
+    pub(crate) fn set_programmer_mode(mut self, programmer_mode: Option<ProgrammerMode>) -> Self {
+        self.programmer_mode = programmer_mode;
+
+        self
+    }
+
+    pub(crate) fn set_out_of_range(mut self, is_out_of_range: bool) -> Self {
+        self.is_out_of_range = is_out_of_range;
+
+        self
+    }
+
+    pub(crate) fn set_domain_note(mut self, domain_note: Option<String>) -> Self {
+        self.domain_note = domain_note;
+
+        self
+    }
+
+    pub(crate) fn set_undefined_reason(mut self, undefined_reason: Option<String>) -> Self {
+        self.undefined_reason = undefined_reason;
+
+        self
+    }
+
+    pub(crate) fn set_rounding_options(mut self, rounding_options: RoundingOptions) -> Self {
+        self.rounding_options = rounding_options;
+
+        self
+    }
+
+    pub(crate) fn set_bounds(mut self, bounds: Option<(f64, f64)>) -> Self {
+        self.bounds = bounds;
+
+        self
+    }
+
+    pub(crate) fn set_dropped_unit(mut self, dropped_unit: Option<String>) -> Self {
+        self.dropped_unit = dropped_unit;
+
+        self
+    }
+
     #[allow(dead_code)]
     pub(crate) fn get_value(self) -> KalkValue {
         self.value
@@ -38,23 +111,101 @@ impl CalculationResult {
 
     #[wasm_bindgen(js_name = toPrettyString)]
     pub fn to_string_pretty(&self) -> String {
-        let value = if self.radix == 10 {
-            self.value.to_string_pretty_radix(10)
+        if let Some(reason) = self.get_undefined_reason() {
+            return format!("Not defined, since {}.", reason);
+        }
+
+        let options = &self.rounding_options;
+        let value = if let (Some((lower, upper)), 10) = (self.bounds, self.radix) {
+            self.value.to_string_pretty_bounded(lower, upper, options)
+        } else if self.radix == 10 && options.side_by_side {
+            self.value.to_string_side_by_side(options)
+        } else if self.radix == 10 {
+            self.value.to_string_pretty_with(10, options)
         } else {
+            // In programmer mode, negative values are shown in two's complement
+            let radix_value = match (self.programmer_mode, &self.value) {
+                (Some(mode), KalkValue::Number(..) | KalkValue::Integer(_))
+                    if self.value.to_f64() < 0f64 =>
+                {
+                    KalkValue::Integer(BigInt::from_u64(mode.bit_pattern(&self.value)))
+                }
+                _ => self.value.clone(),
+            };
+
             format!(
                 "{}\n{}",
-                self.value.to_string_pretty_radix(10),
-                self.value.to_string_pretty_radix(self.radix),
+                self.value.to_string_pretty_with(10, options),
+                radix_value.to_string_pretty_with(self.radix, options),
             )
         };
 
-        if self.is_approximation {
+        if self.is_approximation && !value.starts_with('≈') {
             format!("≈ {}", value)
         } else {
             value
         }
     }
 
+    /// Check if an operation gave a result that was too large or too small to be
+    /// represented, which was replaced with infinity or zero.
+    #[wasm_bindgen(js_name = isOutOfRange)]
+    pub fn is_out_of_range(&self) -> bool {
+        self.is_out_of_range
+    }
+
+    /// Get what the first operation that was given values outside of its domain gave,
+    /// eg. `1 / 0 is undefined, and gave ∞`, if there was any.
+    #[wasm_bindgen(js_name = getDomainNote)]
+    pub fn get_domain_note(&self) -> Option<String> {
+        self.domain_note.clone()
+    }
+
+    /// Get why the result is undefined, eg. `0 / 0 is undefined`, if it is and the reason is known.
+    #[wasm_bindgen(js_name = getUndefinedReason)]
+    pub fn get_undefined_reason(&self) -> Option<String> {
+        if self.value.is_nan() {
+            self.undefined_reason.clone()
+        } else {
+            None
+        }
+    }
+
+    /// Get the unit that was lost in an operation during the calculation, if any,
+    /// eg. `km` in `2 + 3 km`.
+    #[wasm_bindgen(js_name = getDroppedUnit)]
+    pub fn get_dropped_unit(&self) -> Option<String> {
+        self.dropped_unit.clone()
+    }
+
+    /// Get the steps of the calculation, one per line, if tracing was enabled.
+    #[wasm_bindgen(js_name = getStepsString)]
+    pub fn steps_to_string(&self) -> String {
+        self.steps
+            .iter()
+            .map(|step| step.to_string())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Get the unit of the value, eg. `m` for `2 m`.
+    #[wasm_bindgen(js_name = getUnit)]
+    pub fn get_unit(&self) -> Option<String> {
+        self.value.get_unit().cloned()
+    }
+
+    /// Get the radix the result should also be displayed in, eg. 2 for binary.
+    #[wasm_bindgen(js_name = getRadix)]
+    pub fn get_radix(&self) -> u8 {
+        self.radix
+    }
+
+    /// Check if the value is only an approximation, eg. after a numerical integration.
+    #[wasm_bindgen(js_name = isApproximation)]
+    pub fn is_approximation(&self) -> bool {
+        self.is_approximation
+    }
+
     #[wasm_bindgen(js_name = getValue)]
     pub fn to_f64(&self) -> f64 {
         self.value.to_f64()
@@ -78,9 +229,67 @@ impl CalculationResult {
         self.value.to_scientific_notation(complex_number_type)
     }
 
+    #[wasm_bindgen(js_name = toLatex)]
+    pub fn to_latex(&self) -> String {
+        self.value.to_latex()
+    }
+
+    #[wasm_bindgen(js_name = toMathML)]
+    pub fn to_mathml(&self) -> String {
+        self.value.to_mathml()
+    }
+
+    /// Get the value written as decimals, without its estimate, eg. `0.3333333333` for 1/3.
+    /// The estimate is available with `estimate_js`.
+    #[wasm_bindgen(js_name = getDecimal)]
+    pub fn get_decimal(&self) -> String {
+        self.value.to_string_pretty_with(
+            10,
+            &RoundingOptions {
+                estimate: false,
+                ..self.rounding_options.clone()
+            },
+        )
+    }
+
     #[wasm_bindgen(js_name = estimate)]
     pub fn estimate_js(&self) -> Option<String> {
-        self.value.estimate()
+        self.value.estimate_with(&self.rounding_options)
+    }
+
+    #[wasm_bindgen(js_name = estimateWithVulgarFractions)]
+    pub fn estimate_with_vulgar_fractions_js(&self) -> Option<String> {
+        self.value.estimate_with(&RoundingOptions {
+            vulgar_fractions: true,
+            ..self.rounding_options.clone()
+        })
+    }
+}
+
+impl CalculationResult {
+    /// Get the steps of the calculation, if tracing was enabled.
+    pub fn get_steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// Get the bounds for the exact value, if interval mode was enabled
+    /// and the value could be bounded.
+    pub fn get_bounds(&self) -> Option<(f64, f64)> {
+        self.bounds
+    }
+
+    /// Add a note to the result, eg. in `Middleware::after_eval`.
+    pub fn add_annotation(&mut self, annotation: &str) {
+        self.annotations.push(annotation.to_string());
+    }
+
+    pub fn get_annotations(&self) -> &[String] {
+        &self.annotations
+    }
+
+    /// Get an estimate of the value, where the options decide what it may be written as.
+    pub fn estimate_with(&self, options: &RoundingOptions) -> Option<String> {
+        self.value.estimate_with(options)
     }
 }
 
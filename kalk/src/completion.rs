@@ -0,0 +1,50 @@
+/// A name that can be completed while typing, eg. a function or a unit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionItem {
+    pub name: String,
+    pub kind: CompletionKind,
+    /// The amount of arguments, for functions.
+    pub arity: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Function,
+    Variable,
+    Constant,
+    Unit,
+}
+
+/// Functions that are evaluated by the interpreter itself rather than the prelude,
+/// and the amount of arguments they take.
+pub(crate) const SPECIAL_FUNCS: &[(&str, usize)] = &[
+    ("sum", 3),
+    ("prod", 3),
+    ("integrate", 3),
+    ("integral", 3),
+    ("findroot", 2),
+    ("bisect", 3),
+    ("lim", 2),
+    ("maximize", 3),
+    ("minimize", 3),
+    ("derive", 2),
+    ("taylor", 3),
+    ("simplify", 1),
+    ("now", 0),
+    ("precision", 0),
+    ("anglemode", 0),
+    ("backend", 0),
+    ("rand", 0),
+    ("randint", 2),
+    ("randnormal", 2),
+];
+
+impl CompletionItem {
+    pub(crate) fn new(name: &str, kind: CompletionKind, arity: Option<usize>) -> Self {
+        CompletionItem {
+            name: name.to_string(),
+            kind,
+            arity,
+        }
+    }
+}
@@ -0,0 +1,19 @@
+/// Physical constants (CODATA 2018 values, in SI units) that can be loaded
+/// into a context with `Context::load_physics_constants`. These are not
+/// available by default, since names like `c` and `h` are common variable names.
+pub(crate) const PHYSICS_CONSTANTS: &[(&str, f64)] = &[
+    ("c", 299_792_458f64),             // speed of light in vacuum
+    ("G", 6.674_30e-11),               // gravitational constant
+    ("h", 6.626_070_15e-34),           // Planck constant
+    ("hbar", 1.054_571_817e-34),       // reduced Planck constant
+    ("e_charge", 1.602_176_634e-19),   // elementary charge
+    ("k_B", 1.380_649e-23),            // Boltzmann constant
+    ("N_A", 6.022_140_76e23),          // Avogadro constant
+    ("R", 8.314_462_618),              // molar gas constant
+    ("g0", 9.806_65),                  // standard acceleration of gravity
+    ("m_e", 9.109_383_701_5e-31),      // electron mass
+    ("m_p", 1.672_621_923_69e-27),     // proton mass
+    ("epsilon0", 8.854_187_812_8e-12), // vacuum electric permittivity
+    ("mu0", 1.256_637_062_12e-6),      // vacuum magnetic permeability
+    ("sigma_SB", 5.670_374_419e-8),    // Stefan-Boltzmann constant
+];
@@ -0,0 +1,395 @@
+use crate::ast::{ConditionalPiece, Expr, ExprSpan, Identifier, Stmt};
+use crate::errors::KalkError;
+use crate::inverter::contains_var;
+use crate::lexer::TokenKind;
+use crate::symbol_table::SymbolTable;
+
+impl Expr {
+    /// Differentiate the expression symbolically with respect to a variable,
+    /// eg. `2x cos(x^2)` for `sin(x^2)`. The result is simplified.
+    /// Trigonometric functions are differentiated as if radians are used.
+    pub fn derive(&self, symbol_table: &SymbolTable, var_name: &str) -> Result<Expr, KalkError> {
+        Ok(derive(symbol_table, self, var_name)?.simplify())
+    }
+}
+
+fn derive(symbol_table: &SymbolTable, expr: &Expr, var_name: &str) -> Result<Expr, KalkError> {
+    if !contains_var(symbol_table, expr, var_name) {
+        return Ok(literal(0f64));
+    }
+
+    match expr {
+        Expr::Binary(left, op, right, _) => derive_binary(symbol_table, left, op, right, var_name),
+        Expr::Unary(TokenKind::Minus, expr, _) => Ok(Expr::Unary(
+            TokenKind::Minus,
+            Box::new(derive(symbol_table, expr, var_name)?),
+            ExprSpan::default(),
+        )),
+        Expr::Unary(TokenKind::Percent, expr, _) => Ok(binary(
+            derive(symbol_table, expr, var_name)?,
+            TokenKind::Slash,
+            literal(100f64),
+        )),
+        Expr::Unit(identifier, expr) => Ok(Expr::Unit(
+            identifier.clone(),
+            Box::new(derive(symbol_table, expr, var_name)?),
+        )),
+        Expr::Var(identifier) => {
+            if identifier.full_name == var_name {
+                return Ok(literal(1f64));
+            }
+
+            // A variable defined in terms of the variable, eg. y = 2x
+            match symbol_table.get_var(&identifier.full_name) {
+                Some(Stmt::VarDecl(_, var_expr)) => derive(symbol_table, var_expr, var_name),
+                _ => Ok(literal(0f64)),
+            }
+        }
+        Expr::Group(expr) => derive(symbol_table, expr, var_name),
+        Expr::FnCall(identifier, arguments) => {
+            derive_fn_call(symbol_table, identifier, arguments, var_name)
+        }
+        Expr::Piecewise(pieces) => {
+            let mut derived_pieces = Vec::new();
+            for piece in pieces {
+                derived_pieces.push(ConditionalPiece {
+                    expr: derive(symbol_table, &piece.expr, var_name)?,
+                    condition: piece.condition.clone(),
+                });
+            }
+
+            Ok(Expr::Piecewise(derived_pieces))
+        }
+        Expr::If(condition, then_branch, else_branch) => Ok(Expr::If(
+            condition.clone(),
+            Box::new(derive(symbol_table, then_branch, var_name)?),
+            Box::new(derive(symbol_table, else_branch, var_name)?),
+        )),
+        Expr::Vector(values) => Ok(Expr::Vector(
+            values
+                .iter()
+                .map(|value| derive(symbol_table, value, var_name))
+                .collect::<Result<Vec<Expr>, KalkError>>()?,
+        )),
+        _ => Err(KalkError::UnableToDifferentiate(expr.to_string())),
+    }
+}
+
+fn derive_binary(
+    symbol_table: &SymbolTable,
+    left: &Expr,
+    op: &TokenKind,
+    right: &Expr,
+    var_name: &str,
+) -> Result<Expr, KalkError> {
+    let left_is_constant = !contains_var(symbol_table, left, var_name);
+    let right_is_constant = !contains_var(symbol_table, right, var_name);
+    let derive = |expr: &Expr| derive(symbol_table, expr, var_name);
+
+    Ok(match op {
+        TokenKind::Plus | TokenKind::Minus => binary(derive(left)?, op.clone(), derive(right)?),
+        // (fg)' = f'g + fg'
+        TokenKind::Star => {
+            if left_is_constant {
+                binary(left.clone(), TokenKind::Star, derive(right)?)
+            } else if right_is_constant {
+                binary(right.clone(), TokenKind::Star, derive(left)?)
+            } else {
+                binary(
+                    binary(derive(left)?, TokenKind::Star, right.clone()),
+                    TokenKind::Plus,
+                    binary(left.clone(), TokenKind::Star, derive(right)?),
+                )
+            }
+        }
+        // (f/g)' = (f'g - fg') / g^2
+        TokenKind::Slash => {
+            if right_is_constant {
+                binary(derive(left)?, TokenKind::Slash, right.clone())
+            } else {
+                let numerator = binary(
+                    binary(derive(left)?, TokenKind::Star, right.clone()),
+                    TokenKind::Minus,
+                    binary(left.clone(), TokenKind::Star, derive(right)?),
+                );
+
+                binary(
+                    numerator,
+                    TokenKind::Slash,
+                    binary(right.clone(), TokenKind::Power, literal(2f64)),
+                )
+            }
+        }
+        TokenKind::Power => {
+            if right_is_constant {
+                // (f^n)' = n f^(n - 1) f'
+                let exponent = match right {
+                    Expr::Literal(value, _) => literal(value - 1f64),
+                    _ => binary(right.clone(), TokenKind::Minus, literal(1f64)),
+                };
+
+                binary(
+                    binary(
+                        right.clone(),
+                        TokenKind::Star,
+                        binary(left.clone(), TokenKind::Power, exponent),
+                    ),
+                    TokenKind::Star,
+                    derive(left)?,
+                )
+            } else if left_is_constant {
+                // (a^g)' = a^g ln(a) g'
+                let power = binary(left.clone(), TokenKind::Power, right.clone());
+                let power = if is_euler_number(symbol_table, left) {
+                    power
+                } else {
+                    binary(power, TokenKind::Star, fn_call("ln", left.clone()))
+                };
+
+                binary(power, TokenKind::Star, derive(right)?)
+            } else {
+                // (f^g)' = f^g (g' ln(f) + g f' / f)
+                let sum = binary(
+                    binary(derive(right)?, TokenKind::Star, fn_call("ln", left.clone())),
+                    TokenKind::Plus,
+                    binary(
+                        binary(right.clone(), TokenKind::Star, derive(left)?),
+                        TokenKind::Slash,
+                        left.clone(),
+                    ),
+                );
+
+                binary(
+                    binary(left.clone(), TokenKind::Power, right.clone()),
+                    TokenKind::Star,
+                    sum,
+                )
+            }
+        }
+        _ => return Err(KalkError::UnableToDifferentiate(format!("{:?}", op))),
+    })
+}
+
+fn derive_fn_call(
+    symbol_table: &SymbolTable,
+    identifier: &Identifier,
+    arguments: &[Expr],
+    var_name: &str,
+) -> Result<Expr, KalkError> {
+    let unable_to_differentiate =
+        || KalkError::UnableToDifferentiate(format!("{}(..)", identifier.full_name));
+
+    // User-defined functions are differentiated by inserting the arguments into the body
+    if let Some(Stmt::FnDecl(_, parameters, body)) = symbol_table.get_fn(&identifier.full_name) {
+        if identifier.prime_count > 0 || parameters.len() != arguments.len() {
+            return Err(unable_to_differentiate());
+        }
+
+        let mut body = *body.clone();
+        for (parameter, argument) in parameters.iter().zip(arguments) {
+            body = substitute(&body, parameter, argument);
+        }
+
+        return derive(symbol_table, &body, var_name);
+    }
+
+    if arguments.len() == 2 && identifier.full_name == "log" {
+        // log(f, b)' = f' / (f ln(b))
+        if contains_var(symbol_table, &arguments[1], var_name) {
+            return Err(unable_to_differentiate());
+        }
+
+        let denominator = binary(
+            arguments[0].clone(),
+            TokenKind::Star,
+            fn_call("ln", arguments[1].clone()),
+        );
+
+        return Ok(binary(
+            derive(symbol_table, &arguments[0], var_name)?,
+            TokenKind::Slash,
+            denominator,
+        ));
+    }
+
+    if arguments.len() != 1 || identifier.prime_count > 0 {
+        return Err(unable_to_differentiate());
+    }
+
+    // Chain rule: f(g)' = f'(g) g'
+    let argument = &arguments[0];
+    let outer =
+        outer_derivative(&identifier.full_name, argument).ok_or_else(unable_to_differentiate)?;
+
+    Ok(binary(
+        outer,
+        TokenKind::Star,
+        derive(symbol_table, argument, var_name)?,
+    ))
+}
+
+/// Get the derivative of a built-in function of one argument, evaluated at `u`.
+fn outer_derivative(name: &str, u: &Expr) -> Option<Expr> {
+    let call = |name: &str| fn_call(name, u.clone());
+    let squared = |expr: Expr| binary(expr, TokenKind::Power, literal(2f64));
+    let negate = |expr: Expr| Expr::Unary(TokenKind::Minus, Box::new(expr), ExprSpan::default());
+    let reciprocal = |expr: Expr| binary(literal(1f64), TokenKind::Slash, expr);
+    let u_squared = || squared(u.clone());
+
+    Some(match name {
+        "sin" => call("cos"),
+        "cos" => negate(call("sin")),
+        "tan" => squared(call("sec")),
+        "cot" => negate(squared(call("csc"))),
+        "sec" => binary(call("sec"), TokenKind::Star, call("tan")),
+        "csc" => negate(binary(call("csc"), TokenKind::Star, call("cot"))),
+        "sinh" => call("cosh"),
+        "cosh" => call("sinh"),
+        "tanh" => squared(call("sech")),
+        "coth" => negate(squared(call("csch"))),
+        "sech" => negate(binary(call("sech"), TokenKind::Star, call("tanh"))),
+        "csch" => negate(binary(call("csch"), TokenKind::Star, call("coth"))),
+        "asin" => reciprocal(fn_call(
+            "sqrt",
+            binary(literal(1f64), TokenKind::Minus, u_squared()),
+        )),
+        "acos" => negate(reciprocal(fn_call(
+            "sqrt",
+            binary(literal(1f64), TokenKind::Minus, u_squared()),
+        ))),
+        "atan" => reciprocal(binary(literal(1f64), TokenKind::Plus, u_squared())),
+        "acot" => negate(reciprocal(binary(
+            literal(1f64),
+            TokenKind::Plus,
+            u_squared(),
+        ))),
+        "asinh" => reciprocal(fn_call(
+            "sqrt",
+            binary(u_squared(), TokenKind::Plus, literal(1f64)),
+        )),
+        "acosh" => reciprocal(fn_call(
+            "sqrt",
+            binary(u_squared(), TokenKind::Minus, literal(1f64)),
+        )),
+        "atanh" => reciprocal(binary(literal(1f64), TokenKind::Minus, u_squared())),
+        "exp" => call("exp"),
+        "ln" => reciprocal(u.clone()),
+        "log" => reciprocal(binary(
+            u.clone(),
+            TokenKind::Star,
+            fn_call("ln", literal(10f64)),
+        )),
+        "sqrt" | "√" => reciprocal(binary(literal(2f64), TokenKind::Star, call("sqrt"))),
+        "cbrt" => reciprocal(binary(
+            literal(3f64),
+            TokenKind::Star,
+            squared(call("cbrt")),
+        )),
+        "abs" => binary(u.clone(), TokenKind::Slash, call("abs")),
+        _ => return None,
+    })
+}
+
+/// Replace a variable with an expression, eg. the parameters of a function with its arguments.
+pub(crate) fn substitute(expr: &Expr, var_name: &str, value: &Expr) -> Expr {
+    let substitute = |expr: &Expr| substitute(expr, var_name, value);
+
+    match expr {
+        Expr::Var(identifier) if identifier.full_name == var_name => match value {
+            Expr::Binary(_, _, _, _) | Expr::Unary(_, _, _) => Expr::Group(Box::new(value.clone())),
+            _ => value.clone(),
+        },
+        Expr::Binary(left, op, right, _) => binary(substitute(left), op.clone(), substitute(right)),
+        Expr::Unary(op, expr, _) => {
+            Expr::Unary(op.clone(), Box::new(substitute(expr)), ExprSpan::default())
+        }
+        Expr::Unit(identifier, expr) => Expr::Unit(identifier.clone(), Box::new(substitute(expr))),
+        Expr::Group(expr) => Expr::Group(Box::new(substitute(expr))),
+        Expr::FnCall(identifier, arguments) => Expr::FnCall(
+            identifier.clone(),
+            arguments.iter().map(substitute).collect(),
+        ),
+        Expr::Piecewise(pieces) => Expr::Piecewise(
+            pieces
+                .iter()
+                .map(|piece| ConditionalPiece {
+                    expr: substitute(&piece.expr),
+                    condition: substitute(&piece.condition),
+                })
+                .collect(),
+        ),
+        Expr::Vector(values) => Expr::Vector(values.iter().map(substitute).collect()),
+        Expr::If(condition, then_branch, else_branch) => Expr::If(
+            Box::new(substitute(condition)),
+            Box::new(substitute(then_branch)),
+            Box::new(substitute(else_branch)),
+        ),
+        _ => expr.clone(),
+    }
+}
+
+fn is_euler_number(symbol_table: &SymbolTable, expr: &Expr) -> bool {
+    match expr {
+        Expr::Var(identifier) => {
+            identifier.full_name == "e" && symbol_table.get_var(&identifier.full_name).is_none()
+        }
+        _ => false,
+    }
+}
+
+fn binary(left: Expr, op: TokenKind, right: Expr) -> Expr {
+    Expr::Binary(Box::new(left), op, Box::new(right), ExprSpan::default())
+}
+
+fn fn_call(name: &str, argument: Expr) -> Expr {
+    Expr::FnCall(Identifier::from_full_name(name), vec![argument])
+}
+
+fn literal(value: f64) -> Expr {
+    Expr::Literal(value, ExprSpan::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser;
+
+    fn derive(input: &str) -> String {
+        let mut context = parser::Context::new();
+        parser::derive(&mut context, input, "x").unwrap()
+    }
+
+    #[test]
+    fn test_polynomials() {
+        assert_eq!(derive("x^2"), "2x");
+        assert_eq!(derive("3x^4 - 2x + 7"), "12x^3 - 2");
+        assert_eq!(derive("5"), "0");
+        assert_eq!(derive("y^2"), "0");
+        assert_eq!(derive("x/2"), "1 / 2");
+    }
+
+    #[test]
+    fn test_rules() {
+        assert_eq!(derive("x sin(x)"), "sin(x) + x * cos(x)");
+        assert_eq!(derive("sin(x^2)"), "cos(x^2) * 2x");
+        assert_eq!(derive("1/x"), "-1 / x^2");
+        assert_eq!(derive("e^(2x)"), "2e^(2x)");
+        assert_eq!(derive("2^x"), "2^x * ln(2)");
+        assert_eq!(derive("ln(x)"), "1 / x");
+    }
+
+    #[test]
+    fn test_user_defined_functions() {
+        let mut context = parser::Context::new();
+        parser::parse(&mut context, "f(x) = x^3 + 1").unwrap();
+        assert_eq!(
+            parser::derive(&mut context, "f(2x)", "x").unwrap(),
+            "6(2x)^2"
+        );
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let mut context = parser::Context::new();
+        assert!(parser::derive(&mut context, "floor(x)", "x").is_err());
+    }
+}
@@ -0,0 +1,352 @@
+use crate::ast::{ConditionalPiece, Expr, ExprSpan, Identifier, Stmt};
+use crate::lexer::TokenKind;
+use crate::parser::DECL_UNIT;
+use std::fmt;
+
+impl fmt::Display for Stmt {
+    /// Convert the statement back into kalker syntax, eg. `f(x) = 2x + 1`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stmt::VarDecl(identifier, expr) => {
+                write!(f, "{} = {}", identifier_to_string(identifier), expr)
+            }
+            Stmt::MultiVarDecl(identifiers, expr) => {
+                let identifiers = identifiers
+                    .iter()
+                    .map(identifier_to_string)
+                    .collect::<Vec<String>>();
+
+                write!(f, "({}) = {}", identifiers.join(", "), expr)
+            }
+            Stmt::FnDecl(identifier, parameters, body) => {
+                let parameters = parameters
+                    .iter()
+                    .map(|parameter| {
+                        parameter
+                            .strip_prefix(&format!("{}-", identifier.full_name))
+                            .unwrap_or(parameter)
+                    })
+                    .collect::<Vec<&str>>();
+
+                write!(
+                    f,
+                    "{}({}) = {}",
+                    identifier_to_string(identifier),
+                    parameters.join(", "),
+                    body
+                )
+            }
+            Stmt::UnitDecl(identifier, base_unit, def) => {
+                // The base unit is stored as a placeholder variable in the definition
+                let base_unit = Expr::Var(Identifier::from_full_name(base_unit));
+                let def = crate::differentiator::substitute(def, DECL_UNIT, &base_unit);
+
+                write!(f, "unit {} = {}", identifier, def)
+            }
+            Stmt::Expr(expr) => write!(f, "{}", expr),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    /// Convert the expression back into kalker syntax, eg. `2x^2 + sin(x)`.
+    /// Parentheses are added where they are needed.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Binary(left, op, right, _) => write!(f, "{}", binary_to_string(left, op, right)),
+            Expr::Unary(op, expr, _) => {
+                let expr_str = if let Expr::Binary(_, _, _, _) = &**expr {
+                    format!("({})", expr)
+                } else {
+                    expr.to_string()
+                };
+
+                match op {
+                    TokenKind::Minus => write!(f, "-{}", expr_str),
+                    TokenKind::Not => write!(f, "not {}", expr_str),
+                    TokenKind::Exclamation => write!(f, "{}!", expr_str),
+                    TokenKind::Percent => write!(f, "{}%", expr_str),
+                    // The side that the target of a limit is approached from, eg. `0+`
+                    TokenKind::GreaterThan => write!(f, "{}+", expr_str),
+                    TokenKind::LessThan => write!(f, "{}-", expr_str),
+                    _ => write!(f, "{}", expr_str),
+                }
+            }
+            Expr::Unit(identifier, expr) => write!(f, "{} {}", expr, identifier),
+            Expr::Var(identifier) => write!(f, "{}", identifier_to_string(identifier)),
+            Expr::Group(expr) => write!(f, "({})", expr),
+            Expr::FnCall(identifier, arguments) => write!(
+                f,
+                "{}({})",
+                identifier_to_string(identifier),
+                join(arguments, ", ")
+            ),
+            Expr::Literal(value, _) => write!(f, "{}", value),
+            Expr::Integer(value) => write!(f, "{}", value),
+            Expr::Boolean(value) => write!(f, "{}", value),
+            Expr::String(value) => write!(f, "{}", string_literal_to_string(value)),
+            Expr::Piecewise(pieces) => write!(f, "{}", piecewise_to_string(pieces)),
+            Expr::Vector(values) => write!(f, "({})", join(values, ", ")),
+            Expr::Matrix(rows) => {
+                let rows = rows
+                    .iter()
+                    .map(|row| join(row, ", "))
+                    .collect::<Vec<String>>();
+
+                write!(f, "[{}]", rows.join("; "))
+            }
+            Expr::Indexer(value, indexes) => write!(f, "{}[[{}]]", value, join(indexes, ", ")),
+            Expr::Comprehension(left, conditions, _) => {
+                write!(f, "[{} : {}]", left, join(conditions, ", "))
+            }
+            Expr::Equation(left, right, _) => write!(f, "{} = {}", left, right),
+            Expr::Let(identifier, value, body) => write!(
+                f,
+                "let {} = {} in {}",
+                identifier_to_string(identifier),
+                value,
+                body
+            ),
+            Expr::WithAngleUnit(angle_unit, expr) => {
+                write!(f, "with {} {{ {} }}", angle_unit, expr)
+            }
+            Expr::If(condition, then_branch, else_branch) => write!(
+                f,
+                "if {} then {} else {}",
+                condition, then_branch, else_branch
+            ),
+        }
+    }
+}
+
+/// How tightly an operator binds its operands. Higher values bind tighter.
+fn precedence(op: &TokenKind) -> u8 {
+    match op {
+        TokenKind::ToKeyword | TokenKind::Arrow | TokenKind::Colon | TokenKind::Comma => 0,
+        TokenKind::Or => 1,
+        TokenKind::And => 2,
+        TokenKind::Equals
+        | TokenKind::NotEquals
+        | TokenKind::ApproxEquals
+        | TokenKind::GreaterThan
+        | TokenKind::LessThan
+        | TokenKind::GreaterOrEquals
+        | TokenKind::LessOrEquals => 3,
+        TokenKind::Plus | TokenKind::Minus => 4,
+        TokenKind::Star
+        | TokenKind::Slash
+        | TokenKind::Percent
+        | TokenKind::Mod
+        | TokenKind::Rem
+        | TokenKind::Div
+        | TokenKind::Quot => 5,
+        TokenKind::Power => 7,
+        _ => 6,
+    }
+}
+
+/// Check if the operand of a binary expression needs to be put inside parentheses
+/// to be parsed back the same way, eg. the right side of `a - (b - c)`.
+pub(crate) fn needs_group(op: &TokenKind, operand: &Expr, is_right: bool) -> bool {
+    match operand {
+        Expr::Binary(_, operand_op, _, _) => {
+            let (precedence, operand_precedence) = (precedence(op), precedence(operand_op));
+            if operand_precedence != precedence {
+                return operand_precedence < precedence;
+            }
+
+            match op {
+                // Power is right-associative
+                TokenKind::Power => !is_right,
+                TokenKind::Minus
+                | TokenKind::Slash
+                | TokenKind::Percent
+                | TokenKind::Mod
+                | TokenKind::Rem
+                | TokenKind::Div
+                | TokenKind::Quot => is_right,
+                _ => false,
+            }
+        }
+        // Eg. (-x)^2, since it could be mistaken for -(x^2)
+        Expr::Unary(TokenKind::Minus, _, _) => *op == TokenKind::Power && !is_right,
+        Expr::Literal(value, _) if *value < 0f64 => *op == TokenKind::Power && !is_right,
+        Expr::Integer(value) if value.is_negative() => *op == TokenKind::Power && !is_right,
+        Expr::Unit(_, _) => precedence(op) > precedence(&TokenKind::Plus),
+        _ => false,
+    }
+}
+
+fn binary_to_string(left: &Expr, op: &TokenKind, right: &Expr) -> String {
+    let operand_to_string = |operand: &Expr, is_right: bool| {
+        if needs_group(op, operand, is_right) {
+            format!("({})", operand)
+        } else {
+            operand.to_string()
+        }
+    };
+    let left_str = operand_to_string(left, false);
+    let right_str = operand_to_string(right, true);
+
+    let op_str = match op {
+        TokenKind::Star => {
+            // Write eg. 2 * x as 2x
+            if is_coefficient(left) && starts_with_letter_or_group(right, &right_str) {
+                return format!("{}{}", left_str, right_str);
+            }
+
+            "*"
+        }
+        TokenKind::Power => return format!("{}^{}", left_str, right_str),
+        TokenKind::Comma => return format!("{}, {}", left_str, right_str),
+        _ => operator_to_string(op),
+    };
+
+    format!("{} {} {}", left_str, op_str, right_str)
+}
+
+/// Get the symbol of a binary operator, eg. `+` or `and`.
+pub(crate) fn operator_to_string(op: &TokenKind) -> &'static str {
+    match op {
+        TokenKind::Plus => "+",
+        TokenKind::Minus => "-",
+        TokenKind::Star => "*",
+        TokenKind::Slash => "/",
+        TokenKind::Power => "^",
+        TokenKind::Percent => "%",
+        TokenKind::Mod => "mod",
+        TokenKind::Rem => "rem",
+        TokenKind::Div => "div",
+        TokenKind::Quot => "quot",
+        TokenKind::Equals => "=",
+        TokenKind::NotEquals => "!=",
+        TokenKind::ApproxEquals => "≈",
+        TokenKind::GreaterThan => ">",
+        TokenKind::LessThan => "<",
+        TokenKind::GreaterOrEquals => ">=",
+        TokenKind::LessOrEquals => "<=",
+        TokenKind::And => "and",
+        TokenKind::Or => "or",
+        TokenKind::ToKeyword => "to",
+        TokenKind::Arrow => "->",
+        TokenKind::Colon => ":",
+        TokenKind::Comma => ",",
+        _ => "?",
+    }
+}
+
+fn is_coefficient(expr: &Expr) -> bool {
+    matches!(expr, Expr::Literal(_, _))
+}
+
+fn starts_with_letter_or_group(expr: &Expr, expr_str: &str) -> bool {
+    let starts_with_letter = expr_str
+        .chars()
+        .next()
+        .is_some_and(|first| first.is_alphabetic() || first == '(');
+
+    starts_with_letter && !matches!(expr, Expr::Unit(_, _) | Expr::Boolean(_))
+}
+
+fn piecewise_to_string(pieces: &[ConditionalPiece]) -> String {
+    let pieces = pieces
+        .iter()
+        .map(|piece| {
+            let is_otherwise = piece.condition
+                == Expr::Binary(
+                    Box::new(Expr::Literal(1f64, ExprSpan::default())),
+                    TokenKind::Equals,
+                    Box::new(Expr::Literal(1f64, ExprSpan::default())),
+                    ExprSpan::default(),
+                );
+            if is_otherwise {
+                format!("{} otherwise", piece.expr)
+            } else {
+                format!("{} if {}", piece.expr, piece.condition)
+            }
+        })
+        .collect::<Vec<String>>();
+
+    format!("{{ {} }}", pieces.join("; "))
+}
+
+/// Put the text between double quotes, with the characters that the lexer unescapes escaped.
+fn string_literal_to_string(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+
+    format!("\"{}\"", escaped)
+}
+
+fn identifier_to_string(identifier: &Identifier) -> String {
+    format!(
+        "{}{}",
+        identifier.pure_name,
+        "'".repeat(identifier.prime_count as usize)
+    )
+}
+
+fn join(exprs: &[Expr], separator: &str) -> String {
+    exprs
+        .iter()
+        .map(|expr| expr.to_string())
+        .collect::<Vec<String>>()
+        .join(separator)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Stmt;
+    use crate::parser;
+
+    fn to_string(input: &str) -> String {
+        let mut context = parser::Context::new();
+        let statements = parser::parse(&mut context, input).unwrap();
+
+        statements
+            .iter()
+            .map(|stmt| stmt.to_string())
+            .collect::<Vec<String>>()
+            .join("; ")
+    }
+
+    #[test]
+    fn test_to_string() {
+        assert_eq!(to_string("2x + 3"), "2x + 3");
+        assert_eq!(to_string("f(x)=x^2/3"), "f(x) = x^2 / 3");
+        assert_eq!(to_string("a - (b - c)"), "a - (b - c)");
+        assert_eq!(to_string("2sin(x)^2"), "2sin(x)^2");
+        assert_eq!(to_string("(1, 2) * 3"), "(1, 2) * 3");
+        assert_eq!(to_string("[1, 2; 3, 4]"), "[1, 2; 3, 4]");
+        assert_eq!(to_string("-x^2"), "(-x)^2");
+        assert_eq!(to_string("unit km = 1000m"), "unit km = 1000m");
+    }
+
+    #[test]
+    fn test_expressions_read_back_the_same() {
+        let mut context = parser::Context::new();
+        for input in [
+            "2^3^2",
+            "(2^3)^2",
+            "8 / (4 / 2)",
+            "1 - 2 - 3",
+            "x / 2y",
+            "sqrt(x + 1)",
+        ] {
+            let parsed = parser::parse(&mut context, input).unwrap();
+            let printed = parsed[0].to_string();
+            let reparsed = parser::parse(&mut context, &printed).unwrap();
+            if let (Stmt::Expr(expr), Stmt::Expr(reparsed_expr)) = (&parsed[0], &reparsed[0]) {
+                assert_eq!(
+                    expr.canonical_hash(),
+                    reparsed_expr.canonical_hash(),
+                    "{} was printed as {}",
+                    input,
+                    printed
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+/// The unit of points in time, such as the value of `now()`.
+/// Values with this unit are the amount of seconds since 1970-01-01 00:00:00 UTC.
+pub(crate) const TIMESTAMP_UNIT: &str = "utc";
+
+/// Units of time, and how many seconds they are.
+pub(crate) const DURATION_UNITS: &[(&str, f64)] = &[
+    ("s", 1f64),
+    ("sec", 1f64),
+    ("second", 1f64),
+    ("seconds", 1f64),
+    ("min", 60f64),
+    ("minute", 60f64),
+    ("minutes", 60f64),
+    ("h", 3600f64),
+    ("hour", 3600f64),
+    ("hours", 3600f64),
+    ("day", 86400f64),
+    ("days", 86400f64),
+    ("week", 604800f64),
+    ("weeks", 604800f64),
+];
+
+/// Get the amount of seconds that one of the given time unit is.
+/// Timestamps are also counted in seconds, in order to be able to add durations to them.
+pub(crate) fn seconds_per_unit(unit: &str) -> Option<f64> {
+    if unit == TIMESTAMP_UNIT {
+        return Some(1f64);
+    }
+
+    DURATION_UNITS
+        .iter()
+        .find(|(name, _)| *name == unit)
+        .map(|(_, seconds)| *seconds)
+}
+
+pub(crate) fn is_duration_unit(unit: &str) -> bool {
+    unit != TIMESTAMP_UNIT && seconds_per_unit(unit).is_some()
+}
+
+/// Format an amount of seconds as days, hours, minutes and seconds, eg. `4h 45min`.
+pub(crate) fn format_duration(seconds: f64) -> String {
+    // Round to milliseconds to avoid eg. 59.99999s because of floating point errors
+    let total_milliseconds = (seconds.abs() * 1000f64).round();
+    let mut remaining_seconds = (total_milliseconds / 1000f64).trunc();
+    let milliseconds = total_milliseconds % 1000f64;
+
+    let mut parts = Vec::new();
+    for (name, unit_seconds) in [("d", 86400f64), ("h", 3600f64), ("min", 60f64)] {
+        let amount = (remaining_seconds / unit_seconds).trunc();
+        remaining_seconds -= amount * unit_seconds;
+        if amount != 0f64 {
+            parts.push(format!("{}{}", amount, name));
+        }
+    }
+
+    if remaining_seconds != 0f64 || milliseconds != 0f64 || parts.is_empty() {
+        parts.push(format!(
+            "{}s",
+            crate::kalk_value::format_number(remaining_seconds + milliseconds / 1000f64)
+        ));
+    }
+
+    let sign = if seconds < 0f64 { "-" } else { "" };
+
+    format!("{}{}", sign, parts.join(" "))
+}
+
+/// Format an amount of seconds since 1970-01-01 00:00:00 UTC as a date and time.
+pub(crate) fn format_timestamp(seconds: f64) -> String {
+    let seconds = seconds.round() as i64;
+    let days = seconds.div_euclid(86400);
+    let seconds_of_day = seconds.rem_euclid(86400);
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60
+    )
+}
+
+/// Convert an amount of days since 1970-01-01 into a (year, month, day) date
+/// in the proleptic Gregorian calendar.
+/// Source: http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// Get the current time as the amount of seconds since 1970-01-01 00:00:00 UTC.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn now() -> Option<f64> {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()
+        .map(|duration| duration.as_secs_f64())
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn now() -> Option<f64> {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = Date, js_name = now)]
+        fn date_now() -> f64;
+    }
+
+    Some(date_now() / 1000f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(4.75 * 3600f64), "4h 45min");
+        assert_eq!(format_duration(90061.5), "1d 1h 1min 1.5s");
+        assert_eq!(format_duration(-90f64), "-1min 30s");
+        assert_eq!(format_duration(0f64), "0s");
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0f64), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(951827696f64), "2000-02-29 12:34:56 UTC");
+        assert_eq!(format_timestamp(-1f64), "1969-12-31 23:59:59 UTC");
+    }
+}
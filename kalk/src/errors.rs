@@ -5,6 +5,7 @@ use crate::lexer::TokenKind;
 pub enum KalkError {
     CannotIndexByImaginary,
     CanOnlyIndexX,
+    DivisionByZero,
     Expected(String),
     ExpectedDx,
     ExpectedIf,
@@ -15,22 +16,51 @@ pub enum KalkError {
     IncorrectAmountOfIndexes(usize, usize),
     ItemOfIndexDoesNotExist(Vec<usize>),
     InconsistentColumnWidths,
+    /// The form, eg. `0^0`, when indeterminate forms are errors.
+    IndeterminateForm(String),
+    Interrupted,
     InvalidComprehension(String),
     InvalidNumberLiteral(String),
     InvalidOperator,
+    /// The units of the operation, eg. `°C + °C`.
+    InvalidTemperatureOperation(String),
     InvalidUnit,
+    MaxRecursionDepthExceeded,
+    /// How the function was called, eg. `sqrt(-1)`, when it isn't a real number.
+    NotReal(String),
+    NothingToResume,
+    NothingToStore,
+    /// How the function was called, eg. `log(0)`, when it is undefined.
+    OutsideDomain(String),
+    Overflow,
     TimedOut,
     VariableReferencesItself,
     PiecewiseConditionsAreFalse,
+    Pole(String),
+    /// The message of the middleware that rejected the statement or result.
+    Rejected(String),
+    SingularMatrix,
+    /// The operator or function that needed more values than there were on the stack.
+    StackUnderflow(String),
+    EvaluationBudgetExceeded,
     EvaluationError(String),
+    /// The name of the register that nothing has been stored in.
+    EmptyRegister(String),
     UnexpectedToken(TokenKind, Option<TokenKind>),
     UnexpectedType(String, Vec<String>),
-    UndefinedFn(String),
-    UndefinedVar(String),
+    /// The name of the function, and similar names that have been defined.
+    UndefinedFn(String, Vec<String>),
+    /// The name of the variable, and similar names that have been defined.
+    UndefinedVar(String, Vec<String>),
+    UnableToDifferentiate(String),
     UnableToInvert(String),
     UnableToSolveEquation,
+    Underflow,
     UnableToOverrideConstant(String),
+    /// The name of the built-in function that a declaration would replace.
+    UnableToOverrideFunction(String),
     UnableToParseExpression,
+    UnitDropped(String),
     UnrecognizedBase,
     Unknown,
     WasStmt(crate::ast::Stmt),
@@ -41,15 +71,20 @@ impl ToString for KalkError {
         match self {
             KalkError::CannotIndexByImaginary => String::from("Cannot index by imaginary numbers."),
             KalkError::CanOnlyIndexX => String::from("Indexing (getting an item with a specific index) is only possible on vectors and matrices."),
+            KalkError::DivisionByZero => String::from("Division by zero."),
             KalkError::Expected(description) => format!("Expected: {}", description),
             KalkError::ExpectedDx => String::from("Expected eg. dx, to specify for which variable the operation is being done to. Example with integration: ∫(0, 1, x dx) or ∫(0, 1, x, dx). You may need to put parenthesis around the expression before dx/dy/du/etc."),
             KalkError::ExpectedIf => String::from("Expected 'if', with a condition after it."),
             KalkError::ExpectedReal => String::from("Expected a real value but got imaginary."),
             KalkError::IncompatibleTypesForOperation(operation, got1, got2) => format!("Incompatible types for operation '{}': {} and {}.", operation, got1, got2),
             KalkError::IncompatibleVectorsMatrixes => String::from("Incompatible vectors/matrixes."),
+            KalkError::IndeterminateForm(form) => format!("Indeterminate form: {}. The result has no single value.", form),
             KalkError::IncorrectAmountOfArguments(expected, func, got) => format!(
-                "Expected {} arguments for function {}, but got {}.",
-                expected, func, got
+                "{} expects {} argument{}, got {}.",
+                func,
+                expected,
+                if *expected == 1 { "" } else { "s" },
+                got
             ),
             KalkError::IncorrectAmountOfIndexes(expected,  got) => format!(
                 "Expected {} indexes but got {}.",
@@ -57,14 +92,28 @@ impl ToString for KalkError {
             ),
             KalkError::ItemOfIndexDoesNotExist(indices) => format!("Item of index ⟦{}⟧ does not exist.", indices.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", ")),
             KalkError::InconsistentColumnWidths => String::from("Inconsistent column widths. Matrix columns must be the same size."),
+            KalkError::Interrupted => String::from("The calculation was interrupted."),
             KalkError::InvalidComprehension(x) => format!("Invalid comprehension: {}", x),
             KalkError::InvalidNumberLiteral(x) => format!("Invalid number literal: '{}'.", x),
             KalkError::InvalidOperator => String::from("Invalid operator."),
+            KalkError::InvalidTemperatureOperation(operation) => format!("Unable to calculate {}. Absolute temperatures can only be subtracted from each other, and a difference (eg. in Δ°C) can be added to them. A temperature can be turned into a difference with eg. `to Δ°C`.", operation),
             KalkError::InvalidUnit => String::from("Invalid unit."),
+            KalkError::MaxRecursionDepthExceeded => String::from("Max recursion depth exceeded."),
+            KalkError::NotReal(expr) => format!("Domain error: {} is not a real number.", expr),
+            KalkError::NothingToResume => String::from("There is no interrupted calculation to resume."),
+            KalkError::NothingToStore => String::from("There is no result to store."),
+            KalkError::OutsideDomain(expr) => format!("Domain error: {} is undefined.", expr),
+            KalkError::Overflow => String::from("Overflow. The result is too large to be represented."),
             KalkError::TimedOut => String::from("Operation took too long."),
             KalkError::VariableReferencesItself => String::from("Variable references itself."),
             KalkError::PiecewiseConditionsAreFalse => String::from("All the conditions in the piecewise are false."),
+            KalkError::Rejected(message) => format!("Rejected: {}", message),
+            KalkError::StackUnderflow(name) => format!("Not enough values on the stack for '{}'.", name),
+            KalkError::Pole(expr) => format!("Domain error: {} is undefined, since the gamma function has a pole there.", expr),
+            KalkError::SingularMatrix => String::from("The matrix is singular, so the system doesn't have a unique solution."),
+            KalkError::EvaluationBudgetExceeded => String::from("Evaluation budget exceeded. The expression is too large to evaluate."),
             KalkError::EvaluationError(msg) => format!("Evaluation error: {}", msg),
+            KalkError::EmptyRegister(name) => format!("Nothing has been stored in the register '{}'.", name),
             KalkError::UnexpectedToken(got, expected) => {
                 if let Some(expected) = expected {
                     format!("Unexpected token: '{:?}', expected '{:?}'.", got, expected)
@@ -75,14 +124,32 @@ impl ToString for KalkError {
             KalkError::UnexpectedType(got, expected) => {
                 format!("Unexpected type. Got {:?} but expected: {:?}.", got, expected.join(", "))
             }
+            KalkError::UnableToDifferentiate(msg) => format!("Unable to differentiate: {}", msg),
             KalkError::UnableToInvert(msg) => format!("Unable to invert: {}", msg),
-            KalkError::UndefinedFn(name) => format!("Undefined function: '{}'.", name),
-            KalkError::UndefinedVar(name) => format!("Undefined variable: '{}'.", name),
+            KalkError::UndefinedFn(name, suggestions) => format!("Undefined function: '{}'.{}", name, suggestions_to_string(suggestions)),
+            KalkError::UndefinedVar(name, suggestions) => format!("Undefined variable: '{}'.{}", name, suggestions_to_string(suggestions)),
             KalkError::UnableToParseExpression => String::from("Unable to parse expression."),
             KalkError::UnableToSolveEquation => String::from("Unable to solve equation."),
+            KalkError::Underflow => String::from("Underflow. The result is too small to be represented, and would be rounded to zero."),
             KalkError::UnableToOverrideConstant(name) => format!("Unable to override constant: '{}'.", name),
+            KalkError::UnableToOverrideFunction(name) => format!("Unable to override built-in function: '{}'.", name),
+            KalkError::UnitDropped(unit) => format!("The unit '{}' would be lost in this operation. Use the same unit (or compatible units) on both sides.", unit),
             KalkError::UnrecognizedBase => String::from("Unrecognized base."),
             KalkError::Unknown | KalkError::WasStmt(_) => String::from("Unknown error."),
         }
     }
 }
+
+/// Eg. " Did you mean 'sin', 'sinh' or 'sign'?", or nothing if there are no suggestions.
+fn suggestions_to_string(suggestions: &[String]) -> String {
+    let quoted: Vec<String> = suggestions
+        .iter()
+        .map(|suggestion| format!("'{}'", suggestion))
+        .collect();
+
+    match quoted.split_last() {
+        None => String::new(),
+        Some((last, [])) => format!(" Did you mean {}?", last),
+        Some((last, rest)) => format!(" Did you mean {} or {}?", rest.join(", "), last),
+    }
+}
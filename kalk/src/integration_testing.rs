@@ -41,22 +41,47 @@ mod tests {
         }
     }
 
+    #[test_case("aliases")]
     #[test_case("ambiguities/comparison_in_function")]
+    #[test_case("angle_units")]
+    #[test_case("approximations")]
     #[test_case("basics")]
     #[test_case("comparisons")]
+    #[test_case("compound_assignment")]
+    #[test_case("compound_units")]
+    #[test_case("conditionals")]
+    #[test_case("data_sizes")]
     #[test_case("comprehensions")]
     #[test_case("equations")]
     #[test_case("derivation")]
+    #[test_case("destructuring")]
     #[test_case("functions")]
     #[test_case("groups")]
+    #[test_case("integers")]
     #[test_case("integration")]
+    #[test_case("let")]
+    #[test_case("limits")]
+    #[test_case("logic")]
+    #[test_case("matrices/inverse")]
+    #[test_case("matrices/linsolve")]
     #[test_case("matrices/operations")]
     #[test_case("matrices/transpose")]
+    #[test_case("modulo")]
+    #[test_case("multiline")]
+    #[test_case("polynomials")]
+    #[test_case("prefixes")]
     #[test_case("radix")]
     #[test_case("recursion")]
     #[test_case("redefining")]
+    #[test_case("root_finding")]
+    #[test_case("rounding")]
+    #[test_case("strings")]
     #[test_case("sum")]
+    #[test_case("symbolic_derivation")]
+    #[test_case("temperatures")]
+    #[test_case("unicode")]
     #[test_case("variables")]
+    #[test_case("vector_functions")]
     #[test_case("vectors")]
     fn test_file(name: &str) {
         assert!(is_true(eval_file(name)));
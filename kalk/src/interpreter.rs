@@ -1,13 +1,17 @@
-use crate::ast::{Expr, Stmt};
+use crate::ast::{Expr, ExprSpan, Stmt};
 use crate::ast::{Identifier, RangedVar};
 use crate::calculation_result::CalculationResult;
 use crate::errors::KalkError;
-use crate::kalk_value::KalkValue;
-use crate::lexer::TokenKind;
-use crate::parser::DECL_UNIT;
+use crate::kalk_value::{KalkValue, VectorBuilder};
+use crate::lexer::{Span, TokenKind};
+use crate::parser::{DomainPolicy, IndeterminatePolicy, OverflowPolicy, ProgrammerMode, DECL_UNIT};
+use crate::random::Rng;
+use crate::steps::{format_value, Step, StepKind, MAX_STEPS};
 use crate::symbol_table::SymbolTable;
-use crate::{as_number_or_zero, numerical};
-use crate::{float, prelude};
+use crate::{analysis, as_number_or_zero, duration, inverter, numerical, radix, temperature};
+use crate::{float, prelude, primitive};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 pub struct Context<'a> {
     pub symbol_table: &'a mut SymbolTable,
@@ -20,6 +24,49 @@ pub struct Context<'a> {
     #[cfg(not(target_arch = "wasm32"))]
     start_time: std::time::SystemTime,
     is_approximation: bool,
+    recursion_depth: u32,
+    max_recursion_depth: u32,
+    evaluation_budget: Option<u64>,
+    evaluated_nodes: u64,
+    overflow_policy: OverflowPolicy,
+    indeterminate_policy: IndeterminatePolicy,
+    domain_policy: DomainPolicy,
+    /// True if a result was too large or too small and replaced with infinity or zero.
+    is_out_of_range: bool,
+    /// What the first operation with values outside of its domain gave, eg. `1 / 0` gave `∞`.
+    domain_note: Option<String>,
+    /// Why the first undefined value became undefined, eg. `0 / 0 is undefined`.
+    undefined_reason: Option<String>,
+    strict_units: bool,
+    programmer_mode: Option<ProgrammerMode>,
+    /// A unit that was lost in an operation, eg. `km` in `2 + 3 km`.
+    dropped_unit: Option<String>,
+    /// The radix the result was converted to with eg. `to hex`.
+    display_radix: Option<u8>,
+    interrupt_flag: Option<Arc<AtomicBool>>,
+    /// The steps of the calculation, if tracing is enabled.
+    steps: Option<Vec<Step>>,
+    /// The amount of outermost `sum`/`prod` loops that have been started.
+    started_loops: usize,
+    resume_from: Option<LoopCheckpoint>,
+    checkpoint: Option<LoopCheckpoint>,
+    /// Created when a random number is needed, unless it was given with `set_rng`.
+    rng: Option<Rng>,
+    /// The innermost expression in the input that an error passed through, eg. `1/0`,
+    /// together with the error, to tell it apart from errors that were handled.
+    error_span: Option<(Span, KalkError)>,
+}
+
+/// The state of an interrupted `sum`/`prod` loop, used to continue it later.
+/// Only loops that aren't inside other loops are checkpointed.
+#[derive(Debug, Clone)]
+pub(crate) struct LoopCheckpoint {
+    /// The position of the loop among the outermost loops of the input, starting from 0.
+    pub(crate) loop_index: usize,
+    /// The value of the loop variable in the iteration that was interrupted.
+    pub(crate) next_n: i128,
+    /// The sum or product of the iterations before `next_n`.
+    pub(crate) partial_result: KalkValue,
 }
 
 impl<'a> Context<'a> {
@@ -40,35 +87,207 @@ impl<'a> Context<'a> {
             #[cfg(not(target_arch = "wasm32"))]
             start_time: std::time::SystemTime::now(),
             is_approximation: false,
+            recursion_depth: 0,
+            max_recursion_depth: crate::parser::DEFAULT_MAX_RECURSION_DEPTH,
+            evaluation_budget: None,
+            evaluated_nodes: 0,
+            overflow_policy: OverflowPolicy::Infinity,
+            indeterminate_policy: IndeterminatePolicy::Conventional,
+            domain_policy: DomainPolicy::Complex,
+            is_out_of_range: false,
+            domain_note: None,
+            undefined_reason: None,
+            strict_units: false,
+            programmer_mode: None,
+            dropped_unit: None,
+            display_radix: None,
+            interrupt_flag: None,
+            steps: None,
+            started_loops: 0,
+            resume_from: None,
+            checkpoint: None,
+            rng: None,
+            error_span: None,
+        }
+    }
+
+    /// Set the maximum amount of nested user-defined function calls.
+    pub fn set_max_recursion_depth(mut self, max_recursion_depth: u32) -> Self {
+        self.max_recursion_depth = max_recursion_depth;
+
+        self
+    }
+
+    /// Set the maximum amount of expression nodes that may be evaluated.
+    pub fn set_evaluation_budget(mut self, evaluation_budget: Option<u64>) -> Self {
+        self.evaluation_budget = evaluation_budget;
+
+        self
+    }
+
+    pub fn set_overflow_policy(mut self, overflow_policy: OverflowPolicy) -> Self {
+        self.overflow_policy = overflow_policy;
+
+        self
+    }
+
+    pub fn set_indeterminate_policy(mut self, indeterminate_policy: IndeterminatePolicy) -> Self {
+        self.indeterminate_policy = indeterminate_policy;
+
+        self
+    }
+
+    pub fn set_domain_policy(mut self, domain_policy: DomainPolicy) -> Self {
+        self.domain_policy = domain_policy;
+
+        self
+    }
+
+    /// Give `KalkError::UnitDropped` instead of a warning when an operation loses a unit.
+    pub fn set_strict_units(mut self, strict_units: bool) -> Self {
+        self.strict_units = strict_units;
+
+        self
+    }
+
+    /// Truncate every value to an integer and wrap it around to the word size.
+    pub fn set_programmer_mode(mut self, programmer_mode: Option<ProgrammerMode>) -> Self {
+        self.programmer_mode = programmer_mode;
+
+        self
+    }
+
+    /// Also show the result in another radix, eg. 16 if the input contains `0xff`.
+    /// A conversion like `to bin` in the input takes precedence over this.
+    pub fn set_display_radix(mut self, radix: Option<u8>) -> Self {
+        self.display_radix = radix;
+
+        self
+    }
+
+    /// Called when an operation loses a unit, eg. when adding a number without
+    /// a unit to a number with a unit, or when the units can't be converted.
+    pub(crate) fn drop_unit(&mut self, unit: &str) -> Result<(), KalkError> {
+        if self.strict_units {
+            return Err(KalkError::UnitDropped(unit.to_string()));
+        }
+
+        self.dropped_unit.get_or_insert_with(|| unit.to_string());
+
+        Ok(())
+    }
+
+    /// Record the steps of the calculation in the result.
+    pub fn set_trace(mut self, trace: bool) -> Self {
+        self.steps = if trace { Some(Vec::new()) } else { None };
+
+        self
+    }
+
+    /// Stop the evaluation with `KalkError::Interrupted` when the flag is set.
+    /// The flag is reset when this happens.
+    pub fn set_interrupt_flag(mut self, interrupt_flag: Option<Arc<AtomicBool>>) -> Self {
+        self.interrupt_flag = interrupt_flag;
+
+        self
+    }
+
+    /// Continue an interrupted loop instead of starting it from the beginning,
+    /// when the same statements are interpreted again.
+    pub(crate) fn resume_from(mut self, checkpoint: Option<LoopCheckpoint>) -> Self {
+        self.resume_from = checkpoint;
+
+        self
+    }
+
+    /// Continue from the state of a random number generator, eg. one with a seed.
+    pub(crate) fn set_rng(mut self, rng: Option<Rng>) -> Self {
+        self.rng = rng;
+
+        self
+    }
+
+    /// Get the state of the random number generator, if any random numbers were generated.
+    pub(crate) fn take_rng(&mut self) -> Option<Rng> {
+        self.rng.take()
+    }
+
+    /// A context with the same settings that evaluates with another symbol table,
+    /// eg. a copy for another thread. It gets its own random number generator, seeded
+    /// from this one if it exists, so that the numbers are reproducible but not repeated.
+    pub(crate) fn with_symbol_table<'b>(
+        &mut self,
+        symbol_table: &'b mut SymbolTable,
+    ) -> Context<'b> {
+        let mut context = Context::new(
+            symbol_table,
+            &self.angle_unit,
+            #[cfg(feature = "rug")]
+            self.precision,
+            #[cfg(not(target_arch = "wasm32"))]
+            self.timeout,
+            #[cfg(target_arch = "wasm32")]
+            None,
+        )
+        .set_max_recursion_depth(self.max_recursion_depth)
+        .set_evaluation_budget(self.evaluation_budget)
+        .set_overflow_policy(self.overflow_policy)
+        .set_indeterminate_policy(self.indeterminate_policy)
+        .set_domain_policy(self.domain_policy)
+        .set_strict_units(self.strict_units)
+        .set_programmer_mode(self.programmer_mode)
+        .set_display_radix(self.display_radix)
+        .set_trace(self.steps.is_some())
+        .set_interrupt_flag(self.interrupt_flag.clone())
+        .set_rng(self.rng.as_mut().map(|rng| Rng::from_seed(rng.next_u64())));
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            context.start_time = self.start_time;
+        }
+
+        context
+    }
+
+    fn get_rng(&mut self) -> &mut Rng {
+        self.rng.get_or_insert_with(Rng::from_entropy)
+    }
+
+    /// Get the part of the input that the error came from, if it is known.
+    pub(crate) fn take_error_span(&mut self, err: &KalkError) -> Option<Span> {
+        match self.error_span.take() {
+            Some((span, span_err)) if &span_err == err => Some(span),
+            _ => None,
         }
     }
 
+    /// Get the state of the loop that was running when the evaluation was interrupted, if any.
+    pub(crate) fn take_checkpoint(&mut self) -> Option<LoopCheckpoint> {
+        self.checkpoint.take()
+    }
+
     pub fn interpret(
         &mut self,
         statements: Vec<Stmt>,
     ) -> Result<Option<CalculationResult>, KalkError> {
         for (i, stmt) in statements.iter().enumerate() {
             let num = eval_stmt(self, stmt)?;
-
-            // Insert the last value into the `ans` variable.
-            self.symbol_table.set(if num.has_unit() {
-                Stmt::VarDecl(
-                    Identifier::from_full_name("ans"),
-                    Box::new(Expr::Unit(
-                        num.get_unit().unwrap().to_string(),
-                        Box::new(crate::ast::build_literal_ast(&num)),
-                    )),
-                )
-            } else {
-                Stmt::VarDecl(
-                    Identifier::from_full_name("ans"),
-                    Box::new(crate::ast::build_literal_ast(&num)),
-                )
-            });
+            remember_result(self.symbol_table, &num, matches!(stmt, Stmt::Expr(_)));
 
             if i == statements.len() - 1 {
                 if let Stmt::Expr(_) = stmt {
-                    return Ok(Some(CalculationResult::new(num, 10, self.is_approximation)));
+                    return Ok(Some(
+                        CalculationResult::new(
+                            num,
+                            self.display_radix.unwrap_or(10),
+                            self.is_approximation,
+                        )
+                        .set_out_of_range(self.is_out_of_range)
+                        .set_domain_note(self.domain_note.take())
+                        .set_undefined_reason(self.undefined_reason.take())
+                        .set_dropped_unit(self.dropped_unit.take())
+                        .set_programmer_mode(self.programmer_mode)
+                        .set_steps(self.steps.take().unwrap_or_default()),
+                    ));
                 }
             }
         }
@@ -77,6 +296,44 @@ impl<'a> Context<'a> {
     }
 }
 
+/// Insert the value into the `ans` variable, and into the results (`_`)
+/// if it is the value of an expression.
+pub(crate) fn remember_result(symbol_table: &mut SymbolTable, num: &KalkValue, is_expr: bool) {
+    let literal = build_value_ast(num);
+    if is_expr {
+        symbol_table.push_result(literal.clone());
+    }
+
+    symbol_table.set(Stmt::VarDecl(
+        Identifier::from_full_name("ans"),
+        Box::new(literal),
+    ));
+}
+
+/// An expression with the value, including its unit.
+fn build_value_ast(num: &KalkValue) -> Expr {
+    if num.has_unit() {
+        Expr::Unit(
+            num.get_unit().unwrap().to_string(),
+            Box::new(crate::ast::build_literal_ast(num)),
+        )
+    } else {
+        crate::ast::build_literal_ast(num)
+    }
+}
+
+fn record_step(context: &mut Context, kind: StepKind, input: String, output: String) {
+    if let Some(steps) = &mut context.steps {
+        if steps.len() < MAX_STEPS {
+            steps.push(Step {
+                kind,
+                input,
+                output,
+            });
+        }
+    }
+}
+
 struct SumVar {
     name: String,
     value: i128,
@@ -85,6 +342,9 @@ struct SumVar {
 fn eval_stmt(context: &mut Context, stmt: &Stmt) -> Result<KalkValue, KalkError> {
     match stmt {
         Stmt::VarDecl(_, _) => eval_var_decl_stmt(context, stmt),
+        Stmt::MultiVarDecl(identifiers, value) => {
+            eval_multi_var_decl_stmt(context, identifiers, value)
+        }
         Stmt::FnDecl(_, _, _) => eval_fn_decl_stmt(),
         Stmt::UnitDecl(_, _, _) => eval_unit_decl_stmt(),
         Stmt::Expr(expr) => eval_expr_stmt(context, expr),
@@ -92,10 +352,60 @@ fn eval_stmt(context: &mut Context, stmt: &Stmt) -> Result<KalkValue, KalkError>
 }
 
 fn eval_var_decl_stmt(context: &mut Context, stmt: &Stmt) -> Result<KalkValue, KalkError> {
+    // A compound assignment, eg. `x += 2`, refers to the variable itself. It is evaluated
+    // right away, so that the value doesn't grow with every assignment.
+    if let Stmt::VarDecl(identifier, value) = stmt {
+        // A constant is evaluated once to make sure that the declaration works,
+        // before it becomes a constant
+        if context
+            .symbol_table
+            .is_pending_constant(&identifier.full_name)
+        {
+            eval_expr(context, value, None)?;
+            context.symbol_table.insert(stmt.clone());
+            context.symbol_table.confirm_constant(&identifier.full_name);
+
+            return Ok(KalkValue::from(1));
+        }
+
+        if inverter::contains_var(context.symbol_table, value, &identifier.full_name) {
+            let num = eval_expr(context, value, None)?;
+            context.symbol_table.insert(Stmt::VarDecl(
+                identifier.clone(),
+                Box::new(build_value_ast(&num)),
+            ));
+
+            return Ok(KalkValue::from(1));
+        }
+    }
+
     context.symbol_table.insert(stmt.clone());
     Ok(KalkValue::from(1))
 }
 
+/// The value is evaluated once to make sure that it has an element for every variable.
+fn eval_multi_var_decl_stmt(
+    context: &mut Context,
+    identifiers: &[Identifier],
+    value: &Expr,
+) -> Result<KalkValue, KalkError> {
+    match eval_expr(context, value, None)? {
+        KalkValue::Vector(values) if values.len() == identifiers.len() => (),
+        _ => {
+            return Err(KalkError::Expected(format!(
+                "a vector with {} elements, one for each variable",
+                identifiers.len()
+            )))
+        }
+    }
+
+    for var_decl in analysis::multi_var_decl_elements(identifiers, value) {
+        context.symbol_table.insert(var_decl);
+    }
+
+    Ok(KalkValue::from(1))
+}
+
 fn eval_fn_decl_stmt() -> Result<KalkValue, KalkError> {
     Ok(KalkValue::from(1)) // Nothing needs to happen here, since the parser will already have added the FnDecl's to the symbol table.
 }
@@ -108,11 +418,37 @@ fn eval_expr_stmt(context: &mut Context, expr: &Expr) -> Result<KalkValue, KalkE
     eval_expr(context, expr, None)
 }
 
+/// Evaluate the expression. Sparse vectors become regular vectors, since most operations
+/// don't support them, see `eval_expr_sparse`.
+#[inline]
 pub(crate) fn eval_expr(
     context: &mut Context,
     expr: &Expr,
     unit: Option<&String>,
 ) -> Result<KalkValue, KalkError> {
+    Ok(eval_expr_sparse(context, expr, unit)?.into_dense())
+}
+
+/// Same as `eval_expr`, but sparse vectors are kept as they are. This is used where the
+/// value is given to an operation that supports them, eg. an addition or an indexer.
+fn eval_expr_sparse(
+    context: &mut Context,
+    expr: &Expr,
+    unit: Option<&String>,
+) -> Result<KalkValue, KalkError> {
+    context.evaluated_nodes += 1;
+    if let Some(evaluation_budget) = context.evaluation_budget {
+        if context.evaluated_nodes > evaluation_budget {
+            return Err(KalkError::EvaluationBudgetExceeded);
+        }
+    }
+
+    if let Some(interrupt_flag) = &context.interrupt_flag {
+        if interrupt_flag.swap(false, Ordering::Relaxed) {
+            return Err(KalkError::Interrupted);
+        }
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     if let (Ok(elapsed), Some(timeout)) = (context.start_time.elapsed(), context.timeout) {
         if elapsed.as_millis() >= timeout {
@@ -120,13 +456,15 @@ pub(crate) fn eval_expr(
         }
     }
 
-    match expr {
-        Expr::Binary(left, op, right) => eval_binary_expr(context, left, op, right, unit),
-        Expr::Unary(op, expr) => eval_unary_expr(context, op, expr, unit),
+    let result = match expr {
+        Expr::Binary(left, op, right, _) => eval_binary_expr(context, left, op, right, unit),
+        Expr::Unary(op, expr, _) => eval_unary_expr(context, op, expr, unit),
         Expr::Unit(identifier, expr) => eval_unit_expr(context, identifier, expr),
         Expr::Var(identifier) => eval_var_expr(context, identifier, unit),
-        Expr::Literal(value) => eval_literal_expr(context, *value, unit),
+        Expr::Literal(value, _) => eval_literal_expr(context, *value, unit),
+        Expr::Integer(value) => Ok(KalkValue::Integer(value.clone()).with_unit(unit.cloned())),
         Expr::Boolean(value) => Ok(KalkValue::Boolean(*value)),
+        Expr::String(value) => eval_string_expr(value),
         Expr::Group(expr) => eval_group_expr(context, expr, unit),
         Expr::FnCall(identifier, expressions) => {
             eval_fn_call_expr(context, identifier, expressions, unit)
@@ -135,10 +473,85 @@ pub(crate) fn eval_expr(
         Expr::Vector(values) => eval_vector(context, values),
         Expr::Matrix(rows) => eval_matrix(context, rows),
         Expr::Indexer(var, indexes) => eval_indexer(context, var, indexes, unit),
-        Expr::Comprehension(left, conditions, vars) => Ok(KalkValue::Vector(eval_comprehension(
-            context, left, conditions, vars,
-        )?)),
+        Expr::Comprehension(left, conditions, vars) => {
+            let mut builder = VectorBuilder::new();
+            eval_comprehension(context, left, conditions, vars, &mut builder)?;
+
+            Ok(builder.build())
+        }
         Expr::Equation(left, right, identifier) => eval_equation(context, left, right, identifier),
+        Expr::Let(identifier, value, body) => eval_let(context, identifier, value, body, unit),
+        Expr::WithAngleUnit(angle_unit, expr) => {
+            eval_with_angle_unit(context, angle_unit, expr, unit)
+        }
+        Expr::If(condition, then_branch, else_branch) => {
+            eval_if(context, condition, then_branch, else_branch, unit)
+        }
+    };
+
+    if let (Err(err), Some(span)) = (&result, expr.span()) {
+        record_error_span(context, span, err);
+    }
+
+    match (context.programmer_mode, result) {
+        (Some(mode), Ok(value)) => Ok(mode.wrap_value(value)),
+        (_, result) => result,
+    }
+}
+
+/// Operations that keep sparse vectors sparse, eg. `v + w` or `2v`, or None if the operation
+/// needs regular vectors. Only finite numbers without units are used for scaling,
+/// since eg. `0 * ∞` isn't zero.
+fn eval_sparse_binary(
+    context: &mut Context,
+    left: &KalkValue,
+    op: &TokenKind,
+    right: &KalkValue,
+) -> Option<Result<KalkValue, KalkError>> {
+    let is_scalar = |value: &KalkValue| {
+        matches!(value, KalkValue::Number(..) | KalkValue::Integer(_))
+            && !value.has_unit()
+            && value.to_f64().is_finite()
+            && value.imaginary_to_f64().is_finite()
+    };
+    let result = match (left, op, right) {
+        (
+            KalkValue::SparseVector(x),
+            TokenKind::Plus | TokenKind::Minus,
+            KalkValue::SparseVector(y),
+        ) => {
+            if x.len() != y.len() {
+                return Some(Err(KalkError::IncompatibleVectorsMatrixes));
+            }
+
+            x.clone().zip(y.clone(), |a, b| match op {
+                TokenKind::Plus => a.add(context, b),
+                _ => a.sub(context, b),
+            })
+        }
+        (KalkValue::SparseVector(x), TokenKind::Star, y) if is_scalar(y) => {
+            x.clone().map(|a| a.mul(context, y.clone()))
+        }
+        (x, TokenKind::Star, KalkValue::SparseVector(y)) if is_scalar(x) => {
+            y.clone().map(|b| x.clone().mul(context, b))
+        }
+        (KalkValue::SparseVector(x), TokenKind::Slash, y)
+            if is_scalar(y) && (y.to_f64() != 0f64 || y.has_imaginary()) =>
+        {
+            x.clone().map(|a| a.div(context, y.clone()))
+        }
+        _ => return None,
+    };
+
+    Some(result.map(KalkValue::SparseVector))
+}
+
+/// Remember the span as where the error came from, unless the error already
+/// passed through an expression inside it.
+fn record_error_span(context: &mut Context, span: Span, err: &KalkError) {
+    match &context.error_span {
+        Some((_, recorded_err)) if recorded_err == err => (),
+        _ => context.error_span = Some((span, err.clone())),
     }
 }
 
@@ -153,35 +566,93 @@ fn eval_binary_expr(
         // TODO: When the unit conversion function takes a Float instead of Expr,
         // move this to the match statement further down.
         if let Expr::Var(right_unit) = right_expr {
-            let left_unit = eval_expr(context, left_expr, None)?.get_unit().cloned();
-            return convert_unit(
-                context,
-                left_expr,
-                left_unit.as_ref(),
-                Some(&right_unit.full_name),
-            );
+            let to_unit = &right_unit.full_name;
+            if let Some(radix) = radix::radix_by_name(to_unit)
+                .filter(|_| !context.symbol_table.contains_unit(to_unit))
+            {
+                context.display_radix = Some(radix);
+
+                return eval_expr(context, left_expr, unit);
+            }
+
+            let left = eval_expr(context, left_expr, None)?;
+            if let KalkValue::Vector(_) | KalkValue::Matrix(_) = left {
+                return convert_elements(context, left, to_unit);
+            }
+
+            let left_unit = left
+                .get_unit()
+                .cloned()
+                .or_else(|| angle_unit_of_result(context, left_expr));
+            return convert_unit(context, left_expr, left_unit.as_ref(), Some(to_unit));
             // TODO: Avoid evaluating this twice.
         }
     }
 
-    let left = eval_expr(context, left_expr, None)?;
-    let mut right = eval_expr(context, right_expr, None)?;
-    if let Expr::Unary(TokenKind::Percent, _) = right_expr {
-        right = right.mul(context, left.clone())?;
+    let mut left = eval_expr_sparse(context, left_expr, None)?;
+
+    // The right side is only evaluated when it's needed, eg. in `x != 0 and 1/x > 2`
+    if let (TokenKind::And, KalkValue::Boolean(false)) | (TokenKind::Or, KalkValue::Boolean(true)) =
+        (op, &left)
+    {
+        return Ok(left);
+    }
+
+    let mut right = eval_expr_sparse(context, right_expr, None)?;
+    if matches!(left, KalkValue::SparseVector(_)) || matches!(right, KalkValue::SparseVector(_)) {
+        if !matches!(right_expr, Expr::Unary(TokenKind::Percent, _, _)) {
+            if let Some(result) = eval_sparse_binary(context, &left, op, &right) {
+                return result;
+            }
+        }
+
+        left = left.into_dense();
+        right = right.into_dense();
+    }
+    if let Expr::Unary(TokenKind::Percent, _, _) = right_expr {
+        right = left.clone().mul(context, right)?;
         if let TokenKind::Star = op {
             return Ok(right);
         }
     }
 
+    if let Some(form) = indeterminate_form(op, &left, &right) {
+        let unit = left.get_unit().or_else(|| right.get_unit()).cloned();
+        let value = match context.indeterminate_policy {
+            IndeterminatePolicy::Error => return Err(KalkError::IndeterminateForm(form)),
+            IndeterminatePolicy::Conventional if *op == TokenKind::Power => 1f64,
+            _ => {
+                set_undefined_reason(context, || format!("{} is an indeterminate form", form));
+
+                f64::NAN
+            }
+        };
+
+        return Ok(KalkValue::Number(float!(value), float!(0f64), unit));
+    }
+
+    let operands = Operands::of(&left, Some(&right));
+    let operation = context.steps.is_some().then(|| {
+        format!(
+            "{} {} {}",
+            format_value(&left),
+            crate::display::operator_to_string(op),
+            format_value(&right)
+        )
+    });
     let result = match op {
         TokenKind::Plus => left.add(context, right),
         TokenKind::Minus => left.sub(context, right),
         TokenKind::Star => left.mul(context, right),
         TokenKind::Slash => left.div(context, right),
-        TokenKind::Percent => left.rem(context, right),
+        TokenKind::Percent | TokenKind::Rem => left.rem(context, right),
+        TokenKind::Mod => left.rem_euclid(context, right),
+        TokenKind::Div => left.div_euclid(context, right),
+        TokenKind::Quot => left.quot(context, right),
         TokenKind::Power => left.pow(context, right),
         TokenKind::Equals => left.eq(context, right),
         TokenKind::NotEquals => left.not_eq(context, right),
+        TokenKind::ApproxEquals => left.approx_eq(context, right),
         TokenKind::GreaterThan => left.greater_than(context, right),
         TokenKind::LessThan => left.less_than(context, right),
         TokenKind::GreaterOrEquals => left.greater_or_equals(context, right),
@@ -190,9 +661,19 @@ fn eval_binary_expr(
         TokenKind::Or => left.or(&right),
         _ => Ok(KalkValue::from(1f64)),
     }?;
+    let issue = domain_issue_of_operation(op, &operands, &result);
+    check_range(context, op, operands, &result)?;
+    let result = match issue {
+        Some((issue, description)) => check_domain(context, issue, description, result)?,
+        None => result,
+    };
+    if let Some(operation) = operation {
+        let output = format_value(&result);
+        record_step(context, StepKind::Operation, operation, output);
+    }
 
     if unit.is_some() {
-        if let KalkValue::Number(real, imaginary, _) = result {
+        if let KalkValue::Number(real, imaginary, _) = result.clone().into_number() {
             return Ok(KalkValue::Number(real, imaginary, unit.cloned()));
         }
     };
@@ -207,221 +688,1231 @@ fn eval_unary_expr(
     unit: Option<&String>,
 ) -> Result<KalkValue, KalkError> {
     let num = eval_expr(context, expr, unit)?;
+    let operation = context.steps.is_some().then(|| match op {
+        TokenKind::Not => format!("not {}", format_value(&num)),
+        TokenKind::Percent => format!("{}%", format_value(&num)),
+        _ => format!("{}!", format_value(&num)),
+    });
 
-    match op {
-        TokenKind::Minus => num.mul(context, KalkValue::from(-1f64)),
+    let result = match op {
+        TokenKind::Minus => return num.mul(context, KalkValue::from(-1f64)),
         TokenKind::Not => match num {
             KalkValue::Boolean(boolean) => Ok(KalkValue::Boolean(!boolean)),
             _ => Err(KalkError::InvalidOperator),
         },
         TokenKind::Percent => num.mul(context, KalkValue::from(0.01f64)),
-        TokenKind::Exclamation => prelude::special_funcs::factorial(num),
+        TokenKind::Exclamation => {
+            let operands = Operands::of(&num, None);
+            let result = prelude::special_funcs::factorial(num)?;
+            check_range(context, op, operands, &result)?;
+
+            Ok(result)
+        }
         _ => Err(KalkError::InvalidOperator),
+    }?;
+
+    if let Some(operation) = operation {
+        let output = format_value(&result);
+        record_step(context, StepKind::Operation, operation, output);
     }
+
+    Ok(result)
 }
 
-fn eval_unit_expr(
-    context: &mut Context,
-    identifier: &str,
-    expr: &Expr,
-) -> Result<KalkValue, KalkError> {
-    let angle_unit = &context.angle_unit.clone();
-    if (identifier == "rad" || identifier == "deg") && angle_unit != identifier {
-        return convert_unit(
-            context,
-            expr,
-            Some(&identifier.to_string()),
-            Some(angle_unit),
-        );
+/// Find out if the operands of an operator form an indeterminate form, eg. `0^0`,
+/// and if so, return how it's written. Only real numbers are checked.
+fn indeterminate_form(op: &TokenKind, left: &KalkValue, right: &KalkValue) -> Option<String> {
+    let (left, right) = match (left, right) {
+        (
+            KalkValue::Number(left, left_imaginary, _),
+            KalkValue::Number(right, right_imaginary, _),
+        ) if *left_imaginary == 0f64 && *right_imaginary == 0f64 => {
+            (primitive!(left), primitive!(right))
+        }
+        _ => return None,
+    };
+
+    let form = match op {
+        TokenKind::Power if left == 0f64 && right == 0f64 => "0^0",
+        TokenKind::Power if left.is_infinite() && right == 0f64 => "∞^0",
+        TokenKind::Power if left == 1f64 && right.is_infinite() => "1^∞",
+        TokenKind::Star
+            if (left == 0f64 && right.is_infinite()) || (left.is_infinite() && right == 0f64) =>
+        {
+            "0·∞"
+        }
+        TokenKind::Plus if left.is_infinite() && right == -left => "∞ - ∞",
+        TokenKind::Minus if left.is_infinite() && right == left => "∞ - ∞",
+        _ => return None,
+    };
+
+    Some(form.to_string())
+}
+
+/// Properties of the operands of an operation, used to check the range of its result.
+struct Operands {
+    are_finite: bool,
+    left_is_zero: bool,
+    right_is_zero: bool,
+    /// The values of the operands, if they are all real numbers.
+    real_values: Option<(f64, Option<f64>)>,
+}
+
+impl Operands {
+    fn of(left: &KalkValue, right: Option<&KalkValue>) -> Self {
+        let real_values = match (real_value(left), right.map(real_value)) {
+            (Some(left), None) => Some((left, None)),
+            (Some(left), Some(Some(right))) => Some((left, Some(right))),
+            _ => None,
+        };
+
+        Operands {
+            are_finite: is_finite(left) && right.is_none_or(is_finite),
+            left_is_zero: is_zero(left),
+            right_is_zero: right.is_some_and(is_zero),
+            real_values,
+        }
     }
+}
 
-    eval_expr(context, expr, Some(&identifier.to_string()))
+/// The value of a real number, or None if it is complex or not a number.
+fn real_value(value: &KalkValue) -> Option<f64> {
+    match value {
+        KalkValue::Number(real, imaginary, _) if *imaginary == 0f64 && !real.is_nan() => {
+            Some(primitive!(real))
+        }
+        KalkValue::Integer(_) => Some(value.to_f64()),
+        _ => None,
+    }
 }
 
-pub fn convert_unit(
+fn is_finite(value: &KalkValue) -> bool {
+    match value {
+        KalkValue::Number(real, imaginary, _) => real.is_finite() && imaginary.is_finite(),
+        KalkValue::Integer(_) => true,
+        _ => false,
+    }
+}
+
+fn is_zero(value: &KalkValue) -> bool {
+    match value {
+        KalkValue::Number(real, imaginary, _) => *real == 0f64 && *imaginary == 0f64,
+        _ => false,
+    }
+}
+
+/// Check if an operation on finite numbers gave a result that is outside of the range
+/// of numbers that can be represented, ie. infinity, or zero when multiplying,
+/// dividing or raising non-zero numbers. Division by zero is not counted as an overflow.
+fn check_range(
     context: &mut Context,
-    expr: &Expr,
-    from_unit: Option<&String>,
-    to_unit: Option<&String>,
-) -> Result<KalkValue, KalkError> {
-    let (from_unit, to_unit) = if let (Some(from_unit), Some(to_unit)) = (from_unit, to_unit) {
-        (from_unit, to_unit)
-    } else {
-        return Err(KalkError::InvalidUnit);
-    };
+    op: &TokenKind,
+    operands: Operands,
+    result: &KalkValue,
+) -> Result<(), KalkError> {
+    if !operands.are_finite || !matches!(result, KalkValue::Number(_, _, _)) {
+        return Ok(());
+    }
 
-    if let Some(Stmt::UnitDecl(_, _, unit_def)) =
-        context.symbol_table.get_unit(to_unit, from_unit).cloned()
-    {
-        context.symbol_table.insert(Stmt::VarDecl(
-            Identifier::from_full_name(DECL_UNIT),
-            Box::new(expr.clone()),
-        ));
+    let is_division_by_zero =
+        matches!(op, TokenKind::Slash | TokenKind::Div | TokenKind::Quot) && operands.right_is_zero;
+    let is_overflow = !is_finite(result) && !result.is_nan() && !is_division_by_zero;
+    let can_underflow = matches!(op, TokenKind::Star | TokenKind::Slash | TokenKind::Power);
+    let is_underflow = can_underflow
+        && is_zero(result)
+        && !operands.left_is_zero
+        && (*op != TokenKind::Star || !operands.right_is_zero);
+    apply_overflow_policy(context, is_overflow, is_underflow)
+}
 
-        let (real, imaginary, _) = as_number_or_zero!(eval_expr(context, &unit_def, None)?);
-        Ok(KalkValue::Number(real, imaginary, Some(to_unit.clone())))
-    } else {
-        Err(KalkError::InvalidUnit)
+/// Check if a function gave an infinite result for finite real arguments, eg. `exp(1000)`,
+/// where it doesn't have a pole. Zero results aren't counted, since eg. `sin(0)` is exactly zero.
+fn check_fn_range(
+    context: &mut Context,
+    name: &str,
+    args: &[Option<f64>],
+    result: &KalkValue,
+) -> Result<(), KalkError> {
+    let are_finite = args.iter().all(|arg| arg.is_some_and(f64::is_finite));
+    if !are_finite || !matches!(result, KalkValue::Number(_, _, _)) {
+        return Ok(());
     }
+
+    let is_overflow = !is_finite(result) && !result.is_nan() && !FUNCS_WITH_POLES.contains(&name);
+
+    apply_overflow_policy(context, is_overflow, false)
 }
 
-fn eval_var_expr(
+fn apply_overflow_policy(
     context: &mut Context,
-    identifier: &Identifier,
-    unit: Option<&String>,
-) -> Result<KalkValue, KalkError> {
-    // If there is a constant with this name, return a literal expression with its value
-    if let Some(value) = prelude::CONSTANTS.get(identifier.full_name.as_ref() as &str) {
-        return eval_expr(context, &Expr::Literal(*value), unit);
+    is_overflow: bool,
+    is_underflow: bool,
+) -> Result<(), KalkError> {
+    if !is_overflow && !is_underflow {
+        return Ok(());
     }
 
-    if let Some(sum_variables) = &context.sum_variables {
-        let sum_variable = sum_variables
-            .iter()
-            .find(|x| x.name == identifier.full_name);
-        if let Some(sum_variable) = sum_variable {
-            return Ok(KalkValue::from(sum_variable.value));
+    match context.overflow_policy {
+        OverflowPolicy::Infinity => {
+            context.is_out_of_range = true;
+
+            Ok(())
         }
+        OverflowPolicy::Error if is_overflow => Err(KalkError::Overflow),
+        OverflowPolicy::Error => Err(KalkError::Underflow),
     }
+}
 
-    // Look for the variable in the symbol table
-    let var_decl = context
-        .symbol_table
-        .get_var(identifier.full_name.as_ref() as &str)
-        .cloned();
-    if let Some(Stmt::VarDecl(_, expr)) = var_decl {
-        eval_expr(context, &expr, unit)
-    } else {
-        Err(KalkError::UndefinedVar(identifier.full_name.clone()))
-    }
+/// How the values given to an operation are outside of its domain.
+#[derive(Clone, Copy)]
+enum DomainIssue {
+    DivisionByZero,
+    /// The result is infinite or undefined, eg. for `log(0)`.
+    Undefined,
+    /// The result is a complex number, eg. for `sqrt(-1)`.
+    NotReal,
 }
 
-#[allow(unused_variables)]
-#[cfg(feature = "rug")]
-fn eval_literal_expr(
-    context: &mut Context,
-    value: f64,
-    unit: Option<&String>,
-) -> Result<KalkValue, KalkError> {
-    let mut float = float!(value);
-    float.set_prec(context.precision);
+/// Functions where an infinite result for a finite argument is a pole, rather than an overflow.
+const FUNCS_WITH_POLES: &[&str] = &[
+    "acoth", "acsch", "atanh", "cot", "coth", "csc", "csch", "digamma", "ln", "log", "log2", "zeta",
+];
 
-    Ok(KalkValue::Number(float, float!(0), unit.cloned()))
-}
+fn domain_issue_of_operation(
+    op: &TokenKind,
+    operands: &Operands,
+    result: &KalkValue,
+) -> Option<(DomainIssue, String)> {
+    let (left, right) = match (operands.real_values, result) {
+        (Some((left, Some(right))), KalkValue::Number(..)) => (left, right),
+        _ => return None,
+    };
+    let are_finite = operands.are_finite;
+
+    let is_division = matches!(
+        op,
+        TokenKind::Slash
+            | TokenKind::Div
+            | TokenKind::Quot
+            | TokenKind::Percent
+            | TokenKind::Rem
+            | TokenKind::Mod
+    );
+    let divides_by_zero = (is_division && right == 0f64 && !is_finite(result))
+        || (*op == TokenKind::Power && left == 0f64 && right < 0f64);
+    let issue = if are_finite && divides_by_zero {
+        DomainIssue::DivisionByZero
+    } else if result.is_nan() {
+        DomainIssue::Undefined
+    } else if are_finite
+        && *op == TokenKind::Power
+        && real_value(result).is_none()
+        && is_finite(result)
+    {
+        DomainIssue::NotReal
+    } else {
+        return None;
+    };
+
+    let description = format!(
+        "{} {} {}",
+        KalkValue::from(left),
+        crate::display::operator_to_string(op),
+        KalkValue::from(right)
+    );
+
+    Some((issue, description))
+}
+
+/// Find out if a function gave a result outside of the real numbers for real arguments.
+fn domain_issue_of_fn_call(
+    name: &str,
+    args: &[Option<f64>],
+    result: &KalkValue,
+) -> Option<(DomainIssue, String)> {
+    let (real, imaginary) = match result {
+        KalkValue::Number(real, imaginary, _) if args.iter().all(Option::is_some) => {
+            (primitive!(real), primitive!(imaginary))
+        }
+        _ => return None,
+    };
+
+    // Infinite arguments may give infinite or complex results without being outside of the domain
+    let are_finite = args.iter().flatten().all(|arg| arg.is_finite());
+    let is_pole = are_finite && real.is_infinite() && FUNCS_WITH_POLES.contains(&name);
+    let issue = if real.is_nan() || imaginary.is_nan() || is_pole {
+        DomainIssue::Undefined
+    } else if are_finite && imaginary != 0f64 && imaginary.is_finite() {
+        DomainIssue::NotReal
+    } else {
+        return None;
+    };
+
+    let args: Vec<String> = args
+        .iter()
+        .flatten()
+        .map(|arg| KalkValue::from(*arg).to_string())
+        .collect();
+
+    Some((issue, format!("{}({})", name, args.join(", "))))
+}
+
+/// Apply the domain policy to the result of an operation that was given values outside of its
+/// domain, and note what it gave. Complex results are only noted when they are replaced.
+fn check_domain(
+    context: &mut Context,
+    issue: DomainIssue,
+    description: String,
+    result: KalkValue,
+) -> Result<KalkValue, KalkError> {
+    let result = match (context.domain_policy, issue) {
+        (DomainPolicy::Error, DomainIssue::DivisionByZero) => {
+            return Err(KalkError::DivisionByZero)
+        }
+        (DomainPolicy::Error, DomainIssue::Undefined) => {
+            return Err(KalkError::OutsideDomain(description))
+        }
+        (DomainPolicy::Error, DomainIssue::NotReal) => return Err(KalkError::NotReal(description)),
+        (DomainPolicy::Complex, DomainIssue::NotReal) => return Ok(result),
+        (DomainPolicy::Real, DomainIssue::NotReal) => {
+            let unit = result.get_unit().cloned();
+            context.domain_note.get_or_insert_with(|| {
+                format!(
+                    "{} is not a real number, and was made undefined",
+                    description
+                )
+            });
+            set_undefined_reason(context, || format!("{} is not a real number", description));
+
+            return Ok(KalkValue::Number(float!(f64::NAN), float!(0f64), unit));
+        }
+        _ => result,
+    };
+
+    if result.is_nan() {
+        set_undefined_reason(context, || format!("{} is undefined", description));
+    }
+
+    context.domain_note.get_or_insert_with(|| {
+        if result.is_nan() {
+            format!("{} is undefined", description)
+        } else {
+            format!(
+                "{} is undefined, and gave {}",
+                description,
+                result.to_string_pretty()
+            )
+        }
+    });
+
+    Ok(result)
+}
+
+/// Remember why a value became undefined, unless an earlier one did. Operations on undefined
+/// values give undefined values, so the first one is usually why the result is undefined.
+pub(crate) fn set_undefined_reason(context: &mut Context, reason: impl FnOnce() -> String) {
+    context.undefined_reason.get_or_insert_with(reason);
+}
+
+fn eval_unit_expr(
+    context: &mut Context,
+    identifier: &str,
+    expr: &Expr,
+) -> Result<KalkValue, KalkError> {
+    // Eg. 4.7k is 4700
+    if let Some(factor) = crate::units::standalone_prefix(identifier) {
+        let value = eval_expr(context, expr, None)?;
+
+        return value.mul(context, KalkValue::from(factor));
+    }
+
+    let angle_unit = &context.angle_unit.clone();
+    if let Some(from_unit) = angle_unit_of_result(context, expr) {
+        if prelude::is_angle_unit(identifier) {
+            // The result is already an angle, eg. in `asin(0.5) deg`,
+            // so it is converted to the unit instead of being given it.
+            return convert_unit(
+                context,
+                expr,
+                Some(&from_unit),
+                Some(&identifier.to_string()),
+            );
+        }
+    }
+
+    if prelude::is_angle_unit(identifier) && angle_unit != identifier {
+        return convert_unit(
+            context,
+            expr,
+            Some(&identifier.to_string()),
+            Some(angle_unit),
+        );
+    }
+
+    eval_expr(context, expr, Some(&identifier.to_string()))
+}
+
+/// The unit of an expression that gives an angle without a unit, eg. `asin(0.5)`,
+/// which is in the angle unit that is in use.
+fn angle_unit_of_result(context: &Context, expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Group(expr) => angle_unit_of_result(context, expr),
+        Expr::FnCall(identifier, _)
+            if prelude::returns_angle(&identifier.full_name)
+                && context.symbol_table.get_fn(&identifier.full_name).is_none() =>
+        {
+            Some(context.angle_unit.clone())
+        }
+        _ => None,
+    }
+}
+
+pub fn convert_unit(
+    context: &mut Context,
+    expr: &Expr,
+    from_unit: Option<&String>,
+    to_unit: Option<&String>,
+) -> Result<KalkValue, KalkError> {
+    let result = convert_unit_value(context, expr, from_unit, to_unit)?;
+    if context.steps.is_some() {
+        let input = match (expr, from_unit) {
+            (Expr::Unit(_, _), _) | (_, None) => format!("{}", expr),
+            (_, Some(from_unit)) => format!("{} {}", expr, from_unit),
+        };
+        let input = format!("{} to {}", input, to_unit.map_or("", |unit| unit.as_str()));
+        let output = format_value(&result);
+        record_step(context, StepKind::UnitConversion, input, output);
+    }
+
+    Ok(result)
+}
+
+/// Convert every element of a vector or matrix to a unit, eg. `(1ft, 30cm, 2m) to m`.
+fn convert_elements(
+    context: &mut Context,
+    value: KalkValue,
+    to_unit: &String,
+) -> Result<KalkValue, KalkError> {
+    match value {
+        KalkValue::Vector(values) => Ok(KalkValue::Vector(
+            values
+                .into_iter()
+                .map(|value| convert_elements(context, value, to_unit))
+                .collect::<Result<Vec<KalkValue>, KalkError>>()?,
+        )),
+        KalkValue::Matrix(rows) => {
+            let mut converted_rows = Vec::new();
+            for row in rows {
+                converted_rows.push(
+                    row.into_iter()
+                        .map(|value| convert_elements(context, value, to_unit))
+                        .collect::<Result<Vec<KalkValue>, KalkError>>()?,
+                );
+            }
+
+            Ok(KalkValue::Matrix(converted_rows))
+        }
+        value => {
+            let expr = crate::ast::build_literal_ast(&value);
+            convert_unit(context, &expr, value.get_unit(), Some(to_unit))
+        }
+    }
+}
+
+fn convert_unit_value(
+    context: &mut Context,
+    expr: &Expr,
+    from_unit: Option<&String>,
+    to_unit: Option<&String>,
+) -> Result<KalkValue, KalkError> {
+    let (from_unit, to_unit) = if let (Some(from_unit), Some(to_unit)) = (from_unit, to_unit) {
+        (from_unit, to_unit)
+    } else {
+        return Err(KalkError::InvalidUnit);
+    };
+
+    if from_unit == to_unit {
+        return eval_expr(context, expr, Some(to_unit));
+    }
+
+    // Currencies and units of time are converted using a factor
+    // instead of being declared as units in the symbol table.
+    let factor = match (
+        context.symbol_table.get_exchange_rate(from_unit),
+        context.symbol_table.get_exchange_rate(to_unit),
+        duration::seconds_per_unit(from_unit),
+        duration::seconds_per_unit(to_unit),
+    ) {
+        (Some(from_rate), Some(to_rate), _, _) => Some(to_rate / from_rate),
+        (_, _, Some(from_seconds), Some(to_seconds)) => Some(from_seconds / to_seconds),
+        _ => None,
+    };
+    // So are units with prefixes, eg. km to m, if they haven't been declared
+    let is_declared = |unit: &str| context.symbol_table.unit_types.contains_key(unit);
+    let factor = factor.or_else(|| {
+        crate::units::conversion_factor(from_unit, to_unit)
+            .filter(|_| !is_declared(from_unit) && !is_declared(to_unit))
+    });
+    if let Some(factor) = factor {
+        let value = eval_expr(context, expr, None)?;
+        let (real, imaginary, _) = as_number_or_zero!(value.mul(context, KalkValue::from(factor))?);
+
+        return Ok(KalkValue::Number(real, imaginary, Some(to_unit.clone())));
+    }
+
+    // Temperatures are converted with an offset, eg. 0 °C is 32 °F
+    if temperature::is_temperature_unit(from_unit) && temperature::is_temperature_unit(to_unit) {
+        let value = eval_expr(context, expr, None)?.to_f64();
+        let converted = temperature::convert(value, from_unit, to_unit).unwrap();
+
+        return Ok(KalkValue::Number(
+            float!(converted),
+            float!(0f64),
+            Some(to_unit.clone()),
+        ));
+    }
+
+    if let Some(Stmt::UnitDecl(_, _, unit_def)) =
+        context.symbol_table.get_unit(to_unit, from_unit).cloned()
+    {
+        context.symbol_table.insert(Stmt::VarDecl(
+            Identifier::from_full_name(DECL_UNIT),
+            Box::new(expr.clone()),
+        ));
+
+        let (real, imaginary, _) = as_number_or_zero!(eval_expr(context, &unit_def, None)?);
+        Ok(KalkValue::Number(real, imaginary, Some(to_unit.clone())))
+    } else {
+        Err(KalkError::InvalidUnit)
+    }
+}
+
+fn eval_var_expr(
+    context: &mut Context,
+    identifier: &Identifier,
+    unit: Option<&String>,
+) -> Result<KalkValue, KalkError> {
+    // If there is a constant with this name, return a literal expression with its value,
+    // unless it has been replaced by a variable
+    if let Some(value) = prelude::CONSTANTS.get(identifier.full_name.as_ref() as &str) {
+        if context
+            .symbol_table
+            .get_var(&identifier.full_name)
+            .is_none()
+        {
+            return eval_expr(context, &Expr::Literal(*value, ExprSpan::default()), unit);
+        }
+    }
+
+    if let Some(sum_variables) = &context.sum_variables {
+        let sum_variable = sum_variables
+            .iter()
+            .find(|x| x.name == identifier.full_name);
+        if let Some(sum_variable) = sum_variable {
+            return Ok(KalkValue::from(sum_variable.value));
+        }
+    }
+
+    // Results from the history, eg. `_` or `_2`
+    if let Some(expr) = context
+        .symbol_table
+        .get_result(&identifier.full_name)
+        .cloned()
+    {
+        return eval_expr(context, &expr, unit);
+    }
+
+    // Look for the variable in the symbol table
+    let var_decl = context
+        .symbol_table
+        .get_var(identifier.full_name.as_ref() as &str)
+        .cloned();
+    if let Some(Stmt::VarDecl(_, expr)) = var_decl {
+        // The domain policy would otherwise make `sqrt(-1)` undefined or an error
+        if identifier.full_name == "i"
+            && context.domain_policy != DomainPolicy::Complex
+            && *expr == crate::symbol_table::imaginary_unit()
+        {
+            return Ok(KalkValue::Number(float!(0f64), float!(1f64), None));
+        }
+
+        eval_expr_sparse(context, &expr, unit)
+    } else {
+        Err(KalkError::UndefinedVar(
+            identifier.full_name.clone(),
+            context
+                .symbol_table
+                .similar_names(&identifier.full_name, false),
+        ))
+    }
+}
+
+fn eval_let(
+    context: &mut Context,
+    identifier: &Identifier,
+    value: &Expr,
+    body: &Expr,
+    unit: Option<&String>,
+) -> Result<KalkValue, KalkError> {
+    let value = eval_expr(context, value, None)?;
+    context.symbol_table.push_scope();
+    context.symbol_table.insert_local(Stmt::VarDecl(
+        identifier.clone(),
+        Box::new(crate::ast::build_literal_ast(&value)),
+    ))?;
+    let result = eval_expr(context, body, unit);
+    context.symbol_table.pop_scope();
+
+    result
+}
+
+fn eval_if(
+    context: &mut Context,
+    condition: &Expr,
+    then_branch: &Expr,
+    else_branch: &Expr,
+    unit: Option<&String>,
+) -> Result<KalkValue, KalkError> {
+    match eval_expr(context, condition, None)? {
+        KalkValue::Boolean(true) => eval_expr(context, then_branch, unit),
+        KalkValue::Boolean(false) => eval_expr(context, else_branch, unit),
+        value => Err(KalkError::UnexpectedType(
+            value.get_type_name(),
+            vec![String::from("boolean")],
+        )),
+    }
+}
+
+fn eval_with_angle_unit(
+    context: &mut Context,
+    angle_unit: &str,
+    expr: &Expr,
+    unit: Option<&String>,
+) -> Result<KalkValue, KalkError> {
+    let global_angle_unit = std::mem::replace(&mut context.angle_unit, angle_unit.into());
+    let result = eval_expr(context, expr, unit);
+    context.angle_unit = global_angle_unit;
+
+    result
+}
+
+#[allow(unused_variables)]
+#[cfg(feature = "rug")]
+fn eval_literal_expr(
+    context: &mut Context,
+    value: f64,
+    unit: Option<&String>,
+) -> Result<KalkValue, KalkError> {
+    let mut float = float!(value);
+    float.set_prec(context.precision);
+
+    Ok(KalkValue::Number(float, float!(0), unit.cloned()))
+}
+
+#[allow(unused_variables)]
+#[cfg(not(feature = "rug"))]
+fn eval_literal_expr(
+    context: &mut Context,
+    value: f64,
+    unit: Option<&String>,
+) -> Result<KalkValue, KalkError> {
+    Ok(KalkValue::Number(float!(value), float!(0), unit.cloned()))
+}
+
+fn eval_string_expr(value: &str) -> Result<KalkValue, KalkError> {
+    Ok(KalkValue::String(value.to_string()))
+}
+
+fn eval_group_expr(
+    context: &mut Context,
+    expr: &Expr,
+    unit: Option<&String>,
+) -> Result<KalkValue, KalkError> {
+    eval_expr_sparse(context, expr, unit)
+}
+
+/// Evaluate the Taylor polynomial of `taylor(expr, x0, degree)` at the current value of x.
+/// This is kept out of `eval_fn_call_expr` to keep its stack frame small for deep recursion.
+fn eval_taylor_call(
+    context: &mut Context,
+    expressions: &[Expr],
+    unit: Option<&String>,
+) -> Result<KalkValue, KalkError> {
+    if expressions.len() != 3 {
+        return Err(KalkError::IncorrectAmountOfArguments(
+            3,
+            "taylor".into(),
+            expressions.len(),
+        ));
+    }
+
+    let x0 = eval_expr(context, &expressions[1], None)?;
+    let degree = eval_expr(context, &expressions[2], None)?.to_f64();
+    if degree < 0f64 || degree.fract() != 0f64 {
+        return Err(KalkError::Expected(String::from(
+            "a non-negative integer as the degree, eg. taylor(sin(x), 0, 5)",
+        )));
+    }
+
+    // Inside a function where x is a parameter, the variable has the name of the parameter
+    let mut var_name = String::from("x");
+    crate::ast::for_each_identifier_mut(&mut expressions[0].clone(), &mut |identifier| {
+        if identifier.pure_name == "x" {
+            var_name = identifier.full_name.clone();
+        }
+    });
+    let polynomial =
+        numerical::taylor_polynomial(context, &expressions[0], &var_name, x0, degree as usize)?;
+
+    eval_expr(context, &polynomial, unit)
+}
+
+pub(crate) fn eval_fn_call_expr(
+    context: &mut Context,
+    identifier: &Identifier,
+    expressions: &[Expr],
+    unit: Option<&String>,
+) -> Result<KalkValue, KalkError> {
+    if identifier.prime_count > 0 {
+        context.is_approximation = true;
+    }
+
+    // Only eg. `now()` and registered functions can be called without arguments
+    if expressions.is_empty()
+        && !prelude::NULLARY_FUNCS.contains(&identifier.full_name.as_str())
+        && context
+            .symbol_table
+            .get_native_fn(&identifier.full_name)
+            .is_none()
+    {
+        if let Some(arity) = context.symbol_table.get_fn_arity(&identifier.full_name) {
+            return Err(KalkError::IncorrectAmountOfArguments(
+                arity,
+                identifier.full_name.clone(),
+                0,
+            ));
+        }
+
+        return Err(KalkError::Expected(format!(
+            "arguments for function {}",
+            identifier.full_name
+        )));
+    }
+
+    // A user-defined function may have replaced the built-in one
+    let is_replaced = prelude::is_prelude_func(&identifier.full_name)
+        && context.symbol_table.get_fn(&identifier.full_name).is_some();
+
+    if !is_replaced {
+        if let Some(value) = eval_special_fn_call(context, identifier, expressions, unit)? {
+            return Ok(value);
+        }
+    }
+
+    // Prelude vector function
+    if !is_replaced && prelude::is_vector_func(&identifier.full_name) {
+        let mut values = Vec::new();
+        for expression in expressions {
+            let value = match eval_expr_sparse(context, expression, None)? {
+                // The zeros of a sparse vector can be left out of the sum
+                KalkValue::SparseVector(vector) if identifier.full_name == "sum" => {
+                    let mut values: Vec<KalkValue> =
+                        vector.entries().iter().map(|(_, x)| x.clone()).collect();
+                    if values.is_empty() {
+                        values.push(KalkValue::from(0f64));
+                    }
+
+                    KalkValue::Vector(values)
+                }
+                value => value.into_dense(),
+            };
+            if expressions.len() == 1 {
+                if let KalkValue::Vector(internal_values) = value {
+                    values = internal_values;
+                    break;
+                }
+            }
+
+            values.push(value);
+        }
+
+        return prelude::call_vector_func(&identifier.full_name, KalkValue::Vector(values))
+            .unwrap_or_else(|| Ok(KalkValue::nan()));
+    }
+
+    // Prelude
+    if !is_replaced {
+        if let Some(value) = eval_prelude_fn_call(context, identifier, expressions)? {
+            return Ok(value);
+        }
+    }
+
+    eval_user_fn_call(context, identifier, expressions, unit)
+}
+
+/// Evaluate the functions that need their arguments unevaluated, eg. `sum` and `integrate`,
+/// or None if the identifier isn't one of them. These are kept out of `eval_fn_call_expr`
+/// so that its stack frame stays small for recursive user functions.
+#[inline(never)]
+fn eval_special_fn_call(
+    context: &mut Context,
+    identifier: &Identifier,
+    expressions: &[Expr],
+    unit: Option<&String>,
+) -> Result<Option<KalkValue>, KalkError> {
+    match identifier.full_name.as_ref() {
+        "sum" | "prod" => {
+            // If loop, eg. sum(k=1, 100, k)
+            if let Expr::Binary(left, TokenKind::Equals, right, _) = &expressions[0] {
+                if let Expr::Var(var_identifier) = &**left {
+                    // Make sure exactly 3 arguments were supplied.
+                    if expressions.len() != 3 {
+                        return Err(KalkError::IncorrectAmountOfArguments(
+                            3,
+                            "sum/prod".into(),
+                            expressions.len(),
+                        ));
+                    }
+
+                    //(var_identifier.pure_name.as_ref(), &**right);
+                    return eval_loop(
+                        context,
+                        identifier,
+                        &var_identifier.pure_name,
+                        right,
+                        &expressions[1],
+                        &expressions[2],
+                        unit.cloned(),
+                    )
+                    .map(Some);
+                }
+            }
+        }
+        "integrate" => {
+            context.is_approximation = true;
+
+            return match expressions.len() {
+                3 => numerical::integrate_with_unknown_variable(
+                    context,
+                    &expressions[0],
+                    &expressions[1],
+                    &expressions[2],
+                ),
+                4 => numerical::integrate(
+                    context,
+                    &expressions[0],
+                    &expressions[1],
+                    &expressions[2],
+                    if let Expr::Var(integration_variable) = &expressions[3] {
+                        &integration_variable.full_name[1..]
+                    } else {
+                        return Err(KalkError::ExpectedDx);
+                    },
+                ),
+                _ => Err(KalkError::IncorrectAmountOfArguments(
+                    3,
+                    "integrate".into(),
+                    expressions.len(),
+                )),
+            }
+            .map(Some);
+        }
+        "findroot" => {
+            context.is_approximation = true;
+            if expressions.len() != 2 {
+                return Err(KalkError::IncorrectAmountOfArguments(
+                    2,
+                    "findroot".into(),
+                    expressions.len(),
+                ));
+            }
+
+            let guess = eval_expr(context, &expressions[1], None)?;
+
+            return numerical::find_root_from(context, &expressions[0], "x", guess).map(Some);
+        }
+        "lim" => {
+            context.is_approximation = true;
+            if expressions.len() != 2 {
+                return Err(KalkError::IncorrectAmountOfArguments(
+                    2,
+                    "lim".into(),
+                    expressions.len(),
+                ));
+            }
+
+            let (var_name, target) = match &expressions[1] {
+                Expr::Binary(left, TokenKind::Arrow, target, _) => match &**left {
+                    Expr::Var(identifier) => (identifier.full_name.clone(), &**target),
+                    _ => {
+                        return Err(KalkError::Expected(String::from(
+                            "a variable before the arrow, eg. lim(sin(x)/x, x -> 0)",
+                        )))
+                    }
+                },
+                _ => {
+                    return Err(KalkError::Expected(String::from(
+                        "the variable and the value it approaches, eg. lim(sin(x)/x, x -> 0)",
+                    )))
+                }
+            };
+
+            return match target {
+                Expr::Unary(TokenKind::GreaterThan, target, _) => {
+                    numerical::limit(context, &expressions[0], &var_name, target, Some(true))
+                }
+                Expr::Unary(TokenKind::LessThan, target, _) => {
+                    numerical::limit(context, &expressions[0], &var_name, target, Some(false))
+                }
+                _ => numerical::limit(context, &expressions[0], &var_name, target, None),
+            }
+            .map(Some);
+        }
+        "bisect" => {
+            context.is_approximation = true;
+            if expressions.len() != 3 {
+                return Err(KalkError::IncorrectAmountOfArguments(
+                    3,
+                    "bisect".into(),
+                    expressions.len(),
+                ));
+            }
+
+            return numerical::bisect(
+                context,
+                &expressions[0],
+                "x",
+                &expressions[1],
+                &expressions[2],
+            )
+            .map(Some);
+        }
+        "now" => {
+            if !expressions.is_empty() {
+                return Err(KalkError::IncorrectAmountOfArguments(
+                    0,
+                    "now".into(),
+                    expressions.len(),
+                ));
+            }
+
+            let seconds = duration::now().ok_or_else(|| {
+                KalkError::EvaluationError(String::from("unable to get the current time."))
+            })?;
+
+            return Ok(Some(KalkValue::Number(
+                float!(seconds),
+                float!(0),
+                Some(String::from(duration::TIMESTAMP_UNIT)),
+            )));
+        }
+        "rand" => {
+            if !expressions.is_empty() {
+                return Err(KalkError::IncorrectAmountOfArguments(
+                    0,
+                    "rand".into(),
+                    expressions.len(),
+                ));
+            }
+
+            return Ok(Some(KalkValue::from(context.get_rng().next_f64())));
+        }
+        "randint" | "randnormal" => {
+            if expressions.len() != 2 {
+                return Err(KalkError::IncorrectAmountOfArguments(
+                    2,
+                    identifier.full_name.clone(),
+                    expressions.len(),
+                ));
+            }
+
+            let a = eval_expr(context, &expressions[0], None)?;
+            let b = eval_expr(context, &expressions[1], None)?;
+            if a.has_imaginary() || b.has_imaginary() {
+                return Err(KalkError::ExpectedReal);
+            }
+
+            let (a, b) = (a.to_f64(), b.to_f64());
+            let value = if identifier.full_name == "randint" {
+                // Every integer between a and b, including a and b
+                let (min, max) = (a.ceil(), b.floor());
+                if min > max {
+                    return Err(KalkError::EvaluationError(format!(
+                        "there are no integers between {} and {}.",
+                        a, b
+                    )));
+                }
+
+                (min + (context.get_rng().next_f64() * (max - min + 1f64)).floor()).min(max)
+            } else {
+                context.get_rng().next_normal(a, b)
+            };
+
+            return Ok(Some(KalkValue::from(value)));
+        }
+        "precision" | "anglemode" | "backend" => {
+            if !expressions.is_empty() {
+                return Err(KalkError::IncorrectAmountOfArguments(
+                    0,
+                    identifier.full_name.clone(),
+                    expressions.len(),
+                ));
+            }
+
+            let value = match identifier.full_name.as_ref() {
+                // The amount of bits used for the significand
+                #[cfg(feature = "rug")]
+                "precision" => context.precision as i32,
+                #[cfg(not(feature = "rug"))]
+                "precision" => f64::MANTISSA_DIGITS as i32,
+                // 0 for radians, 1 for degrees, 2 for gradians and 3 for turns
+                "anglemode" => prelude::ANGLE_UNITS
+                    .iter()
+                    .position(|unit| *unit == context.angle_unit)
+                    .unwrap_or(0) as i32,
+                // 0 for 64-bit floats and 1 for arbitrary precision
+                _ => cfg!(feature = "rug") as i32,
+            };
+
+            return Ok(Some(KalkValue::from(value)));
+        }
+        "derive" => {
+            if expressions.len() != 2 {
+                return Err(KalkError::IncorrectAmountOfArguments(
+                    2,
+                    "derive".into(),
+                    expressions.len(),
+                ));
+            }
+
+            let var_name = match &expressions[1] {
+                Expr::Var(var_identifier) => var_identifier.full_name.clone(),
+                _ => return Err(KalkError::Expected(String::from("a variable"))),
+            };
+            let derivative = expressions[0].derive(context.symbol_table, &var_name)?;
+
+            return eval_symbolic(context, &derivative, unit).map(Some);
+        }
+        "taylor" => return eval_taylor_call(context, expressions, unit).map(Some),
+        "simplify" => {
+            if expressions.len() != 1 {
+                return Err(KalkError::IncorrectAmountOfArguments(
+                    1,
+                    "simplify".into(),
+                    expressions.len(),
+                ));
+            }
+
+            return eval_symbolic(context, &expressions[0].simplify(), unit).map(Some);
+        }
+        "maximize" | "minimize" => {
+            context.is_approximation = true;
+            if expressions.len() != 3 {
+                return Err(KalkError::IncorrectAmountOfArguments(
+                    3,
+                    identifier.full_name.clone(),
+                    expressions.len(),
+                ));
+            }
+
+            return numerical::find_extremum(
+                context,
+                &expressions[0],
+                "x",
+                &expressions[1],
+                &expressions[2],
+                identifier.full_name == "maximize",
+            )
+            .map(Some);
+        }
+        _ => (),
+    }
 
-#[allow(unused_variables)]
-#[cfg(not(feature = "rug"))]
-fn eval_literal_expr(
-    context: &mut Context,
-    value: f64,
-    unit: Option<&String>,
-) -> Result<KalkValue, KalkError> {
-    Ok(KalkValue::Number(float!(value), float!(0), unit.cloned()))
+    Ok(None)
 }
 
-fn eval_group_expr(
+/// Evaluate the result of a symbolic operation, eg. `simplify`, or give it as text in kalker
+/// syntax if it has variables that aren't defined, eg. `5x` for `simplify(2x + 3x)`.
+fn eval_symbolic(
     context: &mut Context,
     expr: &Expr,
     unit: Option<&String>,
 ) -> Result<KalkValue, KalkError> {
-    eval_expr(context, expr, unit)
+    match eval_expr(context, expr, unit) {
+        Err(KalkError::UndefinedVar(_, _)) => Ok(KalkValue::String(expr.to_string())),
+        result => result,
+    }
 }
 
-pub(crate) fn eval_fn_call_expr(
+fn eval_user_fn_call(
     context: &mut Context,
     identifier: &Identifier,
     expressions: &[Expr],
     unit: Option<&String>,
 ) -> Result<KalkValue, KalkError> {
-    if identifier.prime_count > 0 {
-        context.is_approximation = true;
-    }
+    // Symbol Table
+    let stmt_definition = context.symbol_table.get_fn(&identifier.full_name).cloned();
 
-    // Special functions
-    match identifier.full_name.as_ref() {
-        "sum" | "prod" => {
-            // If loop, eg. sum(k=1, 100, k)
-            if let Expr::Binary(left, TokenKind::Equals, right) = &expressions[0] {
-                if let Expr::Var(var_identifier) = &**left {
-                    // Make sure exactly 3 arguments were supplied.
-                    if expressions.len() != 3 {
-                        return Err(KalkError::IncorrectAmountOfArguments(
-                            3,
-                            "sum/prod".into(),
-                            expressions.len(),
-                        ));
-                    }
+    match stmt_definition {
+        Some(Stmt::FnDecl(_, arguments, fn_body)) => {
+            if arguments.len() != expressions.len() {
+                return Err(KalkError::IncorrectAmountOfArguments(
+                    arguments.len(),
+                    identifier.full_name.clone(),
+                    expressions.len(),
+                ));
+            }
 
-                    //(var_identifier.pure_name.as_ref(), &**right);
-                    return eval_loop(
-                        context,
-                        identifier,
-                        &var_identifier.pure_name,
-                        right,
-                        &expressions[1],
-                        &expressions[2],
-                        unit.cloned(),
-                    );
+            if context.recursion_depth >= context.max_recursion_depth {
+                return Err(KalkError::MaxRecursionDepthExceeded);
+            }
+
+            // Initialise the arguments as their own variables.
+            let mut new_argument_values = Vec::new();
+            let mut argument_strings = Vec::new();
+            let mut expansion = *fn_body.clone();
+            for (i, argument) in arguments.iter().enumerate() {
+                let argument_identifier = if argument.contains('-') {
+                    let identifier_parts: Vec<&str> = argument.split('-').collect();
+                    Identifier::parameter_from_name(identifier_parts[1], identifier_parts[0])
+                } else {
+                    Identifier::from_full_name(argument)
+                };
+                let argument_value = eval_expr(context, &expressions[i], None)?;
+                let argument_ast = crate::ast::build_literal_ast(&argument_value);
+                if context.steps.is_some() {
+                    argument_strings.push(format_value(&argument_value));
+                    expansion =
+                        crate::differentiator::substitute(&expansion, argument, &argument_ast);
                 }
+
+                let var_decl = Stmt::VarDecl(argument_identifier, Box::new(argument_ast));
+
+                // Don't set these values just yet,
+                // to avoid affecting the value of arguments
+                // during recursion.
+                new_argument_values.push((argument, var_decl));
             }
-        }
-        "integrate" => {
-            context.is_approximation = true;
 
-            return match expressions.len() {
-                3 => numerical::integrate_with_unknown_variable(
-                    context,
-                    &expressions[0],
-                    &expressions[1],
-                    &expressions[2],
-                ),
-                4 => numerical::integrate(
+            let mut old_argument_values = Vec::new();
+            for (name, value) in new_argument_values {
+                // Save the original argument values,
+                // so that they can be reverted to after
+                // the function call is evaluated.
+                // This is necessary since recursive
+                // function calls have the same argument names.
+                old_argument_values.push(context.symbol_table.get_and_remove_var(name));
+
+                // Now set the new variable value
+                eval_stmt(context, &value)?;
+            }
+
+            let call = format!("{}({})", identifier.full_name, argument_strings.join(", "));
+            if context.steps.is_some() {
+                record_step(
                     context,
-                    &expressions[0],
-                    &expressions[1],
-                    &expressions[2],
-                    if let Expr::Var(integration_variable) = &expressions[3] {
-                        &integration_variable.full_name[1..]
-                    } else {
-                        return Err(KalkError::ExpectedDx);
-                    },
-                ),
-                _ => Err(KalkError::IncorrectAmountOfArguments(
-                    3,
-                    "integrate".into(),
-                    expressions.len(),
-                )),
-            };
+                    StepKind::FunctionExpansion,
+                    call.clone(),
+                    expansion.to_string(),
+                );
+            }
+
+            context.recursion_depth += 1;
+            let fn_value = eval_expr(context, &fn_body, unit);
+            context.recursion_depth -= 1;
+
+            if let Ok(value) = &fn_value {
+                let output = format_value(value);
+                record_step(context, StepKind::FunctionCall, call, output);
+            }
+
+            // Revert to original argument values
+            for old_argument_value in old_argument_values.into_iter().flatten() {
+                context.symbol_table.insert(old_argument_value);
+            }
+
+            fn_value
         }
-        _ => (),
+        _ => match context.symbol_table.get_fn_arity(&identifier.full_name) {
+            Some(arity) if arity != expressions.len() => {
+                Err(KalkError::IncorrectAmountOfArguments(
+                    arity,
+                    identifier.full_name.clone(),
+                    expressions.len(),
+                ))
+            }
+            _ => Err(KalkError::UndefinedFn(
+                identifier.full_name.clone(),
+                context
+                    .symbol_table
+                    .similar_names(&identifier.full_name, true),
+            )),
+        },
     }
+}
 
-    // Prelude vector function
-    if prelude::is_vector_func(&identifier.full_name) {
-        let mut values = Vec::new();
-        for expression in expressions {
-            let value = eval_expr(context, expression, None)?;
-            if expressions.len() == 1 {
-                if let KalkValue::Vector(internal_values) = value {
-                    values = internal_values;
-                    break;
-                }
-            }
+/// Call a function that was registered by the application, or None if there is no such
+/// function or a user-defined function replaced it.
+fn eval_native_fn_call(
+    context: &mut Context,
+    identifier: &Identifier,
+    expressions: &[Expr],
+) -> Option<Result<KalkValue, KalkError>> {
+    if context.symbol_table.get_fn(&identifier.full_name).is_some() {
+        return None;
+    }
 
-            values.push(value);
+    let native_function = context
+        .symbol_table
+        .get_native_fn(&identifier.full_name)?
+        .clone();
+    if expressions.len() != native_function.arity {
+        return Some(Err(KalkError::IncorrectAmountOfArguments(
+            native_function.arity,
+            identifier.full_name.clone(),
+            expressions.len(),
+        )));
+    }
+
+    let mut arguments = Vec::new();
+    for expression in expressions {
+        match eval_expr(context, expression, None) {
+            Ok(argument) => arguments.push(argument),
+            Err(err) => return Some(Err(err)),
         }
+    }
 
-        return prelude::call_vector_func(&identifier.full_name, KalkValue::Vector(values))
-            .unwrap_or_else(|| Ok(KalkValue::nan()));
+    Some((native_function.function)(&arguments).map_err(|message| {
+        KalkError::EvaluationError(format!("{}: {}", identifier.full_name, message))
+    }))
+}
+
+/// Call a function from the prelude that takes one or two arguments, eg. `sqrt(x)`,
+/// or one registered by the application, or None if there is no such function.
+/// This is kept separate from `eval_fn_call_expr`, so that its values don't take up
+/// stack space in recursive calls of user-defined functions.
+fn eval_prelude_fn_call(
+    context: &mut Context,
+    identifier: &Identifier,
+    expressions: &[Expr],
+) -> Result<Option<KalkValue>, KalkError> {
+    if let Some(result) = eval_native_fn_call(context, identifier, expressions) {
+        return result.map(Some);
     }
 
-    // Prelude
+    let mut call = None;
+    let mut args = [None, None];
     let prelude_func = match expressions.len() {
         1 => {
             let x = eval_expr(context, &expressions[0], None)?;
+            args[0] = real_value(&x);
             if identifier.prime_count > 0 {
-                return numerical::derive_func(context, identifier, x);
+                return numerical::derive_func(context, identifier, x).map(Some);
             } else {
+                call = context
+                    .steps
+                    .is_some()
+                    .then(|| format!("{}({})", identifier.full_name, format_value(&x)));
                 prelude::call_unary_func(
                     context,
                     &identifier.full_name,
@@ -433,6 +1924,15 @@ pub(crate) fn eval_fn_call_expr(
         2 => {
             let x = eval_expr(context, &expressions[0], None)?;
             let y = eval_expr(context, &expressions[1], None)?;
+            args = [real_value(&x), real_value(&y)];
+            call = context.steps.is_some().then(|| {
+                format!(
+                    "{}({}, {})",
+                    identifier.full_name,
+                    format_value(&x),
+                    format_value(&y)
+                )
+            });
             prelude::call_binary_func(
                 context,
                 &identifier.full_name,
@@ -445,11 +1945,33 @@ pub(crate) fn eval_fn_call_expr(
     };
 
     if let Some((result, _)) = prelude_func {
-        if result.is_err() && expressions.len() == 1 {
+        let args = &args[..expressions.len()];
+        let issue = match &result {
+            Ok(value) => domain_issue_of_fn_call(&identifier.full_name, args, value),
+            Err(_) => None,
+        };
+        if let Ok(value) = &result {
+            check_fn_range(context, &identifier.full_name, args, value)?;
+        }
+
+        let result = match (result, issue) {
+            (Ok(value), Some((issue, description))) => {
+                check_domain(context, issue, description, value)
+            }
+            (result, _) => result,
+        };
+
+        if let (Some(call), Ok(value)) = (call, &result) {
+            let output = format_value(value);
+            record_step(context, StepKind::FunctionCall, call, output);
+        }
+
+        // If a vector/matrix was given to a function that doesn't take one, call the function
+        // on every item in the vector/matrix. Other errors, eg. that a matrix is singular,
+        // are kept.
+        if matches!(result, Err(KalkError::UnexpectedType(_, _))) && expressions.len() == 1 {
             let x = eval_expr(context, &expressions[0], None)?;
 
-            // If a vector/matrix was given, call the function on every item
-            // in the vector/matrix.
             if let KalkValue::Vector(values) = x {
                 let mut new_values = Vec::new();
                 let mut success = true;
@@ -468,7 +1990,7 @@ pub(crate) fn eval_fn_call_expr(
                 }
 
                 if success {
-                    return Ok(KalkValue::Vector(new_values));
+                    return Ok(Some(KalkValue::Vector(new_values)));
                 }
             } else if let KalkValue::Matrix(rows) = x {
                 let mut new_rows = Vec::new();
@@ -493,75 +2015,15 @@ pub(crate) fn eval_fn_call_expr(
                 }
 
                 if success {
-                    return Ok(KalkValue::Matrix(new_rows));
+                    return Ok(Some(KalkValue::Matrix(new_rows)));
                 }
             }
         }
 
-        return result;
+        return result.map(Some);
     }
 
-    // Symbol Table
-    let stmt_definition = context.symbol_table.get_fn(&identifier.full_name).cloned();
-
-    match stmt_definition {
-        Some(Stmt::FnDecl(_, arguments, fn_body)) => {
-            if arguments.len() != expressions.len() {
-                return Err(KalkError::IncorrectAmountOfArguments(
-                    arguments.len(),
-                    identifier.full_name.clone(),
-                    expressions.len(),
-                ));
-            }
-
-            // Initialise the arguments as their own variables.
-            let mut new_argument_values = Vec::new();
-            for (i, argument) in arguments.iter().enumerate() {
-                let argument_identifier = if argument.contains('-') {
-                    let identifier_parts: Vec<&str> = argument.split('-').collect();
-                    Identifier::parameter_from_name(identifier_parts[1], identifier_parts[0])
-                } else {
-                    Identifier::from_full_name(argument)
-                };
-                let var_decl = Stmt::VarDecl(
-                    argument_identifier,
-                    Box::new(crate::ast::build_literal_ast(&eval_expr(
-                        context,
-                        &expressions[i],
-                        None,
-                    )?)),
-                );
-
-                // Don't set these values just yet,
-                // to avoid affecting the value of arguments
-                // during recursion.
-                new_argument_values.push((argument, var_decl));
-            }
-
-            let mut old_argument_values = Vec::new();
-            for (name, value) in new_argument_values {
-                // Save the original argument values,
-                // so that they can be reverted to after
-                // the function call is evaluated.
-                // This is necessary since recursive
-                // function calls have the same argument names.
-                old_argument_values.push(context.symbol_table.get_and_remove_var(name));
-
-                // Now set the new variable value
-                eval_stmt(context, &value)?;
-            }
-
-            let fn_value = eval_expr(context, &fn_body, unit);
-
-            // Revert to original argument values
-            for old_argument_value in old_argument_values.into_iter().flatten() {
-                context.symbol_table.insert(old_argument_value);
-            }
-
-            fn_value
-        }
-        _ => Err(KalkError::UndefinedFn(identifier.full_name.clone())),
-    }
+    Ok(None)
 }
 
 fn eval_loop(
@@ -577,15 +2039,22 @@ fn eval_loop(
         context.sum_variables = Some(Vec::new());
     }
 
-    {
+    let loop_index = {
         let sum_variables = context.sum_variables.as_mut().unwrap();
         sum_variables.push(SumVar {
             name: var_name.into(),
             value: 0,
         });
-    }
 
-    let start = eval_expr(context, start_expr, None)?.to_f64() as i128;
+        if sum_variables.len() == 1 {
+            context.started_loops += 1;
+            Some(context.started_loops - 1)
+        } else {
+            None
+        }
+    };
+
+    let mut start = eval_expr(context, start_expr, None)?.to_f64() as i128;
     let end = eval_expr(context, end_expr, None)?.to_f64() as i128;
     let sum_else_prod = match identifier.full_name.as_ref() {
         "sum" => true,
@@ -598,11 +2067,32 @@ fn eval_loop(
         KalkValue::from(1f64)
     };
 
+    let is_resumed_loop = context
+        .resume_from
+        .as_ref()
+        .is_some_and(|checkpoint| Some(checkpoint.loop_index) == loop_index);
+    if is_resumed_loop {
+        let checkpoint = context.resume_from.take().unwrap();
+        start = checkpoint.next_n;
+        sum = checkpoint.partial_result;
+    }
+
     for n in start..=end {
         let sum_variables = context.sum_variables.as_mut().unwrap();
         sum_variables.last_mut().unwrap().value = n;
 
-        let eval = eval_expr(context, expression, None)?;
+        let eval = match eval_expr(context, expression, None) {
+            Err(KalkError::Interrupted) if loop_index.is_some() => {
+                context.checkpoint = Some(LoopCheckpoint {
+                    loop_index: loop_index.unwrap(),
+                    next_n: n,
+                    partial_result: sum,
+                });
+
+                return Err(KalkError::Interrupted);
+            }
+            result => result?,
+        };
         if sum_else_prod {
             sum = sum.add(context, eval)?;
         } else {
@@ -663,8 +2153,21 @@ fn eval_indexer(
     index_expressions: &[Expr],
     unit: Option<&String>,
 ) -> Result<KalkValue, KalkError> {
-    let var_value = eval_expr(context, var, unit)?;
+    let var_value = eval_expr_sparse(context, var, unit)?;
     match var_value {
+        KalkValue::SparseVector(vector) => {
+            if index_expressions.len() != 1 {
+                return Err(KalkError::IncorrectAmountOfIndexes(
+                    index_expressions.len(),
+                    1,
+                ));
+            }
+
+            let index = as_indices(context, index_expressions)?[0];
+            vector
+                .get(index.wrapping_sub(1))
+                .ok_or_else(|| KalkError::ItemOfIndexDoesNotExist(vec![index]))
+        }
         KalkValue::Vector(values) => {
             if index_expressions.len() != 1 {
                 return Err(KalkError::IncorrectAmountOfIndexes(
@@ -743,7 +2246,8 @@ fn eval_comprehension(
     left: &Expr,
     conditions: &[Expr],
     vars: &[RangedVar],
-) -> Result<Vec<KalkValue>, KalkError> {
+    builder: &mut VectorBuilder,
+) -> Result<(), KalkError> {
     if vars.len() != conditions.len() {
         return Err(KalkError::InvalidComprehension(String::from("Expected a new variable to be introduced for every condition (conditions are comma separated).")));
     }
@@ -752,37 +2256,33 @@ fn eval_comprehension(
     let var = vars.first().unwrap();
     context.symbol_table.insert(Stmt::VarDecl(
         Identifier::from_full_name(&var.name),
-        Box::new(Expr::Literal(0f64)),
+        Box::new(Expr::Literal(0f64, ExprSpan::default())),
     ));
 
     let min = eval_expr(context, &var.min, None)?.to_f64() as i32;
     let max = eval_expr(context, &var.max, None)?.to_f64() as i32;
 
-    let mut values = Vec::new();
     for i in min..max {
         context.symbol_table.set(Stmt::VarDecl(
             Identifier::from_full_name(&var.name),
-            Box::new(Expr::Literal(i as f64)),
+            Box::new(Expr::Literal(i as f64, ExprSpan::default())),
         ));
 
         if conditions.len() > 1 {
-            let x = eval_comprehension(context, left, &conditions[1..], &vars[1..])?;
-            for value in x {
-                values.push(value);
-            }
+            eval_comprehension(context, left, &conditions[1..], &vars[1..], builder)?;
         }
 
         let condition = eval_expr(context, condition, None)?;
         if let KalkValue::Boolean(boolean) = condition {
             if boolean && vars.len() == 1 {
-                values.push(eval_expr(context, left, None)?);
+                builder.push(eval_expr(context, left, None)?);
             }
         }
     }
 
     context.symbol_table.get_and_remove_var(&var.name);
 
-    Ok(values)
+    Ok(())
 }
 
 fn eval_equation(
@@ -797,6 +2297,7 @@ fn eval_equation(
         Box::new(left.clone()),
         TokenKind::Minus,
         Box::new(right.clone()),
+        ExprSpan::default(),
     );
     numerical::find_root(context, &expr, &unknown_var.full_name)
 }
@@ -872,6 +2373,50 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_sparse_vector() {
+        fn parse_expr(parser_context: &mut crate::parser::Context, input: &str) -> Expr {
+            match crate::parser::parse(parser_context, input).unwrap().pop() {
+                Some(Stmt::Expr(expr)) => *expr,
+                stmt => panic!("Expected an expression, got {:?}", stmt),
+            }
+        }
+
+        let mut parser_context = crate::parser::Context::new();
+        let mostly_zeros = "[floor(x/990) : 0 ≤ x and 1000 > x]";
+        let comprehension = parse_expr(&mut parser_context, mostly_zeros);
+        let combined = parse_expr(
+            &mut parser_context,
+            &format!("2{} + {}/2", mostly_zeros, mostly_zeros),
+        );
+        let indexed = parse_expr(
+            &mut parser_context,
+            &format!("({} * 3)[[995]]", mostly_zeros),
+        );
+
+        let mut symbol_table = SymbolTable::new();
+        let mut context = context(&mut symbol_table, "rad");
+        for (expr, item) in [(&comprehension, 1f64), (&combined, 2.5f64)] {
+            match eval_expr_sparse(&mut context, expr, None).unwrap() {
+                KalkValue::SparseVector(vector) => {
+                    assert_eq!(vector.len(), 1000);
+                    assert_eq!(vector.entries().len(), 10);
+                    assert!(cmp(vector.get(999).unwrap(), item));
+                    assert!(cmp(vector.get(3).unwrap(), 0f64));
+                }
+                value => panic!("Expected a sparse vector, got {:?}", value),
+            }
+        }
+
+        assert!(cmp(eval_expr(&mut context, &indexed, None).unwrap(), 3f64));
+
+        // Other operations get a regular vector
+        match eval_expr(&mut context, &comprehension, None).unwrap() {
+            KalkValue::Vector(values) => assert_eq!(values.len(), 1000),
+            value => panic!("Expected a vector, got {:?}", value),
+        }
+    }
+
     #[test]
     fn test_literal() {
         let stmt = Stmt::Expr(literal(1f64));
@@ -1003,7 +2548,7 @@ mod tests {
 
         assert_eq!(
             interpret(stmt),
-            Err(KalkError::UndefinedVar(String::from("x")))
+            Err(KalkError::UndefinedVar(String::from("x"), Vec::new()))
         );
     }
 
@@ -1043,8 +2588,28 @@ mod tests {
 
         assert_eq!(
             interpret(stmt),
-            Err(KalkError::UndefinedFn(String::from("f")))
+            Err(KalkError::UndefinedFn(String::from("f"), Vec::new()))
+        );
+    }
+
+    #[test]
+    fn test_matrix_fn_errors() {
+        let singular = Expr::Matrix(vec![
+            vec![*literal(1f64), *literal(2f64)],
+            vec![*literal(2f64), *literal(4f64)],
+        ]);
+
+        // The function isn't called on every item instead, since it takes a matrix
+        assert_eq!(
+            interpret(Stmt::Expr(fn_call("inv", vec![singular.clone()]))),
+            Err(KalkError::SingularMatrix)
         );
+        assert!(cmp(
+            interpret(Stmt::Expr(fn_call("det", vec![singular])))
+                .unwrap()
+                .unwrap(),
+            0f64
+        ));
     }
 
     #[test_case(1f64, 2f64, 9f64)]
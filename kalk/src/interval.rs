@@ -0,0 +1,400 @@
+//! Interval arithmetic, used to find bounds for the exact value of a real result.
+//! Every operation rounds its bounds outwards, so the exact value of the expression
+//! (with the decimal numbers of the input taken as they are written) is always
+//! between the bounds. Expressions that can't be bounded this way, eg. ones with
+//! complex numbers, units or piecewise functions, give `None`.
+//!
+//! This is a separate evaluator that works with f64 bounds, also with the rug feature.
+//! It evaluates the last statement again after the interpreter, so only the final
+//! result gets bounds, not the values that the interpreter calculates along the way.
+//! The bounds are left out if the result of the interpreter isn't within them.
+use crate::ast::{Expr, Stmt};
+use crate::lexer::TokenKind;
+use crate::prelude;
+use crate::symbol_table::SymbolTable;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+/// How deeply variables and functions may refer to each other before giving up,
+/// eg. for recursive functions.
+const MAX_DEPTH: u32 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Interval {
+    pub(crate) lower: f64,
+    pub(crate) upper: f64,
+}
+
+impl Interval {
+    fn point(value: f64) -> Self {
+        Interval {
+            lower: value,
+            upper: value,
+        }
+    }
+
+    /// An interval around a value that has been rounded to the nearest float,
+    /// eg. 0.1 or π. Integers that can be represented exactly stay points.
+    fn around(value: f64) -> Self {
+        if value.fract() == 0f64 && value.abs() < 2f64.powi(53) {
+            Interval::point(value)
+        } else {
+            Interval::widened(value, value, 1)
+        }
+    }
+
+    /// Round the bounds outwards by some units in the last place,
+    /// to account for the rounding error of the operation they were calculated with.
+    fn widened(lower: f64, upper: f64, ulps: u32) -> Self {
+        let (mut lower, mut upper) = (lower, upper);
+        for _ in 0..ulps {
+            lower = next_down(lower);
+            upper = next_up(upper);
+        }
+
+        Interval { lower, upper }
+    }
+
+    pub(crate) fn contains(&self, value: f64) -> bool {
+        self.lower <= value && value <= self.upper
+    }
+
+    fn neg(self) -> Self {
+        Interval {
+            lower: -self.upper,
+            upper: -self.lower,
+        }
+    }
+
+    fn add(self, rhs: Self) -> Self {
+        Interval::widened(self.lower + rhs.lower, self.upper + rhs.upper, 1)
+    }
+
+    fn sub(self, rhs: Self) -> Self {
+        self.add(rhs.neg())
+    }
+
+    fn mul(self, rhs: Self) -> Self {
+        let products = [
+            self.lower * rhs.lower,
+            self.lower * rhs.upper,
+            self.upper * rhs.lower,
+            self.upper * rhs.upper,
+        ];
+        let lower = products.iter().cloned().fold(f64::INFINITY, f64::min);
+        let upper = products.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+
+        Interval::widened(lower, upper, 1)
+    }
+
+    fn div(self, rhs: Self) -> Option<Self> {
+        if rhs.contains(0f64) {
+            return None;
+        }
+
+        let reciprocal = Interval::widened(1f64 / rhs.upper, 1f64 / rhs.lower, 1);
+
+        Some(self.mul(reciprocal))
+    }
+
+    fn powi(self, exponent: i32) -> Option<Self> {
+        if exponent < 0 {
+            return Interval::point(1f64).div(self.powi(-exponent)?);
+        }
+
+        let (lower, upper) = (self.lower.powi(exponent), self.upper.powi(exponent));
+        let (lower, upper) = if exponent % 2 == 1 {
+            (lower, upper)
+        } else if self.contains(0f64) {
+            (0f64, lower.max(upper))
+        } else {
+            (lower.min(upper), lower.max(upper))
+        };
+
+        // powi can be off by more than one unit in the last place for large exponents
+        Some(Interval::widened(lower, upper, exponent.max(1) as u32))
+            .map(|interval| interval.clamp_lower(0f64, exponent % 2 == 0))
+    }
+
+    /// Make sure the lower bound isn't below `min`, if `condition` is true.
+    /// This is used when the value is known to not be negative, eg. x^2.
+    fn clamp_lower(self, min: f64, condition: bool) -> Self {
+        if condition && self.lower < min {
+            Interval {
+                lower: min,
+                upper: self.upper,
+            }
+        } else {
+            self
+        }
+    }
+
+    fn pow(self, exponent: Self) -> Option<Self> {
+        if exponent.lower == exponent.upper && exponent.lower.fract() == 0f64 {
+            return self.powi(exponent.lower as i32);
+        }
+
+        // x^y = e^(y ln(x))
+        exponent.mul(self.ln()?).exp()
+    }
+
+    fn sqrt(self) -> Option<Self> {
+        if self.upper < 0f64 {
+            return None;
+        }
+
+        let interval = Interval::widened(self.lower.max(0f64).sqrt(), self.upper.sqrt(), 1);
+
+        Some(interval.clamp_lower(0f64, true))
+    }
+
+    fn exp(self) -> Option<Self> {
+        Some(Interval::widened(self.lower.exp(), self.upper.exp(), 2).clamp_lower(0f64, true))
+    }
+
+    fn ln(self) -> Option<Self> {
+        if self.lower <= 0f64 {
+            return None;
+        }
+
+        Some(Interval::widened(self.lower.ln(), self.upper.ln(), 2))
+    }
+
+    fn abs(self) -> Self {
+        if self.contains(0f64) {
+            Interval {
+                lower: 0f64,
+                upper: self.lower.abs().max(self.upper.abs()),
+            }
+        } else {
+            let (lower, upper) = (self.lower.abs(), self.upper.abs());
+            Interval {
+                lower: lower.min(upper),
+                upper: lower.max(upper),
+            }
+        }
+    }
+
+    fn sin(self) -> Self {
+        let (sin_lower, sin_upper) = (self.lower.sin(), self.upper.sin());
+        let mut interval = Interval::widened(sin_lower.min(sin_upper), sin_lower.max(sin_upper), 2);
+
+        // Include the peaks that are inside the interval, at π/2 + 2πk and -π/2 + 2πk.
+        // The check is a bit generous, since including a peak can only make the bounds wider.
+        let contains_peak = |offset: f64| {
+            let first = ((self.lower - offset) / (2f64 * PI) - 1e-9).ceil();
+            let last = ((self.upper - offset) / (2f64 * PI) + 1e-9).floor();
+
+            first <= last
+        };
+        if self.upper - self.lower >= 2f64 * PI || contains_peak(PI / 2f64) {
+            interval.upper = 1f64;
+        }
+
+        if self.upper - self.lower >= 2f64 * PI || contains_peak(-PI / 2f64) {
+            interval.lower = -1f64;
+        }
+
+        Interval {
+            lower: interval.lower.max(-1f64),
+            upper: interval.upper.min(1f64),
+        }
+    }
+
+    fn cos(self) -> Self {
+        self.add(Interval::around(PI / 2f64)).sin()
+    }
+
+    fn tan(self) -> Option<Self> {
+        self.sin().div(self.cos())
+    }
+}
+
+fn next_up(value: f64) -> f64 {
+    if value.is_nan() || value == f64::INFINITY {
+        value
+    } else if value == 0f64 {
+        f64::from_bits(1)
+    } else if value > 0f64 {
+        f64::from_bits(value.to_bits() + 1)
+    } else {
+        f64::from_bits(value.to_bits() - 1)
+    }
+}
+
+fn next_down(value: f64) -> f64 {
+    -next_up(-value)
+}
+
+/// Find bounds for the value of an expression statement, if it is real.
+pub(crate) fn eval_stmt(
+    symbol_table: &SymbolTable,
+    angle_unit: &str,
+    stmt: &Stmt,
+) -> Option<Interval> {
+    if let Stmt::Expr(expr) = stmt {
+        let mut context = Context {
+            symbol_table,
+            angle_unit,
+            arguments: HashMap::new(),
+            depth: 0,
+        };
+
+        eval_expr(&mut context, expr)
+    } else {
+        None
+    }
+}
+
+struct Context<'a> {
+    symbol_table: &'a SymbolTable,
+    angle_unit: &'a str,
+    /// The bounds of the arguments of the functions that are being evaluated.
+    arguments: HashMap<String, Interval>,
+    depth: u32,
+}
+
+fn eval_expr(context: &mut Context, expr: &Expr) -> Option<Interval> {
+    match expr {
+        Expr::Literal(value, _) => Some(Interval::around(*value)),
+        Expr::Integer(value) => Some(Interval::around(value.to_f64())),
+        Expr::Group(expr) => eval_expr(context, expr),
+        Expr::Unary(TokenKind::Minus, expr, _) => Some(eval_expr(context, expr)?.neg()),
+        Expr::Binary(left, op, right, _) => {
+            let (left, right) = (eval_expr(context, left)?, eval_expr(context, right)?);
+            match op {
+                TokenKind::Plus => Some(left.add(right)),
+                TokenKind::Minus => Some(left.sub(right)),
+                TokenKind::Star => Some(left.mul(right)),
+                TokenKind::Slash => left.div(right),
+                TokenKind::Power => left.pow(right),
+                _ => None,
+            }
+        }
+        Expr::Var(identifier) => {
+            let name = &identifier.full_name;
+            if let Some(argument) = context.arguments.get(name) {
+                return Some(*argument);
+            }
+
+            if let Some(value) = prelude::CONSTANTS.get(name.as_str()) {
+                return Some(Interval::around(*value));
+            }
+
+            match context.symbol_table.get_var(name) {
+                Some(Stmt::VarDecl(_, expr)) => {
+                    with_depth(context, |context| eval_expr(context, expr))
+                }
+                _ => None,
+            }
+        }
+        Expr::FnCall(identifier, arguments) => {
+            let mut values = Vec::new();
+            for argument in arguments {
+                values.push(eval_expr(context, argument)?);
+            }
+
+            eval_fn_call(context, &identifier.full_name, values)
+        }
+        _ => None,
+    }
+}
+
+fn eval_fn_call(context: &mut Context, name: &str, values: Vec<Interval>) -> Option<Interval> {
+    if let Some(Stmt::FnDecl(_, parameters, body)) = context.symbol_table.get_fn(name) {
+        if parameters.len() != values.len() {
+            return None;
+        }
+
+        let arguments = parameters.iter().cloned().zip(values).collect();
+        let outer_arguments = std::mem::replace(&mut context.arguments, arguments);
+        let result = with_depth(context, |context| eval_expr(context, body));
+        context.arguments = outer_arguments;
+
+        return result;
+    }
+
+    let x = match values.as_slice() {
+        [x] => *x,
+        _ => return None,
+    };
+    let to_radians = |x: Interval| match context.angle_unit {
+        "deg" => x.mul(Interval::around(PI / 180f64)),
+        "grad" => x.mul(Interval::around(PI / 200f64)),
+        "turn" => x.mul(Interval::around(2f64 * PI)),
+        _ => x,
+    };
+
+    match name {
+        "abs" => Some(x.abs()),
+        "sqrt" | "√" => x.sqrt(),
+        "exp" => x.exp(),
+        "ln" => x.ln(),
+        "log" => x.ln()?.div(Interval::around(10f64.ln())),
+        "sin" => Some(to_radians(x).sin()),
+        "cos" => Some(to_radians(x).cos()),
+        "tan" => to_radians(x).tan(),
+        _ => None,
+    }
+}
+
+fn with_depth(
+    context: &mut Context,
+    eval: impl FnOnce(&mut Context) -> Option<Interval>,
+) -> Option<Interval> {
+    if context.depth >= MAX_DEPTH {
+        return None;
+    }
+
+    context.depth += 1;
+    let result = eval(context);
+    context.depth -= 1;
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Interval;
+    use crate::parser;
+
+    fn bounds(input: &str) -> Option<Interval> {
+        let mut context = parser::Context::new();
+        let mut statements = parser::parse(&mut context, input).unwrap();
+        let stmt = statements.pop().unwrap();
+
+        super::eval_stmt(context.get_symbol_table(), "rad", &stmt)
+    }
+
+    #[test]
+    fn test_bounds_contain_exact_value() {
+        let interval = bounds("0.1 + 0.2").unwrap();
+        assert!(interval.contains(0.3));
+        assert!(interval.upper - interval.lower < 1e-15);
+
+        let interval = bounds("sin(pi)").unwrap();
+        assert!(interval.contains(0f64));
+
+        let interval = bounds("f(x) = x^2 - 2; f(sqrt(2))").unwrap();
+        assert!(interval.contains(0f64));
+
+        let interval = bounds("cos(x)").map(|_| ());
+        assert_eq!(interval, None);
+    }
+
+    #[test]
+    fn test_peaks() {
+        let interval = Interval::widened(1.5, 1.6, 0).sin();
+        assert_eq!(interval.upper, 1f64);
+        assert!(interval.lower <= 1.5f64.sin().min(1.6f64.sin()));
+
+        let interval = Interval::widened(-0.5, 0.25, 0).cos();
+        assert_eq!(interval.upper, 1f64);
+        assert!(interval.lower <= 0.5f64.cos());
+
+        let interval = Interval::widened(0f64, 7f64, 0).sin();
+        assert_eq!((interval.lower, interval.upper), (-1f64, 1f64));
+        assert!(bounds("1 / (1 - 1)").is_none());
+        assert!(bounds("sqrt(-1)").is_none());
+    }
+}
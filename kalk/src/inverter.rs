@@ -1,5 +1,5 @@
 use crate::ast::Identifier;
-use crate::ast::{Expr, Stmt};
+use crate::ast::{Expr, ExprSpan, Stmt};
 use crate::errors::KalkError;
 use crate::lexer::TokenKind;
 use crate::prelude;
@@ -69,10 +69,10 @@ fn invert(
     unknown_var: &str,
 ) -> Result<(Expr, Expr), KalkError> {
     match expr {
-        Expr::Binary(left, op, right) => {
+        Expr::Binary(left, op, right, _) => {
             invert_binary(target_expr, symbol_table, left, op, right, unknown_var)
         }
-        Expr::Unary(op, expr) => invert_unary(target_expr, op, expr),
+        Expr::Unary(op, expr, _) => invert_unary(target_expr, op, expr),
         Expr::Unit(identifier, expr) => {
             invert_unit(target_expr, symbol_table, identifier, expr, unknown_var)
         }
@@ -85,7 +85,9 @@ fn invert(
             arguments,
             unknown_var,
         ),
-        Expr::Literal(_) | Expr::Boolean(_) => Ok((target_expr, expr.clone())),
+        Expr::Literal(_, _) | Expr::Integer(_) | Expr::Boolean(_) | Expr::String(_) => {
+            Ok((target_expr, expr.clone()))
+        }
         Expr::Piecewise(_) => Err(KalkError::UnableToInvert(String::from("Piecewise"))),
         Expr::Vector(_) => Err(KalkError::UnableToInvert(String::from("Vector"))),
         Expr::Matrix(_) => Err(KalkError::UnableToInvert(String::from("Matrix"))),
@@ -94,6 +96,9 @@ fn invert(
             Err(KalkError::UnableToInvert(String::from("Comprehension")))
         }
         Expr::Equation(_, _, _) => Err(KalkError::UnableToInvert(String::from("Equation"))),
+        Expr::Let(_, _, _) => Err(KalkError::UnableToInvert(String::from("Let"))),
+        Expr::WithAngleUnit(_, _) => Err(KalkError::UnableToInvert(String::from("With"))),
+        Expr::If(_, _, _) => Err(KalkError::UnableToInvert(String::from("If"))),
     }
 }
 
@@ -116,7 +121,7 @@ fn invert_binary(
                     symbol_table,
                     left,
                     &TokenKind::Plus,
-                    &multiply_into(&Expr::Literal(-1f64), inside_group)?,
+                    &multiply_into(&Expr::Literal(-1f64, ExprSpan::default()), inside_group)?,
                     unknown_var,
                 );
             }
@@ -154,7 +159,12 @@ fn invert_binary(
                 return invert(
                     target_expr,
                     symbol_table,
-                    &Expr::Binary(inside_group.clone(), *op, Box::new(right.clone())),
+                    &Expr::Binary(
+                        inside_group.clone(),
+                        *op,
+                        Box::new(right.clone()),
+                        ExprSpan::default(),
+                    ),
                     unknown_var,
                 );
             }
@@ -165,7 +175,12 @@ fn invert_binary(
                 return invert(
                     target_expr,
                     symbol_table,
-                    &Expr::Binary(Box::new(left.clone()), *op, inside_group.clone()),
+                    &Expr::Binary(
+                        Box::new(left.clone()),
+                        *op,
+                        inside_group.clone(),
+                        ExprSpan::default(),
+                    ),
                     unknown_var,
                 );
             }
@@ -210,7 +225,12 @@ fn invert_binary(
         }
 
         return invert(
-            Expr::Binary(Box::new(target_expr), op_inv, Box::new(right.clone())),
+            Expr::Binary(
+                Box::new(target_expr),
+                op_inv,
+                Box::new(right.clone()),
+                ExprSpan::default(),
+            ),
             symbol_table,
             left,
             unknown_var,
@@ -218,13 +238,22 @@ fn invert_binary(
     }
 
     // Otherwise, invert the left side.
-    let final_target_expr = Expr::Binary(Box::new(target_expr), op_inv, Box::new(left.clone()));
+    let final_target_expr = Expr::Binary(
+        Box::new(target_expr),
+        op_inv,
+        Box::new(left.clone()),
+        ExprSpan::default(),
+    );
     invert(
         // Eg. 2-a
         // If the operator is minus (and the left expression is being inverted),
         // make the target expression negative to keep balance.
         if let TokenKind::Minus = op {
-            Expr::Unary(TokenKind::Minus, Box::new(final_target_expr))
+            Expr::Unary(
+                TokenKind::Minus,
+                Box::new(final_target_expr),
+                ExprSpan::default(),
+            )
         } else {
             final_target_expr
         },
@@ -238,7 +267,7 @@ fn invert_unary(target_expr: Expr, op: &TokenKind, expr: &Expr) -> Result<(Expr,
     match op {
         TokenKind::Minus => Ok((
             // Make the target expression negative
-            Expr::Unary(TokenKind::Minus, Box::new(target_expr)),
+            Expr::Unary(TokenKind::Minus, Box::new(target_expr), ExprSpan::default()),
             expr.clone(), // And then continue inverting the inner-expression.
         )),
         _ => Err(KalkError::UnableToInvert(String::new())),
@@ -256,6 +285,7 @@ fn invert_unit(
         Box::new(target_expr),
         TokenKind::ToKeyword,
         Box::new(Expr::Var(Identifier::from_full_name(identifier))),
+        ExprSpan::default(),
     );
     invert(x, symbol_table, expr, unknown_var)
 }
@@ -303,7 +333,8 @@ fn invert_fn_call(
                                 Expr::Binary(
                                     Box::new(target_expr),
                                     TokenKind::Power,
-                                    Box::new(Expr::Literal(2f64)),
+                                    Box::new(Expr::Literal(2f64, ExprSpan::default())),
+                                    ExprSpan::default(),
                                 ),
                                 symbol_table,
                                 &arguments[0],
@@ -337,7 +368,10 @@ fn invert_fn_call(
     {
         (parameters, body)
     } else {
-        return Err(KalkError::UndefinedFn(identifier.full_name.clone()));
+        return Err(KalkError::UndefinedFn(
+            identifier.full_name.clone(),
+            symbol_table.similar_names(&identifier.full_name, true),
+        ));
     };
 
     // Make sure the input is valid.
@@ -365,11 +399,11 @@ fn invert_fn_call(
 pub fn contains_var(symbol_table: &SymbolTable, expr: &Expr, var_name: &str) -> bool {
     // Recursively scan the expression for the variable.
     match expr {
-        Expr::Binary(left, _, right) => {
+        Expr::Binary(left, _, right, _) => {
             contains_var(symbol_table, left, var_name)
                 || contains_var(symbol_table, right, var_name)
         }
-        Expr::Unary(_, expr) => contains_var(symbol_table, expr, var_name),
+        Expr::Unary(_, expr, _) => contains_var(symbol_table, expr, var_name),
         Expr::Unit(_, expr) => contains_var(symbol_table, expr, var_name),
         Expr::Var(identifier) => {
             identifier.full_name == var_name
@@ -391,7 +425,7 @@ pub fn contains_var(symbol_table: &SymbolTable, expr: &Expr, var_name: &str) ->
 
             false
         }
-        Expr::Literal(_) | Expr::Boolean(_) => false,
+        Expr::Literal(_, _) | Expr::Integer(_) | Expr::Boolean(_) | Expr::String(_) => false,
         Expr::Piecewise(_) => true, // Let it try to invert this. It will just display the error message.
         Expr::Vector(items) => items
             .iter()
@@ -402,32 +436,41 @@ pub fn contains_var(symbol_table: &SymbolTable, expr: &Expr, var_name: &str) ->
         Expr::Indexer(_, _) => false,
         Expr::Comprehension(_, _, _) => false,
         Expr::Equation(_, _, _) => false,
+        Expr::Let(_, value, body) => {
+            contains_var(symbol_table, value, var_name)
+                || contains_var(symbol_table, body, var_name)
+        }
+        Expr::WithAngleUnit(_, expr) => contains_var(symbol_table, expr, var_name),
+        Expr::If(_, _, _) => true, // Like piecewise, let it try to invert this and display the error.
     }
 }
 
 /// Multiply an expression into a group.
 fn multiply_into(expr: &Expr, base_expr: &Expr) -> Result<Expr, KalkError> {
     match base_expr {
-        Expr::Binary(left, op, right) => match op {
+        Expr::Binary(left, op, right, _) => match op {
             // If + or -, multiply the expression with each term.
             TokenKind::Plus | TokenKind::Minus => Ok(Expr::Binary(
                 Box::new(multiply_into(expr, left)?),
                 *op,
                 Box::new(multiply_into(expr, right)?),
+                ExprSpan::default(),
             )),
             // If * or /, only multiply with the first factor.
             TokenKind::Star | TokenKind::Slash => Ok(Expr::Binary(
                 Box::new(multiply_into(expr, left)?),
                 *op,
                 right.clone(),
+                ExprSpan::default(),
             )),
             _ => Err(KalkError::UnableToInvert(String::new())),
         },
         // If it's a literal, just multiply them together.
-        Expr::Literal(_) | Expr::Var(_) => Ok(Expr::Binary(
+        Expr::Literal(_, _) | Expr::Integer(_) | Expr::Var(_) => Ok(Expr::Binary(
             Box::new(expr.clone()),
             TokenKind::Star,
             Box::new(base_expr.clone()),
+            ExprSpan::default(),
         )),
         Expr::Group(_) => Err(KalkError::UnableToInvert(String::from(
             "Parenthesis multiplied with parenthesis (this should be possible in the future).",
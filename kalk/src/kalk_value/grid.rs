@@ -0,0 +1,87 @@
+/// Characters used to draw the brackets around a grid.
+struct Brackets {
+    single: (char, char),
+    top: (char, char),
+    middle: (char, char),
+    bottom: (char, char),
+}
+
+const ASCII_BRACKETS: Brackets = Brackets {
+    single: ('[', ']'),
+    top: ('[', ']'),
+    middle: ('[', ']'),
+    bottom: ('[', ']'),
+};
+
+const UNICODE_BRACKETS: Brackets = Brackets {
+    single: ('[', ']'),
+    top: ('⎡', '⎤'),
+    middle: ('⎢', '⎥'),
+    bottom: ('⎣', '⎦'),
+};
+
+/// Render a grid of already formatted cells, eg. the values of a matrix, with
+/// every column right-aligned and brackets around the rows. With `unicode`,
+/// the brackets of consecutive rows are joined together (⎡ ⎢ ⎣).
+pub(crate) fn to_grid_string(rows: &[Vec<String>], unicode: bool) -> String {
+    let brackets = if unicode {
+        &UNICODE_BRACKETS
+    } else {
+        &ASCII_BRACKETS
+    };
+
+    let column_count = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let mut column_widths = vec![0; column_count];
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            column_widths[i] = column_widths[i].max(cell.chars().count());
+        }
+    }
+
+    let mut lines = Vec::new();
+    for (i, row) in rows.iter().enumerate() {
+        let (left, right) = if rows.len() == 1 {
+            brackets.single
+        } else if i == 0 {
+            brackets.top
+        } else if i == rows.len() - 1 {
+            brackets.bottom
+        } else {
+            brackets.middle
+        };
+
+        let cells = row
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| format!("{:>width$}", cell, width = column_widths[i]))
+            .collect::<Vec<String>>()
+            .join("  ");
+        lines.push(format!("{} {} {}", left, cells, right));
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_grid_string;
+
+    fn cells(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter()
+            .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_to_grid_string() {
+        let rows = cells(&[&["1", "-20"], &["300", "4"]]);
+        assert_eq!(to_grid_string(&rows, false), "[   1  -20 ]\n[ 300    4 ]");
+        assert_eq!(to_grid_string(&rows, true), "⎡   1  -20 ⎤\n⎣ 300    4 ⎦");
+
+        let rows = cells(&[&["1"], &["π"], &["3"]]);
+        assert_eq!(to_grid_string(&rows, true), "⎡ 1 ⎤\n⎢ π ⎥\n⎣ 3 ⎦");
+
+        let rows = cells(&[&["1", "2", "3"]]);
+        assert_eq!(to_grid_string(&rows, true), "[ 1  2  3 ]");
+    }
+}
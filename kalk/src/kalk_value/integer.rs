@@ -0,0 +1,518 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// The amount of bits an exact integer may have, which is as many as the largest float has.
+/// Results that would be larger than this, eg. 10^400, overflow just like floats do.
+pub(crate) const MAX_BITS: u64 = 1024;
+
+/// An integer of any size, used for results that are too large to be exact as floats,
+/// eg. 2^200 or 30!.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BigInt {
+    negative: bool,
+    /// The absolute value in base 2^32, with the least significant digit first
+    /// and without any leading zeros, which means that zero has no digits.
+    digits: Vec<u32>,
+}
+
+impl BigInt {
+    fn new(negative: bool, mut digits: Vec<u32>) -> Self {
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+
+        BigInt {
+            negative: negative && !digits.is_empty(),
+            digits,
+        }
+    }
+
+    pub fn from_u64(value: u64) -> Self {
+        BigInt::new(false, vec![value as u32, (value >> 32) as u32])
+    }
+
+    /// The float as an integer, unless it has decimals or isn't finite.
+    pub fn from_f64(value: f64) -> Option<Self> {
+        if !value.is_finite() || value.fract() != 0f64 {
+            return None;
+        }
+
+        if value == 0f64 {
+            return Some(BigInt::from_u64(0));
+        }
+
+        // The value is mantissa * 2^(exponent - 1075), where the mantissa has 53 bits.
+        let bits = value.abs().to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as i64;
+        let mantissa = (bits & ((1 << 52) - 1)) | (1 << 52);
+        let shift = exponent - 1075;
+        let magnitude = if shift >= 0 {
+            shift_left(&BigInt::from_u64(mantissa).digits, shift as u64)
+        } else {
+            BigInt::from_u64(mantissa >> -shift).digits
+        };
+
+        Some(BigInt::new(value < 0f64, magnitude))
+    }
+
+    /// The value of the digits in the radix, eg. `ff` in base 16,
+    /// unless they aren't digits of it or the result would have more than `MAX_BITS` bits.
+    pub fn from_str_radix(digits: &str, radix: u32) -> Option<Self> {
+        let mut result = Vec::new();
+        for c in digits.chars() {
+            let digit = c.to_digit(radix)?;
+            result = add_magnitude(&mul_small(&result, radix), &[digit]);
+            while result.last() == Some(&0) {
+                result.pop();
+            }
+
+            if result.len() as u64 * 32 > MAX_BITS + 32 {
+                return None;
+            }
+        }
+
+        Some(BigInt::new(false, result)).filter(|result| result.bits() <= MAX_BITS)
+    }
+
+    /// The digits of the integer in the radix, eg. `ff` for 255 in base 16.
+    pub fn to_string_radix(&self, radix: u32) -> String {
+        const DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+        if self.is_zero() {
+            return String::from("0");
+        }
+
+        let mut digits = Vec::new();
+        let mut rest = self.digits.clone();
+        while !rest.is_empty() {
+            let (quotient, remainder) = div_rem_small(&rest, radix);
+            digits.push(DIGITS[remainder as usize] as char);
+            rest = quotient;
+        }
+
+        if self.negative {
+            digits.push('-');
+        }
+
+        digits.iter().rev().collect()
+    }
+
+    /// The closest float, which is infinity if the integer is too large.
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self
+            .digits
+            .iter()
+            .rev()
+            .fold(0f64, |result, digit| result * 4294967296f64 + *digit as f64);
+
+        if self.negative {
+            -magnitude
+        } else {
+            magnitude
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn abs(&self) -> BigInt {
+        BigInt::new(false, self.digits.clone())
+    }
+
+    pub fn to_u64(&self) -> Option<u64> {
+        match (self.negative, self.digits.as_slice()) {
+            (true, _) => None,
+            (false, []) => Some(0),
+            (false, [low]) => Some(*low as u64),
+            (false, [low, high]) => Some(((*high as u64) << 32) | *low as u64),
+            _ => None,
+        }
+    }
+
+    /// The lowest 64 bits of the integer in two's complement.
+    pub fn wrapping_to_u64(&self) -> u64 {
+        let low = *self.digits.first().unwrap_or(&0) as u64
+            | (*self.digits.get(1).unwrap_or(&0) as u64) << 32;
+        if self.negative {
+            low.wrapping_neg()
+        } else {
+            low
+        }
+    }
+
+    /// The amount of bits needed to write the absolute value.
+    pub fn bits(&self) -> u64 {
+        match self.digits.last() {
+            Some(last) => self.digits.len() as u64 * 32 - last.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    /// Divide the integers, with the quotient rounded towards zero, like with floats,
+    /// so the remainder has the same sign as the dividend. None is returned if the divisor is zero.
+    pub fn div_rem(&self, divisor: &BigInt) -> Option<(BigInt, BigInt)> {
+        if divisor.is_zero() {
+            return None;
+        }
+
+        let (quotient, remainder) = div_rem_magnitude(&self.digits, &divisor.digits);
+
+        Some((
+            BigInt::new(self.negative != divisor.negative, quotient),
+            BigInt::new(self.negative, remainder),
+        ))
+    }
+
+    /// Raise the integer to the power of the exponent, unless the result would have more than `MAX_BITS` bits.
+    pub fn pow(&self, mut exponent: u64) -> Option<BigInt> {
+        let mut result = BigInt::from_u64(1);
+        if exponent == 0 {
+            return Some(result);
+        }
+
+        // |x|^n < 2^(bits * n)
+        if self.bits().saturating_sub(1).saturating_mul(exponent) > MAX_BITS {
+            return None;
+        }
+
+        let mut base = self.clone();
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = &result * &base;
+            }
+
+            exponent >>= 1;
+            if exponent > 0 {
+                base = &base * &base;
+            }
+        }
+
+        Some(result).filter(|result| result.bits() <= MAX_BITS)
+    }
+
+    /// n!, unless the result would have more than `MAX_BITS` bits.
+    pub fn factorial(n: u64) -> Option<BigInt> {
+        let mut result = vec![1];
+        for i in 2..=n {
+            result = mul_small(&result, u32::try_from(i).ok()?);
+            if result.len() as u64 * 32 > MAX_BITS + 32 {
+                return None;
+            }
+        }
+
+        Some(BigInt::new(false, result)).filter(|result| result.bits() <= MAX_BITS)
+    }
+}
+
+impl std::fmt::Display for BigInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        // Split the number up in groups of 9 decimal digits, starting with the least significant one.
+        let mut groups = Vec::new();
+        let mut rest = self.digits.clone();
+        while !rest.is_empty() {
+            let (quotient, remainder) = div_rem_small(&rest, 1_000_000_000);
+            groups.push(remainder);
+            rest = quotient;
+        }
+
+        let mut output = String::from(if self.negative { "-" } else { "" });
+        output.push_str(&groups.pop().unwrap().to_string());
+        for group in groups.iter().rev() {
+            output.push_str(&format!("{:09}", group));
+        }
+
+        write!(f, "{}", output)
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.digits, &other.digits),
+            (true, true) => cmp_magnitude(&other.digits, &self.digits),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Neg for BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::new(!self.negative, self.digits)
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::new(self.negative, add_magnitude(&self.digits, &other.digits));
+        }
+
+        // The signs are different, so the smaller absolute value is subtracted from the larger one.
+        match cmp_magnitude(&self.digits, &other.digits) {
+            Ordering::Less => {
+                BigInt::new(other.negative, sub_magnitude(&other.digits, &self.digits))
+            }
+            _ => BigInt::new(self.negative, sub_magnitude(&self.digits, &other.digits)),
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other.clone()
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        let mut result = vec![0u32; self.digits.len() + other.digits.len()];
+        for (i, x) in self.digits.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, y) in other.digits.iter().enumerate() {
+                let product = *x as u64 * *y as u64 + result[i + j] as u64 + carry;
+                result[i + j] = product as u32;
+                carry = product >> 32;
+            }
+
+            result[i + other.digits.len()] = carry as u32;
+        }
+
+        BigInt::new(self.negative != other.negative, result)
+    }
+}
+
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (longer, shorter) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut result = Vec::with_capacity(longer.len() + 1);
+    let mut carry = 0u64;
+    for (i, x) in longer.iter().enumerate() {
+        let sum = *x as u64 + *shorter.get(i).unwrap_or(&0) as u64 + carry;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    }
+
+    result.push(carry as u32);
+
+    result
+}
+
+/// a - b, where a has to be at least as large as b.
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, x) in a.iter().enumerate() {
+        let mut difference = *x as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if difference < 0 {
+            difference += 1 << 32;
+            borrow = 1;
+        }
+
+        result.push(difference as u32);
+    }
+
+    result
+}
+
+fn mul_small(a: &[u32], factor: u32) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0u64;
+    for x in a {
+        let product = *x as u64 * factor as u64 + carry;
+        result.push(product as u32);
+        carry = product >> 32;
+    }
+
+    if carry > 0 {
+        result.push(carry as u32);
+    }
+
+    result
+}
+
+fn div_rem_small(a: &[u32], divisor: u32) -> (Vec<u32>, u32) {
+    let mut quotient = vec![0u32; a.len()];
+    let mut remainder = 0u64;
+    for (i, x) in a.iter().enumerate().rev() {
+        let current = (remainder << 32) | *x as u64;
+        quotient[i] = (current / divisor as u64) as u32;
+        remainder = current % divisor as u64;
+    }
+
+    while quotient.last() == Some(&0) {
+        quotient.pop();
+    }
+
+    (quotient, remainder as u32)
+}
+
+/// Long division, one bit at a time. This is slow for large divisors,
+/// but they are rare, since most of the time only small numbers are divided.
+fn div_rem_magnitude(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if b.len() == 1 {
+        let (quotient, remainder) = div_rem_small(a, b[0]);
+
+        return (quotient, vec![remainder]);
+    }
+
+    let mut quotient = vec![0u32; a.len()];
+    let mut remainder: Vec<u32> = Vec::new();
+    for i in (0..a.len() * 32).rev() {
+        remainder = shift_left(&remainder, 1);
+        if (a[i / 32] >> (i % 32)) & 1 == 1 {
+            if remainder.is_empty() {
+                remainder.push(1);
+            } else {
+                remainder[0] |= 1;
+            }
+        }
+
+        if cmp_magnitude(&remainder, b) != Ordering::Less {
+            remainder = sub_magnitude(&remainder, b);
+            while remainder.last() == Some(&0) {
+                remainder.pop();
+            }
+
+            quotient[i / 32] |= 1 << (i % 32);
+        }
+    }
+
+    (quotient, remainder)
+}
+
+fn shift_left(a: &[u32], shift: u64) -> Vec<u32> {
+    let digit_shift = (shift / 32) as usize;
+    let bit_shift = shift % 32;
+    let mut result = vec![0u32; digit_shift];
+    let mut carry = 0u32;
+    for x in a {
+        let shifted = (*x as u64) << bit_shift;
+        result.push(shifted as u32 | carry);
+        carry = (shifted >> 32) as u32;
+    }
+
+    if carry > 0 {
+        result.push(carry);
+    }
+
+    while result.last() == Some(&0) {
+        result.pop();
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BigInt;
+
+    fn int(value: f64) -> BigInt {
+        BigInt::from_f64(value).unwrap()
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let x = int(2f64).pow(100).unwrap();
+        assert_eq!(x.to_string(), "1267650600228229401496703205376");
+        assert_eq!(
+            (&x + &int(1f64)).to_string(),
+            "1267650600228229401496703205377"
+        );
+        assert_eq!(
+            (&int(1f64) - &x).to_string(),
+            "-1267650600228229401496703205375"
+        );
+        assert_eq!(
+            (&x * &int(-3f64)).to_string(),
+            "-3802951800684688204490109616128"
+        );
+        assert_eq!(&x - &x, int(0f64));
+        assert!(int(-5f64) < int(3f64));
+        assert!(x > int(1e20));
+    }
+
+    #[test]
+    fn test_div_rem() {
+        let x = int(2f64).pow(200).unwrap();
+        let (quotient, remainder) = x.div_rem(&int(7f64)).unwrap();
+        assert_eq!(remainder, int(4f64));
+        assert_eq!(&(&quotient * &int(7f64)) + &remainder, x);
+
+        let y = &int(3f64).pow(50).unwrap() + &int(11f64);
+        let (quotient, remainder) = x.div_rem(&y).unwrap();
+        assert_eq!(&(&quotient * &y) + &remainder, x);
+        assert!(remainder < y);
+
+        let (quotient, remainder) = int(-7f64).div_rem(&int(2f64)).unwrap();
+        assert_eq!((quotient, remainder), (int(-3f64), int(-1f64)));
+        assert!(x.div_rem(&int(0f64)).is_none());
+    }
+
+    #[test]
+    fn test_conversions() {
+        for value in [
+            0f64,
+            1f64,
+            -42f64,
+            9007199254740993f64,
+            1e300,
+            -2f64.powi(80),
+        ] {
+            assert_eq!(int(value).to_f64(), value);
+        }
+
+        assert!(BigInt::from_f64(0.5f64).is_none());
+        assert!(BigInt::from_f64(f64::INFINITY).is_none());
+        assert_eq!(
+            BigInt::factorial(25).unwrap().to_string(),
+            "15511210043330985984000000"
+        );
+        assert!(BigInt::factorial(170).is_some());
+        assert!(BigInt::factorial(171).is_none());
+        assert!(int(3f64).pow(1 << 40).is_none());
+    }
+
+    #[test]
+    fn test_radix() {
+        let x = BigInt::from_str_radix("9007199254740993", 10).unwrap();
+        assert_eq!(x.to_string(), "9007199254740993");
+        assert_eq!(x.to_string_radix(16), "20000000000001");
+        let max = BigInt::from_str_radix("ffffffffffffffff", 16).unwrap();
+        assert_eq!(max.to_u64(), Some(u64::MAX));
+        assert_eq!((-max).to_string_radix(2), format!("-{}", "1".repeat(64)));
+        assert_eq!(BigInt::from_str_radix("0", 10), Some(int(0f64)));
+        assert_eq!(int(0f64).to_string_radix(8), "0");
+        assert!(BigInt::from_str_radix("12a", 10).is_none());
+    }
+}
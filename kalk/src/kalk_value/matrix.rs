@@ -0,0 +1,353 @@
+use super::KalkValue;
+use crate::{float, primitive};
+
+/// Matrices with at least this many rows or columns are multiplied with a blocked
+/// algorithm, and inverted and decomposed without going through `KalkValue`s,
+/// as long as they only contain real numbers.
+pub(crate) const REAL_MATRIX_THRESHOLD: usize = 32;
+
+/// The size of the square blocks that are multiplied at a time.
+/// A block from each matrix should fit in the CPU cache together.
+const BLOCK_SIZE: usize = 64;
+
+#[cfg(not(feature = "rug"))]
+type Real = f64;
+#[cfg(feature = "rug")]
+type Real = rug::Float;
+
+/// A matrix of real numbers, stored as one row after another. The arithmetic is the same
+/// as with `KalkValue`s, including the precision of arbitrary precision values.
+pub(crate) struct RealMatrix {
+    values: Vec<Real>,
+    row_count: usize,
+    column_count: usize,
+}
+
+// The values are only borrowed on the right hand side, since arbitrary precision values
+// can't be copied
+#[allow(clippy::op_ref)]
+impl RealMatrix {
+    /// Convert the rows of a matrix into a `RealMatrix`. Returns `None` if a value
+    /// is not a real number without unit, since those need the regular arithmetic.
+    pub(crate) fn from_rows(rows: &[Vec<KalkValue>]) -> Option<Self> {
+        let column_count = rows.first()?.len();
+        let mut values = Vec::with_capacity(rows.len() * column_count);
+        for row in rows {
+            if row.len() != column_count {
+                return None;
+            }
+
+            for value in row {
+                match value {
+                    KalkValue::Number(real, imaginary, None) if *imaginary == 0f64 => {
+                        values.push(real.clone())
+                    }
+                    _ => return None,
+                }
+            }
+        }
+
+        Some(RealMatrix {
+            values,
+            row_count: rows.len(),
+            column_count,
+        })
+    }
+
+    pub(crate) fn into_rows(self) -> Vec<Vec<KalkValue>> {
+        self.values
+            .chunks(self.column_count.max(1))
+            .map(|row| {
+                row.iter()
+                    .map(|value| KalkValue::Number(value.clone(), float!(0f64), None))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Multiply two matrices one block at a time, to avoid going through
+    /// the columns of the right hand side matrix in a cache-unfriendly way.
+    /// The products are still summed in the same order as the naive algorithm.
+    pub(crate) fn mul_blocked(&self, rhs: &RealMatrix) -> Option<RealMatrix> {
+        if self.column_count != rhs.row_count {
+            return None;
+        }
+
+        let (n, m, p) = (self.row_count, self.column_count, rhs.column_count);
+        let mut result = vec![float!(0f64); n * p];
+        for k_block in (0..m).step_by(BLOCK_SIZE) {
+            let k_end = (k_block + BLOCK_SIZE).min(m);
+            for i_block in (0..n).step_by(BLOCK_SIZE) {
+                let i_end = (i_block + BLOCK_SIZE).min(n);
+                for j_block in (0..p).step_by(BLOCK_SIZE) {
+                    let j_end = (j_block + BLOCK_SIZE).min(p);
+                    for i in i_block..i_end {
+                        let result_row = &mut result[i * p..(i + 1) * p];
+                        for k in k_block..k_end {
+                            let lhs_value = &self.values[i * m + k];
+                            let rhs_row = &rhs.values[k * p..(k + 1) * p];
+                            for j in j_block..j_end {
+                                result_row[j] += lhs_value.clone() * &rhs_row[j];
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Some(RealMatrix {
+            values: result,
+            row_count: n,
+            column_count: p,
+        })
+    }
+
+    /// Decompose a square matrix into PA = LU with partial pivoting, the same way as
+    /// `lu_decompose` in the prelude, but with the rows next to each other in memory.
+    /// Returns the original row index of every row, or None if the matrix is singular.
+    pub(crate) fn lu_decompose(mut self) -> Option<(RealMatrix, Vec<usize>)> {
+        let n = self.row_count;
+        if self.column_count != n {
+            return None;
+        }
+
+        let largest = self
+            .values
+            .iter()
+            .map(|value| primitive!(value).abs())
+            .fold(0f64, f64::max);
+
+        // Pivots this close to zero are the result of rounding errors
+        let tolerance = largest * n as f64 * f64::EPSILON;
+        let mut permutation: Vec<usize> = (0..n).collect();
+        for k in 0..n {
+            let mut pivot_index = k;
+            let mut pivot_abs = primitive!(self.values[k * n + k]).abs();
+            for i in k + 1..n {
+                let value_abs = primitive!(self.values[i * n + k]).abs();
+                if value_abs > pivot_abs {
+                    pivot_index = i;
+                    pivot_abs = value_abs;
+                }
+            }
+
+            if pivot_abs <= tolerance {
+                return None;
+            }
+
+            for j in 0..n {
+                self.values.swap(k * n + j, pivot_index * n + j);
+            }
+            permutation.swap(k, pivot_index);
+
+            let (upper, lower) = self.values.split_at_mut((k + 1) * n);
+            let pivot_row = &upper[k * n..];
+            for row in lower.chunks_mut(n) {
+                let factor = row[k].clone() / &pivot_row[k];
+                for (value, pivot_value) in row[k + 1..].iter_mut().zip(&pivot_row[k + 1..]) {
+                    *value -= factor.clone() * pivot_value;
+                }
+
+                row[k] = factor;
+            }
+        }
+
+        Some((self, permutation))
+    }
+
+    /// The determinant of a square matrix, which is zero if it's singular.
+    pub(crate) fn determinant(self) -> Real {
+        let n = self.row_count;
+        match self.lu_decompose() {
+            Some((lu, permutation)) => {
+                let mut result = float!(permutation_sign(&permutation));
+                for i in 0..n {
+                    result *= &lu.values[i * n + i];
+                }
+
+                result
+            }
+            None => float!(0f64),
+        }
+    }
+
+    /// The inverse of a square matrix, or None if it's singular.
+    /// Every column is solved with the LU decomposition, like with `linsolve`.
+    pub(crate) fn inverse(self) -> Option<RealMatrix> {
+        let n = self.row_count;
+        let (lu, permutation) = self.lu_decompose()?;
+        let mut result = vec![float!(0f64); n * n];
+        let mut x: Vec<Real> = Vec::with_capacity(n);
+        for column in 0..n {
+            // Forward substitution, Ly = Pe, where e is the column of the identity matrix
+            x.clear();
+            for (i, original_index) in permutation.iter().enumerate() {
+                let mut value = float!(if *original_index == column {
+                    1f64
+                } else {
+                    0f64
+                });
+                for (l, y_value) in lu.values[i * n..i * n + i].iter().zip(&x) {
+                    value -= l.clone() * y_value;
+                }
+
+                x.push(value);
+            }
+
+            // Back substitution, Ux = y
+            for i in (0..n).rev() {
+                let (solved, unsolved) = x.split_at_mut(i + 1);
+                let value = &mut solved[i];
+                for (u, x_value) in lu.values[i * n + i + 1..(i + 1) * n].iter().zip(&*unsolved) {
+                    *value -= u.clone() * x_value;
+                }
+
+                *value /= &lu.values[i * n + i];
+            }
+
+            for (i, value) in x.drain(..).enumerate() {
+                result[i * n + column] = value;
+            }
+        }
+
+        Some(RealMatrix {
+            values: result,
+            row_count: n,
+            column_count: n,
+        })
+    }
+}
+
+/// 1 if the permutation consists of an even amount of swaps, and otherwise -1.
+pub(crate) fn permutation_sign(permutation: &[usize]) -> f64 {
+    let mut visited = vec![false; permutation.len()];
+    let mut sign = 1f64;
+    for start in 0..permutation.len() {
+        // A cycle of n items is n - 1 swaps
+        let mut length = 0;
+        let mut i = start;
+        while !visited[i] {
+            visited[i] = true;
+            i = permutation[i];
+            length += 1;
+        }
+
+        if length > 0 && length % 2 == 0 {
+            sign = -sign;
+        }
+    }
+
+    sign
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{permutation_sign, RealMatrix};
+    use crate::float;
+    use crate::kalk_value::KalkValue;
+    use crate::test_helpers::cmp;
+
+    fn to_rows(values: &[&[f64]]) -> Vec<Vec<KalkValue>> {
+        values
+            .iter()
+            .map(|row| row.iter().map(|value| KalkValue::from(*value)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_mul_blocked() {
+        // Big enough to consist of several blocks, with blocks that aren't full
+        let (n, m, p) = (70, 130, 67);
+        let lhs: Vec<Vec<KalkValue>> = (0..n)
+            .map(|i| {
+                (0..m)
+                    .map(|k| KalkValue::from((i * k % 7) as f64 - 3.5))
+                    .collect()
+            })
+            .collect();
+        let rhs: Vec<Vec<KalkValue>> = (0..m)
+            .map(|k| {
+                (0..p)
+                    .map(|j| KalkValue::from((k + 2 * j) as f64 / 4.0))
+                    .collect()
+            })
+            .collect();
+
+        let result = RealMatrix::from_rows(&lhs)
+            .unwrap()
+            .mul_blocked(&RealMatrix::from_rows(&rhs).unwrap())
+            .unwrap()
+            .into_rows();
+
+        // The same as with the regular arithmetic
+        for i in [0, 1, 63, 64, 69] {
+            for j in [0, 5, 63, 64, 66] {
+                let mut expected = KalkValue::from(0f64);
+                for (k, value) in lhs[i].iter().enumerate() {
+                    expected = expected
+                        .add_without_unit(&value.clone().mul_without_unit(&rhs[k][j]).unwrap())
+                        .unwrap();
+                }
+
+                assert_eq!(result[i][j], expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_rows() {
+        let complex = vec![vec![KalkValue::Number(float!(1f64), float!(2f64), None)]];
+        assert!(RealMatrix::from_rows(&complex).is_none());
+
+        let with_unit = vec![vec![KalkValue::Number(
+            float!(1f64),
+            float!(0f64),
+            Some(String::from("m")),
+        )]];
+        assert!(RealMatrix::from_rows(&with_unit).is_none());
+    }
+
+    #[test]
+    fn test_determinant_and_inverse() {
+        // The first pivot is zero, so the rows need to be swapped
+        let rows = to_rows(&[
+            &[0f64, 2f64, 1f64],
+            &[1f64, 1f64, 0f64],
+            &[3f64, 0f64, 1f64],
+        ]);
+        let determinant = RealMatrix::from_rows(&rows).unwrap().determinant();
+        assert!(cmp(crate::primitive!(determinant), -5f64));
+
+        let inverse = RealMatrix::from_rows(&rows)
+            .unwrap()
+            .inverse()
+            .unwrap()
+            .into_rows();
+        let expected = [
+            [-0.2f64, 0.4f64, 0.2f64],
+            [0.2f64, 0.6f64, -0.2f64],
+            [0.6f64, -1.2f64, 0.4f64],
+        ];
+        for (row, expected_row) in inverse.iter().zip(expected) {
+            for (value, expected) in row.iter().zip(expected_row) {
+                assert!(cmp(value.to_f64(), expected));
+            }
+        }
+
+        let singular = to_rows(&[&[1f64, 2f64], &[2f64, 4f64]]);
+        let determinant = RealMatrix::from_rows(&singular).unwrap().determinant();
+        assert_eq!(crate::primitive!(determinant), 0f64);
+        assert!(RealMatrix::from_rows(&singular)
+            .unwrap()
+            .inverse()
+            .is_none());
+    }
+
+    #[test]
+    fn test_permutation_sign() {
+        assert_eq!(permutation_sign(&[0, 1, 2]), 1f64);
+        assert_eq!(permutation_sign(&[1, 0, 2]), -1f64);
+        assert_eq!(permutation_sign(&[1, 2, 0]), 1f64);
+        assert_eq!(permutation_sign(&[3, 2, 1, 0]), 1f64);
+    }
+}
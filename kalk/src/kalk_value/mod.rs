@@ -11,14 +11,28 @@ pub mod regular;
 #[cfg(not(feature = "rug"))]
 pub use regular::*;
 
+mod grid;
+mod integer;
+pub(crate) mod matrix;
 mod rounding;
+mod sparse;
 
-use crate::ast::Expr;
+pub use integer::BigInt;
+pub use rounding::RoundingOptions;
+pub use sparse::SparseVector;
+pub(crate) use sparse::VectorBuilder;
+
+use crate::ast::{Expr, ExprSpan};
 use crate::errors::KalkError;
 use crate::radix;
 use wasm_bindgen::prelude::*;
 
 const ACCEPTABLE_COMPARISON_MARGIN: f64 = 0.00000001;
+/// How close values need to be, relative to their size, to be approximately equal with ≈,
+/// eg. 1000 ≈ 1000.0001.
+const APPROXIMATE_EQUALITY_TOLERANCE: f64 = 1e-6;
+/// 2^53, the largest number where floats still can represent every integer.
+const MAX_EXACT_FLOAT: f64 = 9007199254740992f64;
 
 #[macro_export]
 #[cfg(not(feature = "rug"))]
@@ -56,8 +70,8 @@ macro_rules! primitive {
 #[macro_export]
 macro_rules! as_number_or_return {
     ($x:expr) => {{
-        if let KalkValue::Number(real, imaginary, unit) = $x {
-            (
+        match $x {
+            KalkValue::Number(real, imaginary, unit) => (
                 real,
                 if imaginary == -0f64 {
                     float!(0)
@@ -65,12 +79,14 @@ macro_rules! as_number_or_return {
                     imaginary
                 },
                 unit,
-            )
-        } else {
-            return Err(KalkError::UnexpectedType(
-                $x.get_type_name(),
-                vec![String::from("number")],
-            ));
+            ),
+            KalkValue::Integer(integer) => (float!(integer.to_f64()), float!(0), None),
+            _ => {
+                return Err(KalkError::UnexpectedType(
+                    $x.get_type_name(),
+                    vec![String::from("number")],
+                ));
+            }
         }
     }};
 }
@@ -97,10 +113,10 @@ macro_rules! as_vector_or_return {
 macro_rules! as_number_or_zero {
     ($x:expr) => {{
         use $crate::float;
-        if let KalkValue::Number(real, imaginary, unit) = $x {
-            (real, imaginary, unit)
-        } else {
-            (float!(0), float!(0), None)
+        match $x {
+            KalkValue::Number(real, imaginary, unit) => (real, imaginary, unit),
+            KalkValue::Integer(integer) => (float!(integer.to_f64()), float!(0), None),
+            _ => (float!(0), float!(0), None),
         }
     }};
 }
@@ -129,34 +145,58 @@ impl ScientificNotation {
     }
 }
 
-impl std::fmt::Display for ScientificNotation {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl ScientificNotation {
+    /// Same as `to_string`, but the value is written in the given format. With significant
+    /// figures, the exponent is increased if the value is rounded up to 10, eg. 9.9999×10^5
+    /// with 4 significant figures is 1.000×10^6.
+    pub fn to_string_with(&self, format: &NumberFormat) -> String {
         let sign = if self.negative { "-" } else { "" };
-        let digits_and_mul = if self.value == 1f64 {
+        let mut exponent = self.exponent;
+        let digits_and_mul = if let Some(significant_figures) = significant_figures(format) {
+            let scientific = format!("{:.*e}", significant_figures - 1, self.value.abs());
+            let (mantissa, carry) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+            exponent += carry.parse::<i32>().unwrap_or(0);
+
+            format!("{}×", grouped(mantissa, format))
+        } else if self.value.abs() == 1f64 {
             String::new()
         } else {
-            format!("{}×", format_number(self.value))
+            format!("{}×", format_number_with(self.value.abs(), format))
         };
 
-        write!(
-            f,
+        format!(
             "{}{}10^{} {}",
             sign,
             digits_and_mul,
-            self.exponent - 1,
+            exponent - 1,
             if self.imaginary { "i" } else { "" }
         )
     }
 }
 
+impl std::fmt::Display for ScientificNotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_string_with(&NumberFormat::default()))
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub enum KalkValue {
     #[cfg(not(feature = "rug"))]
     Number(f64, f64, Option<String>),
     #[cfg(feature = "rug")]
     Number(Float, Float, Option<String>),
+    /// An exact integer that is too large to be a float without losing precision, eg. 2^200.
+    /// Integers that fit in a float are numbers, so only results of operations on integers,
+    /// like 2^200 or 30!, are of this kind. They become numbers again in inexact operations.
+    Integer(BigInt),
     Boolean(bool),
+    /// Text, eg. the result of `format(pi, "%.3f")`, to label results in scripts.
+    String(String),
     Vector(Vec<KalkValue>),
+    /// A vector of mostly zeros from a comprehension. Only some operations support it,
+    /// eg. addition, scaling, indexing and `sum`, and the others get a regular vector.
+    SparseVector(SparseVector),
     Matrix(Vec<Vec<KalkValue>>),
 }
 
@@ -167,18 +207,25 @@ impl std::fmt::Display for KalkValue {
                 let as_str = format_number(primitive!(real));
 
                 if self.has_imaginary() {
-                    let imaginary_as_str = format_number(primitive!(imaginary).abs());
-                    let sign = if imaginary < &0f64 { "-" } else { "+" };
+                    // The imaginary unit is written as i instead of 1i
+                    let imaginary_as_str = match format_number(primitive!(imaginary).abs()) {
+                        one if one == "1" => String::new(),
+                        imaginary_as_str => imaginary_as_str,
+                    };
+                    let is_negative = imaginary < &0f64;
 
                     if &as_str == "0" {
-                        write!(f, "{}", imaginary_as_str)
+                        let sign = if is_negative { "-" } else { "" };
+                        write!(f, "{}{}i", sign, imaginary_as_str)
                     } else {
+                        let sign = if is_negative { "-" } else { "+" };
                         write!(f, "{} {} {}i", as_str, sign, imaginary_as_str)
                     }
                 } else {
                     write!(f, "{}", as_str)
                 }
             }
+            KalkValue::Integer(integer) => write!(f, "{}", integer),
             KalkValue::Boolean(is_true) => {
                 if *is_true {
                     write!(f, "true")
@@ -186,49 +233,52 @@ impl std::fmt::Display for KalkValue {
                     write!(f, "false")
                 }
             }
+            KalkValue::String(value) => write!(f, "{}", value),
             KalkValue::Vector(values) => {
                 write!(
                     f,
                     "({})",
                     values
                         .iter()
-                        .map(|x| x.estimate().unwrap_or_else(|| x.to_string()))
+                        .map(|x| element_to_string(x, 10))
                         .collect::<Vec<String>>()
                         .join(", ")
                 )
             }
+            KalkValue::SparseVector(vector) => {
+                write!(f, "{}", KalkValue::Vector(vector.to_dense()))
+            }
             KalkValue::Matrix(rows) => {
-                let mut value_strings = Vec::new();
-                let mut longest = 0;
-                for row in rows {
-                    for value in row {
-                        let value_str = value.estimate().unwrap_or_else(|| value.to_string());
-                        longest = longest.max(value_str.len());
-                        value_strings.push(format!("{},", value_str));
-                    }
-
-                    value_strings.last_mut().unwrap().pop(); // Trailing comma
-                    value_strings.push(String::from("\n"));
-                }
+                write!(
+                    f,
+                    "{}",
+                    grid::to_grid_string(&matrix_cells(rows, 10), false)
+                )
+            }
+        }
+    }
+}
 
-                let mut result = String::from("[");
-                for value_str in value_strings {
-                    if value_str == "\n" {
-                        result.push_str("\n ");
-                    } else {
-                        result.push_str(&format!("{:width$} ", value_str, width = longest + 1));
-                    }
-                }
+fn matrix_cells(rows: &[Vec<KalkValue>], radix: u8) -> Vec<Vec<String>> {
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .map(|value| element_to_string(value, radix))
+                .collect()
+        })
+        .collect()
+}
 
-                result.pop(); // Trailing new-line
-                result.pop(); // Trailing space
-                result.pop(); // Trailing space
-                result.pop(); // Trailing comma
-                result.push(']');
+/// Format a value inside a vector or matrix, eg. `1/2 km`, or `ff₁₆` if the radix is 16.
+fn element_to_string(value: &KalkValue, radix: u8) -> String {
+    if radix != 10 {
+        return value.to_string_pretty_with(radix, &RoundingOptions::default());
+    }
 
-                write!(f, "{}", result)
-            }
-        }
+    let value_str = value.estimate().unwrap_or_else(|| value.to_string());
+    match value.get_unit() {
+        Some(unit) => format!("{} {}", value_str, unit),
+        None => value_str,
     }
 }
 
@@ -239,22 +289,47 @@ impl KalkValue {
 
     pub fn get_type_name(&self) -> String {
         match self {
-            KalkValue::Number(_, _, _) => String::from("number"),
+            KalkValue::Number(_, _, _) | KalkValue::Integer(_) => String::from("number"),
             KalkValue::Boolean(_) => String::from("boolean"),
-            KalkValue::Vector(_) => String::from("vector"),
+            KalkValue::String(_) => String::from("string"),
+            KalkValue::Vector(_) | KalkValue::SparseVector(_) => String::from("vector"),
             KalkValue::Matrix(_) => String::from("matrix"),
         }
     }
 
+    /// The value as it is written when it is put into a string, eg. `5 m`.
+    /// Strings are written as they are, without quotes.
+    pub(crate) fn to_text(&self) -> String {
+        match (self, self.get_unit()) {
+            (KalkValue::String(value), _) => value.clone(),
+            (_, Some(unit)) => format!("{} {}", self, unit),
+            _ => self.to_string(),
+        }
+    }
+
+    /// Join the values as text, eg. `"n = " + 3` gives `n = 3`.
+    fn concatenate(&self, rhs: &KalkValue) -> KalkValue {
+        KalkValue::String(format!("{}{}", self.to_text(), rhs.to_text()))
+    }
+
     pub fn to_string_big(&self) -> String {
         fn trim_num(num_str: String) -> String {
-            num_str
-                .trim_end_matches('0')
-                .trim_end_matches('.')
-                .to_string()
+            let num_str = rounding::normalize_zero_str(num_str);
+            if num_str.contains('.') {
+                num_str
+                    .trim_end_matches('0')
+                    .trim_end_matches('.')
+                    .to_string()
+            } else {
+                num_str
+            }
         }
 
         if let KalkValue::Number(real, imaginary, _) = self {
+            if !real.is_finite() || !imaginary.is_finite() {
+                return self.to_string();
+            }
+
             if !self.has_imaginary() {
                 return trim_num(real.to_string());
             }
@@ -272,11 +347,24 @@ impl KalkValue {
     }
 
     pub fn to_string_real(&self, radix: u8) -> String {
-        radix::to_radix_pretty(self.to_f64(), radix)
+        self.to_string_real_with(radix, &NumberFormat::default())
+    }
+
+    fn to_string_real_with(&self, radix: u8, format: &NumberFormat) -> String {
+        format_part(self.to_f64(), radix, format)
     }
 
     pub fn to_string_imaginary(&self, radix: u8, include_i: bool) -> String {
-        let value = radix::to_radix_pretty(self.imaginary_to_f64(), radix);
+        self.to_string_imaginary_with(radix, include_i, &NumberFormat::default())
+    }
+
+    fn to_string_imaginary_with(
+        &self,
+        radix: u8,
+        include_i: bool,
+        format: &NumberFormat,
+    ) -> String {
+        let value = format_part(self.imaginary_to_f64(), radix, format);
         if include_i && value == "1" {
             String::from("i")
         } else if include_i && value == "-1" {
@@ -289,8 +377,28 @@ impl KalkValue {
     }
 
     pub fn to_string_pretty_radix(&self, radix: u8) -> String {
+        self.to_string_pretty_with(radix, &RoundingOptions::default())
+    }
+
+    /// Same as `to_string_pretty_radix`, but the estimate is made with the given options.
+    pub fn to_string_pretty_with(&self, radix: u8, options: &RoundingOptions) -> String {
         let (real, imaginary, unit) = match self {
             KalkValue::Number(real, imaginary, unit) => (real, imaginary, unit),
+            KalkValue::Integer(integer) if radix == 10 => {
+                return grouped(&integer.to_string(), &options.number_format)
+            }
+            KalkValue::Integer(integer) => return radix::integer_to_radix_pretty(integer, radix),
+            KalkValue::Matrix(rows) => {
+                return grid::to_grid_string(&matrix_cells(rows, radix), true)
+            }
+            KalkValue::Vector(values) => {
+                let values: Vec<String> = values
+                    .iter()
+                    .map(|value| element_to_string(value, radix))
+                    .collect();
+
+                return format!("({})", values.join(", "));
+            }
             _ => return self.to_string(),
         };
 
@@ -304,19 +412,39 @@ impl KalkValue {
             return format!("{}∞", if real_f64.is_sign_negative() { "-" } else { "" });
         }
 
+        if let (Some(unit), 10, false) = (unit, radix, self.has_imaginary()) {
+            if unit == crate::duration::TIMESTAMP_UNIT {
+                return crate::duration::format_timestamp(real_f64);
+            }
+
+            // Show eg. 4.75 h as 4h 45min
+            if crate::duration::is_duration_unit(unit) && real_f64.fract() != 0f64 {
+                let seconds = real_f64 * crate::duration::seconds_per_unit(unit).unwrap();
+                let formatted = crate::duration::format_duration(seconds);
+                if formatted.contains(' ') {
+                    return formatted;
+                }
+            }
+        }
+
         let sci_notation_real = self.to_scientific_notation(ComplexNumberType::Real);
         let mut new_real = real.clone();
         let mut new_imaginary = imaginary.clone();
         let mut has_scientific_notation = false;
         let result_str = if (-6..8).contains(&sci_notation_real.exponent) || real == &0f64 {
-            self.to_string_real(radix)
+            self.to_string_real_with(radix, &options.number_format)
         } else if sci_notation_real.exponent <= -14 {
             new_real = float!(0);
             String::from("0")
         } else if radix == 10 {
             has_scientific_notation = true;
 
-            sci_notation_real.to_string().trim().to_string()
+            sci_notation_real
+                .to_string_with(&options.number_format)
+                .trim()
+                .to_string()
+        } else if let Some(integer) = BigInt::from_f64(real_f64) {
+            radix::integer_to_radix_pretty(&integer, radix)
         } else {
             return String::new();
         };
@@ -326,14 +454,17 @@ impl KalkValue {
             || imaginary == &0f64
             || imaginary == &1f64
         {
-            self.to_string_imaginary(radix, true)
+            self.to_string_imaginary_with(radix, true, &options.number_format)
         } else if sci_notation_imaginary.exponent <= -14 {
             new_imaginary = float!(0);
             String::from("0")
         } else if radix == 10 {
             has_scientific_notation = true;
 
-            sci_notation_imaginary.to_string().trim().to_string()
+            sci_notation_imaginary
+                .to_string_with(&options.number_format)
+                .trim()
+                .to_string()
         } else {
             return String::new();
         };
@@ -364,7 +495,7 @@ impl KalkValue {
 
         let new_value = KalkValue::Number(new_real, new_imaginary, unit.clone());
 
-        if let Some(estimate) = new_value.estimate() {
+        if let Some(estimate) = new_value.estimate_with(options) {
             if estimate != output && radix == 10 {
                 output.push_str(&format!(" ≈ {}", estimate));
             }
@@ -379,6 +510,85 @@ impl KalkValue {
         self.to_string_pretty_radix(10)
     }
 
+    /// Same as `to_string_pretty_with`, but the estimate is written after the decimals in
+    /// parentheses. If some of the decimals are cut off, the decimals are marked with ≈ and …,
+    /// eg. `≈ 0.3333333333… (1/3)`, and otherwise the estimate is unless it's exact,
+    /// eg. `0.9999991 (≈ 1)` but `0.5 (1/2)`.
+    pub(crate) fn to_string_side_by_side(&self, options: &RoundingOptions) -> String {
+        let decimal = self.to_string_pretty_with(
+            10,
+            &RoundingOptions {
+                estimate: false,
+                ..options.clone()
+            },
+        );
+        let estimate = match self.estimate_with(options) {
+            Some(estimate) if estimate != decimal && !decimal.contains('≈') => estimate,
+            _ => return self.to_string_pretty_with(10, options),
+        };
+
+        let is_exact = rounding::has_exact_decimals(self.to_f64())
+            && rounding::has_exact_decimals(self.imaginary_to_f64());
+        // A fraction is exact if the value times its denominator is an integer, eg. 0.5 = 1/2
+        let is_exact_fraction = !self.has_imaginary()
+            && estimate
+                .rsplit_once('/')
+                .and_then(|(_, denom)| denom.parse::<f64>().ok())
+                .filter(|denom| (self.to_f64() * denom).fract() == 0f64)
+                .is_some();
+        if is_exact && is_exact_fraction {
+            format!("{} ({})", decimal, estimate)
+        } else if is_exact {
+            format!("{} (≈ {})", decimal, estimate)
+        } else if decimal.ends_with(|c: char| c.is_ascii_digit()) {
+            format!("≈ {}… ({})", decimal, estimate)
+        } else {
+            format!("≈ {} ({})", decimal, estimate)
+        }
+    }
+
+    /// Same as `to_string_pretty_with`, but only the decimals that are certain, given bounds
+    /// for the exact value, are shown. Uncertain decimals are cut off and replaced with "…".
+    pub(crate) fn to_string_pretty_bounded(
+        &self,
+        lower: f64,
+        upper: f64,
+        options: &RoundingOptions,
+    ) -> String {
+        let is_plain_real = match self {
+            KalkValue::Number(_, imaginary, None) => {
+                imaginary == &0f64
+                    && self.to_f64().is_finite()
+                    && (self.to_f64() == 0f64
+                        || (-6..8).contains(
+                            &self
+                                .to_scientific_notation(ComplexNumberType::Real)
+                                .exponent,
+                        ))
+            }
+            _ => false,
+        };
+        if !is_plain_real {
+            return self.to_string_pretty_with(10, options);
+        }
+
+        match rounding::certain_digits(lower, upper) {
+            Some((digits, rounding::MAX_CERTAIN_DECIMALS)) => {
+                let output = rounding::trim_zeroes(&digits);
+                match self.estimate_with(options) {
+                    Some(estimate) if estimate != output => format!("{} ≈ {}", output, estimate),
+                    _ => output,
+                }
+            }
+            Some((digits, _)) => format!("{}…", digits),
+            None => format!(
+                "[{}, {}]",
+                rounding::trim_zeroes(&format!("{:.*}", rounding::MAX_CERTAIN_DECIMALS, lower)),
+                rounding::trim_zeroes(&format!("{:.*}", rounding::MAX_CERTAIN_DECIMALS, upper)),
+            ),
+        }
+    }
+
     pub fn to_string_with_unit(&self) -> String {
         match self {
             KalkValue::Number(_, _, unit) => {
@@ -389,9 +599,39 @@ impl KalkValue {
     }
 
     /// Get an estimate of what the number is, eg. 3.141592 => π. Does not work properly with scientific notation.
+    /// This is the estimate that is shown by `to_string_pretty`.
     pub fn estimate(&self) -> Option<String> {
-        let rounded_real = rounding::estimate(self, ComplexNumberType::Real);
-        let rounded_imaginary = rounding::estimate(self, ComplexNumberType::Imaginary);
+        self.estimate_with(&RoundingOptions::default())
+    }
+
+    /// Same as `estimate`, but the options decide what the number may be written as,
+    /// eg. only fractions and not constants. None is returned if there is no better way
+    /// to write the number than as it is.
+    pub fn estimate_with(&self, options: &RoundingOptions) -> Option<String> {
+        if !options.estimate {
+            return None;
+        }
+
+        let estimate = self.estimate_parts(options)?;
+        if options.vulgar_fractions {
+            Some(rounding::to_vulgar_fractions(&estimate))
+        } else {
+            Some(estimate)
+        }
+    }
+
+    fn estimate_parts(&self, options: &RoundingOptions) -> Option<String> {
+        // The imaginary part is estimated without its sign, which is added as an operator,
+        // so that eg. -(π + 1)i doesn't become - -π - 1i.
+        let absolute_imaginary = match self {
+            KalkValue::Number(real, imaginary, _) => {
+                KalkValue::Number(real.clone(), imaginary.clone().abs(), None)
+            }
+            _ => return None,
+        };
+        let rounded_real = rounding::estimate(self, ComplexNumberType::Real, options);
+        let rounded_imaginary =
+            rounding::estimate(&absolute_imaginary, ComplexNumberType::Imaginary, options);
 
         if let (None, None) = (&rounded_real, &rounded_imaginary) {
             return None;
@@ -407,7 +647,7 @@ impl KalkValue {
         let imaginary_value = if let Some(value) = rounded_imaginary {
             Some(value)
         } else if self.has_imaginary() {
-            Some(self.to_string_imaginary(10, false))
+            Some(absolute_imaginary.to_string_imaginary(10, false))
         } else {
             None
         };
@@ -425,29 +665,98 @@ impl KalkValue {
                     return Some(String::from("0"));
                 }
             } else {
-                let sign = if value.starts_with('-') { "-" } else { "+" };
-                let value = match value.as_ref() {
-                    "1" => String::from("i"),
-                    "-1" => String::from("-i"),
-                    _ => format!("{}i", value),
-                };
+                let is_negative = self.imaginary_to_f64() < 0f64;
+                let value = rounding::with_imaginary_unit(&value);
 
                 // If there is a real value as well
                 if !output.is_empty() {
-                    output.push_str(&format!(" {} {}", sign, value.trim_start_matches('-')));
+                    let sign = if is_negative { "-" } else { "+" };
+                    output.push_str(&format!(" {} {}", sign, value));
+                } else if is_negative {
+                    output.push_str(&format!("-{}", value));
                 } else {
                     output.push_str(&value);
                 }
             }
         }
 
+        // Values on the unit circle at a multiple of eg. π/6 are always written in polar form,
+        // eg. e^(iπ/3), while other values only are when the option is set and it's shorter.
+        if self.has_real() && self.has_imaginary() {
+            let on_unit_circle = (self.to_f64().hypot(self.imaginary_to_f64()) - 1f64).abs() < 1e-9;
+            if let Some(polar) = self.estimate_polar(options) {
+                if on_unit_circle
+                    || (options.polar && polar.chars().count() < output.chars().count())
+                {
+                    return Some(polar);
+                }
+            }
+        }
+
         Some(output)
     }
 
+    /// Estimate a complex number as r·e^(iθ), eg. e^(iπ/4), or as r·cis(θ) in degrees,
+    /// eg. cis(45°), if the angle is a multiple of π and the absolute value can be estimated.
+    fn estimate_polar(&self, options: &RoundingOptions) -> Option<String> {
+        if !options.constants {
+            return None;
+        }
+
+        let (real, imaginary) = (self.to_f64(), self.imaginary_to_f64());
+        let radians = imaginary.atan2(real);
+        // Multiples of π/2 are simpler as eg. 2i
+        let angle = rounding::equivalent_constant(radians).filter(|angle| {
+            angle.contains('π')
+                && !angle.contains(' ')
+                && !matches!(angle.as_str(), "π/2" | "-π/2" | "π" | "-π")
+        })?;
+        let absolute_value = real.hypot(imaginary);
+        let absolute_value = if (absolute_value - 1f64).abs() < 1e-9 {
+            String::new()
+        } else if (absolute_value - absolute_value.round()).abs() < 1e-9 {
+            (absolute_value.round() as i64).to_string()
+        } else {
+            let options = RoundingOptions {
+                polar: false,
+                vulgar_fractions: false,
+                ..options.clone()
+            };
+            let estimate = KalkValue::from(absolute_value)
+                .estimate_with(&options)
+                .filter(|estimate| !estimate.contains(' '))?;
+
+            format!("{}·", estimate)
+        };
+
+        if options.degrees {
+            let degrees = radians.to_degrees().round() as i64;
+
+            return Some(format!("{}cis({}°)", absolute_value, degrees));
+        }
+
+        // Eg. -3π/4 => -3iπ/4
+        let coefficient_len = angle
+            .chars()
+            .take_while(|c| *c == '-' || c.is_ascii_digit())
+            .count();
+        let (coefficient, angle) = angle.split_at(coefficient_len);
+
+        Some(format!("{}e^({}i{})", absolute_value, coefficient, angle))
+    }
+
+    /// Same as `estimate`, but simple fractions are written as unicode vulgar fractions, eg. 2 + 1/2 => 2½.
+    pub fn estimate_with_vulgar_fractions(&self) -> Option<String> {
+        self.estimate_with(&RoundingOptions {
+            vulgar_fractions: true,
+            ..Default::default()
+        })
+    }
+
     /// Basic up/down rounding from 0.00xxx or 0.999xxx or xx.000xxx, etc.
     pub fn round(&self) -> Option<KalkValue> {
-        let rounded_real = rounding::round(self, ComplexNumberType::Real);
-        let rounded_imaginary = rounding::round(self, ComplexNumberType::Imaginary);
+        let rounded_real = rounding::round(self, ComplexNumberType::Real, None);
+        let rounded_imaginary = rounding::round(self, ComplexNumberType::Imaginary, None);
 
         if let (None, None) = (&rounded_real, &rounded_imaginary) {
             return None;
@@ -482,10 +791,10 @@ impl KalkValue {
     }
 
     pub fn has_real(&self) -> bool {
-        if let KalkValue::Number(real, _, _) = self {
-            real != &0f64
-        } else {
-            false
+        match self {
+            KalkValue::Number(real, _, _) => real != &0f64,
+            KalkValue::Integer(integer) => !integer.is_zero(),
+            _ => false,
         }
     }
 
@@ -505,6 +814,62 @@ impl KalkValue {
         }
     }
 
+    /// The value as an exact integer, if it's a real integer without a unit. Numbers larger
+    /// than 2^53 aren't counted, since they may already have been rounded.
+    pub(crate) fn as_exact_integer(&self) -> Option<BigInt> {
+        match self {
+            KalkValue::Integer(integer) => Some(integer.clone()),
+            KalkValue::Number(real, imaginary, None) if imaginary == &0f64 => {
+                let value = primitive!(real);
+                if real == &value && value.abs() <= MAX_EXACT_FLOAT {
+                    BigInt::from_f64(value)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// n! as an exact integer, if the value is a non-negative integer
+    /// and the factorial is too large to be exact as a float.
+    pub(crate) fn exact_factorial(&self) -> Option<KalkValue> {
+        let n = self.as_exact_integer()?.to_u64()?;
+
+        // 18! is the largest factorial that is smaller than 2^53
+        if n <= 18 {
+            return None;
+        }
+
+        BigInt::factorial(n).map(KalkValue::from)
+    }
+
+    /// Turn a sparse vector into a regular vector.
+    pub(crate) fn into_dense(self) -> KalkValue {
+        match self {
+            KalkValue::SparseVector(vector) => KalkValue::Vector(vector.to_dense()),
+            _ => self,
+        }
+    }
+
+    /// Turn exact integers into (inexact) numbers, including the ones in vectors and matrices.
+    pub(crate) fn into_number(self) -> KalkValue {
+        match self {
+            KalkValue::Integer(integer) => KalkValue::from(integer.to_f64()),
+            KalkValue::Vector(values) => {
+                KalkValue::Vector(values.into_iter().map(KalkValue::into_number).collect())
+            }
+            KalkValue::Matrix(rows) => KalkValue::Matrix(
+                rows.into_iter()
+                    .map(|row| row.into_iter().map(KalkValue::into_number).collect())
+                    .collect(),
+            ),
+            _ => self,
+        }
+    }
+
+    /// The value as scientific notation. Values that are too large for an f64,
+    /// and other values that aren't finite, are given as they are, with the exponent 0.
     pub fn to_scientific_notation(
         &self,
         complex_number_type: ComplexNumberType,
@@ -513,6 +878,15 @@ impl KalkValue {
             ComplexNumberType::Real => self.to_f64(),
             ComplexNumberType::Imaginary => self.imaginary_to_f64(),
         };
+        if !value.is_finite() {
+            return ScientificNotation {
+                negative: value < 0f64,
+                value,
+                exponent: 0,
+                imaginary: complex_number_type == ComplexNumberType::Imaginary,
+            };
+        }
+
         let exponent = value.abs().log10().floor() as i32 + 1;
 
         ScientificNotation {
@@ -540,6 +914,15 @@ impl KalkValue {
         }
     }
 
+    /// The number with another unit, or without a unit if it's None.
+    pub(crate) fn with_unit(self, unit: Option<String>) -> KalkValue {
+        match self {
+            KalkValue::Number(real, imaginary, _) => KalkValue::Number(real, imaginary, unit),
+            KalkValue::Integer(_) if unit.is_some() => self.into_number().with_unit(unit),
+            _ => self,
+        }
+    }
+
     pub(crate) fn convert_to_unit(
         &self,
         context: &mut crate::interpreter::Context,
@@ -548,7 +931,7 @@ impl KalkValue {
         if let KalkValue::Number(real, _, unit) = self {
             let result = crate::interpreter::convert_unit(
                 context,
-                &Expr::Literal(primitive!(real)),
+                &Expr::Literal(primitive!(real), ExprSpan::default()),
                 unit.as_ref(),
                 Some(&to_unit.to_string()),
             );
@@ -568,7 +951,11 @@ impl KalkValue {
         context: &mut crate::interpreter::Context,
         rhs: KalkValue,
     ) -> Result<KalkValue, KalkError> {
-        let right = calculate_unit(context, &self, rhs.clone()).unwrap_or(rhs);
+        if let Some(result) = add_temperatures(&self, &rhs, false) {
+            return result;
+        }
+
+        let right = calculate_unit(context, &self, rhs.clone())?.unwrap_or(rhs);
         self.add_without_unit(&right)
     }
 
@@ -577,7 +964,11 @@ impl KalkValue {
         context: &mut crate::interpreter::Context,
         rhs: KalkValue,
     ) -> Result<KalkValue, KalkError> {
-        let right = calculate_unit(context, &self, rhs.clone()).unwrap_or(rhs);
+        if let Some(result) = add_temperatures(&self, &rhs, true) {
+            return result;
+        }
+
+        let right = calculate_unit(context, &self, rhs.clone())?.unwrap_or(rhs);
         self.sub_without_unit(&right)
     }
 
@@ -586,7 +977,14 @@ impl KalkValue {
         context: &mut crate::interpreter::Context,
         rhs: KalkValue,
     ) -> Result<KalkValue, KalkError> {
-        let right = calculate_unit(context, &self, rhs.clone()).unwrap_or(rhs);
+        if let Some((right, unit)) = combine_units(context, &self, rhs.clone(), 1) {
+            return Ok(self
+                .with_unit(None)
+                .mul_without_unit(&right)?
+                .with_unit(unit));
+        }
+
+        let right = calculate_unit(context, &self, rhs.clone())?.unwrap_or(rhs);
         self.mul_without_unit(&right)
     }
 
@@ -595,7 +993,14 @@ impl KalkValue {
         context: &mut crate::interpreter::Context,
         rhs: KalkValue,
     ) -> Result<KalkValue, KalkError> {
-        let right = calculate_unit(context, &self, rhs.clone()).unwrap_or(rhs);
+        if let Some((right, unit)) = combine_units(context, &self, rhs.clone(), -1) {
+            return Ok(self
+                .with_unit(None)
+                .div_without_unit(&right)?
+                .with_unit(unit));
+        }
+
+        let right = calculate_unit(context, &self, rhs.clone())?.unwrap_or(rhs);
         self.div_without_unit(&right)
     }
 
@@ -604,7 +1009,19 @@ impl KalkValue {
         context: &mut crate::interpreter::Context,
         rhs: KalkValue,
     ) -> Result<KalkValue, KalkError> {
-        let right = calculate_unit(context, &self, rhs.clone()).unwrap_or(rhs);
+        // Eg. `(3 m)^2` gives 9 m^2
+        let exponent = rhs
+            .as_exact_integer()
+            .map(|exponent| exponent.to_f64())
+            .filter(|exponent| exponent.abs() <= i32::MAX as f64);
+        if let (Some(unit), Some(exponent)) = (self.get_unit(), exponent) {
+            let unit = crate::units::Dimension::parse(unit)
+                .pow(exponent as i32)
+                .into_unit();
+            return Ok(self.with_unit(None).pow_without_unit(&rhs)?.with_unit(unit));
+        }
+
+        let right = calculate_unit(context, &self, rhs.clone())?.unwrap_or(rhs);
         self.pow_without_unit(&right)
     }
 
@@ -613,8 +1030,12 @@ impl KalkValue {
         context: &mut crate::interpreter::Context,
         rhs: KalkValue,
     ) -> Result<KalkValue, KalkError> {
+        if matches!(self, KalkValue::Integer(_)) || matches!(rhs, KalkValue::Integer(_)) {
+            return exact_or_float(self, &rhs, exact_rem, |x, y| x.rem(context, y));
+        }
+
         Ok(if let KalkValue::Number(real, _, _) = &self {
-            let right = calculate_unit(context, &self, rhs.clone()).unwrap_or(rhs);
+            let right = calculate_unit(context, &self, rhs.clone())?.unwrap_or(rhs);
             if let KalkValue::Number(right_real, _, right_unit) = right {
                 KalkValue::Number(real % right_real, float!(0f64), right_unit)
             } else {
@@ -625,12 +1046,109 @@ impl KalkValue {
         })
     }
 
+    /// The Euclidean remainder, which is never negative, eg. -7 mod 3 = 2.
+    pub(crate) fn rem_euclid(
+        self,
+        context: &mut crate::interpreter::Context,
+        rhs: KalkValue,
+    ) -> Result<KalkValue, KalkError> {
+        Ok(self.integer_division(context, rhs, true)?.1)
+    }
+
+    /// The Euclidean quotient, which is rounded so that the
+    /// remainder is never negative, eg. -7 div 3 = -3.
+    pub(crate) fn div_euclid(
+        self,
+        context: &mut crate::interpreter::Context,
+        rhs: KalkValue,
+    ) -> Result<KalkValue, KalkError> {
+        Ok(self.integer_division(context, rhs, true)?.0)
+    }
+
+    /// The quotient rounded towards zero, eg. -7 quot 3 = -2.
+    pub(crate) fn quot(
+        self,
+        context: &mut crate::interpreter::Context,
+        rhs: KalkValue,
+    ) -> Result<KalkValue, KalkError> {
+        Ok(self.integer_division(context, rhs, false)?.0)
+    }
+
+    /// Divide two real numbers, giving the integer quotient and the remainder.
+    /// If `euclidean` is false, the quotient is truncated, like with `%`.
+    fn integer_division(
+        self,
+        context: &mut crate::interpreter::Context,
+        rhs: KalkValue,
+        euclidean: bool,
+    ) -> Result<(KalkValue, KalkValue), KalkError> {
+        let right = calculate_unit(context, &self, rhs.clone())?.unwrap_or(rhs);
+        self.integer_division_without_unit(right, euclidean)
+    }
+
+    /// The quotient and remainder, eg. (3, 2) for 17 and 5.
+    pub(crate) fn integer_division_without_unit(
+        self,
+        right: KalkValue,
+        euclidean: bool,
+    ) -> Result<(KalkValue, KalkValue), KalkError> {
+        if matches!(self, KalkValue::Integer(_)) || matches!(right, KalkValue::Integer(_)) {
+            if let (Some(dividend), Some(divisor)) =
+                (self.as_exact_integer(), right.as_exact_integer())
+            {
+                if let Some((mut quotient, mut remainder)) = dividend.div_rem(&divisor) {
+                    if euclidean && remainder.is_negative() {
+                        let one = BigInt::from_u64(1);
+                        remainder = &remainder + &divisor.abs();
+                        quotient = if divisor.is_negative() {
+                            &quotient + &one
+                        } else {
+                            &quotient - &one
+                        };
+                    }
+
+                    return Ok((KalkValue::from(quotient), KalkValue::from(remainder)));
+                }
+            }
+
+            return self
+                .into_number()
+                .integer_division_without_unit(right.into_number(), euclidean);
+        }
+
+        if let (KalkValue::Number(real, _, _), KalkValue::Number(right_real, _, right_unit)) =
+            (&self, &right)
+        {
+            if self.has_imaginary() || right.has_imaginary() {
+                return Err(KalkError::ExpectedReal);
+            }
+
+            let mut remainder = real.clone() % right_real.clone();
+            if euclidean && remainder < 0f64 {
+                remainder += right_real.clone().abs();
+            }
+
+            let quotient = ((real.clone() - remainder.clone()) / right_real.clone()).round();
+
+            return Ok((
+                KalkValue::Number(quotient, float!(0f64), right_unit.clone()),
+                KalkValue::Number(remainder, float!(0f64), right_unit.clone()),
+            ));
+        }
+
+        Err(KalkError::IncompatibleTypesForOperation(
+            String::from("integer division"),
+            self.get_type_name(),
+            right.get_type_name(),
+        ))
+    }
+
     pub(crate) fn eq(
         self,
         context: &mut crate::interpreter::Context,
         rhs: KalkValue,
     ) -> Result<KalkValue, KalkError> {
-        let right = calculate_unit(context, &self, rhs.clone()).unwrap_or(rhs);
+        let right = calculate_unit(context, &self, rhs.clone())?.unwrap_or(rhs);
         self.eq_without_unit(&right)
     }
 
@@ -639,16 +1157,25 @@ impl KalkValue {
         context: &mut crate::interpreter::Context,
         rhs: KalkValue,
     ) -> Result<KalkValue, KalkError> {
-        let right = calculate_unit(context, &self, rhs.clone()).unwrap_or(rhs);
+        let right = calculate_unit(context, &self, rhs.clone())?.unwrap_or(rhs);
         self.not_eq_without_unit(&right)
     }
 
+    pub(crate) fn approx_eq(
+        self,
+        context: &mut crate::interpreter::Context,
+        rhs: KalkValue,
+    ) -> Result<KalkValue, KalkError> {
+        let right = calculate_unit(context, &self, rhs.clone())?.unwrap_or(rhs);
+        self.approx_eq_without_unit(&right, None)
+    }
+
     pub(crate) fn greater_than(
         self,
         context: &mut crate::interpreter::Context,
         rhs: KalkValue,
     ) -> Result<KalkValue, KalkError> {
-        let right = calculate_unit(context, &self, rhs.clone()).unwrap_or(rhs);
+        let right = calculate_unit(context, &self, rhs.clone())?.unwrap_or(rhs);
         self.greater_than_without_unit(&right)
     }
 
@@ -657,7 +1184,7 @@ impl KalkValue {
         context: &mut crate::interpreter::Context,
         rhs: KalkValue,
     ) -> Result<KalkValue, KalkError> {
-        let right = calculate_unit(context, &self, rhs.clone()).unwrap_or(rhs);
+        let right = calculate_unit(context, &self, rhs.clone())?.unwrap_or(rhs);
         self.less_than_without_unit(&right)
     }
 
@@ -666,7 +1193,7 @@ impl KalkValue {
         context: &mut crate::interpreter::Context,
         rhs: KalkValue,
     ) -> Result<KalkValue, KalkError> {
-        let right = calculate_unit(context, &self, rhs.clone()).unwrap_or(rhs);
+        let right = calculate_unit(context, &self, rhs.clone())?.unwrap_or(rhs);
         if let (KalkValue::Boolean(greater), KalkValue::Boolean(equal)) = (
             self.greater_than_without_unit(&right)?,
             self.eq_without_unit(&right)?,
@@ -682,7 +1209,7 @@ impl KalkValue {
         context: &mut crate::interpreter::Context,
         rhs: KalkValue,
     ) -> Result<KalkValue, KalkError> {
-        let right = calculate_unit(context, &self, rhs.clone()).unwrap_or(rhs);
+        let right = calculate_unit(context, &self, rhs.clone())?.unwrap_or(rhs);
         if let (KalkValue::Boolean(less), KalkValue::Boolean(equal)) = (
             self.less_than_without_unit(&right)?,
             self.eq_without_unit(&right)?,
@@ -724,17 +1251,23 @@ impl KalkValue {
             (
                 KalkValue::Number(real, imaginary, _),
                 KalkValue::Number(real_rhs, imaginary_rhs, unit),
-            ) => Ok(KalkValue::Number(
-                real + real_rhs,
-                imaginary + imaginary_rhs,
-                unit.clone(),
+            ) => Ok(exact_if_large(
+                &self,
+                rhs,
+                KalkValue::Number(real + real_rhs, imaginary + imaginary_rhs, unit.clone()),
+                |x, y| Some(x + y),
             )),
+            // Eg. "width: " + 5 m
+            (KalkValue::String(_), _) | (_, KalkValue::String(_)) => Ok(self.concatenate(rhs)),
             (KalkValue::Matrix(_), _) | (_, KalkValue::Matrix(_)) => {
                 calculate_matrix(self, rhs, &KalkValue::add_without_unit)
             }
             (KalkValue::Vector(_), _) | (_, KalkValue::Vector(_)) => {
                 calculate_vector(self, rhs, &KalkValue::add_without_unit)
             }
+            (KalkValue::Integer(_), _) | (_, KalkValue::Integer(_)) => {
+                exact_or_float(self, rhs, |x, y| Some(x + y), |x, y| x.add_without_unit(&y))
+            }
             _ => Err(KalkError::IncompatibleTypesForOperation(
                 String::from("addition"),
                 self.get_type_name(),
@@ -748,10 +1281,11 @@ impl KalkValue {
             (
                 KalkValue::Number(real, imaginary, _),
                 KalkValue::Number(real_rhs, imaginary_rhs, unit),
-            ) => Ok(KalkValue::Number(
-                real - real_rhs,
-                imaginary - imaginary_rhs,
-                unit.clone(),
+            ) => Ok(exact_if_large(
+                &self,
+                rhs,
+                KalkValue::Number(real - real_rhs, imaginary - imaginary_rhs, unit.clone()),
+                |x, y| Some(x - y),
             )),
             (KalkValue::Matrix(_), _) | (_, KalkValue::Matrix(_)) => {
                 calculate_matrix(self, rhs, &KalkValue::sub_without_unit)
@@ -759,6 +1293,9 @@ impl KalkValue {
             (KalkValue::Vector(_), _) | (_, KalkValue::Vector(_)) => {
                 calculate_vector(self, rhs, &KalkValue::sub_without_unit)
             }
+            (KalkValue::Integer(_), _) | (_, KalkValue::Integer(_)) => {
+                exact_or_float(self, rhs, |x, y| Some(x - y), |x, y| x.sub_without_unit(&y))
+            }
             _ => Err(KalkError::IncompatibleTypesForOperation(
                 String::from("subtraction"),
                 self.get_type_name(),
@@ -777,14 +1314,30 @@ impl KalkValue {
         };
 
         match (lhs, rhs) {
+            // Real numbers are multiplied on their own, since eg. ∞ times the imaginary part 0
+            // would make the imaginary part of the result undefined.
             (
                 KalkValue::Number(real, imaginary, _),
                 KalkValue::Number(real_rhs, imaginary_rhs, unit),
-            ) => Ok(KalkValue::Number(
-                // (a + bi)(c + di) = ac + adi + bci + bdi²
-                real.clone() * real_rhs - imaginary.clone() * imaginary_rhs,
-                real.clone() * imaginary_rhs + imaginary * real_rhs,
-                unit.clone(),
+            ) if *imaginary == 0f64 && *imaginary_rhs == 0f64 => Ok(exact_if_large(
+                lhs,
+                rhs,
+                KalkValue::Number(real.clone() * real_rhs, float!(0), unit.clone()),
+                exact_mul,
+            )),
+            (
+                KalkValue::Number(real, imaginary, _),
+                KalkValue::Number(real_rhs, imaginary_rhs, unit),
+            ) => Ok(exact_if_large(
+                lhs,
+                rhs,
+                KalkValue::Number(
+                    // (a + bi)(c + di) = ac + adi + bci + bdi²
+                    real.clone() * real_rhs - imaginary.clone() * imaginary_rhs,
+                    real.clone() * imaginary_rhs + imaginary * real_rhs,
+                    unit.clone(),
+                ),
+                exact_mul,
             )),
             (KalkValue::Matrix(_), KalkValue::Number(_, _, _)) => {
                 calculate_matrix(lhs.clone(), rhs, &KalkValue::mul_without_unit)
@@ -815,6 +1368,17 @@ impl KalkValue {
                 }
 
                 let rhs_columns = rows_rhs.first().unwrap();
+                let threshold = matrix::REAL_MATRIX_THRESHOLD;
+                if rows.len().max(lhs_columns.len()).max(rhs_columns.len()) >= threshold {
+                    let lhs_real = matrix::RealMatrix::from_rows(rows);
+                    let rhs_real = matrix::RealMatrix::from_rows(rows_rhs);
+                    if let (Some(lhs_real), Some(rhs_real)) = (lhs_real, rhs_real) {
+                        if let Some(result) = lhs_real.mul_blocked(&rhs_real) {
+                            return Ok(KalkValue::Matrix(result.into_rows()));
+                        }
+                    }
+                }
+
                 let mut result = vec![vec![KalkValue::from(0f64); rhs_columns.len()]; rows.len()];
 
                 // For every row in lhs
@@ -844,6 +1408,14 @@ impl KalkValue {
 
                 Ok(KalkValue::Vector(multiplied_values))
             }
+            (KalkValue::Number(_, _, _), KalkValue::Vector(values_rhs)) => {
+                let mut multiplied_values = Vec::new();
+                for value in values_rhs {
+                    multiplied_values.push(lhs.clone().mul_without_unit(value)?);
+                }
+
+                Ok(KalkValue::Vector(multiplied_values))
+            }
             (KalkValue::Vector(values), KalkValue::Vector(values_rhs)) => {
                 if values.len() != values_rhs.len() {
                     return Err(KalkError::IncompatibleVectorsMatrixes);
@@ -856,6 +1428,9 @@ impl KalkValue {
 
                 Ok(sum)
             }
+            (KalkValue::Integer(_), _) | (_, KalkValue::Integer(_)) => {
+                exact_or_float(lhs.clone(), rhs, exact_mul, |x, y| x.mul_without_unit(&y))
+            }
             _ => Err(KalkError::IncompatibleTypesForOperation(
                 String::from("multiplication"),
                 self.get_type_name(),
@@ -890,6 +1465,9 @@ impl KalkValue {
             (KalkValue::Vector(_), _) | (_, KalkValue::Vector(_)) => {
                 calculate_vector(self, rhs, &KalkValue::div_without_unit)
             }
+            (KalkValue::Integer(_), _) | (_, KalkValue::Integer(_)) => {
+                exact_or_float(self, rhs, exact_div, |x, y| x.div_without_unit(&y))
+            }
             _ => Err(KalkError::IncompatibleTypesForOperation(
                 String::from("division"),
                 self.get_type_name(),
@@ -924,10 +1502,11 @@ impl KalkValue {
                         unit.clone(),
                     ))
                 } else {
-                    Ok(KalkValue::Number(
-                        pow(real, real_rhs.clone()),
-                        float!(0),
-                        unit.clone(),
+                    Ok(exact_if_large(
+                        &self,
+                        rhs,
+                        KalkValue::Number(pow(real, real_rhs.clone()), float!(0), unit.clone()),
+                        exact_pow,
                     ))
                 }
             }
@@ -972,6 +1551,9 @@ impl KalkValue {
             (KalkValue::Vector(_), _) | (_, KalkValue::Vector(_)) => {
                 calculate_vector(self, rhs, &KalkValue::pow_without_unit)
             }
+            (KalkValue::Integer(_), _) | (_, KalkValue::Integer(_)) => {
+                exact_or_float(self, rhs, exact_pow, |x, y| x.pow_without_unit(&y))
+            }
             _ => Err(KalkError::IncompatibleTypesForOperation(
                 String::from("pow"),
                 self.get_type_name(),
@@ -986,13 +1568,19 @@ impl KalkValue {
                 KalkValue::Number(real, imaginary, _),
                 KalkValue::Number(real_rhs, imaginary_rhs, _),
             ) => Ok(KalkValue::Boolean(
-                (real.clone() - real_rhs.clone()).abs() < ACCEPTABLE_COMPARISON_MARGIN
-                    && (imaginary.clone() - imaginary_rhs.clone()).abs()
-                        < ACCEPTABLE_COMPARISON_MARGIN,
+                // Infinities are only equal to themselves, since their difference is NaN
+                (real == real_rhs
+                    || (real.clone() - real_rhs.clone()).abs() < ACCEPTABLE_COMPARISON_MARGIN)
+                    && (imaginary == imaginary_rhs
+                        || (imaginary.clone() - imaginary_rhs.clone()).abs()
+                            < ACCEPTABLE_COMPARISON_MARGIN),
             )),
             (KalkValue::Boolean(boolean), KalkValue::Boolean(boolean_rhs)) => {
                 Ok(KalkValue::Boolean(boolean == boolean_rhs))
             }
+            (KalkValue::String(value), KalkValue::String(value_rhs)) => {
+                Ok(KalkValue::Boolean(value == value_rhs))
+            }
             (KalkValue::Matrix(rows), KalkValue::Matrix(rows_rhs)) => {
                 let mut matrices_are_equal = true;
                 for (row, row_rhs) in rows.iter().zip(rows_rhs) {
@@ -1020,6 +1608,15 @@ impl KalkValue {
 
                 Ok(KalkValue::Boolean(vecs_are_equal))
             }
+            (KalkValue::Integer(_), _) | (_, KalkValue::Integer(_)) => {
+                match (self.as_exact_integer(), rhs.as_exact_integer()) {
+                    (Some(x), Some(y)) => Ok(KalkValue::Boolean(x == y)),
+                    _ => self
+                        .clone()
+                        .into_number()
+                        .eq_without_unit(&rhs.clone().into_number()),
+                }
+            }
             _ => Err(KalkError::IncompatibleTypesForOperation(
                 String::from("equal"),
                 self.get_type_name(),
@@ -1028,6 +1625,82 @@ impl KalkValue {
         }
     }
 
+    /// Check if the values are within the tolerance of each other, or if there is none,
+    /// within `APPROXIMATE_EQUALITY_TOLERANCE` relative to the largest of them.
+    /// Vectors and matrices are compared element by element.
+    pub(crate) fn approx_eq_without_unit(
+        &self,
+        rhs: &KalkValue,
+        tolerance: Option<f64>,
+    ) -> Result<KalkValue, KalkError> {
+        match (self, rhs) {
+            (
+                KalkValue::Number(..) | KalkValue::Integer(_),
+                KalkValue::Number(..) | KalkValue::Integer(_),
+            ) => {
+                let (real, imaginary) = (self.to_f64(), self.imaginary_to_f64());
+                let (real_rhs, imaginary_rhs) = (rhs.to_f64(), rhs.imaginary_to_f64());
+                // Infinities are only equal to themselves, since their difference is NaN
+                if real == real_rhs && imaginary == imaginary_rhs {
+                    return Ok(KalkValue::Boolean(true));
+                }
+
+                let difference = (real - real_rhs).hypot(imaginary - imaginary_rhs);
+                if !difference.is_finite() {
+                    return Ok(KalkValue::Boolean(false));
+                }
+
+                let tolerance = tolerance.unwrap_or_else(|| {
+                    let largest = real.hypot(imaginary).max(real_rhs.hypot(imaginary_rhs));
+
+                    APPROXIMATE_EQUALITY_TOLERANCE * largest.max(1f64)
+                });
+
+                Ok(KalkValue::Boolean(difference <= tolerance))
+            }
+            (KalkValue::Boolean(boolean), KalkValue::Boolean(boolean_rhs)) => {
+                Ok(KalkValue::Boolean(boolean == boolean_rhs))
+            }
+            (KalkValue::Vector(values), KalkValue::Vector(values_rhs)) => {
+                if values.len() != values_rhs.len() {
+                    return Ok(KalkValue::Boolean(false));
+                }
+
+                for (value, value_rhs) in values.iter().zip(values_rhs) {
+                    if let KalkValue::Boolean(false) =
+                        value.approx_eq_without_unit(value_rhs, tolerance)?
+                    {
+                        return Ok(KalkValue::Boolean(false));
+                    }
+                }
+
+                Ok(KalkValue::Boolean(true))
+            }
+            (KalkValue::Matrix(rows), KalkValue::Matrix(rows_rhs)) => {
+                if rows.len() != rows_rhs.len() {
+                    return Ok(KalkValue::Boolean(false));
+                }
+
+                for (row, row_rhs) in rows.iter().zip(rows_rhs) {
+                    let row = KalkValue::Vector(row.clone());
+                    let row_rhs = KalkValue::Vector(row_rhs.clone());
+                    if let KalkValue::Boolean(false) =
+                        row.approx_eq_without_unit(&row_rhs, tolerance)?
+                    {
+                        return Ok(KalkValue::Boolean(false));
+                    }
+                }
+
+                Ok(KalkValue::Boolean(true))
+            }
+            _ => Err(KalkError::IncompatibleTypesForOperation(
+                String::from("approximately equal"),
+                self.get_type_name(),
+                rhs.get_type_name(),
+            )),
+        }
+    }
+
     pub(crate) fn not_eq_without_unit(&self, rhs: &KalkValue) -> Result<KalkValue, KalkError> {
         match (self, rhs) {
             (
@@ -1041,6 +1714,9 @@ impl KalkValue {
             (KalkValue::Boolean(boolean), KalkValue::Boolean(boolean_rhs)) => {
                 Ok(KalkValue::Boolean(boolean != boolean_rhs))
             }
+            (KalkValue::String(value), KalkValue::String(value_rhs)) => {
+                Ok(KalkValue::Boolean(value != value_rhs))
+            }
             (KalkValue::Vector(_), KalkValue::Vector(_))
             | (KalkValue::Matrix(_), KalkValue::Matrix(_)) => {
                 if let KalkValue::Boolean(boolean) = self.eq_without_unit(rhs)? {
@@ -1049,6 +1725,15 @@ impl KalkValue {
                     unreachable!()
                 }
             }
+            (KalkValue::Integer(_), _) | (_, KalkValue::Integer(_)) => {
+                match (self.as_exact_integer(), rhs.as_exact_integer()) {
+                    (Some(x), Some(y)) => Ok(KalkValue::Boolean(x != y)),
+                    _ => self
+                        .clone()
+                        .into_number()
+                        .not_eq_without_unit(&rhs.clone().into_number()),
+                }
+            }
             _ => Err(KalkError::IncompatibleTypesForOperation(
                 String::from("not equal"),
                 self.get_type_name(),
@@ -1069,6 +1754,15 @@ impl KalkValue {
             (KalkValue::Number(real, _, _), KalkValue::Number(real_rhs, _, _)) => Ok(
                 KalkValue::Boolean(real.clone() - real_rhs.clone() > ACCEPTABLE_COMPARISON_MARGIN),
             ),
+            (KalkValue::Integer(_), _) | (_, KalkValue::Integer(_)) => {
+                match (self.as_exact_integer(), rhs.as_exact_integer()) {
+                    (Some(x), Some(y)) => Ok(KalkValue::Boolean(x > y)),
+                    _ => self
+                        .clone()
+                        .into_number()
+                        .greater_than_without_unit(&rhs.clone().into_number()),
+                }
+            }
             _ => Err(KalkError::IncompatibleTypesForOperation(
                 String::from("greater than"),
                 self.get_type_name(),
@@ -1086,6 +1780,15 @@ impl KalkValue {
             (KalkValue::Number(real, _, _), KalkValue::Number(real_rhs, _, _)) => Ok(
                 KalkValue::Boolean(real.clone() - real_rhs.clone() < -ACCEPTABLE_COMPARISON_MARGIN),
             ),
+            (KalkValue::Integer(_), _) | (_, KalkValue::Integer(_)) => {
+                match (self.as_exact_integer(), rhs.as_exact_integer()) {
+                    (Some(x), Some(y)) => Ok(KalkValue::Boolean(x < y)),
+                    _ => self
+                        .clone()
+                        .into_number()
+                        .less_than_without_unit(&rhs.clone().into_number()),
+                }
+            }
             _ => Err(KalkError::IncompatibleTypesForOperation(
                 String::from("less than"),
                 self.get_type_name(),
@@ -1101,6 +1804,7 @@ impl KalkValue {
                 imaginary.clone() * (-1f64),
                 unit.clone(),
             )),
+            KalkValue::Integer(_) => Ok(self.clone()),
             _ => Err(KalkError::UnexpectedType(
                 self.get_type_name(),
                 vec![String::from("number")],
@@ -1109,8 +1813,32 @@ impl KalkValue {
     }
 }
 
+/// Format the real or imaginary part of a value, where the number format is only used in base 10.
+fn format_part(value: f64, radix: u8, format: &NumberFormat) -> String {
+    if radix == 10 {
+        format_number_with(value, format)
+    } else {
+        radix::to_radix_pretty(rounding::normalize(value), radix)
+    }
+}
+
 pub fn format_number(input: f64) -> String {
-    let rounded = format!("{:.1$}", input, 10);
+    format_number_with(input, &NumberFormat::default())
+}
+
+/// Same as `format_number`, but with the separators of the given format.
+pub fn format_number_with(input: f64, format: &NumberFormat) -> String {
+    if input.is_nan() {
+        return String::from("undefined");
+    } else if input.is_infinite() {
+        return format!("{}∞", if input < 0f64 { "-" } else { "" });
+    }
+
+    if let Some(significant_figures) = significant_figures(format) {
+        return grouped(&to_significant_figures(input, significant_figures), format);
+    }
+
+    let rounded = rounding::normalize_zero_str(format!("{:.1$}", rounding::normalize(input), 10));
     let result = if rounded.contains('.') {
         rounded
             .trim_end_matches('0')
@@ -1120,7 +1848,150 @@ pub fn format_number(input: f64) -> String {
         rounded
     };
 
-    spaced(&result)
+    grouped(&result, format)
+}
+
+fn significant_figures(format: &NumberFormat) -> Option<usize> {
+    format
+        .significant_figures
+        .filter(|significant_figures| *significant_figures > 0)
+}
+
+/// Round the value to the amount of significant figures, eg. 3.142 for π with 4.
+/// Trailing zeros are kept, since they are significant, eg. 2.500.
+fn to_significant_figures(value: f64, significant_figures: usize) -> String {
+    let value = rounding::normalize(value);
+    if value == 0f64 {
+        return String::from("0");
+    }
+
+    // The value is rounded in scientific notation first, since the exponent can change
+    // when it's rounded, eg. 99.99 with 3 significant figures is 100
+    let scientific = format!("{:.*e}", significant_figures - 1, value);
+    let exponent = scientific
+        .split_once('e')
+        .and_then(|(_, exponent)| exponent.parse::<i32>().ok())
+        .unwrap_or(0);
+    let rounded = scientific.parse::<f64>().unwrap_or(value);
+    let decimals = (significant_figures as i32 - 1 - exponent).max(0) as usize;
+
+    rounding::normalize_zero_str(format!("{:.*}", decimals, rounded))
+}
+
+/// How the numbers of results are written, eg. 1 234.56 or 1,234.56.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumberFormat {
+    pub decimal_separator: char,
+    /// The separator between groups of three digits in the integer part,
+    /// or None to not group the digits.
+    pub group_separator: Option<char>,
+    /// How many digits the integer part needs to have for its digits to be grouped,
+    /// eg. 5 means that 1234 is not grouped, while 12 345 is.
+    pub min_grouped_digits: usize,
+    /// How many significant figures numbers are written with, eg. `Some(4)` for 3.142,
+    /// or None to write them with up to 10 decimals.
+    pub significant_figures: Option<usize>,
+}
+
+impl NumberFormat {
+    /// Get the format of a locale, eg. `en` for 1,234.56 or `de` for 1.234,56.
+    /// Only the language part of locales like `en_US` is used.
+    pub fn from_locale(locale: &str) -> Option<Self> {
+        let language = locale.split(['_', '-']).next()?;
+        let (decimal_separator, group_separator) = match language.to_lowercase().as_str() {
+            "si" => return Some(NumberFormat::default()),
+            "en" | "ja" | "zh" | "ko" => ('.', ','),
+            "de" | "nl" | "it" | "es" | "pt" | "da" | "id" | "tr" => (',', '.'),
+            "fr" | "sv" | "nb" | "fi" | "pl" | "cs" | "ru" | "uk" => (',', ' '),
+            _ => return None,
+        };
+
+        Some(NumberFormat {
+            decimal_separator,
+            group_separator: Some(group_separator),
+            min_grouped_digits: 4,
+            significant_figures: None,
+        })
+    }
+}
+
+impl Default for NumberFormat {
+    /// Numbers like 12 345.6789, which is what the CLI shows by default.
+    fn default() -> Self {
+        NumberFormat {
+            decimal_separator: '.',
+            group_separator: Some(' '),
+            min_grouped_digits: 5,
+            significant_figures: None,
+        }
+    }
+}
+
+/// If both values are integers, but the result of an operation on them as floats
+/// is too large to be exact, the exact result is calculated instead.
+fn exact_if_large(
+    lhs: &KalkValue,
+    rhs: &KalkValue,
+    result: KalkValue,
+    exact: impl Fn(&BigInt, &BigInt) -> Option<BigInt>,
+) -> KalkValue {
+    let is_large = matches!(
+        &result,
+        KalkValue::Number(real, imaginary, None)
+            if imaginary == &0f64 && primitive!(real).abs() > MAX_EXACT_FLOAT
+    );
+    if !is_large {
+        return result;
+    }
+
+    match (lhs.as_exact_integer(), rhs.as_exact_integer()) {
+        (Some(x), Some(y)) => exact(&x, &y)
+            .filter(|exact| exact.bits() <= integer::MAX_BITS)
+            .map(KalkValue::from)
+            .unwrap_or(result),
+        _ => result,
+    }
+}
+
+/// Do an operation where at least one of the values is an exact integer. The result is exact
+/// if both values are integers and it can be calculated exactly, otherwise floats are used.
+fn exact_or_float(
+    lhs: KalkValue,
+    rhs: &KalkValue,
+    exact: impl Fn(&BigInt, &BigInt) -> Option<BigInt>,
+    inexact: impl FnOnce(KalkValue, KalkValue) -> Result<KalkValue, KalkError>,
+) -> Result<KalkValue, KalkError> {
+    if let (Some(x), Some(y)) = (lhs.as_exact_integer(), rhs.as_exact_integer()) {
+        if let Some(result) = exact(&x, &y).filter(|exact| exact.bits() <= integer::MAX_BITS) {
+            return Ok(KalkValue::from(result));
+        }
+    }
+
+    inexact(lhs.into_number(), rhs.clone().into_number())
+}
+
+fn exact_mul(x: &BigInt, y: &BigInt) -> Option<BigInt> {
+    if x.bits() + y.bits() > integer::MAX_BITS + 1 {
+        return None;
+    }
+
+    Some(x * y)
+}
+
+/// Only divisions without a remainder are exact.
+fn exact_div(x: &BigInt, y: &BigInt) -> Option<BigInt> {
+    match x.div_rem(y) {
+        Some((quotient, remainder)) if remainder.is_zero() => Some(quotient),
+        _ => None,
+    }
+}
+
+fn exact_rem(x: &BigInt, y: &BigInt) -> Option<BigInt> {
+    x.div_rem(y).map(|(_, remainder)| remainder)
+}
+
+fn exact_pow(base: &BigInt, exponent: &BigInt) -> Option<BigInt> {
+    base.pow(exponent.to_u64()?)
 }
 
 fn calculate_vector(
@@ -1137,10 +2008,10 @@ fn calculate_vector(
 
             Ok(KalkValue::Vector(new_values))
         }
-        (KalkValue::Number(_, _, _), KalkValue::Vector(values_rhs)) => {
+        (x @ KalkValue::Number(_, _, _), KalkValue::Vector(values_rhs)) => {
             let mut new_values = Vec::new();
             for value in values_rhs {
-                new_values.push(action(y.clone(), value)?);
+                new_values.push(action(x.clone(), value)?);
             }
 
             Ok(KalkValue::Vector(new_values))
@@ -1237,68 +2108,135 @@ fn calculate_matrix(
 }
 
 fn spaced(number_str: &str) -> String {
-    let dot_pos = number_str.find('.');
-    let integer_boundary = if let Some(dot_pos) = dot_pos {
-        dot_pos
-    } else {
-        number_str.len()
-    };
+    grouped(number_str, &NumberFormat::default())
+}
 
-    if integer_boundary < 5 {
-        return number_str.into();
-    }
+/// Write a number, that has a dot as its decimal separator, with the separators of the format.
+fn grouped(number_str: &str, format: &NumberFormat) -> String {
+    let (integer, decimals) = match number_str.split_once('.') {
+        Some((integer, decimals)) => (integer, Some(decimals)),
+        None => (number_str, None),
+    };
+    let (sign, digits) = match integer.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", integer),
+    };
 
-    let bytes = number_str.as_bytes();
-    let mut at_decimals = dot_pos.is_some();
-    let mut i = number_str.len() - 1;
-    let mut c = 0;
-    let mut new_str = String::new();
-    while i > 0 {
-        if bytes[i] as char == '.' {
-            new_str.push('.');
-            at_decimals = false;
-            i -= 1;
-            c = 0;
-            continue;
-        }
+    let mut output = String::from(sign);
+    match format.group_separator {
+        Some(separator) if digits.chars().count() >= format.min_grouped_digits => {
+            let digit_count = digits.chars().count();
+            for (i, c) in digits.chars().enumerate() {
+                if i > 0 && (digit_count - i) % 3 == 0 {
+                    output.push(separator);
+                }
 
-        if !at_decimals && c == 3 {
-            new_str.push(' ');
-            c = 0;
+                output.push(c);
+            }
         }
-
-        new_str.push(bytes[i] as char);
-        c += 1;
-        i -= 1;
+        _ => output.push_str(digits),
     }
 
-    if c == 3 {
-        new_str.push(' ');
+    if let Some(decimals) = decimals {
+        output.push(format.decimal_separator);
+        output.push_str(decimals);
     }
-    new_str.push(bytes[0] as char);
 
-    new_str.chars().rev().collect::<String>()
+    output
+}
+
+/// Add or subtract temperatures, which is done differently from other units, since they
+/// may have different zeros. None is given if they aren't both temperatures.
+fn add_temperatures(
+    left: &KalkValue,
+    right: &KalkValue,
+    subtract: bool,
+) -> Option<Result<KalkValue, KalkError>> {
+    let result = crate::temperature::add(
+        left.to_f64(),
+        left.get_unit()?,
+        right.to_f64(),
+        right.get_unit()?,
+        subtract,
+    )?;
+
+    Some(result.map(|(value, unit)| KalkValue::Number(float!(value), float!(0f64), Some(unit))))
+}
+
+/// The unit of the product of two numbers, or the quotient if `power` is -1, when at least one
+/// of them has a unit, and the right one without its unit. It's converted to the unit of the
+/// left one first if possible, eg. so that `2 km * 500 m` gives 1 km^2 and `1 h / 30 min` gives 2.
+fn combine_units(
+    context: &mut crate::interpreter::Context,
+    left: &KalkValue,
+    right: KalkValue,
+    power: i32,
+) -> Option<(KalkValue, Option<String>)> {
+    let is_number =
+        |value: &KalkValue| matches!(value, KalkValue::Number(_, _, _) | KalkValue::Integer(_));
+    if !is_number(left) || !is_number(&right) || !(left.has_unit() || right.has_unit()) {
+        return None;
+    }
+
+    let right = match (left.get_unit(), right.get_unit()) {
+        (Some(left_unit), Some(right_unit)) if left_unit != right_unit => {
+            match right.convert_to_unit(context, left_unit) {
+                Some(converted) => converted,
+                // Eg. `1.5 GB / 20 Mbps`, where Mb is converted to GB so that they cancel out
+                None => match crate::units::match_units(left_unit, right_unit) {
+                    Some((factor, unit)) => right
+                        .with_unit(None)
+                        .mul(context, KalkValue::from(factor))
+                        .ok()?
+                        .with_unit(Some(unit)),
+                    None => right,
+                },
+            }
+        }
+        _ => right,
+    };
+    let unit = crate::units::combine(left.get_unit(), right.get_unit(), power);
+
+    Some((right.with_unit(None), unit))
 }
 
 fn calculate_unit(
     context: &mut crate::interpreter::Context,
     left: &KalkValue,
     right: KalkValue,
-) -> Option<KalkValue> {
-    if let (KalkValue::Number(_, _, unit_left), KalkValue::Number(real_right, imaginary_right, _)) =
-        (left, &right)
+) -> Result<Option<KalkValue>, KalkError> {
+    if let (
+        KalkValue::Number(_, _, unit_left),
+        KalkValue::Number(real_right, imaginary_right, unit_right),
+    ) = (left, &right)
     {
-        if left.has_unit() && right.has_unit() {
-            right.convert_to_unit(context, unit_left.as_ref().unwrap())
-        } else {
-            Some(KalkValue::Number(
+        match (unit_left, unit_right) {
+            (Some(unit_left), Some(unit_right)) if unit_left == unit_right => Ok(Some(right)),
+            (Some(unit_left), Some(_)) => {
+                let converted = right.convert_to_unit(context, unit_left);
+                if converted.is_none() {
+                    context.drop_unit(unit_left)?;
+                }
+
+                Ok(converted)
+            }
+            (None, Some(unit_right)) => {
+                context.drop_unit(unit_right)?;
+
+                Ok(Some(KalkValue::Number(
+                    real_right.clone(),
+                    imaginary_right.clone(),
+                    None,
+                )))
+            }
+            _ => Ok(Some(KalkValue::Number(
                 real_right.clone(),
                 imaginary_right.clone(),
                 unit_left.clone(),
-            ))
+            ))),
         }
     } else {
-        None
+        Ok(None)
     }
 }
 
@@ -1351,6 +2289,17 @@ impl From<f64> for KalkValue {
     }
 }
 
+impl From<BigInt> for KalkValue {
+    /// The integer is only kept exact if it's too large to be a float without losing precision.
+    fn from(integer: BigInt) -> Self {
+        if integer.bits() <= 53 {
+            KalkValue::from(integer.to_f64())
+        } else {
+            KalkValue::Integer(integer)
+        }
+    }
+}
+
 impl From<f32> for KalkValue {
     fn from(x: f32) -> Self {
         KalkValue::Number(float!(x), float!(0), None)
@@ -1377,7 +2326,9 @@ impl From<i32> for KalkValue {
 
 #[cfg(test)]
 mod tests {
-    use crate::kalk_value::{spaced, KalkValue};
+    use crate::kalk_value::{
+        format_number, format_number_with, spaced, KalkValue, NumberFormat, RoundingOptions,
+    };
     use crate::test_helpers::cmp;
 
     #[test]
@@ -1398,6 +2349,75 @@ mod tests {
         assert_eq!(spaced("100000.12345"), String::from("100 000.12345"));
         assert_eq!(spaced("1000000.12345"), String::from("1 000 000.12345"));
         assert_eq!(spaced("10000000.12345"), String::from("10 000 000.12345"));
+        assert_eq!(spaced("-1000"), String::from("-1000"));
+        assert_eq!(spaced("-10000"), String::from("-10 000"));
+    }
+
+    #[test]
+    fn test_significant_figures() {
+        let format = |significant_figures: usize| NumberFormat {
+            significant_figures: Some(significant_figures),
+            ..Default::default()
+        };
+        assert_eq!(
+            format_number_with(std::f64::consts::PI, &format(4)),
+            "3.142"
+        );
+        assert_eq!(format_number_with(2.5, &format(4)), "2.500");
+        assert_eq!(format_number_with(-0.00123456, &format(3)), "-0.00123");
+        assert_eq!(format_number_with(99.99, &format(3)), "100");
+        assert_eq!(format_number_with(0.09996, &format(3)), "0.100");
+        assert_eq!(format_number_with(1234567.0, &format(3)), "1 230 000");
+        assert_eq!(format_number_with(0f64, &format(3)), "0");
+        assert_eq!(format_number_with(1f64 / 3f64, &format(0)), "0.3333333333");
+
+        let options = RoundingOptions {
+            number_format: format(4),
+            ..Default::default()
+        };
+        let pretty = |value: f64| KalkValue::from(value).to_string_pretty_with(10, &options);
+        assert_eq!(pretty(std::f64::consts::PI), "3.142 ≈ π");
+        assert!(pretty(1.23456789e20).ends_with("≈ 1.235×10^20"));
+        assert!(pretty(9.99996e20).ends_with("≈ 1.000×10^21"));
+        assert_eq!(pretty(-4.5e-12), "-4.500×10^-12 ≈ 0");
+    }
+
+    #[test]
+    fn test_number_formats() {
+        let english = NumberFormat::from_locale("en_US").unwrap();
+        let german = NumberFormat::from_locale("de").unwrap();
+        let french = NumberFormat::from_locale("fr-FR").unwrap();
+        let ungrouped = NumberFormat {
+            group_separator: None,
+            ..german
+        };
+        assert_eq!(format_number_with(1234.56, &english), "1,234.56");
+        assert_eq!(format_number_with(-1234567.5, &german), "-1.234.567,5");
+        assert_eq!(format_number_with(1234.56, &french), "1 234,56");
+        assert_eq!(format_number_with(123.5, &english), "123.5");
+        assert_eq!(format_number_with(1234567.5, &ungrouped), "1234567,5");
+        assert_eq!(
+            NumberFormat::from_locale("si"),
+            Some(NumberFormat::default())
+        );
+        assert_eq!(NumberFormat::from_locale("xx"), None);
+
+        let options = RoundingOptions {
+            number_format: german,
+            fractions: false,
+            ..Default::default()
+        };
+        let value = KalkValue::Number(float!(1234.5), float!(-2.5), None);
+        assert_eq!(value.to_string_pretty_with(10, &options), "1.234,5 - 2,5i");
+    }
+
+    #[test]
+    fn test_format_number() {
+        assert_eq!(format_number(-0f64), "0");
+        assert_eq!(format_number(-0.00000000001), "0");
+        assert_eq!(format_number(0.3 - 0.2 - 0.1), "0");
+        assert_eq!(format_number(0.9999999999), "1");
+        assert_eq!(format_number(-1.5), "-1.5");
     }
 
     #[test]
@@ -1525,6 +2545,13 @@ mod tests {
             (1.0, 10e-17, "1"),
             (10e-16, 0.0, "0"),
             (3.00000000004, 0.0, "3"),
+            (-0.0, 0.0, "0"),
+            (0.1 + 0.2 - 0.3, 0.0, "0"),
+            (0.3 - 0.2 - 0.1, 0.0, "0"),
+            (0.0, 0.9999999999, "i"),
+            (2.0, -0.9999999999, "2 - i"),
+            (-0.000000000000001, -1.0, "-i"),
+            (-0.0000000001, 0.0, "-10^-10 ≈ 0"),
         ];
         for (real, imaginary, output) in in_out {
             let result =
@@ -1532,4 +2559,88 @@ mod tests {
             assert_eq!(output, result);
         }
     }
+
+    #[test]
+    fn test_complex_estimate() {
+        let sqrt_half = 0.5f64.sqrt();
+        let in_out = vec![
+            (0.5, 0.3333333333, "1/2 + 1/3·i"),
+            (0.5, -0.3333333333, "1/2 - 1/3·i"),
+            (0.0, -0.3333333333, "-1/3·i"),
+            (0.0, 2f64.sqrt(), "√2·i"),
+            (1.0, std::f64::consts::PI, "1 + πi"),
+            (2.0, -std::f64::consts::PI - 1.0, "2 - (π + 1)i"),
+            (0.5, 1.23456, "1/2 + 1.23456i"),
+            (sqrt_half, sqrt_half, "e^(iπ/4)"),
+            (0.5, -(3f64.sqrt()) / 2f64, "e^(-iπ/3)"),
+            (-1.0, 3f64.sqrt(), "-1 + √3·i"),
+        ];
+        for (real, imaginary, output) in in_out {
+            let value = KalkValue::Number(float!(real), float!(imaginary), None);
+            assert_eq!(value.estimate(), Some(String::from(output)));
+        }
+
+        let options = super::RoundingOptions {
+            polar: true,
+            ..Default::default()
+        };
+        let estimate = |real: f64, imaginary: f64| {
+            KalkValue::Number(float!(real), float!(imaginary), None).estimate_with(&options)
+        };
+        assert_eq!(
+            estimate(sqrt_half, sqrt_half),
+            Some(String::from("e^(iπ/4)"))
+        );
+        assert_eq!(
+            estimate(-sqrt_half, -sqrt_half),
+            Some(String::from("e^(-3iπ/4)"))
+        );
+        assert_eq!(
+            estimate(0.5, 3f64.sqrt() / 2f64),
+            Some(String::from("e^(iπ/3)"))
+        );
+        assert_eq!(
+            estimate(1.5 * 3f64.sqrt(), 1.5),
+            Some(String::from("3e^(iπ/6)"))
+        );
+        // The polar form is only used when it is shorter
+        assert_eq!(estimate(1.0, 3f64.sqrt()), Some(String::from("1 + √3·i")));
+
+        let options = super::RoundingOptions {
+            polar: true,
+            degrees: true,
+            ..Default::default()
+        };
+        let estimate = |real: f64, imaginary: f64| {
+            KalkValue::Number(float!(real), float!(imaginary), None).estimate_with(&options)
+        };
+        assert_eq!(
+            estimate(0.5, 3f64.sqrt() / 2f64),
+            Some(String::from("cis(60°)"))
+        );
+        assert_eq!(
+            estimate(1.5 * 3f64.sqrt(), -1.5),
+            Some(String::from("3cis(-30°)"))
+        );
+        assert_eq!(
+            estimate(0.5, 0.3333333333),
+            Some(String::from("1/2 + 1/3·i"))
+        );
+        assert_eq!(
+            KalkValue::Number(float!(0.5), float!(-0.5), None).estimate_with_vulgar_fractions(),
+            Some(String::from("½ - ½·i"))
+        );
+    }
+
+    #[test]
+    fn test_matrix_to_string() {
+        let number = |x: f64| KalkValue::Number(float!(x), float!(0), None);
+        let matrix = KalkValue::Matrix(vec![
+            vec![number(1.0), number(-20.0)],
+            vec![number(300.0), number(0.5)],
+        ]);
+
+        assert_eq!(matrix.to_string(), "[   1  -20 ]\n[ 300  1/2 ]");
+        assert_eq!(matrix.to_string_pretty(), "⎡   1  -20 ⎤\n⎣ 300  1/2 ⎦");
+    }
 }
@@ -2,26 +2,26 @@ use crate::kalk_value::*;
 
 impl KalkValue {
     pub fn to_f64(&self) -> f64 {
-        if let KalkValue::Number(real, _, _) = self {
-            *real
-        } else {
-            f64::NAN
+        match self {
+            KalkValue::Number(real, _, _) => *real,
+            KalkValue::Integer(integer) => integer.to_f64(),
+            _ => f64::NAN,
         }
     }
 
     pub fn imaginary_to_f64(&self) -> f64 {
-        if let KalkValue::Number(_, imaginary, _) = self {
-            *imaginary
-        } else {
-            f64::NAN
+        match self {
+            KalkValue::Number(_, imaginary, _) => *imaginary,
+            KalkValue::Integer(_) => 0f64,
+            _ => f64::NAN,
         }
     }
 
     pub fn values(self) -> (f64, f64) {
-        if let KalkValue::Number(real, imaginary, _) = self {
-            (real, imaginary)
-        } else {
-            (0f64, 0f64)
+        match self {
+            KalkValue::Number(real, imaginary, _) => (real, imaginary),
+            KalkValue::Integer(integer) => (integer.to_f64(), 0f64),
+            _ => (0f64, 0f64),
         }
     }
 }
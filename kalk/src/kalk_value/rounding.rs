@@ -1,50 +1,183 @@
-use std::collections::HashMap;
-
-use crate::{float, primitive};
+use crate::{primitive, text_utils};
 use lazy_static::lazy_static;
 
-use super::{ComplexNumberType, KalkValue};
+use super::{ComplexNumberType, KalkValue, NumberFormat};
 
 lazy_static! {
-    static ref CONSTANTS: HashMap<u32, (u32, &'static str)> = {
-        let mut m = HashMap::new();
-        m.insert(141592, (3, "π"));
-        m.insert(869604, (9, "π²"));
-        m.insert(318909, (0, "1/π"));
-        m.insert(636619, (0, "2/π"));
-        m.insert(718281, (2, "e"));
-        m.insert(389056, (7, "e²"));
-        m.insert(283185, (6, "τ"));
-        m.insert(618033, (1, "ϕ"));
-        m.insert(414213, (1, "√2"));
-        m.insert(707106, (0, "1/√2"));
-        m.insert(693147, (0, "ln(2)"));
-        m.insert(302585, (2, "ln(10)"));
-        // Radian values for common angles
-        m.insert(392699, (0, "π/8"));
-        m.insert(523598, (0, "π/6"));
-        m.insert(785398, (0, "π/4"));
-        m.insert(47197, (1, "π/3"));
-        m.insert(570796, (1, "π/2"));
-        m.insert(94395, (2, "2π/3"));
-        m.insert(356194, (2, "3π/4"));
-        m.insert(617993, (2, "5π/6"));
-        m.insert(665191, (3, "7π/6"));
-        m.insert(926990, (3, "5π/4"));
-        m.insert(188790, (4, "4π/3"));
-        m.insert(712388, (4, "3π/2"));
-        m.insert(23598, (5, "5π/3"));
-        m.insert(497787, (5, "7π/4"));
-        m.insert(759586, (5, "11π/6"));
-        m.insert(283185, (6, "2π"));
-        m.insert(866025, (0, "√3/2"));
-        m
+    /// Constants that values are compared to, and how they are written.
+    /// Values that differ from one of these by an integer are written as eg. π + 1.
+    /// Other multiples of π, eg. 2π/3, and powers of e, eg. e^3, are found without this table.
+    static ref CONSTANTS: Vec<(f64, &'static str)> = {
+        let pi = std::f64::consts::PI;
+        let phi = (1f64 + 5f64.sqrt()) / 2f64;
+
+        vec![
+            (pi, "π"),
+            (pi * pi, "π²"),
+            (pi * pi / 6f64, "π²/6"),
+            (1f64 / pi, "1/π"),
+            (2f64 / pi, "2/π"),
+            (std::f64::consts::E, "e"),
+            (std::f64::consts::E.powi(2), "e²"),
+            (1f64 / std::f64::consts::E, "1/e"),
+            (phi, "ϕ"),
+            (1f64 / phi, "1/ϕ"),
+            (phi * phi, "ϕ²"),
+            // The Euler–Mascheroni constant
+            (0.577_215_664_901_532_9, "γ"),
+            (std::f64::consts::SQRT_2, "√2"),
+            (std::f64::consts::FRAC_1_SQRT_2, "1/√2"),
+            (3f64.sqrt(), "√3"),
+            (3f64.sqrt() / 2f64, "√3/2"),
+            (1f64 / 3f64.sqrt(), "1/√3"),
+            (std::f64::consts::LN_2, "ln(2)"),
+            (std::f64::consts::LN_10, "ln(10)"),
+            (2f64 * pi, "2π"),
+        ]
     };
 }
 
+/// How far off, relative to its magnitude, a value may be from a constant
+/// (plus an integer) to still be written as that constant.
+const CONSTANT_TOLERANCE: f64 = 1e-9;
+
+/// The biggest denominator of the multiples of π that values are written as, eg. 5π/12.
+const MAX_PI_DENOMINATOR: u64 = 12;
+
+/// The biggest radicand of the roots that are added to an integer, eg. 1 + √2.
+const MAX_RADICAND_WITH_INTEGER: f64 = 100f64;
+
+/// How far off, relative to its magnitude, a value may be from a fraction to still be written
+/// as that fraction, eg. 0.666666666 => 2/3.
+const FRACTION_TOLERANCE: f64 = 1e-9;
+
+/// Decides what a value may be written as by `KalkValue::estimate_with`.
+/// Values that are only slightly off from a more exact one, eg. 0.9999999, are always rounded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoundingOptions {
+    /// Give estimates at all. When false, values are only shown as decimals,
+    /// eg. 0.9999991 isn't shown with ≈ 1.
+    pub estimate: bool,
+    /// Write the estimate after the decimals in parentheses, eg. `≈ 0.3333333333… (1/3)`,
+    /// instead of after them with ≈, eg. `0.3333333333 ≈ 1/3`.
+    pub side_by_side: bool,
+    /// Write values as fractions, eg. 0.3333333 => 1/3.
+    pub fractions: bool,
+    /// The biggest denominator of the fractions that values are written as.
+    /// Fractions with a power of ten as the denominator are left as decimals, eg. 0.53.
+    pub max_denominator: u64,
+    /// Write values as common constants, eg. 3.1415926 => π.
+    pub constants: bool,
+    /// Write values as square roots, eg. 1.4142135 => √2.
+    pub roots: bool,
+    /// Write simple fractions as unicode vulgar fractions, eg. 2 + 1/2 => 2½.
+    pub vulgar_fractions: bool,
+    /// Values and the symbols they are written as, in addition to the common constants,
+    /// eg. `(0.9159655941, "G")` for Catalan's constant. Integer multiples of the values
+    /// are also recognised, eg. 2G. These are only used if `constants` is true.
+    pub custom_constants: Vec<(f64, String)>,
+    /// How close a value needs to be to an integer to be rounded to it in estimates,
+    /// eg. `Some(1e-9)`. By default, this depends on the value, and values close to zero
+    /// are rounded less aggressively, eg. 0.9999991 ≈ 1 while 0.0000001 isn't rounded.
+    /// A tolerance of zero turns the rounding off.
+    pub round_tolerance: Option<f64>,
+    /// Write complex values in polar form, eg. 2e^(iπ/4), when that is shorter.
+    /// Values on the unit circle at a multiple of eg. π/6 are always written in polar form.
+    pub polar: bool,
+    /// Write the angles of the polar form in degrees, eg. cis(60°) rather than e^(iπ/3).
+    pub degrees: bool,
+    /// The decimal separator and digit grouping of the value that is shown before the estimate.
+    pub number_format: NumberFormat,
+}
+
+impl Default for RoundingOptions {
+    /// The options used by `KalkValue::estimate`, which is what the CLI shows.
+    fn default() -> Self {
+        RoundingOptions {
+            estimate: true,
+            side_by_side: false,
+            fractions: true,
+            max_denominator: 100,
+            constants: true,
+            roots: true,
+            vulgar_fractions: false,
+            custom_constants: Vec::new(),
+            round_tolerance: None,
+            polar: false,
+            degrees: false,
+            number_format: NumberFormat::default(),
+        }
+    }
+}
+
+/// Values with a magnitude smaller than this are considered to be floating point noise,
+/// eg. the result of 0.1 + 0.2 - 0.3.
+const NOISE_LIMIT: f64 = 1e-14;
+
+/// Values that are closer than this to an integer are displayed as that integer,
+/// eg. 0.9999999999 => 1.
+const INTEGER_SNAP_LIMIT: f64 = 1e-9;
+
+/// Turn negative zero, as well as values that are just floating point noise, into zero.
+pub(super) fn normalize_zero(value: f64) -> f64 {
+    if value.abs() < NOISE_LIMIT {
+        0f64
+    } else {
+        value
+    }
+}
+
+/// Normalize a value before it is displayed. Negative zero and noise become zero,
+/// and values that are only a tiny bit off from an integer become that integer.
+pub(super) fn normalize(value: f64) -> f64 {
+    let value = normalize_zero(value);
+    let rounded = value.round();
+    if value.is_finite() && rounded.abs() < 1e15 && (value - rounded).abs() < INTEGER_SNAP_LIMIT {
+        normalize_zero(rounded)
+    } else {
+        value
+    }
+}
+
+/// Check if all the decimals of the value are shown when it's written with the default
+/// amount of decimals, eg. 0.5 but not 1/3.
+pub(super) fn has_exact_decimals(value: f64) -> bool {
+    let value = normalize(value);
+
+    !value.is_finite() || format!("{:.10}", value).parse::<f64>() == Ok(value)
+}
+
+/// Remove the sign of a formatted zero, eg. -0 => 0.
+pub(super) fn normalize_zero_str(input: String) -> String {
+    if input.trim_start_matches('-').trim_end_matches('0') == "0." || input == "-0" {
+        input.trim_start_matches('-').to_string()
+    } else {
+        input
+    }
+}
+
+/// The amount of decimals that are shown when all of them are certain.
+pub(super) const MAX_CERTAIN_DECIMALS: usize = 10;
+
+/// Find the decimals that are certain, given bounds for the exact value, by rounding
+/// both bounds to fewer and fewer decimals until they are the same. Returns the rounded
+/// value and the amount of decimals, or None if not even the integer part is certain.
+pub(super) fn certain_digits(lower: f64, upper: f64) -> Option<(String, usize)> {
+    (0..=MAX_CERTAIN_DECIMALS).rev().find_map(|decimals| {
+        let lower = normalize_zero_str(format!("{:.*}", decimals, lower));
+        let upper = normalize_zero_str(format!("{:.*}", decimals, upper));
+        if lower == upper {
+            Some((lower, decimals))
+        } else {
+            None
+        }
+    })
+}
+
 pub(super) fn estimate(
     input: &KalkValue,
     complex_number_type: ComplexNumberType,
+    options: &RoundingOptions,
 ) -> Option<String> {
     let (real, imaginary, _) = if let KalkValue::Number(real, imaginary, unit) = input {
         (real, imaginary, unit)
@@ -62,18 +195,30 @@ pub(super) fn estimate(
     }
 
     let value = primitive!(value);
+    if !value.is_finite() {
+        return None;
+    }
 
     // If it's an integer, there's nothing that would be done to it.
     if value.fract() == 0f64 {
         return None;
     }
 
-    if let Some(equivalent_fraction) = equivalent_fraction(value) {
+    if options.constants {
+        if let Some(custom_constant) = equivalent_custom_constant(value, &options.custom_constants)
+        {
+            return Some(custom_constant);
+        }
+    }
+
+    if let Some(equivalent_fraction) =
+        equivalent_fraction(value, options.max_denominator).filter(|_| options.fractions)
+    {
         return Some(equivalent_fraction);
     }
 
     // Match with common numbers, eg. π, 2π/3, √2
-    if let Some(equivalent_constant) = equivalent_constant(value) {
+    if let Some(equivalent_constant) = equivalent_constant(value).filter(|_| options.constants) {
         return Some(equivalent_constant);
     }
 
@@ -81,183 +226,241 @@ pub(super) fn estimate(
     // eg. x² is an integer,
     // then it can be expressed as sqrt(x²).
     // Ignore it if the square root of the result is an integer.
-    if let Some(equivalent_root) = equivalent_root(value) {
+    if let Some(equivalent_root) = equivalent_root(value).filter(|_| options.roots) {
         return Some(equivalent_root);
     }
 
     // If nothing above was relevant, simply round it off a bit, eg. from 0.99999 to 1
+    let tolerance = options.round_tolerance;
     let rounded = match complex_number_type {
-        ComplexNumberType::Real => round(input, complex_number_type)?.values().0,
-        ComplexNumberType::Imaginary => round(input, complex_number_type)?.values().1,
+        ComplexNumberType::Real => round(input, complex_number_type, tolerance)?.values().0,
+        ComplexNumberType::Imaginary => round(input, complex_number_type, tolerance)?.values().1,
     };
-    let rounded_str = rounded.to_string();
-    Some(trim_zeroes(if rounded_str == "-0" {
-        "0"
-    } else {
-        &rounded_str
-    }))
+    Some(trim_zeroes(&normalize_zero_str(rounded.to_string())))
 }
 
-fn equivalent_fraction(value: f64) -> Option<String> {
-    fn gcd(mut a: i64, mut b: i64) -> i64 {
-        while a != 0 {
-            let old_a = a;
-            a = b % a;
-            b = old_a;
-        }
-
-        b.abs()
+/// Find the simplest fraction that the value is close enough to, with a denominator that
+/// isn't bigger than `max_denominator`, eg. 2/3 for 0.666666666 or 2 + 2/7 for 2.2857142857.
+fn equivalent_fraction(value: f64, max_denominator: u64) -> Option<String> {
+    let abs_value = value.abs();
+    let tolerance = FRACTION_TOLERANCE * abs_value.max(1f64);
+    let (numer, denom) = simplest_fraction_between(
+        (abs_value - tolerance).max(0f64),
+        abs_value + tolerance,
+        max_denominator,
+    )?;
+
+    // Fractions of powers of ten are just decimals, eg. 1/10
+    if denom <= 1 || is_power_of_ten(denom) {
+        return None;
     }
 
-    let original_sign = value.signum();
-    let value = value.abs();
-    let abs_value_str = value.to_string();
+    let integer_part = numer / denom;
+    let sign = if value.is_sign_positive() { "" } else { "-" };
+    if integer_part > 1 {
+        let fraction_sign = if value.is_sign_positive() { "+" } else { "-" };
 
-    // https://goodcalculators.com/repeating-decimal-to-fraction-conversion-calculator/
-    let (mut numer, mut denom) = if let Some(repeatend_str) = find_repeatend(&abs_value_str) {
-        let dot_pos = abs_value_str.find('.')?;
-        let repeatend_pos = abs_value_str[dot_pos..].find(&repeatend_str)? + dot_pos;
-        let non_repeating_str = &abs_value_str[..repeatend_pos].trim_end_matches('.');
+        Some(format!(
+            "{}{} {} {}/{}",
+            sign,
+            integer_part,
+            fraction_sign,
+            numer - integer_part * denom,
+            denom
+        ))
+    } else {
+        Some(format!("{}{}/{}", sign, numer, denom))
+    }
+}
 
-        // non-repeating
-        let non_repeating = non_repeating_str.parse::<f64>().unwrap_or(0f64);
-        let non_repeating_dec_count = if let Some(dot_pos) = non_repeating_str.find('.') {
-            non_repeating_str.len() - dot_pos - 1
-        } else {
-            0
-        };
-        let a = non_repeating.fract();
+/// Find the fraction with the smallest denominator in the interval [lower, upper],
+/// where 0 <= lower <= upper, by going down the Stern–Brocot tree. This is done one term of the
+/// continued fraction at a time: if there is no integer in the interval, the fraction is
+/// floor + 1/x, where x is the simplest fraction between the reciprocals of the fractional parts.
+/// Returns None if the denominator would be bigger than `max_denominator`.
+fn simplest_fraction_between(lower: f64, upper: f64, max_denominator: u64) -> Option<(u64, u64)> {
+    let integer = lower.ceil();
+    if integer <= upper {
+        return Some((integer as u64, 1));
+    }
 
-        // repeatend
-        let b = match repeatend_str.parse::<i64>() {
-            Ok(b) => b,
-            Err(_) => return None,
-        };
+    // The numerator of x is the denominator of the fraction,
+    // and it is at least as big as the denominator of x.
+    let floor = lower.floor();
+    let (x_lower, x_upper) = (1f64 / (upper - floor), 1f64 / (lower - floor));
+    if x_lower > max_denominator as f64 {
+        return None;
+    }
 
-        let factor = 10i64.pow(non_repeating_dec_count as u32) as f64;
-        let nines = (10i64.pow(repeatend_str.len() as u32) - 1) as f64;
+    let (x_numer, x_denom) = simplest_fraction_between(x_lower, x_upper, max_denominator)?;
+    if x_numer > max_denominator {
+        return None;
+    }
 
-        let a_numer = a as f64 * factor * nines;
-        let b_numer = b as f64;
-        let ab_denom = nines * factor;
-        let integer_part_as_numer = non_repeating.trunc() * ab_denom;
+    Some((floor as u64 * x_numer + x_denom, x_numer))
+}
 
-        (a_numer + b_numer + integer_part_as_numer, ab_denom)
-    } else {
-        const PREC: f64 = 10e10f64;
+fn is_power_of_ten(mut value: u64) -> bool {
+    while value >= 10 && value.is_multiple_of(10) {
+        value /= 10;
+    }
 
-        (value * PREC, PREC)
-    };
+    value == 1
+}
 
-    let gcd = gcd(numer as i64, denom as i64) as f64;
-    numer /= gcd;
-    denom /= gcd;
+/// Find a constant that the value is equal to, give or take an integer, eg. π + 1.
+/// If there are several, the one that needs the smallest integer is used,
+/// eg. 1.618 is written as ϕ rather than 1/ϕ + 1.
+pub(super) fn equivalent_constant(value: f64) -> Option<String> {
+    if let Some(pi_multiple) = equivalent_pi_multiple(value) {
+        return Some(pi_multiple);
+    }
 
-    numer = numer.trunc();
-    denom = denom.trunc();
+    equivalent_table_constant(value).or_else(|| equivalent_power_of_e(value))
+}
 
-    if denom <= 1f64 || denom >= 100f64 || denom as i64 == 10 {
-        return None;
-    }
+fn equivalent_table_constant(value: f64) -> Option<String> {
+    let abs_value = value.abs();
+    let tolerance = CONSTANT_TOLERANCE * abs_value.max(1f64);
+    let (constant, additional) = CONSTANTS
+        .iter()
+        .filter_map(|(constant, symbol)| {
+            let additional = (abs_value - constant).round();
+            if (abs_value - constant - additional).abs() < tolerance {
+                Some((symbol, additional as i64))
+            } else {
+                None
+            }
+        })
+        .min_by_key(|(_, additional)| additional.abs())?;
+    let constant_sign = if value.is_sign_positive() { "" } else { "-" };
 
-    let integer_part = (numer / denom).trunc();
-    if integer_part > 1f64 {
-        numer -= integer_part * denom;
-        let sign = if original_sign.is_sign_positive() {
+    if additional == 0 {
+        Some(format!("{}{}", constant_sign, constant))
+    } else {
+        let additional_sign = if (additional > 0) == value.is_sign_positive() {
             "+"
         } else {
             "-"
         };
 
         Some(format!(
-            "{} {} {}/{}",
-            integer_part * original_sign,
-            sign,
-            numer.abs(),
-            denom.abs()
+            "{}{} {} {}",
+            constant_sign,
+            constant,
+            additional_sign,
+            additional.abs()
         ))
-    } else {
-        Some(format!("{}/{}", numer * original_sign, denom))
     }
 }
 
-fn find_repeatend(input: &str) -> Option<String> {
-    if input.len() < 10 {
+/// Find a multiple of π that the value is equal to, eg. 2π or 3π/4, by finding the simplest
+/// fraction that the value divided by π is close to.
+fn equivalent_pi_multiple(value: f64) -> Option<String> {
+    let multiple = value.abs() / std::f64::consts::PI;
+    let tolerance = CONSTANT_TOLERANCE * multiple.max(1f64);
+    let (numer, denom) = simplest_fraction_between(
+        (multiple - tolerance).max(0f64),
+        multiple + tolerance,
+        MAX_PI_DENOMINATOR,
+    )?;
+    if numer == 0 || numer > 100 {
         return None;
     }
 
-    for len in 1..=9 {
-        for offset in 1..=len {
-            let chars: Vec<char> = input[..input.len() - offset].chars().rev().collect();
-            let mut chunks = chars.chunks(len);
-            let mut repeats = 1;
-            let mut prev: &[char] = chunks.next()?;
-            for chunk in chunks {
-                if chunk == prev {
-                    repeats += 1;
-                } else {
-                    repeats = 0;
-                    prev = chunk;
-                }
+    let sign = if value.is_sign_positive() { "" } else { "-" };
+    let numer = if numer == 1 {
+        String::new()
+    } else {
+        numer.to_string()
+    };
 
-                let required_repeats = match len {
-                    1..=3 => 4,
-                    _ => 2,
-                };
-                if repeats >= required_repeats && !prev.iter().all(|x| x == &'0') {
-                    return Some(prev.iter().rev().cloned().collect::<String>());
-                }
-            }
-        }
+    if denom == 1 {
+        Some(format!("{}{}π", sign, numer))
+    } else {
+        Some(format!("{}{}π/{}", sign, numer, denom))
     }
-
-    None
 }
 
-fn equivalent_constant(value: f64) -> Option<String> {
-    if let Some((constant_trunc, constant)) = CONSTANTS.get(&((value.abs().fract() * 10e5) as u32))
-    {
-        let additional = value.trunc() as i32 - (*constant_trunc as f64 * value.signum()) as i32;
-        let constant_sign = if value.is_sign_positive() { "" } else { "-" };
+/// Find an integer power of e that the value is equal to, eg. e^3 or e^-2.
+/// The smallest powers are in the table of constants instead, eg. e² and 1/e.
+fn equivalent_power_of_e(value: f64) -> Option<String> {
+    if value <= 0f64 {
+        return None;
+    }
 
-        if additional == 0 {
-            Some(format!("{}{}", constant_sign, constant))
-        } else {
-            let additional_sign = if additional.is_positive() { "+" } else { "-" };
-
-            Some(format!(
-                "{}{} {} {}",
-                constant_sign,
-                constant,
-                additional_sign,
-                additional.abs()
-            ))
-        }
+    let exponent = value.ln().round();
+    if (-1f64..=2f64).contains(&exponent) || exponent.abs() > 20f64 {
+        return None;
+    }
+
+    if (value - exponent.exp()).abs() < CONSTANT_TOLERANCE * value {
+        Some(format!("e^{}", exponent))
     } else {
         None
     }
 }
 
+/// Find a custom constant that the value is an integer multiple of, eg. 2G.
+fn equivalent_custom_constant(value: f64, constants: &[(f64, String)]) -> Option<String> {
+    for (constant, symbol) in constants {
+        if *constant == 0f64 || !constant.is_finite() {
+            continue;
+        }
+
+        let multiple = value / constant;
+        let rounded = multiple.round();
+        if rounded == 0f64 || rounded.abs() > 10f64 || (multiple - rounded).abs() > 1e-9 {
+            continue;
+        }
+
+        return Some(match rounded as i32 {
+            1 => symbol.clone(),
+            -1 => format!("-{}", symbol),
+            multiple => format!("{}{}", multiple, symbol),
+        });
+    }
+
+    None
+}
+
+/// Find a square root that the value is equal to, eg. √5, or an integer plus or minus
+/// a square root, eg. 1 + √2. The integer that is closest to zero is used.
 fn equivalent_root(value: f64) -> Option<String> {
-    if value.fract().abs() == 0f64 || value > 10e5f64 {
+    if value.fract().abs() == 0f64 || value.abs() > 10e5f64 {
         return None;
     }
 
-    let squared = KalkValue::Number(float!(value * value), float!(0), None)
-        .round_if_needed()
-        .values()
-        .0;
+    for n in 0..=10 {
+        for integer in [n as f64, -n as f64] {
+            let root = value - integer;
+            let radicand = (root * root).round();
+            if radicand < 2f64
+                || radicand.sqrt().fract() == 0f64
+                || (integer != 0f64 && radicand > MAX_RADICAND_WITH_INTEGER)
+                || (root.abs() - radicand.sqrt()).abs() >= CONSTANT_TOLERANCE * root.abs().max(1f64)
+            {
+                continue;
+            }
 
-    if squared.clone().sqrt().fract() != 0f64 && squared.clone().fract() == 0f64 {
-        Some(format!("√{}", primitive!(squared) as i32))
-    } else {
-        None
+            return Some(match (integer == 0f64, root < 0f64) {
+                (true, false) => format!("√{}", radicand),
+                (true, true) => format!("-√{}", radicand),
+                (false, false) => format!("{} + √{}", integer, radicand),
+                (false, true) => format!("{} - √{}", integer, radicand),
+            });
+        }
     }
+
+    None
 }
 
+/// Round the value to an integer if it's within the tolerance of one. Without a tolerance,
+/// values close to zero are rounded less aggressively than others.
 pub(super) fn round(
     input: &KalkValue,
     complex_number_type: ComplexNumberType,
+    tolerance: Option<f64>,
 ) -> Option<KalkValue> {
     let (real, imaginary, _) = if let KalkValue::Number(real, imaginary, unit) = input {
         (real, imaginary, unit)
@@ -274,7 +477,11 @@ pub(super) fn round(
     let integer = value.clone().abs().trunc();
 
     // If it's zero something, don't do the rounding as aggressively.
-    let (limit_floor, limit_ceil) = if integer == 0f64 {
+    let (limit_floor, limit_ceil) = if let Some(tolerance) = tolerance {
+        let limit = tolerance.log10();
+
+        (limit, limit)
+    } else if integer == 0f64 {
         (-8f64, -5f64)
     } else {
         (-4f64, -6f64)
@@ -312,6 +519,68 @@ pub(super) fn round(
     }
 }
 
+/// Write an estimate of the imaginary part of a number together with the imaginary unit.
+/// A dot is put between them when the estimate ends with a fraction or root,
+/// eg. 1/3·i, since 1/3i could be mistaken for 1/(3i).
+pub(super) fn with_imaginary_unit(estimate: &str) -> String {
+    if estimate == "1" {
+        String::from("i")
+    } else if estimate.contains(' ') {
+        format!("({})i", estimate)
+    } else if estimate.contains('/') || estimate.contains('√') {
+        format!("{}·i", estimate)
+    } else {
+        format!("{}i", estimate)
+    }
+}
+
+/// Replace simple fractions in an estimate with unicode vulgar fractions, eg. 2 + 1/2 => 2½.
+pub(super) fn to_vulgar_fractions(estimate: &str) -> String {
+    fn term_to_vulgar_fraction(term: &str) -> Option<String> {
+        let (sign, unsigned) = if let Some(unsigned) = term.strip_prefix('-') {
+            ("-", unsigned)
+        } else {
+            ("", term)
+        };
+        let (fraction, suffix) = if let Some(fraction) = unsigned.strip_suffix("·i") {
+            (fraction, "·i")
+        } else if let Some(fraction) = unsigned.strip_suffix('i') {
+            (fraction, "i")
+        } else {
+            (unsigned, "")
+        };
+        let (numer, denom) = fraction.split_once('/')?;
+        let vulgar_fraction =
+            text_utils::parts_to_vulgar_fraction(numer.parse().ok()?, denom.parse().ok()?)?;
+
+        Some(format!("{}{}{}", sign, vulgar_fraction, suffix))
+    }
+
+    let terms: Vec<&str> = estimate.split(' ').collect();
+    let mut output: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < terms.len() {
+        // Mixed numbers, eg. 100 + 1/3 => 100⅓
+        if i + 2 < terms.len() {
+            let is_integer = terms[i].trim_start_matches('-').parse::<u64>().is_ok();
+            let has_same_sign = (terms[i].starts_with('-') && terms[i + 1] == "-")
+                || (!terms[i].starts_with('-') && terms[i + 1] == "+");
+            if is_integer && has_same_sign && !terms[i + 2].ends_with('i') {
+                if let Some(vulgar_fraction) = term_to_vulgar_fraction(terms[i + 2]) {
+                    output.push(format!("{}{}", terms[i], vulgar_fraction));
+                    i += 3;
+                    continue;
+                }
+            }
+        }
+
+        output.push(term_to_vulgar_fraction(terms[i]).unwrap_or_else(|| terms[i].to_string()));
+        i += 1;
+    }
+
+    output.join(" ")
+}
+
 pub(super) fn trim_zeroes(input: &str) -> String {
     if input.contains('.') {
         input
@@ -394,8 +663,162 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_estimate_with_options() {
+        let options = RoundingOptions {
+            fractions: false,
+            constants: false,
+            ..Default::default()
+        };
+        assert_eq!(KalkValue::from(0.5).estimate_with(&options), None);
+        assert_eq!(
+            KalkValue::from(std::f64::consts::PI).estimate_with(&options),
+            None
+        );
+        assert_eq!(
+            KalkValue::from(0.99999999).estimate_with(&options),
+            Some(String::from("1"))
+        );
+        assert_eq!(
+            KalkValue::from(2f64.sqrt()).estimate_with(&options),
+            Some(String::from("√2"))
+        );
+
+        let options = RoundingOptions {
+            vulgar_fractions: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            KalkValue::from(2.5).estimate_with(&options),
+            Some(String::from("2½"))
+        );
+    }
+
+    #[test]
+    fn test_constant_families() {
+        let phi = (1f64 + 5f64.sqrt()) / 2f64;
+        let in_out = vec![
+            (std::f64::consts::PI, "π"),
+            (-std::f64::consts::PI, "-π"),
+            (std::f64::consts::PI + 1f64, "π + 1"),
+            (-std::f64::consts::PI - 1f64, "-π - 1"),
+            (std::f64::consts::PI - 3f64, "π - 3"),
+            (phi, "ϕ"),
+            (phi - 1f64, "1/ϕ"),
+            (phi * phi, "ϕ²"),
+            (0.5772156649015329, "γ"),
+            (2f64.ln(), "ln(2)"),
+            (10f64.ln(), "ln(10)"),
+            (3f64.sqrt() / 2f64, "√3/2"),
+            (0.5f64.sqrt(), "1/√2"),
+            (std::f64::consts::PI.powi(2) / 6f64, "π²/6"),
+            (2f64 * std::f64::consts::PI, "2π"),
+            (std::f64::consts::PI / 2f64, "π/2"),
+            (-3f64 * std::f64::consts::PI / 4f64, "-3π/4"),
+            (5f64 * std::f64::consts::PI / 12f64, "5π/12"),
+            (7f64 * std::f64::consts::PI, "7π"),
+            (3f64.exp(), "e^3"),
+            ((-2f64).exp(), "e^-2"),
+        ];
+
+        for (input, output) in in_out {
+            assert_eq!(equivalent_constant(input), Some(String::from(output)));
+        }
+
+        // Close, but not close enough
+        assert_eq!(equivalent_constant(3.1415929), None);
+        assert_eq!(equivalent_constant(0.5772), None);
+        // Partial sums of 1/n², which approach π²/6
+        assert_eq!(equivalent_constant(1.6449330668), None);
+    }
+
+    #[test]
+    fn test_equivalent_root() {
+        let in_out = vec![
+            (5f64.sqrt(), "√5"),
+            (-5f64.sqrt(), "-√5"),
+            (1f64 + 2f64.sqrt(), "1 + √2"),
+            (2f64 - 3f64.sqrt(), "2 - √3"),
+            (-3f64 + 7f64.sqrt(), "-3 + √7"),
+            (1000f64.sqrt(), "√1000"),
+        ];
+
+        for (input, output) in in_out {
+            assert_eq!(equivalent_root(input), Some(String::from(output)));
+        }
+
+        assert_eq!(equivalent_root(2.00001f64.sqrt()), None);
+        assert_eq!(equivalent_root(3f64), None);
+        assert_eq!(equivalent_root(5f64 + 1000f64.sqrt()), None);
+    }
+
+    #[test]
+    fn test_custom_constants() {
+        let options = RoundingOptions {
+            custom_constants: vec![(0.915965594177219, String::from("G"))],
+            ..Default::default()
+        };
+        let estimate = |value: f64| KalkValue::from(value).estimate_with(&options);
+        assert_eq!(estimate(0.915965594177219), Some(String::from("G")));
+        assert_eq!(estimate(-0.915965594177219), Some(String::from("-G")));
+        assert_eq!(estimate(2f64 * 0.915965594177219), Some(String::from("2G")));
+        assert_eq!(estimate(0.5), Some(String::from("1/2")));
+        assert_eq!(estimate(0.9159), None);
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(-0f64).to_string(), "0");
+        assert_eq!(normalize(0.1 + 0.2 - 0.3), 0f64);
+        assert_eq!(normalize(-(0.1 + 0.2 - 0.3)).to_string(), "0");
+        assert_eq!(normalize(0.9999999999), 1f64);
+        assert_eq!(normalize(-2.00000000001), -2f64);
+        assert_eq!(normalize(0.99999), 0.99999);
+        assert_eq!(normalize(1e-9), 1e-9);
+        assert_eq!(normalize(1e20), 1e20);
+        assert!(normalize(f64::NAN).is_nan());
+    }
+
+    #[test]
+    fn test_certain_digits() {
+        assert_eq!(
+            certain_digits(0.29999999999999993, 0.30000000000000004),
+            Some((String::from("0.3000000000"), 10))
+        );
+        assert_eq!(
+            certain_digits(0.9999999, 1.0000001),
+            Some((String::from("1.000000"), 6))
+        );
+        assert_eq!(
+            certain_digits(-0.0001, 0.0001),
+            Some((String::from("0.000"), 3))
+        );
+        assert_eq!(certain_digits(0f64, 4f64), None);
+    }
+
+    #[test]
+    fn test_normalize_zero_str() {
+        assert_eq!(normalize_zero_str(String::from("-0")), "0");
+        assert_eq!(normalize_zero_str(String::from("-0.000")), "0.000");
+        assert_eq!(normalize_zero_str(String::from("-0.001")), "-0.001");
+        assert_eq!(normalize_zero_str(String::from("-10")), "-10");
+        assert_eq!(normalize_zero_str(String::from("0")), "0");
+    }
+
+    #[test]
+    fn test_to_vulgar_fractions() {
+        assert_eq!(to_vulgar_fractions("1/2"), "½");
+        assert_eq!(to_vulgar_fractions("-3/4"), "-¾");
+        assert_eq!(to_vulgar_fractions("100 + 1/3"), "100⅓");
+        assert_eq!(to_vulgar_fractions("-100 - 2/3"), "-100⅔");
+        assert_eq!(to_vulgar_fractions("2 + 1/2i"), "2 + ½i");
+        assert_eq!(to_vulgar_fractions("4/3"), "4/3");
+        assert_eq!(to_vulgar_fractions("π/3"), "π/3");
+    }
+
     #[test]
     fn test_equivalent_fraction() {
+        let equivalent_fraction = |value: f64| equivalent_fraction(value, 100);
         assert_eq!(equivalent_fraction(0.5f64).unwrap(), "1/2");
         assert_eq!(equivalent_fraction(-0.5f64).unwrap(), "-1/2");
         assert_eq!(equivalent_fraction(1f64 / 3f64).unwrap(), "1/3");
@@ -409,5 +832,37 @@ mod tests {
         assert!(equivalent_fraction(-0.9999999f64).is_none());
         assert!(equivalent_fraction(0.9999999f64).is_none());
         assert!(equivalent_fraction(1.9999999f64).is_none());
+
+        // Fractions without a short repeating decimal part
+        assert_eq!(equivalent_fraction(1f64 / 17f64).unwrap(), "1/17");
+        assert_eq!(equivalent_fraction(22f64 / 7f64).unwrap(), "3 + 1/7");
+        assert_eq!(equivalent_fraction(-89f64 / 97f64).unwrap(), "-89/97");
+        assert_eq!(equivalent_fraction(0.0588235294).unwrap(), "1/17");
+        assert!(equivalent_fraction(1f64 / 101f64).is_none());
+        assert!(equivalent_fraction(0.25f64).is_some());
+        assert!(equivalent_fraction(0.53f64).is_none());
+        assert!(equivalent_fraction(std::f64::consts::PI).is_none());
+    }
+
+    #[test]
+    fn test_max_denominator() {
+        assert_eq!(
+            super::equivalent_fraction(1f64 / 101f64, 1000).unwrap(),
+            "1/101"
+        );
+        assert_eq!(
+            super::equivalent_fraction(355f64 / 113f64, 1000).unwrap(),
+            "3 + 16/113"
+        );
+        assert!(super::equivalent_fraction(1f64 / 17f64, 10).is_none());
+
+        let options = RoundingOptions {
+            max_denominator: 1000,
+            ..Default::default()
+        };
+        assert_eq!(
+            KalkValue::from(1f64 / 317f64).estimate_with(&options),
+            Some(String::from("1/317"))
+        );
     }
 }
@@ -0,0 +1,214 @@
+use super::KalkValue;
+
+/// Vectors shorter than this are always stored as regular vectors.
+const MIN_SPARSE_LENGTH: usize = 64;
+
+/// A vector where most of the items are zero, which only stores the other items,
+/// eg. the result of `[1 if x = y else 0 for x, y ...]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseVector {
+    length: usize,
+    /// The items that aren't zero, with their (zero-based) indexes in increasing order.
+    entries: Vec<(usize, KalkValue)>,
+}
+
+impl SparseVector {
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// The item at the (zero-based) index, or None if it's outside the vector.
+    pub fn get(&self, index: usize) -> Option<KalkValue> {
+        if index >= self.length {
+            return None;
+        }
+
+        Some(
+            match self.entries.binary_search_by_key(&index, |(i, _)| *i) {
+                Ok(position) => self.entries[position].1.clone(),
+                Err(_) => KalkValue::from(0f64),
+            },
+        )
+    }
+
+    /// The items that aren't zero, with their (zero-based) indexes.
+    pub fn entries(&self) -> &[(usize, KalkValue)] {
+        &self.entries
+    }
+
+    pub fn to_dense(&self) -> Vec<KalkValue> {
+        let mut values = vec![KalkValue::from(0f64); self.length];
+        for (index, value) in &self.entries {
+            values[*index] = value.clone();
+        }
+
+        values
+    }
+
+    /// Apply the function to every item that isn't zero. The function should give zero for zero,
+    /// eg. multiplication with a finite number.
+    pub(crate) fn map<E>(
+        self,
+        mut f: impl FnMut(KalkValue) -> Result<KalkValue, E>,
+    ) -> Result<SparseVector, E> {
+        let mut entries = Vec::with_capacity(self.entries.len());
+        for (index, value) in self.entries {
+            let value = f(value)?;
+            if !is_zero(&value) {
+                entries.push((index, value));
+            }
+        }
+
+        Ok(SparseVector {
+            length: self.length,
+            entries,
+        })
+    }
+
+    /// Combine the items at the same indexes with the function, eg. to add the vectors,
+    /// which need to have the same length. The function should give zero for two zeros.
+    pub(crate) fn zip<E>(
+        self,
+        other: SparseVector,
+        mut f: impl FnMut(KalkValue, KalkValue) -> Result<KalkValue, E>,
+    ) -> Result<SparseVector, E> {
+        let mut entries = Vec::new();
+        let mut left = self.entries.into_iter().peekable();
+        let mut right = other.entries.into_iter().peekable();
+        loop {
+            // Indexes are always smaller than the length, so MAX means that there are no more
+            let left_index = left.peek().map_or(usize::MAX, |(i, _)| *i);
+            let right_index = right.peek().map_or(usize::MAX, |(i, _)| *i);
+            let (index, value) = if left_index == usize::MAX && right_index == usize::MAX {
+                break;
+            } else if left_index == right_index {
+                let (index, value) = left.next().unwrap();
+                (index, f(value, right.next().unwrap().1)?)
+            } else if left_index < right_index {
+                let (index, value) = left.next().unwrap();
+                (index, f(value, KalkValue::from(0f64))?)
+            } else {
+                let (index, value) = right.next().unwrap();
+                (index, f(KalkValue::from(0f64), value)?)
+            };
+
+            if !is_zero(&value) {
+                entries.push((index, value));
+            }
+        }
+
+        Ok(SparseVector {
+            length: self.length,
+            entries,
+        })
+    }
+}
+
+/// Collects the items of a vector one at a time, eg. in a comprehension, and only stores
+/// the ones that aren't zero, so that vectors of mostly zeros don't take up as much memory.
+pub(crate) struct VectorBuilder {
+    length: usize,
+    entries: Vec<(usize, KalkValue)>,
+}
+
+impl VectorBuilder {
+    pub(crate) fn new() -> Self {
+        VectorBuilder {
+            length: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, value: KalkValue) {
+        if !is_zero(&value) {
+            self.entries.push((self.length, value));
+        }
+
+        self.length += 1;
+    }
+
+    /// A sparse vector if more than half of the items are zero, and otherwise a regular vector.
+    pub(crate) fn build(self) -> KalkValue {
+        let vector = SparseVector {
+            length: self.length,
+            entries: self.entries,
+        };
+
+        if vector.length >= MIN_SPARSE_LENGTH && vector.entries.len() * 2 < vector.length {
+            KalkValue::SparseVector(vector)
+        } else {
+            KalkValue::Vector(vector.to_dense())
+        }
+    }
+}
+
+/// Only real or imaginary zeros without a unit are left out, since eg. `0 m` has to keep its unit.
+fn is_zero(value: &KalkValue) -> bool {
+    matches!(value, KalkValue::Number(real, imaginary, None) if *real == 0f64 && *imaginary == 0f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VectorBuilder;
+    use crate::kalk_value::KalkValue;
+
+    fn build(values: &[f64]) -> KalkValue {
+        let mut builder = VectorBuilder::new();
+        for value in values {
+            builder.push(KalkValue::from(*value));
+        }
+
+        builder.build()
+    }
+
+    #[test]
+    fn test_build() {
+        let mut values = vec![0f64; 100];
+        values[3] = 2f64;
+        values[99] = 5f64;
+        let vector = match build(&values) {
+            KalkValue::SparseVector(vector) => vector,
+            value => panic!("Expected a sparse vector, got {:?}", value),
+        };
+        assert_eq!(vector.len(), 100);
+        assert_eq!(vector.entries().len(), 2);
+        assert_eq!(vector.get(3), Some(KalkValue::from(2f64)));
+        assert_eq!(vector.get(4), Some(KalkValue::from(0f64)));
+        assert_eq!(vector.get(100), None);
+        assert_eq!(
+            vector.to_dense(),
+            values.into_iter().map(KalkValue::from).collect::<Vec<_>>()
+        );
+
+        // Short vectors and vectors of mostly non-zero values stay regular vectors
+        assert!(matches!(build(&[0f64; 10]), KalkValue::Vector(_)));
+        assert!(matches!(build(&[1f64; 100]), KalkValue::Vector(_)));
+    }
+
+    #[test]
+    fn test_zip() {
+        let mut left = vec![0f64; 100];
+        let mut right = vec![0f64; 100];
+        left[1] = 1f64;
+        left[50] = 2f64;
+        right[50] = -2f64;
+        right[70] = 3f64;
+        let sparse = |values: &[f64]| match build(values) {
+            KalkValue::SparseVector(vector) => vector,
+            value => panic!("Expected a sparse vector, got {:?}", value),
+        };
+
+        let sum = sparse(&left)
+            .zip(sparse(&right), |x, y| x.add_without_unit(&y))
+            .unwrap();
+        assert_eq!(sum.len(), 100);
+        assert_eq!(
+            sum.entries(),
+            &[(1, KalkValue::from(1f64)), (70, KalkValue::from(3f64))]
+        );
+    }
+}
@@ -2,26 +2,31 @@ use crate::kalk_value::*;
 
 impl KalkValue {
     pub fn to_f64(&self) -> f64 {
-        if let KalkValue::Number(real, _, _) = self {
-            real.to_f64_round(rug::float::Round::Nearest)
-        } else {
-            f64::NAN
+        match self {
+            KalkValue::Number(real, _, _) => real.to_f64_round(rug::float::Round::Nearest),
+            KalkValue::Integer(integer) => integer.to_f64(),
+            _ => f64::NAN,
         }
     }
 
     pub fn imaginary_to_f64(&self) -> f64 {
-        if let KalkValue::Number(_, imaginary, _) = self {
-            imaginary.to_f64_round(rug::float::Round::Nearest)
-        } else {
-            f64::NAN
+        match self {
+            KalkValue::Number(_, imaginary, _) => {
+                imaginary.to_f64_round(rug::float::Round::Nearest)
+            }
+            KalkValue::Integer(_) => 0f64,
+            _ => f64::NAN,
         }
     }
 
     pub fn values(self) -> (Float, Float) {
-        if let KalkValue::Number(real, imaginary, _) = self {
-            (real, imaginary)
-        } else {
-            (Float::with_val(63, 0), Float::with_val(63, 0))
+        match self {
+            KalkValue::Number(real, imaginary, _) => (real, imaginary),
+            KalkValue::Integer(integer) => (
+                Float::with_val(63, integer.to_f64()),
+                Float::with_val(63, 0),
+            ),
+            _ => (Float::with_val(63, 0), Float::with_val(63, 0)),
         }
     }
 }
@@ -0,0 +1,538 @@
+use crate::ast::{ConditionalPiece, Expr, ExprSpan, Identifier, Stmt};
+use crate::kalk_value::KalkValue;
+use crate::lexer::TokenKind;
+
+impl Stmt {
+    /// Convert the statement into a LaTeX string, eg. `f(x) = 2x` => `f\left(x\right) = 2 \cdot x`.
+    pub fn to_latex(&self) -> String {
+        match self {
+            Stmt::VarDecl(identifier, expr) => {
+                format!("{} = {}", identifier_to_latex(identifier), expr.to_latex())
+            }
+            Stmt::MultiVarDecl(identifiers, expr) => {
+                let identifiers = identifiers
+                    .iter()
+                    .map(identifier_to_latex)
+                    .collect::<Vec<String>>();
+
+                format!(
+                    "\\left({}\\right) = {}",
+                    identifiers.join(", "),
+                    expr.to_latex()
+                )
+            }
+            Stmt::FnDecl(identifier, parameters, body) => {
+                let parameters = parameters
+                    .iter()
+                    .map(|parameter| {
+                        let name = parameter
+                            .strip_prefix(&format!("{}-", identifier.full_name))
+                            .unwrap_or(parameter);
+
+                        name_to_latex(name)
+                    })
+                    .collect::<Vec<String>>();
+
+                format!(
+                    "{}\\left({}\\right) = {}",
+                    identifier_to_latex(identifier),
+                    parameters.join(", "),
+                    body.to_latex()
+                )
+            }
+            Stmt::UnitDecl(identifier, _, def) => {
+                format!("\\mathrm{{{}}} = {}", identifier, def.to_latex())
+            }
+            Stmt::Expr(expr) => expr.to_latex(),
+        }
+    }
+}
+
+impl Expr {
+    /// Convert the expression into a LaTeX string, eg. `sqrt(x)/2` => `\frac{\sqrt{x}}{2}`.
+    pub fn to_latex(&self) -> String {
+        match self {
+            Expr::Binary(left, op, right, _) => binary_to_latex(left, op, right),
+            Expr::Unary(op, expr, _) => match op {
+                TokenKind::Minus => format!("-{}", expr.to_latex()),
+                TokenKind::Not => format!("\\lnot {}", expr.to_latex()),
+                TokenKind::Exclamation => format!("{}!", expr.to_latex()),
+                TokenKind::Percent => format!("{}\\%", expr.to_latex()),
+                TokenKind::GreaterThan => format!("{}^{{+}}", expr.to_latex()),
+                TokenKind::LessThan => format!("{}^{{-}}", expr.to_latex()),
+                _ => expr.to_latex(),
+            },
+            Expr::Unit(identifier, expr) => match identifier.as_ref() {
+                "deg" | "°" => format!("{}^{{\\circ}}", expr.to_latex()),
+                _ => format!("{}\\,\\mathrm{{{}}}", expr.to_latex(), identifier),
+            },
+            Expr::Var(identifier) => identifier_to_latex(identifier),
+            Expr::Group(expr) => format!("\\left({}\\right)", expr.to_latex()),
+            Expr::FnCall(identifier, arguments) => fn_call_to_latex(identifier, arguments),
+            Expr::Literal(value, _) => value.to_string(),
+            Expr::Integer(value) => value.to_string(),
+            Expr::Boolean(value) => format!("\\text{{{}}}", value),
+            Expr::String(value) => format!("\\text{{\"{}\"}}", text_to_latex(value)),
+            Expr::Piecewise(pieces) => piecewise_to_latex(pieces),
+            Expr::Vector(values) => format!("\\left({}\\right)", join_latex(values, ", ")),
+            Expr::Matrix(rows) => {
+                let rows = rows
+                    .iter()
+                    .map(|row| join_latex(row, " & "))
+                    .collect::<Vec<String>>();
+
+                format!("\\begin{{bmatrix}}{}\\end{{bmatrix}}", rows.join(" \\\\ "))
+            }
+            Expr::Indexer(value, indexes) => {
+                format!("{}_{{{}}}", value.to_latex(), join_latex(indexes, ", "))
+            }
+            Expr::Comprehension(left, conditions, _) => format!(
+                "\\left[{} : {}\\right]",
+                left.to_latex(),
+                join_latex(conditions, ", ")
+            ),
+            Expr::Equation(left, right, _) => format!("{} = {}", left.to_latex(), right.to_latex()),
+            Expr::Let(identifier, value, body) => format!(
+                "\\text{{let }} {} = {} \\text{{ in }} {}",
+                identifier_to_latex(identifier),
+                value.to_latex(),
+                body.to_latex()
+            ),
+            Expr::WithAngleUnit(angle_unit, expr) => format!(
+                "\\left.{}\\right|_{{\\text{{{}}}}}",
+                expr.to_latex(),
+                angle_unit
+            ),
+            Expr::If(condition, then_branch, else_branch) => format!(
+                "\\begin{{cases}}{} & \\text{{if }} {} \\\\ {} & \\text{{otherwise}}\\end{{cases}}",
+                then_branch.to_latex(),
+                condition.to_latex(),
+                else_branch.to_latex()
+            ),
+        }
+    }
+}
+
+impl KalkValue {
+    /// Convert the value into a LaTeX string.
+    /// Estimates (eg. 2π/3 or √5) are used when available.
+    pub fn to_latex(&self) -> String {
+        match self {
+            KalkValue::Number(real, imaginary, unit) => {
+                let value = KalkValue::Number(real.clone(), imaginary.clone(), None);
+                let value_str = if value.is_nan() {
+                    return String::from("\\text{undefined}");
+                } else if let Some(estimate) = value.estimate() {
+                    estimate
+                } else {
+                    // The pretty string may be of the format `1.2×10^20 ≈ 1.2×10^20`
+                    // if it contains scientific notation. Only keep the last part.
+                    let pretty = value.to_string_pretty();
+                    pretty.rsplit("≈ ").next().unwrap_or(&pretty).to_string()
+                };
+
+                let mut output = estimate_to_latex(&value_str);
+                if let Some(unit) = unit {
+                    output.push_str(&format!("\\,\\mathrm{{{}}}", unit));
+                }
+
+                output
+            }
+            KalkValue::Integer(integer) => integer.to_string(),
+            KalkValue::Boolean(value) => format!("\\text{{{}}}", value),
+            KalkValue::String(value) => format!("\\text{{{}}}", text_to_latex(value)),
+            KalkValue::Vector(values) => {
+                let values = values
+                    .iter()
+                    .map(|value| value.to_latex())
+                    .collect::<Vec<String>>();
+
+                format!("\\left({}\\right)", values.join(", "))
+            }
+            KalkValue::SparseVector(_) => self.clone().into_dense().to_latex(),
+            KalkValue::Matrix(rows) => {
+                let rows = rows
+                    .iter()
+                    .map(|row| {
+                        row.iter()
+                            .map(|value| value.to_latex())
+                            .collect::<Vec<String>>()
+                            .join(" & ")
+                    })
+                    .collect::<Vec<String>>();
+
+                format!("\\begin{{bmatrix}}{}\\end{{bmatrix}}", rows.join(" \\\\ "))
+            }
+        }
+    }
+}
+
+fn binary_to_latex(left: &Expr, op: &TokenKind, right: &Expr) -> String {
+    let left_str = left.to_latex();
+    let right_str = right.to_latex();
+    let op_str = match op {
+        TokenKind::Plus => "+",
+        TokenKind::Minus => "-",
+        TokenKind::Star => "\\cdot",
+        TokenKind::Slash => {
+            return format!(
+                "\\frac{{{}}}{{{}}}",
+                without_group(left).to_latex(),
+                without_group(right).to_latex()
+            )
+        }
+        TokenKind::Power => {
+            let base = if let Expr::FnCall(_, _) = left {
+                format!("\\left({}\\right)", left_str)
+            } else {
+                left_str
+            };
+
+            return format!("{{{}}}^{{{}}}", base, without_group(right).to_latex());
+        }
+        TokenKind::Percent | TokenKind::Rem => "\\operatorname{rem}",
+        TokenKind::Mod => "\\bmod",
+        TokenKind::Div => "\\operatorname{div}",
+        TokenKind::Quot => "\\operatorname{quot}",
+        TokenKind::Equals => "=",
+        TokenKind::NotEquals => "\\neq",
+        TokenKind::ApproxEquals => "\\approx",
+        TokenKind::GreaterThan => ">",
+        TokenKind::LessThan => "<",
+        TokenKind::GreaterOrEquals => "\\geq",
+        TokenKind::LessOrEquals => "\\leq",
+        TokenKind::And => "\\land",
+        TokenKind::Or => "\\lor",
+        TokenKind::ToKeyword | TokenKind::Arrow => "\\to",
+        TokenKind::Colon => ":",
+        _ => "?",
+    };
+
+    format!("{} {} {}", left_str, op_str, right_str)
+}
+
+fn fn_call_to_latex(identifier: &Identifier, arguments: &[Expr]) -> String {
+    let primes = "'".repeat(identifier.prime_count as usize);
+    match (identifier.pure_name.as_ref(), arguments) {
+        ("sqrt", [x]) => return format!("\\sqrt{{{}}}", without_group(x).to_latex()),
+        ("cbrt", [x]) => return format!("\\sqrt[3]{{{}}}", without_group(x).to_latex()),
+        ("root", [x, n]) => {
+            return format!(
+                "\\sqrt[{}]{{{}}}",
+                without_group(n).to_latex(),
+                without_group(x).to_latex()
+            )
+        }
+        ("abs", [x]) => return format!("\\left|{}\\right|", x.to_latex()),
+        ("floor", [x]) => return format!("\\left\\lfloor {} \\right\\rfloor", x.to_latex()),
+        ("ceil", [x]) => return format!("\\left\\lceil {} \\right\\rceil", x.to_latex()),
+        ("sum", [start, end, expr]) | ("prod", [start, end, expr]) => {
+            let symbol = if identifier.pure_name == "sum" {
+                "\\sum"
+            } else {
+                "\\prod"
+            };
+
+            return format!(
+                "{}_{{{}}}^{{{}}} {}",
+                symbol,
+                start.to_latex(),
+                end.to_latex(),
+                expr.to_latex()
+            );
+        }
+        ("integrate", [a, b, expr]) => {
+            return format!(
+                "\\int_{{{}}}^{{{}}} {}",
+                a.to_latex(),
+                b.to_latex(),
+                expr.to_latex()
+            )
+        }
+        ("integrate", [a, b, expr, Expr::Var(dx)]) => {
+            return format!(
+                "\\int_{{{}}}^{{{}}} {} \\, {}",
+                a.to_latex(),
+                b.to_latex(),
+                expr.to_latex(),
+                dx.full_name
+            )
+        }
+        _ => (),
+    }
+
+    let name = match identifier.pure_name.as_ref() {
+        "sin" | "cos" | "tan" | "cot" | "sec" | "sinh" | "cosh" | "tanh" | "coth" | "ln"
+        | "log" | "exp" | "arg" | "gcd" | "max" | "min" => format!("\\{}", identifier.pure_name),
+        "cosec" => String::from("\\csc"),
+        "asin" => String::from("\\arcsin"),
+        "acos" => String::from("\\arccos"),
+        "atan" => String::from("\\arctan"),
+        "gamma" | "Γ" => String::from("\\Gamma"),
+        _ if identifier.pure_name.chars().count() > 1 => {
+            format!("\\operatorname{{{}}}", identifier.pure_name)
+        }
+        _ => identifier.pure_name.clone(),
+    };
+
+    format!(
+        "{}{}\\left({}\\right)",
+        name,
+        primes,
+        join_latex(arguments, ", ")
+    )
+}
+
+fn piecewise_to_latex(pieces: &[ConditionalPiece]) -> String {
+    let pieces = pieces
+        .iter()
+        .map(|piece| {
+            let is_otherwise = piece.condition
+                == Expr::Binary(
+                    Box::new(Expr::Literal(1f64, ExprSpan::default())),
+                    TokenKind::Equals,
+                    Box::new(Expr::Literal(1f64, ExprSpan::default())),
+                    ExprSpan::default(),
+                );
+            if is_otherwise {
+                format!("{} & \\text{{otherwise}}", piece.expr.to_latex())
+            } else {
+                format!(
+                    "{} & \\text{{if }} {}",
+                    piece.expr.to_latex(),
+                    piece.condition.to_latex()
+                )
+            }
+        })
+        .collect::<Vec<String>>();
+
+    format!("\\begin{{cases}}{}\\end{{cases}}", pieces.join(" \\\\ "))
+}
+
+fn identifier_to_latex(identifier: &Identifier) -> String {
+    format!(
+        "{}{}",
+        name_to_latex(&identifier.pure_name),
+        "'".repeat(identifier.prime_count as usize)
+    )
+}
+
+fn name_to_latex(name: &str) -> String {
+    let (name, lowered) = if let Some(underscore_pos) = name.find('_') {
+        (&name[..underscore_pos], Some(&name[underscore_pos + 1..]))
+    } else {
+        (name, None)
+    };
+
+    let name = match name {
+        "pi" | "π" => String::from("\\pi"),
+        "tau" | "τ" => String::from("\\tau"),
+        "phi" | "ϕ" => String::from("\\phi"),
+        "inf" | "∞" => String::from("\\infty"),
+        _ if name.chars().count() > 1 => format!("\\mathrm{{{}}}", name),
+        _ => name.to_string(),
+    };
+
+    if let Some(lowered) = lowered {
+        format!("{}_{{{}}}", name, lowered)
+    } else {
+        name
+    }
+}
+
+/// Escape the characters that have a special meaning in LaTeX, for `\text{}`.
+fn text_to_latex(text: &str) -> String {
+    let mut output = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' => output.push_str("\\textbackslash{}"),
+            '~' => output.push_str("\\textasciitilde{}"),
+            '^' => output.push_str("\\textasciicircum{}"),
+            '{' | '}' | '$' | '&' | '#' | '_' | '%' => {
+                output.push('\\');
+                output.push(c);
+            }
+            _ => output.push(c),
+        }
+    }
+
+    output
+}
+
+fn join_latex(exprs: &[Expr], separator: &str) -> String {
+    exprs
+        .iter()
+        .map(|expr| expr.to_latex())
+        .collect::<Vec<String>>()
+        .join(separator)
+}
+
+fn without_group(expr: &Expr) -> &Expr {
+    if let Expr::Group(inner) = expr {
+        inner
+    } else {
+        expr
+    }
+}
+
+/// Convert an estimate string, eg. `2π/3 + √2i`, into LaTeX.
+fn estimate_to_latex(input: &str) -> String {
+    let mut output = String::new();
+    for (i, term) in input.split(' ').enumerate() {
+        if term == "+" || term == "-" {
+            output.push_str(&format!(" {} ", term));
+            continue;
+        }
+
+        // Numbers may contain spaces between the digit groups,
+        // which are kept as thin spaces.
+        if i > 0 && !output.ends_with(' ') {
+            output.push_str("\\,");
+        }
+
+        output.push_str(&term_to_latex(term));
+    }
+
+    output
+}
+
+fn term_to_latex(term: &str) -> String {
+    if let Some(term) = term.strip_prefix('-') {
+        return format!("-{}", term_to_latex(term));
+    }
+
+    if term.len() > 1 {
+        if let Some(term) = term.strip_suffix("·i").or_else(|| term.strip_suffix('i')) {
+            return format!("{}i", term_to_latex(term));
+        }
+    }
+
+    // Polar form, eg. 2e^(iπ/3) or cis(60°)
+    if let Some((modulus, angle)) = term.split_once("e^(") {
+        let angle = angle.trim_end_matches(')');
+        return format!(
+            "{}e^{{{}}}",
+            term_to_latex(modulus.trim_end_matches('·')),
+            term_to_latex(angle)
+        );
+    }
+
+    if let Some((modulus, angle)) = term.split_once("cis(") {
+        let angle = angle.trim_end_matches(')').replace('°', "^\\circ");
+        return format!(
+            "{}\\operatorname{{cis}}({})",
+            term_to_latex(modulus.trim_end_matches('·')),
+            angle
+        );
+    }
+
+    if let Some((numer, denom)) = term.split_once('/') {
+        return format!(
+            "\\frac{{{}}}{{{}}}",
+            term_to_latex(numer),
+            term_to_latex(denom)
+        );
+    }
+
+    let mut output = String::new();
+    let mut chars = term.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            'π' => output.push_str("\\pi "),
+            'τ' => output.push_str("\\tau "),
+            'ϕ' => output.push_str("\\phi "),
+            'γ' => output.push_str("\\gamma "),
+            '²' => output.push_str("^{2}"),
+            '×' => output.push_str(" \\times "),
+            '√' => {
+                let mut radicand = String::new();
+                while let Some(digit) = chars.peek() {
+                    if !digit.is_ascii_digit() {
+                        break;
+                    }
+
+                    radicand.push(*digit);
+                    chars.next();
+                }
+
+                output.push_str(&format!("\\sqrt{{{}}}", radicand));
+            }
+            '^' => {
+                let exponent: String = chars.by_ref().collect();
+                output.push_str(&format!("^{{{}}}", exponent));
+            }
+            'l' if term.starts_with("ln(") => {
+                output.push_str("\\ln");
+                chars.next();
+            }
+            _ => output.push(c),
+        }
+    }
+
+    output.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kalk_value::KalkValue;
+    use crate::lexer::TokenKind;
+    use crate::parser;
+    use crate::test_helpers::*;
+
+    #[test]
+    fn test_value_to_latex() {
+        let in_out = vec![
+            (1f64, "1"),
+            (0.5f64, "\\frac{1}{2}"),
+            (-0.5f64, "-\\frac{1}{2}"),
+            (std::f64::consts::PI * 2f64 / 3f64, "\\frac{2\\pi}{3}"),
+            (5f64.sqrt(), "\\sqrt{5}"),
+            (2f64.sqrt() / 2f64, "\\frac{1}{\\sqrt{2}}"),
+            (std::f64::consts::E.powi(2), "e^{2}"),
+            (100f64 + 1f64 / 3f64, "100 + \\frac{1}{3}"),
+        ];
+
+        for (input, output) in in_out {
+            assert_eq!(KalkValue::from(input).to_latex(), output);
+        }
+    }
+
+    #[test]
+    fn test_complex_value_to_latex() {
+        let value = KalkValue::Number(crate::float!(0.5), crate::float!(-1), None);
+        assert_eq!(value.to_latex(), "\\frac{1}{2} - i");
+
+        let value = KalkValue::Number(crate::float!(0.5), crate::float!(3f64.sqrt() / 2f64), None);
+        assert_eq!(value.to_latex(), "e^{\\frac{i\\pi}{3}}");
+    }
+
+    #[test]
+    fn test_expr_to_latex() {
+        assert_eq!(
+            binary(literal(2f64), TokenKind::Slash, var("x")).to_latex(),
+            "\\frac{2}{x}"
+        );
+        assert_eq!(
+            fn_call(
+                "sqrt",
+                vec![*binary(var("x"), TokenKind::Plus, literal(1f64))]
+            )
+            .to_latex(),
+            "\\sqrt{x + 1}"
+        );
+        assert_eq!(
+            binary(var("x"), TokenKind::Power, group(literal(2f64))).to_latex(),
+            "{x}^{2}"
+        );
+    }
+
+    #[test]
+    fn test_stmt_to_latex() {
+        let mut context = parser::Context::new();
+        let statements = parser::parse(&mut context, "f(x) = 2x/3").unwrap();
+        assert_eq!(
+            statements[0].to_latex(),
+            "f\\left(x\\right) = \\frac{2 \\cdot x}{3}"
+        );
+    }
+}
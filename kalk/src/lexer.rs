@@ -1,12 +1,18 @@
-use crate::text_utils::{is_subscript, is_superscript};
+use crate::text_utils::{
+    is_subscript, is_superscript, is_vulgar_fraction, vulgar_fraction_to_parts,
+};
 use std::iter::Peekable;
 use std::str;
 use std::str::Chars;
+use wasm_bindgen::prelude::*;
 
-#[derive(Clone, Debug, PartialEq, Eq, Copy)]
+#[derive(Clone, Debug, PartialEq, Eq, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TokenKind {
     Unknown,
     Literal,
+    /// A string between double quotes, eg. `"km"`. The value is the text without the quotes.
+    StringLiteral,
     Identifier,
 
     Plus,
@@ -16,13 +22,25 @@ pub enum TokenKind {
     Power,
     Exclamation,
     Percent,
+    Mod,
+    Rem,
+    Div,
+    Quot,
     Tick,
     GreaterThan,
     LessThan,
     Equals,
     NotEquals,
+    /// `≈`, eg. `0.1 + 0.2 ≈ 0.3`.
+    ApproxEquals,
     GreaterOrEquals,
     LessOrEquals,
+    PlusEquals,
+    MinusEquals,
+    StarEquals,
+    SlashEquals,
+    /// `->` or `→`, eg. in `lim(sin(x)/x, x -> 0)`.
+    Arrow,
     And,
     Or,
     Not,
@@ -30,9 +48,16 @@ pub enum TokenKind {
     False,
 
     UnitKeyword,
+    ConstKeyword,
+    AliasKeyword,
     ToKeyword,
     IfKeyword,
     OtherwiseKeyword,
+    LetKeyword,
+    InKeyword,
+    WithKeyword,
+    ThenKeyword,
+    ElseKeyword,
 
     Pipe,
     OpenCeil,
@@ -62,6 +87,25 @@ pub struct Token {
     pub span: (usize, usize),
 }
 
+/// The start and end of a piece of the input, counted in characters (not bytes).
+pub type Span = (usize, usize);
+
+/// What a piece of the input is, for syntax highlighting.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenCategory {
+    Number,
+    /// Operators, brackets, separators and keywords, eg. `+`, `(` and `to`.
+    Operator,
+    Function,
+    Variable,
+    Unit,
+    /// Characters that aren't understood, and closing brackets without an opening bracket.
+    Error,
+    /// Text between double quotes, eg. `"km"`.
+    String,
+}
+
 pub struct Lexer<'a> {
     chars: Peekable<Chars<'a>>,
     index: usize,
@@ -78,10 +122,64 @@ impl<'a> Lexer<'a> {
     }
 
     pub fn lex(&mut self) -> Vec<Token> {
-        let mut tokens = Vec::new();
+        let mut tokens: Vec<Token> = Vec::new();
+        let mut open_groups = Vec::new();
 
         loop {
             let mut next = self.next();
+            match next.kind {
+                TokenKind::OpenParenthesis
+                | TokenKind::OpenDoubleBracket
+                | TokenKind::OpenCeil
+                | TokenKind::OpenFloor
+                | TokenKind::OpenBracket
+                | TokenKind::OpenBrace => open_groups.push(next.kind),
+                TokenKind::ClosedParenthesis
+                | TokenKind::ClosedDoubleBracket
+                | TokenKind::ClosedCeil
+                | TokenKind::ClosedFloor
+                | TokenKind::ClosedBracket
+                | TokenKind::ClosedBrace => {
+                    open_groups.pop();
+                }
+                _ => (),
+            }
+
+            // Allow expressions to be spread over several lines,
+            // eg. when pasting them. Newlines separate rows in matrices
+            // and pieces in piecewise functions, so those are kept.
+            if next.kind == TokenKind::Newline {
+                let inside_group = matches!(
+                    open_groups.last(),
+                    Some(TokenKind::OpenParenthesis)
+                        | Some(TokenKind::OpenDoubleBracket)
+                        | Some(TokenKind::OpenCeil)
+                        | Some(TokenKind::OpenFloor)
+                );
+                let is_continuation = tokens.last().is_some_and(|token| match token.kind {
+                    // `rem`, `div` and `quot` can also be names, eg. in `y = 2rem`
+                    TokenKind::Div => token.value.is_empty(),
+                    TokenKind::Plus
+                    | TokenKind::Minus
+                    | TokenKind::Star
+                    | TokenKind::Slash
+                    | TokenKind::Mod
+                    | TokenKind::Power
+                    | TokenKind::Comma
+                    | TokenKind::Equals
+                    | TokenKind::PlusEquals
+                    | TokenKind::MinusEquals
+                    | TokenKind::StarEquals
+                    | TokenKind::SlashEquals
+                    | TokenKind::Arrow
+                    | TokenKind::Newline => true,
+                    _ => false,
+                });
+                if inside_group || is_continuation {
+                    continue;
+                }
+            }
+
             if next.kind == TokenKind::Power && !next.value.is_empty() {
                 let value: String = next.value.drain(..).collect();
                 let span = next.span;
@@ -126,6 +224,21 @@ impl<'a> Lexer<'a> {
             return self.next_number_literal();
         }
 
+        if let Some((numer, denom)) = vulgar_fraction_to_parts(&c) {
+            let span = (self.index, self.index + 1);
+            self.advance();
+
+            return build(
+                TokenKind::Literal,
+                &(numer as f64 / denom as f64).to_string(),
+                span,
+            );
+        }
+
+        if c == '"' {
+            return self.next_string_literal();
+        }
+
         if is_valid_identifier(Some(&c)) {
             return self.next_identifier();
         }
@@ -164,8 +277,10 @@ impl<'a> Lexer<'a> {
             '%' => build(TokenKind::Percent, "", span),
             '\'' => build(TokenKind::Tick, "", span),
             '≠' => build(TokenKind::NotEquals, "", span),
+            '≈' => build(TokenKind::ApproxEquals, "", span),
             '≥' => build(TokenKind::GreaterOrEquals, "", span),
             '≤' => build(TokenKind::LessOrEquals, "", span),
+            '→' => build(TokenKind::Arrow, "", span),
             // A bit hacky. When the result is handled, this token is turned into two tokens
             'ᵀ' => build(TokenKind::Power, "T", span),
             // Some of the special symbols will be lexed here,
@@ -175,6 +290,7 @@ impl<'a> Lexer<'a> {
             'τ' => build(TokenKind::Identifier, "tau", span),
             'ϕ' => build(TokenKind::Identifier, "phi", span),
             'Γ' => build(TokenKind::Identifier, "gamma", span),
+            '∞' => build(TokenKind::Identifier, "inf", span),
             '∏' => build(TokenKind::Identifier, "prod", span),
             _ => build(TokenKind::Unknown, "", span),
         };
@@ -211,12 +327,59 @@ impl<'a> Lexer<'a> {
                 self.advance();
                 return build(TokenKind::LessOrEquals, "", span);
             }
+            (TokenKind::Plus, Some('=')) => {
+                self.advance();
+                return build(TokenKind::PlusEquals, "", span);
+            }
+            (TokenKind::Minus, Some('=')) => {
+                self.advance();
+                return build(TokenKind::MinusEquals, "", span);
+            }
+            (TokenKind::Minus, Some('>')) => {
+                self.advance();
+                return build(TokenKind::Arrow, "", span);
+            }
+            (TokenKind::Star, Some('=')) => {
+                self.advance();
+                return build(TokenKind::StarEquals, "", span);
+            }
+            (TokenKind::Slash, Some('=')) => {
+                self.advance();
+                return build(TokenKind::SlashEquals, "", span);
+            }
+            (TokenKind::Slash, Some('/')) => {
+                self.advance();
+                return build(TokenKind::Div, "", span);
+            }
             _ => (),
         }
 
         token
     }
 
+    /// A string between double quotes, where `\"` is a quote, `\\` a backslash and `\n` a new line.
+    /// A string without a closing quote becomes an unknown token with a quote as the value.
+    fn next_string_literal(&mut self) -> Token {
+        let start = self.index;
+        let mut value = String::new();
+        self.advance(); // Opening quote
+
+        while let Some(c) = self.advance() {
+            match c {
+                '"' => return build(TokenKind::StringLiteral, &value, (start, self.index)),
+                '\\' => match self.advance() {
+                    Some('n') => value.push('\n'),
+                    Some(escaped) => value.push(escaped),
+                    None => break,
+                },
+                _ => value.push(c),
+            }
+        }
+
+        // The last call to advance went past the end
+        build(TokenKind::Unknown, "\"", (start, self.index - 1))
+    }
+
     fn next_number_literal(&mut self) -> Token {
         let mut start = self.index;
         let mut end = start;
@@ -224,7 +387,7 @@ impl<'a> Lexer<'a> {
         let mut leading_zero = self.peek().unwrap_or(&'\0') == &'0';
         let mut base = 10u8;
 
-        while let Some(c) = self.peek() {
+        while let Some(&c) = self.peek() {
             // If at the second character and
             // the first character is a zero,
             // allow a letter
@@ -247,18 +410,32 @@ impl<'a> Lexer<'a> {
                 }
             }
 
-            if !c.is_digit(base as u32) && *c != '.' && *c != '_' && !c.is_whitespace()
-                || *c == '\n'
-                || *c == '\r'
-            {
+            // Digit separators, eg. 1_000_000 or 1 000 000, are left out of the value
+            if (c == '_' || c == ' ') && self.is_digit_separator(base) {
+                end += 1;
+                self.advance();
+                continue;
+            }
+
+            // Other underscores start the base of the literal, eg. 1101_2
+            if !c.is_digit(base as u32) && c != '.' && c != '_' {
                 break;
             }
 
             end += 1;
-            value.push(*c);
+            value.push(c);
             self.advance();
         }
 
+        // Unicode vulgar fraction after the literal, eg. 2½
+        if let Some((numer, denom)) = self.peek().and_then(vulgar_fraction_to_parts) {
+            if let (10, Ok(whole)) = (base, value.parse::<u64>()) {
+                value = (whole as f64 + numer as f64 / denom as f64).to_string();
+                end += 1;
+                self.advance();
+            }
+        }
+
         // Subscript unicode symbols after the literal, eg. 11₂
         let mut base_str = String::new();
         while crate::text_utils::is_subscript(self.peek().unwrap_or(&'\0')) {
@@ -288,12 +465,49 @@ impl<'a> Lexer<'a> {
         build(TokenKind::Literal, &value, (start, end))
     }
 
+    /// Check if the next character, an underscore or a space, separates groups of digits.
+    /// In decimal literals, the separator needs to be followed by exactly three digits,
+    /// so that eg. `12 5` isn't seen as 125. With a prefix like 0x, any group size is allowed
+    /// after an underscore, eg. 0xff_ff.
+    fn is_digit_separator(&self, base: u8) -> bool {
+        let mut lookahead = self.chars.clone();
+        let separator = lookahead.next();
+        let group: Vec<char> = lookahead
+            .take_while(|c| c.is_digit(base as u32))
+            .take(4)
+            .collect();
+
+        if base != 10 && separator == Some('_') {
+            !group.is_empty()
+        } else {
+            group.len() == 3
+        }
+    }
+
     fn next_identifier(&mut self) -> Token {
         let start = self.index;
         let mut end = start;
         let mut value = String::new();
         let mut subscript = String::new();
 
+        // Names of pre-defined functions may contain digits and underscores, eg. log2
+        let name: String = self
+            .chars
+            .clone()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .collect();
+        if name.chars().any(|c| c.is_ascii_digit()) && crate::prelude::is_prelude_func(&name) {
+            for _ in name.chars() {
+                self.advance();
+            }
+
+            return build(
+                TokenKind::Identifier,
+                &name,
+                (start, start + name.chars().count()),
+            );
+        }
+
         while is_valid_identifier(self.peek()) {
             let c = *self.peek().unwrap();
 
@@ -301,20 +515,31 @@ impl<'a> Lexer<'a> {
             // This is to allow the notation like the following: x_1
             if c == '_' {
                 self.advance();
+
+                // A single underscore, eg. in `_ * 1.21`, is the last result
+                if value.is_empty() && !matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+                    value.push('_');
+                    end = self.index;
+                    break;
+                }
+
                 let num = self.next().value;
                 value.push('_');
                 value.push_str(num.trim_end()); // Trim, since the number_literal function allows whitespace, which identifiers should not contain.
+                end = self.index;
                 break;
             }
 
             // Only allow identifiers with a special character to have *one* character. No more.
             // Break the loop if it isn't the first run and the current character is a special character.
+            // The exception is the units Δ°C and Δ°F.
             if end - start > 0
                 && !(c.is_ascii_alphabetic()
                     || c == '\''
                     || c == '_'
                     || is_superscript(&c)
-                    || is_subscript(&c))
+                    || is_subscript(&c)
+                    || (c == '°' && value == "Δ"))
             {
                 break;
             }
@@ -335,11 +560,21 @@ impl<'a> Lexer<'a> {
             "not" => TokenKind::Not,
             "true" => TokenKind::True,
             "false" => TokenKind::False,
-            "mod" => TokenKind::Percent,
+            "mod" => TokenKind::Mod,
+            "rem" => TokenKind::Rem,
+            "div" => TokenKind::Div,
+            "quot" => TokenKind::Quot,
             "unit" => TokenKind::UnitKeyword,
+            "const" => TokenKind::ConstKeyword,
+            "alias" => TokenKind::AliasKeyword,
             "to" => TokenKind::ToKeyword,
             "if" => TokenKind::IfKeyword,
             "otherwise" => TokenKind::OtherwiseKeyword,
+            "let" => TokenKind::LetKeyword,
+            "in" => TokenKind::InKeyword,
+            "with" => TokenKind::WithKeyword,
+            "then" => TokenKind::ThenKeyword,
+            "else" => TokenKind::ElseKeyword,
             _ => TokenKind::Identifier,
         };
 
@@ -361,7 +596,14 @@ impl<'a> Lexer<'a> {
             "sech⁻¹" => String::from("asech"),
             "∛" => String::from("cbrt"),
             "°" => String::from("deg"),
-            _ => value, // things like log_2 are handled in the parser
+            // Eg. degC is °C and uF is µF. Things like log_2 are handled in the parser.
+            _ => crate::temperature::ASCII_NAMES
+                .iter()
+                .find(|(name, _)| *name == value)
+                .map_or_else(
+                    || crate::units::normalize_prefix(value.clone()),
+                    |(_, unit)| unit.to_string(),
+                ),
         };
 
         if !subscript.is_empty() {
@@ -389,6 +631,92 @@ impl<'a> Lexer<'a> {
     }
 }
 
+/// Classify the pieces of the input, eg. to colourise it while it is being typed.
+/// Whitespace is not included. Only angle units, time units, SI units and units after `to`
+/// are recognised, since other units depend on the definitions in a context.
+/// See `parser::Context::highlight` for that.
+pub fn highlight(input: &str) -> Vec<(Span, TokenCategory)> {
+    highlight_with(input, |identifier| {
+        crate::prelude::is_angle_unit(identifier)
+            || crate::duration::seconds_per_unit(identifier).is_some()
+            || crate::units::is_builtin_unit(identifier)
+            || crate::temperature::is_temperature_unit(identifier)
+    })
+}
+
+/// Classify the pieces of the input, where `is_unit` decides which identifiers are units.
+pub fn highlight_with(input: &str, is_unit: impl Fn(&str) -> bool) -> Vec<(Span, TokenCategory)> {
+    let chars: Vec<char> = input.chars().collect();
+    let tokens = Lexer::new(input).lex();
+    let mut result: Vec<(Span, TokenCategory)> = Vec::new();
+    let mut open_groups = 0usize;
+
+    for (i, token) in tokens.iter().enumerate() {
+        let category = match token.kind {
+            TokenKind::Eof | TokenKind::Newline => continue,
+            TokenKind::Unknown => TokenCategory::Error,
+            TokenKind::Literal => TokenCategory::Number,
+            TokenKind::StringLiteral => TokenCategory::String,
+            TokenKind::Identifier => {
+                let previous = if i > 0 {
+                    Some(tokens[i - 1].kind)
+                } else {
+                    None
+                };
+                let next = tokens.get(i + 1).map(|token| token.kind);
+                if next == Some(TokenKind::OpenParenthesis)
+                    || crate::prelude::is_prelude_func(&token.value)
+                {
+                    TokenCategory::Function
+                } else if previous == Some(TokenKind::ToKeyword) || is_unit(&token.value) {
+                    TokenCategory::Unit
+                } else {
+                    TokenCategory::Variable
+                }
+            }
+            TokenKind::OpenParenthesis
+            | TokenKind::OpenDoubleBracket
+            | TokenKind::OpenCeil
+            | TokenKind::OpenFloor
+            | TokenKind::OpenBracket
+            | TokenKind::OpenBrace => {
+                open_groups += 1;
+                TokenCategory::Operator
+            }
+            TokenKind::ClosedParenthesis
+            | TokenKind::ClosedDoubleBracket
+            | TokenKind::ClosedCeil
+            | TokenKind::ClosedFloor
+            | TokenKind::ClosedBracket
+            | TokenKind::ClosedBrace => {
+                if open_groups == 0 {
+                    TokenCategory::Error
+                } else {
+                    open_groups -= 1;
+                    TokenCategory::Operator
+                }
+            }
+            _ => TokenCategory::Operator,
+        };
+
+        // Number literals may contain spaces, eg. `1 000`, so the span can end with some
+        let (start, mut end) = token.span;
+        end = end.min(chars.len());
+        while end > start && chars[end - 1].is_whitespace() {
+            end -= 1;
+        }
+
+        // Eg. `ᵀ` is lexed as a power and an identifier with the same span
+        if end <= start || result.last().is_some_and(|(span, _)| span.1 > start) {
+            continue;
+        }
+
+        result.push(((start, end), category));
+    }
+
+    result
+}
+
 fn build(kind: TokenKind, value: &str, span: (usize, usize)) -> Token {
     Token {
         kind,
@@ -401,9 +729,10 @@ fn is_valid_identifier(c: Option<&char>) -> bool {
     if let Some(c) = c {
         match c {
             '+' | '-' | '/' | '*' | '%' | '^' | '!' | '(' | ')' | '=' | '.' | ',' | ';' | '|'
-            | '⌊' | '⌋' | '⌈' | '⌉' | '[' | ']' | '{' | '}' | 'π' | '√' | 'τ' | 'ϕ' | 'Γ' | '<'
-            | '>' | '≠' | '≥' | '≤' | '×' | '÷' | '⋅' | '⟦' | '⟧' | '∧' | '∨' | '¬' | ':' | 'ᵀ'
-            | '\n' => false,
+            | '⌊' | '⌋' | '⌈' | '⌉' | '[' | ']' | '{' | '}' | 'π' | '√' | 'τ' | 'ϕ' | 'Γ' | '∞'
+            | '<' | '>' | '≠' | '≈' | '≥' | '≤' | '→' | '×' | '÷' | '⋅' | '⟦' | '⟧' | '∧' | '∨'
+            | '¬' | ':' | 'ᵀ' | '"' | '\n' => false,
+            _ if is_vulgar_fraction(c) => false,
             _ => !c.is_ascii_digit() || is_superscript(c) || is_subscript(c),
         }
     } else {
@@ -449,6 +778,38 @@ mod tests {
         match_tokens(tokens, expected);
     }
 
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_function_names_with_digits() {
+        let tokens = Lexer::new("ceil_log2(x) + log2x").lex();
+        assert_eq!(tokens[0].value, "ceil_log2");
+        assert_eq!(tokens[0].span, (0, 9));
+        assert_eq!(tokens[5].value, "log");
+        assert_eq!(tokens[6].kind, TokenKind::Literal);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_division_keywords() {
+        let tokens = Lexer::new("a mod b rem c div d // e quot f").lex();
+        let expected = vec![
+            TokenKind::Identifier,
+            TokenKind::Mod,
+            TokenKind::Identifier,
+            TokenKind::Rem,
+            TokenKind::Identifier,
+            TokenKind::Div,
+            TokenKind::Identifier,
+            TokenKind::Div,
+            TokenKind::Identifier,
+            TokenKind::Quot,
+            TokenKind::Identifier,
+            TokenKind::Eof,
+        ];
+
+        match_tokens(tokens, expected);
+    }
+
     #[test]
     #[wasm_bindgen_test]
     fn test_brackets() {
@@ -465,6 +826,29 @@ mod tests {
         match_tokens(tokens, expected);
     }
 
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_multiline() {
+        let tokens = Lexer::new("(1 +\n2)\n3 *\n4").lex();
+        let expected = vec![
+            TokenKind::OpenParenthesis,
+            TokenKind::Literal,
+            TokenKind::Plus,
+            TokenKind::Literal,
+            TokenKind::ClosedParenthesis,
+            TokenKind::Newline,
+            TokenKind::Literal,
+            TokenKind::Star,
+            TokenKind::Literal,
+            TokenKind::Eof,
+        ];
+
+        match_tokens(tokens, expected);
+
+        let tokens = Lexer::new("[1, 2\n3, 4]").lex();
+        assert!(tokens.iter().any(|token| token.kind == TokenKind::Newline));
+    }
+
     #[test]
     #[wasm_bindgen_test]
     fn test_empty() {
@@ -495,8 +879,47 @@ mod tests {
         match_tokens(tokens, expected);
     }
 
+    #[test_case("1_000_000", "1000000" ; "underscores")]
+    #[test_case("1 000 000", "1000000" ; "spaces")]
+    #[test_case("12 345.678 9", "12345.678" ; "spaces with decimals")]
+    #[test_case("1_000_2", "1000_2" ; "underscores with base")]
+    #[test_case("0xff_ff", "ffff_16" ; "underscores with prefix")]
+    fn test_digit_separators(input: &str, value: &str) {
+        let tokens = Lexer::new(input).lex();
+
+        assert_eq!(&tokens[0].value, value);
+        assert_eq!(tokens[0].kind, TokenKind::Literal);
+    }
+
+    #[test]
+    fn test_ambiguous_digit_separators() {
+        let tokens = Lexer::new("12 5").lex();
+        let expected = vec![TokenKind::Literal, TokenKind::Literal, TokenKind::Eof];
+        assert_eq!(&tokens[0].value, "12");
+        assert_eq!(&tokens[1].value, "5");
+        match_tokens(tokens, expected);
+
+        let tokens = Lexer::new("1 0000").lex();
+        assert_eq!(&tokens[0].value, "1");
+        assert_eq!(&tokens[1].value, "0000");
+    }
+
+    #[test_case("½", "0.5" ; "half")]
+    #[test_case("¾", "0.75" ; "three quarters")]
+    #[test_case("2½", "2.5" ; "mixed")]
+    #[test_case("10⅛", "10.125" ; "mixed two digits")]
+    fn test_vulgar_fraction_literal(input: &str, value: &str) {
+        let tokens = Lexer::new(input).lex();
+        let expected = vec![TokenKind::Literal, TokenKind::Eof];
+
+        assert_eq!(&tokens[0].value, value);
+        match_tokens(tokens, expected);
+    }
+
     #[test_case("x")]
     #[test_case("xy")]
+    #[test_case("_" ; "underscore")]
+    #[test_case("_2" ; "underscore with index")]
     fn test_identifier(input: &str) {
         let tokens = Lexer::new(input).lex();
         let expected = vec![TokenKind::Identifier, TokenKind::Eof];
@@ -505,6 +928,40 @@ mod tests {
         match_tokens(tokens, expected);
     }
 
+    #[test_case(r#""km""#, "km" ; "plain")]
+    #[test_case(r#""""#, "" ; "empty")]
+    #[test_case(r#""x = %.2f""#, "x = %.2f" ; "format")]
+    #[test_case(r#""a\"b\\c\nd""#, "a\"b\\c\nd" ; "escaped")]
+    fn test_string_literal(input: &str, value: &str) {
+        let tokens = Lexer::new(input).lex();
+        let expected = vec![TokenKind::StringLiteral, TokenKind::Eof];
+
+        assert_eq!(&tokens[0].value, value);
+        assert_eq!(tokens[0].span, (0, input.chars().count()));
+        match_tokens(tokens, expected);
+    }
+
+    #[test]
+    fn test_unterminated_string_literal() {
+        let tokens = Lexer::new("1 + \"km").lex();
+        assert_eq!(tokens[2].kind, TokenKind::Unknown);
+        assert_eq!(tokens[2].span, (4, 7));
+        assert_eq!(tokens[3].kind, TokenKind::Eof);
+    }
+
+    #[test]
+    fn test_underscore_before_operator() {
+        let tokens = Lexer::new("_ * 2").lex();
+        let expected = vec![
+            TokenKind::Identifier,
+            TokenKind::Star,
+            TokenKind::Literal,
+            TokenKind::Eof,
+        ];
+
+        match_tokens(tokens, expected);
+    }
+
     #[test]
     fn test_function_call() {
         let tokens = Lexer::new("f(x)").lex();
@@ -518,4 +975,38 @@ mod tests {
 
         match_tokens(tokens, expected);
     }
+
+    #[test]
+    fn test_highlight() {
+        assert_eq!(
+            highlight("sqrt(x) + 2 to deg"),
+            vec![
+                ((0, 4), TokenCategory::Function),
+                ((4, 5), TokenCategory::Operator),
+                ((5, 6), TokenCategory::Variable),
+                ((6, 7), TokenCategory::Operator),
+                ((8, 9), TokenCategory::Operator),
+                ((10, 11), TokenCategory::Number),
+                ((12, 14), TokenCategory::Operator),
+                ((15, 18), TokenCategory::Unit),
+            ]
+        );
+        assert_eq!(
+            highlight("x_1 + .)"),
+            vec![
+                ((0, 3), TokenCategory::Variable),
+                ((4, 5), TokenCategory::Operator),
+                ((6, 7), TokenCategory::Error),
+                ((7, 8), TokenCategory::Error),
+            ]
+        );
+        assert_eq!(
+            highlight("√4"),
+            vec![
+                ((0, 1), TokenCategory::Function),
+                ((1, 2), TokenCategory::Number)
+            ]
+        );
+        assert_eq!(highlight("Aᵀ").len(), 2);
+    }
 }
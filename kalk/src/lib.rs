@@ -4,16 +4,34 @@
 mod analysis;
 pub mod ast;
 pub mod calculation_result;
+pub mod completion;
+mod constants;
+mod differentiator;
+mod display;
+mod duration;
 mod errors;
 mod integration_testing;
 mod interpreter;
+mod interval;
 mod inverter;
 pub mod kalk_value;
-mod lexer;
+mod latex;
+pub mod lexer;
+mod mathml;
+pub mod middleware;
+mod native;
 mod numerical;
 pub mod parser;
 mod prelude;
-mod radix;
+pub mod radix;
+mod random;
+pub mod rpn;
+#[cfg(feature = "serde")]
+mod serialization;
+mod simplifier;
+pub mod steps;
 mod symbol_table;
+mod temperature;
 mod test_helpers;
 pub mod text_utils;
+mod units;
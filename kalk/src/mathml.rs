@@ -0,0 +1,634 @@
+use crate::ast::{ConditionalPiece, Expr, ExprSpan, Identifier, Stmt};
+use crate::kalk_value::KalkValue;
+use crate::lexer::TokenKind;
+
+const MATH_NAMESPACE: &str = "http://www.w3.org/1998/Math/MathML";
+
+impl Stmt {
+    /// Convert the statement into a MathML `<math>` element.
+    pub fn to_mathml(&self) -> String {
+        wrap_math(&stmt_to_mathml(self))
+    }
+}
+
+impl Expr {
+    /// Convert the expression into a MathML `<math>` element.
+    pub fn to_mathml(&self) -> String {
+        wrap_math(&expr_to_mathml(self))
+    }
+}
+
+impl KalkValue {
+    /// Convert the value into a MathML `<math>` element.
+    /// Estimates (eg. 2π/3 or √5) are used when available.
+    pub fn to_mathml(&self) -> String {
+        wrap_math(&value_to_mathml(self))
+    }
+}
+
+fn wrap_math(content: &str) -> String {
+    format!("<math xmlns=\"{}\">{}</math>", MATH_NAMESPACE, content)
+}
+
+fn mrow(content: &str) -> String {
+    format!("<mrow>{}</mrow>", content)
+}
+
+fn mo(op: &str) -> String {
+    format!("<mo>{}</mo>", escape(op))
+}
+
+fn mi(identifier: &str) -> String {
+    format!("<mi>{}</mi>", escape(identifier))
+}
+
+fn mn(number: &str) -> String {
+    format!("<mn>{}</mn>", escape(number))
+}
+
+fn mtext(text: &str) -> String {
+    format!("<mtext>{}</mtext>", escape(text))
+}
+
+fn fenced(open: &str, content: &str, close: &str) -> String {
+    mrow(&format!("{}{}{}", mo(open), content, mo(close)))
+}
+
+fn escape(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn stmt_to_mathml(stmt: &Stmt) -> String {
+    match stmt {
+        Stmt::VarDecl(identifier, expr) => mrow(&format!(
+            "{}{}{}",
+            identifier_to_mathml(identifier),
+            mo("="),
+            expr_to_mathml(expr)
+        )),
+        Stmt::MultiVarDecl(identifiers, expr) => {
+            let identifiers = identifiers
+                .iter()
+                .map(identifier_to_mathml)
+                .collect::<Vec<String>>();
+
+            mrow(&format!(
+                "{}{}{}",
+                fenced("(", &identifiers.join(&mo(",")), ")"),
+                mo("="),
+                expr_to_mathml(expr)
+            ))
+        }
+        Stmt::FnDecl(identifier, parameters, body) => {
+            let parameters = parameters
+                .iter()
+                .map(|parameter| {
+                    let name = parameter
+                        .strip_prefix(&format!("{}-", identifier.full_name))
+                        .unwrap_or(parameter);
+
+                    name_to_mathml(name)
+                })
+                .collect::<Vec<String>>();
+
+            mrow(&format!(
+                "{}{}{}{}",
+                identifier_to_mathml(identifier),
+                fenced("(", &parameters.join(&mo(",")), ")"),
+                mo("="),
+                expr_to_mathml(body)
+            ))
+        }
+        Stmt::UnitDecl(identifier, _, def) => mrow(&format!(
+            "<mi mathvariant=\"normal\">{}</mi>{}{}",
+            escape(identifier),
+            mo("="),
+            expr_to_mathml(def)
+        )),
+        Stmt::Expr(expr) => expr_to_mathml(expr),
+    }
+}
+
+fn expr_to_mathml(expr: &Expr) -> String {
+    match expr {
+        Expr::Binary(left, op, right, _) => binary_to_mathml(left, op, right),
+        Expr::Unary(op, expr, _) => match op {
+            TokenKind::Minus => mrow(&format!("{}{}", mo("-"), expr_to_mathml(expr))),
+            TokenKind::Not => mrow(&format!("{}{}", mo("¬"), expr_to_mathml(expr))),
+            TokenKind::Exclamation => mrow(&format!("{}{}", expr_to_mathml(expr), mo("!"))),
+            TokenKind::Percent => mrow(&format!("{}{}", expr_to_mathml(expr), mo("%"))),
+            TokenKind::GreaterThan => format!("<msup>{}{}</msup>", expr_to_mathml(expr), mo("+")),
+            TokenKind::LessThan => format!("<msup>{}{}</msup>", expr_to_mathml(expr), mo("-")),
+            _ => expr_to_mathml(expr),
+        },
+        Expr::Unit(identifier, expr) => match identifier.as_ref() {
+            "deg" | "°" => mrow(&format!("{}{}", expr_to_mathml(expr), mo("°"))),
+            _ => mrow(&format!(
+                "{}<mi mathvariant=\"normal\">{}</mi>",
+                expr_to_mathml(expr),
+                escape(identifier)
+            )),
+        },
+        Expr::Var(identifier) => identifier_to_mathml(identifier),
+        Expr::Group(expr) => fenced("(", &expr_to_mathml(expr), ")"),
+        Expr::FnCall(identifier, arguments) => fn_call_to_mathml(identifier, arguments),
+        Expr::Literal(value, _) => mn(&value.to_string()),
+        Expr::Integer(value) => mn(&value.to_string()),
+        Expr::Boolean(value) => mtext(&value.to_string()),
+        Expr::String(value) => format!("<ms>{}</ms>", escape(value)),
+        Expr::Piecewise(pieces) => piecewise_to_mathml(pieces),
+        Expr::Vector(values) => fenced("(", &join_mathml(values), ")"),
+        Expr::Matrix(rows) => {
+            let rows = rows
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|value| format!("<mtd>{}</mtd>", expr_to_mathml(value)))
+                        .collect::<String>()
+                })
+                .collect::<Vec<String>>();
+
+            fenced("[", &table(&rows), "]")
+        }
+        Expr::Indexer(value, indexes) => format!(
+            "<msub>{}{}</msub>",
+            expr_to_mathml(value),
+            mrow(&join_mathml(indexes))
+        ),
+        Expr::Comprehension(left, conditions, _) => fenced(
+            "[",
+            &format!(
+                "{}{}{}",
+                expr_to_mathml(left),
+                mo(":"),
+                join_mathml(conditions)
+            ),
+            "]",
+        ),
+        Expr::Equation(left, right, _) => mrow(&format!(
+            "{}{}{}",
+            expr_to_mathml(left),
+            mo("="),
+            expr_to_mathml(right)
+        )),
+        Expr::Let(identifier, value, body) => mrow(&format!(
+            "{}{}{}{}{}{}",
+            mtext("let "),
+            identifier_to_mathml(identifier),
+            mo("="),
+            expr_to_mathml(value),
+            mtext(" in "),
+            expr_to_mathml(body)
+        )),
+        Expr::WithAngleUnit(angle_unit, expr) => mrow(&format!(
+            "{}{}",
+            expr_to_mathml(expr),
+            mtext(&format!(" ({})", angle_unit))
+        )),
+        Expr::If(condition, then_branch, else_branch) => mrow(&format!(
+            "{}{}{}{}{}{}",
+            mtext("if "),
+            expr_to_mathml(condition),
+            mtext(" then "),
+            expr_to_mathml(then_branch),
+            mtext(" else "),
+            expr_to_mathml(else_branch)
+        )),
+    }
+}
+
+fn value_to_mathml(value: &KalkValue) -> String {
+    match value {
+        KalkValue::Number(real, imaginary, unit) => {
+            let value = KalkValue::Number(real.clone(), imaginary.clone(), None);
+            let value_str = if value.is_nan() {
+                return mtext("undefined");
+            } else if let Some(estimate) = value.estimate() {
+                estimate
+            } else {
+                // The pretty string may be of the format `1.2×10^20 ≈ 1.2×10^20`
+                // if it contains scientific notation. Only keep the last part.
+                let pretty = value.to_string_pretty();
+                pretty.rsplit("≈ ").next().unwrap_or(&pretty).to_string()
+            };
+
+            let mut output = estimate_to_mathml(&value_str);
+            if let Some(unit) = unit {
+                output.push_str(&format!("<mi mathvariant=\"normal\">{}</mi>", escape(unit)));
+            }
+
+            mrow(&output)
+        }
+        KalkValue::Integer(integer) => mrow(&estimate_to_mathml(&integer.to_string())),
+        KalkValue::Boolean(is_true) => mtext(&is_true.to_string()),
+        KalkValue::String(value) => mtext(value),
+        KalkValue::Vector(values) => {
+            let values = values.iter().map(value_to_mathml).collect::<Vec<String>>();
+
+            fenced("(", &values.join(&mo(",")), ")")
+        }
+        KalkValue::SparseVector(_) => value_to_mathml(&value.clone().into_dense()),
+        KalkValue::Matrix(rows) => {
+            let rows = rows
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|value| format!("<mtd>{}</mtd>", value_to_mathml(value)))
+                        .collect::<String>()
+                })
+                .collect::<Vec<String>>();
+
+            fenced("[", &table(&rows), "]")
+        }
+    }
+}
+
+fn binary_to_mathml(left: &Expr, op: &TokenKind, right: &Expr) -> String {
+    let op_str = match op {
+        TokenKind::Plus => "+",
+        TokenKind::Minus => "-",
+        TokenKind::Star => "⋅",
+        TokenKind::Slash => {
+            return format!(
+                "<mfrac>{}{}</mfrac>",
+                mrow(&expr_to_mathml(without_group(left))),
+                mrow(&expr_to_mathml(without_group(right)))
+            )
+        }
+        TokenKind::Power => {
+            return format!(
+                "<msup>{}{}</msup>",
+                mrow(&expr_to_mathml(left)),
+                mrow(&expr_to_mathml(without_group(right)))
+            )
+        }
+        TokenKind::Percent | TokenKind::Rem => "rem",
+        TokenKind::Mod => "mod",
+        TokenKind::Div => "div",
+        TokenKind::Quot => "quot",
+        TokenKind::Equals => "=",
+        TokenKind::NotEquals => "≠",
+        TokenKind::ApproxEquals => "≈",
+        TokenKind::GreaterThan => ">",
+        TokenKind::LessThan => "<",
+        TokenKind::GreaterOrEquals => "≥",
+        TokenKind::LessOrEquals => "≤",
+        TokenKind::And => "∧",
+        TokenKind::Or => "∨",
+        TokenKind::ToKeyword | TokenKind::Arrow => "→",
+        TokenKind::Colon => ":",
+        _ => "?",
+    };
+
+    mrow(&format!(
+        "{}{}{}",
+        expr_to_mathml(left),
+        mo(op_str),
+        expr_to_mathml(right)
+    ))
+}
+
+fn fn_call_to_mathml(identifier: &Identifier, arguments: &[Expr]) -> String {
+    match (identifier.pure_name.as_ref(), arguments) {
+        ("sqrt", [x]) => return format!("<msqrt>{}</msqrt>", expr_to_mathml(without_group(x))),
+        ("cbrt", [x]) => {
+            return format!(
+                "<mroot>{}{}</mroot>",
+                mrow(&expr_to_mathml(without_group(x))),
+                mn("3")
+            )
+        }
+        ("root", [x, n]) => {
+            return format!(
+                "<mroot>{}{}</mroot>",
+                mrow(&expr_to_mathml(without_group(x))),
+                mrow(&expr_to_mathml(without_group(n)))
+            )
+        }
+        ("abs", [x]) => return fenced("|", &expr_to_mathml(x), "|"),
+        ("floor", [x]) => return fenced("⌊", &expr_to_mathml(x), "⌋"),
+        ("ceil", [x]) => return fenced("⌈", &expr_to_mathml(x), "⌉"),
+        ("sum", [start, end, expr]) | ("prod", [start, end, expr]) => {
+            let symbol = if identifier.pure_name == "sum" {
+                "∑"
+            } else {
+                "∏"
+            };
+
+            return mrow(&format!(
+                "<munderover>{}{}{}</munderover>{}",
+                mo(symbol),
+                mrow(&expr_to_mathml(start)),
+                mrow(&expr_to_mathml(end)),
+                expr_to_mathml(expr)
+            ));
+        }
+        ("integrate", [a, b, expr]) => {
+            return mrow(&format!(
+                "<msubsup>{}{}{}</msubsup>{}",
+                mo("∫"),
+                mrow(&expr_to_mathml(a)),
+                mrow(&expr_to_mathml(b)),
+                expr_to_mathml(expr)
+            ))
+        }
+        ("integrate", [a, b, expr, Expr::Var(dx)]) => {
+            return mrow(&format!(
+                "<msubsup>{}{}{}</msubsup>{}{}",
+                mo("∫"),
+                mrow(&expr_to_mathml(a)),
+                mrow(&expr_to_mathml(b)),
+                expr_to_mathml(expr),
+                mi(&dx.full_name)
+            ))
+        }
+        _ => (),
+    }
+
+    let name = match identifier.pure_name.as_ref() {
+        "asin" => "sin⁻¹",
+        "acos" => "cos⁻¹",
+        "atan" => "tan⁻¹",
+        "gamma" => "Γ",
+        name => name,
+    };
+
+    mrow(&format!(
+        "{}{}{}",
+        mi(&format!(
+            "{}{}",
+            name,
+            "′".repeat(identifier.prime_count as usize)
+        )),
+        mo("\u{2061}"), // Function application
+        fenced("(", &join_mathml(arguments), ")")
+    ))
+}
+
+fn piecewise_to_mathml(pieces: &[ConditionalPiece]) -> String {
+    let rows = pieces
+        .iter()
+        .map(|piece| {
+            let is_otherwise = piece.condition
+                == Expr::Binary(
+                    Box::new(Expr::Literal(1f64, ExprSpan::default())),
+                    TokenKind::Equals,
+                    Box::new(Expr::Literal(1f64, ExprSpan::default())),
+                    ExprSpan::default(),
+                );
+            let condition = if is_otherwise {
+                mtext("otherwise")
+            } else {
+                mrow(&format!(
+                    "{}{}",
+                    mtext("if "),
+                    expr_to_mathml(&piece.condition)
+                ))
+            };
+
+            format!(
+                "<mtd>{}</mtd><mtd>{}</mtd>",
+                expr_to_mathml(&piece.expr),
+                condition
+            )
+        })
+        .collect::<Vec<String>>();
+
+    mrow(&format!("{}{}", mo("{"), table(&rows)))
+}
+
+fn table(rows: &[String]) -> String {
+    let rows = rows
+        .iter()
+        .map(|row| format!("<mtr>{}</mtr>", row))
+        .collect::<String>();
+
+    format!("<mtable>{}</mtable>", rows)
+}
+
+fn identifier_to_mathml(identifier: &Identifier) -> String {
+    if identifier.prime_count == 0 {
+        name_to_mathml(&identifier.pure_name)
+    } else {
+        format!(
+            "<msup>{}{}</msup>",
+            name_to_mathml(&identifier.pure_name),
+            mo(&"′".repeat(identifier.prime_count as usize))
+        )
+    }
+}
+
+fn name_to_mathml(name: &str) -> String {
+    let (name, lowered) = if let Some(underscore_pos) = name.find('_') {
+        (&name[..underscore_pos], Some(&name[underscore_pos + 1..]))
+    } else {
+        (name, None)
+    };
+
+    let name = match name {
+        "pi" => mi("π"),
+        "tau" => mi("τ"),
+        "phi" => mi("ϕ"),
+        "inf" => mi("∞"),
+        _ => mi(name),
+    };
+
+    if let Some(lowered) = lowered {
+        format!("<msub>{}{}</msub>", name, mn(lowered))
+    } else {
+        name
+    }
+}
+
+fn join_mathml(exprs: &[Expr]) -> String {
+    exprs
+        .iter()
+        .map(expr_to_mathml)
+        .collect::<Vec<String>>()
+        .join(&mo(","))
+}
+
+fn without_group(expr: &Expr) -> &Expr {
+    if let Expr::Group(inner) = expr {
+        inner
+    } else {
+        expr
+    }
+}
+
+/// Convert an estimate string, eg. `2π/3 + √2i`, into MathML.
+fn estimate_to_mathml(input: &str) -> String {
+    let mut output = String::new();
+    let mut number = String::new();
+    for term in input.split(' ') {
+        if term == "+" || term == "-" {
+            output.push_str(&term_to_mathml(&number));
+            output.push_str(&mo(term));
+            number.clear();
+        } else {
+            // Numbers may contain spaces between the digit groups.
+            number.push_str(term);
+        }
+    }
+
+    output.push_str(&term_to_mathml(&number));
+
+    output
+}
+
+fn term_to_mathml(term: &str) -> String {
+    if let Some(term) = term.strip_prefix('-') {
+        return mrow(&format!("{}{}", mo("-"), term_to_mathml(term)));
+    }
+
+    if term.len() > 1 {
+        if let Some(term) = term.strip_suffix("·i").or_else(|| term.strip_suffix('i')) {
+            return mrow(&format!("{}{}", term_to_mathml(term), mi("i")));
+        }
+    }
+
+    // Polar form, eg. 2e^(iπ/3) or cis(60°)
+    if let Some((modulus, angle)) = term.split_once("e^(") {
+        return mrow(&format!(
+            "{}<msup>{}{}</msup>",
+            term_to_mathml(modulus.trim_end_matches('·')),
+            mi("e"),
+            mrow(&term_to_mathml(angle.trim_end_matches(')')))
+        ));
+    }
+
+    if let Some((modulus, angle)) = term.split_once("cis(") {
+        return mrow(&format!(
+            "{}{}{}{}",
+            term_to_mathml(modulus.trim_end_matches('·')),
+            mi("cis"),
+            mo("\u{2061}"),
+            fenced("(", &mn(angle.trim_end_matches(')')), ")")
+        ));
+    }
+
+    if let Some((numer, denom)) = term.split_once('/') {
+        return format!(
+            "<mfrac>{}{}</mfrac>",
+            mrow(&term_to_mathml(numer)),
+            mrow(&term_to_mathml(denom))
+        );
+    }
+
+    if let Some((mantissa, exponent)) = term.split_once("×10^") {
+        return mrow(&format!(
+            "{}{}<msup>{}{}</msup>",
+            mn(mantissa),
+            mo("×"),
+            mn("10"),
+            mn(exponent)
+        ));
+    }
+
+    if let Some(exponent) = term.strip_prefix("10^") {
+        return format!("<msup>{}{}</msup>", mn("10"), mn(exponent));
+    }
+
+    if let Some(exponent) = term.strip_prefix("e^") {
+        return format!("<msup>{}{}</msup>", mi("e"), mn(exponent));
+    }
+
+    if let Some(radicand) = term.strip_prefix('√') {
+        return format!("<msqrt>{}</msqrt>", mn(radicand));
+    }
+
+    if let Some(base) = term.strip_suffix('²') {
+        return format!("<msup>{}{}</msup>", term_to_mathml(base), mn("2"));
+    }
+
+    if let Some(argument) = term
+        .strip_prefix("ln(")
+        .and_then(|term| term.strip_suffix(')'))
+    {
+        return mrow(&format!(
+            "{}{}{}",
+            mi("ln"),
+            mo("\u{2061}"),
+            fenced("(", &mn(argument), ")")
+        ));
+    }
+
+    // Eg. 2π
+    let digits: String = term
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    let rest = &term[digits.len()..];
+    match (digits.is_empty(), rest.is_empty()) {
+        (_, true) => mn(&digits),
+        (true, false) => mi(rest),
+        (false, false) => mrow(&format!("{}{}", mn(&digits), mi(rest))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kalk_value::KalkValue;
+    use crate::lexer::TokenKind;
+    use crate::parser;
+    use crate::test_helpers::*;
+
+    fn strip_math(input: String) -> String {
+        input
+            .trim_start_matches("<math xmlns=\"http://www.w3.org/1998/Math/MathML\">")
+            .trim_end_matches("</math>")
+            .to_string()
+    }
+
+    #[test]
+    fn test_value_to_mathml() {
+        let in_out = vec![
+            (1f64, "<mrow><mn>1</mn></mrow>"),
+            (
+                0.5f64,
+                "<mrow><mfrac><mrow><mn>1</mn></mrow><mrow><mn>2</mn></mrow></mfrac></mrow>",
+            ),
+            (
+                std::f64::consts::PI * 2f64 / 3f64,
+                "<mrow><mfrac><mrow><mrow><mn>2</mn><mi>π</mi></mrow></mrow><mrow><mn>3</mn></mrow></mfrac></mrow>",
+            ),
+            (5f64.sqrt(), "<mrow><msqrt><mn>5</mn></msqrt></mrow>"),
+            (
+                100f64 + 1f64 / 3f64,
+                "<mrow><mn>100</mn><mo>+</mo><mfrac><mrow><mn>1</mn></mrow><mrow><mn>3</mn></mrow></mfrac></mrow>",
+            ),
+        ];
+
+        for (input, output) in in_out {
+            assert_eq!(strip_math(KalkValue::from(input).to_mathml()), output);
+        }
+
+        let value = KalkValue::Number(crate::float!(0.5), crate::float!(3f64.sqrt() / 2f64), None);
+        assert!(strip_math(value.to_mathml()).contains("<msup><mi>e</mi><mrow><mfrac>"));
+    }
+
+    #[test]
+    fn test_expr_to_mathml() {
+        assert_eq!(
+            strip_math(binary(var("x"), TokenKind::LessThan, literal(2f64)).to_mathml()),
+            "<mrow><mi>x</mi><mo>&lt;</mo><mn>2</mn></mrow>"
+        );
+        assert_eq!(
+            strip_math(fn_call("sqrt", vec![*var("x")]).to_mathml()),
+            "<msqrt><mi>x</mi></msqrt>"
+        );
+    }
+
+    #[test]
+    fn test_stmt_to_mathml() {
+        let mut context = parser::Context::new();
+        let statements = parser::parse(&mut context, "f(x) = x^2").unwrap();
+        assert_eq!(
+            strip_math(statements[0].to_mathml()),
+            "<mrow><mi>f</mi><mrow><mo>(</mo><mi>x</mi><mo>)</mo></mrow><mo>=</mo><msup><mrow><mi>x</mi></mrow><mrow><mn>2</mn></mrow></msup></mrow>"
+        );
+    }
+}
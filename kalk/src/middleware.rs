@@ -0,0 +1,129 @@
+//! Hooks for applications that embed kalker, to intercept statements before they are
+//! evaluated and results after they have been calculated, eg. to log, authorise or
+//! rewrite calculations, without changing the interpreter.
+use crate::ast::Stmt;
+use crate::calculation_result::CalculationResult;
+
+/// Middleware is added to a context with `parser::Context::add_middleware`. Statements are
+/// passed through each middleware in the order they were added, and results in the opposite
+/// order. An error stops the calculation with `KalkError::Rejected` and the message, and
+/// definitions of the input that was rejected are removed again.
+pub trait Middleware: Send + Sync {
+    /// Called with each statement before it is evaluated, eg. both `x = 2` and `x^2`
+    /// for the input `x = 2; x^2`. The statement that is returned is evaluated instead.
+    fn before_eval(&self, stmt: Stmt) -> Result<Stmt, String> {
+        Ok(stmt)
+    }
+
+    /// Called with the result of the input and the statement that gave it.
+    /// Returning None hides the result, like ending the input with a semicolon does.
+    fn after_eval(
+        &self,
+        _stmt: &Stmt,
+        result: CalculationResult,
+    ) -> Result<Option<CalculationResult>, String> {
+        Ok(Some(result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Middleware;
+    use crate::ast::{Expr, ExprSpan, Stmt};
+    use crate::calculation_result::CalculationResult;
+    use crate::errors::KalkError;
+    use crate::parser;
+    use std::sync::{Arc, Mutex};
+
+    fn eval(context: &mut parser::Context, input: &str) -> Result<Option<f64>, KalkError> {
+        parser::eval(
+            context,
+            input,
+            #[cfg(feature = "rug")]
+            63,
+        )
+        .map(|result| result.map(|result| result.to_f64()))
+    }
+
+    /// Rejects function declarations, and doubles literals.
+    struct Doubler;
+
+    impl Middleware for Doubler {
+        fn before_eval(&self, stmt: Stmt) -> Result<Stmt, String> {
+            match stmt {
+                Stmt::FnDecl(_, _, _) => Err(String::from("no functions")),
+                Stmt::Expr(expr) => match *expr {
+                    Expr::Literal(value, _) => Ok(Stmt::Expr(Box::new(Expr::Literal(
+                        value * 2f64,
+                        ExprSpan::default(),
+                    )))),
+                    expr => Ok(Stmt::Expr(Box::new(expr))),
+                },
+                stmt => Ok(stmt),
+            }
+        }
+    }
+
+    /// Logs the results, and hides the ones that are negative.
+    struct Logger {
+        log: Mutex<Vec<String>>,
+    }
+
+    impl Middleware for Logger {
+        fn after_eval(
+            &self,
+            stmt: &Stmt,
+            mut result: CalculationResult,
+        ) -> Result<Option<CalculationResult>, String> {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("{} = {}", stmt, result));
+            if result.to_f64() < 0f64 {
+                return Ok(None);
+            }
+
+            result.add_annotation("logged");
+
+            Ok(Some(result))
+        }
+    }
+
+    #[test]
+    fn test_before_eval() {
+        let mut context = parser::Context::new().add_middleware(Arc::new(Doubler));
+        assert_eq!(eval(&mut context, "3"), Ok(Some(6f64)));
+        assert_eq!(eval(&mut context, "x = 5; x + 1"), Ok(Some(6f64)));
+        assert_eq!(
+            eval(&mut context, "f(x) = 2x"),
+            Err(KalkError::Rejected(String::from("no functions")))
+        );
+        assert!(eval(&mut context, "f(2)").is_err());
+
+        // A rejected input doesn't declare its constants
+        assert!(eval(&mut context, "const c = 1; g(x) = x").is_err());
+        assert_eq!(eval(&mut context, "c = 2; c"), Ok(Some(2f64)));
+    }
+
+    #[test]
+    fn test_after_eval() {
+        let logger = Arc::new(Logger {
+            log: Mutex::new(Vec::new()),
+        });
+        let mut context = parser::Context::new()
+            .add_middleware(logger.clone())
+            .add_middleware(Arc::new(Doubler));
+        let result = parser::eval(
+            &mut context,
+            "2",
+            #[cfg(feature = "rug")]
+            63,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(result.to_f64(), 4f64);
+        assert_eq!(result.get_annotations(), [String::from("logged")]);
+        assert_eq!(eval(&mut context, "-1"), Ok(None));
+        assert_eq!(*logger.log.lock().unwrap(), ["4 = 4", "-1 = -1"]);
+    }
+}
@@ -0,0 +1,20 @@
+//! Functions written in Rust by applications that embed kalker, eg. for finance or chemistry,
+//! which are added to a context with `parser::Context::register_function`.
+use crate::kalk_value::KalkValue;
+use std::sync::Arc;
+
+/// The arguments are evaluated before the function is called. An error message
+/// stops the calculation with `KalkError::EvaluationError`.
+pub(crate) type NativeFn = dyn Fn(&[KalkValue]) -> Result<KalkValue, String> + Send + Sync;
+
+#[derive(Clone)]
+pub(crate) struct NativeFunction {
+    pub(crate) arity: usize,
+    pub(crate) function: Arc<NativeFn>,
+}
+
+impl std::fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "NativeFunction({} arguments)", self.arity)
+    }
+}
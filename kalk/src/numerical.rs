@@ -1,6 +1,7 @@
 use crate::as_number_or_zero;
 use crate::ast;
 use crate::ast::Expr;
+use crate::ast::ExprSpan;
 use crate::ast::Identifier;
 use crate::ast::Stmt;
 use crate::errors::KalkError;
@@ -9,6 +10,9 @@ use crate::interpreter;
 use crate::kalk_value::KalkValue;
 use crate::lexer::TokenKind;
 
+/// Integers bigger than this can not be represented exactly by an f64.
+const MAX_EXACT_INTEGER: f64 = 9007199254740992f64; // 2^53
+
 pub fn derive_func(
     context: &mut interpreter::Context,
     name: &Identifier,
@@ -49,7 +53,7 @@ pub fn integrate_with_unknown_variable(
     let mut integration_variable: Option<&str> = None;
 
     // integral(a, b, expr dx)
-    if let Expr::Binary(_, TokenKind::Star, right) = expr {
+    if let Expr::Binary(_, TokenKind::Star, right, _) = expr {
         if let Expr::Var(right_name) = &**right {
             if right_name.full_name.starts_with('d') {
                 // Take the value, but remove the d, so that only eg. x is left from dx
@@ -65,7 +69,7 @@ pub fn integrate_with_unknown_variable(
     // "dx" is still in the expression. Set dx = 1, so that it doesn't affect the expression value.
     context.symbol_table.set(Stmt::VarDecl(
         Identifier::from_full_name(&format!("d{}", integration_variable.unwrap())),
-        Box::new(Expr::Literal(1f64)),
+        Box::new(Expr::Literal(1f64, ExprSpan::default())),
     ));
 
     Ok(integrate(context, a, b, expr, integration_variable.unwrap())?.round_if_needed())
@@ -144,6 +148,19 @@ pub fn find_root(
     context: &mut interpreter::Context,
     expr: &Expr,
     var_name: &str,
+) -> Result<KalkValue, KalkError> {
+    let result = find_root_from(context, expr, var_name, KalkValue::from(1f64));
+    context.symbol_table.get_and_remove_var(var_name);
+
+    result
+}
+
+/// Find a root of the expression with Newton's method, starting at `initial`.
+pub fn find_root_from(
+    context: &mut interpreter::Context,
+    expr: &Expr,
+    var_name: &str,
+    initial: KalkValue,
 ) -> Result<KalkValue, KalkError> {
     const FN_NAME: &str = "tmp.";
     let f = Stmt::FnDecl(
@@ -152,7 +169,7 @@ pub fn find_root(
         Box::new(expr.clone()),
     );
     context.symbol_table.set(f);
-    let mut approx = KalkValue::from(1f64);
+    let mut approx = initial;
     for _ in 0..100 {
         let (new_approx, done) =
             newton_method(context, approx, &Identifier::from_full_name(FN_NAME))?;
@@ -171,13 +188,490 @@ pub fn find_root(
     )?
     .values();
 
-    context.symbol_table.get_and_remove_var(var_name);
-
     if test_real.is_nan() || test_real.abs() > 0.0001f64 || test_imaginary.abs() > 0.0001f64 {
         return Err(KalkError::UnableToSolveEquation);
     }
 
-    Ok(approx)
+    Ok(approx.round_if_needed())
+}
+
+/// Find a root of the expression in the interval [a, b] with the bisection method.
+/// The expression needs to have different signs at a and b.
+pub fn bisect(
+    context: &mut interpreter::Context,
+    expr: &Expr,
+    var_name: &str,
+    a_expr: &Expr,
+    b_expr: &Expr,
+) -> Result<KalkValue, KalkError> {
+    let f = set_real_fn(context, expr, var_name);
+    let mut a = interpreter::eval_expr(context, a_expr, None)?.to_f64();
+    let mut b = interpreter::eval_expr(context, b_expr, None)?.to_f64();
+    let mut f_a = f(context, a)?;
+    let f_b = f(context, b)?;
+    if f_a == 0f64 {
+        return Ok(KalkValue::from(a));
+    } else if f_b == 0f64 {
+        return Ok(KalkValue::from(b));
+    } else if f_a.is_nan() || f_b.is_nan() || f_a.signum() == f_b.signum() {
+        return Err(KalkError::EvaluationError(String::from(
+            "bisect needs the expression to have different signs at the ends of the interval.",
+        )));
+    }
+
+    for _ in 0..200 {
+        let middle = (a + b) / 2f64;
+        let f_middle = f(context, middle)?;
+        if f_middle == 0f64 || (b - a).abs() < f64::EPSILON * middle.abs().max(1f64) {
+            return Ok(KalkValue::from(middle).round_if_needed());
+        }
+
+        if f_middle.signum() == f_a.signum() {
+            a = middle;
+            f_a = f_middle;
+        } else {
+            b = middle;
+        }
+    }
+
+    Ok(KalkValue::from((a + b) / 2f64).round_if_needed())
+}
+
+/// Find the minimum (or maximum) of the expression in the interval [a, b] with golden-section search.
+/// Returns a vector containing the position and the value.
+pub fn find_extremum(
+    context: &mut interpreter::Context,
+    expr: &Expr,
+    var_name: &str,
+    a_expr: &Expr,
+    b_expr: &Expr,
+    maximize: bool,
+) -> Result<KalkValue, KalkError> {
+    let f = set_real_fn(context, expr, var_name);
+    let sign = if maximize { -1f64 } else { 1f64 };
+    let inverse_phi = (5f64.sqrt() - 1f64) / 2f64;
+
+    let mut a = interpreter::eval_expr(context, a_expr, None)?.to_f64();
+    let mut b = interpreter::eval_expr(context, b_expr, None)?.to_f64();
+    let mut c = b - (b - a) * inverse_phi;
+    let mut d = a + (b - a) * inverse_phi;
+    let mut f_c = sign * f(context, c)?;
+    let mut f_d = sign * f(context, d)?;
+    for _ in 0..200 {
+        if (b - a).abs() < 1e-10 * (c.abs() + d.abs()).max(1f64) {
+            break;
+        }
+
+        if f_c < f_d {
+            b = d;
+            d = c;
+            f_d = f_c;
+            c = b - (b - a) * inverse_phi;
+            f_c = sign * f(context, c)?;
+        } else {
+            a = c;
+            c = d;
+            f_c = f_d;
+            d = a + (b - a) * inverse_phi;
+            f_d = sign * f(context, d)?;
+        }
+    }
+
+    let x = (a + b) / 2f64;
+    let value = f(context, x)?;
+
+    Ok(KalkValue::Vector(vec![
+        KalkValue::from(x).round_if_needed(),
+        KalkValue::from(value).round_if_needed(),
+    ]))
+}
+
+/// The Taylor polynomial of the expression around x0 with the given degree, as an expression
+/// of the variable, eg. `x - x^3 / 6` for sin(x) around 0 with the degree 3.
+/// The derivatives are found symbolically and evaluated at x0.
+pub fn taylor_polynomial(
+    context: &mut interpreter::Context,
+    expr: &Expr,
+    var_name: &str,
+    x0: KalkValue,
+    degree: usize,
+) -> Result<Expr, KalkError> {
+    if x0.has_imaginary() {
+        return Err(KalkError::Expected(String::from(
+            "a real number to expand the Taylor polynomial around",
+        )));
+    }
+
+    let original_variable_value = context.symbol_table.get_and_remove_var(var_name);
+    context.symbol_table.set(Stmt::VarDecl(
+        Identifier::from_full_name(var_name),
+        Box::new(ast::build_literal_ast(&x0)),
+    ));
+    let derivatives = derivatives_at(context, expr, var_name, degree);
+    if let Some(value) = original_variable_value {
+        context.symbol_table.insert(value);
+    } else {
+        context.symbol_table.get_and_remove_var(var_name);
+    }
+
+    let x0 = x0.to_f64();
+    let variable = Expr::Var(Identifier::from_full_name(var_name));
+    let base = if x0 == 0f64 {
+        variable
+    } else {
+        let (op, offset) = if x0 < 0f64 {
+            (TokenKind::Plus, -x0)
+        } else {
+            (TokenKind::Minus, x0)
+        };
+        Expr::Group(Box::new(Expr::Binary(
+            Box::new(variable),
+            op,
+            Box::new(Expr::Literal(offset, ExprSpan::default())),
+            ExprSpan::default(),
+        )))
+    };
+
+    let mut polynomial: Option<Expr> = None;
+    let mut factorial = 1f64;
+    for (k, derivative) in derivatives?.into_iter().enumerate() {
+        if k > 0 {
+            factorial *= k as f64;
+        }
+
+        let (real, imaginary) = (derivative.to_f64(), derivative.imaginary_to_f64());
+        if real == 0f64 && imaginary == 0f64 {
+            continue;
+        }
+
+        let is_negative = imaginary == 0f64 && real < 0f64;
+        let power = match k {
+            0 => None,
+            1 => Some(base.clone()),
+            _ => Some(Expr::Binary(
+                Box::new(base.clone()),
+                TokenKind::Power,
+                Box::new(Expr::Literal(k as f64, ExprSpan::default())),
+                ExprSpan::default(),
+            )),
+        };
+
+        // An integer is divided by the factorial as a reduced fraction,
+        // eg. x^3 / 6 rather than 0.1666666667x^3
+        let is_exact = imaginary == 0f64 && real.fract() == 0f64 && real.abs() < MAX_EXACT_INTEGER;
+        let term = if is_exact && factorial < MAX_EXACT_INTEGER {
+            let divisor = gcd(real.abs() as u64, factorial as u64) as f64;
+            let (numerator, denominator) = (real.abs() / divisor, factorial / divisor);
+            let numerator = match power {
+                Some(power) if numerator == 1f64 => power,
+                Some(power) => Expr::Binary(
+                    Box::new(Expr::Literal(numerator, ExprSpan::default())),
+                    TokenKind::Star,
+                    Box::new(power),
+                    ExprSpan::default(),
+                ),
+                None => Expr::Literal(numerator, ExprSpan::default()),
+            };
+
+            if denominator == 1f64 {
+                numerator
+            } else {
+                Expr::Binary(
+                    Box::new(numerator),
+                    TokenKind::Slash,
+                    Box::new(Expr::Literal(denominator, ExprSpan::default())),
+                    ExprSpan::default(),
+                )
+            }
+        } else {
+            let magnitude = if is_negative {
+                KalkValue::from(-real)
+            } else {
+                derivative
+            };
+            let coefficient =
+                ast::build_literal_ast(&magnitude.div_without_unit(&KalkValue::from(factorial))?);
+            let coefficient = if imaginary == 0f64 {
+                coefficient
+            } else {
+                Expr::Group(Box::new(coefficient))
+            };
+
+            match power {
+                Some(power) => Expr::Binary(
+                    Box::new(coefficient),
+                    TokenKind::Star,
+                    Box::new(power),
+                    ExprSpan::default(),
+                ),
+                None => coefficient,
+            }
+        };
+
+        polynomial = Some(match polynomial {
+            Some(left) => Expr::Binary(
+                Box::new(left),
+                if is_negative {
+                    TokenKind::Minus
+                } else {
+                    TokenKind::Plus
+                },
+                Box::new(term),
+                ExprSpan::default(),
+            ),
+            None if is_negative => {
+                Expr::Unary(TokenKind::Minus, Box::new(term), ExprSpan::default())
+            }
+            None => term,
+        });
+    }
+
+    Ok(polynomial.unwrap_or(Expr::Literal(0f64, ExprSpan::default())))
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// The values of the expression and its derivatives up to the given order,
+/// with the current value of the variable.
+fn derivatives_at(
+    context: &mut interpreter::Context,
+    expr: &Expr,
+    var_name: &str,
+    order: usize,
+) -> Result<Vec<KalkValue>, KalkError> {
+    let mut derivative = expr.clone();
+    let mut values = Vec::with_capacity(order + 1);
+    for k in 0..=order {
+        if k > 0 {
+            derivative = derivative.derive(context.symbol_table, var_name)?;
+        }
+
+        values.push(interpreter::eval_expr(context, &derivative, None)?.round_if_needed());
+    }
+
+    Ok(values)
+}
+
+/// Estimate the limit of the expression as the variable approaches the target, from above if
+/// `from_above` is true, from below if it is false, and from both sides if it is None.
+/// The function is sampled at step sizes that halve each time, and the values are extrapolated
+/// towards a step size of zero. Values that keep growing give ±∞, and the result is NaN when
+/// the values don't approach a single value, or the sides approach different values.
+pub fn limit(
+    context: &mut interpreter::Context,
+    expr: &Expr,
+    var_name: &str,
+    target_expr: &Expr,
+    from_above: Option<bool>,
+) -> Result<KalkValue, KalkError> {
+    let target_value = interpreter::eval_expr(context, target_expr, None)?;
+    let target = target_value.to_f64();
+    if target.is_nan() || target_value.imaginary_to_f64() != 0f64 {
+        return Err(KalkError::EvaluationError(String::from(
+            "lim needs the variable to approach a real number, inf or -inf.",
+        )));
+    }
+
+    context.symbol_table.set(Stmt::FnDecl(
+        Identifier::from_full_name("tmp."),
+        vec![var_name.into()],
+        Box::new(expr.clone()),
+    ));
+
+    let (real, imaginary) = if target.is_infinite() {
+        // Infinity can only be approached from one side
+        one_sided_limit(context, target, -target.signum())?
+    } else if let Some(from_above) = from_above {
+        one_sided_limit(context, target, if from_above { 1f64 } else { -1f64 })?
+    } else {
+        let below = one_sided_limit(context, target, -1f64)?;
+        let above = one_sided_limit(context, target, 1f64)?;
+        if approximately_equal(below.0, above.0) && approximately_equal(below.1, above.1) {
+            above
+        } else {
+            interpreter::set_undefined_reason(context, || {
+                format!(
+                    "the limit from below is {}, while the limit from above is {}",
+                    KalkValue::Number(float!(below.0), float!(below.1), None).round_if_needed(),
+                    KalkValue::Number(float!(above.0), float!(above.1), None).round_if_needed(),
+                )
+            });
+
+            (f64::NAN, 0f64)
+        }
+    };
+
+    Ok(KalkValue::Number(float!(real), float!(imaginary), None).round_if_needed())
+}
+
+/// The limit of the temporary function as its argument approaches the target in the direction
+/// of the sign of `direction`, as a real and an imaginary part.
+fn one_sided_limit(
+    context: &mut interpreter::Context,
+    target: f64,
+    direction: f64,
+) -> Result<(f64, f64), KalkError> {
+    const SAMPLES: i32 = 40;
+    let step = if target.is_infinite() {
+        1f64
+    } else {
+        0.125f64 * target.abs().max(1f64)
+    };
+
+    let mut real_values = Vec::new();
+    let mut imaginary_values = Vec::new();
+    for k in 0..SAMPLES {
+        let h = step / 2f64.powi(k);
+        let x = if target.is_infinite() {
+            -direction / h
+        } else {
+            target + direction * h
+        };
+        let value = interpreter::eval_fn_call_expr(
+            context,
+            &Identifier::from_full_name("tmp."),
+            &[Expr::Literal(x, ExprSpan::default())],
+            None,
+        )?;
+        let (real, imaginary) = (value.to_f64(), value.imaginary_to_f64());
+
+        // Far away from the target, the value may overflow even though the expression approaches
+        // a value, eg. `e^x / e^x`, so only the values before that are used.
+        if real.is_infinite() && real_values.len() < 10 {
+            return Ok((real, 0f64));
+        } else if !real.is_finite() || !imaginary.is_finite() {
+            break;
+        }
+
+        real_values.push(real);
+        imaginary_values.push(imaginary);
+    }
+
+    if real_values.len() < 10 {
+        interpreter::set_undefined_reason(context, || {
+            String::from("the expression is undefined close to the point it approaches")
+        });
+
+        return Ok((f64::NAN, 0f64));
+    }
+
+    let real = extrapolate(&real_values);
+    let imaginary = extrapolate(&imaginary_values);
+    if real.is_nan() || imaginary.is_nan() {
+        interpreter::set_undefined_reason(context, || {
+            String::from("the expression doesn't approach a single value")
+        });
+
+        return Ok((f64::NAN, 0f64));
+    }
+
+    Ok((real, imaginary))
+}
+
+/// Estimate the value that a sequence of values approaches, where each value is at half the
+/// step size of the previous one, with Richardson extrapolation. The estimate with the smallest
+/// error is kept, and like in Ridders' method for derivatives, it stops once the error grows,
+/// since the values that are very close to the target tend to be dominated by rounding errors,
+/// eg. `cos(x) - 1` is 0 for small enough x.
+fn extrapolate(values: &[f64]) -> f64 {
+    const MAX_ORDER: usize = 6;
+    let mut best = values[values.len() - 1];
+    let mut best_error = f64::INFINITY;
+    let mut previous_row = vec![values[0]];
+    for (k, value) in values.iter().enumerate().skip(1) {
+        let mut row = vec![*value];
+        for j in 1..=k.min(MAX_ORDER) {
+            let factor = 2f64.powi(j as i32) - 1f64;
+            row.push(row[j - 1] + (row[j - 1] - previous_row[j - 1]) / factor);
+        }
+
+        let mut row_error = f64::INFINITY;
+        for j in 0..row.len() {
+            let error = if j == 0 {
+                (row[0] - previous_row[0]).abs()
+            } else {
+                (row[j] - row[j - 1])
+                    .abs()
+                    .max((row[j] - previous_row[j - 1]).abs())
+            };
+            row_error = row_error.min(error);
+
+            // The values far away from the target can be equal by chance, eg. 2^(1/2) = 4^(1/4)
+            if k >= 3 && error <= best_error {
+                best = row[j];
+                best_error = error;
+            }
+        }
+
+        if best_error <= 1e-9 * best.abs().max(1f64) && row_error > 10f64 * best_error {
+            break;
+        }
+
+        previous_row = row;
+    }
+
+    if is_converged(best, best_error) {
+        // The estimate can't be told apart from zero, eg. when the values approach it slowly
+        return if best.abs() <= 10f64 * best_error {
+            0f64
+        } else {
+            best
+        };
+    }
+
+    // If the last differences have the same sign and don't get smaller,
+    // the values keep growing in the same direction.
+    let differences: Vec<f64> = values.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let last_differences = &differences[differences.len().saturating_sub(8)..];
+    let is_growing = last_differences.windows(2).all(|pair| {
+        pair[0] != 0f64
+            && pair[0].signum() == pair[1].signum()
+            && pair[1].abs() >= 0.9 * pair[0].abs()
+    });
+    if is_growing {
+        return last_differences[0].signum() * f64::INFINITY;
+    }
+
+    f64::NAN
+}
+
+fn is_converged(estimate: f64, error: f64) -> bool {
+    error <= 1e-6 * estimate.abs().max(1f64)
+}
+
+fn approximately_equal(a: f64, b: f64) -> bool {
+    a == b || (a.is_finite() && b.is_finite() && is_converged(a.abs().max(b.abs()), (a - b).abs()))
+}
+
+/// Declare a temporary function with the expression as its body,
+/// and return a closure that calls it with a real number.
+fn set_real_fn(
+    context: &mut interpreter::Context,
+    expr: &Expr,
+    var_name: &str,
+) -> impl Fn(&mut interpreter::Context, f64) -> Result<f64, KalkError> {
+    const FN_NAME: &str = "tmp.";
+    context.symbol_table.set(Stmt::FnDecl(
+        Identifier::from_full_name(FN_NAME),
+        vec![var_name.into()],
+        Box::new(expr.clone()),
+    ));
+
+    |context: &mut interpreter::Context, x: f64| {
+        Ok(interpreter::eval_fn_call_expr(
+            context,
+            &Identifier::from_full_name(FN_NAME),
+            &[Expr::Literal(x, ExprSpan::default())],
+            None,
+        )?
+        .to_f64())
+    }
 }
 
 fn newton_method(
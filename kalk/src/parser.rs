@@ -1,37 +1,271 @@
-use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 
 use crate::analysis;
-use crate::ast::Identifier;
+use crate::ast::{for_each_identifier_mut, Identifier};
 use crate::calculation_result::CalculationResult;
 use crate::errors::KalkError;
+use crate::interpreter::LoopCheckpoint;
+use crate::kalk_value::{BigInt, KalkValue, NumberFormat, RoundingOptions};
+use crate::random::Rng;
 use crate::{
-    ast::{Expr, Stmt},
-    interpreter,
-    lexer::{Lexer, Token, TokenKind},
+    ast::{Expr, ExprSpan, Stmt},
+    float, interpreter, interval,
+    lexer::{self, Lexer, Token, TokenKind},
+    middleware::Middleware,
+    native::NativeFunction,
+    numerical, primitive,
     symbol_table::SymbolTable,
 };
 use wasm_bindgen::prelude::*;
 
 pub const DECL_UNIT: &str = ".u";
 pub const DEFAULT_ANGLE_UNIT: &str = "rad";
+pub const DEFAULT_MAX_RECURSION_DEPTH: u32 = 100;
+/// The precision that is tried first when automatic precision is enabled.
+pub const AUTO_PRECISION_START: u32 = 64;
+/// The amount of results that are kept in the evaluation cache before it is cleared.
+const MAX_CACHED_RESULTS: usize = 100;
+/// Functions that can give a different result each time they are called.
+const IMPURE_FUNCS: [&str; 4] = ["now", "rand", "randint", "randnormal"];
+
+/// What happens when the result of an operation is outside of the range of numbers
+/// that can be represented, eg. `10^400` without the rug feature.
+/// The same policy is used with and without the rug feature,
+/// although the range of numbers is much larger with rug.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Give infinity (or zero, for underflow) and mark the result as out of range.
+    Infinity,
+    /// Stop the calculation with `KalkError::Overflow` or `KalkError::Underflow`.
+    Error,
+}
+
+/// What an operation gives when its operands form an indeterminate form, ie.
+/// `0^0`, `∞^0`, `1^∞`, `0·∞` or `∞ - ∞`, where the value depends on how the
+/// operands were approached. The policy is applied to the operators, so that the
+/// result doesn't depend on whether the rug feature is enabled.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndeterminatePolicy {
+    /// Stop the calculation with `KalkError::IndeterminateForm`.
+    Error,
+    /// Give NaN, which is shown as undefined.
+    NaN,
+    /// Give the conventional value, like IEEE 754 does: `0^0`, `∞^0` and `1^∞` are 1,
+    /// while `0·∞` and `∞ - ∞` have no conventional value and give NaN.
+    Conventional,
+}
+
+/// What happens when an operation on real numbers is given values outside of its domain,
+/// eg. `1/0`, `log(0)` or `sqrt(-1)`. Operations on complex numbers are not affected.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DomainPolicy {
+    /// Stop the calculation with `KalkError::DivisionByZero` or `KalkError::OutsideDomain`.
+    Error,
+    /// Stay in the real numbers: poles give infinity, and values that aren't real give NaN,
+    /// eg. `sqrt(-1)`.
+    Real,
+    /// Give the complex value where there is one, eg. `i` for `sqrt(-1)`, and infinity at poles.
+    Complex,
+}
+
+/// What happens when a declaration has the name of a built-in function or constant,
+/// eg. `pi = 3` or `sin(x) = x`. Constants declared with `const` can never be replaced.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadowingPolicy {
+    /// Stop with `KalkError::UnableToOverrideConstant` or `KalkError::UnableToOverrideFunction`.
+    Deny,
+    /// Replace the built-in one, and give a `ShadowingWarning`.
+    Warn,
+    /// Replace the built-in one without a warning.
+    Allow,
+}
+
+/// A built-in function or constant that was replaced by a declaration,
+/// with `ShadowingPolicy::Warn`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShadowingWarning {
+    pub name: String,
+    pub is_function: bool,
+}
+
+impl std::fmt::Display for ShadowingWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = if self.is_function {
+            "function"
+        } else {
+            "constant"
+        };
+
+        write!(
+            f,
+            "'{}' replaces the built-in {} with the same name.",
+            self.name, kind
+        )
+    }
+}
+
+/// Integer arithmetic with a fixed word size, for working with bit patterns.
+/// Every value is truncated to an integer and wrapped around to the word size,
+/// eg. 255 + 1 = 0 with 8 unsigned bits, and the result is shown in
+/// two's complement in other number bases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgrammerMode {
+    word_size: u32,
+    signed: bool,
+}
+
+impl ProgrammerMode {
+    /// The word size is the amount of bits, which may be 8, 16, 32 or 64.
+    pub fn new(word_size: u32, signed: bool) -> Option<Self> {
+        if matches!(word_size, 8 | 16 | 32 | 64) {
+            Some(ProgrammerMode { word_size, signed })
+        } else {
+            None
+        }
+    }
+
+    pub fn get_word_size(&self) -> u32 {
+        self.word_size
+    }
+
+    pub fn is_signed(&self) -> bool {
+        self.signed
+    }
+
+    /// Truncate the value to an integer and wrap it around to the word size.
+    pub(crate) fn wrap(&self, value: f64) -> f64 {
+        if !value.is_finite() {
+            return value;
+        }
+
+        self.wrap_bits(value.trunc() as i128 as u64).to_f64()
+    }
+
+    /// Same as `wrap`, but values with more than 53 bits are kept as exact integers,
+    /// since a float can't hold every 64-bit value.
+    pub(crate) fn wrap_value(&self, value: KalkValue) -> KalkValue {
+        match value {
+            KalkValue::Integer(integer) => self.wrap_bits(integer.wrapping_to_u64()),
+            KalkValue::Number(real, imaginary, None)
+                if imaginary == 0f64 && primitive!(real).is_finite() =>
+            {
+                self.wrap_bits(primitive!(real).trunc() as i128 as u64)
+            }
+            KalkValue::Number(real, imaginary, unit) => KalkValue::Number(
+                float!(self.wrap(primitive!(real))),
+                float!(self.wrap(primitive!(imaginary))),
+                unit,
+            ),
+            _ => value,
+        }
+    }
+
+    /// Wrap the lowest bits of an integer in two's complement around to the word size.
+    pub(crate) fn wrap_bits(&self, bits: u64) -> KalkValue {
+        let bits = bits & self.mask();
+        if self.signed && bits >> (self.word_size - 1) == 1 {
+            KalkValue::from(-BigInt::from_u64(bits.wrapping_neg() & self.mask()))
+        } else {
+            KalkValue::from(BigInt::from_u64(bits))
+        }
+    }
+
+    /// The bit pattern of the value, as an unsigned integer.
+    pub(crate) fn bit_pattern(&self, value: &KalkValue) -> u64 {
+        let bits = match value {
+            KalkValue::Integer(integer) => integer.wrapping_to_u64(),
+            _ => value.to_f64().trunc() as i128 as u64,
+        };
+
+        bits & self.mask()
+    }
+
+    fn mask(&self) -> u64 {
+        u64::MAX >> (64 - self.word_size)
+    }
+}
 
 /// Struct containing the current state of the parser. It stores user-defined functions and variables.
+/// The context is `Send` and `Sync`, and can be cloned to evaluate expressions on several threads
+/// with the same definitions, without parsing them again. Clones share the interrupt flag.
 #[wasm_bindgen]
+#[derive(Clone)]
 pub struct Context {
     tokens: Vec<Token>,
     pos: usize,
-    symbol_table: Cell<SymbolTable>,
+    symbol_table: SymbolTable,
     angle_unit: String,
     timeout: Option<u32>,
+    max_recursion_depth: u32,
+    evaluation_budget: Option<u64>,
+    overflow_policy: OverflowPolicy,
+    indeterminate_policy: IndeterminatePolicy,
+    domain_policy: DomainPolicy,
+    strict_units: bool,
+    interval_mode: bool,
+    number_format: NumberFormat,
+    programmer_mode: Option<ProgrammerMode>,
+    /// Values that are shown with a symbol in estimates, eg. `≈ G`.
+    estimate_constants: Vec<(f64, String)>,
+    /// The biggest denominator of the fractions in estimates, eg. `0.3333333333 ≈ 1/3`.
+    max_denominator: u64,
+    /// Whether results are shown with estimates at all, eg. `0.9999991 ≈ 1`.
+    estimate: bool,
+    /// Whether estimates are shown after the decimals in parentheses, eg. `≈ 0.3333333333… (1/3)`.
+    side_by_side_estimate: bool,
+    /// How close a result needs to be to an integer to be estimated as it.
+    round_tolerance: Option<f64>,
+    trace: bool,
     /// This is true whenever the parser is currently parsing a unit declaration.
     /// It is necessary to keep track of this in order to know when to find (figure out) units that haven't been defined yet.
     /// Unit names are instead treated as variables.
     parsing_unit_decl: bool,
+    /// This is true while the target of a limit is being parsed, where a trailing `+` or `-`
+    /// is the side that the target is approached from, eg. `x -> 0+`.
+    parsing_limit_target: bool,
+    /// Contextual keywords that end the expression that is being parsed, eg. `in` in the value
+    /// of a let expression. Anywhere else, they are names.
+    expected_keywords: Vec<TokenKind>,
     /// When a unit declaration is being parsed, this value will be set
     /// whenever a unit in the expression is found. Eg. unit a = 3b, it will be set to Some("b")
     unit_decl_base_unit: Option<String>,
     other_radix: Option<u8>,
     current_stmt_start_pos: usize,
+    /// This is true when the last statement of the input was followed by a semicolon,
+    /// which means that its result should not be shown.
+    output_suppressed: bool,
+    auto_precision: bool,
+    interrupt_flag: Option<Arc<AtomicBool>>,
+    /// The last calculation that was interrupted in the middle of a loop.
+    checkpoint: Option<Checkpoint>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    /// Results that have been stored with `store_register`, which are separate from variables.
+    registers: HashMap<String, Expr>,
+    /// The random number generator, which continues where it was in the previous input.
+    rng: Option<Rng>,
+    /// The part of the input that the error of the last evaluation came from.
+    error_span: Option<lexer::Span>,
+    /// The parts of the input that were replaced with the values of aliases.
+    alias_spans: Vec<lexer::Span>,
+    /// Results of earlier inputs by their canonical hash, so that eg. `1 + 2` isn't evaluated
+    /// again after `1+2`. It is cleared whenever a setting or definition changes.
+    eval_cache: HashMap<u64, CalculationResult>,
+}
+
+/// An interrupted calculation. The statements are evaluated again when resuming,
+/// but the interrupted loop continues from where it was stopped.
+#[derive(Clone)]
+struct Checkpoint {
+    statements: Vec<Stmt>,
+    loop_checkpoint: LoopCheckpoint,
 }
 
 #[wasm_bindgen]
@@ -41,13 +275,40 @@ impl Context {
         let mut context = Self {
             tokens: Vec::new(),
             pos: 0,
-            symbol_table: Cell::from(SymbolTable::new()),
+            symbol_table: SymbolTable::new(),
             angle_unit: DEFAULT_ANGLE_UNIT.into(),
             timeout: None,
+            max_recursion_depth: DEFAULT_MAX_RECURSION_DEPTH,
+            evaluation_budget: None,
+            overflow_policy: OverflowPolicy::Infinity,
+            indeterminate_policy: IndeterminatePolicy::Conventional,
+            domain_policy: DomainPolicy::Complex,
+            programmer_mode: None,
+            strict_units: false,
+            interval_mode: false,
+            number_format: NumberFormat::default(),
+            estimate_constants: Vec::new(),
+            max_denominator: RoundingOptions::default().max_denominator,
+            estimate: true,
+            side_by_side_estimate: false,
+            round_tolerance: None,
+            trace: false,
             parsing_unit_decl: false,
+            parsing_limit_target: false,
+            expected_keywords: Vec::new(),
             unit_decl_base_unit: None,
             other_radix: None,
             current_stmt_start_pos: 0,
+            output_suppressed: false,
+            auto_precision: false,
+            interrupt_flag: None,
+            checkpoint: None,
+            middleware: Vec::new(),
+            registers: HashMap::new(),
+            rng: None,
+            error_span: None,
+            alias_spans: Vec::new(),
+            eval_cache: HashMap::new(),
         };
 
         parse(&mut context, crate::prelude::INIT).unwrap();
@@ -56,6 +317,7 @@ impl Context {
     }
 
     pub fn set_angle_unit(mut self, unit: &str) -> Self {
+        self.eval_cache.clear();
         self.angle_unit = unit.into();
 
         self
@@ -65,943 +327,4535 @@ impl Context {
     /// The calculation will stop after this amount of time has passed.
     #[cfg(not(target_arch = "wasm32"))]
     pub fn set_timeout(mut self, timeout: Option<u32>) -> Self {
+        self.eval_cache.clear();
         self.timeout = timeout;
 
         self
     }
 
-    #[wasm_bindgen(js_name = evaluate)]
-    #[cfg(not(feature = "rug"))]
-    pub fn js_eval(&mut self, input: &str) -> Result<Option<CalculationResult>, JsValue> {
-        let result = eval(self, input);
+    /// Set the maximum amount of nested function calls, eg. when a function calls itself.
+    /// Going deeper than this results in an error instead of a stack overflow.
+    pub fn set_max_recursion_depth(mut self, max_recursion_depth: u32) -> Self {
+        self.eval_cache.clear();
+        self.max_recursion_depth = max_recursion_depth;
 
-        match result {
-            Ok(Some(value)) => Ok(Some(value)),
-            Ok(None) => Ok(None),
-            Err(err) => Err(err.to_string().into()),
-        }
+        self
     }
-}
 
-impl Default for Context {
-    fn default() -> Self {
-        Self::new()
+    /// Evaluate at a low precision first, and only use a higher precision
+    /// (at most the one given to `eval`) if that changes the pretty result.
+    /// This only has an effect when the rug feature is enabled.
+    pub fn set_auto_precision(mut self, auto_precision: bool) -> Self {
+        self.eval_cache.clear();
+        self.auto_precision = auto_precision;
+
+        self
     }
-}
 
-/// Evaluate expressions/declarations and return the answer.
-///
-/// `None` will be returned if the last statement is a declaration.
-pub fn eval(
-    context: &mut Context,
-    input: &str,
-    #[cfg(feature = "rug")] precision: u32,
-) -> Result<Option<CalculationResult>, KalkError> {
-    let statements = parse(context, input)?;
+    pub fn is_auto_precision(&self) -> bool {
+        self.auto_precision
+    }
 
-    let symbol_table = context.symbol_table.get_mut();
-    let mut interpreter = interpreter::Context::new(
-        symbol_table,
-        &context.angle_unit,
-        #[cfg(feature = "rug")]
-        precision,
-        context.timeout.map(|timeout| timeout as u128),
-    );
-    let result = interpreter.interpret(statements);
-    if let Ok(Some(mut num)) = result {
-        num.set_radix(context.other_radix.unwrap_or(10));
-        Ok(Some(num))
-    } else {
-        result
+    /// Set the maximum amount of expression nodes that may be evaluated for one input.
+    /// Unlike the timeout, this also works when compiled to WebAssembly.
+    #[wasm_bindgen(js_name = setEvaluationBudget)]
+    pub fn set_evaluation_budget(mut self, evaluation_budget: Option<u64>) -> Self {
+        self.eval_cache.clear();
+        self.evaluation_budget = evaluation_budget;
+
+        self
     }
-}
 
-/// Parse expressions/declarations and return a syntax tree.
-///
-/// `None` will be returned if the last statement is a declaration.
-pub fn parse(context: &mut Context, input: &str) -> Result<Vec<Stmt>, KalkError> {
-    let mut lexer = Lexer::new(input);
-    context.tokens = lexer.lex();
-    context.pos = 0;
-    context.parsing_unit_decl = false;
-    context.unit_decl_base_unit = None;
-    context.other_radix = lexer.get_other_radix();
+    /// Set what happens when a result is too large or too small to be represented.
+    /// By default, infinity or zero is given and the result is marked as out of range.
+    #[wasm_bindgen(js_name = setOverflowPolicy)]
+    pub fn set_overflow_policy(mut self, overflow_policy: OverflowPolicy) -> Self {
+        self.eval_cache.clear();
+        self.overflow_policy = overflow_policy;
 
-    let mut statements: Vec<Stmt> = Vec::new();
-    while !is_at_end(context) {
-        context.current_stmt_start_pos = context.pos;
-        let parsed = match parse_stmt(context) {
-            Ok(stmt) => stmt,
-            Err(KalkError::WasStmt(stmt)) => stmt,
-            Err(err) => return Err(err),
-        };
-        let symbol_table = context.symbol_table.get_mut();
-        let analysed = analysis::analyse_stmt(symbol_table, parsed)?;
-        statements.push(analysed);
+        self
+    }
 
-        if match_token(context, TokenKind::Semicolon) {
-            advance(context);
-        }
+    /// Set what `0^0`, `∞^0`, `1^∞`, `0·∞` and `∞ - ∞` give.
+    /// By default, the conventional value is given, eg. 1 for `0^0`.
+    #[wasm_bindgen(js_name = setIndeterminatePolicy)]
+    pub fn set_indeterminate_policy(mut self, indeterminate_policy: IndeterminatePolicy) -> Self {
+        self.eval_cache.clear();
+        self.indeterminate_policy = indeterminate_policy;
 
-        skip_newlines(context);
+        self
     }
 
-    Ok(statements)
-}
+    /// Set what `1/0`, `log(0)`, `sqrt(-1)` etc. give. By default, complex values are given
+    /// where there are any, and infinity at poles. Results that are undefined or infinite since
+    /// a value was outside of the domain have a note about it, with `get_domain_note`.
+    #[wasm_bindgen(js_name = setDomainPolicy)]
+    pub fn set_domain_policy(mut self, domain_policy: DomainPolicy) -> Self {
+        self.eval_cache.clear();
+        self.domain_policy = domain_policy;
 
-fn parse_stmt(context: &mut Context) -> Result<Stmt, KalkError> {
-    if match_token(context, TokenKind::UnitKeyword) {
-        parse_unit_decl_stmt(context)
-    } else {
-        Ok(Stmt::Expr(Box::new(parse_expr(context)?)))
+        self
     }
-}
 
-fn parse_piecewise(context: &mut Context) -> Result<Expr, KalkError> {
-    advance(context);
-    skip_newlines(context);
+    /// Set what happens when a declaration has the name of a built-in function or constant.
+    /// By default, it gives an error.
+    #[wasm_bindgen(js_name = setShadowingPolicy)]
+    pub fn set_shadowing_policy(mut self, shadowing_policy: ShadowingPolicy) -> Self {
+        self.eval_cache.clear();
+        self.symbol_table.set_shadowing_policy(shadowing_policy);
 
-    let mut pieces = Vec::new();
-    let mut reached_otherwise = false;
-    // Parse `expr if condition`
-    // do-while loop
-    while {
-        let left_expr = parse_expr(context)?;
-        if match_token(context, TokenKind::IfKeyword) {
-            advance(context);
-            pieces.push(crate::ast::ConditionalPiece {
-                expr: left_expr,
-                condition: parse_expr(context)?,
-            });
-        } else if match_token(context, TokenKind::OtherwiseKeyword) {
-            advance(context);
-            // Yeah, a bit hacky, but there's no `true` keyword...
-            let true_expr = Expr::Binary(
-                Box::new(Expr::Literal(1f64)),
-                TokenKind::Equals,
-                Box::new(Expr::Literal(1f64)),
-            );
-            pieces.push(crate::ast::ConditionalPiece {
-                expr: left_expr,
-                condition: true_expr,
-            });
+        self
+    }
 
-            reached_otherwise = true;
-        } else {
-            return Err(KalkError::ExpectedIf);
-        }
+    /// Show the value as the symbol in the estimates of results, eg. `0.9159655942 ≈ G`,
+    /// in addition to common constants like π. Integer multiples are also recognised.
+    #[wasm_bindgen(js_name = registerEstimateConstant)]
+    pub fn register_estimate_constant(&mut self, value: f64, symbol: &str) {
+        self.eval_cache.clear();
+        self.estimate_constants
+            .retain(|(_, existing_symbol)| existing_symbol != symbol);
+        self.estimate_constants.push((value, symbol.to_string()));
+    }
 
-        if match_token(context, TokenKind::Semicolon) {
-            advance(context);
-        }
-        skip_newlines(context);
+    /// Set the biggest denominator of the fractions in the estimates of results.
+    /// By default, it is 100, so eg. 1/317 is only shown as a decimal.
+    #[wasm_bindgen(js_name = setMaxDenominator)]
+    pub fn set_max_denominator(mut self, max_denominator: u64) -> Self {
+        self.eval_cache.clear();
+        self.max_denominator = max_denominator;
 
-        (previous(context).kind == TokenKind::Semicolon
-            || previous(context).kind == TokenKind::Newline)
-            && !reached_otherwise
-            && !match_token(context, TokenKind::ClosedBrace)
-    } {}
+        self
+    }
 
-    advance(context);
+    /// Show results without estimates, eg. only `0.9999991` instead of `0.9999991 ≈ 1`,
+    /// when false. This is true by default.
+    #[wasm_bindgen(js_name = setEstimate)]
+    pub fn set_estimate(mut self, estimate: bool) -> Self {
+        self.eval_cache.clear();
+        self.estimate = estimate;
 
-    Ok(Expr::Piecewise(pieces))
-}
+        self
+    }
 
-fn parse_unit_decl_stmt(context: &mut Context) -> Result<Stmt, KalkError> {
-    advance(context); // Unit keyword
-    let identifier = advance(context).clone();
-    consume(context, TokenKind::Equals)?;
+    /// Show the estimates of results after the decimals in parentheses,
+    /// eg. `≈ 0.3333333333… (1/3)` instead of `0.3333333333 ≈ 1/3`, when true.
+    #[wasm_bindgen(js_name = setSideBySideEstimate)]
+    pub fn set_side_by_side_estimate(mut self, side_by_side_estimate: bool) -> Self {
+        self.eval_cache.clear();
+        self.side_by_side_estimate = side_by_side_estimate;
 
-    // Parse the mut definition
-    context.unit_decl_base_unit = None;
-    context.parsing_unit_decl = true;
-    let def = parse_expr(context)?;
-    context.parsing_unit_decl = false;
+        self
+    }
 
-    let base_unit = if let Some(base_unit) = &context.unit_decl_base_unit {
-        base_unit.clone()
-    } else {
-        return Err(KalkError::InvalidUnit);
-    };
+    /// Set how close a result needs to be to an integer to be estimated as it, eg. `Some(1e-9)`,
+    /// where zero turns the rounding off. By default, values close to zero are rounded
+    /// less aggressively than others.
+    #[wasm_bindgen(js_name = setRoundTolerance)]
+    pub fn set_round_tolerance(mut self, round_tolerance: Option<f64>) -> Self {
+        self.eval_cache.clear();
+        self.round_tolerance = round_tolerance;
 
-    // Automatically create a second unit decl with the expression inverted.
-    // This will turn eg. unit a = 3b, into unit b = a/3
-    // This is so that you only have to define `a`, and it will figure out the formula for `b` since it is used in the formula for `a`.
-    let stmt_inv = Stmt::UnitDecl(
-        base_unit.clone(),
-        identifier.value.clone(),
-        Box::new(def.invert(context.symbol_table.get_mut(), DECL_UNIT)?),
-    );
-    let stmt = Stmt::UnitDecl(identifier.value, base_unit, Box::new(def));
+        self
+    }
 
-    context.symbol_table.get_mut().insert(stmt.clone());
-    context.symbol_table.get_mut().insert(stmt_inv);
+    /// Give an error instead of a warning when an operation loses a unit,
+    /// eg. `2 + 3 km`. The warning is available with `get_dropped_unit` on the result.
+    #[wasm_bindgen(js_name = setStrictUnits)]
+    pub fn set_strict_units(mut self, strict_units: bool) -> Self {
+        self.eval_cache.clear();
+        self.strict_units = strict_units;
 
-    Ok(stmt)
-}
+        self
+    }
 
-fn parse_expr(context: &mut Context) -> Result<Expr, KalkError> {
-    parse_or(context)
-}
+    /// Also calculate bounds for the exact value of real results with interval arithmetic,
+    /// and only show the decimals that are certain, eg. `0.1 + 0.2` => 0.3, while
+    /// `(10^16 + 1) - 10^16` shows the bounds, since it suffers from rounding errors.
+    /// The bounds are calculated separately with f64 arithmetic, see the `interval` module.
+    #[wasm_bindgen(js_name = setIntervalMode)]
+    pub fn set_interval_mode(mut self, interval_mode: bool) -> Self {
+        self.eval_cache.clear();
+        self.interval_mode = interval_mode;
 
-fn parse_comprehension(context: &mut Context) -> Result<Expr, KalkError> {
-    let left = parse_or(context)?;
+        self
+    }
 
-    if match_token(context, TokenKind::Colon) {
-        let op = advance(context).kind;
-        skip_newlines(context);
-        let right = Box::new(parse_comprehension_comma(context)?);
-        return Ok(Expr::Binary(Box::new(left), op, right));
+    /// Use integer arithmetic with the word size (8, 16, 32 or 64 bits), see `ProgrammerMode`.
+    /// A word size of 0 turns it off again.
+    #[wasm_bindgen(js_name = setProgrammerMode)]
+    pub fn js_set_programmer_mode(self, word_size: u32, signed: bool) -> Self {
+        self.set_programmer_mode(ProgrammerMode::new(word_size, signed))
     }
 
-    Ok(left)
-}
+    /// Make `rand`, `randint` and `randnormal` give the same numbers every time,
+    /// eg. to make a script reproducible. Without a seed, they are seeded with entropy.
+    #[wasm_bindgen(js_name = setSeed)]
+    pub fn set_seed(mut self, seed: u64) -> Self {
+        self.eval_cache.clear();
+        self.rng = Some(Rng::from_seed(seed));
 
-fn parse_comprehension_comma(context: &mut Context) -> Result<Expr, KalkError> {
-    let left = parse_or(context)?;
+        self
+    }
 
-    if match_token(context, TokenKind::Comma) {
-        let op = advance(context).kind;
-        skip_newlines(context);
-        let right = Box::new(parse_comprehension_comma(context)?);
-        return Ok(Expr::Binary(Box::new(left), op, right));
+    /// Record the steps of each calculation, eg. `2 * 3 = 6`,
+    /// which can be read from the result with `get_steps`.
+    #[wasm_bindgen(js_name = setTrace)]
+    pub fn set_trace(mut self, trace: bool) -> Self {
+        self.eval_cache.clear();
+        self.trace = trace;
+
+        self
     }
 
-    Ok(left)
-}
+    pub fn is_trace(&self) -> bool {
+        self.trace
+    }
 
-fn parse_or(context: &mut Context) -> Result<Expr, KalkError> {
-    let left = parse_and(context)?;
+    /// Define physical constants, such as `c`, `h` and `k_B`, as constants.
+    /// Variables with the same names are replaced.
+    #[wasm_bindgen(js_name = loadPhysicsConstants)]
+    pub fn load_physics_constants(&mut self) {
+        self.eval_cache.clear();
+        let symbol_table = &mut self.symbol_table;
+        for (name, value) in crate::constants::PHYSICS_CONSTANTS {
+            if symbol_table.is_constant(name) {
+                continue;
+            }
 
-    if match_token(context, TokenKind::Or) {
-        let op = advance(context).kind;
-        skip_newlines(context);
-        let right = Box::new(parse_or(context)?);
-        return Ok(Expr::Binary(Box::new(left), op, right));
+            symbol_table.insert(Stmt::VarDecl(
+                Identifier::from_full_name(name),
+                Box::new(crate::ast::build_literal_ast(&KalkValue::from(*value))),
+            ));
+            symbol_table.mark_as_constant(name);
+        }
     }
 
-    Ok(left)
-}
+    #[wasm_bindgen(js_name = evaluate)]
+    #[cfg(not(feature = "rug"))]
+    pub fn js_eval(&mut self, input: &str) -> Result<Option<CalculationResult>, JsValue> {
+        let result = eval(self, input);
 
-fn parse_and(context: &mut Context) -> Result<Expr, KalkError> {
-    let left = parse_comparison(context)?;
+        match result {
+            Ok(Some(value)) => Ok(Some(value)),
+            Ok(None) => Ok(None),
+            Err(err) => Err(err.to_string().into()),
+        }
+    }
 
-    if match_token(context, TokenKind::And) {
-        let op = advance(context).kind;
-        skip_newlines(context);
-        let right = Box::new(parse_and(context)?);
-        return Ok(Expr::Binary(Box::new(left), op, right));
+    /// Parse the input and convert the statements into MathML, one `<math>` element per statement.
+    #[wasm_bindgen(js_name = toMathML)]
+    pub fn js_to_mathml(&mut self, input: &str) -> Result<String, JsValue> {
+        match parse(self, input) {
+            Ok(statements) => Ok(statements.iter().map(|stmt| stmt.to_mathml()).collect()),
+            Err(err) => Err(err.to_string().into()),
+        }
     }
 
-    Ok(left)
+    /// Classify the pieces of the input for syntax highlighting, as a flat list of
+    /// `start, end, category` triples. The spans are counted in characters.
+    #[wasm_bindgen(js_name = highlight)]
+    pub fn js_highlight(&self, input: &str) -> Vec<u32> {
+        self.highlight(input)
+            .into_iter()
+            .flat_map(|((start, end), category)| [start as u32, end as u32, category as u32])
+            .collect()
+    }
 }
 
-fn parse_comparison(context: &mut Context) -> Result<Expr, KalkError> {
-    let at_start_of_line = context.current_stmt_start_pos == context.pos;
-    let mut left = parse_to(context)?;
+impl Context {
+    /// Set the exchange rates used to convert between currencies, eg. `100 usd to eur`.
+    /// Each currency code becomes a unit, and the value is how much of that currency
+    /// one unit of a common base currency is worth. The rates replace any previous ones.
+    pub fn set_exchange_rates(&mut self, exchange_rates: HashMap<String, f64>) {
+        self.eval_cache.clear();
+        self.symbol_table.set_exchange_rates(exchange_rates);
+    }
 
-    // Equality check
-    while match_token(context, TokenKind::Equals)
-        || match_token(context, TokenKind::NotEquals)
-        || match_token(context, TokenKind::GreaterThan)
-        || match_token(context, TokenKind::LessThan)
-        || match_token(context, TokenKind::GreaterOrEquals)
-        || match_token(context, TokenKind::LessOrEquals)
-    {
-        let op = advance(context).kind;
+    /// Stop the calculation with `KalkError::Interrupted` when the flag is set, eg. by a Ctrl-C handler.
+    /// The flag is reset when this happens. If a `sum` or `prod` loop was running,
+    /// the calculation can be continued with `resume`.
+    pub fn set_interrupt_flag(mut self, interrupt_flag: Arc<AtomicBool>) -> Self {
+        self.interrupt_flag = Some(interrupt_flag);
 
-        if let (true, Some((identifier, parameters))) =
-            (at_start_of_line, analysis::is_fn_decl(&left))
-        {
-            context.symbol_table.get_mut().set(Stmt::FnDecl(
-                identifier.clone(),
-                parameters.clone(),
-                Box::new(Expr::Literal(1f64)),
-            ));
-            let right = if match_token(context, TokenKind::OpenBrace) {
-                parse_piecewise(context)?
-            } else {
-                parse_expr(context)?
-            };
-            let fn_decl = Stmt::FnDecl(identifier, parameters, Box::new(right));
+        self
+    }
 
-            // Hack to return a statement...
-            return Err(KalkError::WasStmt(fn_decl));
+    /// Intercept the statements before they are evaluated and the results after,
+    /// eg. to log or authorise calculations. See `Middleware`.
+    pub fn add_middleware(mut self, middleware: Arc<dyn Middleware>) -> Self {
+        self.eval_cache.clear();
+        self.middleware.push(middleware);
+
+        self
+    }
+
+    /// Add a function that is written in Rust, eg. for finance, which is called with
+    /// the values of `arity` arguments. It behaves like a built-in function, so it can't
+    /// be redefined unless the shadowing policy allows it. A function with the same name
+    /// that was registered before is replaced.
+    pub fn register_function(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&[KalkValue]) -> Result<KalkValue, String> + Send + Sync + 'static,
+    ) {
+        self.eval_cache.clear();
+        self.symbol_table.register_native_fn(
+            name,
+            NativeFunction {
+                arity,
+                function: Arc::new(function),
+            },
+        );
+    }
+
+    /// Parse the input once, so that it can be evaluated many times with different values for
+    /// its variables with `CompiledInput::eval_with`, eg. for each point of a plot. Like in
+    /// `eval`, a name with several letters that hasn't been defined is seen as a product of
+    /// variables, so such variables need to be defined before the input is compiled.
+    pub fn compile(
+        &mut self,
+        input: &str,
+        #[cfg(feature = "rug")] precision: u32,
+    ) -> Result<CompiledInput<'_>, KalkError> {
+        self.error_span = None;
+        let statements = match parse_with_positions(self, input, false) {
+            Ok(statements) => statements.into_iter().map(|(_, stmt)| stmt).collect(),
+            Err(mut errors) => {
+                let syntax_error = errors.remove(0);
+                self.error_span = Some(syntax_error.span);
+
+                return Err(syntax_error.error);
+            }
         };
 
-        let right = parse_comparison(context)?;
+        Ok(CompiledInput {
+            context: self,
+            statements,
+            #[cfg(feature = "rug")]
+            precision,
+        })
+    }
 
-        left = match right {
-            Expr::Binary(
-                inner_left,
-                inner_op @ (TokenKind::Equals
-                | TokenKind::NotEquals
-                | TokenKind::GreaterThan
-                | TokenKind::LessThan
-                | TokenKind::GreaterOrEquals
-                | TokenKind::LessOrEquals),
-                inner_right,
-            ) => Expr::Binary(
-                Box::new(Expr::Binary(
-                    Box::new(left),
-                    op,
-                    Box::new(*inner_left.clone()),
-                )),
-                TokenKind::And,
-                Box::new(Expr::Binary(
-                    Box::new(*inner_left),
-                    inner_op,
-                    Box::new(*inner_right),
-                )),
-            ),
-            _ => Expr::Binary(Box::new(left), op, Box::new(right)),
+    /// Get a hash of the input that doesn't depend on whitespace or redundant parentheses,
+    /// eg. the same one for `1+2` and `(1 + 2)`. The input is parsed with the definitions
+    /// of the context, without changing them. None is returned if it can't be parsed.
+    pub fn canonical_hash(&self, input: &str) -> Option<u64> {
+        match parse(&mut self.clone(), input) {
+            Ok(statements) if !statements.is_empty() => {
+                Some(crate::ast::canonical_hash(&statements))
+            }
+            _ => None,
         }
     }
 
-    Ok(left)
-}
-
-fn parse_to(context: &mut Context) -> Result<Expr, KalkError> {
-    let left = parse_term(context)?;
+    /// Store the last result in a named register, like the memory of a calculator.
+    /// Registers aren't variables, so they can only be used again with `recall_register`.
+    pub fn store_register(&mut self, name: &str) -> Result<(), KalkError> {
+        match self.symbol_table.get_var("ans") {
+            Some(Stmt::VarDecl(_, expr)) => {
+                self.registers.insert(name.to_string(), *expr.clone());
 
-    if match_token(context, TokenKind::ToKeyword) {
-        advance(context);
-        let right = Expr::Var(Identifier::from_full_name(&advance(context).value)); // Parse this as a variable for now.
+                Ok(())
+            }
+            _ => Err(KalkError::NothingToStore),
+        }
+    }
 
-        return Ok(Expr::Binary(
-            Box::new(left),
-            TokenKind::ToKeyword,
-            Box::new(right),
+    /// Make the value of a register the last result, so that it can be used as `ans`.
+    pub fn recall_register(&mut self, name: &str) -> Result<(), KalkError> {
+        let expr = self
+            .registers
+            .get(name)
+            .cloned()
+            .ok_or_else(|| KalkError::EmptyRegister(name.to_string()))?;
+        self.symbol_table.set(Stmt::VarDecl(
+            Identifier::from_full_name("ans"),
+            Box::new(expr),
         ));
+
+        Ok(())
     }
 
-    Ok(left)
-}
+    /// Get the names of the registers that have a value, in alphabetical order.
+    pub fn get_register_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.registers.keys().cloned().collect();
+        names.sort();
 
-fn parse_term(context: &mut Context) -> Result<Expr, KalkError> {
-    let mut left = parse_factor(context)?;
+        names
+    }
 
-    while match_token(context, TokenKind::Plus) || match_token(context, TokenKind::Minus) {
-        let op = peek(context).kind;
-        advance(context);
-        let right = parse_factor(context)?;
+    /// Check if there is an interrupted calculation that can be continued with `resume`.
+    pub fn has_checkpoint(&self) -> bool {
+        self.checkpoint.is_some()
+    }
+
+    /// Use integer arithmetic with a fixed word size, or turn it off with None.
+    pub fn set_programmer_mode(mut self, programmer_mode: Option<ProgrammerMode>) -> Self {
+        self.eval_cache.clear();
+        self.programmer_mode = programmer_mode;
 
-        left = Expr::Binary(Box::new(left), op, Box::new(right));
+        self
     }
 
-    Ok(left)
-}
+    pub fn get_programmer_mode(&self) -> Option<ProgrammerMode> {
+        self.programmer_mode
+    }
 
-fn parse_factor(context: &mut Context) -> Result<Expr, KalkError> {
-    let mut left = parse_unit(context)?;
+    /// Get the functions, variables, constants and units that start with the prefix,
+    /// eg. for tab completion. User-defined functions include their amount of parameters.
+    pub fn get_completions(&self, prefix: &str) -> Vec<crate::completion::CompletionItem> {
+        self.symbol_table.completions(prefix)
+    }
 
-    if let Expr::Unary(TokenKind::Percent, percent_left) = left.clone() {
-        let try_parse = parse_unit(context);
-        if try_parse.is_ok() {
-            left = Expr::Binary(percent_left, TokenKind::Percent, Box::new(try_parse?));
-        }
+    /// Get the variables, functions and units that have been defined, as kalker syntax,
+    /// eg. `f(x) = 2x`. With the serde feature, the context is serialized as these.
+    pub fn get_definitions(&self) -> Vec<String> {
+        self.symbol_table.definitions()
     }
 
-    while match_token(context, TokenKind::Star)
-        || match_token(context, TokenKind::Slash)
-        || match_token(context, TokenKind::Percent)
-        || match_token(context, TokenKind::Identifier)
-        || match_token(context, TokenKind::Literal)
-        || match_token(context, TokenKind::OpenParenthesis)
-        || match_token(context, TokenKind::OpenCeil)
-        || match_token(context, TokenKind::OpenFloor)
-        || match_token(context, TokenKind::OpenBracket)
-    {
-        // If the token is an identifier, literal, or open parenthesis,
-        // assume it's multiplication. Eg. 3y or (3x + 2)(2 + 3)
-        let op = match peek(context).kind {
-            TokenKind::Identifier
-            | TokenKind::Literal
-            | TokenKind::OpenParenthesis
-            | TokenKind::OpenCeil
-            | TokenKind::OpenFloor
-            | TokenKind::OpenBracket => TokenKind::Star,
-            _ => advance(context).kind,
+    /// Get the variables that have been defined, as kalker syntax, in alphabetical order.
+    pub fn get_variables(&self) -> Vec<String> {
+        let mut variables: Vec<(&str, String)> = self
+            .symbol_table
+            .iter_variables()
+            .filter_map(|stmt| match stmt {
+                Stmt::VarDecl(identifier, _) => Some((
+                    identifier.full_name.as_str(),
+                    self.definition_to_string(stmt),
+                )),
+                _ => None,
+            })
+            .collect();
+        variables.sort();
+
+        variables
+            .into_iter()
+            .map(|(_, variable)| variable)
+            .collect()
+    }
+
+    /// Get the functions that have been defined, as kalker syntax, in alphabetical order.
+    pub fn get_functions(&self) -> Vec<String> {
+        let mut functions: Vec<(&str, String)> = self
+            .symbol_table
+            .iter_functions()
+            .filter_map(|stmt| match stmt {
+                Stmt::FnDecl(identifier, _, _) => Some((
+                    identifier.full_name.as_str(),
+                    self.definition_to_string(stmt),
+                )),
+                _ => None,
+            })
+            .collect();
+        functions.sort();
+
+        functions
+            .into_iter()
+            .map(|(_, function)| function)
+            .collect()
+    }
+
+    /// Get the definition of the variable or function with the name, as kalker syntax,
+    /// or None if it hasn't been defined.
+    pub fn get_definition(&self, name: &str) -> Option<String> {
+        let stmt = match self.symbol_table.get_var(name) {
+            Some(stmt @ Stmt::VarDecl(_, _)) => stmt,
+            _ => self.symbol_table.get_fn(name)?,
         };
 
-        let right = parse_unit(context)?;
+        Some(self.definition_to_string(stmt))
+    }
+
+    /// A declaration as kalker syntax. Declarations are shown with the aliases
+    /// they were written with, eg. `f(x) = sq(x)` after `alias sq = sqrt`.
+    fn definition_to_string(&self, stmt: &Stmt) -> String {
+        let definition = match self.symbol_table.get_alias_source(stmt) {
+            Some(source) => source.to_string(),
+            None => stmt.to_string(),
+        };
 
-        left = Expr::Binary(Box::new(left), op, Box::new(right));
+        match stmt {
+            Stmt::VarDecl(identifier, _)
+                if self
+                    .symbol_table
+                    .is_declared_constant(&identifier.full_name) =>
+            {
+                format!("const {}", definition)
+            }
+            _ => definition,
+        }
     }
 
-    Ok(left)
-}
+    /// Get the characters of the input that the error of the last evaluation came from,
+    /// eg. the variable that isn't defined, the division by zero with `DomainPolicy::Error`,
+    /// or the statement with a syntax error.
+    /// This is None if the evaluation succeeded, or if it isn't known where the error came from.
+    pub fn get_error_span(&self) -> Option<lexer::Span> {
+        self.error_span
+    }
 
-fn parse_unit(context: &mut Context) -> Result<Expr, KalkError> {
-    let expr = parse_exponent(context)?;
+    /// Get the built-in functions and constants that have been replaced by declarations
+    /// since the last time, with `ShadowingPolicy::Warn`.
+    pub fn take_shadowing_warnings(&mut self) -> Vec<ShadowingWarning> {
+        self.symbol_table.take_shadowing_warnings()
+    }
 
-    if match_token(context, TokenKind::Identifier) {
-        let peek = &peek(context).value.clone();
-        if context.symbol_table.get_mut().contains_unit(peek) {
-            return Ok(Expr::Unit(
-                advance(context).value.to_string(),
-                Box::new(expr),
-            ));
-        }
+    /// Remove the variable or function with the name. Returns false if it wasn't defined.
+    pub fn remove_definition(&mut self, name: &str) -> bool {
+        self.eval_cache.clear();
+        self.symbol_table.remove(name)
     }
 
-    Ok(expr)
-}
+    /// Classify the pieces of the input for syntax highlighting, like `lexer::highlight`,
+    /// but with the units that have been defined in the context.
+    pub fn highlight(&self, input: &str) -> Vec<(lexer::Span, lexer::TokenCategory)> {
+        lexer::highlight_with(input, |identifier| {
+            self.symbol_table.contains_unit(identifier) || identifier == DEFAULT_ANGLE_UNIT
+        })
+    }
 
-fn parse_exponent(context: &mut Context) -> Result<Expr, KalkError> {
-    let left = parse_unary(context)?;
+    /// Set the decimal separator and digit grouping of results, eg. `1,234.56`.
+    /// Input still uses a dot as the decimal separator.
+    pub fn set_number_format(mut self, number_format: NumberFormat) -> Self {
+        self.eval_cache.clear();
+        self.number_format = number_format;
 
-    if match_token(context, TokenKind::Power) {
-        let op = advance(context).kind;
-        let right = Box::new(parse_exponent(context)?);
+        self
+    }
 
-        return Ok(Expr::Binary(Box::new(left), op, right));
+    pub fn get_number_format(&self) -> NumberFormat {
+        self.number_format
     }
 
-    Ok(left)
-}
+    /// Get the amount of parameters of a function that has been defined, eg. 2 for f(x, y).
+    pub(crate) fn function_parameter_count(&self, name: &str) -> Option<usize> {
+        match self.symbol_table.get_fn(name) {
+            Some(Stmt::FnDecl(_, parameters, _)) => Some(parameters.len()),
+            _ => None,
+        }
+    }
 
-fn parse_unary(context: &mut Context) -> Result<Expr, KalkError> {
-    if match_token(context, TokenKind::Minus) || match_token(context, TokenKind::Not) {
-        let op = advance(context).kind;
-        let expr = Box::new(parse_unary(context)?);
+    #[cfg(any(feature = "serde", test))]
+    pub(crate) fn get_symbol_table(&self) -> &SymbolTable {
+        &self.symbol_table
+    }
 
-        return Ok(Expr::Unary(op, expr));
+    #[cfg(feature = "serde")]
+    pub(crate) fn set_symbol_table(mut self, symbol_table: SymbolTable) -> Self {
+        self.eval_cache.clear();
+        self.symbol_table = symbol_table;
+
+        self
     }
+}
 
-    let expr = parse_indexer(context)?;
-    if match_token(context, TokenKind::Percent) {
-        Ok(Expr::Unary(advance(context).kind, Box::new(expr)))
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Evaluate expressions/declarations and return the answer.
+///
+/// `None` will be returned if the last statement is a declaration,
+/// or if it is followed by a semicolon, eg. `x = 3; 2x;`.
+pub fn eval(
+    context: &mut Context,
+    input: &str,
+    #[cfg(feature = "rug")] precision: u32,
+) -> Result<Option<CalculationResult>, KalkError> {
+    context.error_span = None;
+
+    // Parsing already defines the functions and units of the input,
+    // so they are removed again if middleware rejects the input.
+    let symbol_table = if context.middleware.is_empty() {
+        None
     } else {
-        Ok(expr)
+        Some(context.symbol_table.clone())
+    };
+    let result = eval_statements(
+        context,
+        input,
+        #[cfg(feature = "rug")]
+        precision,
+    );
+    if let (Err(KalkError::Rejected(_)), Some(symbol_table)) = (&result, symbol_table) {
+        context.symbol_table = symbol_table;
+    }
+
+    // An interrupted calculation may still evaluate the declarations when it's resumed
+    if context.checkpoint.is_none() {
+        context.symbol_table.take_pending_constants();
     }
+
+    result
 }
 
-fn parse_indexer(context: &mut Context) -> Result<Expr, KalkError> {
-    let left = parse_factorial(context)?;
+fn eval_statements(
+    context: &mut Context,
+    input: &str,
+    #[cfg(feature = "rug")] precision: u32,
+) -> Result<Option<CalculationResult>, KalkError> {
+    let statements: Vec<Stmt> = match parse_with_positions(context, input, false) {
+        Ok(statements) => statements.into_iter().map(|(_, stmt)| stmt).collect(),
+        Err(mut errors) => {
+            let syntax_error = errors.remove(0);
+            context.error_span = Some(syntax_error.span);
 
-    if match_token(context, TokenKind::OpenDoubleBracket) {
-        advance(context);
-        let mut indexes = vec![parse_expr(context)?];
-        while match_token(context, TokenKind::Comma) {
-            advance(context);
-            indexes.push(parse_expr(context)?);
+            return Err(syntax_error.error);
         }
+    };
 
-        consume(context, TokenKind::ClosedDoubleBracket)?;
+    let cache_key = eval_cache_key(
+        context,
+        &statements,
+        #[cfg(feature = "rug")]
+        precision,
+    );
+    match cache_key.and_then(|key| context.eval_cache.get(&key)) {
+        Some(result) => {
+            let result = result.clone();
+            interpreter::remember_result(
+                &mut context.symbol_table,
+                &result.clone().get_value(),
+                true,
+            );
 
-        return Ok(Expr::Indexer(Box::new(left), indexes));
+            return Ok(Some(result));
+        }
+        None if cache_key.is_none() => context.eval_cache.clear(),
+        None => (),
     }
 
-    Ok(left)
+    #[cfg(feature = "rug")]
+    let result = if context.auto_precision {
+        eval_with_auto_precision(context, statements, precision)
+    } else {
+        interpret(context, statements, None, precision)
+    };
+
+    #[cfg(not(feature = "rug"))]
+    let result = interpret(context, statements, None);
+
+    if let (Some(key), Ok(Some(result))) = (cache_key, &result) {
+        if context.eval_cache.len() >= MAX_CACHED_RESULTS {
+            context.eval_cache.clear();
+        }
+
+        context.eval_cache.insert(key, result.clone());
+    }
+
+    result.map_err(|err| with_split_name_suggestions(context, err))
 }
 
-fn parse_factorial(context: &mut Context) -> Result<Expr, KalkError> {
-    let expr = parse_primary(context)?;
+/// The key of the input in the evaluation cache, or None if evaluating it again could
+/// give a different result, or could change something, eg. a declaration or `rand()`.
+fn eval_cache_key(
+    context: &Context,
+    statements: &[Stmt],
+    #[cfg(feature = "rug")] precision: u32,
+) -> Option<u64> {
+    let expr = match statements {
+        [Stmt::Expr(expr)] => expr,
+        _ => return None,
+    };
+    if context.output_suppressed
+        || !context.middleware.is_empty()
+        || may_change(&context.symbol_table, expr, &mut HashSet::new())
+    {
+        return None;
+    }
 
-    Ok(if match_token(context, TokenKind::Exclamation) {
-        advance(context);
-        Expr::Unary(TokenKind::Exclamation, Box::new(expr))
-    } else {
-        expr
-    })
+    let mut hasher = DefaultHasher::new();
+    crate::ast::canonical_hash(statements).hash(&mut hasher);
+    #[cfg(feature = "rug")]
+    precision.hash(&mut hasher);
+
+    Some(hasher.finish())
 }
 
-fn parse_primary(context: &mut Context) -> Result<Expr, KalkError> {
-    let expr = match peek(context).kind {
-        TokenKind::OpenParenthesis | TokenKind::OpenBracket => parse_vector(context)?,
-        TokenKind::Pipe | TokenKind::OpenCeil | TokenKind::OpenFloor => parse_group_fn(context)?,
-        TokenKind::Identifier => parse_identifier(context)?,
-        TokenKind::Literal => Expr::Literal(string_to_num(&advance(context).value)?),
-        TokenKind::True => {
-            advance(context);
-            Expr::Boolean(true)
+/// Check if the expression could give a different result when it is evaluated again
+/// with the same definitions, eg. since it uses `ans` or random numbers,
+/// directly or through the variables and functions that it uses.
+fn may_change(symbol_table: &SymbolTable, expr: &Expr, checked: &mut HashSet<String>) -> bool {
+    let mut names = Vec::new();
+    for_each_identifier_mut(&mut expr.clone(), &mut |identifier| {
+        // Eg. `f'` is the derivative of `f`
+        names.push(identifier.full_name.clone());
+        names.push(identifier.pure_name.clone());
+    });
+
+    names.into_iter().any(|name| {
+        if !checked.insert(name.clone()) {
+            return false;
         }
-        TokenKind::False => {
-            advance(context);
-            Expr::Boolean(false)
+
+        // Names that start with an underscore may be results, eg. `_2`
+        if name == "ans"
+            || name.starts_with('_')
+            || IMPURE_FUNCS.contains(&name.as_str())
+            || symbol_table.get_native_fn(&name).is_some()
+        {
+            return true;
         }
-        _ => return Err(KalkError::UnexpectedToken(peek(context).kind, None)),
-    };
 
-    Ok(expr)
+        [symbol_table.get_var(&name), symbol_table.get_fn(&name)]
+            .iter()
+            .flatten()
+            .any(|stmt| match stmt {
+                Stmt::VarDecl(_, expr) | Stmt::FnDecl(_, _, expr) => {
+                    may_change(symbol_table, expr, checked)
+                }
+                _ => false,
+            })
+    })
 }
 
-fn parse_group_fn(context: &mut Context) -> Result<Expr, KalkError> {
-    let name = match &peek(context).kind {
-        TokenKind::Pipe => "abs",
-        TokenKind::OpenCeil => "ceil",
-        TokenKind::OpenFloor => "floor",
-        _ => unreachable!(),
-    };
+/// Names that haven't been defined, eg. `radus`, are split up into one variable per letter,
+/// so the error is about eg. `r`. In that case, suggest names that are similar to the whole name.
+fn with_split_name_suggestions(context: &Context, err: KalkError) -> KalkError {
+    match err {
+        KalkError::UndefinedVar(name, suggestions) if suggestions.is_empty() => {
+            let suggestions = context
+                .tokens
+                .iter()
+                .enumerate()
+                .filter(|(_, token)| {
+                    token.kind == TokenKind::Identifier
+                        && token.value.chars().count() > 1
+                        && token.value.contains(name.as_str())
+                })
+                .map(|(i, token)| {
+                    let is_fn_call = context.tokens.get(i + 1).map(|next| next.kind)
+                        == Some(TokenKind::OpenParenthesis);
+                    context.symbol_table.similar_names(&token.value, is_fn_call)
+                })
+                .find(|suggestions| !suggestions.is_empty())
+                .unwrap_or_default();
+
+            KalkError::UndefinedVar(name, suggestions)
+        }
+        err => err,
+    }
+}
 
-    match parse_vector(context)? {
-        Expr::Vector(arguments) => Ok(Expr::FnCall(Identifier::from_full_name(name), arguments)),
-        Expr::Group(argument) => Ok(Expr::FnCall(
-            Identifier::from_full_name(name),
-            vec![*argument],
-        )),
-        _ => unreachable!(),
+/// Continue the last calculation that was interrupted in the middle of a `sum` or `prod` loop.
+/// The loop continues from the iteration that was interrupted, with the result of the
+/// previous iterations, while the rest of the input is evaluated again.
+pub fn resume(
+    context: &mut Context,
+    #[cfg(feature = "rug")] precision: u32,
+) -> Result<Option<CalculationResult>, KalkError> {
+    let checkpoint = context
+        .checkpoint
+        .take()
+        .ok_or(KalkError::NothingToResume)?;
+    context.output_suppressed = false;
+
+    let result = interpret(
+        context,
+        checkpoint.statements,
+        Some(checkpoint.loop_checkpoint),
+        #[cfg(feature = "rug")]
+        precision,
+    );
+    if context.checkpoint.is_none() {
+        context.symbol_table.take_pending_constants();
     }
+
+    result
 }
 
-fn parse_vector(context: &mut Context) -> Result<Expr, KalkError> {
-    let kind = advance(context).kind;
+/// Evaluate the statements with a precision that is doubled, starting from
+/// `AUTO_PRECISION_START`, until doubling it doesn't change the pretty result
+/// or `max_precision` is reached.
+#[cfg(feature = "rug")]
+fn eval_with_auto_precision(
+    context: &mut Context,
+    statements: Vec<Stmt>,
+    max_precision: u32,
+) -> Result<Option<CalculationResult>, KalkError> {
+    fn to_pretty(
+        result: &Result<Option<CalculationResult>, KalkError>,
+    ) -> Result<Option<String>, KalkError> {
+        match result {
+            Ok(value) => Ok(value.as_ref().map(|value| value.to_string_pretty())),
+            Err(err) => Err(err.clone()),
+        }
+    }
 
-    if kind == TokenKind::OpenBracket {
-        skip_newlines(context);
+    // Every precision starts from the same state, so that eg. `x += 1`, `ans`
+    // and random numbers only take effect once, and give the same values each time.
+    let symbol_table = context.symbol_table.clone();
+    let rng = context.rng.get_or_insert_with(Rng::from_entropy).clone();
+
+    let mut precision = AUTO_PRECISION_START.min(max_precision);
+    let mut result = interpret(context, statements.clone(), None, precision);
+    while precision < max_precision {
+        precision = precision.saturating_mul(2).min(max_precision);
+        context.symbol_table = symbol_table.clone();
+        context.rng = Some(rng.clone());
+        let next_result = interpret(context, statements.clone(), None, precision);
+        if to_pretty(&next_result) == to_pretty(&result) {
+            return next_result;
+        }
+
+        result = next_result;
     }
 
-    let first_expr = if kind == TokenKind::OpenBracket {
-        parse_comprehension(context)?
-    } else {
-        parse_expr(context)?
-    };
-    let mut rows = vec![vec![first_expr]];
-    let mut column_count = None;
-    let mut items_in_row = 1;
-    while match_token(context, TokenKind::Comma)
-        || match_token(context, TokenKind::Semicolon)
-        || (match_token(context, TokenKind::Newline)
-            && peek_next(context).kind != TokenKind::ClosedBracket)
-    {
-        if kind == TokenKind::OpenBracket
-            && (match_token(context, TokenKind::Newline)
-                || match_token(context, TokenKind::Semicolon))
-        {
-            if let Some(columns) = column_count {
-                if columns != items_in_row {
-                    return Err(KalkError::InconsistentColumnWidths);
-                }
-            } else {
-                column_count = Some(items_in_row);
-            }
-
-            rows.push(Vec::new());
-            items_in_row = 0;
-        }
+    result
+}
 
-        advance(context);
-        rows.last_mut().unwrap().push(parse_expr(context)?);
-        items_in_row += 1;
+pub(crate) fn interpret(
+    context: &mut Context,
+    statements: Vec<Stmt>,
+    resume_from: Option<LoopCheckpoint>,
+    #[cfg(feature = "rug")] precision: u32,
+) -> Result<Option<CalculationResult>, KalkError> {
+    let statements = statements
+        .into_iter()
+        .map(|stmt| {
+            context
+                .middleware
+                .iter()
+                .try_fold(stmt, |stmt, middleware| {
+                    middleware.before_eval(stmt).map_err(KalkError::Rejected)
+                })
+        })
+        .collect::<Result<Vec<Stmt>, KalkError>>()?;
+    // Declarations may change the results of earlier inputs, eg. in compiled or loaded input
+    if statements.iter().any(|stmt| !matches!(stmt, Stmt::Expr(_))) {
+        context.eval_cache.clear();
     }
 
-    if peek(context).kind == TokenKind::Eof {
-        return Err(KalkError::Expected(String::from(
-            "Closing group symbol, eg. )",
-        )));
-    }
+    let last_stmt_for_middleware = if context.middleware.is_empty() {
+        None
+    } else {
+        statements.last().cloned()
+    };
 
-    if kind == TokenKind::OpenBracket {
-        skip_newlines(context);
+    // The statements are only needed again if the calculation can be interrupted
+    let statements_copy = context.interrupt_flag.as_ref().map(|_| statements.clone());
+    let last_stmt = if context.interval_mode {
+        statements.last().cloned()
+    } else {
+        None
+    };
+    let mut interpreter = new_interpreter(
+        context,
+        #[cfg(feature = "rug")]
+        precision,
+    )
+    .resume_from(resume_from);
+    let result = interpreter.interpret(statements);
+    let error_span = result
+        .as_ref()
+        .err()
+        .and_then(|err| interpreter.take_error_span(err));
+    let rng = interpreter.take_rng();
+    let loop_checkpoint = interpreter.take_checkpoint();
+    context.error_span = error_span;
+    context.rng = rng;
+    if let (Some(loop_checkpoint), Some(statements)) = (loop_checkpoint, statements_copy) {
+        context.checkpoint = Some(Checkpoint {
+            statements,
+            loop_checkpoint,
+        });
     }
 
-    advance(context);
-
-    if rows.len() == 1 {
-        let mut values = rows.pop().unwrap();
-        if values.len() == 1 {
-            Ok(Expr::Group(Box::new(values.pop().unwrap())))
-        } else {
-            Ok(Expr::Vector(values))
+    if context.output_suppressed {
+        result.map(|_| None)
+    } else if let Ok(Some(num)) = result {
+        // The bounds are only used if they agree with the result, in case they were
+        // calculated for an expression that doesn't mean the same thing to the interpreter.
+        let bounds = last_stmt
+            .filter(|_| num.imaginary_to_f64() == 0f64 && num.get_unit().is_none())
+            .and_then(|stmt| interval::eval_stmt(&context.symbol_table, &context.angle_unit, &stmt))
+            .filter(|interval| interval.contains(num.to_f64()))
+            .map(|interval| (interval.lower, interval.upper));
+
+        let mut result = Some(
+            num.set_rounding_options(RoundingOptions {
+                custom_constants: context.estimate_constants.clone(),
+                max_denominator: context.max_denominator,
+                estimate: context.estimate,
+                side_by_side: context.side_by_side_estimate,
+                round_tolerance: context.round_tolerance,
+                degrees: context.angle_unit == "deg",
+                number_format: context.number_format,
+                ..Default::default()
+            })
+            .set_bounds(bounds),
+        );
+        if let Some(stmt) = last_stmt_for_middleware {
+            for middleware in context.middleware.iter().rev() {
+                result = match result {
+                    Some(result) => middleware
+                        .after_eval(&stmt, result)
+                        .map_err(KalkError::Rejected)?,
+                    None => break,
+                };
+            }
         }
+
+        Ok(result)
     } else {
-        Ok(Expr::Matrix(rows))
+        result
     }
 }
 
-fn parse_identifier(context: &mut Context) -> Result<Expr, KalkError> {
-    let at_start_of_line = context.current_stmt_start_pos == context.pos;
-    let identifier = Identifier::from_full_name(&advance(context).value);
+/// An interpreter that evaluates with the symbol table and the settings of the context,
+/// eg. its policies, programmer mode and random number generator.
+fn new_interpreter(
+    context: &mut Context,
+    #[cfg(feature = "rug")] precision: u32,
+) -> interpreter::Context<'_> {
+    interpreter::Context::new(
+        &mut context.symbol_table,
+        &context.angle_unit,
+        #[cfg(feature = "rug")]
+        precision,
+        context.timeout.map(|timeout| timeout as u128),
+    )
+    .set_max_recursion_depth(context.max_recursion_depth)
+    .set_evaluation_budget(context.evaluation_budget)
+    .set_overflow_policy(context.overflow_policy)
+    .set_indeterminate_policy(context.indeterminate_policy)
+    .set_domain_policy(context.domain_policy)
+    .set_strict_units(context.strict_units)
+    .set_programmer_mode(context.programmer_mode)
+    .set_display_radix(context.other_radix)
+    .set_trace(context.trace)
+    .set_interrupt_flag(context.interrupt_flag.clone())
+    .set_rng(context.rng.take())
+}
 
-    let mut log_base = None;
-    if identifier.full_name.starts_with("log") {
-        if let Some(lowered) = identifier.get_lowered_part() {
-            if let Ok(lowered_float) = lowered.parse::<f64>() {
-                log_base = Some(Expr::Literal(lowered_float));
+/// Input that has been parsed with `Context::compile`, which is evaluated without being lexed
+/// and parsed again. The context can't be used for anything else while it exists.
+pub struct CompiledInput<'a> {
+    context: &'a mut Context,
+    statements: Vec<Stmt>,
+    #[cfg(feature = "rug")]
+    precision: u32,
+}
+
+impl CompiledInput<'_> {
+    /// Evaluate the input with variables set to the values, eg. `&[("x", 3.0)]`.
+    /// The variables get their previous values back afterwards.
+    pub fn eval_with(
+        &mut self,
+        values: &[(&str, f64)],
+    ) -> Result<Option<CalculationResult>, KalkError> {
+        let context = &mut *self.context;
+        let old_vars: Vec<Option<Stmt>> = values
+            .iter()
+            .map(|(name, _)| context.symbol_table.get_and_remove_var(name))
+            .collect();
+        for (name, value) in values {
+            context.symbol_table.set(Stmt::VarDecl(
+                Identifier::from_full_name(name),
+                Box::new(Expr::Literal(*value, ExprSpan::default())),
+            ));
+        }
+
+        #[cfg(feature = "rug")]
+        let result = if context.auto_precision {
+            eval_with_auto_precision(context, self.statements.clone(), self.precision)
+        } else {
+            interpret(context, self.statements.clone(), None, self.precision)
+        };
+        #[cfg(not(feature = "rug"))]
+        let result = interpret(context, self.statements.clone(), None);
+
+        // In reverse, in case a variable was given twice
+        for ((name, _), old_var) in values.iter().zip(old_vars).rev() {
+            context.symbol_table.get_and_remove_var(name);
+            if let Some(old_var) = old_var {
+                context.symbol_table.insert(old_var);
             }
         }
-    }
 
-    if context.parsing_unit_decl
-        && !context
-            .symbol_table
-            .get_mut()
-            .contains_var(&identifier.full_name)
-    {
-        context.unit_decl_base_unit = Some(identifier.full_name);
-        Ok(Expr::Var(Identifier::from_full_name(DECL_UNIT)))
-    } else if log_base.is_some()
-        || context
-            .symbol_table
-            .get_mut()
-            .contains_fn(&identifier.pure_name)
-    {
-        let identifier_pos = context.pos;
+        result.map_err(|err| with_split_name_suggestions(context, err))
+    }
+}
 
-        // Function call
-        let mut arguments = match parse_primary(context)? {
-            Expr::Vector(arguments) => arguments,
-            Expr::Group(argument) => vec![*argument],
-            argument => vec![argument],
-        };
+/// Evaluate a function, or an expression of x, for the values from `start` to `end`
+/// (inclusive), incrementing by `step`. Returns the input and output value of each row.
+///
+/// The rows are split between `threads` threads, which is useful for expensive functions.
+/// Each thread gets its own copy of the symbol table, and its own evaluation budget.
+pub fn table(
+    context: &mut Context,
+    function: &str,
+    start: f64,
+    end: f64,
+    step: f64,
+    threads: usize,
+    #[cfg(feature = "rug")] precision: u32,
+) -> Result<Vec<(KalkValue, KalkValue)>, KalkError> {
+    if step == 0f64 || !step.is_finite() || (end - start) * step < 0f64 {
+        return Err(KalkError::EvaluationError(String::from(
+            "the step of a table needs to go from the start value towards the end value.",
+        )));
+    }
 
-        // If it's a re-definition, revert and parse as a declaration
-        if at_start_of_line
-            && match_token(context, TokenKind::Equals)
-            && arguments.iter().all(|x| matches!(x, Expr::Var(_)))
-        {
-            for argument in &arguments {
-                let mut all_vars_exist = true;
-                if let Expr::Var(argument_identifier) = argument {
-                    if !context
-                        .symbol_table
-                        .get_mut()
-                        .contains_var(&argument_identifier.full_name)
-                    {
-                        all_vars_exist = false;
-                    }
-                }
+    let input = if context.symbol_table.contains_fn(function) {
+        format!("{}(x)", function)
+    } else {
+        function.to_string()
+    };
+    let expr = match parse(context, &input)?.pop() {
+        Some(Stmt::Expr(expr)) => expr,
+        _ => return Err(KalkError::Expected(String::from("an expression"))),
+    };
 
-                if !all_vars_exist {
-                    context
-                        .symbol_table
-                        .get_mut()
-                        .get_and_remove_fn(&identifier.full_name);
-                    context.pos = identifier_pos;
+    let row_count = ((end - start) / step + 1e-9).floor() as usize + 1;
+    let x_values: Vec<f64> = (0..row_count).map(|i| start + i as f64 * step).collect();
+    let eval_rows = |interpreter: &mut interpreter::Context, x_values: &[f64]| {
+        // Temporarily replace x with the value of each row
+        let old_x = interpreter.symbol_table.get_and_remove_var("x");
+        let mut rows = Vec::with_capacity(x_values.len());
+        let mut result = Ok(());
+        for x in x_values {
+            interpreter.symbol_table.set(Stmt::VarDecl(
+                Identifier::from_full_name("x"),
+                Box::new(Expr::Literal(*x, ExprSpan::default())),
+            ));
 
-                    return Ok(Expr::Var(identifier));
+            match interpreter::eval_expr(interpreter, &expr, None) {
+                Ok(value) => rows.push((KalkValue::from(*x), value)),
+                Err(err) => {
+                    result = Err(err);
+                    break;
                 }
             }
         }
 
-        if let Some(log_base) = log_base {
-            let log_arg = arguments.remove(0);
-            Ok(Expr::FnCall(
-                Identifier::from_full_name("log"),
-                vec![log_arg, log_base],
-            ))
-        } else {
-            Ok(Expr::FnCall(identifier, arguments))
+        interpreter.symbol_table.get_and_remove_var("x");
+        if let Some(old_x) = old_x {
+            interpreter.symbol_table.insert(old_x);
         }
-    } else {
-        Ok(Expr::Var(identifier))
+
+        result.map(|_| rows)
+    };
+
+    let mut interpreter = new_interpreter(
+        context,
+        #[cfg(feature = "rug")]
+        precision,
+    );
+    #[cfg(target_arch = "wasm32")]
+    let _ = threads; // Threads are not available in WebAssembly
+    #[cfg(not(target_arch = "wasm32"))]
+    if threads > 1 && x_values.len() > 1 {
+        let chunk_size = x_values.len().div_ceil(threads);
+        let chunks: Vec<&[f64]> = x_values.chunks(chunk_size).collect();
+        let mut symbol_tables = vec![interpreter.symbol_table.clone(); chunks.len()];
+        let thread_interpreters: Vec<_> = symbol_tables
+            .iter_mut()
+            .map(|symbol_table| interpreter.with_symbol_table(symbol_table))
+            .collect();
+        let eval_rows = &eval_rows;
+        let results: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = thread_interpreters
+                .into_iter()
+                .zip(chunks)
+                .map(|(mut interpreter, chunk)| {
+                    scope.spawn(move || eval_rows(&mut interpreter, chunk))
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap_or(Err(KalkError::Unknown)))
+                .collect()
+        });
+        let rng = interpreter.take_rng();
+        context.rng = rng;
+
+        let mut rows = Vec::with_capacity(x_values.len());
+        for result in results {
+            rows.extend(result?);
+        }
+
+        return Ok(rows);
     }
+
+    let result = eval_rows(&mut interpreter, &x_values);
+    let rng = interpreter.take_rng();
+    context.rng = rng;
+
+    result
 }
 
-fn peek(context: &Context) -> &Token {
-    if context.pos >= context.tokens.len() {
-        context.tokens.last().unwrap() // Eof
-    } else {
-        &context.tokens[context.pos]
+/// Simplify an expression, eg. `2x + 3x` into `5x`, and return it in kalker syntax.
+/// Nothing is evaluated, so undefined variables are kept as they are.
+pub fn simplify(context: &mut Context, input: &str) -> Result<String, KalkError> {
+    match parse(context, input)?.pop() {
+        Some(Stmt::Expr(expr)) => Ok(expr.simplify().to_string()),
+        _ => Err(KalkError::Expected(String::from("an expression"))),
     }
 }
 
-fn peek_next(context: &Context) -> &Token {
-    &context.tokens[context.pos + 1]
+/// Differentiate an expression symbolically with respect to a variable,
+/// eg. `2x` for `x^2`, and return the simplified derivative in kalker syntax.
+pub fn derive(context: &mut Context, input: &str, var_name: &str) -> Result<String, KalkError> {
+    match parse(context, input)?.pop() {
+        Some(Stmt::Expr(expr)) => Ok(expr
+            .derive(&context.symbol_table, var_name.trim())?
+            .to_string()),
+        _ => Err(KalkError::Expected(String::from("an expression"))),
+    }
 }
 
-fn previous(context: &Context) -> &Token {
-    &context.tokens[context.pos - 1]
+/// The Taylor polynomial of a function, or an expression of x, around x0 with the given degree,
+/// eg. `x - x^3 / 6` for `sin(x)` around 0 with the degree 3, in kalker syntax.
+pub fn taylor(
+    context: &mut Context,
+    function: &str,
+    x0: f64,
+    degree: usize,
+    #[cfg(feature = "rug")] precision: u32,
+) -> Result<String, KalkError> {
+    let input = if context.symbol_table.contains_fn(function) {
+        format!("{}(x)", function)
+    } else {
+        function.to_string()
+    };
+    let expr = match parse(context, &input)?.pop() {
+        Some(Stmt::Expr(expr)) => expr,
+        _ => return Err(KalkError::Expected(String::from("an expression"))),
+    };
+
+    let mut interpreter = new_interpreter(
+        context,
+        #[cfg(feature = "rug")]
+        precision,
+    );
+    let polynomial =
+        numerical::taylor_polynomial(&mut interpreter, &expr, "x", KalkValue::from(x0), degree);
+    let rng = interpreter.take_rng();
+    context.rng = rng;
+
+    Ok(polynomial?.to_string())
 }
 
-fn match_token(context: &Context, kind: TokenKind) -> bool {
-    if is_at_end(context) {
-        return false;
+/// Create a symbol table from definitions given by `SymbolTable::definitions`.
+#[cfg_attr(not(feature = "serde"), allow(dead_code))]
+pub(crate) fn parse_definitions(definitions: &[String]) -> Result<SymbolTable, KalkError> {
+    // The definitions were accepted when they were made
+    let mut context = Context::new().set_shadowing_policy(ShadowingPolicy::Allow);
+
+    // Definitions from the prelude already exist
+    let existing_definitions = context.symbol_table.definitions();
+    let definitions: Vec<&String> = definitions
+        .iter()
+        .filter(|definition| !existing_definitions.contains(definition))
+        .collect();
+
+    // Declare the functions and variables once first, so that eg. functions are
+    // recognised as functions in the definitions that come before their own.
+    for definition in &definitions {
+        if !definition.starts_with("const ") && !definition.starts_with("unit ") {
+            let _ = parse(&mut context, definition);
+        }
     }
 
-    peek(context).kind == kind
-}
+    for definition in definitions {
+        parse(&mut context, definition)?;
+    }
+    for name in context.symbol_table.take_pending_constants() {
+        context.symbol_table.mark_as_constant(&name);
+    }
+    context
+        .symbol_table
+        .set_shadowing_policy(ShadowingPolicy::Deny);
 
-fn advance(context: &mut Context) -> &Token {
-    context.pos += 1;
-    previous(context)
+    Ok(context.symbol_table)
 }
 
-fn consume(context: &mut Context, kind: TokenKind) -> Result<&Token, KalkError> {
-    if match_token(context, kind) {
-        return Ok(advance(context));
+/// Parse expressions/declarations and return a syntax tree.
+///
+/// `None` will be returned if the last statement is a declaration.
+pub fn parse(context: &mut Context, input: &str) -> Result<Vec<Stmt>, KalkError> {
+    match parse_with_positions(context, input, false) {
+        Ok(statements) => Ok(statements.into_iter().map(|(_, stmt)| stmt).collect()),
+        Err(mut errors) => Err(errors.remove(0).error),
     }
+}
 
-    Err(KalkError::UnexpectedToken(peek(context).kind, Some(kind)))
+/// An error found while parsing, with the span of the input from the start of
+/// the statement to the token where the error was found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub error: KalkError,
+    pub span: lexer::Span,
 }
 
-fn is_at_end(context: &Context) -> bool {
-    context.pos >= context.tokens.len() || peek(context).kind == TokenKind::Eof
+/// Parse expressions/declarations like `parse`, but continue with the next statement
+/// after an error, so that all the errors of eg. a script are found at once.
+pub fn parse_all(context: &mut Context, input: &str) -> Result<Vec<Stmt>, Vec<SyntaxError>> {
+    Ok(parse_with_positions(context, input, true)?
+        .into_iter()
+        .map(|(_, stmt)| stmt)
+        .collect())
 }
 
-fn skip_newlines(context: &mut Context) {
-    while match_token(context, TokenKind::Newline) {
-        advance(context);
+/// Evaluate a file with declarations, eg. a prelude with user-defined functions,
+/// one statement at a time. Errors are returned together with the line they are on,
+/// counting from 1. If the file can't be parsed, nothing is evaluated and every syntax
+/// error is returned. Otherwise, if a statement fails, the statements after it are skipped.
+pub fn load(
+    context: &mut Context,
+    input: &str,
+    #[cfg(feature = "rug")] precision: u32,
+) -> Result<(), Vec<(usize, KalkError)>> {
+    let line_of = |char_index: usize| {
+        input
+            .chars()
+            .take(char_index)
+            .filter(|c| *c == '\n')
+            .count()
+            + 1
+    };
+
+    let statements = parse_with_positions(context, input, true).map_err(|errors| {
+        errors
+            .into_iter()
+            .map(|syntax_error| (line_of(syntax_error.span.1), syntax_error.error))
+            .collect::<Vec<_>>()
+    })?;
+
+    for (stmt_start, stmt) in statements {
+        interpret(
+            context,
+            vec![stmt],
+            None,
+            #[cfg(feature = "rug")]
+            precision,
+        )
+        .map_err(|err| {
+            vec![(
+                line_of(stmt_start),
+                with_split_name_suggestions(context, err),
+            )]
+        })?;
     }
+
+    Ok(())
 }
 
-fn string_to_num(value: &str) -> Result<f64, KalkError> {
-    let base = get_base(value)?;
-    if let Some(result) = crate::radix::parse_float_radix(&value.replace(' ', ""), base) {
-        Ok(result)
-    } else {
-        Err(KalkError::InvalidNumberLiteral(value.into()))
+/// Parse the input into statements, each together with the index of the character
+/// in the input that it starts at. With `recover`, parsing continues with the next
+/// statement after an error, otherwise only the first error is returned.
+fn parse_with_positions(
+    context: &mut Context,
+    input: &str,
+    recover: bool,
+) -> Result<Vec<(usize, Stmt)>, Vec<SyntaxError>> {
+    let mut lexer = Lexer::new(input);
+    context.tokens = lexer.lex();
+    context.pos = 0;
+    context.parsing_unit_decl = false;
+    context.unit_decl_base_unit = None;
+    context.expected_keywords.clear();
+    context.other_radix = lexer.get_other_radix();
+    context.alias_spans.clear();
+    apply_aliases(context, 0);
+
+    context.output_suppressed = false;
+
+    let chars: Vec<char> = input.chars().collect();
+    let mut statements: Vec<(usize, Stmt)> = Vec::new();
+    let mut errors = Vec::new();
+    while !is_at_end(context) {
+        context.current_stmt_start_pos = context.pos;
+        let stmt_start = peek(context).span.0;
+        let statement_count = statements.len();
+        if let Err(error) = parse_next_stmt(context, &mut statements) {
+            let span = (stmt_start, peek(context).span.1);
+            errors.push(SyntaxError { error, span });
+            if !recover {
+                break;
+            }
+
+            synchronize(context);
+        } else if statements.len() > statement_count {
+            record_alias_source(context, &chars, &statements.last().unwrap().1);
+        }
+
+        context.output_suppressed = match_token(context, TokenKind::Semicolon);
+        if context.output_suppressed {
+            advance(context);
+        }
+
+        skip_newlines(context);
     }
-}
 
-fn get_base(value: &str) -> Result<u8, KalkError> {
-    let underscore_pos = if let Some(i) = value.find('_') {
-        i
-    } else {
-        return Ok(10);
-    };
+    // Functions and units are already declared when they are parsed
+    if !errors.is_empty()
+        || statements
+            .iter()
+            .any(|(_, stmt)| !matches!(stmt, Stmt::Expr(_)))
+    {
+        context.eval_cache.clear();
+    }
 
-    let subscript = value.chars().skip(underscore_pos + 1usize);
-    if let Some(base) = crate::text_utils::parse_subscript(subscript) {
-        Ok(base)
+    if errors.is_empty() {
+        Ok(statements)
     } else {
-        Err(KalkError::UnrecognizedBase)
+        Err(errors)
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::ast::Identifier;
-    use crate::lexer::{Token, TokenKind::*};
-    use crate::test_helpers::*;
-    use wasm_bindgen_test::*;
+/// Parse the statement (or alias declaration) at the current position.
+fn parse_next_stmt(
+    context: &mut Context,
+    statements: &mut Vec<(usize, Stmt)>,
+) -> Result<(), KalkError> {
+    if match_token(context, TokenKind::AliasKeyword) && is_name(peek_next(context)) {
+        return parse_alias(context);
+    }
 
-    fn parse_with_context(context: &mut Context, tokens: Vec<Token>) -> Result<Stmt, KalkError> {
-        context.tokens = tokens;
-        context.pos = 0;
+    let stmt_start = peek(context).span.0;
+    let parsed = match parse_stmt(context) {
+        Ok(stmt) => stmt,
+        Err(KalkError::WasStmt(stmt)) => stmt,
+        Err(err) => return Err(err),
+    };
+    let symbol_table = &mut context.symbol_table;
+    let analysed = analysis::analyse_stmt(symbol_table, parsed)?;
+    statements.push((stmt_start, analysed));
 
-        let parsed = parse_stmt(context)?;
-        let symbol_table = context.symbol_table.get_mut();
-        analysis::analyse_stmt(symbol_table, parsed)
+    Ok(())
+}
+
+/// Remember the text of a declaration that uses aliases, eg. `f(x) = sq(x)`, which was
+/// just parsed, so that it can be shown like that instead of with the values of the aliases.
+fn record_alias_source(context: &mut Context, input: &[char], stmt: &Stmt) {
+    if !matches!(stmt, Stmt::VarDecl(_, _) | Stmt::FnDecl(_, _, _)) {
+        return;
     }
 
-    fn parse(tokens: Vec<Token>) -> Result<Stmt, KalkError> {
-        let mut context = Context::new();
-        context.tokens = tokens;
-        context.pos = 0;
+    let mut first_token = &context.tokens[context.current_stmt_start_pos];
+    if first_token.kind == TokenKind::ConstKeyword {
+        first_token = &context.tokens[context.current_stmt_start_pos + 1];
+    }
+
+    let start = first_token.span.0;
+    let end = context.tokens[context.pos - 1].span.1.min(input.len());
+    if context
+        .alias_spans
+        .iter()
+        .any(|span| span.0 >= start && span.1 <= end)
+    {
+        let source: String = input[start..end].iter().collect();
+        context
+            .symbol_table
+            .set_alias_source(stmt, source.trim().to_string());
+    }
+}
+
+/// Skip to the end of the statement that couldn't be parsed, ie. the next semicolon
+/// or line break outside of brackets, so that parsing can continue after it.
+fn synchronize(context: &mut Context) {
+    context.pos = context.current_stmt_start_pos;
+    context.parsing_unit_decl = false;
+    context.unit_decl_base_unit = None;
+    context.expected_keywords.clear();
+
+    let mut depth = 0;
+    while !is_at_end(context) {
+        match peek(context).kind {
+            TokenKind::OpenParenthesis
+            | TokenKind::OpenDoubleBracket
+            | TokenKind::OpenCeil
+            | TokenKind::OpenFloor
+            | TokenKind::OpenBracket
+            | TokenKind::OpenBrace => depth += 1,
+            TokenKind::ClosedParenthesis
+            | TokenKind::ClosedDoubleBracket
+            | TokenKind::ClosedCeil
+            | TokenKind::ClosedFloor
+            | TokenKind::ClosedBracket
+            | TokenKind::ClosedBrace => depth -= 1,
+            TokenKind::Semicolon | TokenKind::Newline if depth <= 0 => break,
+            _ => (),
+        }
+
+        advance(context);
+    }
+}
+
+/// Parse an alias declaration, eg. `alias sq = sqrt`. The name will be replaced
+/// with the tokens after the equals sign wherever it is used from now on.
+fn parse_alias(context: &mut Context) -> Result<(), KalkError> {
+    advance(context); // Alias keyword
+    keyword_to_identifier(context);
+    let name = consume(context, TokenKind::Identifier)?.value.clone();
+    consume(context, TokenKind::Equals)?;
+
+    let mut tokens = Vec::new();
+    while !is_at_end(context)
+        && !match_token(context, TokenKind::Semicolon)
+        && !match_token(context, TokenKind::Newline)
+    {
+        tokens.push(advance(context).clone());
+    }
+
+    if tokens.is_empty() {
+        return Err(KalkError::Expected(String::from("a value for the alias")));
+    }
+
+    // Wrap it in parentheses to make sure eg. `2x` still
+    // becomes `2(a + b)` and not `2a + b`, if x is an alias.
+    if tokens.len() > 1 {
+        let (start, end) = (tokens[0].span.0, tokens.last().unwrap().span.1);
+        tokens.insert(
+            0,
+            Token {
+                kind: TokenKind::OpenParenthesis,
+                value: String::new(),
+                span: (start, start),
+            },
+        );
+        tokens.push(Token {
+            kind: TokenKind::ClosedParenthesis,
+            value: String::new(),
+            span: (end, end),
+        });
+    }
+
+    context.symbol_table.insert_alias(&name, tokens);
+    apply_aliases(context, context.pos);
+
+    Ok(())
+}
+
+/// Replace the aliases found in the tokens after `start` with their values.
+fn apply_aliases(context: &mut Context, start: usize) {
+    let mut previous_kind = start
+        .checked_sub(1)
+        .map(|previous| context.tokens[previous].kind);
+    let symbol_table = &context.symbol_table;
+    let input_tokens: Vec<Token> = context.tokens.drain(start..).collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < input_tokens.len() {
+        let token = &input_tokens[i];
+        let name_length = alias_name_length(&input_tokens[i..]);
+        let alias = (1..=name_length).rev().find_map(|length| {
+            symbol_table
+                .get_alias(&join_token_values(&input_tokens[i..i + length]))
+                .map(|alias_tokens| (length, alias_tokens))
+        });
+        match alias {
+            // The name in eg. `alias x2 = ...` should not be replaced, but it is joined into one token
+            _ if previous_kind == Some(TokenKind::AliasKeyword) && name_length > 1 => {
+                let last = &input_tokens[i + name_length - 1];
+                tokens.push(Token {
+                    kind: TokenKind::Identifier,
+                    value: join_token_values(&input_tokens[i..i + name_length]),
+                    span: (token.span.0, last.span.1),
+                });
+                i += name_length;
+            }
+            Some((length, alias_tokens)) if previous_kind != Some(TokenKind::AliasKeyword) => {
+                // The value of the alias is where the name is in this input
+                let span = (token.span.0, input_tokens[i + length - 1].span.1);
+                context.alias_spans.push(span);
+                tokens.extend(alias_tokens.iter().map(|alias_token| Token {
+                    span,
+                    ..alias_token.clone()
+                }));
+                i += length;
+            }
+            _ => {
+                tokens.push(token.clone());
+                i += 1;
+            }
+        }
+
+        previous_kind = Some(token.kind);
+    }
+
+    context.tokens.append(&mut tokens);
+}
+
+/// Names with digits, eg. `r2`, are lexed as several tokens, which are right after each other.
+/// Get the amount of tokens at the start that could be part of such a name.
+fn alias_name_length(tokens: &[Token]) -> usize {
+    if tokens.first().map(|token| token.kind) != Some(TokenKind::Identifier) {
+        return 0;
+    }
+
+    1 + tokens
+        .windows(2)
+        .take_while(|pair| {
+            let (previous, token) = (&pair[0], &pair[1]);
+            token.span.0 == previous.span.1
+                && (token.kind == TokenKind::Identifier
+                    || (token.kind == TokenKind::Literal
+                        && token.value.chars().all(|c| c.is_ascii_digit())))
+        })
+        .count()
+}
+
+fn join_token_values(tokens: &[Token]) -> String {
+    tokens.iter().map(|token| token.value.as_str()).collect()
+}
+
+fn parse_stmt(context: &mut Context) -> Result<Stmt, KalkError> {
+    if match_token(context, TokenKind::UnitKeyword) {
+        parse_unit_decl_stmt(context)
+    } else if match_token(context, TokenKind::ConstKeyword) && is_name(peek_next(context)) {
+        parse_const_decl_stmt(context)
+    } else if !is_at_end(context)
+        && is_name(peek(context))
+        && matches!(
+            peek_next(context).kind,
+            TokenKind::PlusEquals
+                | TokenKind::MinusEquals
+                | TokenKind::StarEquals
+                | TokenKind::SlashEquals
+        )
+    {
+        keyword_to_identifier(context);
+        parse_compound_assignment_stmt(context)
+    } else {
+        Ok(Stmt::Expr(Box::new(parse_expr(context)?)))
+    }
+}
+
+fn parse_piecewise(context: &mut Context) -> Result<Expr, KalkError> {
+    advance(context);
+    skip_newlines(context);
+
+    let mut pieces = Vec::new();
+    let mut reached_otherwise = false;
+    // Parse `expr if condition`
+    // do-while loop
+    while {
+        let left_expr = parse_expr(context)?;
+        if match_token(context, TokenKind::IfKeyword) {
+            advance(context);
+            pieces.push(crate::ast::ConditionalPiece {
+                expr: left_expr,
+                condition: parse_expr(context)?,
+            });
+        } else if match_token(context, TokenKind::OtherwiseKeyword) {
+            advance(context);
+            // Yeah, a bit hacky, but there's no `true` keyword...
+            let true_expr = Expr::Binary(
+                Box::new(Expr::Literal(1f64, ExprSpan::default())),
+                TokenKind::Equals,
+                Box::new(Expr::Literal(1f64, ExprSpan::default())),
+                ExprSpan::default(),
+            );
+            pieces.push(crate::ast::ConditionalPiece {
+                expr: left_expr,
+                condition: true_expr,
+            });
+
+            reached_otherwise = true;
+        } else {
+            return Err(KalkError::ExpectedIf);
+        }
+
+        if match_token(context, TokenKind::Semicolon) {
+            advance(context);
+        }
+        skip_newlines(context);
+
+        (previous(context).kind == TokenKind::Semicolon
+            || previous(context).kind == TokenKind::Newline)
+            && !reached_otherwise
+            && !match_token(context, TokenKind::ClosedBrace)
+    } {}
+
+    advance(context);
+
+    Ok(Expr::Piecewise(pieces))
+}
+
+fn parse_unit_decl_stmt(context: &mut Context) -> Result<Stmt, KalkError> {
+    advance(context); // Unit keyword
+    let identifier = advance(context).clone();
+    consume(context, TokenKind::Equals)?;
+
+    // Parse the mut definition
+    context.unit_decl_base_unit = None;
+    context.parsing_unit_decl = true;
+    let def = parse_expr(context)?;
+    context.parsing_unit_decl = false;
+
+    let base_unit = if let Some(base_unit) = &context.unit_decl_base_unit {
+        base_unit.clone()
+    } else {
+        return Err(KalkError::InvalidUnit);
+    };
+
+    // Automatically create a second unit decl with the expression inverted.
+    // This will turn eg. unit a = 3b, into unit b = a/3
+    // This is so that you only have to define `a`, and it will figure out the formula for `b` since it is used in the formula for `a`.
+    let stmt_inv = Stmt::UnitDecl(
+        base_unit.clone(),
+        identifier.value.clone(),
+        Box::new(def.invert(&mut context.symbol_table, DECL_UNIT)?),
+    );
+    let stmt = Stmt::UnitDecl(identifier.value, base_unit, Box::new(def));
+
+    context.symbol_table.insert(stmt.clone());
+    context.symbol_table.insert_inverted_unit(stmt_inv);
+
+    Ok(stmt)
+}
+
+fn parse_const_decl_stmt(context: &mut Context) -> Result<Stmt, KalkError> {
+    advance(context); // Const keyword
+    let identifier = Identifier::from_full_name(&consume(context, TokenKind::Identifier)?.value);
+    consume(context, TokenKind::Equals)?;
+    let value = parse_expr(context)?;
+
+    let symbol_table = &mut context.symbol_table;
+    symbol_table.check_shadowing(&identifier, false)?;
+
+    // It's only a constant once the declaration has been evaluated, so that eg. a declaration
+    // that fails or is rejected by middleware doesn't keep the name from being used.
+    symbol_table.mark_as_pending_constant(&identifier.full_name);
+
+    Ok(Stmt::VarDecl(identifier, Box::new(value)))
+}
+
+/// Parse eg. `x += 2`, which turns into `x = x + (2)`.
+fn parse_compound_assignment_stmt(context: &mut Context) -> Result<Stmt, KalkError> {
+    let identifier = Identifier::from_full_name(&advance(context).value.clone());
+    let op = match advance(context).kind {
+        TokenKind::PlusEquals => TokenKind::Plus,
+        TokenKind::MinusEquals => TokenKind::Minus,
+        TokenKind::StarEquals => TokenKind::Star,
+        TokenKind::SlashEquals => TokenKind::Slash,
+        _ => unreachable!(),
+    };
+    let value = parse_expr(context)?;
+
+    let symbol_table = &mut context.symbol_table;
+    if symbol_table.is_declared_constant(&identifier.full_name)
+        || (crate::prelude::is_constant(&identifier.full_name)
+            && symbol_table.get_var(&identifier.full_name).is_none())
+    {
+        return Err(KalkError::UnableToOverrideConstant(identifier.pure_name));
+    }
+
+    if !matches!(
+        symbol_table.get_var(&identifier.full_name),
+        Some(Stmt::VarDecl(_, _))
+    ) {
+        let suggestions = symbol_table.similar_names(&identifier.full_name, false);
+        return Err(KalkError::UndefinedVar(identifier.full_name, suggestions));
+    }
+
+    // The interpreter replaces the previous value with the result of this,
+    // instead of keeping the declaration as an expression.
+    Ok(Stmt::VarDecl(
+        identifier.clone(),
+        Box::new(Expr::Binary(
+            Box::new(Expr::Var(identifier)),
+            op,
+            Box::new(Expr::Group(Box::new(value))),
+            ExprSpan::default(),
+        )),
+    ))
+}
+
+fn parse_expr(context: &mut Context) -> Result<Expr, KalkError> {
+    parse_or(context)
+}
+
+fn parse_comprehension(context: &mut Context) -> Result<Expr, KalkError> {
+    let start = context.pos;
+    let left = parse_or(context)?;
+
+    if match_token(context, TokenKind::Colon) {
+        let op = advance(context).kind;
+        skip_newlines(context);
+        let right = Box::new(parse_comprehension_comma(context)?);
+        return Ok(Expr::Binary(
+            Box::new(left),
+            op,
+            right,
+            span_since(context, start),
+        ));
+    }
+
+    Ok(left)
+}
+
+fn parse_comprehension_comma(context: &mut Context) -> Result<Expr, KalkError> {
+    let start = context.pos;
+    let left = parse_or(context)?;
+
+    if match_token(context, TokenKind::Comma) {
+        let op = advance(context).kind;
+        skip_newlines(context);
+        let right = Box::new(parse_comprehension_comma(context)?);
+        return Ok(Expr::Binary(
+            Box::new(left),
+            op,
+            right,
+            span_since(context, start),
+        ));
+    }
+
+    Ok(left)
+}
+
+fn parse_or(context: &mut Context) -> Result<Expr, KalkError> {
+    let start = context.pos;
+    let left = parse_and(context)?;
+
+    if match_token(context, TokenKind::Or) {
+        let op = advance(context).kind;
+        skip_newlines(context);
+        let right = Box::new(parse_or(context)?);
+        return Ok(Expr::Binary(
+            Box::new(left),
+            op,
+            right,
+            span_since(context, start),
+        ));
+    }
+
+    Ok(left)
+}
+
+fn parse_and(context: &mut Context) -> Result<Expr, KalkError> {
+    let start = context.pos;
+    let left = parse_not(context)?;
+
+    if match_token(context, TokenKind::And) {
+        let op = advance(context).kind;
+        skip_newlines(context);
+        let right = Box::new(parse_and(context)?);
+        return Ok(Expr::Binary(
+            Box::new(left),
+            op,
+            right,
+            span_since(context, start),
+        ));
+    }
+
+    Ok(left)
+}
+
+/// `not` binds looser than comparisons, so that eg. `not x > 1` means `not (x > 1)`.
+/// It can still be used as an operand of a comparison, eg. `a = not b`.
+fn parse_not(context: &mut Context) -> Result<Expr, KalkError> {
+    let start = context.pos;
+    if match_token(context, TokenKind::Not) {
+        advance(context);
+        let expr = parse_not(context)?;
+
+        return Ok(Expr::Unary(
+            TokenKind::Not,
+            Box::new(expr),
+            span_since(context, start),
+        ));
+    }
+
+    parse_comparison(context)
+}
+
+fn parse_comparison(context: &mut Context) -> Result<Expr, KalkError> {
+    let at_start_of_line = context.current_stmt_start_pos == context.pos;
+    let start = context.pos;
+    let mut left = parse_to(context)?;
+
+    if match_token(context, TokenKind::Arrow) {
+        return parse_limit_target(context, left);
+    }
+
+    let mut previous = left.clone();
+    let mut previous_start = start;
+    let mut is_chain = false;
+
+    // Equality check
+    while match_token(context, TokenKind::Equals)
+        || match_token(context, TokenKind::NotEquals)
+        || match_token(context, TokenKind::ApproxEquals)
+        || match_token(context, TokenKind::GreaterThan)
+        || match_token(context, TokenKind::LessThan)
+        || match_token(context, TokenKind::GreaterOrEquals)
+        || match_token(context, TokenKind::LessOrEquals)
+    {
+        let op = advance(context).kind;
+
+        if let (true, Some((identifier, parameters))) =
+            (at_start_of_line, analysis::is_fn_decl(&left))
+        {
+            context.symbol_table.check_shadowing(&identifier, true)?;
+            context.symbol_table.set(Stmt::FnDecl(
+                identifier.clone(),
+                parameters.clone(),
+                Box::new(Expr::Literal(1f64, ExprSpan::default())),
+            ));
+            let right = if match_token(context, TokenKind::OpenBrace) {
+                parse_piecewise(context)?
+            } else {
+                parse_expr(context)?
+            };
+            let fn_decl = Stmt::FnDecl(identifier, parameters, Box::new(right));
+
+            // Hack to return a statement...
+            return Err(KalkError::WasStmt(fn_decl));
+        };
+
+        // A chain of comparisons, eg. `0 < x < 10`, is a conjunction of the comparison of each
+        // operand with the next one, eg. `0 < x and x < 10`, rather than `(0 < x) < 10`.
+        let right_start = context.pos;
+        let right = parse_to(context)?;
+        let comparison = Expr::Binary(
+            Box::new(previous.clone()),
+            op,
+            Box::new(right.clone()),
+            span_since(context, previous_start),
+        );
+        left = if is_chain {
+            Expr::Binary(
+                Box::new(left),
+                TokenKind::And,
+                Box::new(comparison),
+                span_since(context, start),
+            )
+        } else {
+            comparison
+        };
+        previous = right;
+        previous_start = right_start;
+        is_chain = true;
+    }
+
+    Ok(left)
+}
+
+/// The `x -> a` in `lim(f(x), x -> a)`. A trailing `+` or `-` makes it `x -> a+` or `x -> a-`,
+/// which is represented as a unary `>` or `<` of the target.
+fn parse_limit_target(context: &mut Context, left: Expr) -> Result<Expr, KalkError> {
+    advance(context);
+    context.parsing_limit_target = true;
+    let target = parse_to(context);
+    context.parsing_limit_target = false;
+    let mut target = target?;
+
+    if match_token(context, TokenKind::Plus) {
+        advance(context);
+        target = Expr::Unary(
+            TokenKind::GreaterThan,
+            Box::new(target),
+            ExprSpan::default(),
+        );
+    } else if match_token(context, TokenKind::Minus) {
+        advance(context);
+        target = Expr::Unary(TokenKind::LessThan, Box::new(target), ExprSpan::default());
+    }
+
+    Ok(Expr::Binary(
+        Box::new(left),
+        TokenKind::Arrow,
+        Box::new(target),
+        ExprSpan::default(),
+    ))
+}
+
+fn parse_to(context: &mut Context) -> Result<Expr, KalkError> {
+    let start = context.pos;
+    let left = parse_term(context)?;
+
+    if match_token(context, TokenKind::ToKeyword) {
+        advance(context);
+        let right = Expr::Var(Identifier::from_full_name(&parse_to_unit(context))); // Parse this as a variable for now.
+
+        return Ok(Expr::Binary(
+            Box::new(left),
+            TokenKind::ToKeyword,
+            Box::new(right),
+            span_since(context, start),
+        ));
+    }
+
+    Ok(left)
+}
+
+/// The unit to convert to, which may be divided by other units, eg. `km/h`.
+fn parse_to_unit(context: &mut Context) -> String {
+    let unit = advance(context).value.clone();
+    let mut dimension = crate::units::Dimension::parse(&unit);
+    let mut is_compound = false;
+    while match_token(context, TokenKind::Slash)
+        && peek_next(context).kind == TokenKind::Identifier
+        && context
+            .symbol_table
+            .contains_unit(&peek_next(context).value)
+    {
+        advance(context);
+        let denominator = crate::units::Dimension::parse(&advance(context).value);
+        dimension = dimension.mul(&denominator, -1);
+        is_compound = true;
+    }
+
+    if is_compound {
+        dimension.to_string()
+    } else {
+        unit
+    }
+}
+
+fn parse_term(context: &mut Context) -> Result<Expr, KalkError> {
+    let start = context.pos;
+    let mut left = parse_factor(context)?;
+
+    while match_token(context, TokenKind::Plus) || match_token(context, TokenKind::Minus) {
+        if context.parsing_limit_target
+            && matches!(
+                peek_next(context).kind,
+                TokenKind::ClosedParenthesis | TokenKind::Comma | TokenKind::Eof
+            )
+        {
+            break;
+        }
+
+        let op = peek(context).kind;
+        advance(context);
+        let right = parse_factor(context)?;
+
+        left = Expr::Binary(
+            Box::new(left),
+            op,
+            Box::new(right),
+            span_since(context, start),
+        );
+    }
+
+    Ok(left)
+}
+
+fn parse_factor(context: &mut Context) -> Result<Expr, KalkError> {
+    let start = context.pos;
+    let mut left = parse_unit(context)?;
+
+    if let Expr::Unary(TokenKind::Percent, percent_left, _) = left.clone() {
+        let try_parse = parse_unit(context);
+        if try_parse.is_ok() {
+            left = Expr::Binary(
+                percent_left,
+                TokenKind::Percent,
+                Box::new(try_parse?),
+                span_since(context, start),
+            );
+        }
+    }
+
+    while {
+        resolve_keyword_after_operand(context);
+
+        match_token(context, TokenKind::Star)
+            || match_token(context, TokenKind::Slash)
+            || match_token(context, TokenKind::Percent)
+            || match_token(context, TokenKind::Mod)
+            || match_token(context, TokenKind::Rem)
+            || match_token(context, TokenKind::Div)
+            || match_token(context, TokenKind::Quot)
+            || match_token(context, TokenKind::Identifier)
+            || match_token(context, TokenKind::Literal)
+            || match_token(context, TokenKind::OpenParenthesis)
+            || match_token(context, TokenKind::OpenCeil)
+            || match_token(context, TokenKind::OpenFloor)
+            || match_token(context, TokenKind::OpenBracket)
+    } {
+        // If the token is an identifier, literal, or open parenthesis,
+        // assume it's multiplication. Eg. 3y or (3x + 2)(2 + 3)
+        let op = match peek(context).kind {
+            TokenKind::Identifier
+            | TokenKind::Literal
+            | TokenKind::OpenParenthesis
+            | TokenKind::OpenCeil
+            | TokenKind::OpenFloor
+            | TokenKind::OpenBracket => TokenKind::Star,
+            _ => advance(context).kind,
+        };
+
+        let right = parse_unit(context)?;
+
+        left = Expr::Binary(
+            Box::new(left),
+            op,
+            Box::new(right),
+            span_since(context, start),
+        );
+    }
+
+    Ok(left)
+}
+
+fn parse_unit(context: &mut Context) -> Result<Expr, KalkError> {
+    let expr = parse_exponent(context)?;
+    resolve_keyword_after_operand(context);
+
+    // In a unit declaration, the unit in the definition is the one it's based on.
+    // A unit followed by a parenthesis is a function call instead, eg. `min` in `2 min(3, 4)`.
+    if match_token(context, TokenKind::Identifier)
+        && !context.parsing_unit_decl
+        && context.tokens.get(context.pos + 1).map(|token| token.kind)
+            != Some(TokenKind::OpenParenthesis)
+    {
+        let peek = &peek(context).value.clone();
+        if context.symbol_table.contains_unit(peek) {
+            return Ok(Expr::Unit(
+                advance(context).value.to_string(),
+                Box::new(expr),
+            ));
+        }
+    }
+
+    Ok(expr)
+}
+
+fn parse_exponent(context: &mut Context) -> Result<Expr, KalkError> {
+    let start = context.pos;
+    let left = parse_unary(context)?;
+
+    if match_token(context, TokenKind::Power) {
+        let op = advance(context).kind;
+        let right = Box::new(parse_exponent(context)?);
+
+        return Ok(Expr::Binary(
+            Box::new(left),
+            op,
+            right,
+            span_since(context, start),
+        ));
+    }
+
+    Ok(left)
+}
+
+fn parse_unary(context: &mut Context) -> Result<Expr, KalkError> {
+    let start = context.pos;
+    if match_token(context, TokenKind::Minus) || match_token(context, TokenKind::Not) {
+        let op = advance(context).kind;
+        let expr = Box::new(parse_unary(context)?);
+
+        return Ok(Expr::Unary(op, expr, span_since(context, start)));
+    }
+
+    let expr = parse_indexer(context)?;
+    if match_token(context, TokenKind::Percent) {
+        Ok(Expr::Unary(
+            advance(context).kind,
+            Box::new(expr),
+            span_since(context, start),
+        ))
+    } else {
+        Ok(expr)
+    }
+}
+
+fn parse_indexer(context: &mut Context) -> Result<Expr, KalkError> {
+    let left = parse_factorial(context)?;
+
+    if match_token(context, TokenKind::OpenDoubleBracket) {
+        advance(context);
+        let mut indexes = vec![parse_expr(context)?];
+        while match_token(context, TokenKind::Comma) {
+            advance(context);
+            indexes.push(parse_expr(context)?);
+        }
+
+        consume(context, TokenKind::ClosedDoubleBracket)?;
+
+        return Ok(Expr::Indexer(Box::new(left), indexes));
+    }
+
+    Ok(left)
+}
+
+fn parse_factorial(context: &mut Context) -> Result<Expr, KalkError> {
+    let start = context.pos;
+    let expr = parse_primary(context)?;
+
+    Ok(if match_token(context, TokenKind::Exclamation) {
+        advance(context);
+        Expr::Unary(
+            TokenKind::Exclamation,
+            Box::new(expr),
+            span_since(context, start),
+        )
+    } else {
+        expr
+    })
+}
+
+fn parse_primary(context: &mut Context) -> Result<Expr, KalkError> {
+    let expr = match peek(context).kind {
+        TokenKind::OpenParenthesis | TokenKind::OpenBracket => parse_vector(context)?,
+        TokenKind::Pipe | TokenKind::OpenCeil | TokenKind::OpenFloor => parse_group_fn(context)?,
+        TokenKind::Identifier => parse_identifier(context)?,
+        TokenKind::LetKeyword if starts_construct(context, TokenKind::Equals) => {
+            parse_let(context)?
+        }
+        TokenKind::WithKeyword if starts_construct(context, TokenKind::OpenBrace) => {
+            parse_with(context)?
+        }
+        _ if is_contextual_keyword(peek(context)) => {
+            keyword_to_identifier(context);
+            parse_identifier(context)?
+        }
+        TokenKind::IfKeyword => parse_if(context)?,
+        TokenKind::Literal => {
+            let token = advance(context).clone();
+            parse_literal(&token.value)?.with_span(ExprSpan::new(token.span))
+        }
+        TokenKind::StringLiteral => Expr::String(advance(context).value.clone()),
+        TokenKind::Unknown if peek(context).value == "\"" => {
+            return Err(KalkError::Expected(String::from(
+                "a closing quote at the end of the string",
+            )))
+        }
+        TokenKind::True => {
+            advance(context);
+            Expr::Boolean(true)
+        }
+        TokenKind::False => {
+            advance(context);
+            Expr::Boolean(false)
+        }
+        _ => return Err(KalkError::UnexpectedToken(peek(context).kind, None)),
+    };
+
+    Ok(expr)
+}
+
+fn parse_let(context: &mut Context) -> Result<Expr, KalkError> {
+    advance(context); // Let keyword
+    keyword_to_identifier(context);
+    let identifier = Identifier::from_full_name(&consume(context, TokenKind::Identifier)?.value);
+    consume(context, TokenKind::Equals)?;
+    let value = parse_with_expected_keyword(context, TokenKind::InKeyword)?;
+    skip_newlines(context);
+    consume(context, TokenKind::InKeyword)?;
+    skip_newlines(context);
+    let body = parse_expr(context)?;
+
+    Ok(Expr::Let(identifier, Box::new(value), Box::new(body)))
+}
+
+fn parse_with(context: &mut Context) -> Result<Expr, KalkError> {
+    advance(context); // With keyword
+    let angle_unit = consume(context, TokenKind::Identifier)?.value.clone();
+    if !crate::prelude::is_angle_unit(&angle_unit) {
+        return Err(KalkError::Expected(String::from(
+            "an angle unit after 'with', either rad, deg, grad or turn",
+        )));
+    }
+
+    consume(context, TokenKind::OpenBrace)?;
+    skip_newlines(context);
+    let expr = parse_expr(context)?;
+    skip_newlines(context);
+    consume(context, TokenKind::ClosedBrace)?;
+
+    Ok(Expr::WithAngleUnit(angle_unit, Box::new(expr)))
+}
+
+fn parse_if(context: &mut Context) -> Result<Expr, KalkError> {
+    advance(context); // If keyword
+    let condition = parse_with_expected_keyword(context, TokenKind::ThenKeyword)?;
+    skip_newlines(context);
+    consume(context, TokenKind::ThenKeyword)?;
+    skip_newlines(context);
+    let then_branch = parse_with_expected_keyword(context, TokenKind::ElseKeyword)?;
+    skip_newlines(context);
+    consume(context, TokenKind::ElseKeyword)?;
+    skip_newlines(context);
+    let else_branch = parse_expr(context)?;
+
+    Ok(Expr::If(
+        Box::new(condition),
+        Box::new(then_branch),
+        Box::new(else_branch),
+    ))
+}
+
+/// Parse an expression that ends with a contextual keyword, eg. the value before `in`.
+fn parse_with_expected_keyword(
+    context: &mut Context,
+    keyword: TokenKind,
+) -> Result<Expr, KalkError> {
+    context.expected_keywords.push(keyword);
+    let expr = parse_expr(context);
+    context.expected_keywords.pop();
+
+    expr
+}
+
+/// Words that became keywords after names could already be anything, eg. `rem` and `in`.
+/// They are only keywords where the syntax expects them, so that variables and units
+/// with those names, eg. `in` for inches, keep working.
+fn is_contextual_keyword(token: &Token) -> bool {
+    match token.kind {
+        // `//` is also lexed as div
+        TokenKind::Div => !token.value.is_empty(),
+        TokenKind::Rem
+        | TokenKind::Quot
+        | TokenKind::ConstKeyword
+        | TokenKind::AliasKeyword
+        | TokenKind::LetKeyword
+        | TokenKind::InKeyword
+        | TokenKind::WithKeyword
+        | TokenKind::ThenKeyword
+        | TokenKind::ElseKeyword => true,
+        _ => false,
+    }
+}
+
+fn is_name(token: &Token) -> bool {
+    token.kind == TokenKind::Identifier || is_contextual_keyword(token)
+}
+
+/// Use the contextual keyword at the position as a name.
+fn keyword_to_identifier(context: &mut Context) {
+    if !is_at_end(context) && is_contextual_keyword(peek(context)) {
+        context.tokens[context.pos].kind = TokenKind::Identifier;
+    }
+}
+
+/// Whether the keyword at the position is followed by a name and then the token,
+/// eg. `let x =` or `with deg {`.
+fn starts_construct(context: &Context, kind: TokenKind) -> bool {
+    context.tokens.get(context.pos + 1).is_some_and(is_name)
+        && context
+            .tokens
+            .get(context.pos + 2)
+            .is_some_and(|token| token.kind == kind)
+}
+
+/// Decide whether a contextual keyword right after an operand is a keyword, eg. `rem` in
+/// `x rem 3` or `in` in `let x = 2 in x`, or a name, eg. the unit in `2 in` or `rem` in `2rem`.
+fn resolve_keyword_after_operand(context: &mut Context) {
+    if is_at_end(context) || !is_contextual_keyword(peek(context)) {
+        return;
+    }
+
+    let is_keyword = match peek(context).kind {
+        TokenKind::Rem | TokenKind::Div | TokenKind::Quot => context
+            .tokens
+            .get(context.pos + 1)
+            .is_some_and(|next| can_start_operand(next.kind)),
+        TokenKind::LetKeyword => starts_construct(context, TokenKind::Equals),
+        TokenKind::WithKeyword => starts_construct(context, TokenKind::OpenBrace),
+        kind => context.expected_keywords.contains(&kind),
+    };
+    if !is_keyword {
+        keyword_to_identifier(context);
+    }
+}
+
+fn can_start_operand(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Identifier
+            | TokenKind::Literal
+            | TokenKind::StringLiteral
+            | TokenKind::True
+            | TokenKind::False
+            | TokenKind::Minus
+            | TokenKind::Not
+            | TokenKind::Pipe
+            | TokenKind::OpenParenthesis
+            | TokenKind::OpenBracket
+            | TokenKind::OpenCeil
+            | TokenKind::OpenFloor
+            | TokenKind::IfKeyword
+            | TokenKind::LetKeyword
+            | TokenKind::WithKeyword
+            | TokenKind::Rem
+            | TokenKind::Div
+            | TokenKind::Quot
+            | TokenKind::ConstKeyword
+            | TokenKind::AliasKeyword
+    )
+}
+
+fn parse_group_fn(context: &mut Context) -> Result<Expr, KalkError> {
+    let name = match &peek(context).kind {
+        TokenKind::Pipe => "abs",
+        TokenKind::OpenCeil => "ceil",
+        TokenKind::OpenFloor => "floor",
+        _ => unreachable!(),
+    };
+
+    match parse_vector(context)? {
+        Expr::Vector(arguments) => Ok(Expr::FnCall(Identifier::from_full_name(name), arguments)),
+        Expr::Group(argument) => Ok(Expr::FnCall(
+            Identifier::from_full_name(name),
+            vec![*argument],
+        )),
+        _ => unreachable!(),
+    }
+}
+
+fn parse_vector(context: &mut Context) -> Result<Expr, KalkError> {
+    let kind = advance(context).kind;
+
+    if kind == TokenKind::OpenBracket {
+        skip_newlines(context);
+    }
+
+    let first_expr = if kind == TokenKind::OpenBracket {
+        parse_comprehension(context)?
+    } else {
+        parse_expr(context)?
+    };
+    let mut rows = vec![vec![first_expr]];
+    let mut column_count = None;
+    let mut items_in_row = 1;
+    while match_token(context, TokenKind::Comma)
+        || match_token(context, TokenKind::Semicolon)
+        || (match_token(context, TokenKind::Newline)
+            && peek_next(context).kind != TokenKind::ClosedBracket)
+    {
+        if kind == TokenKind::OpenBracket
+            && (match_token(context, TokenKind::Newline)
+                || match_token(context, TokenKind::Semicolon))
+        {
+            if let Some(columns) = column_count {
+                if columns != items_in_row {
+                    return Err(KalkError::InconsistentColumnWidths);
+                }
+            } else {
+                column_count = Some(items_in_row);
+            }
+
+            rows.push(Vec::new());
+            items_in_row = 0;
+        }
+
+        advance(context);
+        rows.last_mut().unwrap().push(parse_expr(context)?);
+        items_in_row += 1;
+    }
+
+    if peek(context).kind == TokenKind::Eof {
+        return Err(KalkError::Expected(String::from(
+            "Closing group symbol, eg. )",
+        )));
+    }
+
+    if kind == TokenKind::OpenBracket {
+        skip_newlines(context);
+    }
+
+    advance(context);
+
+    if rows.len() == 1 {
+        let mut values = rows.pop().unwrap();
+        if values.len() == 1 {
+            Ok(Expr::Group(Box::new(values.pop().unwrap())))
+        } else {
+            Ok(Expr::Vector(values))
+        }
+    } else {
+        Ok(Expr::Matrix(rows))
+    }
+}
+
+fn parse_identifier(context: &mut Context) -> Result<Expr, KalkError> {
+    let at_start_of_line = context.current_stmt_start_pos == context.pos;
+    let token = advance(context);
+    let identifier = Identifier::from_full_name(&token.value).with_span(token.span);
+
+    let mut log_base = None;
+    if identifier.full_name.starts_with("log") {
+        if let Some(lowered) = identifier.get_lowered_part() {
+            if let Ok(lowered_float) = lowered.parse::<f64>() {
+                log_base = Some(Expr::Literal(lowered_float, ExprSpan::default()));
+            }
+        }
+    }
+
+    if context.parsing_unit_decl && !context.symbol_table.contains_var(&identifier.full_name) {
+        context.unit_decl_base_unit = Some(identifier.full_name);
+        Ok(Expr::Var(Identifier::from_full_name(DECL_UNIT)))
+    } else if log_base.is_some() || context.symbol_table.contains_fn(&identifier.pure_name) {
+        let identifier_pos = context.pos;
+
+        // Function call
+        let mut arguments = if match_token(context, TokenKind::OpenParenthesis)
+            && peek_next(context).kind == TokenKind::ClosedParenthesis
+        {
+            // No arguments, eg. now()
+            advance(context);
+            advance(context);
+            Vec::new()
+        } else {
+            match parse_primary(context)? {
+                Expr::Vector(arguments) => arguments,
+                Expr::Group(argument) => vec![*argument],
+                argument => vec![argument],
+            }
+        };
+
+        // If it's a re-definition, revert and parse as a declaration
+        if at_start_of_line
+            && match_token(context, TokenKind::Equals)
+            && arguments.iter().all(|x| matches!(x, Expr::Var(_)))
+        {
+            for argument in &arguments {
+                let mut all_vars_exist = true;
+                if let Expr::Var(argument_identifier) = argument {
+                    if !context
+                        .symbol_table
+                        .contains_var(&argument_identifier.full_name)
+                    {
+                        all_vars_exist = false;
+                    }
+                }
+
+                if !all_vars_exist {
+                    context
+                        .symbol_table
+                        .get_and_remove_fn(&identifier.full_name);
+                    context.pos = identifier_pos;
+
+                    return Ok(Expr::Var(identifier));
+                }
+            }
+        }
+
+        if let Some(log_base) = log_base {
+            let log_arg = arguments.remove(0);
+            Ok(Expr::FnCall(
+                Identifier::from_full_name("log"),
+                vec![log_arg, log_base],
+            ))
+        } else {
+            Ok(Expr::FnCall(identifier, arguments))
+        }
+    } else {
+        Ok(Expr::Var(identifier))
+    }
+}
+
+fn peek(context: &Context) -> &Token {
+    if context.pos >= context.tokens.len() {
+        context.tokens.last().unwrap() // Eof
+    } else {
+        &context.tokens[context.pos]
+    }
+}
+
+fn peek_next(context: &Context) -> &Token {
+    &context.tokens[context.pos + 1]
+}
+
+fn previous(context: &Context) -> &Token {
+    &context.tokens[context.pos - 1]
+}
+
+/// The span from the token at the position to the last token that was parsed,
+/// eg. of an operation that started there.
+fn span_since(context: &Context, start: usize) -> ExprSpan {
+    ExprSpan::new((context.tokens[start].span.0, previous(context).span.1))
+}
+
+fn match_token(context: &Context, kind: TokenKind) -> bool {
+    if is_at_end(context) {
+        return false;
+    }
+
+    peek(context).kind == kind
+}
+
+fn advance(context: &mut Context) -> &Token {
+    context.pos += 1;
+    previous(context)
+}
+
+fn consume(context: &mut Context, kind: TokenKind) -> Result<&Token, KalkError> {
+    if match_token(context, kind) {
+        return Ok(advance(context));
+    }
+
+    Err(KalkError::UnexpectedToken(peek(context).kind, Some(kind)))
+}
+
+fn is_at_end(context: &Context) -> bool {
+    context.pos >= context.tokens.len() || peek(context).kind == TokenKind::Eof
+}
+
+fn skip_newlines(context: &mut Context) {
+    while match_token(context, TokenKind::Newline) {
+        advance(context);
+    }
+}
+
+/// Integers that are too large to be exact as floats are kept as exact integers,
+/// eg. `9007199254740993`.
+fn parse_literal(value: &str) -> Result<Expr, KalkError> {
+    let number = string_to_num(value)?;
+    if number.abs() >= 9007199254740992f64 {
+        let digits = value.split('_').next().unwrap_or(value);
+        if let Some(integer) = BigInt::from_str_radix(digits, get_base(value)? as u32) {
+            return Ok(Expr::Integer(integer));
+        }
+    }
+
+    Ok(Expr::Literal(number, ExprSpan::default()))
+}
+
+fn string_to_num(value: &str) -> Result<f64, KalkError> {
+    let base = get_base(value)?;
+    if let Some(result) = crate::radix::parse_float_radix(value, base) {
+        Ok(result)
+    } else {
+        Err(KalkError::InvalidNumberLiteral(value.into()))
+    }
+}
+
+fn get_base(value: &str) -> Result<u8, KalkError> {
+    let underscore_pos = if let Some(i) = value.find('_') {
+        i
+    } else {
+        return Ok(10);
+    };
+
+    let subscript = value.chars().skip(underscore_pos + 1usize);
+    match crate::text_utils::parse_subscript(subscript) {
+        Some(base) if (2..=36).contains(&base) => Ok(base),
+        _ => Err(KalkError::UnrecognizedBase),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Identifier;
+    use crate::lexer::{Token, TokenKind::*};
+    use crate::test_helpers::*;
+    use wasm_bindgen_test::*;
+
+    fn parse_with_context(context: &mut Context, tokens: Vec<Token>) -> Result<Stmt, KalkError> {
+        context.tokens = tokens;
+        context.pos = 0;
+
+        let parsed = parse_stmt(context)?;
+        let symbol_table = &mut context.symbol_table;
+        analysis::analyse_stmt(symbol_table, parsed)
+    }
+
+    fn eval(context: &mut Context, input: &str) -> Result<Option<CalculationResult>, KalkError> {
+        #[cfg(feature = "rug")]
+        return super::eval(context, input, 63);
+
+        #[cfg(not(feature = "rug"))]
+        super::eval(context, input)
+    }
+
+    fn load(context: &mut Context, input: &str) -> Result<(), Vec<(usize, KalkError)>> {
+        #[cfg(feature = "rug")]
+        return super::load(context, input, 63);
+
+        #[cfg(not(feature = "rug"))]
+        super::load(context, input)
+    }
+
+    fn resume(context: &mut Context) -> Result<Option<CalculationResult>, KalkError> {
+        #[cfg(feature = "rug")]
+        return super::resume(context, 63);
+
+        #[cfg(not(feature = "rug"))]
+        super::resume(context)
+    }
+
+    fn parse(tokens: Vec<Token>) -> Result<Stmt, KalkError> {
+        let mut context = Context::new();
+        context.tokens = tokens;
+        context.pos = 0;
 
         let parsed = match parse_stmt(&mut context) {
             Ok(stmt) => stmt,
             Err(KalkError::WasStmt(stmt)) => stmt,
             Err(err) => return Err(err),
         };
-        let symbol_table = context.symbol_table.get_mut();
-        analysis::analyse_stmt(symbol_table, parsed)
+        let symbol_table = &mut context.symbol_table;
+        analysis::analyse_stmt(symbol_table, parsed)
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_var() {
+        // x
+        let tokens = vec![token(Identifier, "x"), token(Eof, "")];
+
+        assert_eq!(parse(tokens).unwrap(), Stmt::Expr(var("x")));
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_var_multiplication() {
+        let mut context = Context::new();
+        context.symbol_table.insert(Stmt::VarDecl(
+            Identifier::from_full_name("x"),
+            literal(1f64),
+        ));
+        context.symbol_table.insert(Stmt::VarDecl(
+            Identifier::from_full_name("y"),
+            literal(2f64),
+        ));
+
+        // xy²
+        let tokens = vec![
+            token(Identifier, "xy"),
+            token(Power, ""),
+            token(Literal, "2"),
+            token(Eof, ""),
+        ];
+
+        assert_eq!(
+            parse(tokens).unwrap(),
+            Stmt::Expr(binary(
+                var("x"),
+                Star,
+                binary(var("y"), Power, literal(2f64))
+            ))
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_binary() {
+        // 1+2*(3-4/5)
+        let tokens = vec![
+            token(Literal, "1"),
+            token(Plus, ""),
+            token(Literal, "2"),
+            token(Star, ""),
+            token(OpenParenthesis, ""),
+            token(Literal, "3"),
+            token(Minus, ""),
+            token(Literal, "4"),
+            token(Slash, ""),
+            token(Literal, "5"),
+            token(ClosedParenthesis, ""),
+            token(Eof, ""),
+        ];
+
+        assert_eq!(
+            parse(tokens).unwrap(),
+            Stmt::Expr(binary(
+                literal(1f64),
+                Plus,
+                binary(
+                    literal(2f64),
+                    Star,
+                    group(binary(
+                        literal(3f64),
+                        Minus,
+                        binary(literal(4f64), Slash, literal(5f64))
+                    ))
+                )
+            ))
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_pow() {
+        let tokens = vec![
+            token(Literal, "1"),
+            token(Star, ""),
+            token(Literal, "2"),
+            token(Power, ""),
+            token(Literal, "3"),
+            token(Power, ""),
+            token(Literal, "4"),
+            token(Plus, ""),
+            token(Literal, "5"),
+            token(Eof, ""),
+        ];
+
+        assert_eq!(
+            parse(tokens).unwrap(),
+            Stmt::Expr(binary(
+                binary(
+                    literal(1f64),
+                    Star,
+                    binary(
+                        literal(2f64),
+                        Power,
+                        binary(literal(3f64), Power, literal(4f64)),
+                    ),
+                ),
+                Plus,
+                literal(5f64)
+            )),
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_chained_comparison() {
+        // 0 < 1 <= 2 < 3
+        let tokens = vec![
+            token(Literal, "0"),
+            token(LessThan, ""),
+            token(Literal, "1"),
+            token(LessOrEquals, ""),
+            token(Literal, "2"),
+            token(LessThan, ""),
+            token(Literal, "3"),
+            token(Eof, ""),
+        ];
+
+        assert_eq!(
+            parse(tokens).unwrap(),
+            Stmt::Expr(binary(
+                binary(
+                    binary(literal(0f64), LessThan, literal(1f64)),
+                    And,
+                    binary(literal(1f64), LessOrEquals, literal(2f64)),
+                ),
+                And,
+                binary(literal(2f64), LessThan, literal(3f64)),
+            )),
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn test_pow_unary() {
+        let tokens = vec![
+            token(Literal, "10"),
+            token(Power, ""),
+            token(Minus, ""),
+            token(Literal, "1"),
+            token(Eof, ""),
+        ];
+
+        assert_eq!(
+            parse(tokens).unwrap(),
+            Stmt::Expr(binary(literal(10f64), Power, unary(Minus, literal(1f64)))),
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_percent() {
+        let tokens = vec![
+            token(Literal, "1"),
+            token(Percent, ""),
+            token(Literal, "1"),
+            token(Plus, ""),
+            token(Literal, "5"),
+            token(Percent, ""),
+            token(Eof, ""),
+        ];
+
+        assert_eq!(
+            parse(tokens).unwrap(),
+            Stmt::Expr(binary(
+                binary(literal(1f64), Percent, literal(1f64)),
+                Plus,
+                unary(Percent, literal(5f64))
+            ))
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_unit() {
+        let tokens = vec![token(Literal, "1"), token(Identifier, "a")];
+
+        let mut context = Context::new();
+        context
+            .symbol_table
+            .insert(unit_decl("a", "b", var(super::DECL_UNIT)));
+
+        assert_eq!(
+            parse_with_context(&mut context, tokens).unwrap(),
+            Stmt::Expr(unit("a", literal(1f64)))
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_var_decl() {
+        let tokens = vec![
+            token(Identifier, "x"),
+            token(Equals, ""),
+            token(Literal, "1"),
+            token(Plus, ""),
+            token(Literal, "2"),
+            token(Eof, ""),
+        ];
+
+        assert_eq!(
+            parse(tokens).unwrap(),
+            Stmt::VarDecl(
+                Identifier::from_full_name("x"),
+                binary(literal(1f64), Plus, literal(2f64))
+            )
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_fn_decl() {
+        let tokens = vec![
+            token(Identifier, "f"),
+            token(OpenParenthesis, ""),
+            token(Identifier, "x"),
+            token(ClosedParenthesis, ""),
+            token(Equals, ""),
+            token(Literal, "1"),
+            token(Plus, ""),
+            token(Identifier, "x"),
+            token(Eof, ""),
+        ];
+
+        assert_eq!(
+            parse(tokens).unwrap(),
+            Stmt::FnDecl(
+                Identifier::from_full_name("f"),
+                vec![String::from("f-x")],
+                binary(literal(1f64), Plus, param_var("f", "x"))
+            )
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_let() {
+        let tokens = vec![
+            token(LetKeyword, ""),
+            token(Identifier, "t"),
+            token(Equals, ""),
+            token(Literal, "2"),
+            token(InKeyword, ""),
+            token(Identifier, "t"),
+            token(Plus, ""),
+            token(Identifier, "t"),
+            token(Eof, ""),
+        ];
+
+        assert_eq!(
+            parse(tokens).unwrap(),
+            Stmt::Expr(Box::new(Expr::Let(
+                Identifier::parameter_from_name("t", "let1"),
+                literal(2f64),
+                binary(param_var("let1", "t"), Plus, param_var("let1", "t"))
+            )))
+        );
+    }
+
+    #[test]
+    fn test_contextual_keywords() {
+        let mut context = Context::new();
+        let value =
+            |context: &mut Context, input: &str| eval(context, input).unwrap().unwrap().to_f64();
+
+        // Names that were used before they became keywords still work
+        eval(&mut context, "rem = 5").unwrap();
+        assert_eq!(value(&mut context, "rem + 1"), 6f64);
+        assert_eq!(value(&mut context, "2rem"), 10f64);
+        assert_eq!(value(&mut context, "rem rem 3"), 2f64);
+        eval(&mut context, "rem += 1").unwrap();
+        assert_eq!(value(&mut context, "rem"), 6f64);
+        eval(&mut context, "y = 2rem\nz = 1").unwrap();
+        assert_eq!(value(&mut context, "y"), 12f64);
+        eval(&mut context, "in = 3; with = 4; then = 5; else = 6").unwrap();
+        assert_eq!(value(&mut context, "let x = in in x + with"), 7f64);
+        assert_eq!(value(&mut context, "if false then then else else"), 6f64);
+        eval(&mut context, "const = 7; alias = 8").unwrap();
+        assert_eq!(value(&mut context, "const + alias"), 15f64);
+
+        // The keywords still work where they are expected
+        assert_eq!(value(&mut context, "7 rem 3"), 1f64);
+        assert_eq!(value(&mut context, "7 div 2"), 3f64);
+        assert_eq!(value(&mut context, "let rem = 2 in rem^2"), 4f64);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_not() {
+        // not 1 > 2 and true
+        let tokens = vec![
+            token(Not, ""),
+            token(Literal, "1"),
+            token(GreaterThan, ""),
+            token(Literal, "2"),
+            token(And, ""),
+            token(True, ""),
+            token(Eof, ""),
+        ];
+
+        assert_eq!(
+            parse(tokens).unwrap(),
+            Stmt::Expr(binary(
+                unary(Not, binary(literal(1f64), GreaterThan, literal(2f64))),
+                And,
+                Box::new(Expr::Boolean(true)),
+            )),
+        );
+    }
+
+    #[test]
+    fn test_if() {
+        let tokens = vec![
+            token(IfKeyword, ""),
+            token(Identifier, "x"),
+            token(GreaterThan, ""),
+            token(Literal, "0"),
+            token(ThenKeyword, ""),
+            token(Identifier, "x"),
+            token(ElseKeyword, ""),
+            token(Minus, ""),
+            token(Identifier, "x"),
+            token(Eof, ""),
+        ];
+
+        assert_eq!(
+            parse(tokens).unwrap(),
+            Stmt::Expr(Box::new(Expr::If(
+                binary(var("x"), GreaterThan, literal(0f64)),
+                var("x"),
+                unary(Minus, var("x"))
+            )))
+        );
+
+        let mut context = Context::new();
+        assert_eq!(
+            eval(&mut context, "if 1 then 2 else 3").err(),
+            Some(KalkError::UnexpectedType(
+                String::from("number"),
+                vec![String::from("boolean")]
+            ))
+        );
+    }
+
+    #[test]
+    fn test_max_recursion_depth() {
+        let input = "f(x) = {\n    f(x - 1) if x >= 1\n    x otherwise\n}";
+        let mut context = Context::new();
+        eval(&mut context, input).unwrap();
+        assert!(eval(&mut context, "f(99)").is_ok());
+        assert_eq!(
+            eval(&mut context, "f(100)").err(),
+            Some(KalkError::MaxRecursionDepthExceeded)
+        );
+
+        let mut context = Context::new().set_max_recursion_depth(16);
+        eval(&mut context, input).unwrap();
+        assert!(eval(&mut context, "f(15)").is_ok());
+        assert_eq!(
+            eval(&mut context, "f(16)").err(),
+            Some(KalkError::MaxRecursionDepthExceeded)
+        );
+    }
+
+    #[test]
+    fn test_const_decl() {
+        let mut context = Context::new();
+        assert!(eval(&mut context, "const g = 9.81").is_ok());
+        assert_eq!(
+            eval(&mut context, "g = 3").err(),
+            Some(KalkError::UnableToOverrideConstant(String::from("g")))
+        );
+        assert_eq!(
+            eval(&mut context, "const g = 3").err(),
+            Some(KalkError::UnableToOverrideConstant(String::from("g")))
+        );
+        assert_eq!(
+            eval(&mut context, "const pi = 3").err(),
+            Some(KalkError::UnableToOverrideConstant(String::from("pi")))
+        );
+        assert!(
+            eval(&mut context, "2g")
+                .unwrap()
+                .unwrap()
+                .get_value()
+                .to_f64()
+                == 19.62
+        );
+
+        // A declaration that fails doesn't make the name a constant
+        assert!(eval(&mut context, "const h = y").is_err());
+        assert!(eval(&mut context, "const m = 1; 2 +").is_err());
+        assert!(eval(&mut context, "h = 2; m = 3").is_ok());
+        assert_eq!(
+            eval(&mut context, "const k = 1; k = 2").err(),
+            Some(KalkError::UnableToOverrideConstant(String::from("k")))
+        );
+        assert!(eval(&mut context, "const k = 5").is_ok());
+        assert_eq!(
+            eval(&mut context, "k = 2").err(),
+            Some(KalkError::UnableToOverrideConstant(String::from("k")))
+        );
+    }
+
+    #[test]
+    fn test_table() {
+        let mut context = Context::new();
+        eval(&mut context, "f(x) = x^2").unwrap();
+        eval(&mut context, "x = 7").unwrap();
+
+        #[cfg(not(feature = "rug"))]
+        let rows = super::table(&mut context, "f", 0f64, 1f64, 0.25, 1).unwrap();
+        #[cfg(feature = "rug")]
+        let rows = super::table(&mut context, "f", 0f64, 1f64, 0.25, 1, 63).unwrap();
+        let rows: Vec<(f64, f64)> = rows.iter().map(|(x, y)| (x.to_f64(), y.to_f64())).collect();
+        assert_eq!(
+            rows,
+            vec![
+                (0.0, 0.0),
+                (0.25, 0.0625),
+                (0.5, 0.25),
+                (0.75, 0.5625),
+                (1.0, 1.0)
+            ]
+        );
+
+        #[cfg(not(feature = "rug"))]
+        let rows = super::table(&mut context, "2x + 1", 3f64, 1f64, -1f64, 1).unwrap();
+        #[cfg(feature = "rug")]
+        let rows = super::table(&mut context, "2x + 1", 3f64, 1f64, -1f64, 1, 63).unwrap();
+        let values: Vec<f64> = rows.iter().map(|(_, y)| y.to_f64()).collect();
+        assert_eq!(values, vec![7.0, 5.0, 3.0]);
+
+        // x is restored afterwards
+        assert_eq!(eval(&mut context, "x").unwrap().unwrap().to_f64(), 7.0);
+
+        #[cfg(not(feature = "rug"))]
+        assert!(super::table(&mut context, "f", 0f64, 1f64, -1f64, 1).is_err());
+
+        // The same rows, in the same order, when split between threads
+        #[cfg(not(feature = "rug"))]
+        let rows = super::table(&mut context, "f", 0f64, 10f64, 0.5, 4).unwrap();
+        #[cfg(feature = "rug")]
+        let rows = super::table(&mut context, "f", 0f64, 10f64, 0.5, 4, 63).unwrap();
+        let rows: Vec<(f64, f64)> = rows.iter().map(|(x, y)| (x.to_f64(), y.to_f64())).collect();
+        let expected: Vec<(f64, f64)> = (0..=20)
+            .map(|i| (i as f64 * 0.5, (i as f64 * 0.5).powi(2)))
+            .collect();
+        assert_eq!(rows, expected);
+        assert_eq!(eval(&mut context, "x").unwrap().unwrap().to_f64(), 7.0);
+
+        // The rows are evaluated with the settings of the context
+        let mut context = Context::new().set_domain_policy(DomainPolicy::Error);
+        for threads in [1, 2] {
+            #[cfg(not(feature = "rug"))]
+            let result = super::table(&mut context, "sqrt(x)", -1f64, 1f64, 1f64, threads);
+            #[cfg(feature = "rug")]
+            let result = super::table(&mut context, "sqrt(x)", -1f64, 1f64, 1f64, threads, 63);
+            assert!(matches!(result, Err(KalkError::NotReal(_))));
+        }
+    }
+
+    #[test]
+    fn test_compile() {
+        let mut context = Context::new();
+        eval(&mut context, "x = 7").unwrap();
+        eval(&mut context, "rate = 0").unwrap();
+
+        #[cfg(not(feature = "rug"))]
+        let mut compiled = context.compile("x^2 + rate").unwrap();
+        #[cfg(feature = "rug")]
+        let mut compiled = context.compile("x^2 + rate", 63).unwrap();
+        let values: Vec<f64> = [1f64, 2f64, 3f64]
+            .iter()
+            .map(|x| {
+                compiled
+                    .eval_with(&[("x", *x), ("rate", 0.5)])
+                    .unwrap()
+                    .unwrap()
+                    .to_f64()
+            })
+            .collect();
+        assert_eq!(values, vec![1.5, 4.5, 9.5]);
+
+        // The same variable twice gives the last value
+        assert_eq!(
+            compiled
+                .eval_with(&[("x", 1f64), ("x", 2f64)])
+                .unwrap()
+                .unwrap()
+                .to_f64(),
+            4f64
+        );
+        assert_eq!(eval(&mut context, "x").unwrap().unwrap().to_f64(), 7.0);
+        assert_eq!(eval(&mut context, "rate").unwrap().unwrap().to_f64(), 0.0);
+
+        #[cfg(not(feature = "rug"))]
+        let mut compiled = context.compile("y + 1").unwrap();
+        #[cfg(feature = "rug")]
+        let mut compiled = context.compile("y + 1", 63).unwrap();
+        assert!(matches!(
+            compiled.eval_with(&[]),
+            Err(KalkError::UndefinedVar(_, _))
+        ));
+
+        #[cfg(not(feature = "rug"))]
+        let result = context.compile("2 +");
+        #[cfg(feature = "rug")]
+        let result = context.compile("2 +", 63);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        let mut context = Context::new();
+        assert_eq!(
+            eval(&mut context, "total += 2").err(),
+            Some(KalkError::UndefinedVar(String::from("total"), Vec::new()))
+        );
+        assert_eq!(
+            eval(&mut context, "pi *= 2").err(),
+            Some(KalkError::UnableToOverrideConstant(String::from("pi")))
+        );
+
+        // The previous value is evaluated, instead of being nested in the new one
+        eval(&mut context, "total = 0").unwrap();
+        for _ in 0..5000 {
+            eval(&mut context, "total += 2").unwrap();
+        }
+        assert_eq!(
+            eval(&mut context, "total").unwrap().unwrap().to_f64(),
+            10000f64
+        );
+        assert_eq!(
+            eval(&mut context, "total -= 1; total *= 2; total")
+                .unwrap()
+                .unwrap()
+                .to_f64(),
+            19998f64
+        );
+
+        // Vectors and matrices stay vectors and matrices
+        eval(&mut context, "v = [1, 2]; v += 1; a = [1, 2; 3, 4]; a *= 2").unwrap();
+        eval(&mut context, "w = [1 km, 2 km]; w += 1 km").unwrap();
+        for (input, output) in [
+            ("v", "(2, 3)"),
+            ("a", "⎡ 2  4 ⎤\n⎣ 6  8 ⎦"),
+            ("w", "(2 km, 3 km)"),
+        ] {
+            let result = eval(&mut context, input).unwrap().unwrap();
+            assert_eq!(result.to_string_pretty(), output, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_suppressed_output() {
+        let mut context = Context::new();
+        assert!(eval(&mut context, "a = 2; b = 3; a + b;")
+            .unwrap()
+            .is_none());
+        assert_eq!(eval(&mut context, "ans").unwrap().unwrap().to_f64(), 5f64);
+        assert_eq!(
+            eval(&mut context, "a = 4; a * b")
+                .unwrap()
+                .unwrap()
+                .to_f64(),
+            12f64
+        );
+    }
+
+    #[test]
+    fn test_result_history() {
+        let mut context = Context::new();
+        assert!(eval(&mut context, "_").is_err());
+        eval(&mut context, "2").unwrap();
+        eval(&mut context, "3").unwrap();
+        eval(&mut context, "x = 10").unwrap();
+        assert_eq!(eval(&mut context, "_ * 2").unwrap().unwrap().to_f64(), 6f64);
+        assert_eq!(
+            eval(&mut context, "_2 + _").unwrap().unwrap().to_f64(),
+            9f64
+        );
+        assert_eq!(eval(&mut context, "_4").unwrap().unwrap().to_f64(), 2f64);
+        assert_eq!(
+            eval(&mut context, "_9").err(),
+            Some(KalkError::UndefinedVar(String::from("_9"), Vec::new()))
+        );
+
+        // Integers that are too large to be exact as floats stay exact
+        let factorial = "15 511 210 043 330 985 984 000 000";
+        eval(&mut context, "25!").unwrap();
+        for input in ["ans", "_", "_2 + 0"] {
+            let result = eval(&mut context, input).unwrap().unwrap();
+            assert_eq!(result.to_string_pretty(), factorial, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_cache() {
+        let mut context = Context::new();
+        eval(&mut context, "x = 2").unwrap();
+        assert_eq!(
+            eval(&mut context, "2x + 1").unwrap().unwrap().to_f64(),
+            5f64
+        );
+        assert_eq!(context.eval_cache.len(), 1);
+        eval(&mut context, "7").unwrap();
+
+        // Equivalent inputs give the cached result, which still becomes the last result
+        assert_eq!(
+            eval(&mut context, "(2 x)+1").unwrap().unwrap().to_f64(),
+            5f64
+        );
+        assert_eq!(context.eval_cache.len(), 2);
+        assert_eq!(eval(&mut context, "ans").unwrap().unwrap().to_f64(), 5f64);
+        assert_eq!(eval(&mut context, "_3").unwrap().unwrap().to_f64(), 7f64);
+
+        // Declarations and settings may change the results
+        eval(&mut context, "x = 3").unwrap();
+        assert!(context.eval_cache.is_empty());
+        assert_eq!(
+            eval(&mut context, "2x + 1").unwrap().unwrap().to_f64(),
+            7f64
+        );
+        for input in ["x = 4", "h(t) = t + x"] {
+            #[cfg(not(feature = "rug"))]
+            let mut compiled = context.compile(input).unwrap();
+            #[cfg(feature = "rug")]
+            let mut compiled = context.compile(input, 63).unwrap();
+            compiled.eval_with(&[]).unwrap();
+            assert_eq!(
+                eval(&mut context, "2x + 1").unwrap().unwrap().to_f64(),
+                9f64,
+                "{}",
+                input
+            );
+        }
+
+        eval(&mut context, "sin(90)").unwrap();
+        context = context.set_angle_unit("deg");
+        assert_eq!(
+            eval(&mut context, "sin(90)").unwrap().unwrap().to_f64(),
+            1f64
+        );
+
+        // Results that may be different the next time aren't cached
+        eval(&mut context, "f(y) = y + rand()").unwrap();
+        eval(&mut context, "g(y) = f(y) + 1").unwrap();
+        for input in ["rand()", "g(2)", "ans + 1", "_", "1 + 2;"] {
+            eval(&mut context, input).unwrap();
+            assert!(context.eval_cache.is_empty(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_physics_constants() {
+        let mut context = Context::new();
+        assert!(eval(&mut context, "c").is_err());
+        assert!(eval(&mut context, "h = 2").is_ok());
+
+        context.load_physics_constants();
+        assert_eq!(
+            eval(&mut context, "c").unwrap().unwrap().to_f64(),
+            299792458f64
+        );
+        assert_eq!(
+            eval(&mut context, "h").unwrap().unwrap().to_f64(),
+            6.62607015e-34
+        );
+        assert!(
+            eval(&mut context, "k_B N_A - R")
+                .unwrap()
+                .unwrap()
+                .to_f64()
+                .abs()
+                < 1e-8
+        );
+        assert_eq!(
+            eval(&mut context, "c = 3").err(),
+            Some(KalkError::UnableToOverrideConstant(String::from("c")))
+        );
+
+        // Loading them again should not fail
+        context.load_physics_constants();
+    }
+
+    #[test]
+    fn test_exchange_rates() {
+        let mut context = Context::new();
+        let mut rates = HashMap::new();
+        rates.insert(String::from("usd"), 1f64);
+        rates.insert(String::from("eur"), 0.5f64);
+        context.set_exchange_rates(rates);
+
+        let result = eval(&mut context, "100 usd to eur").unwrap().unwrap();
+        assert_eq!(result.to_f64(), 50f64);
+        assert_eq!(result.get_value().get_unit(), Some(&String::from("eur")));
+        assert_eq!(
+            eval(&mut context, "(3 + 2) eur to usd")
+                .unwrap()
+                .unwrap()
+                .to_f64(),
+            10f64
+        );
+
+        let mut rates = HashMap::new();
+        rates.insert(String::from("sek"), 10f64);
+        context.set_exchange_rates(rates);
+        assert!(eval(&mut context, "100 usd to eur").is_err());
+    }
+
+    #[test]
+    fn test_durations() {
+        let mut context = Context::new();
+        let result = eval(&mut context, "3h + 45min").unwrap().unwrap();
+        assert_eq!(result.to_f64(), 3.75);
+        assert_eq!(result.to_string_pretty(), "3h 45min");
+
+        let result = eval(&mut context, "1.5 days to hours").unwrap().unwrap();
+        assert_eq!(result.to_f64(), 36f64);
+        assert_eq!(result.to_string_pretty(), "36 hours");
+
+        let before = eval(&mut context, "now()").unwrap().unwrap().to_f64();
+        let result = eval(&mut context, "now() + 90min").unwrap().unwrap();
+        assert!(result.to_f64() - before >= 5400f64);
+        assert!(result.to_string_pretty().ends_with(" UTC"));
+
+        // Variables and parameters with the same names as units of time
+        assert!(eval(&mut context, "f(h) = 2h").unwrap().is_none());
+        assert_eq!(eval(&mut context, "f(3)").unwrap().unwrap().to_f64(), 6f64);
+        assert!(eval(&mut context, "h = 4").unwrap().is_none());
+        assert_eq!(eval(&mut context, "3h").unwrap().unwrap().to_f64(), 12f64);
+        assert_eq!(
+            eval(&mut context, "min(5, 2)").unwrap().unwrap().to_f64(),
+            2f64
+        );
+
+        // Functions with the same names as units of time
+        let result = eval(&mut context, "2 sec(0)").unwrap().unwrap();
+        assert_eq!(result.to_f64(), 2f64);
+        assert_eq!(result.get_unit(), None);
+        let result = eval(&mut context, "2 min(3, 4)").unwrap().unwrap();
+        assert_eq!(result.to_f64(), 6f64);
+        assert_eq!(result.get_unit(), None);
+        let result = eval(&mut context, "2 min").unwrap().unwrap();
+        assert_eq!(result.get_unit(), Some(String::from("min")));
+    }
+
+    #[test]
+    #[cfg(feature = "rug")]
+    fn test_auto_precision() {
+        let mut context = Context::new().set_auto_precision(true);
+
+        // Needs more than 64 bits to not lose the 1
+        let result = super::eval(&mut context, "(10^20 + 1) - 10^20", 1024).unwrap();
+        assert_eq!(result.unwrap().to_f64(), 1f64);
+
+        // Side effects only happen once, even though it is evaluated at several precisions
+        super::eval(&mut context, "x = 1; x += 2", 1024).unwrap();
+        super::eval(&mut context, "x += 1", 1024).unwrap();
+        let result = super::eval(&mut context, "x", 1024).unwrap();
+        assert_eq!(result.unwrap().to_f64(), 4f64);
+        super::eval(&mut context, "y = 0", 1024).unwrap();
+        let mut compiled = context.compile("y += 1", 1024).unwrap();
+        compiled.eval_with(&[]).unwrap();
+        compiled.eval_with(&[]).unwrap();
+        let result = super::eval(&mut context, "y", 1024).unwrap();
+        assert_eq!(result.unwrap().to_f64(), 2f64);
+
+        let values = |context: &mut Context| {
+            (0..3)
+                .map(|_| {
+                    let result = super::eval(context, "rand()", 1024).unwrap();
+                    result.unwrap().to_f64()
+                })
+                .collect::<Vec<f64>>()
+        };
+        let random_values = values(&mut Context::new().set_seed(42));
+        let mut context = Context::new().set_auto_precision(true).set_seed(42);
+        assert_eq!(values(&mut context), random_values);
+        let result = super::eval(&mut context, "_2", 1024).unwrap();
+        assert_eq!(result.unwrap().to_f64(), random_values[1]);
+    }
+
+    #[test]
+    fn test_simplify() {
+        let mut context = Context::new();
+        assert_eq!(simplify(&mut context, "2x + 3x + 1*y^1").unwrap(), "5x + y");
+        assert!(simplify(&mut context, "a = 2").is_err());
+
+        // As a function, the result is only an expression if its variables aren't defined
+        let simplified =
+            |context: &mut Context, input: &str| eval(context, input).unwrap().unwrap().to_string();
+        assert_eq!(simplified(&mut context, "simplify(2x + 3x - x*1)"), "4x");
+        assert_eq!(simplified(&mut context, "simplify(2*3 + z)"), "z + 6");
+        eval(&mut context, "x = 2").unwrap();
+        assert_eq!(simplified(&mut context, "simplify(2x + 3x)"), "10");
+        eval(&mut context, "f(y) = simplify(y*1 + 0)").unwrap();
+        assert_eq!(simplified(&mut context, "f(3)"), "3");
+        assert!(matches!(
+            eval(&mut context, "simplify(1, 2)"),
+            Err(KalkError::IncorrectAmountOfArguments(1, _, 2))
+        ));
+    }
+
+    #[test]
+    fn test_derive() {
+        let mut context = Context::new();
+        let derived =
+            |context: &mut Context, input: &str| eval(context, input).unwrap().unwrap().to_string();
+        assert_eq!(derived(&mut context, "derive(x^2, x)"), "2x");
+        assert_eq!(derived(&mut context, "derive(x ln(x), x)"), "ln(x) + 1");
+        assert_eq!(derived(&mut context, "derive(x/x, x)"), "0");
+        assert_eq!(derive(&mut context, "x ln(x) - x", "x").unwrap(), "ln(x)");
+
+        // The derivative is evaluated when the variable is defined, eg. inside a function
+        eval(&mut context, "f(x) = derive(x^3 + 2x, x)").unwrap();
+        assert_eq!(derived(&mut context, "f(2)"), "14");
+        eval(&mut context, "x = 3").unwrap();
+        assert_eq!(derived(&mut context, "derive(x^2, x)"), "6");
+        assert!(matches!(
+            eval(&mut context, "derive(x^2, 2)"),
+            Err(KalkError::Expected(_))
+        ));
+    }
+
+    #[test]
+    fn test_taylor() {
+        let mut context = Context::new();
+        let taylor = |context: &mut Context, function: &str, x0: f64, degree: usize| {
+            #[cfg(not(feature = "rug"))]
+            let polynomial = super::taylor(context, function, x0, degree);
+            #[cfg(feature = "rug")]
+            let polynomial = super::taylor(context, function, x0, degree, 63);
+
+            polynomial.unwrap()
+        };
+        assert_eq!(
+            taylor(&mut context, "sin(x)", 0f64, 5),
+            "x - x^3 / 6 + x^5 / 120"
+        );
+        assert_eq!(
+            taylor(&mut context, "ln(x)", 1f64, 3),
+            "(x - 1) - (x - 1)^2 / 2 + (x - 1)^3 / 3"
+        );
+        assert_eq!(
+            taylor(&mut context, "1/x", -1f64, 2),
+            "-1 - (x + 1) - (x + 1)^2"
+        );
+        eval(&mut context, "f(x) = x^3 + 2x").unwrap();
+        assert_eq!(taylor(&mut context, "f", 0f64, 5), "2x + x^3");
+
+        eval(&mut context, "p(x) = taylor(exp(x), 0, 12)").unwrap();
+        assert!(cmp(
+            eval(&mut context, "p(0.5)").unwrap().unwrap().to_f64(),
+            0.5f64.exp()
+        ));
+        assert!(eval(&mut context, "taylor(e^x, 0, -1)").is_err());
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let interrupt_flag = Arc::new(AtomicBool::new(true));
+        let mut context = Context::new().set_interrupt_flag(interrupt_flag.clone());
+        assert_eq!(
+            eval(&mut context, "2 + 3").err(),
+            Some(KalkError::Interrupted)
+        );
+        assert!(!interrupt_flag.load(std::sync::atomic::Ordering::Relaxed));
+        assert!(eval(&mut context, "2 + 3").is_ok());
+    }
+
+    #[test]
+    fn test_resume() {
+        let mut context = Context::new().set_interrupt_flag(Arc::new(AtomicBool::new(false)));
+        assert_eq!(resume(&mut context).err(), Some(KalkError::NothingToResume));
+
+        // As if the loop was interrupted when n was 6
+        context.checkpoint = Some(Checkpoint {
+            statements: super::parse(&mut context, "x = 2; x * sum(n = 1, 10, n)").unwrap(),
+            loop_checkpoint: LoopCheckpoint {
+                loop_index: 0,
+                next_n: 6,
+                partial_result: KalkValue::from(15f64),
+            },
+        });
+        assert!(context.has_checkpoint());
+        assert_eq!(resume(&mut context).unwrap().unwrap().to_f64(), 110f64);
+        assert!(!context.has_checkpoint());
+    }
+
+    #[test]
+    fn test_max_denominator() {
+        let estimate = |context: &mut Context, input: &str| {
+            eval(context, input).unwrap().unwrap().estimate_js()
+        };
+        let mut context = Context::new();
+        assert_eq!(estimate(&mut context, "1/317"), None);
+        assert_eq!(estimate(&mut context, "5/17"), Some(String::from("5/17")));
+
+        let mut context = Context::new().set_max_denominator(1000);
+        assert_eq!(estimate(&mut context, "1/317"), Some(String::from("1/317")));
     }
 
     #[test]
-    #[wasm_bindgen_test]
-    fn test_var() {
-        // x
-        let tokens = vec![token(Identifier, "x"), token(Eof, "")];
+    fn test_estimate_options() {
+        let mut context = Context::new();
+        let result = eval(&mut context, "0.9999991").unwrap().unwrap();
+        assert_eq!(result.estimate_js(), Some(String::from("1")));
+
+        let mut context = Context::new().set_estimate(false);
+        let result = eval(&mut context, "0.9999991").unwrap().unwrap();
+        assert_eq!(result.estimate_js(), None);
+        assert_eq!(result.to_string_pretty(), "0.9999991");
+        let result = eval(&mut context, "pi").unwrap().unwrap();
+        assert_eq!(result.estimate_js(), None);
+
+        let mut context = Context::new().set_round_tolerance(Some(1e-9));
+        let estimate = |context: &mut Context, input: &str| {
+            eval(context, input).unwrap().unwrap().estimate_js()
+        };
+        assert_eq!(estimate(&mut context, "0.9999991"), None);
+        assert_eq!(
+            estimate(&mut context, "2.9999999999"),
+            Some(String::from("3"))
+        );
+        assert_eq!(estimate(&mut context, "0.5"), Some(String::from("1/2")));
 
-        assert_eq!(parse(tokens).unwrap(), Stmt::Expr(var("x")));
+        let mut context = Context::new().set_round_tolerance(Some(0f64));
+        assert_eq!(estimate(&mut context, "2.9999999999"), None);
     }
 
     #[test]
-    #[wasm_bindgen_test]
-    fn test_var_multiplication() {
+    fn test_side_by_side_estimate() {
+        let mut context = Context::new().set_side_by_side_estimate(true);
+        let pretty = |context: &mut Context, input: &str| {
+            eval(context, input).unwrap().unwrap().to_string_pretty()
+        };
+        assert_eq!(pretty(&mut context, "1/3"), "≈ 0.3333333333… (1/3)");
+        assert_eq!(pretty(&mut context, "pi"), "≈ 3.1415926536… (π)");
+        assert_eq!(pretty(&mut context, "0.5"), "0.5 (1/2)");
+        assert_eq!(pretty(&mut context, "0.3333333333"), "0.3333333333 (≈ 1/3)");
+        assert_eq!(pretty(&mut context, "0.9999991"), "0.9999991 (≈ 1)");
+        assert_eq!(pretty(&mut context, "2.5"), "2.5 (2 + 1/2)");
+        assert_eq!(pretty(&mut context, "3"), "3");
+        assert_eq!(pretty(&mut context, "1.23"), "1.23");
+
+        let result = eval(&mut context, "2/3").unwrap().unwrap();
+        assert_eq!(result.get_decimal(), "0.6666666667");
+        assert_eq!(result.estimate_js(), Some(String::from("2/3")));
+    }
+
+    #[test]
+    fn test_estimate_constants() {
         let mut context = Context::new();
-        context.symbol_table.get_mut().insert(Stmt::VarDecl(
-            Identifier::from_full_name("x"),
-            literal(1f64),
+        context.register_estimate_constant(0.915965594177219, "G");
+        let result = eval(&mut context, "0.915965594177219 * 3")
+            .unwrap()
+            .unwrap();
+        assert!(result.to_string_pretty().ends_with("≈ 3G"));
+        assert_eq!(result.estimate_js(), Some(String::from("3G")));
+    }
+
+    #[test]
+    fn test_completions() {
+        use crate::completion::{CompletionItem, CompletionKind};
+
+        let mut context = Context::new();
+        eval(
+            &mut context,
+            "sinc(x) = sin(x) / x; sigma = 2; unit km = 1000m",
+        )
+        .unwrap();
+        assert_eq!(
+            context.get_completions("si"),
+            vec![
+                CompletionItem::new("sigma", CompletionKind::Variable, None),
+                CompletionItem::new("simplify", CompletionKind::Function, Some(1)),
+                CompletionItem::new("sin", CompletionKind::Function, Some(1)),
+                CompletionItem::new("sinc", CompletionKind::Function, Some(1)),
+                CompletionItem::new("sinh", CompletionKind::Function, Some(1)),
+            ]
+        );
+        assert_eq!(
+            context.get_completions("k"),
+            vec![CompletionItem::new("km", CompletionKind::Unit, None)]
+        );
+        assert!(context.get_completions("").contains(&CompletionItem::new(
+            "pi",
+            CompletionKind::Constant,
+            None
+        )));
+    }
+
+    #[test]
+    fn test_highlight() {
+        use crate::lexer::TokenCategory;
+
+        let mut context = Context::new();
+        eval(&mut context, "unit km = 1000m").unwrap();
+        let categories: Vec<TokenCategory> = context
+            .highlight("3km + 2deg")
+            .into_iter()
+            .map(|(_, category)| category)
+            .collect();
+        assert_eq!(
+            categories,
+            vec![
+                TokenCategory::Number,
+                TokenCategory::Unit,
+                TokenCategory::Operator,
+                TokenCategory::Number,
+                TokenCategory::Unit,
+            ]
+        );
+        assert_eq!(context.js_highlight("2km"), vec![0, 1, 0, 1, 3, 4]);
+    }
+
+    #[test]
+    fn test_vectorized_conversions() {
+        let mut context = Context::new();
+        eval(&mut context, "unit cm = 100m").unwrap();
+        let mut eval_pretty = |input: &str| {
+            eval(&mut context, input)
+                .unwrap()
+                .unwrap()
+                .to_string_pretty()
+        };
+        assert_eq!(
+            eval_pretty("(1m, 30cm, 2m) to cm"),
+            "(100 cm, 30 cm, 200 cm)"
+        );
+        assert_eq!(eval_pretty("(90min, 2h) to min"), "(90 min, 120 min)");
+        assert_eq!(eval_pretty("255 to hex"), "255\nff₁₆");
+        assert_eq!(eval_pretty("(2, 5) to bin"), "(2, 5)\n(10₂, 101₂)");
+        assert_eq!(eval_pretty("0xff to dec"), "255");
+    }
+
+    #[test]
+    fn test_load() {
+        let mut context = Context::new();
+        let prelude = "f(x) = 2x\n\ng(x) = {\n    x if x > 0\n    0 otherwise\n}\na = f(3)";
+        assert!(load(&mut context, prelude).is_ok());
+        assert_eq!(
+            eval(&mut context, "g(-1) + a").unwrap().unwrap().to_f64(),
+            6f64
+        );
+
+        let mut context = Context::new();
+        let result = load(&mut context, "f(x) = x\n\nf(2, 3)\nc = 1");
+        assert!(matches!(
+            result.as_ref().map_err(Vec::as_slice),
+            Err([(3, KalkError::IncorrectAmountOfArguments(_, _, _))])
         ));
-        context.symbol_table.get_mut().insert(Stmt::VarDecl(
-            Identifier::from_full_name("y"),
-            literal(2f64),
+
+        let mut context = Context::new();
+        let result = load(&mut context, "a = 1; b = 2\nc = (3");
+        assert!(matches!(
+            result.as_ref().map_err(Vec::as_slice),
+            Err([(2, _)])
         ));
 
-        // xy²
-        let tokens = vec![
-            token(Identifier, "xy"),
-            token(Power, ""),
-            token(Literal, "2"),
-            token(Eof, ""),
-        ];
+        let mut context = Context::new();
+        let result = load(&mut context, "a = (1 +)\nb = 2\nc = )\nd = 4");
+        assert!(matches!(
+            result.as_ref().map_err(Vec::as_slice),
+            Err([(1, _), (3, _)])
+        ));
+    }
+
+    #[test]
+    fn test_parse_all() {
+        let mut context = Context::new();
+        let errors = parse_all(&mut context, "1 +; x = 2\n(3 *\n 4))\ny = )").unwrap_err();
+        let spans: Vec<lexer::Span> = errors.iter().map(|error| error.span).collect();
+        assert_eq!(spans, vec![(0, 4), (19, 20), (21, 26)]);
+        assert_eq!(eval(&mut context, "x").unwrap().unwrap().to_f64(), 2f64);
+
+        let mut context = Context::new();
+        assert_eq!(
+            parse_all(&mut context, "1 + 2; 3").map(|stmts| stmts.len()),
+            Ok(2)
+        );
+    }
+
+    #[test]
+    fn test_interval_mode() {
+        let mut context = Context::new().set_interval_mode(true);
+        let pretty = |context: &mut Context, input: &str| {
+            eval(context, input).unwrap().unwrap().to_string_pretty()
+        };
+        assert_eq!(pretty(&mut context, "0.1 + 0.2"), "0.3");
+        assert_eq!(pretty(&mut context, "sqrt(2)^2"), "2");
+        assert!(pretty(&mut context, "(10^16 + 1) - 10^16").starts_with('['));
+        assert!(pretty(&mut context, "(10^12 + 0.1) - 10^12").ends_with('…'));
+        assert_eq!(pretty(&mut context, "10^400"), "∞");
+        assert_eq!(pretty(&mut context, "-(10^400)"), "-∞");
+        eval(&mut context, "unit cm = 100m").unwrap();
+        assert!(eval(&mut context, "2 cm")
+            .unwrap()
+            .unwrap()
+            .get_bounds()
+            .is_none());
+
+        let mut context = Context::new();
+        assert!(eval(&mut context, "0.1 + 0.2")
+            .unwrap()
+            .unwrap()
+            .get_bounds()
+            .is_none());
+    }
+
+    #[test]
+    fn test_digit_separators() {
+        let mut context = Context::new();
+        let value =
+            |context: &mut Context, input: &str| eval(context, input).unwrap().unwrap().to_f64();
+        assert_eq!(value(&mut context, "1_000_000"), 1000000f64);
+        assert_eq!(value(&mut context, "1 000 000"), 1000000f64);
+        assert_eq!(value(&mut context, "1_234.5"), 1234.5f64);
+        assert_eq!(value(&mut context, "1101_2"), 13f64);
+        assert_eq!(value(&mut context, "1_000_2"), 8f64);
+        assert_eq!(value(&mut context, "0xff_ff"), 65535f64);
+        assert!(eval(&mut context, "1_00").is_err());
+
+        let mut context =
+            Context::new().set_number_format(NumberFormat::from_locale("de").unwrap());
+        assert_eq!(
+            eval(&mut context, "1 234 567 + 0.375")
+                .unwrap()
+                .unwrap()
+                .to_string_pretty(),
+            "1.234.567,375 ≈ 1234567 + 3/8"
+        );
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let mut context = Context::new();
+        eval(&mut context, "radius = 2; area(r) = pi r^2").unwrap();
+        assert_eq!(
+            eval(&mut context, "radus").err(),
+            Some(KalkError::UndefinedVar(
+                String::from("r"),
+                vec![String::from("radius")]
+            ))
+        );
+        assert_eq!(
+            eval(&mut context, "sqr(4)").err(),
+            Some(KalkError::UndefinedVar(
+                String::from("s"),
+                vec![String::from("sqrt")]
+            ))
+        );
+        assert_eq!(
+            eval(&mut context, "aria(2)")
+                .err()
+                .map(|err| err.to_string()),
+            Some(String::from(
+                "Undefined variable: 'a'. Did you mean 'area'?"
+            ))
+        );
+        assert_eq!(
+            eval(&mut context, "xy").err(),
+            Some(KalkError::UndefinedVar(String::from("x"), Vec::new()))
+        );
+    }
+
+    #[test]
+    fn test_definitions() {
+        let mut context = Context::new();
+        eval(
+            &mut context,
+            "g(x) = 2x; f(x) = g(x) + a; const a = 3; unit km = 1000m",
+        )
+        .unwrap();
+        let definitions = context.get_definitions();
+        assert!(definitions.contains(&String::from("f(x) = g(x) + a")));
+        assert!(definitions.contains(&String::from("const a = 3")));
+        assert!(definitions.contains(&String::from("unit km = 1000m")));
+
+        let symbol_table = parse_definitions(&definitions).unwrap();
+        assert_eq!(symbol_table.definitions(), definitions);
+        assert!(symbol_table.is_constant("a"));
+    }
+
+    #[test]
+    fn test_list_and_remove_definitions() {
+        let mut context = Context::new();
+        eval(
+            &mut context,
+            "b = 2; const a = 3; g(x) = 2x; f(x, y) = x + y",
+        )
+        .unwrap();
+        assert_eq!(
+            context.get_variables(),
+            vec![
+                String::from("const a = 3"),
+                String::from("ans = 1"),
+                String::from("b = 2")
+            ]
+        );
+        assert_eq!(
+            context.get_functions(),
+            vec![String::from("f(x, y) = x + y"), String::from("g(x) = 2x")]
+        );
+
+        assert!(context.remove_definition("a"));
+        assert!(context.remove_definition("g"));
+        assert!(!context.remove_definition("g"));
+        assert!(!context.remove_definition("i"));
+        assert_eq!(
+            context.get_functions(),
+            vec![String::from("f(x, y) = x + y")]
+        );
+        assert!(eval(&mut context, "a").is_err());
+
+        // It isn't a constant anymore, so it can be redefined without const
+        eval(&mut context, "a = 4").unwrap();
+        assert_eq!(eval(&mut context, "a").unwrap().unwrap().to_f64(), 4f64);
+    }
+
+    #[test]
+    fn test_alias_definitions() {
+        let mut context = Context::new();
+        eval(&mut context, "alias sq = sqrt; alias k2 = 2^2").unwrap();
+        assert_eq!(eval(&mut context, "3k2").unwrap().unwrap().to_f64(), 12f64);
+        eval(&mut context, "f(x) = sq(x) + k2; g(x) = x; const c = 2k2").unwrap();
+        assert_eq!(
+            context.get_functions(),
+            vec![String::from("f(x) = sq(x) + k2"), String::from("g(x) = x")]
+        );
+        assert_eq!(
+            context.get_definition("c"),
+            Some(String::from("const c = 2k2"))
+        );
+
+        // The definitions that are stored are still written without the aliases
+        assert!(context
+            .get_definitions()
+            .contains(&String::from("f(x) = sqrt(x) + (2^2)")));
+
+        // Redefining the declaration or an alias that it uses makes it show what it means
+        eval(&mut context, "g(x) = sq(x); f(x) = 2x").unwrap();
+        assert_eq!(
+            context.get_definition("g"),
+            Some(String::from("g(x) = sq(x)"))
+        );
+        assert_eq!(context.get_definition("f"), Some(String::from("f(x) = 2x")));
+        eval(&mut context, "alias sq = cbrt").unwrap();
+        assert_eq!(
+            context.get_definition("g"),
+            Some(String::from("g(x) = sqrt(x)"))
+        );
+    }
+
+    #[test]
+    fn test_shadowing_policy() {
+        let mut context = Context::new();
+        assert_eq!(
+            eval(&mut context, "pi = 3").err(),
+            Some(KalkError::UnableToOverrideConstant(String::from("pi")))
+        );
+        assert_eq!(
+            eval(&mut context, "sin(x) = x").err(),
+            Some(KalkError::UnableToOverrideFunction(String::from("sin")))
+        );
+        assert_eq!(
+            eval(&mut context, "sin(0)").unwrap().unwrap().to_f64(),
+            0f64
+        );
+
+        let mut context = Context::new().set_shadowing_policy(ShadowingPolicy::Warn);
+        eval(&mut context, "pi = 3; sin(x) = 2x").unwrap();
+        assert_eq!(
+            context.take_shadowing_warnings(),
+            vec![
+                ShadowingWarning {
+                    name: String::from("pi"),
+                    is_function: false,
+                },
+                ShadowingWarning {
+                    name: String::from("sin"),
+                    is_function: true,
+                },
+            ]
+        );
+        assert!(context.take_shadowing_warnings().is_empty());
+        assert_eq!(
+            eval(&mut context, "sin(pi)").unwrap().unwrap().to_f64(),
+            6f64
+        );
+
+        // Constants declared with const can't be replaced, whatever the policy
+        let mut context = Context::new().set_shadowing_policy(ShadowingPolicy::Allow);
+        eval(&mut context, "e = 2; const a = 1").unwrap();
+        assert!(context.take_shadowing_warnings().is_empty());
+        assert_eq!(eval(&mut context, "e").unwrap().unwrap().to_f64(), 2f64);
+        assert_eq!(
+            eval(&mut context, "a = 2").err(),
+            Some(KalkError::UnableToOverrideConstant(String::from("a")))
+        );
+    }
+
+    #[test]
+    fn test_incorrect_amount_of_arguments() {
+        let mut context = Context::new();
+        eval(&mut context, "f(x, y) = x + y").unwrap();
+        for (input, expected, name, got) in [
+            ("sqrt(1, 2)", 1, "sqrt", 2),
+            ("sqrt()", 1, "sqrt", 0),
+            ("gcd(4)", 2, "gcd", 1),
+            ("integrate(1, 2)", 3, "integrate", 2),
+            ("f(1)", 2, "f", 1),
+            ("f()", 2, "f", 0),
+        ] {
+            assert_eq!(
+                eval(&mut context, input).err(),
+                Some(KalkError::IncorrectAmountOfArguments(
+                    expected,
+                    String::from(name),
+                    got
+                )),
+                "{}",
+                input
+            );
+        }
+
+        assert_eq!(
+            eval(&mut context, "sqrt(1, 2)")
+                .err()
+                .map(|err| err.to_string()),
+            Some(String::from("sqrt expects 1 argument, got 2."))
+        );
+        assert_eq!(
+            eval(&mut context, "max(1, 2, 3)")
+                .unwrap()
+                .unwrap()
+                .to_f64(),
+            3f64
+        );
+    }
+
+    #[test]
+    fn test_error_span() {
+        fn error_span(context: &mut Context, input: &str) -> Option<lexer::Span> {
+            assert!(eval(context, input).is_err(), "{}", input);
+
+            context.get_error_span()
+        }
+
+        let mut context = Context::new();
+        assert_eq!(error_span(&mut context, "2 + 3 * radius"), Some((8, 14)));
+        assert_eq!(error_span(&mut context, "1 + sqrt(1, 2)"), Some((4, 8)));
+        assert_eq!(
+            error_span(&mut context, "f(x) = x + y; 3 + f(2)"),
+            Some((18, 19))
+        );
+        assert_eq!(error_span(&mut context, "a = 1; 2 +; 3"), Some((7, 11)));
+
+        // The variable inside the function is in another input
+        eval(&mut context, "g(x) = x + z").unwrap();
+        assert_eq!(error_span(&mut context, "1 + g(2)"), Some((4, 5)));
+
+        assert!(eval(&mut context, "1 + 2").is_ok());
+        assert_eq!(context.get_error_span(), None);
+
+        // Errors from operators point at the operation
+        assert_eq!(error_span(&mut context, "2 * (not 3)"), Some((5, 10)));
+        assert_eq!(
+            error_span(&mut context, "1 + [1, 2] + [1, 2, 3]"),
+            Some((0, 22))
+        );
+        let mut context = Context::new().set_domain_policy(DomainPolicy::Error);
+        assert_eq!(error_span(&mut context, "2 + 1/0"), Some((4, 7)));
+        assert_eq!(error_span(&mut context, "3 + 2^(1/0)"), Some((7, 10)));
+        assert_eq!(error_span(&mut context, "5 * 0^-1"), Some((4, 8)));
+    }
+
+    #[test]
+    fn test_shared_context() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Context>();
+
+        let mut context = Context::new();
+        eval(&mut context, "f(x) = x^2 + a; a = 1").unwrap();
+        let context = &context;
+        let results: Vec<f64> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (1..=4)
+                .map(|x| {
+                    scope.spawn(move || {
+                        let mut context = context.clone();
+                        let input = format!("a = {}; f({})", x, x);
+                        eval(&mut context, &input).unwrap().unwrap().to_f64()
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+        assert_eq!(results, vec![2f64, 6f64, 12f64, 20f64]);
+
+        // The clones don't change the original context
+        let mut context = context.clone();
+        assert_eq!(eval(&mut context, "f(2)").unwrap().unwrap().to_f64(), 5f64);
+    }
+
+    #[test]
+    fn test_atan2_angle_unit() {
+        let mut context = Context::new().set_angle_unit("deg");
+        let result = eval(&mut context, "atan2(1, -1)").unwrap().unwrap();
+        assert!(cmp(result.to_f64(), 135f64));
+
+        let result = eval(&mut context, "atan2(-1, 0)").unwrap().unwrap();
+        assert!(cmp(result.to_f64(), -90f64));
+
+        let mut context = Context::new();
+        let result = eval(&mut context, "atan2(1, -1)").unwrap().unwrap();
+        assert!(cmp(result.to_f64(), 3f64 * std::f64::consts::FRAC_PI_4));
+        assert!(matches!(
+            eval(&mut context, "atan2(i, 1)"),
+            Err(KalkError::ExpectedReal)
+        ));
+    }
+
+    #[test]
+    fn test_angle_unit_of_results() {
+        let mut context = Context::new();
+        for input in ["asin(0.5) deg", "asin(0.5) to deg", "(asin(0.5)) deg"] {
+            let result = eval(&mut context, input).unwrap().unwrap();
+            assert!(cmp(result.to_f64(), 30f64));
+            assert_eq!(result.get_value().get_unit(), Some(&String::from("deg")));
+        }
+
+        let result = eval(&mut context, "atan2(1, 1) deg + 1").unwrap().unwrap();
+        assert!(cmp(result.to_f64(), 46f64));
+
+        // Other expressions are still given the unit
+        let result = eval(&mut context, "x = 30; x deg").unwrap().unwrap();
+        assert!(cmp(result.to_f64(), std::f64::consts::FRAC_PI_6));
+
+        let mut context = Context::new().set_angle_unit("deg");
+        let result = eval(&mut context, "asin(0.5) rad").unwrap().unwrap();
+        assert!(cmp(result.to_f64(), std::f64::consts::FRAC_PI_6));
+        let result = eval(&mut context, "asin(0.5) to rad").unwrap().unwrap();
+        assert!(cmp(result.to_f64(), std::f64::consts::FRAC_PI_6));
+    }
+
+    #[test]
+    fn test_gradians_and_turns() {
+        let mut context = Context::new();
+        let result = eval(&mut context, "100 grad to deg").unwrap().unwrap();
+        assert!(cmp(result.to_f64(), 90f64));
+        let result = eval(&mut context, "0.5 turn").unwrap().unwrap();
+        assert!(cmp(result.to_f64(), std::f64::consts::PI));
+        let result = eval(&mut context, "asin(1) to turn").unwrap().unwrap();
+        assert!(cmp(result.to_f64(), 0.25f64));
+        let result = eval(&mut context, "with grad { cos(200) }")
+            .unwrap()
+            .unwrap();
+        assert!(cmp(result.to_f64(), -1f64));
+
+        let mut context = Context::new().set_angle_unit("turn");
+        let result = eval(&mut context, "sin(0.25)").unwrap().unwrap();
+        assert!(cmp(result.to_f64(), 1f64));
+        let result = eval(&mut context, "atan2(-1, 0)").unwrap().unwrap();
+        assert!(cmp(result.to_f64(), -0.25f64));
+        assert_eq!(
+            eval(&mut context, "anglemode()").unwrap().unwrap().to_f64(),
+            3f64
+        );
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut context = Context::new();
+        assert_eq!(context.store_register("a"), Err(KalkError::NothingToStore));
+
+        eval(&mut context, "6 * 7").unwrap();
+        context.store_register("a").unwrap();
+        eval(&mut context, "1 + 1").unwrap();
+        context.store_register("b").unwrap();
+        assert_eq!(context.get_register_names(), vec!["a", "b"]);
+
+        // Registers aren't variables
+        assert!(eval(&mut context, "a + 1").is_err());
+
+        context.recall_register("a").unwrap();
+        assert_eq!(
+            eval(&mut context, "ans + 1").unwrap().unwrap().to_f64(),
+            43f64
+        );
+        context.recall_register("b").unwrap();
+        assert_eq!(eval(&mut context, "ans").unwrap().unwrap().to_f64(), 2f64);
+        assert_eq!(
+            context.recall_register("c"),
+            Err(KalkError::EmptyRegister(String::from("c")))
+        );
+    }
+
+    #[test]
+    fn test_random() {
+        let values = |context: &mut Context| {
+            let mut values = Vec::new();
+            for input in ["rand()", "randint(1, 6)", "randnormal(10, 2)"] {
+                values.push(eval(context, input).unwrap().unwrap().to_f64());
+            }
+
+            values
+        };
+
+        // The generator continues between inputs, and the same seed gives the same numbers
+        let mut context = Context::new().set_seed(42);
+        let first = values(&mut context);
+        assert_ne!(first, values(&mut context));
+        assert_eq!(first, values(&mut Context::new().set_seed(42)));
+
+        let mut context = Context::new();
+        for _ in 0..100 {
+            let x = eval(&mut context, "rand()").unwrap().unwrap().to_f64();
+            assert!((0f64..1f64).contains(&x));
+            let n = eval(&mut context, "randint(1.5, 3)")
+                .unwrap()
+                .unwrap()
+                .to_f64();
+            assert!(n == 2f64 || n == 3f64);
+        }
+
+        assert!(eval(&mut context, "randint(1.2, 1.8)").is_err());
+    }
+
+    #[test]
+    fn test_environment_funcs() {
+        let mut context = Context::new().set_angle_unit("deg");
+        assert_eq!(
+            eval(&mut context, "anglemode()").unwrap().unwrap().to_f64(),
+            1f64
+        );
+        assert_eq!(
+            eval(&mut context, "with rad { anglemode() }")
+                .unwrap()
+                .unwrap()
+                .to_f64(),
+            0f64
+        );
+
+        #[cfg(not(feature = "rug"))]
+        {
+            assert_eq!(
+                eval(&mut context, "precision()").unwrap().unwrap().to_f64(),
+                53f64
+            );
+            assert_eq!(
+                eval(&mut context, "backend()").unwrap().unwrap().to_f64(),
+                0f64
+            );
+        }
+
+        assert!(matches!(
+            eval(&mut context, "backend(2)"),
+            Err(KalkError::IncorrectAmountOfArguments(0, _, 1))
+        ));
+    }
+
+    #[test]
+    fn test_calculation_result() {
+        let mut context = Context::new();
+        eval(&mut context, "unit km = 1000m").unwrap();
+        let result = eval(&mut context, "2.5 km").unwrap().unwrap();
+        assert_eq!(result.to_f64(), 2.5f64);
+        assert_eq!(result.get_unit(), Some(String::from("km")));
+        assert_eq!(result.get_radix(), 10);
+        assert!(!result.is_approximation());
+
+        let result = eval(&mut context, "f(x) = x^2; f'(3)").unwrap().unwrap();
+        assert!(result.is_approximation());
+        assert_eq!(result.get_unit(), None);
+    }
+
+    #[test]
+    fn test_dropped_unit() {
+        let mut context = Context::new();
+        eval(&mut context, "unit km = 1000m; unit h = 3600s").unwrap();
+        for input in ["2 + 3km", "3km + 2h"] {
+            let result = eval(&mut context, input).unwrap().unwrap();
+            assert_eq!(
+                result.get_dropped_unit(),
+                Some(String::from("km")),
+                "{}",
+                input
+            );
+        }
+
+        for input in ["3km + 2", "3km * 2", "2 * 3km", "2km + 500m", "50km + 10%"] {
+            let result = eval(&mut context, input).unwrap().unwrap();
+            assert_eq!(result.get_dropped_unit(), None, "{}", input);
+        }
 
+        let mut context = Context::new().set_strict_units(true);
+        eval(&mut context, "unit km = 1000m").unwrap();
+        assert!(matches!(
+            eval(&mut context, "2 + 3km"),
+            Err(KalkError::UnitDropped(_))
+        ));
         assert_eq!(
-            parse(tokens).unwrap(),
-            Stmt::Expr(binary(
-                var("x"),
-                Star,
-                binary(var("y"), Power, literal(2f64))
-            ))
+            eval(&mut context, "3km + 2").unwrap().unwrap().to_f64(),
+            5f64
         );
     }
 
     #[test]
-    #[wasm_bindgen_test]
-    fn test_binary() {
-        // 1+2*(3-4/5)
-        let tokens = vec![
-            token(Literal, "1"),
-            token(Plus, ""),
-            token(Literal, "2"),
-            token(Star, ""),
-            token(OpenParenthesis, ""),
-            token(Literal, "3"),
-            token(Minus, ""),
-            token(Literal, "4"),
-            token(Slash, ""),
-            token(Literal, "5"),
-            token(ClosedParenthesis, ""),
-            token(Eof, ""),
-        ];
-
+    #[cfg(not(feature = "rug"))]
+    fn test_programmer_mode() {
+        assert_eq!(ProgrammerMode::new(12, true), None);
+
+        let mut context = Context::new().set_programmer_mode(ProgrammerMode::new(8, false));
+        let mut eval_f64 = |input: &str| eval(&mut context, input).unwrap().unwrap().to_f64();
+        assert_eq!(eval_f64("255 + 1"), 0f64);
+        assert_eq!(eval_f64("0 - 1"), 255f64);
+        assert_eq!(eval_f64("7 / 2"), 3f64);
+        assert_eq!(eval_f64("bitcmp(0)"), 255f64);
+        assert_eq!(eval_f64("bitshift(1, 9)"), 0f64);
+
+        let mut context = Context::new().set_programmer_mode(ProgrammerMode::new(16, true));
         assert_eq!(
-            parse(tokens).unwrap(),
-            Stmt::Expr(binary(
-                literal(1f64),
-                Plus,
-                binary(
-                    literal(2f64),
-                    Star,
-                    group(binary(
-                        literal(3f64),
-                        Minus,
-                        binary(literal(4f64), Slash, literal(5f64))
-                    ))
-                )
-            ))
+            eval(&mut context, "32767 + 1").unwrap().unwrap().to_f64(),
+            -32768f64
         );
+        let mut result = eval(&mut context, "-2").unwrap().unwrap();
+        result.set_radix(16);
+        assert_eq!(result.to_string_pretty(), "-2\nfffe₁₆");
+
+        let mut context = Context::new().set_programmer_mode(ProgrammerMode::new(64, false));
+        let result = eval(&mut context, "bitshift(1, 40)").unwrap().unwrap();
+        assert_eq!(result.to_f64(), 2f64.powi(40));
     }
 
     #[test]
-    #[wasm_bindgen_test]
-    fn test_pow() {
-        let tokens = vec![
-            token(Literal, "1"),
-            token(Star, ""),
-            token(Literal, "2"),
-            token(Power, ""),
-            token(Literal, "3"),
-            token(Power, ""),
-            token(Literal, "4"),
-            token(Plus, ""),
-            token(Literal, "5"),
-            token(Eof, ""),
-        ];
+    fn test_programmer_mode_64_bits() {
+        let mut context = Context::new().set_programmer_mode(ProgrammerMode::new(64, false));
+        let mut eval_hex = |input: &str| {
+            let mut result = eval(&mut context, input).unwrap().unwrap();
+            result.set_radix(16);
+            result.to_string_pretty()
+        };
+        let max = "18 446 744 073 709 551 615\nffffffffffffffff₁₆";
+        assert_eq!(eval_hex("0 - 1"), max);
+        assert_eq!(eval_hex("(0 - 1) + 0"), max);
+        assert_eq!(eval_hex("(0 - 1) + 1"), "0\n0₁₆");
+        assert_eq!(
+            eval_hex("9007199254740993"),
+            "9 007 199 254 740 993\n20000000000001₁₆"
+        );
 
+        let mut context = Context::new().set_programmer_mode(ProgrammerMode::new(64, true));
+        let mut eval_hex = |input: &str| {
+            let mut result = eval(&mut context, input).unwrap().unwrap();
+            result.set_radix(16);
+            result.to_string_pretty()
+        };
+        assert_eq!(eval_hex("-1"), "-1\nffffffffffffffff₁₆");
         assert_eq!(
-            parse(tokens).unwrap(),
-            Stmt::Expr(binary(
-                binary(
-                    literal(1f64),
-                    Star,
-                    binary(
-                        literal(2f64),
-                        Power,
-                        binary(literal(3f64), Power, literal(4f64)),
-                    ),
-                ),
-                Plus,
-                literal(5f64)
-            )),
+            eval_hex("2^63"),
+            "-9 223 372 036 854 775 808\n8000000000000000₁₆"
         );
     }
 
-    #[wasm_bindgen_test]
-    fn test_pow_unary() {
-        let tokens = vec![
-            token(Literal, "10"),
-            token(Power, ""),
-            token(Minus, ""),
-            token(Literal, "1"),
-            token(Eof, ""),
-        ];
+    #[test]
+    #[cfg(not(feature = "rug"))]
+    fn test_overflow_policy() {
+        let mut context = Context::new();
+        let result = eval(&mut context, "10^400").unwrap().unwrap();
+        assert!(result.to_f64().is_infinite());
+        assert!(result.is_out_of_range());
+        assert!(!eval(&mut context, "10^300")
+            .unwrap()
+            .unwrap()
+            .is_out_of_range());
+        assert!(!eval(&mut context, "1/0")
+            .unwrap()
+            .unwrap()
+            .is_out_of_range());
+        assert!(eval(&mut context, "exp(1000)")
+            .unwrap()
+            .unwrap()
+            .is_out_of_range());
+        assert!(!eval(&mut context, "ln(0)")
+            .unwrap()
+            .unwrap()
+            .is_out_of_range());
+
+        let mut context = Context::new().set_overflow_policy(OverflowPolicy::Error);
+        assert_eq!(
+            eval(&mut context, "10^400").err(),
+            Some(KalkError::Overflow)
+        );
+        assert_eq!(eval(&mut context, "200!").err(), Some(KalkError::Overflow));
+        assert_eq!(
+            eval(&mut context, "exp(1000)").err(),
+            Some(KalkError::Overflow)
+        );
+        assert_eq!(
+            eval(&mut context, "gamma(200)").err(),
+            Some(KalkError::Overflow)
+        );
+        eval(&mut context, "f(x) = exp(x) + 1").unwrap();
+        assert_eq!(
+            eval(&mut context, "f(1000)").err(),
+            Some(KalkError::Overflow)
+        );
+        assert!(eval(&mut context, "ln(0)").is_ok());
+        assert_eq!(
+            eval(&mut context, "10^-200 * 10^-200").err(),
+            Some(KalkError::Underflow)
+        );
+        assert!(eval(&mut context, "0 * 10^-200").is_ok());
+    }
 
+    #[test]
+    #[cfg(feature = "rug")]
+    fn test_overflow_policy() {
+        // Arbitrary precision numbers have a much larger range, but it's still limited
+        let mut context = Context::new();
+        assert!(!eval(&mut context, "10^400")
+            .unwrap()
+            .unwrap()
+            .is_out_of_range());
+        assert!(eval(&mut context, "e^(10^20)")
+            .unwrap()
+            .unwrap()
+            .is_out_of_range());
+
+        let mut context = Context::new().set_overflow_policy(OverflowPolicy::Error);
         assert_eq!(
-            parse(tokens).unwrap(),
-            Stmt::Expr(binary(literal(10f64), Power, unary(Minus, literal(1f64)))),
+            eval(&mut context, "e^(10^20)").err(),
+            Some(KalkError::Overflow)
+        );
+        assert_eq!(
+            eval(&mut context, "exp(10^20)").err(),
+            Some(KalkError::Overflow)
         );
+        assert!(eval(&mut context, "ln(0)").is_ok());
     }
 
     #[test]
-    #[wasm_bindgen_test]
-    fn test_percent() {
-        let tokens = vec![
-            token(Literal, "1"),
-            token(Percent, ""),
-            token(Literal, "1"),
-            token(Plus, ""),
-            token(Literal, "5"),
-            token(Percent, ""),
-            token(Eof, ""),
-        ];
+    fn test_domain_policy() {
+        let mut context = Context::new();
+        let result = eval(&mut context, "1/0").unwrap().unwrap();
+        assert!(result.to_f64().is_infinite());
+        assert_eq!(
+            result.get_domain_note(),
+            Some(String::from("1 / 0 is undefined, and gave ∞"))
+        );
+        let result = eval(&mut context, "log(0)").unwrap().unwrap();
+        assert_eq!(result.to_f64(), f64::NEG_INFINITY);
+        assert_eq!(
+            result.get_domain_note(),
+            Some(String::from("log(0) is undefined, and gave -∞"))
+        );
+        let result = eval(&mut context, "sqrt(-1)").unwrap().unwrap();
+        assert_eq!(result.imaginary_to_f64(), 1f64);
+        assert_eq!(result.get_domain_note(), None);
+        for input in ["1/2", "sqrt(4)", "sqrt(-i)", "exp(1000)", "log(0 + i)"] {
+            let result = eval(&mut context, input).unwrap().unwrap();
+            assert_eq!(result.get_domain_note(), None, "{}", input);
+        }
 
+        let mut context = Context::new().set_domain_policy(DomainPolicy::Real);
+        for input in ["sqrt(-1)", "ln(-1)", "asin(2)", "(-8)^(1/3)"] {
+            let result = eval(&mut context, input).unwrap().unwrap();
+            assert!(result.to_f64().is_nan(), "{}", input);
+            assert!(result.get_domain_note().is_some(), "{}", input);
+        }
+        assert!(eval(&mut context, "1/0")
+            .unwrap()
+            .unwrap()
+            .to_f64()
+            .is_infinite());
+        assert!(eval(&mut context, "0^-1")
+            .unwrap()
+            .unwrap()
+            .to_f64()
+            .is_infinite());
         assert_eq!(
-            parse(tokens).unwrap(),
-            Stmt::Expr(binary(
-                binary(literal(1f64), Percent, literal(1f64)),
-                Plus,
-                unary(Percent, literal(5f64))
-            ))
+            eval(&mut context, "sqrt(-i)")
+                .unwrap()
+                .unwrap()
+                .imaginary_to_f64(),
+            -(0.5f64.sqrt())
+        );
+
+        let mut context = Context::new().set_domain_policy(DomainPolicy::Error);
+        assert_eq!(
+            eval(&mut context, "1/0").err(),
+            Some(KalkError::DivisionByZero)
+        );
+        assert_eq!(
+            eval(&mut context, "0^-1").err(),
+            Some(KalkError::DivisionByZero)
+        );
+        assert_eq!(
+            eval(&mut context, "2 + log(0)").err(),
+            Some(KalkError::OutsideDomain(String::from("log(0)")))
+        );
+        assert_eq!(
+            eval(&mut context, "sqrt(-1)").err(),
+            Some(KalkError::NotReal(String::from("sqrt(-1)")))
         );
+        assert!(eval(&mut context, "sqrt(-i) + 1/2").is_ok());
     }
 
     #[test]
-    #[wasm_bindgen_test]
-    fn test_unit() {
-        let tokens = vec![token(Literal, "1"), token(Identifier, "a")];
-
+    fn test_infinity_and_undefined() {
         let mut context = Context::new();
-        context
-            .symbol_table
-            .get_mut()
-            .insert(unit_decl("a", "b", var(super::DECL_UNIT)));
+        let pretty = |context: &mut Context, input: &str| {
+            eval(context, input).unwrap().unwrap().to_string_pretty()
+        };
+        assert_eq!(pretty(&mut context, "inf"), "∞");
+        assert_eq!(pretty(&mut context, "-∞"), "-∞");
+        assert_eq!(pretty(&mut context, "2∞ + 1"), "∞");
+        assert_eq!(pretty(&mut context, "e^-inf"), "0");
+        assert_eq!(pretty(&mut context, "1/inf"), "0");
+        assert_eq!(pretty(&mut context, "[1, inf, 0/0]"), "(1, ∞, undefined)");
+        assert_eq!(
+            pretty(&mut context, "0/0 + 1"),
+            "Not defined, since 0 / 0 is undefined."
+        );
+        assert_eq!(
+            pretty(&mut context, "inf - ∞"),
+            "Not defined, since ∞ - ∞ is an indeterminate form."
+        );
+        assert_eq!(
+            pretty(&mut context, "sin(inf)"),
+            "Not defined, since sin(∞) is undefined."
+        );
 
+        let result = eval(&mut context, "0/0").unwrap().unwrap();
+        assert_eq!(result.to_string_big(), "undefined");
         assert_eq!(
-            parse_with_context(&mut context, tokens).unwrap(),
-            Stmt::Expr(unit("a", literal(1f64)))
+            result.get_undefined_reason(),
+            Some(String::from("0 / 0 is undefined"))
+        );
+        assert_eq!(
+            eval(&mut context, "1/0")
+                .unwrap()
+                .unwrap()
+                .get_undefined_reason(),
+            None
         );
     }
 
     #[test]
-    #[wasm_bindgen_test]
-    fn test_var_decl() {
-        let tokens = vec![
-            token(Identifier, "x"),
-            token(Equals, ""),
-            token(Literal, "1"),
-            token(Plus, ""),
-            token(Literal, "2"),
-            token(Eof, ""),
-        ];
-
+    fn test_limits() {
+        let mut context = Context::new();
+        let pretty = |context: &mut Context, input: &str| {
+            eval(context, input).unwrap().unwrap().to_string_pretty()
+        };
+        assert_eq!(pretty(&mut context, "lim(1/x^2, x -> 0)"), "≈ ∞");
         assert_eq!(
-            parse(tokens).unwrap(),
-            Stmt::VarDecl(
-                Identifier::from_full_name("x"),
-                binary(literal(1f64), Plus, literal(2f64))
-            )
+            pretty(&mut context, "lim(1/x, x -> 0)"),
+            "Not defined, since the limit from below is -∞, while the limit from above is ∞."
         );
+        assert_eq!(
+            pretty(&mut context, "lim(sin(1/x), x -> 0)"),
+            "Not defined, since the expression doesn't approach a single value."
+        );
+        assert!(eval(&mut context, "lim(x, 0)").is_err());
+        assert!(eval(&mut context, "lim(x)").is_err());
     }
 
     #[test]
-    #[wasm_bindgen_test]
-    fn test_fn_decl() {
-        let tokens = vec![
-            token(Identifier, "f"),
-            token(OpenParenthesis, ""),
-            token(Identifier, "x"),
-            token(ClosedParenthesis, ""),
-            token(Equals, ""),
-            token(Literal, "1"),
-            token(Plus, ""),
-            token(Identifier, "x"),
-            token(Eof, ""),
+    fn test_indeterminate_policy() {
+        let forms = [
+            "0^0",
+            "(1/0)^0",
+            "1^(1/0)",
+            "0 * (1/0)",
+            "1/0 - 1/0",
+            "-1/0 + 1/0",
         ];
 
+        let mut context = Context::new();
+        let values: Vec<f64> = forms
+            .iter()
+            .map(|form| eval(&mut context, form).unwrap().unwrap().to_f64())
+            .collect();
+        assert_eq!(values[..3], [1f64, 1f64, 1f64]);
+        assert!(values[3..].iter().all(|value| value.is_nan()));
+        assert_eq!(eval(&mut context, "2^0").unwrap().unwrap().to_f64(), 1f64);
+        assert!(eval(&mut context, "2 * (1/0)")
+            .unwrap()
+            .unwrap()
+            .to_f64()
+            .is_infinite());
+
+        let mut context = Context::new().set_indeterminate_policy(IndeterminatePolicy::NaN);
+        for form in forms {
+            assert!(eval(&mut context, form).unwrap().unwrap().to_f64().is_nan());
+        }
+
+        let mut context = Context::new().set_indeterminate_policy(IndeterminatePolicy::Error);
+        for (form, name) in forms
+            .iter()
+            .zip(["0^0", "∞^0", "1^∞", "0·∞", "∞ - ∞", "∞ - ∞"])
+        {
+            assert_eq!(
+                eval(&mut context, form).err(),
+                Some(KalkError::IndeterminateForm(name.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_trace() {
+        use crate::steps::StepKind;
+
+        let mut context = Context::new().set_trace(true);
+        eval(&mut context, "f(x) = x^2 + 1").unwrap();
+        let result = eval(&mut context, "2 * f(3) + sqrt(16)").unwrap().unwrap();
+        let steps: Vec<String> = result
+            .get_steps()
+            .iter()
+            .map(|step| step.to_string())
+            .collect();
         assert_eq!(
-            parse(tokens).unwrap(),
-            Stmt::FnDecl(
-                Identifier::from_full_name("f"),
-                vec![String::from("f-x")],
-                binary(literal(1f64), Plus, param_var("f", "x"))
-            )
+            steps,
+            vec![
+                "f(3) → 3^2 + 1",
+                "3 ^ 2 = 9",
+                "9 + 1 = 10",
+                "f(3) = 10",
+                "2 * 10 = 20",
+                "sqrt(16) = 4",
+                "20 + 4 = 24",
+            ]
+        );
+        assert_eq!(result.get_steps()[0].kind, StepKind::FunctionExpansion);
+
+        let mut context = Context::new();
+        let result = eval(&mut context, "2 * 3").unwrap().unwrap();
+        assert!(result.get_steps().is_empty());
+    }
+
+    #[test]
+    fn test_evaluation_budget() {
+        let mut context = Context::new().set_evaluation_budget(Some(1000));
+        assert!(eval(&mut context, "sum(n = 1, 10, n)").is_ok());
+        assert_eq!(
+            eval(&mut context, "sum(n = 1, 100000, n)").err(),
+            Some(KalkError::EvaluationBudgetExceeded)
         );
+
+        // The budget is per evaluation
+        assert!(eval(&mut context, "sum(n = 1, 10, n)").is_ok());
     }
 
     #[test]
@@ -1022,7 +4876,7 @@ mod tests {
         let mut context = Context::new();
 
         // Add the function to the symbol table first, in order to prevent errors.
-        context.symbol_table.get_mut().set(Stmt::FnDecl(
+        context.symbol_table.set(Stmt::FnDecl(
             Identifier::from_full_name("f"),
             vec![String::from("x")],
             literal(1f64),
@@ -1040,4 +4894,140 @@ mod tests {
             ))
         );
     }
+
+    #[test]
+    fn test_destructuring() {
+        let mut context = Context::new();
+        eval(&mut context, "(q, r) = divmod(17, 5)").unwrap();
+        assert_eq!(eval(&mut context, "q").unwrap().unwrap().to_f64(), 3f64);
+        assert_eq!(eval(&mut context, "r").unwrap().unwrap().to_f64(), 2f64);
+
+        assert_eq!(
+            eval(&mut context, "(a, b) = (1, 2, 3)").err(),
+            Some(KalkError::Expected(String::from(
+                "a vector with 2 elements, one for each variable"
+            )))
+        );
+        assert!(eval(&mut context, "(a, b) = 5").is_err());
+        assert_eq!(
+            eval(&mut context, "(a, b) = (a, 2)").err(),
+            Some(KalkError::VariableReferencesItself)
+        );
+    }
+
+    #[test]
+    fn test_compound_units() {
+        let mut context = Context::new();
+        for (input, expected, unit) in [
+            ("10 m / 2 s", 5f64, Some("m/s")),
+            ("5 N * 2 m", 10f64, Some("J")),
+            ("3 m/s * 4 s", 12f64, Some("m")),
+            ("2 kg * 3 m/s^2", 6f64, Some("N")),
+            ("20 J / 4 s", 5f64, Some("W")),
+            ("2 / 4 s", 0.5f64, Some("1/s")),
+            ("(3 m)^2", 9f64, Some("m^2")),
+            ("6 m / 2 m", 3f64, None),
+            ("1 h / 30 min", 2f64, None),
+        ] {
+            let result = eval(&mut context, input).unwrap().unwrap();
+            assert_eq!(result.to_f64(), expected, "{}", input);
+            assert_eq!(result.get_unit().as_deref(), unit, "{}", input);
+        }
+
+        eval(&mut context, "unit km = m/1000").unwrap();
+        let result = eval(&mut context, "2 km * 500 m").unwrap().unwrap();
+        assert_eq!(result.to_f64(), 1f64);
+        assert_eq!(result.get_unit(), Some(String::from("km^2")));
+
+        // A variable with the same name as a unit is still a variable
+        let result = eval(&mut context, "s = 3; 2s").unwrap().unwrap();
+        assert_eq!(result.to_f64(), 6f64);
+        assert_eq!(result.get_unit(), None);
+    }
+
+    #[test]
+    fn test_temperatures() {
+        let mut context = Context::new();
+        let result = eval(&mut context, "30 °C - 20 °C").unwrap().unwrap();
+        assert_eq!(result.to_f64(), 10f64);
+        assert_eq!(result.get_unit(), Some(String::from("Δ°C")));
+
+        for input in ["20 °C + 30 °C", "5 deltaC - 20 °C", "300 K + 20 °C"] {
+            assert!(
+                matches!(
+                    eval(&mut context, input),
+                    Err(KalkError::InvalidTemperatureOperation(_))
+                ),
+                "{}",
+                input
+            );
+        }
+
+        assert_eq!(
+            eval(&mut context, "300 K + 10 K")
+                .unwrap()
+                .unwrap()
+                .to_f64(),
+            310f64
+        );
+    }
+
+    #[test]
+    fn test_register_function() {
+        let mut context = Context::new();
+        context.register_function("grow", 3, |arguments| {
+            let (present, rate, periods) = (
+                arguments[0].to_f64(),
+                arguments[1].to_f64(),
+                arguments[2].to_f64(),
+            );
+            if rate <= -1f64 {
+                return Err(String::from("the rate has to be greater than -100%"));
+            }
+
+            Ok(KalkValue::from(present * (1f64 + rate).powf(periods)))
+        });
+        context.register_function("answer", 0, |_| Ok(KalkValue::from(42)));
+
+        assert_eq!(
+            eval(&mut context, "grow(100, 1, 2)")
+                .unwrap()
+                .unwrap()
+                .to_f64(),
+            400f64
+        );
+        assert_eq!(
+            eval(&mut context, "2grow(2 * 50, 1, 1) + answer()")
+                .unwrap()
+                .unwrap()
+                .to_f64(),
+            442f64
+        );
+        assert_eq!(
+            eval(&mut context, "grow(100, 1)").err(),
+            Some(KalkError::IncorrectAmountOfArguments(
+                3,
+                String::from("grow"),
+                2
+            ))
+        );
+        assert_eq!(
+            eval(&mut context, "grow(100, -2, 1)").err(),
+            Some(KalkError::EvaluationError(String::from(
+                "grow: the rate has to be greater than -100%"
+            )))
+        );
+        assert_eq!(
+            eval(&mut context, "grow(x) = x").err(),
+            Some(KalkError::UnableToOverrideFunction(String::from("grow")))
+        );
+        assert_eq!(
+            context.get_completions("grow"),
+            vec![crate::completion::CompletionItem::new(
+                "grow",
+                crate::completion::CompletionKind::Function,
+                Some(3)
+            )]
+        );
+    }
 }
@@ -0,0 +1,307 @@
+//! Functions for loans, investments and interest, with the same arguments and sign
+//! conventions as in spreadsheets: money that is paid out is negative and money that
+//! is received is positive, eg. `pmt(0.05/12, 360, 200000)` is about -1073.64.
+//! The rates are per period, and `type` is 1 for payments at the start of the periods.
+use crate::as_vector_or_return;
+use crate::errors::KalkError;
+use crate::kalk_value::KalkValue;
+
+/// How close to zero the net present value needs to be for a rate to be the IRR.
+const IRR_TOLERANCE: f64 = 1e-10;
+const MAX_IRR_ITERATIONS: usize = 200;
+
+/// The future value of regular payments and a present value, `fv(rate, n, pmt, pv, type)`,
+/// where the last two are optional.
+pub fn fv(x: KalkValue) -> Result<KalkValue, KalkError> {
+    let args = real_args(x, "fv", 3, 5)?;
+    let (rate, periods, payment) = (args[0], args[1], args[2]);
+    let (present_value, payment_type) = (arg_or_zero(&args, 3), arg_or_zero(&args, 4));
+    if rate == 0f64 {
+        return Ok(KalkValue::from(-(present_value + payment * periods)));
+    }
+
+    let growth = (1f64 + rate).powf(periods);
+
+    Ok(KalkValue::from(
+        -(present_value * growth + payment * (1f64 + rate * payment_type) * (growth - 1f64) / rate),
+    ))
+}
+
+/// The present value of regular payments and a future value, `pv(rate, n, pmt, fv, type)`,
+/// where the last two are optional.
+pub fn pv(x: KalkValue) -> Result<KalkValue, KalkError> {
+    let args = real_args(x, "pv", 3, 5)?;
+    let (rate, periods, payment) = (args[0], args[1], args[2]);
+    let (future_value, payment_type) = (arg_or_zero(&args, 3), arg_or_zero(&args, 4));
+    if rate == 0f64 {
+        return Ok(KalkValue::from(-(future_value + payment * periods)));
+    }
+
+    let growth = (1f64 + rate).powf(periods);
+
+    Ok(KalkValue::from(
+        -(future_value + payment * (1f64 + rate * payment_type) * (growth - 1f64) / rate) / growth,
+    ))
+}
+
+/// The payment in each period of a loan, `pmt(rate, n, pv, fv, type)`,
+/// where the last two are optional.
+pub fn pmt(x: KalkValue) -> Result<KalkValue, KalkError> {
+    let args = real_args(x, "pmt", 3, 5)?;
+    let (rate, periods, present_value) = (args[0], args[1], args[2]);
+    let (future_value, payment_type) = (arg_or_zero(&args, 3), arg_or_zero(&args, 4));
+    if periods == 0f64 {
+        return Err(KalkError::Expected(String::from("at least one period")));
+    }
+
+    if rate == 0f64 {
+        return Ok(KalkValue::from(-(present_value + future_value) / periods));
+    }
+
+    let growth = (1f64 + rate).powf(periods);
+
+    Ok(KalkValue::from(
+        -rate * (present_value * growth + future_value)
+            / ((1f64 + rate * payment_type) * (growth - 1f64)),
+    ))
+}
+
+/// The net present value of cash flows at the end of each period, `npv(rate, cf1, cf2, ...)`.
+/// Like in spreadsheets, the first cash flow is discounted by one period.
+pub fn npv(x: KalkValue) -> Result<KalkValue, KalkError> {
+    let mut args = as_vector_or_return!(x);
+    if args.len() < 2 {
+        return Err(KalkError::IncorrectAmountOfArguments(
+            2,
+            String::from("npv"),
+            args.len(),
+        ));
+    }
+
+    let rate = real(&args.remove(0))?;
+    let cash_flows = cash_flows(args)?;
+
+    Ok(KalkValue::from(net_present_value(rate, &cash_flows, 1)))
+}
+
+/// The rate where the net present value of the cash flows is zero, `irr(cf0, cf1, ...)`,
+/// where the first cash flow is now, usually the (negative) investment.
+pub fn irr(x: KalkValue) -> Result<KalkValue, KalkError> {
+    let cash_flows = cash_flows(as_vector_or_return!(x))?;
+    let has_positive = cash_flows.iter().any(|cash_flow| *cash_flow > 0f64);
+    let has_negative = cash_flows.iter().any(|cash_flow| *cash_flow < 0f64);
+    if !has_positive || !has_negative {
+        return Err(KalkError::Expected(String::from(
+            "cash flows with both positive and negative values",
+        )));
+    }
+
+    internal_rate_of_return(&cash_flows)
+        .map(KalkValue::from)
+        .ok_or_else(|| KalkError::EvaluationError(String::from("unable to find the IRR.")))
+}
+
+/// The amount after compound interest, `compound(principal, rate, years, times per year)`,
+/// where the rate is the yearly rate and the last argument is optional, eg. 12 for monthly.
+pub fn compound(x: KalkValue) -> Result<KalkValue, KalkError> {
+    let args = real_args(x, "compound", 3, 4)?;
+    let (principal, rate, years) = (args[0], args[1], args[2]);
+    let times_per_year = args.get(3).copied().unwrap_or(1f64);
+    if times_per_year <= 0f64 {
+        return Err(KalkError::Expected(String::from(
+            "a positive amount of times per year",
+        )));
+    }
+
+    Ok(KalkValue::from(
+        principal * (1f64 + rate / times_per_year).powf(times_per_year * years),
+    ))
+}
+
+/// The effective yearly rate of a nominal rate, `effect(rate, times per year)`,
+/// eg. `effect(0.12, 12)` is about 0.1268.
+pub fn effect(x: KalkValue) -> Result<KalkValue, KalkError> {
+    let args = real_args(x, "effect", 2, 2)?;
+    let (rate, times_per_year) = (args[0], args[1]);
+    if times_per_year <= 0f64 {
+        return Err(KalkError::Expected(String::from(
+            "a positive amount of times per year",
+        )));
+    }
+
+    Ok(KalkValue::from(
+        (1f64 + rate / times_per_year).powf(times_per_year) - 1f64,
+    ))
+}
+
+fn real_args(
+    x: KalkValue,
+    name: &str,
+    min_count: usize,
+    max_count: usize,
+) -> Result<Vec<f64>, KalkError> {
+    let args = as_vector_or_return!(x);
+    if !(min_count..=max_count).contains(&args.len()) {
+        return Err(KalkError::IncorrectAmountOfArguments(
+            max_count,
+            name.to_string(),
+            args.len(),
+        ));
+    }
+
+    args.iter().map(real).collect()
+}
+
+fn real(value: &KalkValue) -> Result<f64, KalkError> {
+    match value {
+        KalkValue::Number(_, _, _) | KalkValue::Integer(_) if value.has_imaginary() => {
+            Err(KalkError::ExpectedReal)
+        }
+        KalkValue::Number(_, _, _) | KalkValue::Integer(_) => Ok(value.to_f64()),
+        _ => Err(KalkError::UnexpectedType(
+            value.get_type_name(),
+            vec![String::from("number")],
+        )),
+    }
+}
+
+fn arg_or_zero(args: &[f64], index: usize) -> f64 {
+    args.get(index).copied().unwrap_or(0f64)
+}
+
+/// The cash flows can be given as separate arguments or as a vector, eg. `irr((-100, 60, 60))`.
+fn cash_flows(args: Vec<KalkValue>) -> Result<Vec<f64>, KalkError> {
+    match args.as_slice() {
+        [KalkValue::Vector(values)] => values.iter().map(real).collect(),
+        _ => args.iter().map(real).collect(),
+    }
+}
+
+/// The sum of the cash flows discounted by the rate, where the first one is `first_period`
+/// periods from now.
+fn net_present_value(rate: f64, cash_flows: &[f64], first_period: i32) -> f64 {
+    cash_flows
+        .iter()
+        .enumerate()
+        .map(|(i, cash_flow)| cash_flow / (1f64 + rate).powi(i as i32 + first_period))
+        .sum()
+}
+
+/// Newton's method from 10%, and bisection if it doesn't converge, eg. when the
+/// derivative is close to zero.
+fn internal_rate_of_return(cash_flows: &[f64]) -> Option<f64> {
+    let mut rate = 0.1f64;
+    for _ in 0..MAX_IRR_ITERATIONS {
+        let value = net_present_value(rate, cash_flows, 0);
+        if value.abs() < IRR_TOLERANCE {
+            return Some(rate);
+        }
+
+        let derivative: f64 = cash_flows
+            .iter()
+            .enumerate()
+            .map(|(i, cash_flow)| -(i as f64) * cash_flow / (1f64 + rate).powi(i as i32 + 1))
+            .sum();
+        rate -= value / derivative;
+        if !rate.is_finite() || rate <= -1f64 {
+            break;
+        }
+    }
+
+    // The rate is greater than -100%, so the bracket starts just above it
+    let mut low = -1f64 + 1e-9;
+    let mut high = 1f64;
+    let low_sign = net_present_value(low, cash_flows, 0).signum();
+    while net_present_value(high, cash_flows, 0).signum() == low_sign {
+        high *= 2f64;
+        if high > 1e9 {
+            return None;
+        }
+    }
+
+    for _ in 0..MAX_IRR_ITERATIONS {
+        let middle = (low + high) / 2f64;
+        let value = net_present_value(middle, cash_flows, 0);
+        if value.abs() < IRR_TOLERANCE || high - low < IRR_TOLERANCE {
+            return Some(middle);
+        }
+
+        if value.signum() == low_sign {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+
+    Some((low + high) / 2f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(f: fn(KalkValue) -> Result<KalkValue, KalkError>, args: &[f64]) -> f64 {
+        let args = args.iter().map(|arg| KalkValue::from(*arg)).collect();
+
+        f(KalkValue::Vector(args)).unwrap().to_f64()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_loans() {
+        assert_close(call(pmt, &[0.05 / 12f64, 360f64, 200000f64]), -1073.643246);
+        assert_close(call(pmt, &[0f64, 10f64, 1000f64]), -100f64);
+        assert_close(call(pmt, &[0.1, 2f64, 0f64, 1000f64, 1f64]), -432.90043290);
+        assert_close(call(fv, &[0.05, 10f64, -100f64]), 1257.78925355);
+        assert_close(call(fv, &[0.05, 10f64, 0f64, -1000f64]), 1628.89462678);
+        assert_close(call(pv, &[0.05, 10f64, -100f64]), 772.17349292);
+        assert_close(call(pv, &[0f64, 10f64, -100f64]), 1000f64);
+
+        assert!(pmt(KalkValue::Vector(vec![KalkValue::from(1)])).is_err());
+    }
+
+    #[test]
+    fn test_cash_flows() {
+        assert_close(call(npv, &[0.1, -100f64, 60f64, 60f64]), 3.75657400);
+        assert_close(call(irr, &[-100f64, 60f64, 60f64]), 0.13066239);
+        assert_close(call(irr, &[-1000f64, 300f64, 400f64, 500f64]), 0.08896339);
+
+        let cash_flows = KalkValue::Vector(vec![
+            KalkValue::from(-100),
+            KalkValue::from(60),
+            KalkValue::from(60),
+        ]);
+        assert_close(
+            irr(KalkValue::Vector(vec![cash_flows])).unwrap().to_f64(),
+            0.13066239,
+        );
+        assert!(irr(KalkValue::Vector(vec![
+            KalkValue::from(1),
+            KalkValue::from(2)
+        ]))
+        .is_err());
+    }
+
+    #[test]
+    fn test_interest() {
+        assert_close(call(compound, &[1000f64, 0.05, 10f64]), 1628.89462678);
+        assert_close(
+            call(compound, &[1000f64, 0.05, 10f64, 12f64]),
+            1647.00949769,
+        );
+        assert_close(call(effect, &[0.12, 12f64]), 0.12682503);
+        assert!(effect(KalkValue::Vector(vec![
+            KalkValue::from(0.12),
+            KalkValue::from(0)
+        ]))
+        .is_err());
+    }
+}
@@ -0,0 +1,237 @@
+//! Functions that turn values into strings, eg. `format(pi, "%.3f")`, to label results in scripts.
+use crate::errors::KalkError;
+use crate::kalk_value::KalkValue;
+use std::iter::Peekable;
+use std::str::Chars;
+
+enum Part {
+    Text(String),
+    Conversion(Conversion),
+}
+
+/// How a value is written in place of a `%` in the format, eg. `%08.3f`.
+struct Conversion {
+    left_align: bool,
+    plus_sign: bool,
+    zero_padded: bool,
+    width: usize,
+    precision: Option<usize>,
+    kind: char,
+}
+
+/// The value as it is written when it is put into a string, eg. `5 m`.
+pub fn format_value(x: KalkValue) -> Result<KalkValue, KalkError> {
+    Ok(KalkValue::String(x.to_text()))
+}
+
+/// Write the value in the format, where every `%` is replaced by the value,
+/// like in printf, eg. `%.3f`, `%e`, `%d`, `%s` or `%%` for a percent sign.
+/// If there are several `%`, they are replaced by the elements of a vector, in order.
+pub fn format_with(x: KalkValue, format: KalkValue) -> Result<KalkValue, KalkError> {
+    let format = if let KalkValue::String(format) = format {
+        format
+    } else {
+        return Err(KalkError::UnexpectedType(
+            format.get_type_name(),
+            vec![String::from("string")],
+        ));
+    };
+
+    let parts = parse(&format)?;
+    let conversion_count = parts
+        .iter()
+        .filter(|part| matches!(part, Part::Conversion(_)))
+        .count();
+    let mut values = match x {
+        KalkValue::Vector(values) if conversion_count != 1 => {
+            if values.len() != conversion_count {
+                return Err(KalkError::Expected(format!(
+                    "a vector with {} elements, one for each % in the format",
+                    conversion_count
+                )));
+            }
+
+            values
+        }
+        _ => vec![x],
+    }
+    .into_iter();
+
+    let mut output = String::new();
+    for part in parts {
+        match part {
+            Part::Text(text) => output.push_str(&text),
+            Part::Conversion(conversion) => {
+                output.push_str(&conversion.apply(&values.next().unwrap())?)
+            }
+        }
+    }
+
+    Ok(KalkValue::String(output))
+}
+
+/// Split the format into text and conversions.
+fn parse(format: &str) -> Result<Vec<Part>, KalkError> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            text.push(c);
+        } else if chars.peek() == Some(&'%') {
+            chars.next();
+            text.push('%');
+        } else {
+            parts.push(Part::Text(std::mem::take(&mut text)));
+            parts.push(Part::Conversion(parse_conversion(&mut chars)?));
+        }
+    }
+
+    parts.push(Part::Text(text));
+
+    Ok(parts)
+}
+
+fn parse_conversion(chars: &mut Peekable<Chars>) -> Result<Conversion, KalkError> {
+    let (mut left_align, mut plus_sign, mut zero_padded) = (false, false, false);
+    while let Some(flag) = chars.peek() {
+        match flag {
+            '-' => left_align = true,
+            '+' => plus_sign = true,
+            '0' => zero_padded = true,
+            _ => break,
+        }
+
+        chars.next();
+    }
+
+    let width = parse_digits(chars).unwrap_or(0);
+    let precision = if chars.peek() == Some(&'.') {
+        chars.next();
+        Some(parse_digits(chars).unwrap_or(0))
+    } else {
+        None
+    };
+
+    let kind = match chars.next() {
+        Some(kind @ ('f' | 'e' | 'd' | 's')) => kind,
+        _ => {
+            return Err(KalkError::Expected(String::from(
+                "f, e, d or s after % in the format, eg. %.3f",
+            )))
+        }
+    };
+
+    Ok(Conversion {
+        left_align,
+        plus_sign,
+        zero_padded,
+        width,
+        precision,
+        kind,
+    })
+}
+
+fn parse_digits(chars: &mut Peekable<Chars>) -> Option<usize> {
+    let mut digits = String::new();
+    while let Some(digit) = chars.peek().filter(|c| c.is_ascii_digit()) {
+        digits.push(*digit);
+        chars.next();
+    }
+
+    digits.parse().ok()
+}
+
+impl Conversion {
+    fn apply(&self, value: &KalkValue) -> Result<String, KalkError> {
+        let formatted = match (self.kind, value) {
+            ('s', _) => value.to_text(),
+            (_, KalkValue::Number(_, _, _)) if value.has_imaginary() => {
+                return Err(KalkError::ExpectedReal)
+            }
+            ('d', KalkValue::Integer(integer)) => integer.to_string(),
+            ('d', KalkValue::Number(_, _, _)) => {
+                // Adding zero turns -0 into 0
+                format!("{:.0}", value.to_f64().round() + 0f64)
+            }
+            ('f', KalkValue::Number(_, _, _)) | ('f', KalkValue::Integer(_)) => {
+                format!("{:.*}", self.precision.unwrap_or(6), value.to_f64())
+            }
+            ('e', KalkValue::Number(_, _, _)) | ('e', KalkValue::Integer(_)) => {
+                format!("{:.*e}", self.precision.unwrap_or(6), value.to_f64())
+            }
+            _ => {
+                return Err(KalkError::UnexpectedType(
+                    value.get_type_name(),
+                    vec![String::from("number")],
+                ))
+            }
+        };
+
+        let is_number = self.kind != 's';
+        let formatted = if self.plus_sign && is_number && !formatted.starts_with('-') {
+            format!("+{}", formatted)
+        } else {
+            formatted
+        };
+
+        let padding = self.width.saturating_sub(formatted.chars().count());
+        Ok(if self.left_align {
+            format!("{}{}", formatted, " ".repeat(padding))
+        } else if self.zero_padded && is_number {
+            // The zeros go after the sign, eg. -007
+            let sign_len = if formatted.starts_with(&['-', '+'][..]) {
+                1
+            } else {
+                0
+            };
+            let (sign, digits) = formatted.split_at(sign_len);
+
+            format!("{}{}{}", sign, "0".repeat(padding), digits)
+        } else {
+            format!("{}{}", " ".repeat(padding), formatted)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_with;
+    use crate::kalk_value::KalkValue;
+
+    fn format(x: KalkValue, format: &str) -> String {
+        format_with(x, KalkValue::String(String::from(format)))
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_format_with() {
+        let pi = KalkValue::from(std::f64::consts::PI);
+        assert_eq!(format(pi.clone(), "%.3f"), "3.142");
+        assert_eq!(format(pi.clone(), "%f"), "3.141593");
+        assert_eq!(format(pi.clone(), "%.2e"), "3.14e0");
+        assert_eq!(format(pi.clone(), "%d%%"), "3%");
+        assert_eq!(format(pi.clone(), "x = %08.3f m"), "x = 0003.142 m");
+        assert_eq!(format(pi.clone(), "[%-6.1f]"), "[3.1   ]");
+        assert_eq!(format(pi, "[%+6.1f]"), "[  +3.1]");
+        assert_eq!(format(KalkValue::from(-0.4), "%d"), "0");
+        assert_eq!(format(KalkValue::from(-7), "%04d"), "-007");
+        assert_eq!(format(KalkValue::from(2.5), "%s"), "2.5");
+        assert_eq!(
+            format(
+                KalkValue::Vector(vec![KalkValue::from(3), KalkValue::from(2)]),
+                "%d rem %d"
+            ),
+            "3 rem 2"
+        );
+
+        assert!(format_with(KalkValue::from(1), KalkValue::from(2)).is_err());
+        assert!(format_with(KalkValue::from(1), KalkValue::String(String::from("%q"))).is_err());
+        assert!(format_with(
+            KalkValue::Vector(vec![KalkValue::from(1)]),
+            KalkValue::String(String::from("%d %d"))
+        )
+        .is_err());
+    }
+}
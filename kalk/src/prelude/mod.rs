@@ -19,12 +19,29 @@ pub use regular::funcs::*;
 #[cfg(not(feature = "rug"))]
 pub use regular::*;
 
-use crate::ast::Expr;
+#[cfg(feature = "finance")]
+mod finance;
+mod formatting;
+mod special;
+
+use crate::ast::{Expr, ExprSpan};
 use crate::interpreter;
+#[cfg(feature = "finance")]
+pub use finance::*;
+pub use formatting::*;
 pub use funcs::*;
+pub use special::*;
 
 // `i` is added in the symbol_table module, since for some reason it didn't work here.
-pub const INIT: &str = "unit deg = (rad*180)/pi";
+pub const INIT: &str = "unit deg = (rad*180)/pi
+unit grad = (rad*200)/pi
+unit turn = rad/(2pi)";
+
+/// The units that can be used for angles, eg. in `with grad { .. }`.
+pub const ANGLE_UNITS: [&str; 4] = ["rad", "deg", "grad", "turn"];
+
+/// Functions that are called without arguments, eg. `now()`.
+pub const NULLARY_FUNCS: [&str; 5] = ["now", "precision", "anglemode", "backend", "rand"];
 
 lazy_static! {
     pub static ref CONSTANTS: HashMap<&'static str, f64> = {
@@ -33,6 +50,7 @@ lazy_static! {
         m.insert("e", std::f64::consts::E);
         m.insert("tau", std::f64::consts::TAU);
         m.insert("phi", 1.618_033_988_749_895);
+        m.insert("inf", f64::INFINITY);
         m
     };
     pub static ref UNARY_FUNCS: HashMap<&'static str, (UnaryFuncInfo, &'static str)> = {
@@ -66,18 +84,29 @@ lazy_static! {
         m.insert("abs", (UnaryFuncInfo(abs, Other), ""));
         m.insert("arg", (UnaryFuncInfo(arg, Other), ""));
         m.insert("bitcmp", (UnaryFuncInfo(bitcmp, Other), ""));
+        m.insert("bits_needed", (UnaryFuncInfo(bits_needed, Other), ""));
         m.insert("cbrt", (UnaryFuncInfo(cbrt, Other), ""));
         m.insert("ceil", (UnaryFuncInfo(ceil, Other), ""));
+        m.insert("ceil_log2", (UnaryFuncInfo(ceil_log2, Other), ""));
+        m.insert("det", (UnaryFuncInfo(det, Other), ""));
+        m.insert("digamma", (UnaryFuncInfo(digamma, Other), ""));
+        m.insert("ψ", (UnaryFuncInfo(digamma, Other), ""));
+        m.insert("erf", (UnaryFuncInfo(erf, Other), ""));
+        m.insert("erfc", (UnaryFuncInfo(erfc, Other), ""));
         m.insert("exp", (UnaryFuncInfo(exp, Other), ""));
         m.insert("floor", (UnaryFuncInfo(floor, Other), ""));
+        m.insert("format", (UnaryFuncInfo(format_value, Other), ""));
         m.insert("frac", (UnaryFuncInfo(frac, Other), ""));
         m.insert("gamma", (UnaryFuncInfo(gamma, Other), ""));
         m.insert("Γ", (UnaryFuncInfo(gamma, Other), ""));
+        m.insert("inv", (UnaryFuncInfo(inv, Other), ""));
         m.insert("iverson", (UnaryFuncInfo(iverson, Other), ""));
         m.insert("Im", (UnaryFuncInfo(im, Other), ""));
         m.insert("ln", (UnaryFuncInfo(ln, Other), ""));
         m.insert("length", (UnaryFuncInfo(length, Other), ""));
         m.insert("log", (UnaryFuncInfo(log, Other), ""));
+        m.insert("log2", (UnaryFuncInfo(log2, Other), ""));
+        m.insert("pow2ceil", (UnaryFuncInfo(pow2ceil, Other), ""));
         m.insert("Re", (UnaryFuncInfo(re, Other), ""));
         m.insert("round", (UnaryFuncInfo(round, Other), ""));
         m.insert("sgn", (UnaryFuncInfo(sgn, Other), ""));
@@ -86,19 +115,31 @@ lazy_static! {
         m.insert("√", (UnaryFuncInfo(sqrt, Other), ""));
         m.insert("transpose", (UnaryFuncInfo(transpose, Other), ""));
         m.insert("trunc", (UnaryFuncInfo(trunc, Other), ""));
+        m.insert("W", (UnaryFuncInfo(lambert_w, Other), ""));
+        m.insert("zeta", (UnaryFuncInfo(zeta, Other), ""));
+        m.insert("ζ", (UnaryFuncInfo(zeta, Other), ""));
         m
     };
     pub static ref BINARY_FUNCS: HashMap<&'static str, (BinaryFuncInfo, &'static str)> = {
         let mut m = HashMap::new();
+        m.insert("atan2", (BinaryFuncInfo(atan2, InverseTrig), "rad"));
+        m.insert("Beta", (BinaryFuncInfo(beta, Other), ""));
         m.insert("bitand", (BinaryFuncInfo(bitand, Other), ""));
         m.insert("bitor", (BinaryFuncInfo(bitor, Other), ""));
         m.insert("bitxor", (BinaryFuncInfo(bitxor, Other), ""));
         m.insert("bitshift", (BinaryFuncInfo(bitshift, Other), ""));
+        m.insert("divmod", (BinaryFuncInfo(divmod, Other), ""));
+        m.insert("format", (BinaryFuncInfo(format_with, Other), ""));
         m.insert("hypot", (BinaryFuncInfo(hypot, Other), ""));
         m.insert("gcd", (BinaryFuncInfo(gcd, Other), ""));
         m.insert("lcm", (BinaryFuncInfo(lcm, Other), ""));
         m.insert("log", (BinaryFuncInfo(logx, Other), ""));
         m.insert("root", (BinaryFuncInfo(nth_root, Other), ""));
+        m.insert("ceil", (BinaryFuncInfo(ceil_to_decimals, Other), ""));
+        m.insert("floor", (BinaryFuncInfo(floor_to_decimals, Other), ""));
+        m.insert("round", (BinaryFuncInfo(round_to_decimals, Other), ""));
+        m.insert("roundto", (BinaryFuncInfo(round_to_multiple, Other), ""));
+        m.insert("trunc", (BinaryFuncInfo(trunc_to_decimals, Other), ""));
         m.insert("nCr", (BinaryFuncInfo(ncr, Other), ""));
         m.insert("comb", (BinaryFuncInfo(ncr, Other), ""));
         m.insert("nPr", (BinaryFuncInfo(npr, Other), ""));
@@ -107,15 +148,30 @@ lazy_static! {
     };
     pub static ref VECTOR_FUNCS: HashMap<&'static str, VectorFuncInfo> = {
         let mut m = HashMap::new();
+        m.insert("approx", VectorFuncInfo(approx, Other));
         m.insert("average", VectorFuncInfo(average, Other));
         m.insert("diag", VectorFuncInfo(diag, Other));
+        m.insert("linsolve", VectorFuncInfo(linsolve, Other));
         m.insert("matrix", VectorFuncInfo(matrix, Other));
         m.insert("max", VectorFuncInfo(max, Other));
         m.insert("min", VectorFuncInfo(min, Other));
         m.insert("perms", VectorFuncInfo(perms, Other));
         m.insert("permutations", VectorFuncInfo(perms, Other));
+        m.insert("polyexpand", VectorFuncInfo(polyexpand, Other));
+        m.insert("polyroots", VectorFuncInfo(polyroots, Other));
+        m.insert("polyval", VectorFuncInfo(polyval, Other));
         m.insert("prod", VectorFuncInfo(prod, Other));
         m.insert("sum", VectorFuncInfo(sum, Other));
+        #[cfg(feature = "finance")]
+        {
+            m.insert("compound", VectorFuncInfo(compound, Other));
+            m.insert("effect", VectorFuncInfo(effect, Other));
+            m.insert("fv", VectorFuncInfo(fv, Other));
+            m.insert("irr", VectorFuncInfo(irr, Other));
+            m.insert("npv", VectorFuncInfo(npv, Other));
+            m.insert("pmt", VectorFuncInfo(pmt, Other));
+            m.insert("pv", VectorFuncInfo(pv, Other));
+        }
         m
     };
 }
@@ -126,6 +182,21 @@ enum FuncType {
     Other,
 }
 
+/// The smallest k where 2^k is at least n, eg. 3 for 5.
+fn ceil_log2_of_word(n: u64) -> u32 {
+    64 - n.saturating_sub(1).leading_zeros()
+}
+
+/// The amount of bits needed to store an integer. Negative integers
+/// are stored in two's complement, so they also need a sign bit.
+fn bits_needed_for_word(n: i64, is_negative: bool) -> u32 {
+    if is_negative {
+        65 - (!n).leading_zeros()
+    } else {
+        (64 - (n as u64).leading_zeros()).max(1)
+    }
+}
+
 pub struct UnaryFuncInfo(fn(KalkValue) -> Result<KalkValue, KalkError>, FuncType);
 
 pub struct BinaryFuncInfo(
@@ -187,15 +258,41 @@ pub fn is_prelude_func(identifier: &str) -> bool {
         || identifier == "integrate"
         || identifier == "integral"
         || identifier == "∫"
+        || identifier == "findroot"
+        || identifier == "bisect"
+        || identifier == "lim"
+        || identifier == "maximize"
+        || identifier == "minimize"
+        || identifier == "randint"
+        || identifier == "randnormal"
+        || NULLARY_FUNCS.contains(&identifier)
+        || identifier == "derive"
+        || identifier == "taylor"
+        || identifier == "simplify"
         || UNARY_FUNCS.contains_key(identifier)
         || BINARY_FUNCS.contains_key(identifier)
         || VECTOR_FUNCS.contains_key(identifier)
 }
 
+/// Whether the function returns an angle in the angle unit that is in use, eg. `asin`.
+pub fn returns_angle(identifier: &str) -> bool {
+    matches!(
+        UNARY_FUNCS.get(identifier),
+        Some((UnaryFuncInfo(_, InverseTrig), _))
+    ) || matches!(
+        BINARY_FUNCS.get(identifier),
+        Some((BinaryFuncInfo(_, InverseTrig), _))
+    )
+}
+
 pub fn is_vector_func(identifier: &str) -> bool {
     VECTOR_FUNCS.contains_key(identifier)
 }
 
+pub fn is_angle_unit(identifier: &str) -> bool {
+    ANGLE_UNITS.contains(&identifier)
+}
+
 pub fn is_constant(identifier: &str) -> bool {
     CONSTANTS.contains_key(identifier)
 }
@@ -242,7 +339,7 @@ fn to_angle_unit(context: &mut interpreter::Context, x: KalkValue, angle_unit: &
         "rad" => x,
         _ => interpreter::convert_unit(
             context,
-            &Expr::Literal(x.to_f64()),
+            &Expr::Literal(x.to_f64(), ExprSpan::default()),
             Some(&String::from("rad")),
             Some(&angle_unit.to_string()),
         )
@@ -259,7 +356,7 @@ fn from_angle_unit(
         "rad" => x,
         _ => interpreter::convert_unit(
             context,
-            &Expr::Literal(x.to_f64()),
+            &Expr::Literal(x.to_f64(), ExprSpan::default()),
             Some(&angle_unit.to_string()),
             Some(&String::from("rad")),
         )
@@ -270,11 +367,15 @@ fn from_angle_unit(
 pub mod funcs {
     use std::cmp::Ordering;
 
+    /// The most amount of iterations `polyroots` does, in case the roots don't converge.
+    const POLYROOTS_MAX_ITERATIONS: usize = 500;
+
     #[cfg(not(feature = "rug"))]
     pub use super::regular::funcs::*;
     use super::special_funcs::factorial;
     #[cfg(feature = "rug")]
     pub use super::with_rug::funcs::*;
+    use crate::kalk_value::matrix::{permutation_sign, RealMatrix, REAL_MATRIX_THRESHOLD};
     use crate::{
         as_number_or_return, as_vector_or_return, errors::KalkError, float, kalk_value::KalkValue,
     };
@@ -337,6 +438,15 @@ pub mod funcs {
 
     pub fn acoth(x: KalkValue) -> Result<KalkValue, KalkError> {
         let (real, _, unit) = as_number_or_return!(x.clone());
+        if !x.has_imaginary() && real == 0f64 {
+            // The principal value, atanh(1/z) where 1/0 is taken as ∞
+            return Ok(KalkValue::Number(
+                float!(0),
+                float!(std::f64::consts::FRAC_PI_2),
+                unit,
+            ));
+        }
+
         if x.has_imaginary() || real <= 1f64 || real >= -1f64 {
             // 1 / z
             let (inv_real, inv_imaginary, inv_unit) =
@@ -396,6 +506,11 @@ pub mod funcs {
 
     pub fn asech(x: KalkValue) -> Result<KalkValue, KalkError> {
         let (real, _, unit) = as_number_or_return!(x.clone());
+        if !x.has_imaginary() && real == 0f64 {
+            // acosh(1/z) where 1/0 is taken as ∞
+            return Ok(KalkValue::Number(float!(f64::INFINITY), float!(0), unit));
+        }
+
         if x.has_imaginary() || real <= 0f64 || real > 1f64 {
             // 1/z
             let inv = KalkValue::from(1f64).div_without_unit(&x)?;
@@ -487,6 +602,37 @@ pub mod funcs {
         }
     }
 
+    /// Whether the values are within the tolerance of each other,
+    /// eg. approx(0.1 + 0.2, 0.3, 10^-9). Without a tolerance, they are compared like with ≈.
+    pub fn approx(x: KalkValue) -> Result<KalkValue, KalkError> {
+        let mut args = as_vector_or_return!(x);
+        let tolerance = match args.len() {
+            2 => None,
+            3 => {
+                let tolerance = args.pop().unwrap();
+                let is_negative_or_nan = tolerance.to_f64() < 0f64 || tolerance.to_f64().is_nan();
+                if tolerance.has_imaginary() || is_negative_or_nan {
+                    return Err(KalkError::Expected(String::from(
+                        "a tolerance that isn't negative",
+                    )));
+                }
+
+                Some(tolerance.to_f64())
+            }
+            count => {
+                return Err(KalkError::IncorrectAmountOfArguments(
+                    3,
+                    String::from("approx"),
+                    count,
+                ))
+            }
+        };
+        let y = args.pop().unwrap();
+        let x = args.pop().unwrap();
+
+        x.approx_eq_without_unit(&y, tolerance)
+    }
+
     pub fn average(x: KalkValue) -> Result<KalkValue, KalkError> {
         let values = as_vector_or_return!(x);
         let count = values.len() as i64;
@@ -513,6 +659,10 @@ pub mod funcs {
         Ok(KalkValue::Number(real.ceil(), imaginary.ceil(), unit))
     }
 
+    pub fn ceil_to_decimals(x: KalkValue, decimals: KalkValue) -> Result<KalkValue, KalkError> {
+        to_decimals(x, decimals, ceil)
+    }
+
     pub fn cos(x: KalkValue) -> Result<KalkValue, KalkError> {
         let (real, imaginary, unit) = as_number_or_return!(x);
 
@@ -597,12 +747,23 @@ pub mod funcs {
         }
     }
 
+    /// The Euclidean quotient and remainder, like with div and mod, eg. divmod(17, 5) = (3, 2).
+    pub fn divmod(x: KalkValue, y: KalkValue) -> Result<KalkValue, KalkError> {
+        let (quotient, remainder) = x.integer_division_without_unit(y, true)?;
+
+        Ok(KalkValue::Vector(vec![quotient, remainder]))
+    }
+
     pub fn floor(x: KalkValue) -> Result<KalkValue, KalkError> {
         let (real, imaginary, unit) = as_number_or_return!(x);
 
         Ok(KalkValue::Number(real.floor(), imaginary.floor(), unit))
     }
 
+    pub fn floor_to_decimals(x: KalkValue, decimals: KalkValue) -> Result<KalkValue, KalkError> {
+        to_decimals(x, decimals, floor)
+    }
+
     pub fn frac(x: KalkValue) -> Result<KalkValue, KalkError> {
         let (real, imaginary, unit) = as_number_or_return!(x);
 
@@ -723,6 +884,16 @@ pub mod funcs {
         }
     }
 
+    pub fn log2(x: KalkValue) -> Result<KalkValue, KalkError> {
+        let (real, _, unit) = as_number_or_return!(x.clone());
+        if x.has_imaginary() || real < 0f64 {
+            // ln(z) / ln(2)
+            ln(x)?.div_without_unit(&KalkValue::from(2f64.ln()))
+        } else {
+            Ok(KalkValue::Number(real.log2(), float!(0), unit))
+        }
+    }
+
     pub fn logx(x: KalkValue, y: KalkValue) -> Result<KalkValue, KalkError> {
         let (real, _, unit) = as_number_or_return!(x.clone());
         let (real_rhs, _, _) = as_number_or_return!(y.clone());
@@ -757,6 +928,219 @@ pub mod funcs {
         })
     }
 
+    /// Solve the linear system Ax = b, given either A and b, or an augmented
+    /// matrix where b is the last column, eg. `linsolve([2, 1, 3; 1, -1, 0])`.
+    pub fn linsolve(x: KalkValue) -> Result<KalkValue, KalkError> {
+        let mut args = as_vector_or_return!(x);
+        let (a, b) = match args.len() {
+            1 => {
+                let mut a = match args.pop().unwrap() {
+                    KalkValue::Matrix(rows) => rows,
+                    value => {
+                        return Err(KalkError::UnexpectedType(
+                            value.get_type_name(),
+                            vec![String::from("matrix")],
+                        ))
+                    }
+                };
+                let b = a.iter_mut().filter_map(|row| row.pop()).collect();
+
+                (a, b)
+            }
+            2 => {
+                let b = match args.pop().unwrap() {
+                    KalkValue::Vector(values) => values,
+                    // A matrix with a single column
+                    KalkValue::Matrix(rows) if rows.iter().all(|row| row.len() == 1) => {
+                        rows.into_iter().flatten().collect()
+                    }
+                    value => {
+                        return Err(KalkError::UnexpectedType(
+                            value.get_type_name(),
+                            vec![String::from("vector")],
+                        ))
+                    }
+                };
+                let a = match args.pop().unwrap() {
+                    KalkValue::Matrix(rows) => rows,
+                    value => {
+                        return Err(KalkError::UnexpectedType(
+                            value.get_type_name(),
+                            vec![String::from("matrix")],
+                        ))
+                    }
+                };
+
+                (a, b)
+            }
+            count => {
+                return Err(KalkError::IncorrectAmountOfArguments(
+                    2,
+                    String::from("linsolve"),
+                    count,
+                ))
+            }
+        };
+
+        let n = a.len();
+        if b.len() != n || a.iter().any(|row| row.len() != n) {
+            return Err(KalkError::IncompatibleVectorsMatrixes);
+        }
+
+        let (lu, permutation) = lu_decompose(a)?;
+
+        Ok(KalkValue::Vector(lu_solve(&lu, &permutation, &b)?))
+    }
+
+    /// The determinant of a square matrix, eg. det([1, 2; 3, 4]) = -2.
+    pub fn det(x: KalkValue) -> Result<KalkValue, KalkError> {
+        let rows = as_square_matrix(x)?;
+        // Matrices with units aren't converted, so the regular arithmetic keeps the units
+        if rows.len() >= REAL_MATRIX_THRESHOLD {
+            if let Some(matrix) = RealMatrix::from_rows(&rows) {
+                return Ok(KalkValue::Number(matrix.determinant(), float!(0f64), None));
+            }
+        }
+
+        let (lu, permutation) = match lu_decompose(rows) {
+            Ok(decomposition) => decomposition,
+            Err(KalkError::SingularMatrix) => return Ok(KalkValue::from(0f64)),
+            Err(err) => return Err(err),
+        };
+
+        let mut result = KalkValue::from(permutation_sign(&permutation));
+        for (i, row) in lu.iter().enumerate() {
+            result = result.mul_without_unit(&row[i])?;
+        }
+
+        Ok(result)
+    }
+
+    /// The inverse of a square matrix, eg. inv([2, 0; 0, 4]) = [0.5, 0; 0, 0.25].
+    pub fn inv(x: KalkValue) -> Result<KalkValue, KalkError> {
+        let rows = as_square_matrix(x)?;
+        let n = rows.len();
+        // Matrices with units aren't converted, so the regular arithmetic keeps the units
+        if n >= REAL_MATRIX_THRESHOLD {
+            if let Some(matrix) = RealMatrix::from_rows(&rows) {
+                return match matrix.inverse() {
+                    Some(inverse) => Ok(KalkValue::Matrix(inverse.into_rows())),
+                    None => Err(KalkError::SingularMatrix),
+                };
+            }
+        }
+
+        // Solve for every column of the identity matrix
+        let (lu, permutation) = lu_decompose(rows)?;
+        let mut result = vec![Vec::with_capacity(n); n];
+        for column in 0..n {
+            let mut identity_column = vec![KalkValue::from(0f64); n];
+            identity_column[column] = KalkValue::from(1f64);
+            let solution = lu_solve(&lu, &permutation, &identity_column)?;
+            for (row, value) in result.iter_mut().zip(solution) {
+                row.push(value);
+            }
+        }
+
+        Ok(KalkValue::Matrix(result))
+    }
+
+    fn as_square_matrix(x: KalkValue) -> Result<Vec<Vec<KalkValue>>, KalkError> {
+        match x {
+            KalkValue::Matrix(rows) if rows.iter().all(|row| row.len() == rows.len()) => Ok(rows),
+            KalkValue::Matrix(_) => Err(KalkError::IncompatibleVectorsMatrixes),
+            value => Err(KalkError::UnexpectedType(
+                value.get_type_name(),
+                vec![String::from("matrix")],
+            )),
+        }
+    }
+
+    /// Solve LUx = Pb, with the result of `lu_decompose`.
+    fn lu_solve(
+        lu: &[Vec<KalkValue>],
+        permutation: &[usize],
+        b: &[KalkValue],
+    ) -> Result<Vec<KalkValue>, KalkError> {
+        let n = lu.len();
+
+        // Forward substitution, Ly = Pb, where L has ones on the diagonal
+        let mut y: Vec<KalkValue> = Vec::with_capacity(n);
+        for (i, (row, original_index)) in lu.iter().zip(permutation).enumerate() {
+            let mut value = b[*original_index].clone();
+            for (l, y_value) in row[..i].iter().zip(&y) {
+                value = value.sub_without_unit(&l.clone().mul_without_unit(y_value)?)?;
+            }
+
+            y.push(value);
+        }
+
+        // Back substitution, Ux = y
+        let mut result = vec![KalkValue::from(0f64); n];
+        for i in (0..n).rev() {
+            let mut value = y[i].clone();
+            for (u, x_value) in lu[i][i + 1..].iter().zip(&result[i + 1..]) {
+                value = value.sub_without_unit(&u.clone().mul_without_unit(x_value)?)?;
+            }
+
+            result[i] = value.div_without_unit(&lu[i][i])?;
+        }
+
+        Ok(result)
+    }
+
+    /// Decompose a square matrix into PA = LU with partial pivoting. L and U are
+    /// returned in the same matrix, since the diagonal of L only consists of ones,
+    /// together with the original row index of every row.
+    fn lu_decompose(
+        mut rows: Vec<Vec<KalkValue>>,
+    ) -> Result<(Vec<Vec<KalkValue>>, Vec<usize>), KalkError> {
+        let n = rows.len();
+        let mut largest = 0f64;
+        for value in rows.iter().flatten() {
+            largest = largest.max(abs(value.clone())?.to_f64());
+        }
+
+        // Pivots this close to zero are the result of rounding errors
+        let tolerance = largest * n as f64 * f64::EPSILON;
+        let mut permutation: Vec<usize> = (0..n).collect();
+        for k in 0..n {
+            // Use the row with the largest value in the column as the pivot,
+            // to keep the rounding errors small
+            let mut pivot_index = k;
+            let mut pivot_abs = abs(rows[k][k].clone())?.to_f64();
+            for (i, row) in rows.iter().enumerate().skip(k + 1) {
+                let value_abs = abs(row[k].clone())?.to_f64();
+                if value_abs > pivot_abs {
+                    pivot_index = i;
+                    pivot_abs = value_abs;
+                }
+            }
+
+            if pivot_abs <= tolerance {
+                return Err(KalkError::SingularMatrix);
+            }
+
+            rows.swap(k, pivot_index);
+            permutation.swap(k, pivot_index);
+
+            let (upper, lower) = rows.split_at_mut(k + 1);
+            let pivot_row = &upper[k];
+            for row in lower {
+                let factor = row[k].clone().div_without_unit(&pivot_row[k])?;
+                for (value, pivot_value) in row[k + 1..].iter_mut().zip(&pivot_row[k + 1..]) {
+                    *value = value
+                        .clone()
+                        .sub_without_unit(&factor.clone().mul_without_unit(pivot_value)?)?;
+                }
+
+                row[k] = factor;
+            }
+        }
+
+        Ok((rows, permutation))
+    }
+
     pub fn matrix(x: KalkValue) -> Result<KalkValue, KalkError> {
         let rows = as_vector_or_return!(x);
         let column_width =
@@ -882,6 +1266,151 @@ pub mod funcs {
         }
     }
 
+    /// Get the coefficients of the polynomial that has the given roots,
+    /// eg. `polyexpand(1, 2)` gives (1, -3, 2) for (x - 1)(x - 2) = x² - 3x + 2.
+    pub fn polyexpand(x: KalkValue) -> Result<KalkValue, KalkError> {
+        let roots = as_vector_or_return!(x);
+        let mut coefficients = vec![KalkValue::from(1f64)];
+        for root in roots {
+            // Multiply the polynomial by (x - root)
+            let mut next = coefficients.clone();
+            next.push(KalkValue::from(0f64));
+            for (i, coefficient) in coefficients.iter().enumerate() {
+                next[i + 1] = next[i + 1]
+                    .clone()
+                    .sub_without_unit(&coefficient.clone().mul_without_unit(&root)?)?;
+            }
+
+            coefficients = next;
+        }
+
+        Ok(KalkValue::Vector(coefficients))
+    }
+
+    /// Find every root of a polynomial, including the complex ones, with the Durand-Kerner
+    /// method, eg. `polyroots(1, -3, 2)` for x² - 3x + 2. The coefficients start with the
+    /// highest degree.
+    pub fn polyroots(x: KalkValue) -> Result<KalkValue, KalkError> {
+        let values = as_vector_or_return!(x);
+        let mut coefficients = Vec::with_capacity(values.len());
+        let mut has_imaginary = false;
+        for value in values {
+            has_imaginary |= value.has_imaginary();
+            let (real, imaginary, _) = as_number_or_return!(value);
+            coefficients.push(KalkValue::Number(real, imaginary, None));
+        }
+
+        // Leading zeros don't change the polynomial
+        let leading_index = coefficients
+            .iter()
+            .position(|coefficient| coefficient.has_real() || coefficient.has_imaginary());
+        let coefficients = match leading_index {
+            Some(i) if i + 1 < coefficients.len() => coefficients.split_off(i),
+            _ => {
+                return Err(KalkError::Expected(String::from(
+                    "a polynomial of at least degree 1",
+                )))
+            }
+        };
+
+        let mut monic = Vec::with_capacity(coefficients.len());
+        for coefficient in &coefficients {
+            monic.push(coefficient.clone().div_without_unit(&coefficients[0])?);
+        }
+
+        // Start at the powers of a complex number that is neither real nor a root of unity
+        let degree = monic.len() - 1;
+        let seed = KalkValue::Number(float!(0.4), float!(0.9), None);
+        let mut roots = vec![KalkValue::from(1f64)];
+        while roots.len() < degree {
+            let next = roots.last().unwrap().clone().mul_without_unit(&seed)?;
+            roots.push(next);
+        }
+
+        for _ in 0..POLYROOTS_MAX_ITERATIONS {
+            let mut converged = true;
+            for i in 0..degree {
+                let mut denominator = KalkValue::from(1f64);
+                for (j, other) in roots.iter().enumerate() {
+                    if j != i {
+                        denominator = denominator
+                            .mul_without_unit(&roots[i].clone().sub_without_unit(other)?)?;
+                    }
+                }
+
+                let change = polynomial_at(&monic, &roots[i])?.div_without_unit(&denominator)?;
+                let tolerance = 1e-15 * abs(roots[i].clone())?.to_f64().max(1f64);
+                if abs(change.clone())?.to_f64() > tolerance {
+                    converged = false;
+                }
+
+                roots[i] = roots[i].clone().sub_without_unit(&change)?;
+            }
+
+            if converged {
+                break;
+            }
+        }
+
+        let mut result = Vec::with_capacity(degree);
+        for root in roots {
+            let magnitude = abs(root.clone())?.to_f64().max(1f64);
+            let (real, imaginary, _) = as_number_or_return!(root);
+
+            // Real polynomials get tiny imaginary parts from rounding errors
+            if !has_imaginary && imaginary.clone().abs() < 1e-10 * magnitude {
+                result.push(KalkValue::Number(real, float!(0), None));
+            } else {
+                result.push(KalkValue::Number(real, imaginary, None));
+            }
+        }
+
+        result.sort_by(|a, b| {
+            (a.to_f64(), a.imaginary_to_f64())
+                .partial_cmp(&(b.to_f64(), b.imaginary_to_f64()))
+                .unwrap_or(Ordering::Equal)
+        });
+
+        Ok(KalkValue::Vector(result))
+    }
+
+    /// Evaluate a polynomial at a value, or at every value in a vector, eg. `polyval((1, -3, 2), 4)`
+    /// for x² - 3x + 2 where x = 4. The coefficients start with the highest degree.
+    pub fn polyval(x: KalkValue) -> Result<KalkValue, KalkError> {
+        let mut args = as_vector_or_return!(x);
+        if args.len() != 2 {
+            return Err(KalkError::IncorrectAmountOfArguments(
+                2,
+                String::from("polyval"),
+                args.len(),
+            ));
+        }
+
+        let point = args.pop().unwrap();
+        let coefficients = args.pop().unwrap();
+        let coefficients = as_vector_or_return!(coefficients);
+        if let KalkValue::Vector(points) = point {
+            let mut values = Vec::with_capacity(points.len());
+            for point in points {
+                values.push(polynomial_at(&coefficients, &point)?);
+            }
+
+            Ok(KalkValue::Vector(values))
+        } else {
+            polynomial_at(&coefficients, &point)
+        }
+    }
+
+    /// Evaluate a polynomial with Horner's method.
+    fn polynomial_at(coefficients: &[KalkValue], x: &KalkValue) -> Result<KalkValue, KalkError> {
+        let mut result = KalkValue::from(0f64);
+        for coefficient in coefficients {
+            result = result.mul_without_unit(x)?.add_without_unit(coefficient)?;
+        }
+
+        Ok(result)
+    }
+
     pub fn prod(x: KalkValue) -> Result<KalkValue, KalkError> {
         let values = as_vector_or_return!(x);
         let mut prod = KalkValue::from(1f64);
@@ -902,6 +1431,19 @@ pub mod funcs {
         Ok(KalkValue::Number(real.round(), imaginary.round(), unit))
     }
 
+    pub fn round_to_decimals(x: KalkValue, decimals: KalkValue) -> Result<KalkValue, KalkError> {
+        to_decimals(x, decimals, round)
+    }
+
+    /// Round to the closest multiple, eg. roundto(17, 5) = 15.
+    pub fn round_to_multiple(x: KalkValue, multiple: KalkValue) -> Result<KalkValue, KalkError> {
+        if !multiple.has_real() && !multiple.has_imaginary() {
+            return Err(KalkError::Expected(String::from("a multiple other than 0")));
+        }
+
+        round(x.div_without_unit(&multiple)?)?.mul_without_unit(&multiple)
+    }
+
     pub fn sec(x: KalkValue) -> Result<KalkValue, KalkError> {
         KalkValue::from(1f64).div_without_unit(&cos(x)?)
     }
@@ -910,6 +1452,47 @@ pub mod funcs {
         KalkValue::from(1f64).div_without_unit(&cosh(x)?)
     }
 
+    /// The gamma function of a number that isn't real, which doesn't have any poles.
+    /// It uses the same Lanczos approximation as the real gamma function without rug,
+    /// and the reflection formula Γ(z)Γ(1 - z) = π / sin(πz) when the real part is below 1/2.
+    pub(crate) fn complex_gamma(z: KalkValue) -> Result<KalkValue, KalkError> {
+        let pi = KalkValue::from(std::f64::consts::PI);
+        if z.to_f64() < 0.5f64 {
+            let reflected = complex_gamma(KalkValue::from(1f64).sub_without_unit(&z)?)?;
+            let sin_pi_z = sin(pi.clone().mul_without_unit(&z)?)?;
+
+            return pi.div_without_unit(&sin_pi_z.mul_without_unit(&reflected)?);
+        }
+
+        let coefficients = [
+            676.5203681218851,
+            -1259.1392167224028,
+            771.3234287776531,
+            -176.6150291621406,
+            12.507343278686905,
+            -0.13857109526572012,
+            9.984369578019572e-6,
+            1.5056327351493116e-7,
+        ];
+        let mut a = KalkValue::from(0.9999999999998099f64);
+        for (k, coefficient) in coefficients.iter().enumerate() {
+            let denominator = z.clone().add_without_unit(&KalkValue::from(k as f64))?;
+            a =
+                a.add_without_unit(&KalkValue::from(*coefficient).div_without_unit(&denominator)?)?;
+        }
+
+        let t = z.clone().add_without_unit(&KalkValue::from(6.5f64))?;
+        let power = t
+            .clone()
+            .pow_without_unit(&z.sub_without_unit(&KalkValue::from(0.5f64))?)?;
+        let sqrt_two_pi = KalkValue::from((2f64 * std::f64::consts::PI).sqrt());
+
+        sqrt_two_pi
+            .mul_without_unit(&power)?
+            .mul_without_unit(&exp(t.mul_without_unit(&KalkValue::from(-1f64))?)?)?
+            .mul_without_unit(&a)
+    }
+
     pub fn sin(x: KalkValue) -> Result<KalkValue, KalkError> {
         let (real, imaginary, unit) = as_number_or_return!(x);
         Ok(KalkValue::Number(
@@ -1058,6 +1641,33 @@ pub mod funcs {
         Ok(KalkValue::Number(real.trunc(), imaginary.trunc(), unit))
     }
 
+    pub fn trunc_to_decimals(x: KalkValue, decimals: KalkValue) -> Result<KalkValue, KalkError> {
+        to_decimals(x, decimals, trunc)
+    }
+
+    /// Round to an amount of decimals, eg. round(π, 2) = 3.14, where a negative amount
+    /// rounds to eg. tens, eg. round(1234, -1) = 1230. The value is scaled by a power of ten,
+    /// which is exact for the floats of the precision in use, rather than rounded as an f64.
+    fn to_decimals(
+        x: KalkValue,
+        decimals: KalkValue,
+        rounding: fn(KalkValue) -> Result<KalkValue, KalkError>,
+    ) -> Result<KalkValue, KalkError> {
+        let amount = decimals.to_f64();
+        if decimals.has_imaginary() || amount.fract() != 0f64 || !amount.is_finite() {
+            return Err(KalkError::Expected(String::from(
+                "an integer amount of decimals",
+            )));
+        }
+
+        let scale = KalkValue::from(10f64).pow_without_unit(&KalkValue::from(amount.abs()))?;
+        if amount < 0f64 {
+            rounding(x.div_without_unit(&scale)?)?.mul_without_unit(&scale)
+        } else {
+            rounding(x.mul_without_unit(&scale)?)?.div_without_unit(&scale)
+        }
+    }
+
     pub fn ncr(x: KalkValue, y: KalkValue) -> Result<KalkValue, KalkError> {
         factorial(x.clone())?.div_without_unit(
             &factorial(y.clone())?.mul_without_unit(&factorial(x.sub_without_unit(&y)?)?)?,
@@ -1099,6 +1709,14 @@ mod tests {
             (abs, (-3f64, 4f64), (5f64, 0f64)),
             (abs, (3f64, -4f64), (5f64, 0f64)),
             (abs, (-3f64, 0f64), (3f64, 0f64)),
+            (log2, (8f64, 0f64), (3f64, 0f64)),
+            (ceil_log2, (5f64, 0f64), (3f64, 0f64)),
+            (ceil_log2, (1f64, 0f64), (0f64, 0f64)),
+            (pow2ceil, (5f64, 0f64), (8f64, 0f64)),
+            (pow2ceil, (1024f64, 0f64), (1024f64, 0f64)),
+            (bits_needed, (255f64, 0f64), (8f64, 0f64)),
+            (bits_needed, (0f64, 0f64), (1f64, 0f64)),
+            (bits_needed, (-129f64, 0f64), (9f64, 0f64)),
         ];
 
         for (i, (func, input, expected_output)) in in_out.iter().enumerate() {
@@ -1121,6 +1739,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_binary_funcs() {
         let in_out = vec![
             (
@@ -1135,6 +1754,13 @@ mod tests {
             (gcd, ((12f64, -8f64), (6f64, 4f64)), (2f64, 0f64)),
             (lcm, ((12f64, -8f64), (6f64, 4f64)), (52f64, 0f64)),
             (lcm, ((1f64, -2f64), (3f64, 1f64)), (5f64, -5f64)),
+            (atan2, ((1f64, 0f64), (1f64, 0f64)), (0.7853982f64, 0f64)),
+            (atan2, ((1f64, 0f64), (-1f64, 0f64)), (2.3561945f64, 0f64)),
+            (atan2, ((-1f64, 0f64), (-1f64, 0f64)), (-2.3561945f64, 0f64)),
+            (atan2, ((0f64, 0f64), (-2f64, 0f64)), (3.1415927f64, 0f64)),
+            (atan2, ((-3f64, 0f64), (0f64, 0f64)), (-1.5707963f64, 0f64)),
+            (hypot, ((3f64, 0f64), (4f64, 0f64)), (5f64, 0f64)),
+            (hypot, ((3f64, 4f64), (0f64, 0f64)), (5f64, 0f64)),
         ];
 
         for (i, (func, input, expected_output)) in in_out.iter().enumerate() {
@@ -1159,6 +1785,57 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_gamma() {
+        use crate::prelude::special_funcs::factorial;
+
+        let in_out = vec![
+            (5f64, 24f64),
+            (0.5f64, 1.7724539f64),
+            (-0.5f64, -3.5449077f64),
+            (-1.5f64, 2.3632718f64),
+            (-2.5f64, -0.9453087f64),
+        ];
+        for (input, expected_output) in in_out {
+            let actual_output = gamma(val(input)).unwrap().to_f64();
+            println!(
+                "gamma({}) | expected: {}, got: {}",
+                input, expected_output, actual_output
+            );
+            assert!(cmp(expected_output, actual_output));
+        }
+
+        assert!(cmp(factorial(val(-0.5f64)).unwrap().to_f64(), 1.7724539f64));
+        assert!(cmp(factorial(val(3f64)).unwrap().to_f64(), 6f64));
+        assert_eq!(
+            gamma(val(-2f64)),
+            Err(KalkError::Pole(String::from("gamma(-2)")))
+        );
+        assert!(gamma(val(0f64)).is_err());
+        assert_eq!(
+            factorial(val(-1f64)),
+            Err(KalkError::Pole(String::from("(-1)!")))
+        );
+
+        // Values with an imaginary part aren't poles, even if the real part is
+        let complex_in_out = vec![
+            ((0f64, 1f64), (-0.1549498283f64, -0.4980156681f64)),
+            ((-2f64, 1f64), (0.1339097176f64, -0.0962865153f64)),
+            ((3f64, 2f64), (-0.4226372863f64, 0.8718142557f64)),
+        ];
+        for ((real, imaginary), expected_output) in complex_in_out {
+            let input = KalkValue::Number(float!(real), float!(imaginary), None);
+            let actual_output = gamma(input.clone()).unwrap();
+            assert!(cmp(expected_output.0, actual_output.to_f64()));
+            assert!(cmp(expected_output.1, actual_output.imaginary_to_f64()));
+
+            let input_minus_one = input.sub_without_unit(&val(1f64)).unwrap();
+            let actual_output = factorial(input_minus_one).unwrap();
+            assert!(cmp(expected_output.0, actual_output.to_f64()));
+            assert!(cmp(expected_output.1, actual_output.imaginary_to_f64()));
+        }
+    }
+
     #[test]
     fn test_perms() {
         let vecs = vec![
@@ -1220,6 +1897,191 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_linsolve() {
+        fn to_matrix(rows: Vec<Vec<f64>>) -> KalkValue {
+            KalkValue::Matrix(
+                rows.into_iter()
+                    .map(|row| row.into_iter().map(val).collect())
+                    .collect(),
+            )
+        }
+
+        fn to_f64s(x: KalkValue) -> Vec<f64> {
+            if let KalkValue::Vector(values) = x {
+                values.iter().map(|value| value.to_f64()).collect()
+            } else {
+                panic!("Expected a vector");
+            }
+        }
+
+        // The first pivot is zero, so the rows need to be swapped
+        let a = to_matrix(vec![
+            vec![0f64, 2f64, 5f64],
+            vec![1f64, 1f64, 1f64],
+            vec![2f64, 5f64, -1f64],
+        ]);
+        let b = KalkValue::Vector(vec![val(-4f64), val(6f64), val(27f64)]);
+        let result = to_f64s(linsolve(KalkValue::Vector(vec![a, b])).unwrap());
+        for (value, expected) in result.iter().zip([5f64, 3f64, -2f64]) {
+            assert!(cmp(*value, expected));
+        }
+
+        let augmented = to_matrix(vec![vec![2f64, 1f64, 3f64], vec![1f64, -1f64, 0f64]]);
+        let result = to_f64s(linsolve(KalkValue::Vector(vec![augmented])).unwrap());
+        assert!(cmp(result[0], 1f64));
+        assert!(cmp(result[1], 1f64));
+
+        let singular = to_matrix(vec![vec![1f64, 2f64], vec![2f64, 4f64]]);
+        let b = KalkValue::Vector(vec![val(1f64), val(2f64)]);
+        assert_eq!(
+            linsolve(KalkValue::Vector(vec![singular, b])),
+            Err(KalkError::SingularMatrix)
+        );
+
+        let not_square = to_matrix(vec![vec![1f64, 2f64, 3f64], vec![4f64, 5f64, 6f64]]);
+        let b = KalkValue::Vector(vec![val(1f64), val(2f64)]);
+        assert_eq!(
+            linsolve(KalkValue::Vector(vec![not_square, b])),
+            Err(KalkError::IncompatibleVectorsMatrixes)
+        );
+    }
+
+    #[test]
+    fn test_det_and_inv() {
+        // Matrices with 2 on the diagonal and -1 next to it have the determinant n + 1.
+        // The large one uses `RealMatrix`, and the small one the regular arithmetic.
+        for n in [3, 40] {
+            let mut rows: Vec<Vec<KalkValue>> = (0..n)
+                .map(|i| {
+                    (0..n)
+                        .map(|j| {
+                            if i == j {
+                                val(2f64)
+                            } else if i + 1 == j || j + 1 == i {
+                                val(-1f64)
+                            } else {
+                                val(0f64)
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            let a = KalkValue::Matrix(rows.clone());
+            assert!(cmp(det(a.clone()).unwrap().to_f64(), n as f64 + 1f64));
+
+            let inverse = inv(a.clone()).unwrap();
+            let product = a.mul_without_unit(&inverse).unwrap();
+            if let KalkValue::Matrix(product_rows) = product {
+                for (i, row) in product_rows.iter().enumerate() {
+                    for (j, value) in row.iter().enumerate() {
+                        assert!(cmp(value.to_f64(), if i == j { 1f64 } else { 0f64 }));
+                    }
+                }
+            } else {
+                panic!("Expected a matrix");
+            }
+
+            // Swapping two rows changes the sign
+            rows.swap(0, 1);
+            assert!(cmp(
+                det(KalkValue::Matrix(rows)).unwrap().to_f64(),
+                -(n as f64 + 1f64)
+            ));
+        }
+
+        // Units are kept by both ways of calculating it
+        for n in [3, 40] {
+            let rows: Vec<Vec<KalkValue>> = (0..n)
+                .map(|i| {
+                    (0..n)
+                        .map(|j| {
+                            let value = if i == j { 2f64 } else { 0f64 };
+                            KalkValue::Number(float!(value), float!(0f64), Some(String::from("km")))
+                        })
+                        .collect()
+                })
+                .collect();
+            let a = KalkValue::Matrix(rows);
+            assert_eq!(
+                det(a.clone()).unwrap().get_unit(),
+                Some(&String::from("km"))
+            );
+            if let KalkValue::Matrix(inverse_rows) = inv(a).unwrap() {
+                assert!(cmp(inverse_rows[1][1].to_f64(), 0.5f64));
+                assert_eq!(inverse_rows[1][1].get_unit(), Some(&String::from("km")));
+            } else {
+                panic!("Expected a matrix");
+            }
+        }
+
+        let singular =
+            KalkValue::Matrix(vec![vec![val(1f64), val(2f64)], vec![val(2f64), val(4f64)]]);
+        assert_eq!(det(singular.clone()).unwrap().to_f64(), 0f64);
+        assert_eq!(inv(singular), Err(KalkError::SingularMatrix));
+        assert_eq!(
+            det(KalkValue::Matrix(vec![vec![val(1f64), val(2f64)]])),
+            Err(KalkError::IncompatibleVectorsMatrixes)
+        );
+    }
+
+    #[test]
+    fn test_polyroots() {
+        fn roots(coefficients: Vec<f64>) -> Vec<(f64, f64)> {
+            let values = coefficients.into_iter().map(val).collect();
+            if let KalkValue::Vector(roots) = polyroots(KalkValue::Vector(values)).unwrap() {
+                roots
+                    .iter()
+                    .map(|root| (root.to_f64(), root.imaginary_to_f64()))
+                    .collect()
+            } else {
+                panic!("Expected a vector");
+            }
+        }
+
+        let in_out = vec![
+            (vec![2f64, -4f64], vec![(2f64, 0f64)]),
+            (vec![1f64, -3f64, 2f64], vec![(1f64, 0f64), (2f64, 0f64)]),
+            (
+                vec![0f64, 1f64, 0f64, 4f64],
+                vec![(0f64, -2f64), (0f64, 2f64)],
+            ),
+            (
+                vec![1f64, -6f64, 11f64, -6f64],
+                vec![(1f64, 0f64), (2f64, 0f64), (3f64, 0f64)],
+            ),
+            (
+                vec![1f64, 0f64, 0f64, -1f64],
+                vec![
+                    (-0.5f64, -0.8660254f64),
+                    (-0.5f64, 0.8660254f64),
+                    (1f64, 0f64),
+                ],
+            ),
+        ];
+
+        for (input, expected_output) in in_out {
+            let output = roots(input);
+            assert_eq!(output.len(), expected_output.len());
+            for (root, expected) in output.iter().zip(expected_output) {
+                assert!(cmp(root.0, expected.0) && cmp(root.1, expected.1));
+            }
+        }
+
+        assert!(polyroots(KalkValue::Vector(vec![val(0f64), val(3f64)])).is_err());
+
+        // Purely imaginary roots keep their sign and imaginary unit when they are shown
+        for (input, expected_output) in [
+            (vec![1f64, 0f64, 1f64], "(-i, i)"),
+            (vec![1f64, 0f64, 4f64], "(-2i, 2i)"),
+            (vec![1f64, -2f64, 5f64], "(1 - 2i, 1 + 2i)"),
+        ] {
+            let values = input.into_iter().map(val).collect();
+            let output = polyroots(KalkValue::Vector(values)).unwrap();
+            assert_eq!(output.to_string_pretty(), expected_output);
+        }
+    }
+
     #[test]
     #[allow(clippy::approx_constant)]
     fn test_trig_funcs() {
@@ -1479,4 +2341,65 @@ mod tests {
             assert!(cmp(expected_output.1, actual_output.imaginary_to_f64()));
         }
     }
+
+    #[test]
+    #[allow(clippy::approx_constant)]
+    fn test_hyperbolic_branch_cuts() {
+        // Values on and around the branch cuts, compared with mpmath. Infinities are
+        // compared exactly, since they are the expected results at the branch points.
+        let inf = f64::INFINITY;
+        let in_out = vec![
+            (
+                atanh as fn(KalkValue) -> Result<KalkValue, KalkError>,
+                (2f64, 0f64),
+                (0.5493061f64, -1.5707963f64),
+            ),
+            (atanh, (-2f64, 0f64), (-0.5493061f64, 1.5707963f64)),
+            (atanh, (1f64, 0f64), (inf, 0f64)),
+            (atanh, (-1f64, 0f64), (-inf, 0f64)),
+            (atanh, (0f64, 1f64), (0f64, 0.7853982f64)),
+            (acoth, (0.5f64, 0f64), (0.5493061f64, -1.5707963f64)),
+            (acoth, (0f64, 0f64), (0f64, 1.5707963f64)),
+            (acoth, (1f64, 0f64), (inf, 0f64)),
+            (acoth, (-1f64, 0f64), (-inf, 0f64)),
+            (acoth, (0f64, 0.3f64), (0f64, -1.2793395f64)),
+            (acoth, (0f64, 2f64), (0f64, -0.4636476f64)),
+            (acosh, (1f64, 0f64), (0f64, 0f64)),
+            (acosh, (0f64, 0f64), (0f64, 1.5707963f64)),
+            (acosh, (-1f64, 0f64), (0f64, 3.1415927f64)),
+            (acosh, (-2f64, 0f64), (1.3169579f64, 3.1415927f64)),
+            (asech, (2f64, 0f64), (0f64, 1.0471976f64)),
+            (asech, (1f64, 0f64), (0f64, 0f64)),
+            (asech, (0f64, 0f64), (inf, 0f64)),
+            (asech, (-0.5f64, 0f64), (1.3169579f64, 3.1415927f64)),
+            (asech, (-1f64, 0f64), (0f64, 3.1415927f64)),
+            (asech, (0f64, 0.5f64), (1.4436355f64, -1.5707963f64)),
+            (asinh, (0f64, 1f64), (0f64, 1.5707963f64)),
+            (asinh, (0f64, -1f64), (0f64, -1.5707963f64)),
+            (asinh, (0f64, 2f64), (1.3169579f64, 1.5707963f64)),
+            (asinh, (0f64, -2f64), (-1.3169579f64, -1.5707963f64)),
+            (acsch, (0f64, 0.5f64), (-1.3169579f64, -1.5707963f64)),
+            (acsch, (0f64, 2f64), (0f64, -0.5235988f64)),
+            (acsch, (0f64, 0f64), (inf, 0f64)),
+        ];
+
+        for (i, (func, input, expected_output)) in in_out.iter().enumerate() {
+            let actual_output =
+                func(KalkValue::Number(float!(input.0), float!(input.1), None)).unwrap();
+            let real = actual_output.to_f64();
+            let imaginary = actual_output.imaginary_to_f64();
+            println!(
+                "{} | expected: {}, {}, got: {}, {}",
+                i, expected_output.0, expected_output.1, real, imaginary
+            );
+
+            if expected_output.0.is_infinite() {
+                assert_eq!(expected_output.0, real);
+            } else {
+                assert!(cmp(expected_output.0, real));
+            }
+
+            assert!(cmp(expected_output.1, imaginary));
+        }
+    }
 }
@@ -0,0 +1,388 @@
+//! Special functions that are defined by series or integrals rather than elementary functions,
+//! eg. the error function and the Riemann zeta function. With the rug feature, MPFR is used
+//! where it has the function, and the others are calculated at the precision of the value.
+use crate::errors::KalkError;
+use crate::kalk_value::KalkValue;
+use crate::{as_number_or_return, float};
+
+#[cfg(not(feature = "rug"))]
+use regular as backend;
+#[cfg(feature = "rug")]
+use with_rug as backend;
+
+pub fn erf(x: KalkValue) -> Result<KalkValue, KalkError> {
+    if x.has_imaginary() {
+        return Err(KalkError::ExpectedReal);
+    }
+
+    let (real, _, unit) = as_number_or_return!(x);
+
+    Ok(KalkValue::Number(backend::erf(real), float!(0), unit))
+}
+
+pub fn erfc(x: KalkValue) -> Result<KalkValue, KalkError> {
+    if x.has_imaginary() {
+        return Err(KalkError::ExpectedReal);
+    }
+
+    let (real, _, unit) = as_number_or_return!(x);
+
+    Ok(KalkValue::Number(backend::erfc(real), float!(0), unit))
+}
+
+/// The beta function, B(a, b) = Γ(a)Γ(b) / Γ(a + b).
+pub fn beta(x: KalkValue, y: KalkValue) -> Result<KalkValue, KalkError> {
+    if x.has_imaginary() || y.has_imaginary() {
+        return Err(KalkError::ExpectedReal);
+    }
+
+    let (real, _, unit) = as_number_or_return!(x);
+    let (real_rhs, _, _) = as_number_or_return!(y);
+    if backend::is_pole(&real) || backend::is_pole(&real_rhs) {
+        return Err(KalkError::Pole(format!(
+            "Beta({}, {})",
+            KalkValue::Number(real, float!(0), None).to_string_pretty(),
+            KalkValue::Number(real_rhs, float!(0), None).to_string_pretty()
+        )));
+    }
+
+    Ok(KalkValue::Number(
+        backend::beta(real, real_rhs),
+        float!(0),
+        unit,
+    ))
+}
+
+/// The derivative of ln(Γ(x)), which has the same poles as the gamma function.
+pub fn digamma(x: KalkValue) -> Result<KalkValue, KalkError> {
+    if x.has_imaginary() {
+        return Err(KalkError::ExpectedReal);
+    }
+
+    let (real, _, unit) = as_number_or_return!(x);
+    if backend::is_pole(&real) {
+        return Err(KalkError::Pole(format!(
+            "digamma({})",
+            KalkValue::Number(real, float!(0), None).to_string_pretty()
+        )));
+    }
+
+    Ok(KalkValue::Number(backend::digamma(real), float!(0), unit))
+}
+
+/// The Riemann zeta function for real values, which is infinite at 1.
+pub fn zeta(x: KalkValue) -> Result<KalkValue, KalkError> {
+    if x.has_imaginary() {
+        return Err(KalkError::ExpectedReal);
+    }
+
+    let (real, _, unit) = as_number_or_return!(x);
+
+    Ok(KalkValue::Number(backend::zeta(real), float!(0), unit))
+}
+
+/// The principal branch of the Lambert W function, the inverse of w·e^w.
+/// It is only real from -1/e, and is NaN below that.
+pub fn lambert_w(x: KalkValue) -> Result<KalkValue, KalkError> {
+    if x.has_imaginary() {
+        return Err(KalkError::ExpectedReal);
+    }
+
+    let (real, _, unit) = as_number_or_return!(x);
+
+    Ok(KalkValue::Number(backend::lambert_w(real), float!(0), unit))
+}
+
+/// A guess for W(x) that Halley's method converges from.
+fn lambert_w_guess(x: f64) -> f64 {
+    if x < -0.25f64 {
+        // Series around the branch point at -1/e
+        let p = (2f64 * (std::f64::consts::E * x + 1f64)).max(0f64).sqrt();
+
+        -1f64 + p - p * p / 3f64 + 11f64 / 72f64 * p * p * p
+    } else if x < 3f64 {
+        x.ln_1p()
+    } else {
+        let ln = x.ln();
+
+        ln - ln.ln()
+    }
+}
+
+#[cfg(not(feature = "rug"))]
+mod regular {
+    use std::f64::consts::{E, PI};
+
+    pub(super) fn is_pole(x: &f64) -> bool {
+        *x <= 0f64 && x.fract() == 0f64
+    }
+
+    pub(super) fn erf(x: f64) -> f64 {
+        if x.abs() >= 2.5f64 {
+            return x.signum() * (1f64 - erfc_continued_fraction(x.abs()));
+        }
+
+        // Maclaurin series, 2/√π · Σ (-1)^n x^(2n + 1) / (n!(2n + 1))
+        let x2 = x * x;
+        let mut power = x;
+        let mut sum = x;
+        for n in 1..100 {
+            power *= -x2 / n as f64;
+            let term = power / (2 * n + 1) as f64;
+            sum += term;
+            if term.abs() <= f64::EPSILON * sum.abs() {
+                break;
+            }
+        }
+
+        sum * 2f64 / PI.sqrt()
+    }
+
+    pub(super) fn erfc(x: f64) -> f64 {
+        // 1 - erf(x) loses the precision when erf(x) is close to 1
+        if x >= 2.5f64 {
+            erfc_continued_fraction(x)
+        } else if x <= -2.5f64 {
+            2f64 - erfc_continued_fraction(-x)
+        } else {
+            1f64 - erf(x)
+        }
+    }
+
+    /// erfc(x) = e^(-x²)/√π · 1/(x + (1/2)/(x + 1/(x + (3/2)/(x + ...)))), for large x.
+    fn erfc_continued_fraction(x: f64) -> f64 {
+        let mut fraction = x;
+        for n in (1..=80).rev() {
+            fraction = x + (n as f64 / 2f64) / fraction;
+        }
+
+        (-x * x).exp() / (fraction * PI.sqrt())
+    }
+
+    pub(super) fn beta(a: f64, b: f64) -> f64 {
+        use crate::prelude::regular::funcs::precise_gamma;
+
+        precise_gamma(a) * precise_gamma(b) / precise_gamma(a + b)
+    }
+
+    pub(super) fn digamma(x: f64) -> f64 {
+        // Reflection formula: ψ(1 - x) - ψ(x) = π cot(πx)
+        if x <= 0f64 {
+            return digamma(1f64 - x) - PI / (PI * x).tan();
+        }
+
+        // ψ(x + 1) = ψ(x) + 1/x, to get to where the asymptotic series is precise
+        let mut x = x;
+        let mut result = 0f64;
+        while x < 6f64 {
+            result -= 1f64 / x;
+            x += 1f64;
+        }
+
+        let inv = 1f64 / x;
+        let inv2 = inv * inv;
+
+        result + x.ln()
+            - 0.5f64 * inv
+            - inv2
+                * (1f64 / 12f64
+                    - inv2
+                        * (1f64 / 120f64
+                            - inv2 * (1f64 / 252f64 - inv2 * (1f64 / 240f64 - inv2 / 132f64))))
+    }
+
+    pub(super) fn zeta(s: f64) -> f64 {
+        if s == 1f64 {
+            return f64::INFINITY;
+        }
+
+        if s < 0f64 {
+            // The trivial zeros
+            if s % 2f64 == 0f64 {
+                return 0f64;
+            }
+
+            // Functional equation: ζ(s) = 2^s π^(s - 1) sin(πs/2) Γ(1 - s) ζ(1 - s)
+            return 2f64.powf(s)
+                * PI.powf(s - 1f64)
+                * (PI * s / 2f64).sin()
+                * crate::prelude::regular::funcs::precise_gamma(1f64 - s)
+                * zeta(1f64 - s);
+        }
+
+        // Borwein's algorithm for the alternating zeta function, where the error is about 3/(3 + √8)^n
+        let n = 30;
+        let mut d = Vec::with_capacity(n + 1);
+        let mut term = 1f64;
+        let mut sum = 1f64;
+        d.push(sum);
+        for i in 0..n {
+            term *= 4f64 * (n + i) as f64 * (n - i) as f64 / ((2 * i + 1) * (2 * i + 2)) as f64;
+            sum += term;
+            d.push(sum);
+        }
+
+        let d_n = d[n];
+        let mut series = 0f64;
+        for (k, d_k) in d.iter().take(n).enumerate() {
+            let sign = if k % 2 == 0 { 1f64 } else { -1f64 };
+            series += sign * (d_k - d_n) / ((k + 1) as f64).powf(s);
+        }
+
+        -series / (d_n * (1f64 - 2f64.powf(1f64 - s)))
+    }
+
+    pub(super) fn lambert_w(x: f64) -> f64 {
+        let branch_point = -1f64 / E;
+        if x.is_nan() || x < branch_point {
+            return f64::NAN;
+        } else if x == branch_point {
+            return -1f64;
+        } else if x.is_infinite() {
+            return x;
+        }
+
+        // Halley's method for w·e^w - x = 0
+        let mut w = super::lambert_w_guess(x);
+        for _ in 0..100 {
+            let ew = w.exp();
+            let f = w * ew - x;
+            let change = f / (ew * (w + 1f64) - (w + 2f64) * f / (2f64 * w + 2f64));
+            w -= change;
+            if change.is_nan() || change.abs() <= 4f64 * f64::EPSILON * (1f64 + w.abs()) {
+                break;
+            }
+        }
+
+        w
+    }
+}
+
+#[cfg(feature = "rug")]
+mod with_rug {
+    use rug::Float;
+
+    pub(super) fn is_pole(x: &Float) -> bool {
+        *x <= 0 && x.is_integer()
+    }
+
+    pub(super) fn erf(x: Float) -> Float {
+        x.erf()
+    }
+
+    pub(super) fn erfc(x: Float) -> Float {
+        x.erfc()
+    }
+
+    pub(super) fn beta(a: Float, b: Float) -> Float {
+        let sum = a.clone() + &b;
+
+        a.gamma() * b.gamma() / sum.gamma()
+    }
+
+    pub(super) fn digamma(x: Float) -> Float {
+        x.digamma()
+    }
+
+    pub(super) fn zeta(s: Float) -> Float {
+        s.zeta()
+    }
+
+    pub(super) fn lambert_w(x: Float) -> Float {
+        let precision = x.prec();
+        let branch_point = -(Float::with_val(precision, 1).exp().recip());
+        if x.is_nan() || x < branch_point {
+            return Float::with_val(precision, f64::NAN);
+        } else if x == branch_point {
+            return Float::with_val(precision, -1);
+        } else if x.is_infinite() {
+            return x;
+        }
+
+        // Halley's method for w·e^w - x = 0, starting from the f64 guess. It converges
+        // cubically, so a few iterations are enough for the precision.
+        let mut w = Float::with_val(precision, super::lambert_w_guess(x.to_f64()));
+        for _ in 0..100 {
+            let ew = w.clone().exp();
+            let f = w.clone() * &ew - &x;
+            let w_plus_one: Float = w.clone() + 1;
+            let correction: Float = (w.clone() + 2) * &f / (w_plus_one.clone() * 2);
+            let change = f / (ew * w_plus_one - correction);
+            w -= &change;
+
+            let tolerance = (w.clone().abs() + 1) >> (precision - 4);
+            if change.is_nan() || change.abs() <= tolerance {
+                break;
+            }
+        }
+
+        w
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::cmp;
+
+    fn call(func: fn(KalkValue) -> Result<KalkValue, KalkError>, x: f64) -> f64 {
+        func(KalkValue::from(x)).unwrap().to_f64()
+    }
+
+    #[test]
+    fn test_special_funcs() {
+        let in_out = vec![
+            (
+                erf as fn(KalkValue) -> Result<KalkValue, KalkError>,
+                0.5f64,
+                0.5204998778f64,
+            ),
+            (erf, -2f64, -0.9953222650f64),
+            (erf, 3f64, 0.9999779095f64),
+            (erfc, 0.5f64, 0.4795001222f64),
+            (digamma, 1f64, -0.5772156649f64),
+            (digamma, 0.5f64, -1.9635100260f64),
+            (digamma, -0.5f64, 0.0364899740f64),
+            (digamma, 10f64, 2.2517525891f64),
+            (zeta, 2f64, 1.6449340668f64),
+            (zeta, 3f64, 1.2020569032f64),
+            (zeta, 0.5f64, -1.4603545088f64),
+            (zeta, 0f64, -0.5f64),
+            (zeta, -1f64, -0.0833333333f64),
+            (zeta, -2f64, 0f64),
+            (lambert_w, 1f64, 0.5671432904f64),
+            (lambert_w, -0.3f64, -0.4894022272f64),
+            (lambert_w, 10f64, 1.7455280027f64),
+            (lambert_w, 0f64, 0f64),
+        ];
+
+        for (func, input, expected_output) in in_out {
+            let output = call(func, input);
+            assert!(
+                cmp(output, expected_output),
+                "{} != {} for {}",
+                output,
+                expected_output,
+                input
+            );
+        }
+
+        // erfc keeps its precision where erf is close to 1
+        assert!((call(erfc, 6f64) / 2.151973671e-17f64 - 1f64).abs() < 1e-6);
+        assert!(call(zeta, 1f64).is_infinite());
+        assert!(call(lambert_w, -1f64).is_nan());
+        assert!(digamma(KalkValue::from(-2f64)).is_err());
+    }
+
+    #[test]
+    fn test_beta() {
+        let beta = |a: f64, b: f64| {
+            super::beta(KalkValue::from(a), KalkValue::from(b))
+                .unwrap()
+                .to_f64()
+        };
+        assert!(cmp(beta(2f64, 3f64), 1f64 / 12f64));
+        assert!(cmp(beta(0.5f64, 0.5f64), std::f64::consts::PI));
+        assert!(super::beta(KalkValue::from(0f64), KalkValue::from(1f64)).is_err());
+    }
+}
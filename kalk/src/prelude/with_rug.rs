@@ -2,7 +2,21 @@ pub mod special_funcs {
     use crate::{as_number_or_return, errors::KalkError, float, prelude::KalkValue};
 
     pub fn factorial(x: KalkValue) -> Result<KalkValue, KalkError> {
+        if let Some(exact) = x.exact_factorial() {
+            return Ok(exact);
+        }
+
+        // Only real values can be poles
+        if x.has_imaginary() {
+            return crate::prelude::funcs::complex_gamma(
+                x.add_without_unit(&KalkValue::from(1f64))?,
+            );
+        }
+
         let (real, _, unit) = as_number_or_return!(x);
+        if super::funcs::is_pole(&(real.clone() + 1f64)) {
+            return Err(KalkError::Pole(format!("({})!", real.to_f64())));
+        }
 
         Ok(KalkValue::Number((real + 1f64).gamma(), float!(0), unit))
     }
@@ -11,8 +25,9 @@ pub mod special_funcs {
 pub(crate) mod funcs {
     use crate::errors::KalkError;
     use crate::kalk_value::KalkValue;
-    use crate::prelude::funcs::abs;
+    use crate::prelude::funcs::{abs, complex_gamma};
     use crate::{as_number_or_return, float};
+    use std::convert::TryFrom;
 
     pub fn arg(x: KalkValue) -> Result<KalkValue, KalkError> {
         let (real, imaginary, unit) = as_number_or_return!(x);
@@ -21,62 +36,126 @@ pub(crate) mod funcs {
     }
 
     pub fn gamma(x: KalkValue) -> Result<KalkValue, KalkError> {
+        if x.has_imaginary() {
+            return complex_gamma(x);
+        }
+
         let (real, _, unit) = as_number_or_return!(x);
+        if is_pole(&real) {
+            return Err(KalkError::Pole(format!("gamma({})", real.to_f64())));
+        }
 
         Ok(KalkValue::Number(real.gamma(), float!(0), unit))
     }
 
+    /// The gamma function is undefined at zero and the negative integers.
+    pub(super) fn is_pole(x: &rug::Float) -> bool {
+        *x <= 0 && x.is_integer()
+    }
+
+    /// Round the value to an integer for the bitwise functions.
+    /// Values outside the range of i64 keep their lower 64 bits, eg. for 64-bit unsigned values.
+    fn to_word(x: &rug::Float) -> i64 {
+        x.to_integer().map_or(0, |x| x.to_i64_wrapping())
+    }
+
     pub fn bitcmp(x: KalkValue) -> Result<KalkValue, KalkError> {
         let (real, _, _) = as_number_or_return!(x);
 
-        Ok(KalkValue::from(
-            !real.to_i32_saturating().unwrap_or(i32::MAX),
-        ))
+        Ok(KalkValue::from(!to_word(&real)))
     }
 
     pub fn bitand(x: KalkValue, y: KalkValue) -> Result<KalkValue, KalkError> {
         let (real, _, _) = as_number_or_return!(x);
         let (real_rhs, _, _) = as_number_or_return!(y);
 
-        Ok(KalkValue::from(
-            real.to_i32_saturating().unwrap_or(i32::MAX)
-                & real_rhs.to_i32_saturating().unwrap_or(i32::MAX),
-        ))
+        Ok(KalkValue::from(to_word(&real) & to_word(&real_rhs)))
     }
 
     pub fn bitor(x: KalkValue, y: KalkValue) -> Result<KalkValue, KalkError> {
         let (real, _, _) = as_number_or_return!(x);
         let (real_rhs, _, _) = as_number_or_return!(y);
 
-        Ok(KalkValue::from(
-            real.to_i32_saturating().unwrap_or(i32::MAX)
-                | real_rhs.to_i32_saturating().unwrap_or(i32::MAX),
-        ))
+        Ok(KalkValue::from(to_word(&real) | to_word(&real_rhs)))
     }
 
     pub fn bitxor(x: KalkValue, y: KalkValue) -> Result<KalkValue, KalkError> {
         let (real, _, _) = as_number_or_return!(x);
         let (real_rhs, _, _) = as_number_or_return!(y);
 
-        Ok(KalkValue::from(
-            real.to_i32_saturating().unwrap_or(i32::MAX)
-                ^ real_rhs.to_i32_saturating().unwrap_or(i32::MAX),
-        ))
+        Ok(KalkValue::from(to_word(&real) ^ to_word(&real_rhs)))
     }
 
     pub fn bitshift(x: KalkValue, y: KalkValue) -> Result<KalkValue, KalkError> {
         let (real, _, _) = as_number_or_return!(x);
         let (real_rhs, _, _) = as_number_or_return!(y);
 
-        let x = real.to_i32_saturating().unwrap_or(i32::MAX) as i32;
-        let y = real_rhs.to_i32_saturating().unwrap_or(i32::MAX) as i32;
+        let x = to_word(&real);
+        let y = to_word(&real_rhs);
         if y < 0 {
-            Ok(KalkValue::from(x >> y.abs()))
+            let fill = if x < 0 { -1 } else { 0 };
+            Ok(KalkValue::from(
+                u32::try_from(y.unsigned_abs())
+                    .ok()
+                    .and_then(|y| x.checked_shr(y))
+                    .unwrap_or(fill),
+            ))
         } else {
-            Ok(KalkValue::from(x << y))
+            Ok(KalkValue::from(
+                u32::try_from(y)
+                    .ok()
+                    .and_then(|y| x.checked_shl(y))
+                    .unwrap_or(0),
+            ))
         }
     }
 
+    /// The smallest k where 2^k is at least x, eg. the height of a binary tree with x leaves.
+    pub fn ceil_log2(x: KalkValue) -> Result<KalkValue, KalkError> {
+        let (real, _, _) = as_number_or_return!(x);
+        if real < 1f64 {
+            return Ok(KalkValue::nan());
+        }
+
+        let n = to_word(&real.ceil()) as u64;
+
+        Ok(KalkValue::from(crate::prelude::ceil_log2_of_word(n) as i64))
+    }
+
+    /// The smallest power of two that is at least x, eg. the capacity of a buffer.
+    pub fn pow2ceil(x: KalkValue) -> Result<KalkValue, KalkError> {
+        let (real, _, _) = as_number_or_return!(x);
+        if real < 0f64 {
+            return Ok(KalkValue::nan());
+        }
+
+        let n = to_word(&real.ceil()) as u64;
+        let exponent = crate::prelude::ceil_log2_of_word(n) as i32;
+
+        Ok(KalkValue::from(2f64.powi(exponent)))
+    }
+
+    pub fn bits_needed(x: KalkValue) -> Result<KalkValue, KalkError> {
+        let (real, _, _) = as_number_or_return!(x);
+        let is_negative = real < 0f64;
+
+        Ok(KalkValue::from(
+            crate::prelude::bits_needed_for_word(to_word(&real), is_negative) as i64,
+        ))
+    }
+
+    /// The angle of the point (x, y), from -π to π.
+    pub fn atan2(y: KalkValue, x: KalkValue) -> Result<KalkValue, KalkError> {
+        if x.has_imaginary() || y.has_imaginary() {
+            return Err(KalkError::ExpectedReal);
+        }
+
+        let (real, _, unit) = as_number_or_return!(y);
+        let (real_rhs, _, _) = as_number_or_return!(x);
+
+        Ok(KalkValue::Number(real.atan2(&real_rhs), float!(0), unit))
+    }
+
     pub fn hypot(x: KalkValue, y: KalkValue) -> Result<KalkValue, KalkError> {
         let is_complex = x.has_imaginary() || y.has_imaginary();
         let (real, imaginary, unit) = as_number_or_return!(x);
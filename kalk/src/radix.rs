@@ -1,3 +1,5 @@
+use crate::kalk_value::BigInt;
+
 pub fn parse_float_radix(value: &str, radix: u8) -> Option<f64> {
     if radix == 10 {
         return if let Ok(result) = value.parse::<f64>() {
@@ -27,6 +29,17 @@ pub fn parse_float_radix(value: &str, radix: u8) -> Option<f64> {
     Some(sum)
 }
 
+/// Get the radix of a display conversion, eg. 16 for `to hex`.
+pub fn radix_by_name(name: &str) -> Option<u8> {
+    match name {
+        "bin" => Some(2),
+        "oct" => Some(8),
+        "dec" => Some(10),
+        "hex" => Some(16),
+        _ => None,
+    }
+}
+
 const DIGITS: &str = "0123456789abcdefghijklmnopqrstuvwxyz";
 pub fn int_to_radix(value: i64, radix: u8) -> String {
     let mut num = value.abs();
@@ -58,6 +71,15 @@ pub fn float_to_radix(value: f64, radix: u8) -> String {
     result
 }
 
+/// Same as `to_radix_pretty`, but for integers that may be too large to be exact as floats.
+pub fn integer_to_radix_pretty(value: &BigInt, radix: u8) -> String {
+    format!(
+        "{}{}",
+        value.to_string_radix(radix as u32),
+        crate::text_utils::normal_to_subscript(radix.to_string().chars())
+    )
+}
+
 pub fn to_radix_pretty(value: f64, radix: u8) -> String {
     if radix == 10 {
         crate::kalk_value::format_number(value)
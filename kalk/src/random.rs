@@ -0,0 +1,93 @@
+/// A small pseudorandom number generator (SplitMix64) for `rand`, `randint` and `randnormal`.
+/// It isn't suitable for cryptography, but gives the same numbers for the same seed on every
+/// platform, which makes scripts that use random numbers reproducible.
+#[derive(Debug, Clone)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub(crate) fn from_seed(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    /// Seed the generator with the random keys of the standard library together with the time.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn from_entropy() -> Self {
+        use std::collections::hash_map::RandomState;
+        use std::hash::BuildHasher;
+
+        Rng::from_seed(RandomState::new().hash_one(std::time::SystemTime::now()))
+    }
+
+    /// There is no entropy from the operating system in the browser, so `Math.random` is used.
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn from_entropy() -> Self {
+        use wasm_bindgen::prelude::*;
+
+        #[wasm_bindgen]
+        extern "C" {
+            #[wasm_bindgen(js_namespace = Math, js_name = random)]
+            fn math_random() -> f64;
+        }
+
+        let high = (math_random() * 4294967296f64) as u64;
+        let low = (math_random() * 4294967296f64) as u64;
+
+        Rng::from_seed((high << 32) | low)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+        z ^ (z >> 31)
+    }
+
+    /// A number in [0, 1), using the 53 upper bits since that is the precision of an f64.
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A number from the normal distribution, with the Box-Muller transform.
+    pub(crate) fn next_normal(&mut self, mean: f64, standard_deviation: f64) -> f64 {
+        // 1 - x is in (0, 1], which avoids ln(0)
+        let u1 = 1f64 - self.next_f64();
+        let u2 = self.next_f64();
+
+        mean + standard_deviation
+            * (-2f64 * u1.ln()).sqrt()
+            * (2f64 * std::f64::consts::PI * u2).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rng;
+
+    #[test]
+    fn test_same_seed_same_numbers() {
+        let mut rng = Rng::from_seed(42);
+        let mut other = Rng::from_seed(42);
+        for _ in 0..10 {
+            assert_eq!(rng.next_u64(), other.next_u64());
+        }
+
+        assert_ne!(Rng::from_seed(1).next_u64(), Rng::from_seed(2).next_u64());
+    }
+
+    #[test]
+    fn test_next_f64() {
+        let mut rng = Rng::from_seed(7);
+        let mut sum = 0f64;
+        for _ in 0..10000 {
+            let x = rng.next_f64();
+            assert!((0f64..1f64).contains(&x));
+            sum += x;
+        }
+
+        assert!((sum / 10000f64 - 0.5).abs() < 0.02);
+    }
+}
@@ -0,0 +1,284 @@
+//! Input in reverse Polish notation, eg. `3 4 + 2 *`, like on stack based calculators.
+//! Numbers and variables are pushed onto a stack, while operators and functions take
+//! their arguments from the top of it and push the result.
+use crate::ast::{build_literal_ast, Expr, ExprSpan, Identifier, Stmt};
+use crate::display::operator_to_string;
+use crate::errors::KalkError;
+use crate::kalk_value::KalkValue;
+use crate::lexer::{Lexer, Token, TokenKind};
+use crate::parser;
+use crate::prelude;
+
+/// The values that have been entered, where the last one is the top of the stack.
+#[derive(Debug, Clone, Default)]
+pub struct Stack {
+    values: Vec<KalkValue>,
+}
+
+impl Stack {
+    pub fn new() -> Self {
+        Stack::default()
+    }
+
+    /// The values of the stack, from the bottom to the top.
+    pub fn values(&self) -> &[KalkValue] {
+        &self.values
+    }
+
+    fn pop(&mut self, amount: usize, name: &str) -> Result<Vec<KalkValue>, KalkError> {
+        if self.values.len() < amount {
+            return Err(KalkError::StackUnderflow(name.to_string()));
+        }
+
+        Ok(self.values.split_off(self.values.len() - amount))
+    }
+}
+
+/// Push the numbers and apply the operators and functions of the input to the stack,
+/// from left to right. Besides operators and functions, the stack can be managed with
+/// `dup`, `drop`, `swap` and `clear`. If an error occurs, the stack is left as it was.
+pub fn eval(
+    context: &mut parser::Context,
+    stack: &mut Stack,
+    input: &str,
+    #[cfg(feature = "rug")] precision: u32,
+) -> Result<(), KalkError> {
+    let mut new_stack = stack.clone();
+    for token in tokenize(input) {
+        let expr = match token.kind {
+            TokenKind::Literal => {
+                let value = push_value(
+                    context,
+                    &token.value,
+                    #[cfg(feature = "rug")]
+                    precision,
+                )?;
+                new_stack.values.push(value);
+                continue;
+            }
+            TokenKind::Plus
+            | TokenKind::Minus
+            | TokenKind::Star
+            | TokenKind::Slash
+            | TokenKind::Power
+            | TokenKind::Mod
+            | TokenKind::Rem
+            | TokenKind::Div
+            | TokenKind::Quot => {
+                let arguments = new_stack.pop(2, operator_to_string(&token.kind))?;
+                Expr::Binary(
+                    Box::new(value_to_ast(&arguments[0])),
+                    token.kind,
+                    Box::new(value_to_ast(&arguments[1])),
+                    ExprSpan::default(),
+                )
+            }
+            TokenKind::Exclamation => {
+                let arguments = new_stack.pop(1, "!")?;
+                Expr::Unary(
+                    TokenKind::Exclamation,
+                    Box::new(value_to_ast(&arguments[0])),
+                    ExprSpan::default(),
+                )
+            }
+            TokenKind::Identifier => {
+                if eval_stack_word(&mut new_stack, &token.value)? {
+                    continue;
+                }
+
+                if let Some(arity) = function_arity(context, &token.value) {
+                    let arguments = new_stack.pop(arity, &token.value)?;
+                    Expr::FnCall(
+                        Identifier::from_full_name(&token.value),
+                        arguments.iter().map(value_to_ast).collect(),
+                    )
+                } else {
+                    let value = push_value(
+                        context,
+                        &token.value,
+                        #[cfg(feature = "rug")]
+                        precision,
+                    )?;
+                    new_stack.values.push(value);
+                    continue;
+                }
+            }
+            TokenKind::Comma | TokenKind::Semicolon | TokenKind::Eof => continue,
+            _ => return Err(KalkError::UnexpectedToken(token.kind, None)),
+        };
+
+        let result = parser::interpret(
+            context,
+            vec![Stmt::Expr(Box::new(expr))],
+            None,
+            #[cfg(feature = "rug")]
+            precision,
+        )?;
+        if let Some(result) = result {
+            new_stack.values.push(result.get_value());
+        }
+    }
+
+    *stack = new_stack;
+
+    Ok(())
+}
+
+/// Lex each word of the input on its own, since eg. `3 4` would otherwise be
+/// lexed as the number 34. A minus sign in front of a number, eg. `-3`, is part of it.
+fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for word in input.split_whitespace() {
+        let word_tokens = Lexer::new(word).lex();
+        match word_tokens.as_slice() {
+            [minus, literal, ..]
+                if minus.kind == TokenKind::Minus
+                    && literal.kind == TokenKind::Literal
+                    && word_tokens.len() <= 3 =>
+            {
+                tokens.push(Token {
+                    kind: TokenKind::Literal,
+                    value: format!("-{}", literal.value),
+                    span: (minus.span.0, literal.span.1),
+                });
+                tokens.extend(word_tokens.into_iter().skip(2));
+            }
+            _ => tokens.extend(word_tokens),
+        }
+    }
+
+    tokens
+}
+
+/// Evaluate a number or variable, to push onto the stack.
+fn push_value(
+    context: &mut parser::Context,
+    input: &str,
+    #[cfg(feature = "rug")] precision: u32,
+) -> Result<KalkValue, KalkError> {
+    let result = parser::eval(
+        context,
+        input,
+        #[cfg(feature = "rug")]
+        precision,
+    )?;
+
+    result
+        .map(|result| result.get_value())
+        .ok_or_else(|| KalkError::Expected(format!("a value for '{}'", input)))
+}
+
+/// Apply the words that manage the stack itself. Returns false if the name isn't one of them.
+fn eval_stack_word(stack: &mut Stack, name: &str) -> Result<bool, KalkError> {
+    match name {
+        "dup" => {
+            let top = stack.pop(1, name)?;
+            stack.values.extend(top.clone());
+            stack.values.extend(top);
+        }
+        "drop" => {
+            stack.pop(1, name)?;
+        }
+        "swap" => {
+            let mut top = stack.pop(2, name)?;
+            top.reverse();
+            stack.values.extend(top);
+        }
+        "clear" => stack.values.clear(),
+        _ => return Ok(false),
+    }
+
+    Ok(true)
+}
+
+/// The amount of arguments a function takes from the stack,
+/// or None if the name isn't a function that can be used this way.
+fn function_arity(context: &parser::Context, name: &str) -> Option<usize> {
+    if prelude::UNARY_FUNCS.contains_key(name) {
+        Some(1)
+    } else if prelude::BINARY_FUNCS.contains_key(name) {
+        Some(2)
+    } else {
+        context.function_parameter_count(name)
+    }
+}
+
+fn value_to_ast(value: &KalkValue) -> Expr {
+    match value {
+        KalkValue::Number(_, _, Some(unit)) => {
+            Expr::Unit(unit.clone(), Box::new(build_literal_ast(value)))
+        }
+        KalkValue::Vector(values) => Expr::Vector(values.iter().map(value_to_ast).collect()),
+        KalkValue::Matrix(rows) => Expr::Matrix(
+            rows.iter()
+                .map(|row| row.iter().map(value_to_ast).collect())
+                .collect(),
+        ),
+        _ => build_literal_ast(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Stack;
+    use crate::errors::KalkError;
+    use crate::parser;
+    use crate::test_helpers::cmp;
+
+    fn eval(
+        context: &mut parser::Context,
+        stack: &mut Stack,
+        input: &str,
+    ) -> Result<(), KalkError> {
+        super::eval(
+            context,
+            stack,
+            input,
+            #[cfg(feature = "rug")]
+            63,
+        )
+    }
+
+    fn top(stack: &Stack) -> f64 {
+        stack.values().last().unwrap().to_f64()
+    }
+
+    #[test]
+    fn test_operators_and_functions() {
+        let mut context = parser::Context::new();
+        let mut stack = Stack::new();
+        eval(&mut context, &mut stack, "3 4 + 2 *").unwrap();
+        assert_eq!(top(&stack), 14f64);
+
+        eval(&mut context, &mut stack, "sqrt 2 ^").unwrap();
+        assert!(cmp(top(&stack), 14f64));
+
+        eval(&mut context, &mut stack, "clear 5 -3 - 3 !").unwrap();
+        assert_eq!(stack.values().len(), 2);
+        assert_eq!(stack.values()[0].to_f64(), 8f64);
+        assert_eq!(top(&stack), 6f64);
+
+        parser::eval(
+            &mut context,
+            "f(x, y) = x - y",
+            #[cfg(feature = "rug")]
+            63,
+        )
+        .unwrap();
+        eval(&mut context, &mut stack, "clear 10 pi swap f 7 - dup drop").unwrap();
+        assert!(cmp(top(&stack), std::f64::consts::PI - 10f64 - 7f64));
+    }
+
+    #[test]
+    fn test_errors_keep_stack() {
+        let mut context = parser::Context::new();
+        let mut stack = Stack::new();
+        eval(&mut context, &mut stack, "1 2").unwrap();
+        assert_eq!(
+            eval(&mut context, &mut stack, "+ +"),
+            Err(KalkError::StackUnderflow(String::from("+")))
+        );
+        assert_eq!(stack.values().len(), 2);
+        assert_eq!(top(&stack), 2f64);
+    }
+}
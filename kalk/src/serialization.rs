@@ -0,0 +1,122 @@
+//! Serialization of syntax trees and symbol tables, with the `serde` feature.
+//! Statements and expressions are stored as their syntax trees, since kalker syntax
+//! like `f(2)` means different things depending on what has been defined.
+//! Symbol tables are stored as a list of their definitions, eg. `f(x) = 2x`,
+//! which are parsed again in order, so that they are read in the same context.
+use crate::parser;
+use crate::symbol_table::SymbolTable;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+impl Serialize for SymbolTable {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.definitions())
+    }
+}
+
+impl<'de> Deserialize<'de> for SymbolTable {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let definitions = Vec::<String>::deserialize(deserializer)?;
+
+        parser::parse_definitions(&definitions).map_err(|err| de::Error::custom(err.to_string()))
+    }
+}
+
+/// Only the symbol table is stored, not settings like the angle unit.
+impl Serialize for parser::Context {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.get_symbol_table().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for parser::Context {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let symbol_table = SymbolTable::deserialize(deserializer)?;
+
+        Ok(parser::Context::new().set_symbol_table(symbol_table))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Expr, Stmt};
+    use crate::calculation_result::CalculationResult;
+    use crate::errors::KalkError;
+    use crate::parser::{self, Context};
+
+    fn eval(context: &mut Context, input: &str) -> Result<Option<CalculationResult>, KalkError> {
+        #[cfg(feature = "rug")]
+        return parser::eval(context, input, 63);
+
+        #[cfg(not(feature = "rug"))]
+        parser::eval(context, input)
+    }
+
+    fn round_trip(stmt: &Stmt) -> Stmt {
+        let json = serde_json::to_string(stmt).unwrap();
+
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_fn_call_round_trip() {
+        let mut context = Context::new();
+        eval(&mut context, "f(x) = 2x").unwrap();
+        let stmt = parser::parse(&mut context, "f(2)").unwrap().pop().unwrap();
+        assert!(matches!(&stmt, Stmt::Expr(expr) if matches!(**expr, Expr::FnCall(_, _))));
+
+        // It stays a function call, even where `f` isn't defined
+        assert_eq!(round_trip(&stmt), stmt);
+    }
+
+    #[test]
+    fn test_unit_round_trip() {
+        let mut context = Context::new();
+        for input in ["unit km = m/1000", "2 km", "3 m/s * 4 s"] {
+            let stmt = parser::parse(&mut context, input).unwrap().pop().unwrap();
+            assert_eq!(round_trip(&stmt), stmt, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_declaration_round_trip() {
+        let mut context = Context::new();
+        for input in [
+            "x = 3",
+            "g(x, y) = x + y",
+            "(q, r) = divmod(17, 5)",
+            "n = 9007199254740993",
+        ] {
+            let stmt = parser::parse(&mut context, input).unwrap().pop().unwrap();
+            assert_eq!(round_trip(&stmt), stmt, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_context_round_trip() {
+        let mut context = Context::new();
+        eval(&mut context, "f(x) = 2x").unwrap();
+        eval(&mut context, "unit km = m/1000").unwrap();
+        eval(&mut context, "y = f(3) km").unwrap();
+        eval(&mut context, "const c = 5").unwrap();
+
+        let json = serde_json::to_string(&context).unwrap();
+        let mut deserialized: Context = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.get_definitions(), context.get_definitions());
+
+        let result = eval(&mut deserialized, "y").unwrap().unwrap();
+        assert_eq!(result.to_f64(), 6f64);
+        assert_eq!(result.get_unit(), Some(String::from("km")));
+        assert_eq!(
+            eval(&mut deserialized, "f(2) + c")
+                .unwrap()
+                .unwrap()
+                .to_f64(),
+            9f64
+        );
+        assert_eq!(
+            eval(&mut deserialized, "c = 2").err(),
+            Some(KalkError::UnableToOverrideConstant(String::from("c")))
+        );
+    }
+}
@@ -0,0 +1,419 @@
+use crate::ast::{ConditionalPiece, Expr, ExprSpan};
+use crate::display::needs_group;
+use crate::lexer::TokenKind;
+
+/// Integers bigger than this can not be represented exactly by an f64,
+/// so results bigger than this are not folded into a literal.
+const MAX_EXACT_INTEGER: f64 = 9007199254740992f64; // 2^53
+
+impl Expr {
+    /// Rewrite the expression into a simpler form, eg. `2x + 3x` into `5x` or `x^1 * 1` into `x`.
+    /// Literals are only folded together when the result can be represented exactly,
+    /// and variables are assumed to be numbers other than zero in divisions
+    /// (so `x * 0` becomes `0` and `x / x` becomes `1`).
+    pub fn simplify(&self) -> Expr {
+        regroup(simplify(self))
+    }
+}
+
+fn simplify(expr: &Expr) -> Expr {
+    match expr {
+        Expr::Binary(left, op, right, _) => simplify_binary(simplify(left), op, simplify(right)),
+        Expr::Unary(op, expr, _) => simplify_unary(op, simplify(expr)),
+        Expr::Unit(identifier, expr) => Expr::Unit(identifier.clone(), Box::new(simplify(expr))),
+        Expr::Group(expr) => simplify(expr),
+        Expr::FnCall(identifier, arguments) => {
+            Expr::FnCall(identifier.clone(), arguments.iter().map(simplify).collect())
+        }
+        Expr::Piecewise(pieces) => Expr::Piecewise(
+            pieces
+                .iter()
+                .map(|piece| ConditionalPiece {
+                    expr: simplify(&piece.expr),
+                    condition: simplify(&piece.condition),
+                })
+                .collect(),
+        ),
+        Expr::Vector(values) => Expr::Vector(values.iter().map(simplify).collect()),
+        Expr::Matrix(rows) => Expr::Matrix(
+            rows.iter()
+                .map(|row| row.iter().map(simplify).collect())
+                .collect(),
+        ),
+        Expr::Indexer(value, indexes) => Expr::Indexer(
+            Box::new(simplify(value)),
+            indexes.iter().map(simplify).collect(),
+        ),
+        Expr::Equation(left, right, identifier) => Expr::Equation(
+            Box::new(simplify(left)),
+            Box::new(simplify(right)),
+            identifier.clone(),
+        ),
+        Expr::Let(identifier, value, body) => Expr::Let(
+            identifier.clone(),
+            Box::new(simplify(value)),
+            Box::new(simplify(body)),
+        ),
+        Expr::WithAngleUnit(angle_unit, expr) => {
+            Expr::WithAngleUnit(angle_unit.clone(), Box::new(simplify(expr)))
+        }
+        Expr::If(condition, then_branch, else_branch) => Expr::If(
+            Box::new(simplify(condition)),
+            Box::new(simplify(then_branch)),
+            Box::new(simplify(else_branch)),
+        ),
+        _ => expr.clone(),
+    }
+}
+
+fn simplify_binary(left: Expr, op: &TokenKind, right: Expr) -> Expr {
+    match op {
+        TokenKind::Plus | TokenKind::Minus => {
+            let binary = binary(left, op.clone(), right);
+
+            combine_like_terms(&binary).unwrap_or_else(|| simplify_sum(binary))
+        }
+        TokenKind::Star => simplify_product(left, right),
+        TokenKind::Slash => match (&left, &right) {
+            (_, Expr::Literal(divisor, _)) if *divisor == 1f64 => left,
+            (Expr::Literal(dividend, _), Expr::Literal(divisor, _)) => {
+                fold(*dividend, *divisor, |a, b| a / b)
+                    .map(literal)
+                    .unwrap_or_else(|| binary(left, op.clone(), right))
+            }
+            (Expr::Literal(dividend, _), _) if *dividend == 0f64 => literal(0f64),
+            _ if left == right => literal(1f64),
+            // 2x / x => 2
+            (Expr::Binary(factor, TokenKind::Star, divisor, _), _)
+            | (Expr::Binary(divisor, TokenKind::Star, factor, _), _)
+                if **divisor == right =>
+            {
+                *factor.clone()
+            }
+            _ => binary(left, op.clone(), right),
+        },
+        TokenKind::Power => match (&left, &right) {
+            (_, Expr::Literal(exponent, _)) if *exponent == 1f64 => left,
+            (_, Expr::Literal(exponent, _)) if *exponent == 0f64 => literal(1f64),
+            (Expr::Literal(base, _), _) if *base == 1f64 => literal(1f64),
+            (Expr::Literal(base, _), Expr::Literal(exponent, _))
+                if (0f64..=64f64).contains(exponent) && exponent.fract() == 0f64 =>
+            {
+                fold(*base, *exponent, |a, b| a.powi(b as i32))
+                    .map(literal)
+                    .unwrap_or_else(|| binary(left, op.clone(), right))
+            }
+            _ => binary(left, op.clone(), right),
+        },
+        _ => binary(left, op.clone(), right),
+    }
+}
+
+/// Remove additions and subtractions of zero, and fold literals.
+/// Used when the terms can not be combined as like terms.
+fn simplify_sum(expr: Expr) -> Expr {
+    let (left, op, right) = match expr {
+        Expr::Binary(left, op, right, _) => (*left, op, *right),
+        _ => return expr,
+    };
+
+    match (&left, &op, &right) {
+        (_, _, Expr::Literal(value, _)) if *value == 0f64 => left,
+        (Expr::Literal(value, _), TokenKind::Plus, _) if *value == 0f64 => right,
+        (Expr::Literal(value, _), TokenKind::Minus, _) if *value == 0f64 => {
+            simplify_unary(&TokenKind::Minus, right)
+        }
+        (Expr::Literal(a, _), TokenKind::Plus, Expr::Literal(b, _)) => fold(*a, *b, |a, b| a + b)
+            .map(literal)
+            .unwrap_or_else(|| binary(left, op, right)),
+        (Expr::Literal(a, _), TokenKind::Minus, Expr::Literal(b, _)) => fold(*a, *b, |a, b| a - b)
+            .map(literal)
+            .unwrap_or_else(|| binary(left, op, right)),
+        _ => binary(left, op, right),
+    }
+}
+
+fn simplify_product(left: Expr, right: Expr) -> Expr {
+    match (&left, &right) {
+        (Expr::Literal(value, _), _) | (_, Expr::Literal(value, _)) if *value == 0f64 => {
+            literal(0f64)
+        }
+        (Expr::Literal(value, _), _) if *value == 1f64 => right,
+        (_, Expr::Literal(value, _)) if *value == 1f64 => left,
+        (Expr::Literal(value, _), _) if *value == -1f64 => simplify_unary(&TokenKind::Minus, right),
+        (Expr::Literal(a, _), Expr::Literal(b, _)) => fold(*a, *b, |a, b| a * b)
+            .map(literal)
+            .unwrap_or_else(|| binary(left, TokenKind::Star, right)),
+        // Put the coefficient first, eg. x * 2 => 2x
+        (_, Expr::Literal(_, _)) => simplify_product(right, left),
+        // 2 * 3x => 6x
+        (Expr::Literal(a, _), Expr::Binary(inner_left, TokenKind::Star, inner_right, _)) => {
+            if let Expr::Literal(b, _) = &**inner_left {
+                if let Some(coefficient) = fold(*a, *b, |a, b| a * b) {
+                    return simplify_product(literal(coefficient), *inner_right.clone());
+                }
+            }
+
+            binary(left, TokenKind::Star, right)
+        }
+        // 1 / x * x => 1
+        (Expr::Binary(numerator, TokenKind::Slash, divisor, _), factor)
+        | (factor, Expr::Binary(numerator, TokenKind::Slash, divisor, _))
+            if **divisor == *factor =>
+        {
+            *numerator.clone()
+        }
+        _ => binary(left, TokenKind::Star, right),
+    }
+}
+
+fn simplify_unary(op: &TokenKind, expr: Expr) -> Expr {
+    if *op != TokenKind::Minus {
+        return Expr::Unary(op.clone(), Box::new(expr), ExprSpan::default());
+    }
+
+    match expr {
+        Expr::Literal(value, _) => literal(-value),
+        Expr::Unary(TokenKind::Minus, inner, _) => *inner,
+        Expr::Binary(left, TokenKind::Star, right, _) if matches!(*left, Expr::Literal(_, _)) => {
+            simplify_product(simplify_unary(op, *left), *right)
+        }
+        _ => Expr::Unary(TokenKind::Minus, Box::new(expr), ExprSpan::default()),
+    }
+}
+
+/// Combine terms that only differ in their coefficient, eg. `2x + y - x + 1 + 2` => `x + y + 3`.
+/// Returns `None` if a coefficient isn't an integer, since combining those could
+/// introduce floating point errors, or if there is a percentage, since `x + 5%` depends on `x`.
+fn combine_like_terms(expr: &Expr) -> Option<Expr> {
+    let mut terms = Vec::new();
+    collect_terms(expr, 1f64, &mut terms)?;
+
+    let mut combined: Vec<(f64, Expr)> = Vec::new();
+    for (coefficient, base) in terms {
+        if let Some((existing_coefficient, _)) = combined
+            .iter_mut()
+            .find(|(_, existing_base)| *existing_base == base)
+        {
+            *existing_coefficient = fold(*existing_coefficient, coefficient, |a, b| a + b)?;
+        } else {
+            combined.push((coefficient, base));
+        }
+    }
+
+    // Put the constant term last, eg. 2 + x => x + 2
+    let constant = literal(1f64);
+    combined.sort_by_key(|(_, base)| *base == constant);
+
+    let mut result: Option<Expr> = None;
+    for (coefficient, base) in combined {
+        if coefficient == 0f64 {
+            continue;
+        }
+
+        result = Some(match result {
+            None => term(coefficient, base),
+            Some(sum) if coefficient < 0f64 => {
+                binary(sum, TokenKind::Minus, term(-coefficient, base))
+            }
+            Some(sum) => binary(sum, TokenKind::Plus, term(coefficient, base)),
+        });
+    }
+
+    Some(result.unwrap_or(literal(0f64)))
+}
+
+/// Split a sum into (coefficient, base) pairs, where literals get the base `1`.
+fn collect_terms(expr: &Expr, sign: f64, terms: &mut Vec<(f64, Expr)>) -> Option<()> {
+    match expr {
+        Expr::Binary(left, TokenKind::Plus, right, _) => {
+            collect_terms(left, sign, terms)?;
+            collect_terms(right, sign, terms)
+        }
+        Expr::Binary(left, TokenKind::Minus, right, _) => {
+            collect_terms(left, sign, terms)?;
+            collect_terms(right, -sign, terms)
+        }
+        Expr::Unary(TokenKind::Minus, inner, _) => collect_terms(inner, -sign, terms),
+        Expr::Unary(TokenKind::Percent, _, _) => None,
+        Expr::Literal(value, _) => push_term(terms, sign * value, literal(1f64)),
+        Expr::Binary(left, TokenKind::Star, right, _) => match &**left {
+            Expr::Literal(coefficient, _) => push_term(terms, sign * coefficient, *right.clone()),
+            _ => push_term(terms, sign, expr.clone()),
+        },
+        _ => push_term(terms, sign, expr.clone()),
+    }
+}
+
+fn push_term(terms: &mut Vec<(f64, Expr)>, coefficient: f64, base: Expr) -> Option<()> {
+    if !is_exact_integer(coefficient) {
+        return None;
+    }
+
+    terms.push((coefficient, base));
+
+    Some(())
+}
+
+fn term(coefficient: f64, base: Expr) -> Expr {
+    if base == literal(1f64) {
+        literal(coefficient)
+    } else {
+        simplify_product(literal(coefficient), base)
+    }
+}
+
+/// Apply an operation to two literals, if the operands and the result are integers
+/// that can be represented exactly.
+fn fold(a: f64, b: f64, operation: fn(f64, f64) -> f64) -> Option<f64> {
+    let result = operation(a, b);
+    if is_exact_integer(a) && is_exact_integer(b) && is_exact_integer(result) {
+        Some(result)
+    } else {
+        None
+    }
+}
+
+fn is_exact_integer(value: f64) -> bool {
+    value.fract() == 0f64 && value.abs() <= MAX_EXACT_INTEGER
+}
+
+fn binary(left: Expr, op: TokenKind, right: Expr) -> Expr {
+    Expr::Binary(Box::new(left), op, Box::new(right), ExprSpan::default())
+}
+
+fn literal(value: f64) -> Expr {
+    Expr::Literal(value, ExprSpan::default())
+}
+
+/// Put parentheses back where they are needed, since they were removed
+/// while simplifying and other passes (eg. LaTeX output) rely on them.
+fn regroup(expr: Expr) -> Expr {
+    let group_if_needed = |op: &TokenKind, operand: Expr, is_right: bool| {
+        let operand = regroup(operand);
+        if needs_group(op, &operand, is_right) {
+            Expr::Group(Box::new(operand))
+        } else {
+            operand
+        }
+    };
+
+    match expr {
+        Expr::Binary(left, op, right, _) => {
+            let left = group_if_needed(&op, *left, false);
+            let right = group_if_needed(&op, *right, true);
+
+            binary(left, op, right)
+        }
+        Expr::Unary(op, expr, _) => match regroup(*expr) {
+            expr @ Expr::Binary(_, _, _, _) => Expr::Unary(
+                op,
+                Box::new(Expr::Group(Box::new(expr))),
+                ExprSpan::default(),
+            ),
+            expr => Expr::Unary(op, Box::new(expr), ExprSpan::default()),
+        },
+        Expr::Unit(identifier, expr) => Expr::Unit(identifier, Box::new(regroup(*expr))),
+        Expr::FnCall(identifier, arguments) => {
+            Expr::FnCall(identifier, arguments.into_iter().map(regroup).collect())
+        }
+        Expr::Piecewise(pieces) => Expr::Piecewise(
+            pieces
+                .into_iter()
+                .map(|piece| ConditionalPiece {
+                    expr: regroup(piece.expr),
+                    condition: regroup(piece.condition),
+                })
+                .collect(),
+        ),
+        Expr::Vector(values) => Expr::Vector(values.into_iter().map(regroup).collect()),
+        Expr::Matrix(rows) => Expr::Matrix(
+            rows.into_iter()
+                .map(|row| row.into_iter().map(regroup).collect())
+                .collect(),
+        ),
+        Expr::Indexer(value, indexes) => Expr::Indexer(
+            Box::new(regroup(*value)),
+            indexes.into_iter().map(regroup).collect(),
+        ),
+        Expr::Equation(left, right, identifier) => Expr::Equation(
+            Box::new(regroup(*left)),
+            Box::new(regroup(*right)),
+            identifier,
+        ),
+        Expr::Let(identifier, value, body) => Expr::Let(
+            identifier,
+            Box::new(regroup(*value)),
+            Box::new(regroup(*body)),
+        ),
+        Expr::WithAngleUnit(angle_unit, expr) => {
+            Expr::WithAngleUnit(angle_unit, Box::new(regroup(*expr)))
+        }
+        Expr::If(condition, then_branch, else_branch) => Expr::If(
+            Box::new(regroup(*condition)),
+            Box::new(regroup(*then_branch)),
+            Box::new(regroup(*else_branch)),
+        ),
+        _ => expr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Stmt;
+    use crate::parser;
+
+    fn simplify(input: &str) -> String {
+        let mut context = parser::Context::new();
+        match parser::parse(&mut context, input).unwrap().pop() {
+            Some(Stmt::Expr(expr)) => expr.simplify().to_string(),
+            _ => panic!("Expected an expression"),
+        }
+    }
+
+    #[test]
+    fn test_identities() {
+        assert_eq!(simplify("x*1 + 0"), "x");
+        assert_eq!(simplify("1x - 0"), "x");
+        assert_eq!(simplify("0 - x"), "-x");
+        assert_eq!(simplify("sin(x)^1"), "sin(x)");
+        assert_eq!(simplify("(x + y)^0"), "1");
+        assert_eq!(simplify("y * 0 + 2"), "2");
+        assert_eq!(simplify("x/1"), "x");
+        assert_eq!(simplify("--x"), "x");
+        assert_eq!(simplify("x / x"), "1");
+        assert_eq!(simplify("0 / x^2"), "0");
+        assert_eq!(simplify("0 / 0"), "0 / 0");
+        assert_eq!(simplify("2x / x"), "2");
+        assert_eq!(simplify("1 / x * x"), "1");
+        assert_eq!(simplify("sin(x) * (y / sin(x))"), "y");
+    }
+
+    #[test]
+    fn test_constant_folding() {
+        assert_eq!(simplify("2 + 3"), "5");
+        assert_eq!(simplify("2^10 - 24"), "1000");
+        assert_eq!(simplify("6/3"), "2");
+        assert_eq!(simplify("1/3"), "1 / 3");
+        assert_eq!(simplify("0.1 + 0.2"), "0.1 + 0.2");
+        assert_eq!(simplify("2 * (3 * x)"), "6x");
+        assert_eq!(simplify("x * 2"), "2x");
+    }
+
+    #[test]
+    fn test_like_terms() {
+        assert_eq!(simplify("2x + 3x"), "5x");
+        assert_eq!(simplify("2x + y - x + 1 + 2"), "x + y + 3");
+        assert_eq!(simplify("x - x"), "0");
+        assert_eq!(simplify("sin(x) - 3sin(x)"), "-2sin(x)");
+        assert_eq!(simplify("x + 5%"), "x + 5%");
+    }
+
+    #[test]
+    fn test_regroup() {
+        assert_eq!(simplify("a / (b / c^1)"), "a / (b / c)");
+        assert_eq!(simplify("a - (b + 0 - c)"), "a - b + c");
+        assert_eq!(simplify("(x + x)^2"), "(2x)^2");
+        assert_eq!(simplify("max(x + x + 1, 2 * 2)"), "max(2x + 1, 4)");
+    }
+}
@@ -0,0 +1,46 @@
+use crate::kalk_value::KalkValue;
+use std::fmt;
+
+/// The maximum amount of steps that are recorded for one calculation,
+/// since eg. a sum of a million terms would otherwise use a lot of memory.
+pub const MAX_STEPS: usize = 1000;
+
+/// A reduction that was made while evaluating an expression, eg. `2 * 3 = 6`.
+/// These are recorded when tracing is enabled with `parser::Context::set_trace`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub kind: StepKind,
+    /// What was reduced, eg. `2 * 3` or `f(2)`.
+    pub input: String,
+    /// What it was reduced to, eg. `6`, or the body of a function.
+    pub output: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepKind {
+    /// An operator applied to values, eg. `2 * 3 = 6`.
+    Operation,
+    /// A function call and its result, eg. `sqrt(16) = 4`.
+    FunctionCall,
+    /// A call to a user-defined function replaced with its body, eg. `f(2) → 2^2 + 1`.
+    FunctionExpansion,
+    /// A value converted to another unit, eg. `100 cm to m = 1 m`.
+    UnitConversion,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            StepKind::FunctionExpansion => write!(f, "{} → {}", self.input, self.output),
+            _ => write!(f, "{} = {}", self.input, self.output),
+        }
+    }
+}
+
+/// Format a value for a step, including its unit.
+pub(crate) fn format_value(value: &KalkValue) -> String {
+    match value.get_unit() {
+        Some(unit) => format!("{} {}", value, unit),
+        None => value.to_string(),
+    }
+}
@@ -1,10 +1,50 @@
-use crate::{ast::Expr, ast::Identifier, ast::Stmt, prelude};
-use std::collections::HashMap;
+use crate::completion::{CompletionItem, CompletionKind, SPECIAL_FUNCS};
+use crate::errors::KalkError;
+use crate::native::NativeFunction;
+use crate::parser::{ShadowingPolicy, ShadowingWarning};
+use crate::{
+    ast::Expr, ast::ExprSpan, ast::Identifier, ast::Stmt, lexer::Token, prelude, text_utils,
+};
+use std::collections::{HashMap, HashSet, VecDeque};
 
-#[derive(Debug)]
+/// How many results are remembered for `_`, `_2`, `_3`, etc.
+const MAX_RESULTS: usize = 100;
+
+#[derive(Debug, Clone)]
 pub struct SymbolTable {
     pub(crate) hashmap: HashMap<String, Stmt>,
     pub(crate) unit_types: HashMap<String, ()>,
+    /// Local variables, eg. from `let` expressions. The innermost scope is last.
+    scopes: Vec<HashMap<String, Stmt>>,
+    /// Names of variables declared with `const`, which can't be redefined.
+    constants: HashSet<String>,
+    /// Names declared with `const` in the input that is being evaluated. They only become
+    /// constants once their declaration has been evaluated, but can't be redefined before that.
+    pending_constants: HashSet<String>,
+    /// Names declared with `alias`, and the tokens they are replaced with.
+    aliases: HashMap<String, Vec<Token>>,
+    /// Declarations that use aliases, by their key, with the text they were written as,
+    /// eg. `f(x) = sq(x)`, so that they can be shown with the aliases.
+    alias_sources: HashMap<String, (Stmt, String)>,
+    /// Currency units, and how much of the currency one unit of the base currency is worth.
+    exchange_rates: HashMap<String, f64>,
+    /// Keys of the unit declarations that were created automatically by inverting another one.
+    inverted_units: HashSet<String>,
+    /// The results of the latest expressions, with the most recent one last.
+    results: VecDeque<Expr>,
+    shadowing_policy: ShadowingPolicy,
+    /// Built-in functions and constants that were replaced, until the warnings are taken.
+    shadowing_warnings: Vec<ShadowingWarning>,
+    /// Functions registered by the application, see `parser::Context::register_function`.
+    native_functions: HashMap<String, NativeFunction>,
+}
+
+/// The declaration of `i`, which is `sqrt(-1)`.
+pub(crate) fn imaginary_unit() -> Expr {
+    Expr::FnCall(
+        Identifier::from_full_name("sqrt"),
+        vec![Expr::Literal(-1f64, ExprSpan::default())],
+    )
 }
 
 impl SymbolTable {
@@ -12,21 +52,29 @@ impl SymbolTable {
         let mut symbol_table = SymbolTable {
             hashmap: HashMap::new(),
             unit_types: HashMap::new(),
+            scopes: Vec::new(),
+            constants: HashSet::new(),
+            pending_constants: HashSet::new(),
+            aliases: HashMap::new(),
+            alias_sources: HashMap::new(),
+            exchange_rates: HashMap::new(),
+            inverted_units: HashSet::new(),
+            results: VecDeque::new(),
+            shadowing_policy: ShadowingPolicy::Deny,
+            shadowing_warnings: Vec::new(),
+            native_functions: HashMap::new(),
         };
 
-        // i = sqrt(-1)
         symbol_table.insert(Stmt::VarDecl(
             Identifier::from_full_name("i"),
-            Box::new(Expr::FnCall(
-                Identifier::from_full_name("sqrt"),
-                vec![Expr::Literal(-1f64)],
-            )),
+            Box::new(imaginary_unit()),
         ));
 
         symbol_table
     }
 
     pub fn insert(&mut self, value: Stmt) -> &mut Self {
+        let value = without_spans(value);
         match &value {
             Stmt::VarDecl(identifier, _) => {
                 self.hashmap
@@ -35,8 +83,9 @@ impl SymbolTable {
             Stmt::UnitDecl(identifier, to_unit, _) => {
                 self.unit_types.insert(identifier.to_string(), ());
                 self.unit_types.insert(to_unit.to_string(), ());
-                self.hashmap
-                    .insert(format!("unit.{}.{}", identifier, to_unit), value);
+                let key = format!("unit.{}.{}", identifier, to_unit);
+                self.inverted_units.remove(&key);
+                self.hashmap.insert(key, value);
             }
             Stmt::FnDecl(identifier, _, _) => {
                 self.hashmap
@@ -48,10 +97,44 @@ impl SymbolTable {
         self
     }
 
+    /// Insert a unit declaration that was created by inverting another one,
+    /// eg. `unit b = a/3` for `unit a = 3b`.
+    pub(crate) fn insert_inverted_unit(&mut self, value: Stmt) {
+        if let Stmt::UnitDecl(identifier, to_unit, _) = &value {
+            let key = format!("unit.{}.{}", identifier, to_unit);
+            self.insert(value);
+            self.inverted_units.insert(key);
+        }
+    }
+
     pub fn get_var(&self, key: &str) -> Option<&Stmt> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(stmt) = scope.get(key) {
+                return Some(stmt);
+            }
+        }
+
         self.hashmap.get(&format!("var.{}", key))
     }
 
+    /// Remember the result of an expression, so that it can be used as `_`.
+    /// The results before it are moved back, eg. the previous `_` becomes `_2`.
+    pub(crate) fn push_result(&mut self, expr: Expr) {
+        self.results.push_back(expr);
+        if self.results.len() > MAX_RESULTS {
+            self.results.pop_front();
+        }
+    }
+
+    /// Get a result from the history, where `_` (or `_1`) is the last result,
+    /// `_2` is the one before it, and so on. None is returned if it's not a name like that,
+    /// or if there haven't been that many results.
+    pub fn get_result(&self, name: &str) -> Option<&Expr> {
+        let index = result_index(name)?;
+
+        self.results.iter().rev().nth(index - 1)
+    }
+
     pub fn get_unit(&self, key: &str, to_unit: &str) -> Option<&Stmt> {
         self.hashmap.get(&format!("unit.{}.{}", key, to_unit))
     }
@@ -61,6 +144,7 @@ impl SymbolTable {
     }
 
     pub fn set(&mut self, value: Stmt) {
+        let value = without_spans(value);
         let existing_item = match &value {
             Stmt::VarDecl(identifier, _) => self
                 .hashmap
@@ -81,6 +165,314 @@ impl SymbolTable {
         }
     }
 
+    /// Create a new scope for local variables.
+    /// Variables in the scope shadow global ones until the scope is popped.
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    pub fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Insert a variable into the innermost scope.
+    pub fn insert_local(&mut self, value: Stmt) -> Result<(), KalkError> {
+        if let (Stmt::VarDecl(identifier, _), Some(scope)) = (&value, self.scopes.last_mut()) {
+            scope.insert(identifier.full_name.clone(), value);
+
+            Ok(())
+        } else {
+            Err(KalkError::EvaluationError(String::from(
+                "only variables can be declared in a local scope, and only inside one.",
+            )))
+        }
+    }
+
+    /// Get the names that start with the prefix, eg. for tab completion.
+    /// This includes the functions and constants of the prelude, as well as
+    /// the functions, variables and units that have been defined. They are sorted by name.
+    pub fn completions(&self, prefix: &str) -> Vec<CompletionItem> {
+        let mut items = Vec::new();
+        for (name, arity) in SPECIAL_FUNCS {
+            items.push(CompletionItem::new(
+                name,
+                CompletionKind::Function,
+                Some(*arity),
+            ));
+        }
+
+        for name in prelude::UNARY_FUNCS
+            .keys()
+            .chain(prelude::VECTOR_FUNCS.keys())
+        {
+            items.push(CompletionItem::new(name, CompletionKind::Function, Some(1)));
+        }
+
+        for name in prelude::BINARY_FUNCS.keys() {
+            items.push(CompletionItem::new(name, CompletionKind::Function, Some(2)));
+        }
+
+        for (name, native_function) in &self.native_functions {
+            items.push(CompletionItem::new(
+                name,
+                CompletionKind::Function,
+                Some(native_function.arity),
+            ));
+        }
+
+        for name in prelude::CONSTANTS.keys() {
+            items.push(CompletionItem::new(name, CompletionKind::Constant, None));
+        }
+
+        for (name, _) in crate::duration::DURATION_UNITS {
+            items.push(CompletionItem::new(name, CompletionKind::Unit, None));
+        }
+
+        for name in self.unit_types.keys().chain(self.exchange_rates.keys()) {
+            items.push(CompletionItem::new(name, CompletionKind::Unit, None));
+        }
+
+        for stmt in self.hashmap.values() {
+            match stmt {
+                Stmt::VarDecl(identifier, _) => {
+                    let kind = if self.constants.contains(&identifier.full_name) {
+                        CompletionKind::Constant
+                    } else {
+                        CompletionKind::Variable
+                    };
+                    items.push(CompletionItem::new(&identifier.full_name, kind, None));
+                }
+                Stmt::FnDecl(identifier, parameters, _) => items.push(CompletionItem::new(
+                    &identifier.full_name,
+                    CompletionKind::Function,
+                    Some(parameters.len()),
+                )),
+                _ => (),
+            }
+        }
+
+        items.retain(|item| item.name.starts_with(prefix));
+        items.sort_by(|a, b| a.name.cmp(&b.name));
+        items.dedup_by(|a, b| a.name == b.name);
+
+        items
+    }
+
+    /// Get up to three names of variables (or functions) that are similar to the name,
+    /// eg. `sqrt` for `sqr`, to suggest when the name hasn't been defined. The closest are first.
+    pub fn similar_names(&self, name: &str, functions: bool) -> Vec<String> {
+        // Short names are too similar to too many other names
+        let max_distance = (name.chars().count() + 1) / 3;
+        let mut names: Vec<(usize, String)> = self
+            .completions("")
+            .into_iter()
+            .filter(|item| (item.kind == CompletionKind::Function) == functions)
+            .filter(|item| item.kind != CompletionKind::Unit && item.name != name)
+            .map(|item| {
+                (
+                    text_utils::levenshtein_distance(name, &item.name),
+                    item.name,
+                )
+            })
+            .filter(|(distance, _)| *distance <= max_distance)
+            .collect();
+        names.sort();
+
+        names.into_iter().take(3).map(|(_, name)| name).collect()
+    }
+
+    /// Get the variables, functions and units as kalker syntax, eg. `f(x) = 2x`.
+    /// They are sorted, so that the result is the same every time.
+    pub fn definitions(&self) -> Vec<String> {
+        let mut entries: Vec<(&String, &Stmt)> = self
+            .hashmap
+            .iter()
+            .filter(|(key, _)| !self.inverted_units.contains(*key))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+
+        entries
+            .into_iter()
+            .map(|(_, stmt)| match stmt {
+                Stmt::VarDecl(identifier, _) if self.constants.contains(&identifier.full_name) => {
+                    format!("const {}", stmt)
+                }
+                _ => stmt.to_string(),
+            })
+            .collect()
+    }
+
+    pub(crate) fn set_shadowing_policy(&mut self, shadowing_policy: ShadowingPolicy) {
+        self.shadowing_policy = shadowing_policy;
+    }
+
+    /// Check if a function or variable may be declared with the name, according to the
+    /// shadowing policy if it is the name of a built-in one. Should be called before
+    /// the declaration is inserted.
+    pub(crate) fn check_shadowing(
+        &mut self,
+        identifier: &Identifier,
+        is_function: bool,
+    ) -> Result<(), KalkError> {
+        let name = &identifier.full_name;
+        if !is_function && self.is_declared_constant(name) {
+            return Err(KalkError::UnableToOverrideConstant(
+                identifier.pure_name.clone(),
+            ));
+        }
+
+        let is_builtin = if is_function {
+            prelude::is_prelude_func(name) || self.native_functions.contains_key(name)
+        } else {
+            prelude::is_constant(name)
+        };
+        if !is_builtin {
+            return Ok(());
+        }
+
+        match self.shadowing_policy {
+            ShadowingPolicy::Deny if is_function => Err(KalkError::UnableToOverrideFunction(
+                identifier.pure_name.clone(),
+            )),
+            ShadowingPolicy::Deny => Err(KalkError::UnableToOverrideConstant(
+                identifier.pure_name.clone(),
+            )),
+            ShadowingPolicy::Warn => {
+                self.shadowing_warnings.push(ShadowingWarning {
+                    name: name.clone(),
+                    is_function,
+                });
+
+                Ok(())
+            }
+            ShadowingPolicy::Allow => Ok(()),
+        }
+    }
+
+    pub(crate) fn take_shadowing_warnings(&mut self) -> Vec<ShadowingWarning> {
+        std::mem::take(&mut self.shadowing_warnings)
+    }
+
+    pub(crate) fn mark_as_constant(&mut self, identifier: &str) {
+        self.constants.insert(identifier.to_string());
+    }
+
+    /// Remember that the variable is declared with `const`, until its declaration is evaluated.
+    pub(crate) fn mark_as_pending_constant(&mut self, identifier: &str) {
+        self.pending_constants.insert(identifier.to_string());
+    }
+
+    pub(crate) fn is_pending_constant(&self, identifier: &str) -> bool {
+        self.pending_constants.contains(identifier)
+    }
+
+    /// Make the variable a constant, once its declaration with `const` has been evaluated.
+    pub(crate) fn confirm_constant(&mut self, identifier: &str) {
+        if self.pending_constants.remove(identifier) {
+            self.constants.insert(identifier.to_string());
+        }
+    }
+
+    /// Forget the declarations with `const` that haven't been evaluated, eg. since the input failed,
+    /// and return their names.
+    pub(crate) fn take_pending_constants(&mut self) -> HashSet<String> {
+        std::mem::take(&mut self.pending_constants)
+    }
+
+    /// Check if the variable is a predefined constant or has been declared with `const`.
+    pub fn is_constant(&self, identifier: &str) -> bool {
+        prelude::is_constant(identifier) || self.is_declared_constant(identifier)
+    }
+
+    /// Check if the variable has been declared with `const`.
+    pub fn is_declared_constant(&self, identifier: &str) -> bool {
+        self.constants.contains(identifier) || self.pending_constants.contains(identifier)
+    }
+
+    pub(crate) fn insert_alias(&mut self, name: &str, tokens: Vec<Token>) {
+        // The declarations that use the alias were written with what it meant before
+        if self.aliases.insert(name.to_string(), tokens).is_some() {
+            self.alias_sources.clear();
+        }
+    }
+
+    pub(crate) fn get_alias(&self, name: &str) -> Option<&Vec<Token>> {
+        self.aliases.get(name)
+    }
+
+    pub fn contains_alias(&self, name: &str) -> bool {
+        self.aliases.contains_key(name)
+    }
+
+    /// Remember the text that a declaration with aliases was written as, which is shown
+    /// instead of the declaration for as long as it hasn't been replaced.
+    pub(crate) fn set_alias_source(&mut self, stmt: &Stmt, source: String) {
+        if let Some(key) = declaration_key(stmt) {
+            self.alias_sources
+                .insert(key, (without_spans(stmt.clone()), source));
+        }
+    }
+
+    /// Get the text of the declaration with the aliases it was written with, if it used any.
+    pub(crate) fn get_alias_source(&self, stmt: &Stmt) -> Option<&str> {
+        match self.alias_sources.get(&declaration_key(stmt)?) {
+            Some((source_stmt, source)) if source_stmt == stmt => Some(source),
+            _ => None,
+        }
+    }
+
+    /// Replace the exchange rates. Every currency becomes a unit
+    /// that can be converted to any of the other currencies.
+    pub(crate) fn set_exchange_rates(&mut self, exchange_rates: HashMap<String, f64>) {
+        for currency in self.exchange_rates.keys() {
+            self.unit_types.remove(currency);
+        }
+
+        for currency in exchange_rates.keys() {
+            self.unit_types.insert(currency.to_string(), ());
+        }
+
+        self.exchange_rates = exchange_rates;
+    }
+
+    pub fn get_exchange_rate(&self, currency: &str) -> Option<f64> {
+        self.exchange_rates.get(currency).copied()
+    }
+
+    /// The variable declarations, in no particular order. The predefined `i` isn't included.
+    pub fn iter_variables(&self) -> impl Iterator<Item = &Stmt> {
+        self.hashmap
+            .iter()
+            .filter_map(|(key, stmt)| match key.strip_prefix("var.") {
+                Some(name) if name != "i" => Some(stmt),
+                _ => None,
+            })
+    }
+
+    /// The function declarations, in no particular order.
+    pub fn iter_functions(&self) -> impl Iterator<Item = &Stmt> {
+        self.hashmap
+            .iter()
+            .filter(|(key, _)| key.starts_with("fn."))
+            .map(|(_, stmt)| stmt)
+    }
+
+    /// Remove the variable and function with the name, including constants.
+    /// Returns false if there was nothing to remove.
+    pub fn remove(&mut self, name: &str) -> bool {
+        if name == "i" {
+            return false;
+        }
+
+        let removed_var = self.get_and_remove_var(name).is_some();
+        let removed_fn = self.get_and_remove_fn(name).is_some();
+        if removed_var {
+            self.constants.remove(name);
+        }
+
+        removed_var || removed_fn
+    }
+
     pub fn get_and_remove_fn(&mut self, identifier: &str) -> Option<Stmt> {
         self.hashmap.remove(&format!("fn.{}", identifier))
     }
@@ -92,17 +484,90 @@ impl SymbolTable {
     pub fn contains_var(&self, identifier: &str) -> bool {
         prelude::is_constant(identifier)
             || identifier == "i"
+            || result_index(identifier).is_some()
+            || self
+                .scopes
+                .iter()
+                .any(|scope| scope.contains_key(identifier))
             || self.hashmap.contains_key(&format!("var.{}", identifier))
     }
 
     pub fn contains_unit(&self, identifier: &str) -> bool {
         self.unit_types.contains_key(identifier)
+            || crate::duration::seconds_per_unit(identifier).is_some()
+            || crate::units::is_builtin_unit(identifier)
+            || crate::temperature::is_temperature_unit(identifier)
+    }
+
+    /// The amount of arguments the function takes, from its parameters if it has been declared.
+    /// Built-in functions that take any amount, eg. `max`, give None, and `log` gives 2,
+    /// since the base may be left out.
+    pub fn get_fn_arity(&self, identifier: &str) -> Option<usize> {
+        if let Some(Stmt::FnDecl(_, parameters, _)) = self.get_fn(identifier) {
+            return Some(parameters.len());
+        }
+
+        if let Some(native_function) = self.native_functions.get(identifier) {
+            return Some(native_function.arity);
+        }
+
+        if let Some((_, arity)) = SPECIAL_FUNCS.iter().find(|(name, _)| *name == identifier) {
+            Some(*arity)
+        } else if prelude::BINARY_FUNCS.contains_key(identifier) {
+            Some(2)
+        } else if prelude::UNARY_FUNCS.contains_key(identifier) {
+            Some(1)
+        } else {
+            None
+        }
     }
 
     pub fn contains_fn(&self, identifier: &str) -> bool {
         prelude::is_prelude_func(identifier)
+            || self.native_functions.contains_key(identifier)
             || self.hashmap.contains_key(&format!("fn.{}", identifier))
     }
+
+    pub(crate) fn register_native_fn(&mut self, name: &str, native_function: NativeFunction) {
+        self.native_functions
+            .insert(name.to_string(), native_function);
+    }
+
+    pub(crate) fn get_native_fn(&self, identifier: &str) -> Option<&NativeFunction> {
+        self.native_functions.get(identifier)
+    }
+}
+
+/// The spans of a declaration are positions in the input it was declared in, so they
+/// are removed, to not be mistaken for positions in the inputs where it is used.
+fn without_spans(mut stmt: Stmt) -> Stmt {
+    if let Stmt::VarDecl(_, expr) | Stmt::FnDecl(_, _, expr) | Stmt::UnitDecl(_, _, expr) =
+        &mut stmt
+    {
+        crate::ast::for_each_span_mut(expr, &mut |span| *span = None);
+    }
+
+    stmt
+}
+
+/// The key that the variable or function declaration is stored with.
+fn declaration_key(stmt: &Stmt) -> Option<String> {
+    match stmt {
+        Stmt::VarDecl(identifier, _) => Some(format!("var.{}", identifier.full_name)),
+        Stmt::FnDecl(identifier, _, _) => Some(format!("fn.{}", identifier.full_name)),
+        _ => None,
+    }
+}
+
+/// The position of a result in the history, from the name, eg. 1 for `_` and 3 for `_3`.
+fn result_index(name: &str) -> Option<usize> {
+    match name.strip_prefix('_')? {
+        "" => Some(1),
+        index if index.chars().all(|c| c.is_ascii_digit()) => {
+            index.parse().ok().filter(|index| *index > 0)
+        }
+        _ => None,
+    }
 }
 
 impl Default for SymbolTable {
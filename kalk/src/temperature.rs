@@ -0,0 +1,182 @@
+//! Temperatures in kelvin, degrees Celsius and degrees Fahrenheit, which are converted with an
+//! offset instead of only a factor, since their zeros are different. A temperature can also be
+//! a difference between two temperatures, eg. `30 °C - 20 °C` gives `10 Δ°C`, which is only
+//! converted with the factor, eg. `10 Δ°C to Δ°F` gives `18 Δ°F`.
+use crate::errors::KalkError;
+
+struct TemperatureUnit {
+    name: &'static str,
+    /// The size of a degree in ninths of a kelvin, which keeps eg. 20 °C to °F exact.
+    degree: f64,
+    /// Where water freezes on the scale, or None if it's a difference between temperatures.
+    freezing_point: Option<f64>,
+}
+
+/// Kelvin is both an absolute temperature and a difference, since its zero is absolute zero.
+const TEMPERATURE_UNITS: &[TemperatureUnit] = &[
+    TemperatureUnit {
+        name: "K",
+        degree: 9f64,
+        freezing_point: Some(273.15),
+    },
+    TemperatureUnit {
+        name: "°C",
+        degree: 9f64,
+        freezing_point: Some(0f64),
+    },
+    TemperatureUnit {
+        name: "°F",
+        degree: 5f64,
+        freezing_point: Some(32f64),
+    },
+    TemperatureUnit {
+        name: "Δ°C",
+        degree: 9f64,
+        freezing_point: None,
+    },
+    TemperatureUnit {
+        name: "Δ°F",
+        degree: 5f64,
+        freezing_point: None,
+    },
+];
+
+/// Names of the temperature units that can be written without special characters.
+pub(crate) const ASCII_NAMES: [(&str, &str); 4] = [
+    ("degC", "°C"),
+    ("degF", "°F"),
+    ("deltaC", "Δ°C"),
+    ("deltaF", "Δ°F"),
+];
+
+fn get_unit(name: &str) -> Option<&'static TemperatureUnit> {
+    TEMPERATURE_UNITS.iter().find(|unit| unit.name == name)
+}
+
+pub(crate) fn is_temperature_unit(name: &str) -> bool {
+    get_unit(name).is_some()
+}
+
+/// The unit of a difference between temperatures in the unit.
+fn difference_unit(unit: &TemperatureUnit) -> &'static str {
+    match unit.name {
+        "°C" => "Δ°C",
+        "°F" => "Δ°F",
+        _ => "K",
+    }
+}
+
+/// Convert a temperature to another unit. If one of them is a difference, only the size of
+/// the degrees matters, which also makes it possible to turn a temperature into a difference,
+/// eg. `20 °C to Δ°C`. None is given if they aren't both temperature units.
+pub(crate) fn convert(value: f64, from_unit: &str, to_unit: &str) -> Option<f64> {
+    let (from, to) = (get_unit(from_unit)?, get_unit(to_unit)?);
+    Some(match (from.freezing_point, to.freezing_point) {
+        (Some(from_freezing_point), Some(to_freezing_point)) => {
+            (value - from_freezing_point) * from.degree / to.degree + to_freezing_point
+        }
+        _ => value * from.degree / to.degree,
+    })
+}
+
+/// Add or subtract temperatures, where at least one of them is in degrees or a difference.
+/// Absolute temperatures can only be subtracted from each other, which gives a difference,
+/// and a difference can be added to or subtracted from one. None is given if they aren't both
+/// temperatures, or both in kelvin, in which case they are calculated like other units.
+pub(crate) fn add(
+    left: f64,
+    left_unit: &str,
+    right: f64,
+    right_unit: &str,
+    subtract: bool,
+) -> Option<Result<(f64, String), KalkError>> {
+    let (left_temperature, right_temperature) = (get_unit(left_unit)?, get_unit(right_unit)?);
+    if left_unit == "K" && right_unit == "K" {
+        return None;
+    }
+
+    let (sign, operator) = if subtract { (-1f64, "-") } else { (1f64, "+") };
+    let invalid = || {
+        Err(KalkError::InvalidTemperatureOperation(format!(
+            "{} {} {}",
+            left_unit, operator, right_unit
+        )))
+    };
+    Some(
+        match (
+            left_temperature.freezing_point,
+            right_temperature.freezing_point,
+            subtract,
+        ) {
+            (Some(_), Some(_), false) => invalid(),
+            // Kelvin is a difference when it's subtracted from one, eg. `5 Δ°C - 2 K`
+            (None, Some(_), true) if right_unit != "K" => invalid(),
+            (Some(_), Some(_), true) => {
+                let right = convert(right, right_unit, left_unit).unwrap();
+
+                Ok((
+                    left - right,
+                    String::from(difference_unit(left_temperature)),
+                ))
+            }
+            (None, Some(_), false) => {
+                let left = left * left_temperature.degree / right_temperature.degree;
+
+                Ok((left + right, right_unit.to_string()))
+            }
+            (_, None, _) | (None, Some(_), true) => {
+                let right = right * right_temperature.degree / left_temperature.degree;
+
+                Ok((left + sign * right, left_unit.to_string()))
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_convert() {
+        assert_close(convert(100f64, "°C", "°F").unwrap(), 212f64);
+        assert_close(convert(-40f64, "°F", "°C").unwrap(), -40f64);
+        assert_close(convert(0f64, "K", "°C").unwrap(), -273.15);
+        assert_close(convert(10f64, "Δ°C", "Δ°F").unwrap(), 18f64);
+        assert_close(convert(20f64, "°C", "Δ°C").unwrap(), 20f64);
+        assert_close(convert(5f64, "K", "Δ°F").unwrap(), 9f64);
+        assert_eq!(convert(1f64, "°C", "m"), None);
+    }
+
+    #[test]
+    fn test_add() {
+        let (value, unit) = add(30f64, "°C", 68f64, "°F", true).unwrap().unwrap();
+        assert_close(value, 10f64);
+        assert_eq!(unit, "Δ°C");
+
+        let (value, unit) = add(20f64, "°C", 9f64, "Δ°F", false).unwrap().unwrap();
+        assert_close(value, 25f64);
+        assert_eq!(unit, "°C");
+
+        let (value, unit) = add(5f64, "Δ°C", 300f64, "K", false).unwrap().unwrap();
+        assert_close(value, 305f64);
+        assert_eq!(unit, "K");
+
+        assert!(add(20f64, "°C", 30f64, "°C", false).unwrap().is_err());
+        assert!(add(5f64, "Δ°C", 20f64, "°C", true).unwrap().is_err());
+        let (value, unit) = add(5f64, "Δ°C", 2f64, "K", true).unwrap().unwrap();
+        assert_close(value, 3f64);
+        assert_eq!(unit, "Δ°C");
+        assert!(add(300f64, "K", 10f64, "K", false).is_none());
+        assert!(add(20f64, "°C", 1f64, "m", false).is_none());
+    }
+}
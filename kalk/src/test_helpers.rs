@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 use crate::ast::Expr;
+use crate::ast::ExprSpan;
 use crate::ast::Identifier;
 use crate::ast::Stmt;
 use crate::lexer::Token;
@@ -18,7 +19,7 @@ pub fn cmp(x: f64, y: f64) -> bool {
 }
 
 pub fn literal(value: f64) -> Box<Expr> {
-    Box::new(Expr::Literal(value))
+    Box::new(Expr::Literal(value, ExprSpan::default()))
 }
 
 pub fn var(identifier: &str) -> Box<Expr> {
@@ -39,11 +40,11 @@ pub fn fn_call(identifier: &str, arguments: Vec<Expr>) -> Box<Expr> {
 }
 
 pub fn binary(left: Box<Expr>, op: TokenKind, right: Box<Expr>) -> Box<Expr> {
-    Box::new(Expr::Binary(left, op, right))
+    Box::new(Expr::Binary(left, op, right, ExprSpan::default()))
 }
 
 pub fn unary(op: TokenKind, expr: Box<Expr>) -> Box<Expr> {
-    Box::new(Expr::Unary(op, expr))
+    Box::new(Expr::Unary(op, expr, ExprSpan::default()))
 }
 
 pub fn group(expr: Box<Expr>) -> Box<Expr> {
@@ -113,3 +113,68 @@ pub fn normal_to_subscript(chars: impl Iterator<Item = char>) -> String {
 
     subscript
 }
+
+const VULGAR_FRACTIONS: [(char, u8, u8); 18] = [
+    ('½', 1, 2),
+    ('⅓', 1, 3),
+    ('⅔', 2, 3),
+    ('¼', 1, 4),
+    ('¾', 3, 4),
+    ('⅕', 1, 5),
+    ('⅖', 2, 5),
+    ('⅗', 3, 5),
+    ('⅘', 4, 5),
+    ('⅙', 1, 6),
+    ('⅚', 5, 6),
+    ('⅐', 1, 7),
+    ('⅛', 1, 8),
+    ('⅜', 3, 8),
+    ('⅝', 5, 8),
+    ('⅞', 7, 8),
+    ('⅑', 1, 9),
+    ('⅒', 1, 10),
+];
+
+pub fn is_vulgar_fraction(c: &char) -> bool {
+    vulgar_fraction_to_parts(c).is_some()
+}
+
+/// Get the numerator and denominator of a unicode vulgar fraction, eg. ¾ => (3, 4).
+pub fn vulgar_fraction_to_parts(c: &char) -> Option<(u8, u8)> {
+    VULGAR_FRACTIONS
+        .iter()
+        .find(|(fraction, _, _)| fraction == c)
+        .map(|(_, numer, denom)| (*numer, *denom))
+}
+
+/// Get the unicode vulgar fraction for a numerator and denominator, eg. (3, 4) => ¾.
+pub fn parts_to_vulgar_fraction(numer: u8, denom: u8) -> Option<char> {
+    VULGAR_FRACTIONS
+        .iter()
+        .find(|(_, fraction_numer, fraction_denom)| {
+            *fraction_numer == numer && *fraction_denom == denom
+        })
+        .map(|(fraction, _, _)| *fraction)
+}
+
+/// The amount of characters that need to be inserted, removed or replaced
+/// to turn one string into the other, eg. 1 for `sqr` and `sqrt`.
+pub fn levenshtein_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous_row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution_cost = if a_char == *b_char { 0 } else { 1 };
+            row.push(
+                (previous_row[j] + substitution_cost)
+                    .min(previous_row[j + 1] + 1)
+                    .min(row[j] + 1),
+            );
+        }
+        previous_row = row;
+    }
+
+    previous_row[b.len()]
+}
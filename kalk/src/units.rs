@@ -0,0 +1,454 @@
+//! Units that are made of other units, eg. `m/s` or `kg m^2/s^2`. They are kept as text in
+//! the unit of a value, and turned into the powers of the units they are made of when values
+//! are multiplied or divided, which is where the units cancel out, eg. `m/s * s` gives `m`.
+use std::collections::BTreeMap;
+
+/// The SI base units, in the same order as the powers in the dimensions of `SI_UNITS`.
+/// The candela is left out, since `cd` is more likely to be `c*d`.
+const BASE_UNITS: [&str; 6] = ["m", "kg", "s", "A", "K", "mol"];
+
+/// The SI units that are always available, and the powers of the base units they are made of.
+/// Units made of several base units are written with the name when they come up in a result,
+/// eg. `5 N * 2 m` gives `10 J`.
+const SI_UNITS: &[(&str, [i32; 6])] = &[
+    ("m", [1, 0, 0, 0, 0, 0]),
+    ("kg", [0, 1, 0, 0, 0, 0]),
+    ("s", [0, 0, 1, 0, 0, 0]),
+    ("A", [0, 0, 0, 1, 0, 0]),
+    ("K", [0, 0, 0, 0, 1, 0]),
+    ("mol", [0, 0, 0, 0, 0, 1]),
+    ("Hz", [0, 0, -1, 0, 0, 0]),
+    ("N", [1, 1, -2, 0, 0, 0]),
+    ("Pa", [-1, 1, -2, 0, 0, 0]),
+    ("J", [2, 1, -2, 0, 0, 0]),
+    ("W", [2, 1, -3, 0, 0, 0]),
+    ("C", [0, 0, 1, 1, 0, 0]),
+    ("V", [2, 1, -3, -1, 0, 0]),
+    ("F", [-2, -1, 4, 2, 0, 0]),
+    ("ohm", [2, 1, -3, -2, 0, 0]),
+    ("Wb", [2, 1, -2, -1, 0, 0]),
+    ("T", [0, 1, -2, -1, 0, 0]),
+    ("H", [2, 1, -2, -2, 0, 0]),
+];
+
+/// Bytes, which aren't made of the SI units, but can have prefixes like them.
+const BYTE_UNIT: &str = "B";
+
+/// The units of data and how many bytes they are. A bit on its own is written `bit`, since `b`
+/// is more likely to be a variable, but it's `b` with a prefix, eg. `Mb`.
+const DATA_UNITS: [(&str, f64); 3] = [(BYTE_UNIT, 1f64), ("bit", 0.125), ("b", 0.125)];
+
+/// Bits per second, eg. `Mbps`, which is the same as `Mb/s`.
+const BIT_RATE_SUFFIX: &str = "bps";
+
+/// Prefixes that can be put in front of the SI units, grams and units of data, eg. `kJ`, `µF`
+/// and `GiB`, and how much they multiply by. The binary ones are mostly used for bytes, and `K`
+/// is only used for data, eg. `KB`, which is the same as `kB`.
+const PREFIXES: &[(&str, f64)] = &[
+    ("Ki", 1024f64),
+    ("Mi", 1048576f64),
+    ("Gi", 1073741824f64),
+    ("Ti", 1099511627776f64),
+    ("K", 1e3),
+    ("k", 1e3),
+    ("M", 1e6),
+    ("G", 1e9),
+    ("T", 1e12),
+    ("P", 1e15),
+    ("c", 1e-2),
+    ("m", 1e-3),
+    ("µ", 1e-6),
+    ("n", 1e-9),
+    ("p", 1e-12),
+];
+
+/// Prefixes that can be written after a number without a unit, eg. `4.7k` is 4700.
+/// Smaller ones than µ are left out, since they're more likely to be variables, eg. `2n`.
+const STANDALONE_PREFIXES: [&str; 8] = ["k", "M", "G", "µ", "Ki", "Mi", "Gi", "Ti"];
+
+/// Check if the name is one of the units in this module, with or without a prefix.
+pub(crate) fn is_builtin_unit(name: &str) -> bool {
+    split_prefix(name).is_some() || standalone_prefix(name).is_some() || is_bit_rate(name)
+}
+
+/// How much a prefix on its own multiplies by, eg. 1000 for `k`.
+pub(crate) fn standalone_prefix(name: &str) -> Option<f64> {
+    if !STANDALONE_PREFIXES.contains(&name) {
+        return None;
+    }
+
+    PREFIXES
+        .iter()
+        .find(|(prefix, _)| *prefix == name)
+        .map(|(_, factor)| *factor)
+}
+
+/// The unit of data per second that a bit rate is, eg. `Mb` for `Mbps`.
+fn bit_rate_data_unit(name: &str) -> Option<String> {
+    let prefix = name.strip_suffix(BIT_RATE_SUFFIX)?;
+    let data_unit = if prefix.is_empty() {
+        String::from("bit")
+    } else {
+        format!("{}b", prefix)
+    };
+
+    split_prefix(&data_unit)?;
+
+    Some(data_unit)
+}
+
+fn is_bit_rate(name: &str) -> bool {
+    bit_rate_data_unit(name).is_some()
+}
+
+/// How much a unit without a prefix is, and the unit it's given in, eg. (0.001, "kg") for g,
+/// since kg is the SI unit, and (0.125, "B") for bit.
+fn unprefixed_unit(unit: &str) -> Option<(f64, &str)> {
+    if unit == "g" {
+        return Some((1e-3, "kg"));
+    }
+
+    if let Some((_, bytes)) = DATA_UNITS.iter().find(|(name, _)| *name == unit) {
+        return Some((*bytes, BYTE_UNIT));
+    }
+
+    SI_UNITS
+        .iter()
+        .any(|(si_unit, _)| *si_unit == unit)
+        .then_some((1f64, unit))
+}
+
+/// How much the prefix of a unit multiplies by, and the unit without it, eg. (1000, "m") for km.
+/// Grams are given in kilograms and bits in bytes, eg. (0.001, "kg") for g.
+fn split_prefix(name: &str) -> Option<(f64, &str)> {
+    if name == "b" {
+        return None;
+    }
+
+    if let Some(unit) = unprefixed_unit(name) {
+        return Some(unit);
+    }
+
+    PREFIXES.iter().find_map(|(prefix, factor)| {
+        let unit = name.strip_prefix(prefix)?;
+        let is_data = DATA_UNITS.iter().any(|(data_unit, _)| *data_unit == unit);
+        if unit == "kg" || (*prefix == "K" && !is_data) {
+            return None;
+        }
+
+        unprefixed_unit(unit).map(|(unit_factor, unit)| (factor * unit_factor, unit))
+    })
+}
+
+/// How much a unit is in the units it's converted with, and those units, eg. (1000, "m") for
+/// km, (3600, "s") for h and (125000, "B") for Mb. None is given if it isn't a built-in unit,
+/// eg. a currency.
+fn base_unit(name: &str) -> Option<(f64, &str)> {
+    split_prefix(name).or_else(|| crate::duration::seconds_per_unit(name).map(|seconds| (seconds, "s")))
+}
+
+/// What to multiply by to convert between units that are made of the same units, with different
+/// prefixes or units of time, eg. 1000 from km to m and 1/3.6 from km/h to m/s.
+pub(crate) fn conversion_factor(from_unit: &str, to_unit: &str) -> Option<f64> {
+    let (from_factor, from_base) = Dimension::parse(from_unit).into_base();
+    let (to_factor, to_base) = Dimension::parse(to_unit).into_base();
+    let is_builtin = |dimension: &Dimension| dimension.0.keys().all(|name| base_unit(name).is_some());
+
+    (from_base == to_base && is_builtin(&from_base)).then(|| from_factor / to_factor)
+}
+
+/// What to multiply the right value by to write its unit with the units of the left one where
+/// they are the same kind, and that unit, eg. (0.000125, "GB/s") for `GB` and `Mbps`,
+/// which makes them cancel out when the values are multiplied or divided. None is given if
+/// nothing would change.
+pub(crate) fn match_units(left_unit: &str, right_unit: &str) -> Option<(f64, String)> {
+    let left = Dimension::parse(left_unit);
+    let mut factor = 1f64;
+    let mut matched = Dimension::default();
+    let mut has_changed = false;
+    for (name, power) in Dimension::parse(right_unit).0 {
+        let left_name = base_unit(&name).and_then(|(right_factor, base)| {
+            left.0.keys().find_map(|left_name| match base_unit(left_name) {
+                Some((left_factor, left_base)) if left_base == base && *left_name != name => {
+                    Some((right_factor / left_factor, left_name))
+                }
+                _ => None,
+            })
+        });
+        match left_name {
+            Some((name_factor, left_name)) => {
+                factor *= name_factor.powi(power);
+                matched.add(left_name, power);
+                has_changed = true;
+            }
+            None => matched.add(&name, power),
+        }
+    }
+
+    has_changed.then(|| (factor, matched.to_string()))
+}
+
+/// Write the micro prefix in the same way, since there are two characters for it, and `u` is
+/// used when neither can be typed, eg. `uF` is µF.
+pub(crate) fn normalize_prefix(name: String) -> String {
+    if let Some(unit) = name.strip_prefix('μ') {
+        return format!("µ{}", unit);
+    }
+
+    match name.strip_prefix('u') {
+        Some(unit) if split_prefix(unit).is_some() => format!("µ{}", unit),
+        _ => name,
+    }
+}
+
+/// The powers of the units that a unit is made of, eg. m¹ s⁻² for `m/s^2`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Dimension(BTreeMap<String, i32>);
+
+impl Dimension {
+    /// Read a unit as it is written by `Display`, eg. `kg m^2/s^2` or `1/(m s)`.
+    pub(crate) fn parse(unit: &str) -> Self {
+        let mut dimension = Dimension::default();
+        let (numerator, denominator) = match unit.split_once('/') {
+            Some((numerator, denominator)) => (numerator, Some(denominator)),
+            None => (unit, None),
+        };
+        dimension.add_parts(numerator, 1);
+        if let Some(denominator) = denominator {
+            dimension.add_parts(
+                denominator.trim_start_matches('(').trim_end_matches(')'),
+                -1,
+            );
+        }
+
+        dimension
+    }
+
+    fn add_parts(&mut self, parts: &str, sign: i32) {
+        for part in parts.split_whitespace().filter(|part| *part != "1") {
+            let (name, power) = match part.split_once('^') {
+                Some((name, power)) => (name, power.parse::<i32>().unwrap_or(1)),
+                None => (part, 1),
+            };
+            match bit_rate_data_unit(name) {
+                Some(data_unit) => {
+                    self.add(&data_unit, power * sign);
+                    self.add("s", -power * sign);
+                }
+                None => self.add(name, power * sign),
+            }
+        }
+    }
+
+    fn add(&mut self, name: &str, power: i32) {
+        let total = self.0.entry(name.to_string()).or_insert(0);
+        *total += power;
+        if *total == 0 {
+            self.0.remove(name);
+        }
+    }
+
+    /// Multiply by another unit to a power, eg. -1 to divide by it.
+    pub(crate) fn mul(mut self, other: &Dimension, power: i32) -> Self {
+        for (name, other_power) in &other.0 {
+            self.add(name, other_power * power);
+        }
+
+        self
+    }
+
+    pub(crate) fn pow(self, power: i32) -> Self {
+        Dimension::default().mul(&self, power)
+    }
+
+    /// If the unit is only made of SI units, and they are the same as a single SI unit,
+    /// eg. `kg m/s^2` or `J/m`, give that unit instead.
+    fn simplify(self) -> Self {
+        let base_powers = match self.si_base_powers() {
+            Some(base_powers) if self.0.len() > 1 || self.0.values().any(|power| *power != 1) => {
+                base_powers
+            }
+            _ => return self,
+        };
+
+        if base_powers.iter().all(|power| *power == 0) {
+            return Dimension::default();
+        }
+
+        // Hz is left out, since 1/s is clearer when it's a result of a calculation.
+        match SI_UNITS
+            .iter()
+            .find(|(name, powers)| *name != "Hz" && *powers == base_powers)
+        {
+            Some((name, _)) => Dimension::default().mul(&Dimension::parse(name), 1),
+            None => self,
+        }
+    }
+
+    /// The unit written with the units it's converted with, eg. `m/s` for `km/h`, and what to
+    /// multiply by to get there. Units that aren't built in are kept as they are.
+    fn into_base(self) -> (f64, Self) {
+        let mut factor = 1f64;
+        let mut base = Dimension::default();
+        for (name, power) in &self.0 {
+            match base_unit(name) {
+                Some((unit_factor, base_name)) => {
+                    factor *= unit_factor.powi(*power);
+                    base.add(base_name, *power);
+                }
+                None => base.add(name, *power),
+            }
+        }
+
+        (factor, base)
+    }
+
+    fn si_base_powers(&self) -> Option<[i32; 6]> {
+        let mut base_powers = [0; BASE_UNITS.len()];
+        for (name, power) in &self.0 {
+            let (_, unit_powers) = SI_UNITS.iter().find(|(unit, _)| unit == name)?;
+            for (base_power, unit_power) in base_powers.iter_mut().zip(unit_powers) {
+                *base_power += unit_power * power;
+            }
+        }
+
+        Some(base_powers)
+    }
+
+    /// The unit as text, or None if all the units cancelled out.
+    pub(crate) fn into_unit(self) -> Option<String> {
+        let dimension = self.simplify();
+        if dimension.0.is_empty() {
+            None
+        } else {
+            Some(dimension.to_string())
+        }
+    }
+}
+
+impl std::fmt::Display for Dimension {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let write_parts = |parts: Vec<(&String, i32)>| -> String {
+            parts
+                .iter()
+                .map(|(name, power)| match power {
+                    1 => name.to_string(),
+                    _ => format!("{}^{}", name, power),
+                })
+                .collect::<Vec<String>>()
+                .join(" ")
+        };
+        let numerator: Vec<(&String, i32)> = self
+            .0
+            .iter()
+            .filter(|(_, power)| **power > 0)
+            .map(|(name, power)| (name, *power))
+            .collect();
+        let denominator: Vec<(&String, i32)> = self
+            .0
+            .iter()
+            .filter(|(_, power)| **power < 0)
+            .map(|(name, power)| (name, -power))
+            .collect();
+
+        let numerator_text = if numerator.is_empty() {
+            String::from("1")
+        } else {
+            write_parts(numerator)
+        };
+
+        match denominator.len() {
+            0 => write!(f, "{}", numerator_text),
+            1 => write!(f, "{}/{}", numerator_text, write_parts(denominator)),
+            _ => write!(f, "{}/({})", numerator_text, write_parts(denominator)),
+        }
+    }
+}
+
+/// The unit of the product of values with the units, or the quotient if `power` is -1.
+pub(crate) fn combine(left: Option<&String>, right: Option<&String>, power: i32) -> Option<String> {
+    let left = left.map(|unit| Dimension::parse(unit)).unwrap_or_default();
+    let right = right.map(|unit| Dimension::parse(unit)).unwrap_or_default();
+
+    left.mul(&right, power).into_unit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(name: &str) -> Option<String> {
+        Some(name.to_string())
+    }
+
+    #[test]
+    fn test_combine() {
+        assert_eq!(
+            combine(unit("m").as_ref(), unit("s").as_ref(), -1),
+            unit("m/s")
+        );
+        assert_eq!(
+            combine(unit("N").as_ref(), unit("m").as_ref(), 1),
+            unit("J")
+        );
+        assert_eq!(
+            combine(unit("J").as_ref(), unit("s").as_ref(), -1),
+            unit("W")
+        );
+        assert_eq!(
+            combine(unit("m/s").as_ref(), unit("s").as_ref(), 1),
+            unit("m")
+        );
+        assert_eq!(
+            combine(unit("km").as_ref(), unit("km").as_ref(), 1),
+            unit("km^2")
+        );
+        assert_eq!(combine(unit("km").as_ref(), unit("km").as_ref(), -1), None);
+        assert_eq!(combine(None, unit("s").as_ref(), -1), unit("1/s"));
+        assert_eq!(
+            combine(unit("kg").as_ref(), unit("m s").as_ref(), -1),
+            unit("kg/(m s)")
+        );
+        assert_eq!(combine(unit("kg m^2/s^2").as_ref(), None, 1), unit("J"));
+    }
+
+    #[test]
+    fn test_prefixes() {
+        assert_eq!(conversion_factor("km", "m"), Some(1000f64));
+        assert_eq!(conversion_factor("GiB", "MiB"), Some(1024f64));
+        assert_eq!(conversion_factor("mg", "kg"), Some(1e-6));
+        assert_eq!(conversion_factor("kJ", "kN"), None);
+        assert_eq!(standalone_prefix("M"), Some(1e6));
+        assert_eq!(standalone_prefix("m"), None);
+        assert!(is_builtin_unit("µF") && is_builtin_unit("kHz") && is_builtin_unit("B"));
+        assert!(!is_builtin_unit("kkg") && !is_builtin_unit("min"));
+        assert_eq!(normalize_prefix(String::from("uF")), "µF");
+        assert_eq!(normalize_prefix(String::from("μs")), "µs");
+        assert_eq!(normalize_prefix(String::from("ux")), "ux");
+    }
+
+    #[test]
+    fn test_data_units() {
+        assert_eq!(conversion_factor("KB", "B"), Some(1000f64));
+        assert_eq!(conversion_factor("B", "bit"), Some(8f64));
+        assert_eq!(conversion_factor("Mbps", "MB/s"), Some(0.125));
+        assert_eq!(conversion_factor("km/h", "m/s"), Some(1f64 / 3.6));
+        assert_eq!(conversion_factor("USD/h", "USD/min"), None);
+        assert_eq!(
+            match_units("GB", "Mbps"),
+            Some((0.000125, String::from("GB/s")))
+        );
+        assert_eq!(match_units("GB", "s"), None);
+        assert!(is_builtin_unit("Gbps") && is_builtin_unit("KiB") && is_builtin_unit("Mb"));
+        assert!(!is_builtin_unit("b") && !is_builtin_unit("KJ"));
+        assert_eq!(Dimension::parse("kbps").to_string(), "kb/s");
+    }
+
+    #[test]
+    fn test_parse() {
+        let dimension = Dimension::parse("kg/(m s^2)");
+        assert_eq!(dimension.to_string(), "kg/(m s^2)");
+        assert_eq!(Dimension::parse("1/s").pow(2).to_string(), "1/s^2");
+        assert_eq!(Dimension::parse("USD").to_string(), "USD");
+    }
+}